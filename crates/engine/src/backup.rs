@@ -0,0 +1,427 @@
+//
+//
+//
+// Backup Engine
+//
+// Keeps any number of backups of a DB in one directory. A backup is a checkpoint whose table files are moved into a directory shared by
+// every backup, named after their number, size and crc32c - a table file already backed up (same name, size and checksum) is stored
+// once however many backups hold it:
+//
+//   <backup dir>/
+//     shared_checksum/000012_2864144591_4096.sst   table files: <number>_<crc32c>_<size>.sst
+//     private/3/                                    the rest of backup 3: CURRENT, MANIFEST, WALs, blob files
+//     meta/3                                        metadata of backup 3
+//
+// The metadata file lists every file of the backup (relative to the backup dir) with its checksum and size:
+//
+//   timestamp <seconds since the epoch>
+//   sequence <seq no of the newest write>
+//   files <count>
+//   shared_checksum/000012_2864144591_4096.sst 2864144591 4096
+//   private/3/CURRENT 1520311386 16
+//   ...
+//
+// It is written last (to a temp file renamed into place), so a backup exists exactly when its metadata does. Private directories and
+// shared files no metadata refers to are the leftovers of a failed backup or of a purge, and are deleted when the engine is opened and
+// after every purge.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::checkpoint::Checkpoint;
+use crate::db::db_impl::DbImpl;
+use crate::db::filename::{CURRENT, FileType, current_file, parse_file_name, table_file_name};
+use crate::error::{Error, Result};
+use crate::utils::crc32c;
+
+pub(crate) type BackupId = u32;
+
+const SHARED_DIR: &str = "shared_checksum";
+const PRIVATE_DIR: &str = "private";
+const META_DIR: &str = "meta";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct BackupFile {
+    // Relative to the backup dir
+    path: String,
+    checksum: u32,
+    size: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct BackupMeta {
+    timestamp: u64,
+    sequence: u64,
+    files: Vec<BackupFile>,
+}
+
+/// Summary of one backup.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct BackupInfo {
+    pub(crate) id: BackupId,
+    // Unix seconds by the clock of the DB when it was backed up
+    pub(crate) timestamp: u64,
+    pub(crate) sequence: u64,
+    pub(crate) num_files: usize,
+    // Bytes of every file of the backup, shared ones included
+    pub(crate) size: u64,
+}
+
+pub(crate) struct BackupEngine {
+    dir: PathBuf,
+    backups: BTreeMap<BackupId, BackupMeta>,
+}
+
+impl BackupEngine {
+    /// Opens (or creates) the backup directory `dir` and deletes the leftovers of failed backups.
+    pub(crate) fn open(dir: &Path) -> Result<Self> {
+        for sub_dir in [SHARED_DIR, PRIVATE_DIR, META_DIR] {
+            fs::create_dir_all(dir.join(sub_dir))?;
+        }
+
+        let mut backups = BTreeMap::new();
+        for entry in fs::read_dir(dir.join(META_DIR))? {
+            let entry = entry?;
+            let name = entry.file_name();
+            // A metadata file which was never renamed into place is a failed backup
+            if let Some(id) = name.to_str().and_then(|name| name.parse().ok()) {
+                backups.insert(id, BackupMeta::decode(&fs::read_to_string(entry.path())?)?);
+            } else {
+                fs::remove_file(entry.path())?;
+            }
+        }
+
+        let engine = Self {
+            dir: dir.to_path_buf(),
+            backups,
+        };
+        engine.garbage_collect()?;
+        Ok(engine)
+    }
+
+    /// Backs up the DB while it keeps running. Table files already in the backup dir are not copied again.
+    pub(crate) fn create_new_backup(&mut self, db: &DbImpl) -> Result<BackupId> {
+        let id = self.backups.keys().next_back().map_or(1, |id| id + 1);
+        let private = self.private_dir(id);
+        if private.exists() {
+            fs::remove_dir_all(&private)?;
+        }
+        let sequence = Checkpoint::new(db).create(&private)?;
+
+        let mut names: Vec<String> = Vec::new();
+        for entry in fs::read_dir(&private)? {
+            names.push(entry?.file_name().to_string_lossy().into_owned());
+        }
+        names.sort_unstable();
+
+        let mut files = Vec::with_capacity(names.len());
+        for name in names {
+            let path = private.join(&name);
+            let (checksum, size) = file_checksum(&path)?;
+            let relative = match parse_file_name(&name) {
+                Some((FileType::Table, number)) => {
+                    let shared = format!("{SHARED_DIR}/{number:06}_{checksum}_{size}.sst");
+                    if self.dir.join(&shared).exists() {
+                        fs::remove_file(&path)?;
+                    } else {
+                        fs::rename(&path, self.dir.join(&shared))?;
+                    }
+                    shared
+                }
+                _ => format!("{PRIVATE_DIR}/{id}/{name}"),
+            };
+            files.push(BackupFile {
+                path: relative,
+                checksum,
+                size,
+            });
+        }
+
+        let meta = BackupMeta {
+            timestamp: db.clock().now(),
+            sequence,
+            files,
+        };
+        let meta_file = self.meta_file(id);
+        let temp = meta_file.with_extension("tmp");
+        {
+            let mut file = File::create(&temp)?;
+            file.write_all(meta.encode().as_bytes())?;
+            file.sync_all()?;
+        }
+        fs::rename(&temp, &meta_file)?;
+
+        self.backups.insert(id, meta);
+        Ok(id)
+    }
+
+    /// Oldest first.
+    pub(crate) fn backup_info(&self) -> Vec<BackupInfo> {
+        self.backups
+            .iter()
+            .map(|(id, meta)| BackupInfo {
+                id: *id,
+                timestamp: meta.timestamp,
+                sequence: meta.sequence,
+                num_files: meta.files.len(),
+                size: meta.files.iter().map(|f| f.size).sum(),
+            })
+            .collect()
+    }
+
+    /// Checks that every file of the backup is there with the size and checksum it was backed up with.
+    pub(crate) fn verify_backup(&self, id: BackupId) -> Result<()> {
+        for file in &self.backup(id)?.files {
+            self.verify_file(file)?;
+        }
+        Ok(())
+    }
+
+    /// Restores the backup into `db_dir`, which must not hold a DB. Every file is copied (the backup can be purged afterwards) and
+    /// verified on the way.
+    pub(crate) fn restore_db_from_backup(&self, id: BackupId, db_dir: &Path) -> Result<()> {
+        let meta = self.backup(id)?;
+        if current_file(db_dir).exists() {
+            return Err(Error::InvalidArgument(format!(
+                "{} already holds a DB",
+                db_dir.display()
+            )));
+        }
+        fs::create_dir_all(db_dir)?;
+
+        // CURRENT goes last so the DB can't be opened half restored
+        let (current, rest): (Vec<&BackupFile>, Vec<&BackupFile>) = meta
+            .files
+            .iter()
+            .partition(|file| file.path.ends_with(&format!("/{CURRENT}")));
+        for file in rest.into_iter().chain(current) {
+            let (checksum, size) = copy_file(
+                &self.dir.join(&file.path),
+                &db_dir.join(restored_name(&file.path)?),
+            )?;
+            if (checksum, size) != (file.checksum, file.size) {
+                return Err(Error::Corruption(format!(
+                    "backup {id}: {} has checksum {checksum} and size {size}, expected {} and {}",
+                    file.path, file.checksum, file.size
+                )));
+            }
+        }
+        File::open(db_dir)?.sync_all()?;
+        Ok(())
+    }
+
+    /// Deletes all but the `keep` newest backups, and the shared files only they held.
+    pub(crate) fn purge_old_backups(&mut self, keep: usize) -> Result<()> {
+        let purge = self.backups.len().saturating_sub(keep);
+        let ids: Vec<BackupId> = self.backups.keys().take(purge).copied().collect();
+        for id in ids {
+            // The metadata goes first - without it the rest is garbage
+            fs::remove_file(self.meta_file(id))?;
+            self.backups.remove(&id);
+        }
+        self.garbage_collect()
+    }
+
+    fn backup(&self, id: BackupId) -> Result<&BackupMeta> {
+        self.backups
+            .get(&id)
+            .ok_or_else(|| Error::InvalidArgument(format!("backup {id} does not exist")))
+    }
+
+    fn verify_file(&self, file: &BackupFile) -> Result<()> {
+        let path = self.dir.join(&file.path);
+        if !path.exists() {
+            return Err(Error::Corruption(format!("{} is missing", file.path)));
+        }
+        let (checksum, size) = file_checksum(&path)?;
+        if size != file.size {
+            return Err(Error::Corruption(format!(
+                "{} has {size} bytes, expected {}",
+                file.path, file.size
+            )));
+        }
+        if checksum != file.checksum {
+            return Err(Error::Corruption(format!(
+                "{} has checksum {checksum}, expected {}",
+                file.path, file.checksum
+            )));
+        }
+        Ok(())
+    }
+
+    // Deletes the private dirs and shared files no backup refers to
+    fn garbage_collect(&self) -> Result<()> {
+        let referenced: HashSet<&str> = self
+            .backups
+            .values()
+            .flat_map(|meta| meta.files.iter().map(|file| file.path.as_str()))
+            .collect();
+
+        for entry in fs::read_dir(self.dir.join(SHARED_DIR))? {
+            let entry = entry?;
+            let path = format!("{SHARED_DIR}/{}", entry.file_name().to_string_lossy());
+            if !referenced.contains(path.as_str()) {
+                fs::remove_file(entry.path())?;
+            }
+        }
+        for entry in fs::read_dir(self.dir.join(PRIVATE_DIR))? {
+            let entry = entry?;
+            let id = entry
+                .file_name()
+                .to_str()
+                .and_then(|name| name.parse().ok());
+            if !id.is_some_and(|id| self.backups.contains_key(&id)) {
+                fs::remove_dir_all(entry.path())?;
+            }
+        }
+        Ok(())
+    }
+
+    fn private_dir(&self, id: BackupId) -> PathBuf {
+        self.dir.join(PRIVATE_DIR).join(id.to_string())
+    }
+
+    fn meta_file(&self, id: BackupId) -> PathBuf {
+        self.dir.join(META_DIR).join(id.to_string())
+    }
+}
+
+impl BackupMeta {
+    fn encode(&self) -> String {
+        let mut out = format!(
+            "timestamp {}\nsequence {}\nfiles {}\n",
+            self.timestamp,
+            self.sequence,
+            self.files.len()
+        );
+        for file in &self.files {
+            out.push_str(&format!("{} {} {}\n", file.path, file.checksum, file.size));
+        }
+        out
+    }
+
+    fn decode(contents: &str) -> Result<Self> {
+        let corrupt = || Error::Corruption("invalid backup metadata".into());
+        let mut lines = contents.lines();
+        let mut field = |name: &str| -> Result<u64> {
+            lines
+                .next()
+                .and_then(|line| line.strip_prefix(name))
+                .and_then(|value| value.strip_prefix(' '))
+                .and_then(|value| value.parse().ok())
+                .ok_or_else(corrupt)
+        };
+        let timestamp = field("timestamp")?;
+        let sequence = field("sequence")?;
+        let count = field("files")?;
+
+        let files = lines
+            .map(|line| {
+                let mut parts = line.split(' ');
+                let (Some(path), Some(checksum), Some(size), None) =
+                    (parts.next(), parts.next(), parts.next(), parts.next())
+                else {
+                    return Err(corrupt());
+                };
+                Ok(BackupFile {
+                    path: path.to_string(),
+                    checksum: checksum.parse().map_err(|_| corrupt())?,
+                    size: size.parse().map_err(|_| corrupt())?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if files.len() as u64 != count {
+            return Err(corrupt());
+        }
+
+        Ok(Self {
+            timestamp,
+            sequence,
+            files,
+        })
+    }
+}
+
+// The name a backed up file gets back in the DB dir
+fn restored_name(path: &str) -> Result<String> {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    match path.split_once('/') {
+        Some((SHARED_DIR, _)) => {
+            let number = name
+                .split('_')
+                .next()
+                .and_then(|number| number.parse().ok())
+                .ok_or_else(|| Error::Corruption(format!("invalid shared file name {name}")))?;
+            Ok(table_file_name(number))
+        }
+        _ => Ok(name.to_string()),
+    }
+}
+
+// crc32c and size of the file
+fn file_checksum(path: &Path) -> Result<(u32, u64)> {
+    checksum_copy(&mut File::open(path)?, &mut io::sink())
+}
+
+// Copies the file and returns the crc32c and size of what was copied
+fn copy_file(from: &Path, to: &Path) -> Result<(u32, u64)> {
+    let mut file = File::create(to)?;
+    let copied = checksum_copy(&mut File::open(from)?, &mut file)?;
+    file.sync_all()?;
+    Ok(copied)
+}
+
+fn checksum_copy(reader: &mut impl Read, writer: &mut impl Write) -> Result<(u32, u64)> {
+    let mut buf = vec![0; 64 << 10];
+    let (mut checksum, mut size) = (0, 0);
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            return Ok((checksum, size));
+        }
+        checksum = crc32c::extend(checksum, &buf[..n]);
+        size += n as u64;
+        writer.write_all(&buf[..n])?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn meta_round_trip() {
+        let meta = BackupMeta {
+            timestamp: 1_700_000_000,
+            sequence: 42,
+            files: vec![
+                BackupFile {
+                    path: "shared_checksum/000012_99_4096.sst".into(),
+                    checksum: 99,
+                    size: 4096,
+                },
+                BackupFile {
+                    path: "private/3/CURRENT".into(),
+                    checksum: 7,
+                    size: 16,
+                },
+            ],
+        };
+        assert_eq!(BackupMeta::decode(&meta.encode()).unwrap(), meta);
+
+        let truncated = meta.encode().replace("files 2", "files 3");
+        assert!(matches!(
+            BackupMeta::decode(&truncated),
+            Err(Error::Corruption(_))
+        ));
+
+        assert_eq!(
+            restored_name("shared_checksum/000012_99_4096.sst").unwrap(),
+            "000012.sst"
+        );
+        assert_eq!(restored_name("private/3/CURRENT").unwrap(), "CURRENT");
+    }
+}
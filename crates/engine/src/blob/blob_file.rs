@@ -0,0 +1,260 @@
+//
+//
+//
+// Blob Files
+//
+// With enable_blob_files, a flush or compaction moves every value of at least min_blob_size out of the table it writes and appends it to
+// a blob file. The table keeps the key with the BlobIndex op type and the BlobIndex (see blob/blob_index.rs) as its value. Blob files
+// are written once and never modified:
+//
+// | magic (u64 LE) | record 0 | record 1 | ... | footer |
+//
+// Record:
+// | key length (u32 LE) | value length (u32 LE) | crc32c of key and value (u32 LE) | user key | value |
+//
+// Footer:
+// | blob count (u64 LE) | blob bytes (u64 LE) | magic (u64 LE) |
+//
+// The user key is stored with the value so a read can check the reference leads to the blob it was written for. A blob's bytes are the
+// size of its whole record - what garbage collection gets back once nothing references it.
+//
+// An opened blob file keeps only its header and footer in memory - every read is a positioned read of the one record it needs.
+
+use std::sync::Arc;
+
+use crate::blob::blob_index::{BlobIndex, CompressionType};
+use crate::error::{Error, Result};
+use crate::utils::crc32c;
+use crate::utils::random_access_file::RandomAccessFile;
+use crate::versioning::file_version::FileNumber;
+
+pub(crate) const BLOB_MAGIC: u64 = 0x7669_6374_626c_6f62;
+
+const HEADER_SIZE: usize = 8;
+const RECORD_HEADER_SIZE: usize = 12;
+const FOOTER_SIZE: usize = 24;
+
+/// Size of the record holding a blob.
+#[inline]
+pub(crate) fn blob_record_size(user_key: &[u8], value_size: u64) -> u64 {
+    (RECORD_HEADER_SIZE + user_key.len()) as u64 + value_size
+}
+
+pub(crate) struct BlobFileBuilder {
+    number: FileNumber,
+    data: Vec<u8>,
+    blob_count: u64,
+}
+
+/// The contents of a finished blob file and what it holds.
+pub(crate) struct BuiltBlobFile {
+    pub(crate) data: Vec<u8>,
+    pub(crate) blob_count: u64,
+    pub(crate) blob_bytes: u64,
+}
+
+impl BlobFileBuilder {
+    pub(crate) fn new(number: FileNumber) -> Self {
+        Self {
+            number,
+            data: BLOB_MAGIC.to_le_bytes().to_vec(),
+            blob_count: 0,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn number(&self) -> FileNumber {
+        self.number
+    }
+
+    #[inline]
+    pub(crate) fn is_empty(&self) -> bool {
+        self.blob_count == 0
+    }
+
+    /// Appends the value and returns the reference the table stores in its place.
+    pub(crate) fn add(&mut self, user_key: &[u8], value: &[u8]) -> BlobIndex {
+        let offset = self.data.len() as u64;
+        let crc = crc32c::extend(crc32c::value(user_key), value);
+
+        self.data
+            .extend_from_slice(&(user_key.len() as u32).to_le_bytes());
+        self.data
+            .extend_from_slice(&(value.len() as u32).to_le_bytes());
+        self.data.extend_from_slice(&crc.to_le_bytes());
+        self.data.extend_from_slice(user_key);
+        self.data.extend_from_slice(value);
+        self.blob_count += 1;
+
+        BlobIndex {
+            file_number: self.number,
+            offset,
+            size: value.len() as u64,
+            compression: CompressionType::None,
+        }
+    }
+
+    pub(crate) fn finish(mut self) -> BuiltBlobFile {
+        let blob_bytes = (self.data.len() - HEADER_SIZE) as u64;
+        for value in [self.blob_count, blob_bytes, BLOB_MAGIC] {
+            self.data.extend_from_slice(&value.to_le_bytes());
+        }
+        BuiltBlobFile {
+            data: self.data,
+            blob_count: self.blob_count,
+            blob_bytes,
+        }
+    }
+}
+
+/// An opened blob file.
+pub(crate) struct BlobFile {
+    number: FileNumber,
+    file: RandomAccessFile,
+    blob_count: u64,
+    blob_bytes: u64,
+}
+
+impl BlobFile {
+    pub(crate) fn open(number: FileNumber, file: impl Into<RandomAccessFile>) -> Result<Arc<Self>> {
+        let file = file.into();
+        let corrupt = |msg: &str| Error::Corruption(format!("blob file {number}: {msg}"));
+        let size = file.size();
+        if size < (HEADER_SIZE + FOOTER_SIZE) as u64 {
+            return Err(corrupt("too short"));
+        }
+        let header = file.read(0, HEADER_SIZE)?;
+        let footer = file.read(size - FOOTER_SIZE as u64, FOOTER_SIZE)?;
+        let read_u64 =
+            |src: &[u8], at: usize| u64::from_le_bytes(src[at..at + 8].try_into().unwrap());
+        if read_u64(&header, 0) != BLOB_MAGIC || read_u64(&footer, 16) != BLOB_MAGIC {
+            return Err(corrupt("bad magic"));
+        }
+
+        let blob_count = read_u64(&footer, 0);
+        let blob_bytes = read_u64(&footer, 8);
+        if blob_bytes != size - (HEADER_SIZE + FOOTER_SIZE) as u64 {
+            return Err(corrupt("footer does not match the file size"));
+        }
+        Ok(Arc::new(Self {
+            number,
+            file,
+            blob_count,
+            blob_bytes,
+        }))
+    }
+
+    #[inline]
+    pub(crate) fn blob_count(&self) -> u64 {
+        self.blob_count
+    }
+
+    #[inline]
+    pub(crate) fn blob_bytes(&self) -> u64 {
+        self.blob_bytes
+    }
+
+    /// Reads the value `index` references. It must have been written for `user_key`.
+    pub(crate) fn get(&self, user_key: &[u8], index: &BlobIndex) -> Result<Vec<u8>> {
+        let corrupt = |msg: &str| {
+            Error::Corruption(format!(
+                "blob file {} offset {}: {msg}",
+                self.number, index.offset
+            ))
+        };
+        if index.file_number != self.number {
+            return Err(corrupt("reference to another file"));
+        }
+
+        let records_end = self.file.size() - FOOTER_SIZE as u64;
+        let record_size = blob_record_size(user_key, index.size);
+        if index.offset < HEADER_SIZE as u64
+            || index
+                .offset
+                .checked_add(record_size)
+                .is_none_or(|end| end > records_end)
+        {
+            return Err(corrupt("reference out of bounds"));
+        }
+
+        let record = self.file.read(index.offset, record_size as usize)?;
+        let read_u32 = |at: usize| u32::from_le_bytes(record[at..at + 4].try_into().unwrap());
+        if read_u32(0) as usize != user_key.len() || read_u32(4) as u64 != index.size {
+            return Err(corrupt("record does not match the reference"));
+        }
+        let (key, value) = record[RECORD_HEADER_SIZE..].split_at(user_key.len());
+        if key != user_key {
+            return Err(corrupt("record belongs to another key"));
+        }
+        if crc32c::extend(crc32c::value(key), value) != read_u32(8) {
+            return Err(corrupt("checksum mismatch"));
+        }
+        Ok(value.to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::temp_dir::TempDir;
+
+    #[test]
+    fn blobs_round_trip() {
+        let mut builder = BlobFileBuilder::new(9);
+        assert!(builder.is_empty());
+        let a = builder.add(b"a", &[1; 100]);
+        let b = builder.add(b"bb", b"");
+        let c = builder.add(b"c", &[3; 5000]);
+        let built = builder.finish();
+        assert_eq!(built.blob_count, 3);
+        assert_eq!(
+            built.blob_bytes,
+            blob_record_size(b"a", 100) + blob_record_size(b"bb", 0) + blob_record_size(b"c", 5000)
+        );
+
+        // Read from a buffer and by offset from the written file
+        let dir = TempDir::new("blob-file");
+        let path = dir.path().join("9.blob");
+        std::fs::write(&path, &built.data).unwrap();
+        for file in [
+            BlobFile::open(9, built.data).unwrap(),
+            BlobFile::open(9, RandomAccessFile::open(&path).unwrap()).unwrap(),
+        ] {
+            assert_eq!(file.blob_count(), 3);
+            assert_eq!(file.get(b"a", &a).unwrap(), vec![1; 100]);
+            assert_eq!(file.get(b"bb", &b).unwrap(), b"");
+            assert_eq!(file.get(b"c", &c).unwrap(), vec![3; 5000]);
+        }
+    }
+
+    #[test]
+    fn bad_references_and_files_are_corruption() {
+        let mut builder = BlobFileBuilder::new(3);
+        let index = builder.add(b"key", b"value");
+        let built = builder.finish();
+
+        let file = BlobFile::open(3, built.data.clone()).unwrap();
+        assert!(file.get(b"other", &index).is_err());
+        let past_end = BlobIndex {
+            offset: 1 << 20,
+            ..index
+        };
+        assert!(file.get(b"key", &past_end).is_err());
+        let wrong_file = BlobIndex {
+            file_number: 4,
+            ..index
+        };
+        assert!(file.get(b"key", &wrong_file).is_err());
+
+        let mut flipped = built.data.clone();
+        flipped[HEADER_SIZE + RECORD_HEADER_SIZE + 3] ^= 1;
+        let file = BlobFile::open(3, flipped).unwrap();
+        assert!(matches!(
+            file.get(b"key", &index),
+            Err(Error::Corruption(_))
+        ));
+
+        assert!(BlobFile::open(3, built.data[..built.data.len() - 1].to_vec()).is_err());
+        assert!(BlobFile::open(3, Vec::new()).is_err());
+    }
+}
@@ -0,0 +1,83 @@
+//
+//
+//
+// BlobIndex
+//
+// The value stored in the LSM (with the BlobIndex op type) in place of a value which was moved into a blob file:
+//
+// | file number (u64 LE) | offset (u64 LE) | size (u64 LE) | compression (1 byte) |
+//
+// The offset is where the blob's record starts in the blob file and the size is the length of the value (see blob/blob_file.rs).
+
+use crate::error::{Error, Result};
+
+pub(crate) const BLOB_INDEX_SIZE: usize = 25;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub(crate) enum CompressionType {
+    None = 0,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct BlobIndex {
+    pub(crate) file_number: u64,
+    pub(crate) offset: u64,
+    pub(crate) size: u64,
+    pub(crate) compression: CompressionType,
+}
+
+impl BlobIndex {
+    pub(crate) fn encode(&self) -> [u8; BLOB_INDEX_SIZE] {
+        let mut buf = [0u8; BLOB_INDEX_SIZE];
+        buf[..8].copy_from_slice(&self.file_number.to_le_bytes());
+        buf[8..16].copy_from_slice(&self.offset.to_le_bytes());
+        buf[16..24].copy_from_slice(&self.size.to_le_bytes());
+        buf[24] = self.compression as u8;
+        buf
+    }
+
+    pub(crate) fn decode(src: &[u8]) -> Result<Self> {
+        if src.len() != BLOB_INDEX_SIZE {
+            return Err(Error::Corruption(format!(
+                "bad blob index length {}",
+                src.len()
+            )));
+        }
+        let compression = match src[24] {
+            0 => CompressionType::None,
+            other => {
+                return Err(Error::NotSupported(format!(
+                    "blob compression type {other}"
+                )));
+            }
+        };
+        Ok(Self {
+            file_number: u64::from_le_bytes(src[..8].try_into().unwrap()),
+            offset: u64::from_le_bytes(src[8..16].try_into().unwrap()),
+            size: u64::from_le_bytes(src[16..24].try_into().unwrap()),
+            compression,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blob_index_round_trip() {
+        let index = BlobIndex {
+            file_number: 12,
+            offset: 1 << 33,
+            size: 40_000,
+            compression: CompressionType::None,
+        };
+        assert_eq!(BlobIndex::decode(&index.encode()).unwrap(), index);
+
+        let mut encoded = index.encode();
+        encoded[24] = 7;
+        assert!(BlobIndex::decode(&encoded).is_err());
+        assert!(BlobIndex::decode(&encoded[..24]).is_err());
+    }
+}
@@ -0,0 +1,127 @@
+//
+//
+//
+// BlobResolvingIterator
+//
+// Wraps the iterator of a table file of a Version and replaces each BlobIndex entry with a Put of the value it references, read from
+// the Version's blob files. Readers above it (DBIter, multi_get) never see a blob reference. A reference which can't be read stops the
+// iterator with the error.
+//
+// Compactions read the table files without it - they carry blob references over as they are unless the blob file is garbage collected.
+
+use std::sync::Arc;
+
+use crate::error::{Error, Result};
+use crate::iterator::internal_iterator::InternalIterator;
+use crate::key::internal_key::{InternalKeyRef, OperationType};
+use crate::key::iter_key::InternalIterKey;
+use crate::range::fragment::FragmentedTombstones;
+use crate::versioning::file_version::Version;
+
+pub(crate) struct BlobResolvingIterator<I: InternalIterator> {
+    iter: I,
+    version: Arc<Version>,
+    // Set while the iterator is on a resolved blob reference
+    resolved: bool,
+    key: InternalIterKey,
+    value: Vec<u8>,
+    error: Option<Error>,
+}
+
+impl<I: InternalIterator> BlobResolvingIterator<I> {
+    pub(crate) fn new(iter: I, version: Arc<Version>) -> Self {
+        Self {
+            iter,
+            version,
+            resolved: false,
+            key: InternalIterKey::new(),
+            value: Vec::new(),
+            error: None,
+        }
+    }
+
+    // Called after every move of the inner iterator
+    fn resolve(&mut self) {
+        self.resolved = false;
+        if !self.iter.valid() {
+            return;
+        }
+
+        let ikey = InternalKeyRef::from(self.iter.key());
+        if OperationType::from(ikey.op) != OperationType::BlobIndex {
+            return;
+        }
+        match self.version.resolve_blob(ikey.user_key, self.iter.value()) {
+            Ok(value) => {
+                self.key.set(ikey.user_key, ikey.seq_no, OperationType::Put);
+                self.value = value;
+                self.resolved = true;
+            }
+            Err(e) => self.error = Some(e),
+        }
+    }
+}
+
+impl<I: InternalIterator> InternalIterator for BlobResolvingIterator<I> {
+    fn seek_to_first(&mut self) {
+        self.error = None;
+        self.iter.seek_to_first();
+        self.resolve();
+    }
+
+    fn seek_to_last(&mut self) {
+        self.error = None;
+        self.iter.seek_to_last();
+        self.resolve();
+    }
+
+    fn seek(&mut self, key: &[u8]) {
+        self.error = None;
+        self.iter.seek(key);
+        self.resolve();
+    }
+
+    fn seek_for_prev(&mut self, key: &[u8]) {
+        self.error = None;
+        self.iter.seek_for_prev(key);
+        self.resolve();
+    }
+
+    fn valid(&self) -> bool {
+        self.error.is_none() && self.iter.valid()
+    }
+
+    fn next(&mut self) {
+        self.iter.next();
+        self.resolve();
+    }
+
+    fn prev(&mut self) {
+        self.iter.prev();
+        self.resolve();
+    }
+
+    fn key(&self) -> &[u8] {
+        if self.resolved {
+            self.key.as_slice()
+        } else {
+            self.iter.key()
+        }
+    }
+
+    fn value(&self) -> &[u8] {
+        if self.resolved {
+            &self.value
+        } else {
+            self.iter.value()
+        }
+    }
+
+    fn status(&self) -> Option<&Error> {
+        self.error.as_ref().or_else(|| self.iter.status())
+    }
+
+    fn range_tombstones(&self, out: &mut Vec<Arc<FragmentedTombstones>>) -> Result<()> {
+        self.iter.range_tombstones(out)
+    }
+}
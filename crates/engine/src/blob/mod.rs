@@ -0,0 +1,3 @@
+pub(crate) mod blob_file;
+pub(crate) mod blob_index;
+pub(crate) mod blob_iter;
@@ -0,0 +1,129 @@
+//
+//
+//
+// BlockBuilder
+//
+// Builds a data block of sorted key/value entries. Keys are prefix compressed against the previous key and every
+// `restart_interval` entries a full key is written (a restart point) so a reader can binary search the restarts before scanning.
+//
+// Entry:
+// | shared (VarInt) | non_shared (VarInt) | value_len (VarInt) | key_delta ... | value ... |
+//
+// Trailer:
+// | restart_0 (u32 LE) | restart_1 (u32 LE) | ... | num_restarts (u32 LE) |
+//
+// Keys must be added in sorted order - the builder doesn't know the comparator so ordering is the caller's contract.
+
+use crate::utils::var_int::VarInt;
+
+pub(crate) const DEFAULT_RESTART_INTERVAL: usize = 16;
+
+pub(crate) struct BlockBuilder {
+    buf: Vec<u8>,
+    restarts: Vec<u32>,
+    restart_interval: usize,
+    // Entries since the last restart point
+    counter: usize,
+    num_entries: usize,
+    last_key: Vec<u8>,
+    finished: bool,
+}
+
+impl BlockBuilder {
+    pub(crate) fn new(restart_interval: usize) -> Self {
+        debug_assert!(restart_interval >= 1);
+        Self {
+            buf: Vec::new(),
+            restarts: vec![0],
+            restart_interval,
+            counter: 0,
+            num_entries: 0,
+            last_key: Vec::new(),
+            finished: false,
+        }
+    }
+
+    pub(crate) fn add(&mut self, key: &[u8], value: &[u8]) {
+        debug_assert!(!self.finished);
+
+        let shared = if self.counter < self.restart_interval {
+            self.last_key
+                .iter()
+                .zip(key)
+                .take_while(|(a, b)| a == b)
+                .count()
+        } else {
+            self.restarts.push(self.buf.len() as u32);
+            self.counter = 0;
+            0
+        };
+        let non_shared = key.len() - shared;
+
+        self.buf
+            .extend_from_slice(VarInt::new(shared as u32).as_slice());
+        self.buf
+            .extend_from_slice(VarInt::new(non_shared as u32).as_slice());
+        self.buf
+            .extend_from_slice(VarInt::new(value.len() as u32).as_slice());
+        self.buf.extend_from_slice(&key[shared..]);
+        self.buf.extend_from_slice(value);
+
+        self.last_key.truncate(shared);
+        self.last_key.extend_from_slice(&key[shared..]);
+
+        self.counter += 1;
+        self.num_entries += 1;
+    }
+
+    /// Appends the restart trailer and returns the finished block. The builder must be reset before it is used again.
+    pub(crate) fn finish(&mut self) -> &[u8] {
+        if !self.finished {
+            for restart in &self.restarts {
+                self.buf.extend_from_slice(&restart.to_le_bytes());
+            }
+            self.buf
+                .extend_from_slice(&(self.restarts.len() as u32).to_le_bytes());
+            self.finished = true;
+        }
+        &self.buf
+    }
+
+    pub(crate) fn reset(&mut self) {
+        self.buf.clear();
+        self.restarts.clear();
+        self.restarts.push(0);
+        self.counter = 0;
+        self.num_entries = 0;
+        self.last_key.clear();
+        self.finished = false;
+    }
+
+    /// Size of the block if it were finished now.
+    pub(crate) fn current_size_estimate(&self) -> usize {
+        if self.finished {
+            return self.buf.len();
+        }
+        self.buf.len() + (self.restarts.len() + 1) * 4
+    }
+
+    #[inline]
+    pub(crate) fn is_empty(&self) -> bool {
+        self.num_entries == 0
+    }
+
+    #[inline]
+    pub(crate) fn num_entries(&self) -> usize {
+        self.num_entries
+    }
+
+    #[inline]
+    pub(crate) fn last_key(&self) -> &[u8] {
+        &self.last_key
+    }
+}
+
+impl Default for BlockBuilder {
+    fn default() -> Self {
+        Self::new(DEFAULT_RESTART_INTERVAL)
+    }
+}
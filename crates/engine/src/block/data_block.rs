@@ -0,0 +1,429 @@
+//
+//
+//
+// Block + BlockIter
+//
+// A Block is an immutable, decoded view over the bytes produced by BlockBuilder. Blocks are shared (Arc) so any number of iterators
+// (and later the block cache) can read the same block.
+//
+// BlockIter walks the prefix compressed entries. A seek binary searches the restart points for the last restart with a key < target
+// and then scans forward, so a seek decodes at most `restart_interval` entries. Entries can only be decoded forwards, so prev() jumps
+// back to the restart point before the current entry and scans up to it.
+//
+// Any malformed entry or trailer invalidates the iterator and surfaces a Corruption through status().
+
+use std::cmp::Ordering;
+use std::sync::Arc;
+
+use crate::error::{Error, Result};
+use crate::iterator::internal_iterator::InternalIterator;
+use crate::key::comparator::Comparator;
+use crate::utils::var_int::VarInt;
+
+pub(crate) struct Block {
+    data: Vec<u8>,
+    restart_offset: usize,
+    num_restarts: usize,
+}
+
+impl Block {
+    pub(crate) fn new(data: Vec<u8>) -> Result<Self> {
+        if data.len() < 4 {
+            return Err(Error::Corruption(
+                "block too small for restart trailer".into(),
+            ));
+        }
+
+        let num_restarts = u32::from_le_bytes(data[data.len() - 4..].try_into().unwrap()) as usize;
+        let max_restarts = (data.len() - 4) / 4;
+
+        if num_restarts == 0 || num_restarts > max_restarts {
+            return Err(Error::Corruption(format!(
+                "bad block restart count {num_restarts}"
+            )));
+        }
+
+        let restart_offset = data.len() - 4 - num_restarts * 4;
+
+        Ok(Self {
+            data,
+            restart_offset,
+            num_restarts,
+        })
+    }
+
+    #[inline]
+    fn restart_point(&self, index: usize) -> usize {
+        debug_assert!(index < self.num_restarts);
+        let at = self.restart_offset + index * 4;
+        u32::from_le_bytes(self.data[at..at + 4].try_into().unwrap()) as usize
+    }
+
+    #[inline]
+    pub(crate) fn size(&self) -> usize {
+        self.data.len()
+    }
+
+    pub(crate) fn iter(self: &Arc<Self>, comparator: Arc<dyn Comparator>) -> BlockIter {
+        BlockIter {
+            block: Arc::clone(self),
+            comparator,
+            current: self.restart_offset,
+            next_offset: self.restart_offset,
+            restart_index: 0,
+            key: Vec::new(),
+            value_offset: 0,
+            value_len: 0,
+            error: None,
+        }
+    }
+}
+
+// Decodes an entry header at offset. Returns (shared, non_shared, value_len, offset of the key delta)
+#[inline]
+fn decode_entry(data: &[u8], offset: usize, limit: usize) -> Option<(usize, usize, usize, usize)> {
+    let mut at = offset;
+    let mut next = || {
+        let (v, read) = VarInt::try_decode(data.get(at..limit)?)?;
+        at += read;
+        Some(v as usize)
+    };
+
+    let shared = next()?;
+    let non_shared = next()?;
+    let value_len = next()?;
+
+    if limit - at < non_shared + value_len {
+        return None;
+    }
+
+    Some((shared, non_shared, value_len, at))
+}
+
+pub(crate) struct BlockIter {
+    block: Arc<Block>,
+    comparator: Arc<dyn Comparator>,
+    // Offset of the current entry - restart_offset when not valid
+    current: usize,
+    // Offset of the entry after current
+    next_offset: usize,
+    // Restart block which contains current
+    restart_index: usize,
+    key: Vec<u8>,
+    value_offset: usize,
+    value_len: usize,
+    error: Option<Error>,
+}
+
+impl BlockIter {
+    #[inline]
+    fn invalidate(&mut self) {
+        self.current = self.block.restart_offset;
+        self.next_offset = self.block.restart_offset;
+    }
+
+    fn corrupt(&mut self, msg: &str) {
+        self.error = Some(Error::Corruption(format!(
+            "{msg} at block offset {}",
+            self.current
+        )));
+        self.key.clear();
+        self.invalidate();
+    }
+
+    fn seek_to_restart(&mut self, index: usize) {
+        self.key.clear();
+        self.restart_index = index;
+        self.next_offset = self.block.restart_point(index);
+    }
+
+    // Decodes the entry at next_offset. Returns false at the end of the entries or on corruption
+    fn parse_next_entry(&mut self) -> bool {
+        self.current = self.next_offset;
+        let limit = self.block.restart_offset;
+
+        if self.current >= limit {
+            self.invalidate();
+            return false;
+        }
+
+        let Some((shared, non_shared, value_len, key_offset)) =
+            decode_entry(&self.block.data, self.current, limit)
+        else {
+            self.corrupt("bad block entry");
+            return false;
+        };
+
+        if shared > self.key.len() {
+            self.corrupt("block entry shares more than the previous key");
+            return false;
+        }
+
+        self.key.truncate(shared);
+        self.key
+            .extend_from_slice(&self.block.data[key_offset..key_offset + non_shared]);
+        self.value_offset = key_offset + non_shared;
+        self.value_len = value_len;
+        self.next_offset = self.value_offset + value_len;
+
+        while self.restart_index + 1 < self.block.num_restarts
+            && self.block.restart_point(self.restart_index + 1) <= self.current
+        {
+            self.restart_index += 1;
+        }
+
+        true
+    }
+
+    // Full key stored at a restart point
+    fn restart_key(&self, index: usize) -> Option<&[u8]> {
+        let data = &self.block.data;
+        let offset = self.block.restart_point(index);
+        let (shared, non_shared, _, key_offset) =
+            decode_entry(data, offset, self.block.restart_offset)?;
+        if shared != 0 {
+            return None;
+        }
+        Some(&data[key_offset..key_offset + non_shared])
+    }
+}
+
+impl InternalIterator for BlockIter {
+    fn seek_to_first(&mut self) {
+        self.error = None;
+        self.seek_to_restart(0);
+        self.parse_next_entry();
+    }
+
+    fn seek(&mut self, key: &[u8]) {
+        self.error = None;
+
+        // Last restart point with a key < target
+        let mut left = 0;
+        let mut right = self.block.num_restarts - 1;
+        while left < right {
+            let mid = (left + right).div_ceil(2);
+            match self.restart_key(mid) {
+                Some(k) if self.comparator.compare(k, key) == Ordering::Less => left = mid,
+                Some(_) => right = mid - 1,
+                None => {
+                    self.current = self.block.restart_point(mid);
+                    self.corrupt("bad restart point");
+                    return;
+                }
+            }
+        }
+
+        self.seek_to_restart(left);
+        while self.parse_next_entry() {
+            if self.comparator.compare(&self.key, key) != Ordering::Less {
+                return;
+            }
+        }
+    }
+
+    fn seek_to_last(&mut self) {
+        self.error = None;
+        self.seek_to_restart(self.block.num_restarts - 1);
+        while self.parse_next_entry() && self.next_offset < self.block.restart_offset {}
+    }
+
+    fn seek_for_prev(&mut self, key: &[u8]) {
+        self.seek(key);
+        if !self.valid() {
+            if self.error.is_none() {
+                self.seek_to_last();
+            }
+            return;
+        }
+        if self.comparator.compare(&self.key, key) == Ordering::Greater {
+            self.prev();
+        }
+    }
+
+    #[inline]
+    fn valid(&self) -> bool {
+        self.current < self.block.restart_offset
+    }
+
+    fn next(&mut self) {
+        debug_assert!(self.valid());
+        self.parse_next_entry();
+    }
+
+    // Entries can only be decoded forwards - go back to the restart point before the current entry and scan up to it
+    fn prev(&mut self) {
+        debug_assert!(self.valid());
+        let original = self.current;
+
+        while self.block.restart_point(self.restart_index) >= original {
+            if self.restart_index == 0 {
+                self.key.clear();
+                self.invalidate();
+                return;
+            }
+            self.restart_index -= 1;
+        }
+
+        self.seek_to_restart(self.restart_index);
+        while self.parse_next_entry() && self.next_offset < original {}
+    }
+
+    #[inline]
+    fn key(&self) -> &[u8] {
+        debug_assert!(self.valid());
+        &self.key
+    }
+
+    #[inline]
+    fn value(&self) -> &[u8] {
+        debug_assert!(self.valid());
+        &self.block.data[self.value_offset..self.value_offset + self.value_len]
+    }
+
+    fn status(&self) -> Option<&Error> {
+        self.error.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::block_builder::BlockBuilder;
+    use crate::key::comparator::DefaultComparator;
+
+    fn build(entries: &[(Vec<u8>, Vec<u8>)], restart_interval: usize) -> Arc<Block> {
+        let mut builder = BlockBuilder::new(restart_interval);
+        for (k, v) in entries {
+            builder.add(k, v);
+        }
+        Arc::new(Block::new(builder.finish().to_vec()).unwrap())
+    }
+
+    fn entries(n: usize) -> Vec<(Vec<u8>, Vec<u8>)> {
+        (0..n)
+            .map(|i| {
+                (
+                    format!("key-{:05}", i * 2).into_bytes(),
+                    format!("value-{i}").into_bytes(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn iterate_all_entries() {
+        for restart_interval in [1, 3, 16] {
+            let data = entries(100);
+            let block = build(&data, restart_interval);
+            let mut iter = block.iter(DefaultComparator::new());
+
+            iter.seek_to_first();
+            for (k, v) in &data {
+                assert!(iter.valid());
+                assert_eq!(iter.key(), k.as_slice());
+                assert_eq!(iter.value(), v.as_slice());
+                iter.next();
+            }
+            assert!(!iter.valid());
+            assert!(iter.status().is_none());
+        }
+    }
+
+    #[test]
+    fn seek() {
+        let data = entries(100);
+        let block = build(&data, 4);
+        let mut iter = block.iter(DefaultComparator::new());
+
+        // Exact hits
+        for (k, v) in &data {
+            iter.seek(k);
+            assert!(iter.valid());
+            assert_eq!(iter.key(), k.as_slice());
+            assert_eq!(iter.value(), v.as_slice());
+        }
+
+        // Between keys lands on the next key
+        iter.seek(b"key-00011");
+        assert_eq!(iter.key(), b"key-00012");
+
+        iter.seek(b"a");
+        assert_eq!(iter.key(), b"key-00000");
+
+        iter.seek(b"z");
+        assert!(!iter.valid());
+    }
+
+    #[test]
+    fn reverse() {
+        for restart_interval in [1, 3, 16] {
+            let data = entries(50);
+            let block = build(&data, restart_interval);
+            let mut iter = block.iter(DefaultComparator::new());
+
+            iter.seek_to_last();
+            for (k, v) in data.iter().rev() {
+                assert!(iter.valid());
+                assert_eq!(iter.key(), k.as_slice());
+                assert_eq!(iter.value(), v.as_slice());
+                iter.prev();
+            }
+            assert!(!iter.valid());
+
+            // Exact hit and between keys
+            iter.seek_for_prev(b"key-00010");
+            assert_eq!(iter.key(), b"key-00010");
+            iter.seek_for_prev(b"key-00011");
+            assert_eq!(iter.key(), b"key-00010");
+
+            // Past the end lands on the last key, before the start is invalid
+            iter.seek_for_prev(b"z");
+            assert_eq!(iter.key(), data.last().unwrap().0.as_slice());
+            iter.seek_for_prev(b"a");
+            assert!(!iter.valid());
+
+            // Switching direction
+            iter.seek(b"key-00020");
+            iter.prev();
+            assert_eq!(iter.key(), b"key-00018");
+            iter.next();
+            assert_eq!(iter.key(), b"key-00020");
+        }
+    }
+
+    #[test]
+    fn empty_block() {
+        let block = build(&[], 16);
+        let mut iter = block.iter(DefaultComparator::new());
+
+        iter.seek_to_first();
+        assert!(!iter.valid());
+        iter.seek(b"a");
+        assert!(!iter.valid());
+        iter.seek_to_last();
+        assert!(!iter.valid());
+        iter.seek_for_prev(b"a");
+        assert!(!iter.valid());
+        assert!(iter.status().is_none());
+    }
+
+    #[test]
+    fn corruption() {
+        assert!(Block::new(vec![1, 2]).is_err());
+        // Restart count larger than the block
+        assert!(Block::new(vec![0, 0, 0, 0, 9, 0, 0, 0]).is_err());
+
+        // Entry claims a value longer than the block
+        let mut builder = BlockBuilder::new(16);
+        builder.add(b"a", b"value");
+        let mut raw = builder.finish().to_vec();
+        raw[2] = 100;
+
+        let block = Arc::new(Block::new(raw).unwrap());
+        let mut iter = block.iter(DefaultComparator::new());
+        iter.seek_to_first();
+        assert!(!iter.valid());
+        assert!(matches!(iter.status(), Some(Error::Corruption(_))));
+    }
+}
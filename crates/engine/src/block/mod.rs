@@ -1 +1,3 @@
+pub(crate) mod block_builder;
+pub(crate) mod data_block;
 pub mod scratch;
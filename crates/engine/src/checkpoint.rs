@@ -0,0 +1,113 @@
+//
+//
+//
+// Checkpoint
+//
+// An openable copy of a live DB in another directory, taken without stopping writers for longer than it takes to write a MANIFEST:
+//
+//   1. File deletions are disabled so nothing the copy needs disappears while it is made
+//   2. Under the write lock, a MANIFEST describing the DB as of now is written into the copy and the live files are listed, with the
+//      size the live WAL has at that moment (the WAL tail the copy replays on open)
+//   3. Table and blob files are immutable and hard linked (copied when the copy is on another filesystem), WALs are copied up to their size
+//
+// The copy is built in `<dir>.tmp` and renamed to `dir` once complete, so `dir` either doesn't exist or holds a whole checkpoint.
+
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::db::db_impl::DbImpl;
+use crate::db::filename::{blob_file, log_file, table_file};
+use crate::error::{Error, Result};
+
+pub(crate) struct Checkpoint<'a> {
+    db: &'a DbImpl,
+}
+
+impl<'a> Checkpoint<'a> {
+    pub(crate) fn new(db: &'a DbImpl) -> Self {
+        Self { db }
+    }
+
+    /// Creates a checkpoint of the DB in `dir`, which must not exist yet. It opens like any DB. Returns the seq no of the newest write
+    /// in the checkpoint.
+    pub(crate) fn create(&self, dir: &Path) -> Result<u64> {
+        if dir.exists() {
+            return Err(Error::InvalidArgument(format!(
+                "{} already exists",
+                dir.display()
+            )));
+        }
+        let Some(db_path) = self.db.db_path() else {
+            return Err(Error::InvalidArgument(
+                "an in-memory DB has no files to checkpoint".into(),
+            ));
+        };
+
+        let mut temp = dir.as_os_str().to_owned();
+        temp.push(".tmp");
+        let temp = PathBuf::from(temp);
+        if temp.exists() {
+            // Left by a checkpoint which failed half way
+            fs::remove_dir_all(&temp)?;
+        }
+        fs::create_dir_all(&temp)?;
+
+        let result = self.copy_live_files(&db_path, &temp);
+        match result {
+            Ok(sequence) => {
+                fs::rename(&temp, dir)?;
+                Ok(sequence)
+            }
+            Err(e) => {
+                let _ = fs::remove_dir_all(&temp);
+                Err(e)
+            }
+        }
+    }
+
+    fn copy_live_files(&self, db_path: &Path, dir: &Path) -> Result<u64> {
+        self.db.disable_file_deletions();
+        let _deletions = EnableFileDeletions(self.db);
+
+        let files = self.db.live_files(dir)?;
+        for number in files.tables {
+            link_or_copy(&table_file(db_path, number), &table_file(dir, number))?;
+        }
+        for number in files.blob_files {
+            link_or_copy(&blob_file(db_path, number), &blob_file(dir, number))?;
+        }
+        for (number, size) in files.wals {
+            copy_prefix(&log_file(db_path, number), &log_file(dir, number), size)?;
+        }
+        File::open(dir)?.sync_all()?;
+        Ok(files.sequence)
+    }
+}
+
+// Re-enables file deletions however the checkpoint ends
+struct EnableFileDeletions<'a>(&'a DbImpl);
+
+impl Drop for EnableFileDeletions<'_> {
+    fn drop(&mut self) {
+        self.0.enable_file_deletions();
+    }
+}
+
+/// Hard links `from` as `to`, or copies it where a link can't be made.
+pub(crate) fn link_or_copy(from: &Path, to: &Path) -> Result<()> {
+    // Hard links can't cross filesystems
+    if fs::hard_link(from, to).is_err() {
+        fs::copy(from, to)?;
+        File::open(to)?.sync_all()?;
+    }
+    Ok(())
+}
+
+fn copy_prefix(from: &Path, to: &Path, size: u64) -> Result<()> {
+    let mut reader = io::Read::take(File::open(from)?, size);
+    let mut file = File::create(to)?;
+    io::copy(&mut reader, &mut file)?;
+    file.sync_all()?;
+    Ok(())
+}
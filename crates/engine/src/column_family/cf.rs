@@ -1,50 +1,515 @@
 //
 //
 //
+// Column Families
 //
-use std::sync::{Arc, atomic::AtomicPtr};
+// A column family is an independent keyspace of the DB with its own options, comparator, memtables, superversion and range deletion
+// index. Every column family shares the WAL and the MANIFEST, which is what makes a write batch spanning several column families atomic.
+//
+// The ColumnFamilySet is the registry of live column families. It is owned by the VersionSet and only changes after the MANIFEST record
+// describing the change is written. Dropping a column family removes it from the set at once, so it can no longer be found by name or
+// id, but its data is only reclaimed once the last ColumnFamilyHandle to it is gone - a reader holding a handle keeps working on a
+// dropped column family.
+use std::collections::{BTreeMap, HashMap};
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicBool, AtomicPtr, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+
+use mem::allocator::{Allocator, SystemAllocator};
 
-use crate::{
-    memtable::memtable::{Memtable, Mutable},
-    versioning::{memtable_list::MemTableList, superversion::Superversion},
-};
+use crate::error::{Error, Result};
+use crate::key::comparator::{Comparator, InternalKeyComparator};
+use crate::key::internal_key::InternalKeyRef;
+use crate::memtable::memtable::{Immutable, MemID, Memtable, Mutable};
+use crate::options::ColumnFamilyOptions;
+use crate::range::index::RangeDelIndex;
+use crate::versioning::file_version::Version;
+use crate::versioning::memtable_list::{MemListVersion, MemTableList};
+use crate::versioning::seqno_to_time::SeqnoToTimeMapping;
+use crate::versioning::superversion::Superversion;
+
+pub(crate) const DEFAULT_COLUMN_FAMILY_NAME: &str = "default";
+pub(crate) const DEFAULT_COLUMN_FAMILY_ID: u32 = 0;
+
+pub(crate) struct ColumnFamilyDescriptor {
+    pub(crate) name: String,
+    pub(crate) options: ColumnFamilyOptions,
+}
+
+impl ColumnFamilyDescriptor {
+    pub(crate) fn new(name: impl Into<String>, options: ColumnFamilyOptions) -> Self {
+        Self {
+            name: name.into(),
+            options,
+        }
+    }
+}
 
 // Latest view of the LSM Tree
-pub(crate) struct ColumnFamilySet {}
+pub(crate) struct ColumnFamilySet {
+    by_name: HashMap<String, u32>,
+    column_families: BTreeMap<u32, Arc<ColumnFamilyData>>,
+    // Largest id handed out so far - ids are never reused, even after a drop
+    max_column_family: u32,
+    next_memtable_id: MemID,
+    // Dropped column families which may still be referenced by a handle
+    dropped: Vec<Weak<ColumnFamilyData>>,
+}
 
 pub(crate) struct ColumnFamilyData {
-    id: u64,
+    id: u32,
     name: String,
+    options: ColumnFamilyOptions,
+    // Orders the internal keys of the memtables and table files
+    internal_comparator: Arc<InternalKeyComparator>,
+    // Versions of a key with an older timestamp are trimmed by compactions (see key/timestamp.rs)
+    full_history_ts_low: Mutex<Option<Vec<u8>>>,
     //
     // Write Path
-    mem: Memtable<Mutable>,
-    imm: MemTableList,
+    // NOTE: Only written (and switched by a flush) with the versions lock held
+    mem: Mutex<Memtable<Mutable>>,
+    // Full memtables which are still read until a flush writes them out
+    imm: Mutex<MemTableList>,
+    // The range tombstones of the column family, unless per_file_range_deletions puts them into the memtables
+    range_del: RangeDelIndex,
     //
     // Read Path
     // NOTE: Should always be loaded with HzdPtr
     superversion: AtomicPtr<Superversion>,
+    // Superversions replaced by a memtable switch - a reader may still be on one, so they are freed with the column family
+    retired_superversions: Mutex<Vec<Arc<Superversion>>>,
+    // The table files of the column family - replaced (never mutated) under the versions lock
+    version: Mutex<Arc<Version>>,
     // --
-    // NOTE: *Version
     // NOTE: ThreadLocal<Superversion>,
     //
     // Version_history?
+    //
+    // WAL files older than this hold nothing this column family still needs
+    log_number: AtomicU64,
+    dropped: AtomicBool,
+}
+
+impl ColumnFamilyData {
+    fn new(id: u32, name: &str, options: ColumnFamilyOptions, mem_id: MemID) -> Arc<Self> {
+        let internal_comparator =
+            InternalKeyComparator::with_user_comparator(Arc::clone(&options.comparator));
+        let mem = Self::new_memtable(&options, &internal_comparator, mem_id, 0);
+
+        let range_del = RangeDelIndex::new(Arc::clone(&options.comparator));
+        let version = Version::from_levels(Vec::new(), Arc::clone(&internal_comparator));
+
+        Arc::new_cyclic(|cfd: &Weak<ColumnFamilyData>| {
+            // The allocation is in place before the closure runs, so the back pointer is stable for the lifetime of the data
+            let back = NonNull::new(cfd.as_ptr() as *mut ColumnFamilyData).unwrap();
+            let superversion = Arc::new(Superversion::new(
+                back,
+                mem.readable_memtable(),
+                Arc::new(MemListVersion::new(Vec::new())),
+                Arc::default(),
+            ));
+
+            Self {
+                id,
+                name: name.to_string(),
+                options,
+                internal_comparator,
+                full_history_ts_low: Mutex::new(None),
+                mem: Mutex::new(mem),
+                imm: Mutex::new(MemTableList::new()),
+                range_del,
+                superversion: AtomicPtr::new(Arc::into_raw(superversion).cast_mut()),
+                retired_superversions: Mutex::new(Vec::new()),
+                version: Mutex::new(Arc::new(version)),
+                log_number: AtomicU64::new(0),
+                dropped: AtomicBool::new(false),
+            }
+        })
+    }
+
+    fn new_memtable(
+        options: &ColumnFamilyOptions,
+        internal_comparator: &Arc<InternalKeyComparator>,
+        mem_id: MemID,
+        earliest_sequence: u64,
+    ) -> Memtable<Mutable> {
+        Memtable::new(
+            mem_id,
+            options.write_buffer_size.arena_policy(),
+            Allocator::System(SystemAllocator::new()),
+            Arc::clone(internal_comparator),
+        )
+        .with_earliest_sequence(earliest_sequence)
+    }
+
+    #[inline]
+    pub(crate) fn id(&self) -> u32 {
+        self.id
+    }
+
+    #[inline]
+    pub(crate) fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[inline]
+    pub(crate) fn options(&self) -> &ColumnFamilyOptions {
+        &self.options
+    }
+
+    #[inline]
+    pub(crate) fn user_comparator(&self) -> &Arc<dyn Comparator> {
+        &self.options.comparator
+    }
+
+    #[inline]
+    pub(crate) fn internal_comparator(&self) -> &Arc<InternalKeyComparator> {
+        &self.internal_comparator
+    }
+
+    /// Versions of a key with a timestamp below this are trimmed by compactions - None until it is first increased.
+    pub(crate) fn full_history_ts_low(&self) -> Option<Vec<u8>> {
+        self.full_history_ts_low.lock().unwrap().clone()
+    }
+
+    /// Raises full_history_ts_low. It never goes back - a lower timestamp is rejected.
+    pub(crate) fn increase_full_history_ts_low(&self, ts: &[u8]) -> Result<()> {
+        let timestamp_size = self.options.comparator.timestamp_size();
+        if timestamp_size == 0 || ts.len() != timestamp_size {
+            return Err(Error::InvalidArgument(format!(
+                "column family {} has {timestamp_size} byte timestamps, got {}",
+                self.name,
+                ts.len()
+            )));
+        }
+        let mut low = self.full_history_ts_low.lock().unwrap();
+        if low.as_deref().is_some_and(|low| ts < low) {
+            return Err(Error::InvalidArgument(
+                "full_history_ts_low can only be increased".into(),
+            ));
+        }
+        *low = Some(ts.to_vec());
+        Ok(())
+    }
+
+    #[inline]
+    pub(crate) fn mem(&self) -> MutexGuard<'_, Memtable<Mutable>> {
+        self.mem.lock().unwrap()
+    }
+
+    /// Replaces the memtable with an empty one, created after the write `earliest_sequence`, and installs a superversion reading it with
+    /// `seqno_to_time`. Called by a flush once the entries of the old memtable and the immutable memtables are in the current Version -
+    /// the old memtable is returned frozen.
+    pub(crate) fn switch_memtable(
+        &self,
+        mem_id: MemID,
+        earliest_sequence: u64,
+        seqno_to_time: Arc<SeqnoToTimeMapping>,
+    ) -> Memtable<Immutable> {
+        let mut mem = self.mem.lock().unwrap();
+        let old = std::mem::replace(
+            &mut *mem,
+            Self::new_memtable(
+                &self.options,
+                &self.internal_comparator,
+                mem_id,
+                earliest_sequence,
+            ),
+        );
+        let imm = self.imm.lock().unwrap().clear();
+        self.install_superversion(&mem, imm, seqno_to_time);
+        old.freeze()
+    }
+
+    /// The memtables switched out of the write path which no flush has written out yet, newest first.
+    pub(crate) fn immutable_memtables(&self) -> Arc<MemListVersion> {
+        self.imm.lock().unwrap().current()
+    }
+
+    /// Inserts an entry into the memtable. A memtable without room for it is moved to the immutable memtables, which reads keep
+    /// seeing until a flush writes them out, and the entry goes into a new memtable with the id `new_mem_id` hands out.
+    pub(crate) fn insert(
+        &self,
+        key: &[u8],
+        value: &[u8],
+        new_mem_id: impl FnOnce() -> MemID,
+        seqno_to_time: &Arc<SeqnoToTimeMapping>,
+    ) -> Result<()> {
+        let mut mem = self.mem.lock().unwrap();
+        match mem.insert(key, value) {
+            Err(Error::Busy(_)) => {
+                // The entry is the first write the new memtable holds
+                let earliest_sequence = InternalKeyRef::from(key).seq_no.saturating_sub(1);
+                self.switch_to_immutable(
+                    &mut mem,
+                    new_mem_id(),
+                    earliest_sequence,
+                    Arc::clone(seqno_to_time),
+                );
+                mem.insert(key, value)
+            }
+            result => result,
+        }
+    }
+
+    /// Deletes [start, end) below `seq`. The tombstone goes into the RangeDelIndex, or with per_file_range_deletions into the memtable -
+    /// switched like on an insert when it has no room left.
+    pub(crate) fn delete_range(
+        &self,
+        start: &[u8],
+        end: &[u8],
+        seq: u64,
+        new_mem_id: impl FnOnce() -> MemID,
+        seqno_to_time: &Arc<SeqnoToTimeMapping>,
+    ) -> Result<()> {
+        if !self.options.per_file_range_deletions {
+            return self.range_del.insert(start, end, seq);
+        }
+        let mut mem = self.mem.lock().unwrap();
+        match mem.delete_range(start, end, seq) {
+            Err(Error::Busy(_)) => {
+                self.switch_to_immutable(
+                    &mut mem,
+                    new_mem_id(),
+                    seq.saturating_sub(1),
+                    Arc::clone(seqno_to_time),
+                );
+                mem.delete_range(start, end, seq)
+            }
+            result => result,
+        }
+    }
+
+    /// Moves the memtable to the immutable memtables and replaces it with an empty one, created after the write `earliest_sequence`.
+    /// Used when there are no files to flush it to, or in the middle of a write which found it full.
+    pub(crate) fn switch_to_immutable_memtable(
+        &self,
+        mem_id: MemID,
+        earliest_sequence: u64,
+        seqno_to_time: Arc<SeqnoToTimeMapping>,
+    ) {
+        let mut mem = self.mem.lock().unwrap();
+        self.switch_to_immutable(&mut mem, mem_id, earliest_sequence, seqno_to_time);
+    }
+
+    fn switch_to_immutable(
+        &self,
+        mem: &mut Memtable<Mutable>,
+        mem_id: MemID,
+        earliest_sequence: u64,
+        seqno_to_time: Arc<SeqnoToTimeMapping>,
+    ) {
+        let old = std::mem::replace(
+            mem,
+            Self::new_memtable(
+                &self.options,
+                &self.internal_comparator,
+                mem_id,
+                earliest_sequence,
+            ),
+        );
+        let imm = self.imm.lock().unwrap().add(old.freeze());
+        self.install_superversion(mem, imm, seqno_to_time);
+    }
+
+    fn install_superversion(
+        &self,
+        mem: &Memtable<Mutable>,
+        imm: Arc<MemListVersion>,
+        seqno_to_time: Arc<SeqnoToTimeMapping>,
+    ) {
+        let superversion = Arc::new(Superversion::new(
+            NonNull::from(self),
+            mem.readable_memtable(),
+            imm,
+            seqno_to_time,
+        ));
+        let retired = self
+            .superversion
+            .swap(Arc::into_raw(superversion).cast_mut(), Ordering::AcqRel);
+        // SAFETY: Created with Arc::into_raw by the column family, which is the only place a superversion is swapped out
+        self.retired_superversions
+            .lock()
+            .unwrap()
+            .push(unsafe { Arc::from_raw(retired) });
+    }
+
+    #[inline]
+    pub(crate) fn range_del(&self) -> &RangeDelIndex {
+        &self.range_del
+    }
+
+    // NOTE: A flush replaces the superversion but keeps the old one until the column family is dropped, so the reference stays valid -
+    // readers should pin it through the hazard pointer (SVCache) instead, which lets retired superversions be freed early
+    pub(crate) fn superversion(&self) -> &Superversion {
+        // SAFETY: The superversion is installed when the column family is created and freed when it is dropped
+        unsafe { &*self.superversion.load(Ordering::Acquire) }
+    }
+
+    pub(crate) fn current_version(&self) -> Arc<Version> {
+        Arc::clone(&self.version.lock().unwrap())
+    }
+
+    /// Publishes a new set of table files. Readers holding the old Version keep reading it.
+    pub(crate) fn install_version(&self, version: Arc<Version>) {
+        *self.version.lock().unwrap() = version;
+    }
+
+    #[inline]
+    pub(crate) fn log_number(&self) -> u64 {
+        self.log_number.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn set_log_number(&self, number: u64) {
+        self.log_number.store(number, Ordering::Release);
+    }
+
+    #[inline]
+    pub(crate) fn is_dropped(&self) -> bool {
+        self.dropped.load(Ordering::Acquire)
+    }
+}
+
+impl Drop for ColumnFamilyData {
+    // The retired superversions are freed with the field
+    fn drop(&mut self) {
+        let superversion = self
+            .superversion
+            .swap(std::ptr::null_mut(), Ordering::AcqRel);
+        if !superversion.is_null() {
+            // SAFETY: Created with Arc::into_raw and no reader can hold it once the last reference to the column family is gone
+            drop(unsafe { Arc::from_raw(superversion) });
+        }
+    }
 }
 
 // BASIC IMPL
 impl ColumnFamilySet {
-    pub(crate) fn set_memtable() {
-        // Checks
-        // mem->MarkImmutable()
+    pub(crate) fn new() -> Self {
+        Self {
+            by_name: HashMap::new(),
+            column_families: BTreeMap::new(),
+            max_column_family: DEFAULT_COLUMN_FAMILY_ID,
+            next_memtable_id: 1,
+            dropped: Vec::new(),
+        }
+    }
+
+    /// Registers a column family. Called once its creation is in the MANIFEST (or while replaying the MANIFEST).
+    pub(crate) fn create(
+        &mut self,
+        id: u32,
+        name: &str,
+        options: ColumnFamilyOptions,
+    ) -> Arc<ColumnFamilyData> {
+        debug_assert!(!self.column_families.contains_key(&id));
+        debug_assert!(!self.by_name.contains_key(name));
+
+        let mem_id = self.assign_memtable_id();
+        let cfd = ColumnFamilyData::new(id, name, options, mem_id);
+        self.by_name.insert(name.to_string(), id);
+        self.column_families.insert(id, Arc::clone(&cfd));
+        self.max_column_family = self.max_column_family.max(id);
+        cfd
     }
 
-    fn assign_memtable_id() {}
+    /// Removes a column family from the set. Its data lives on until every handle to it is dropped.
+    pub(crate) fn drop_column_family(&mut self, id: u32) -> Option<Arc<ColumnFamilyData>> {
+        let cfd = self.column_families.remove(&id)?;
+        self.by_name.remove(cfd.name());
+        cfd.dropped.store(true, Ordering::Release);
+        self.dropped.push(Arc::downgrade(&cfd));
+        Some(cfd)
+    }
+
+    #[inline]
+    pub(crate) fn get(&self, id: u32) -> Option<&Arc<ColumnFamilyData>> {
+        self.column_families.get(&id)
+    }
+
+    pub(crate) fn get_by_name(&self, name: &str) -> Option<&Arc<ColumnFamilyData>> {
+        self.by_name
+            .get(name)
+            .and_then(|id| self.column_families.get(id))
+    }
+
+    /// Live column families by id.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &Arc<ColumnFamilyData>> {
+        self.column_families.values()
+    }
+
+    #[inline]
+    pub(crate) fn len(&self) -> usize {
+        self.column_families.len()
+    }
+
+    #[inline]
+    pub(crate) fn max_column_family(&self) -> u32 {
+        self.max_column_family
+    }
+
+    pub(crate) fn set_max_column_family(&mut self, max: u32) {
+        self.max_column_family = self.max_column_family.max(max);
+    }
+
+    /// Dropped column families still held by a handle - their files stay live until the last one is gone.
+    pub(crate) fn iter_dropped(&self) -> impl Iterator<Item = Arc<ColumnFamilyData>> + '_ {
+        self.dropped.iter().filter_map(Weak::upgrade)
+    }
+
+    /// Number of dropped column families whose data is still held by a handle.
+    pub(crate) fn num_pending_reclaim(&mut self) -> usize {
+        self.dropped.retain(|cfd| cfd.strong_count() > 0);
+        self.dropped.len()
+    }
+
+    pub(crate) fn assign_memtable_id(&mut self) -> MemID {
+        let id = self.next_memtable_id;
+        self.next_memtable_id += 1;
+        id
+    }
 
     // calculate the oldest log needed for the durability of this column family
-    fn oldest_log_to_keep() {}
+    pub(crate) fn oldest_log_to_keep(&self) -> u64 {
+        self.iter().map(|cfd| cfd.log_number()).min().unwrap_or(0)
+    }
+}
+
+impl Default for ColumnFamilySet {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 // Direct path handle without going through DBImpl
+#[derive(Clone)]
 pub(crate) struct ColumnFamilyHandle {
     // NOTE: Needs to be Arc because if we drop the cf_set then we need to wait for all handles to unref before dropping fully
     inner: Arc<ColumnFamilyData>,
 }
+
+impl ColumnFamilyHandle {
+    pub(crate) fn new(inner: Arc<ColumnFamilyData>) -> Self {
+        Self { inner }
+    }
+
+    #[inline]
+    pub(crate) fn id(&self) -> u32 {
+        self.inner.id()
+    }
+
+    #[inline]
+    pub(crate) fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    #[inline]
+    pub(crate) fn data(&self) -> &Arc<ColumnFamilyData> {
+        &self.inner
+    }
+
+    #[inline]
+    pub(crate) fn is_dropped(&self) -> bool {
+        self.inner.is_dropped()
+    }
+}
@@ -0,0 +1,909 @@
+//
+//
+//
+// CompactionIterator
+//
+// The CompactionIterator sits between the merged input of a flush or compaction and the table builder. It walks internal keys in
+// order (user key asc, seq no desc) and decides which entries make it into the output:
+//
+// Snapshot stripes:
+//
+// Every live snapshot splits the sequence space of a user key into stripes. A version is visible to the earliest snapshot whose seq no is
+// >= the version's seq no (or to no snapshot at all - the "tip" stripe). Within a stripe only the newest version can ever be read, so every
+// older version in the same stripe is dropped. The newest version of each stripe is always kept.
+//
+//    seq:   9      7      5      3      1
+//    key:  k@9    k@7    k@5    k@3    k@1        snapshots = [4, 8]
+//          |tip|  |---- 8 ----|  |----- 4 -----|
+//          keep   keep   drop    keep   drop
+//
+// Merge operands do not hide older versions as they still need the older versions to be resolved.
+//
+// Merges:
+//
+// With a merge operator configured, a run of merge operands for a key is collected until the end of the stripe. If the run reaches a base
+// (Put or Delete) in the same stripe, or the start of the key's history at the bottommost level, the run is full merged into a single Put
+// at the newest operand's seq no. Otherwise the operands are partial merged into a single operand where the operator allows it, and are
+// written out unchanged where it does not. Runs never cross a snapshot boundary so every snapshot still reads the same value. A run also
+// stops at a PutWithExpiry (see ttl.rs) - merged into a Put the value would outlive the base's expiry.
+//
+// Compaction Filter:
+//
+// See compaction/filter.rs - the filter only sees the newest version of a key when that version is in the tip stripe.
+//
+// Tombstones:
+//
+// When the output is the bottommost level, a Delete in the earliest stripe has nothing left to hide and no snapshot which needs it,
+// so it is dropped along with everything older than it.
+//
+// Range Deletions:
+//
+// With the column family's RangeDelIndex given, a version covered by a range tombstone in its own stripe is dropped - no snapshot can
+// read it. A tombstone in a newer stripe is not applied as a snapshot between the two still sees the version. The tombstones themselves
+// stay in the index until garbage collection finds nothing older than them is left (see range/index.rs).
+//
+// The tombstones stored in the input memtables and files are applied the same way. They are also carried into the output: they are
+// fragmented together, only the newest of each stripe is kept per fragment (at the bottommost level nothing in the earliest stripe is
+// kept, there is nothing left below for it to hide), and output_range_tombstones() truncates them to the key range of each output file.
+//
+// User-Defined Timestamps:
+//
+// With timestamps (see key/timestamp.rs) every timestamp of a key is a user key of its own above, so by default every version is kept.
+// Given full_history_ts_low, versions with an older timestamp are only kept for the snapshots which need them: a version below it hides
+// the versions of the key with a still older timestamp in its own and newer stripes, exactly like a newer seq no does. At the bottommost
+// level a tombstone below it is dropped once it has hidden them.
+//
+// Blob References:
+//
+// A BlobIndex entry stands for a Put whose value is in a blob file and is carried over as it is. Its value is only read from the Version's
+// blob files when the filter needs it, when it is the base of a merge, or when its blob file is being garbage collected - the entry is
+// then written out as a Put of the value, for the output to write into a new blob file.
+
+use std::cmp::Ordering;
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+use crate::blob::blob_index::BlobIndex;
+
+use crate::compaction::filter::{
+    CompactionFilter, CompactionFilterContext, FilterDecision, FilterValueType,
+};
+use crate::error::Error;
+use crate::iterator::internal_iterator::InternalIterator;
+use crate::key::comparator::Comparator;
+use crate::key::internal_key::{InternalKeyRef, OperationType};
+use crate::key::iter_key::InternalIterKey;
+use crate::key::timestamp::{strip_timestamp, timestamp_of};
+use crate::merge::helper::{full_merge, partial_merge};
+use crate::merge::operator::MergeOperator;
+use crate::range::fragment::{FragmentedTombstones, RangeTombstone};
+use crate::range::index::RangeDelVersion;
+use crate::versioning::file_version::{FileNumber, Version};
+
+// Stripe of versions which are newer than every snapshot
+const TIP_STRIPE: u64 = u64::MAX;
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct CompactionIterationStats {
+    pub(crate) num_input_records: u64,
+    pub(crate) num_dropped_hidden: u64,
+    pub(crate) num_dropped_filtered: u64,
+    pub(crate) num_dropped_tombstones: u64,
+    pub(crate) num_dropped_range_del: u64,
+    pub(crate) num_changed_values: u64,
+    pub(crate) num_merged_operands: u64,
+    pub(crate) num_relocated_blobs: u64,
+}
+
+pub(crate) struct CompactionIterator<'a, I: InternalIterator> {
+    input: I,
+    user_comparator: &'a dyn Comparator,
+    // Live snapshot seq numbers in ascending order
+    snapshots: &'a [u64],
+    filter: Option<&'a dyn CompactionFilter>,
+    filter_ctx: CompactionFilterContext,
+    merge_operator: Option<&'a dyn MergeOperator>,
+    range_del: Option<&'a RangeDelVersion>,
+    // Version whose blob files the input references, and the blob files being garbage collected
+    blob_version: Option<&'a Version>,
+    relocated_blob_files: Option<&'a BTreeSet<FileNumber>>,
+    // Range tombstones stored in the inputs, one entry per memtable / file which has any
+    input_range_tombstones: Vec<Arc<FragmentedTombstones>>,
+    // What is left of them for the output
+    output_range_tombstones: FragmentedTombstones,
+    // Set if the tombstones of an input couldn't be read - the compaction fails
+    range_tombstones_error: Option<Error>,
+    bottommost_level: bool,
+    // Size of the timestamp at the end of every user key (0 without timestamps) and the timestamp history is kept from
+    timestamp_size: usize,
+    full_history_ts_low: Option<&'a [u8]>,
+
+    // Per user key state
+    current_user_key: Vec<u8>,
+    has_current_user_key: bool,
+    // Stripe of the last version we kept (or dropped as a tombstone) for current_user_key and whether it hides older versions
+    last_stripe: Option<u64>,
+    last_hides: bool,
+    skip_until: Option<Vec<u8>>,
+    // Key (without its timestamp) of the versions seen last, and the oldest stripe of a version below full_history_ts_low kept for it which
+    // hides the versions with an older timestamp
+    history_key: Vec<u8>,
+    history_hidden_from: Option<u64>,
+
+    // Output
+    valid: bool,
+    key_buf: InternalIterKey,
+    key_overridden: bool,
+    value_override: Option<Vec<u8>>,
+    // Output of a merge run (key, value) newest first. While non-empty the input is already positioned past the run.
+    merge_out: Vec<(Vec<u8>, Vec<u8>)>,
+    merge_out_idx: usize,
+
+    status: Option<Error>,
+    stats: CompactionIterationStats,
+}
+
+impl<'a, I: InternalIterator> CompactionIterator<'a, I> {
+    pub(crate) fn new(
+        input: I,
+        user_comparator: &'a dyn Comparator,
+        snapshots: &'a [u64],
+        filter: Option<&'a dyn CompactionFilter>,
+        filter_ctx: CompactionFilterContext,
+        bottommost_level: bool,
+    ) -> Self {
+        debug_assert!(snapshots.windows(2).all(|w| w[0] <= w[1]));
+
+        let mut input_range_tombstones = Vec::new();
+        let range_tombstones_error = input.range_tombstones(&mut input_range_tombstones).err();
+        let horizon = if bottommost_level {
+            snapshots.first().copied().unwrap_or(u64::MAX)
+        } else {
+            0
+        };
+        let output_range_tombstones = FragmentedTombstones::new(
+            input_range_tombstones.iter().flat_map(|t| t.tombstones()),
+            user_comparator,
+        )
+        .compact(horizon, snapshots, user_comparator);
+
+        Self {
+            input,
+            user_comparator,
+            snapshots,
+            filter,
+            filter_ctx,
+            merge_operator: None,
+            range_del: None,
+            blob_version: None,
+            relocated_blob_files: None,
+            input_range_tombstones,
+            output_range_tombstones,
+            range_tombstones_error,
+            bottommost_level,
+            timestamp_size: user_comparator.timestamp_size(),
+            full_history_ts_low: None,
+            current_user_key: Vec::new(),
+            has_current_user_key: false,
+            last_stripe: None,
+            last_hides: false,
+            skip_until: None,
+            history_key: Vec::new(),
+            history_hidden_from: None,
+            valid: false,
+            key_buf: InternalIterKey::new(),
+            key_overridden: false,
+            value_override: None,
+            merge_out: Vec::new(),
+            merge_out_idx: 0,
+            status: None,
+            stats: CompactionIterationStats::default(),
+        }
+    }
+
+    pub(crate) fn with_merge_operator(mut self, merge_operator: &'a dyn MergeOperator) -> Self {
+        self.merge_operator = Some(merge_operator);
+        self
+    }
+
+    /// Trims the history of versions with a timestamp below `ts` (see key/timestamp.rs).
+    pub(crate) fn with_full_history_ts_low(mut self, ts: &'a [u8]) -> Self {
+        debug_assert_eq!(ts.len(), self.timestamp_size);
+        self.full_history_ts_low = Some(ts);
+        self
+    }
+
+    pub(crate) fn with_range_del(mut self, range_del: &'a RangeDelVersion) -> Self {
+        self.range_del = (!range_del.is_empty()).then_some(range_del);
+        self
+    }
+
+    /// Lets blob references of the input be read from the version's blob files. The references to `relocated` blob files are written
+    /// out as Puts of their values.
+    pub(crate) fn with_blob_files(
+        mut self,
+        version: &'a Version,
+        relocated: &'a BTreeSet<FileNumber>,
+    ) -> Self {
+        self.blob_version = Some(version);
+        self.relocated_blob_files = Some(relocated);
+        self
+    }
+
+    // Reads the value of the blob reference the input is on
+    fn read_blob(&self, user_key: &[u8]) -> Result<Vec<u8>, Error> {
+        match self.blob_version {
+            Some(version) => version.resolve_blob(user_key, self.input.value()),
+            None => Err(Error::Corruption(
+                "blob reference found but no blob files were given".into(),
+            )),
+        }
+    }
+
+    // True if the input is on a blob reference into a blob file being garbage collected
+    fn is_relocated_blob(&self) -> bool {
+        self.relocated_blob_files.is_some_and(|relocated| {
+            BlobIndex::decode(self.input.value())
+                .is_ok_and(|index| relocated.contains(&index.file_number))
+        })
+    }
+
+    // True if the user key has a timestamp below full_history_ts_low
+    #[inline]
+    fn below_history_low(&self, user_key: &[u8]) -> bool {
+        self.full_history_ts_low
+            .is_some_and(|low| timestamp_of(user_key, self.timestamp_size) < low)
+    }
+
+    // Records that the version of current_user_key in `stripe` is kept (or dropped as a tombstone after hiding what is older)
+    fn set_last_version(&mut self, stripe: u64, hides: bool) {
+        self.last_stripe = Some(stripe);
+        self.last_hides = hides;
+        if hides && self.below_history_low(&self.current_user_key) {
+            self.history_hidden_from =
+                Some(self.history_hidden_from.map_or(stripe, |s| s.min(stripe)));
+        }
+    }
+
+    // True if a range tombstone in the same stripe covers the version
+    #[inline]
+    fn is_range_deleted(&self, user_key: &[u8], seq: u64, stripe: u64) -> bool {
+        self.range_del
+            .is_some_and(|r| r.is_covered(user_key, seq, stripe))
+            || self
+                .input_range_tombstones
+                .iter()
+                .any(|t| t.max_covering_seq(self.user_comparator, user_key, stripe) > seq)
+    }
+
+    /// The range tombstones to write into an output file which owns the user keys [lower, upper). The first output has no lower bound,
+    /// the last no upper bound, and `upper` of one output is the first user key of the next so no tombstone is lost between them.
+    pub(crate) fn output_range_tombstones(
+        &self,
+        lower: Option<&[u8]>,
+        upper: Option<&[u8]>,
+    ) -> Vec<RangeTombstone> {
+        self.output_range_tombstones
+            .truncated(self.user_comparator, lower, upper)
+            .collect()
+    }
+
+    pub(crate) fn seek_to_first(&mut self) {
+        self.has_current_user_key = false;
+        self.last_stripe = None;
+        self.skip_until = None;
+        self.history_hidden_from = None;
+        self.merge_out.clear();
+        self.merge_out_idx = 0;
+        self.status = None;
+        if let Some(e) = &self.range_tombstones_error {
+            self.status = Some(e.clone());
+            self.valid = false;
+            return;
+        }
+        self.input.seek_to_first();
+        self.find_next_entry();
+    }
+
+    pub(crate) fn valid(&self) -> bool {
+        self.valid
+    }
+
+    pub(crate) fn next(&mut self) {
+        debug_assert!(self.valid);
+
+        if !self.merge_out.is_empty() {
+            self.merge_out_idx += 1;
+            if self.merge_out_idx < self.merge_out.len() {
+                return;
+            }
+            self.merge_out.clear();
+            self.merge_out_idx = 0;
+            self.find_next_entry();
+            return;
+        }
+
+        self.input.next();
+        self.find_next_entry();
+    }
+
+    pub(crate) fn key(&self) -> &[u8] {
+        debug_assert!(self.valid);
+        if !self.merge_out.is_empty() {
+            self.merge_out[self.merge_out_idx].0.as_slice()
+        } else if self.key_overridden {
+            self.key_buf.as_slice()
+        } else {
+            self.input.key()
+        }
+    }
+
+    pub(crate) fn value(&self) -> &[u8] {
+        debug_assert!(self.valid);
+        if !self.merge_out.is_empty() {
+            return self.merge_out[self.merge_out_idx].1.as_slice();
+        }
+        match &self.value_override {
+            Some(v) => v,
+            None => self.input.value(),
+        }
+    }
+
+    /// Set if the iterator stopped because of an error (e.g a failed merge or a corrupt input). The output must be discarded.
+    pub(crate) fn status(&self) -> Option<&Error> {
+        self.status.as_ref().or_else(|| self.input.status())
+    }
+
+    pub(crate) fn stats(&self) -> &CompactionIterationStats {
+        &self.stats
+    }
+
+    // Returns the stripe (earliest snapshot which can see seq) that the version belongs to
+    #[inline]
+    fn stripe_of(&self, seq: u64) -> u64 {
+        let idx = self.snapshots.partition_point(|s| *s < seq);
+        self.snapshots.get(idx).copied().unwrap_or(TIP_STRIPE)
+    }
+
+    #[inline]
+    fn earliest_stripe(&self) -> u64 {
+        self.snapshots.first().copied().unwrap_or(TIP_STRIPE)
+    }
+
+    fn find_next_entry(&mut self) {
+        self.valid = false;
+        self.key_overridden = false;
+        self.value_override = None;
+
+        while self.input.valid() {
+            self.stats.num_input_records += 1;
+
+            let ikey = InternalKeyRef::from(self.input.key());
+            let op = OperationType::from(ikey.op);
+
+            let first_version = !self.has_current_user_key
+                || self
+                    .user_comparator
+                    .compare(ikey.user_key, &self.current_user_key)
+                    != Ordering::Equal;
+
+            if first_version {
+                // The history of a key goes on across the timestamps of its versions
+                if self.timestamp_size > 0 {
+                    let key = strip_timestamp(ikey.user_key, self.timestamp_size);
+                    let same_key = self.has_current_user_key
+                        && self.user_comparator.compare_without_timestamp(
+                            key,
+                            false,
+                            &self.history_key,
+                            false,
+                        ) == Ordering::Equal;
+                    if !same_key {
+                        self.history_key.clear();
+                        self.history_key.extend_from_slice(key);
+                        self.history_hidden_from = None;
+                    }
+                }
+                self.current_user_key.clear();
+                self.current_user_key.extend_from_slice(ikey.user_key);
+                self.has_current_user_key = true;
+                self.last_stripe = None;
+                self.last_hides = false;
+            }
+
+            let stripe = self.stripe_of(ikey.seq_no);
+
+            if let Some(until) = &self.skip_until {
+                if self.user_comparator.compare(ikey.user_key, until) == Ordering::Less {
+                    if stripe == TIP_STRIPE {
+                        self.stats.num_dropped_filtered += 1;
+                        self.input.next();
+                        continue;
+                    }
+                    // Visible to a snapshot - fall through and keep it
+                } else {
+                    self.skip_until = None;
+                }
+            }
+
+            if self.last_stripe == Some(stripe) && self.last_hides {
+                self.stats.num_dropped_hidden += 1;
+                self.input.next();
+                continue;
+            }
+
+            // Hidden by a version with a newer timestamp below full_history_ts_low in this or an older stripe
+            if self.history_hidden_from.is_some_and(|from| stripe >= from)
+                && self.below_history_low(ikey.user_key)
+            {
+                self.stats.num_dropped_hidden += 1;
+                self.input.next();
+                continue;
+            }
+
+            if self.is_range_deleted(ikey.user_key, ikey.seq_no, stripe) {
+                self.stats.num_dropped_range_del += 1;
+                self.input.next();
+                continue;
+            }
+
+            let mut out_op = op;
+
+            let value_type = match op {
+                OperationType::Put | OperationType::BlobIndex => Some(FilterValueType::Value),
+                OperationType::Merge => Some(FilterValueType::MergeOperand),
+                OperationType::PutWithExpiry => Some(FilterValueType::ExpiringValue),
+                _ => None,
+            };
+            let filtered = first_version
+                && stripe == TIP_STRIPE
+                && self.skip_until.is_none()
+                && self.filter.is_some();
+
+            let mut blob_value = None;
+            if op == OperationType::BlobIndex && (filtered || self.is_relocated_blob()) {
+                match self.read_blob(ikey.user_key) {
+                    Ok(value) => blob_value = Some(value),
+                    Err(e) => {
+                        self.status = Some(e);
+                        return;
+                    }
+                }
+            }
+
+            if filtered
+                && let Some(filter) = self.filter
+                && let Some(value_type) = value_type
+            {
+                let value = blob_value.as_deref().unwrap_or(self.input.value());
+                match filter.filter(&self.filter_ctx, ikey.user_key, value_type, value) {
+                    FilterDecision::Keep => {}
+                    FilterDecision::Remove => {
+                        out_op = OperationType::Delete;
+                        self.key_buf
+                            .set(ikey.user_key, ikey.seq_no, OperationType::Delete);
+                        self.key_overridden = true;
+                        self.value_override = Some(Vec::new());
+                        self.stats.num_dropped_filtered += 1;
+                    }
+                    FilterDecision::ChangeValue(v) => {
+                        if op == OperationType::BlobIndex {
+                            out_op = OperationType::Put;
+                            self.key_buf
+                                .set(ikey.user_key, ikey.seq_no, OperationType::Put);
+                            self.key_overridden = true;
+                            blob_value = None;
+                        }
+                        self.value_override = Some(v);
+                        self.stats.num_changed_values += 1;
+                    }
+                    FilterDecision::RemoveAndSkipUntil(until) => {
+                        // Can't skip backwards - keep the key as the decision is invalid
+                        if self.user_comparator.compare(&until, ikey.user_key) == Ordering::Greater
+                        {
+                            self.skip_until = Some(until);
+                            self.stats.num_dropped_filtered += 1;
+                            self.input.next();
+                            continue;
+                        }
+                    }
+                }
+            }
+
+            // Written out as a Put of the value so the blob file no longer holds anything live
+            if out_op == OperationType::BlobIndex && self.is_relocated_blob() {
+                out_op = OperationType::Put;
+                self.key_buf
+                    .set(ikey.user_key, ikey.seq_no, OperationType::Put);
+                self.key_overridden = true;
+                self.value_override = blob_value;
+                self.stats.num_relocated_blobs += 1;
+            }
+
+            if out_op == OperationType::Merge
+                && let Some(merge_operator) = self.merge_operator
+            {
+                self.merge_run(merge_operator, stripe);
+                self.valid = self.status.is_none();
+                return;
+            }
+
+            self.set_last_version(stripe, out_op != OperationType::Merge);
+
+            // With timestamps the versions with an older timestamp are only gone below full_history_ts_low
+            if matches!(out_op, OperationType::Delete | OperationType::SingleDelete)
+                && self.bottommost_level
+                && stripe == self.earliest_stripe()
+                && (self.timestamp_size == 0 || self.below_history_low(&self.current_user_key))
+            {
+                self.stats.num_dropped_tombstones += 1;
+                self.key_overridden = false;
+                self.value_override = None;
+                self.input.next();
+                continue;
+            }
+
+            self.valid = true;
+            return;
+        }
+    }
+
+    // Collects the run of merge operands for the current key starting at the input's current entry and stopping at the end of the stripe.
+    // Leaves the input positioned at the first entry which is not part of the run and fills merge_out.
+    fn merge_run(&mut self, merge_operator: &dyn MergeOperator, stripe: u64) {
+        let newest_seq = InternalKeyRef::from(self.input.key()).seq_no;
+
+        // Newest -> oldest
+        let mut keys = vec![self.input.key().to_vec()];
+        let mut operands = vec![
+            self.value_override
+                .take()
+                .unwrap_or_else(|| self.input.value().to_vec()),
+        ];
+        // Some(None) is a Delete base
+        let mut base: Option<Option<Vec<u8>>> = None;
+        let mut reached_key_end = true;
+
+        self.input.next();
+
+        while self.input.valid() {
+            let ik = InternalKeyRef::from(self.input.key());
+
+            if self
+                .user_comparator
+                .compare(ik.user_key, &self.current_user_key)
+                != Ordering::Equal
+            {
+                break;
+            }
+
+            if self.stripe_of(ik.seq_no) != stripe {
+                reached_key_end = false;
+                break;
+            }
+
+            // Reads as a Delete base. The covered entry is left for the main loop to drop
+            if self.is_range_deleted(ik.user_key, ik.seq_no, stripe) {
+                base = Some(None);
+                break;
+            }
+
+            // Left for the main loop to write out after the run
+            if OperationType::from(ik.op) == OperationType::PutWithExpiry {
+                reached_key_end = false;
+                break;
+            }
+
+            self.stats.num_input_records += 1;
+
+            match OperationType::from(ik.op) {
+                OperationType::Merge => {
+                    keys.push(self.input.key().to_vec());
+                    operands.push(self.input.value().to_vec());
+                    self.input.next();
+                }
+                OperationType::Put => {
+                    base = Some(Some(self.input.value().to_vec()));
+                    self.input.next();
+                    break;
+                }
+                OperationType::BlobIndex => {
+                    match self.read_blob(ik.user_key) {
+                        Ok(value) => base = Some(Some(value)),
+                        Err(e) => {
+                            self.status = Some(e);
+                            return;
+                        }
+                    }
+                    self.input.next();
+                    break;
+                }
+                OperationType::Delete | OperationType::SingleDelete => {
+                    base = Some(None);
+                    self.input.next();
+                    break;
+                }
+                OperationType::PutWithExpiry | OperationType::RangeDelete | OperationType::Max => {
+                    unreachable!()
+                }
+            }
+        }
+
+        let oldest_first: Vec<&[u8]> = operands.iter().rev().map(|o| o.as_slice()).collect();
+
+        self.merge_out_idx = 0;
+
+        if base.is_some() || (reached_key_end && self.bottommost_level) {
+            let base = base.flatten();
+            match full_merge(
+                Some(merge_operator),
+                &self.current_user_key,
+                base.as_deref(),
+                &oldest_first,
+            ) {
+                Ok(v) => {
+                    self.key_buf
+                        .set(&self.current_user_key, newest_seq, OperationType::Put);
+                    self.merge_out.push((self.key_buf.as_slice().to_vec(), v));
+                    self.set_last_version(stripe, true);
+                    self.stats.num_merged_operands += operands.len() as u64;
+                }
+                Err(e) => self.status = Some(e),
+            }
+            return;
+        }
+
+        self.set_last_version(stripe, false);
+
+        if let Some(v) = partial_merge(merge_operator, &self.current_user_key, &oldest_first) {
+            self.key_buf
+                .set(&self.current_user_key, newest_seq, OperationType::Merge);
+            self.merge_out.push((self.key_buf.as_slice().to_vec(), v));
+            self.stats.num_merged_operands += operands.len() as u64;
+            return;
+        }
+
+        self.merge_out.extend(keys.into_iter().zip(operands));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compaction::filter::TableFileCreationReason;
+    use crate::key::comparator::DefaultComparator;
+    use crate::tests::helpers::memtable_with;
+
+    fn ctx() -> CompactionFilterContext {
+        CompactionFilterContext {
+            column_family_id: 0,
+            level: 0,
+            is_full_compaction: false,
+            is_manual_compaction: false,
+            reason: TableFileCreationReason::Flush,
+        }
+    }
+
+    fn collect<I: InternalIterator>(
+        iter: &mut CompactionIterator<'_, I>,
+    ) -> Vec<(String, u64, OperationType, String)> {
+        let mut out = Vec::new();
+        iter.seek_to_first();
+        while iter.valid() {
+            let ik = InternalKeyRef::from(iter.key());
+            out.push((
+                String::from_utf8_lossy(ik.user_key).to_string(),
+                ik.seq_no,
+                OperationType::from(ik.op),
+                String::from_utf8_lossy(iter.value()).to_string(),
+            ));
+            iter.next();
+        }
+        out
+    }
+
+    struct ExpireFilter;
+
+    impl CompactionFilter for ExpireFilter {
+        fn name(&self) -> &str {
+            "ExpireFilter"
+        }
+
+        fn filter(
+            &self,
+            _ctx: &CompactionFilterContext,
+            user_key: &[u8],
+            _value_type: FilterValueType,
+            value: &[u8],
+        ) -> FilterDecision {
+            if value == b"expired" {
+                FilterDecision::Remove
+            } else if user_key.starts_with(b"rewrite") {
+                FilterDecision::ChangeValue(b"rewritten".to_vec())
+            } else if user_key == b"skip" {
+                FilterDecision::RemoveAndSkipUntil(b"skiq".to_vec())
+            } else {
+                FilterDecision::Keep
+            }
+        }
+    }
+
+    #[test]
+    fn drops_versions_hidden_within_a_stripe() {
+        let mem = memtable_with(&[
+            (b"k", 9, OperationType::Put, b"v9"),
+            (b"k", 7, OperationType::Put, b"v7"),
+            (b"k", 5, OperationType::Put, b"v5"),
+            (b"k", 3, OperationType::Put, b"v3"),
+            (b"k", 1, OperationType::Put, b"v1"),
+        ]);
+
+        let comp = DefaultComparator {};
+        let snapshots = [4, 8];
+        let mut iter = CompactionIterator::new(mem.iter(), &comp, &snapshots, None, ctx(), false);
+
+        let seqs: Vec<u64> = collect(&mut iter).iter().map(|e| e.1).collect();
+        assert_eq!(seqs, vec![9, 7, 3]);
+        assert_eq!(iter.stats().num_dropped_hidden, 2);
+    }
+
+    #[test]
+    fn filter_decisions() {
+        let mem = memtable_with(&[
+            (b"a", 1, OperationType::Put, b"keep"),
+            (b"b", 2, OperationType::Put, b"expired"),
+            (b"rewrite", 3, OperationType::Put, b"old"),
+            (b"skip", 4, OperationType::Put, b"x"),
+            (b"skip1", 5, OperationType::Put, b"x"),
+            (b"skip2", 6, OperationType::Put, b"x"),
+            (b"z", 7, OperationType::Put, b"last"),
+        ]);
+
+        let comp = DefaultComparator {};
+        let filter = ExpireFilter;
+        let mut iter = CompactionIterator::new(mem.iter(), &comp, &[], Some(&filter), ctx(), false);
+
+        let out = collect(&mut iter);
+        assert_eq!(
+            out,
+            vec![
+                ("a".into(), 1, OperationType::Put, "keep".into()),
+                // Removed entries become tombstones so older versions in lower levels stay hidden
+                ("b".into(), 2, OperationType::Delete, "".into()),
+                ("rewrite".into(), 3, OperationType::Put, "rewritten".into()),
+                ("z".into(), 7, OperationType::Put, "last".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn filter_never_sees_snapshot_visible_versions() {
+        let mem = memtable_with(&[
+            (b"b", 10, OperationType::Put, b"expired"),
+            (b"b", 4, OperationType::Put, b"expired"),
+            (b"c", 3, OperationType::Put, b"expired"),
+        ]);
+
+        let comp = DefaultComparator {};
+        let filter = ExpireFilter;
+        let snapshots = [5];
+        let mut iter =
+            CompactionIterator::new(mem.iter(), &comp, &snapshots, Some(&filter), ctx(), false);
+
+        let out = collect(&mut iter);
+        assert_eq!(
+            out,
+            vec![
+                ("b".into(), 10, OperationType::Delete, "".into()),
+                ("b".into(), 4, OperationType::Put, "expired".into()),
+                ("c".into(), 3, OperationType::Put, "expired".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn bottommost_drops_tombstones() {
+        let mem = memtable_with(&[
+            (b"a", 6, OperationType::Delete, b""),
+            (b"a", 5, OperationType::Put, b"v"),
+            (b"b", 4, OperationType::Delete, b""),
+            (b"b", 2, OperationType::Put, b"v"),
+        ]);
+
+        let comp = DefaultComparator {};
+        let snapshots = [3];
+        let mut iter = CompactionIterator::new(mem.iter(), &comp, &snapshots, None, ctx(), true);
+
+        // Both tombstones are in the tip stripe, not the earliest one, so they are kept. a@5 shares a stripe with its tombstone
+        // and is dropped, b@2 is visible to snapshot 3 and is kept.
+        let out = collect(&mut iter);
+        assert_eq!(
+            out,
+            vec![
+                ("a".into(), 6, OperationType::Delete, "".into()),
+                ("b".into(), 4, OperationType::Delete, "".into()),
+                ("b".into(), 2, OperationType::Put, "v".into()),
+            ]
+        );
+
+        // Without snapshots everything collapses
+        let mut iter = CompactionIterator::new(mem.iter(), &comp, &[], None, ctx(), true);
+        assert!(collect(&mut iter).is_empty());
+        assert_eq!(iter.stats().num_dropped_tombstones, 2);
+    }
+
+    struct Append;
+
+    impl MergeOperator for Append {
+        fn name(&self) -> &str {
+            "Append"
+        }
+
+        fn full_merge(
+            &self,
+            _key: &[u8],
+            existing_value: Option<&[u8]>,
+            operands: &[&[u8]],
+        ) -> Option<Vec<u8>> {
+            let mut out = existing_value.map(|v| v.to_vec()).unwrap_or_default();
+            for op in operands {
+                if !out.is_empty() {
+                    out.push(b',');
+                }
+                out.extend_from_slice(op);
+            }
+            Some(out)
+        }
+
+        fn partial_merge(&self, _key: &[u8], left: &[u8], right: &[u8]) -> Option<Vec<u8>> {
+            let mut out = left.to_vec();
+            out.push(b',');
+            out.extend_from_slice(right);
+            Some(out)
+        }
+    }
+
+    #[test]
+    fn merges_operands_within_stripes() {
+        let mem = memtable_with(&[
+            (b"a", 9, OperationType::Merge, b"4"),
+            (b"a", 8, OperationType::Merge, b"3"),
+            (b"a", 6, OperationType::Merge, b"2"),
+            (b"a", 5, OperationType::Put, b"1"),
+            (b"a", 4, OperationType::Put, b"0"),
+            (b"b", 3, OperationType::Merge, b"y"),
+            (b"b", 2, OperationType::Merge, b"x"),
+        ]);
+
+        let comp = DefaultComparator {};
+        let op = Append;
+        let snapshots = [7];
+        let mut iter = CompactionIterator::new(mem.iter(), &comp, &snapshots, None, ctx(), false)
+            .with_merge_operator(&op);
+
+        let out = collect(&mut iter);
+        assert_eq!(
+            out,
+            vec![
+                // Tip stripe has no base - partial merged into one operand
+                ("a".into(), 9, OperationType::Merge, "3,4".into()),
+                // Snapshot 7 stripe reaches the base - full merged, a@4 is hidden
+                ("a".into(), 6, OperationType::Put, "1,2".into()),
+                ("b".into(), 3, OperationType::Merge, "x,y".into()),
+            ]
+        );
+        assert!(iter.status().is_none());
+
+        // At the bottommost level the start of the key's history is known so operands without a base are fully merged
+        let mut iter = CompactionIterator::new(mem.iter(), &comp, &[], None, ctx(), true)
+            .with_merge_operator(&op);
+        let out = collect(&mut iter);
+        assert_eq!(
+            out,
+            vec![
+                ("a".into(), 9, OperationType::Put, "1,2,3,4".into()),
+                ("b".into(), 3, OperationType::Put, "x,y".into()),
+            ]
+        );
+    }
+}
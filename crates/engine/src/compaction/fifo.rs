@@ -0,0 +1,322 @@
+//
+//
+//
+// FIFO Compaction
+//
+// FIFO is a compaction style for time-series style data where keys are never updated and old data simply ages out.
+// Every file lives in L0 and the picker never rewrites data to a lower level. Instead it:
+//
+// 1. Deletes files whose oldest ancestor time (from table properties) is older than the TTL
+// 2. Deletes the oldest files once the total size exceeds max_table_files_size
+// 3. Optionally merges small L0 files together (intra-L0) so the file count, and therefore read amplification, stays bounded
+//
+// The checks run in that order and the first one which finds work wins. TTL and size picks are deletion compactions - the input files
+// are dropped from the Version without being read.
+//
+// L0 files are ordered newest -> oldest so "oldest first" means walking the level from the back.
+
+use std::sync::Arc;
+
+use crate::compaction::{Compaction, CompactionInputFiles, CompactionReason};
+use crate::options::CompactionOptionsFifo;
+use crate::versioning::file_version::{FileMetaData, Version};
+
+pub(crate) struct FifoCompactionPicker<'a> {
+    opts: &'a CompactionOptionsFifo,
+}
+
+impl<'a> FifoCompactionPicker<'a> {
+    pub(crate) fn new(opts: &'a CompactionOptionsFifo) -> Self {
+        Self { opts }
+    }
+
+    /// Returns true if the picker may have work for the given version. Cheap enough to be called after every flush.
+    pub(crate) fn needs_compaction(&self, version: &Version, now: u64) -> bool {
+        let files = version.level_files(0);
+
+        if self.opts.ttl > 0
+            && files
+                .last()
+                .is_some_and(|oldest| Self::is_expired(oldest, now, self.opts.ttl))
+        {
+            return true;
+        }
+
+        if version.level_size(0) > self.opts.max_table_files_size {
+            return true;
+        }
+
+        self.opts.allow_compaction && files.len() >= self.opts.intra_l0_file_trigger
+    }
+
+    /// Picks the next FIFO compaction for the version. `now` is the current unix time in seconds.
+    pub(crate) fn pick_compaction(&self, version: &Version, now: u64) -> Option<Compaction> {
+        debug_assert!(
+            (1..crate::versioning::file_version::NUM_LEVELS)
+                .all(|l| version.num_level_files(l) == 0),
+            "FIFO compaction expects all files in L0"
+        );
+
+        let files = version.level_files(0);
+        if files.is_empty() {
+            return None;
+        }
+
+        // Only one FIFO compaction runs at a time. Deleting files out from under a running intra-L0 merge would leave the
+        // merge with inputs which no longer exist in the Version.
+        if files.iter().any(|f| f.being_compacted()) {
+            return None;
+        }
+
+        if let Some(c) = self.pick_ttl_compaction(files, now) {
+            return Some(c);
+        }
+
+        if let Some(c) = self.pick_size_compaction(version, files) {
+            return Some(c);
+        }
+
+        self.pick_intra_l0_compaction(files)
+    }
+
+    #[inline]
+    fn is_expired(file: &FileMetaData, now: u64, ttl: u64) -> bool {
+        let created = file.properties.oldest_ancestor_time();
+        // Files without any time information can never be judged expired
+        created != 0 && created < now.saturating_sub(ttl)
+    }
+
+    fn pick_ttl_compaction(&self, files: &[Arc<FileMetaData>], now: u64) -> Option<Compaction> {
+        if self.opts.ttl == 0 || now < self.opts.ttl {
+            return None;
+        }
+
+        let mut inputs = Vec::new();
+
+        // Walk oldest -> newest and stop at the first file which has not expired. Files are written in time order so nothing newer
+        // than it can have expired either.
+        for f in files.iter().rev() {
+            if !Self::is_expired(f, now, self.opts.ttl) {
+                break;
+            }
+            inputs.push(f.clone());
+        }
+
+        if inputs.is_empty() {
+            return None;
+        }
+
+        Some(Compaction::new(
+            vec![CompactionInputFiles {
+                level: 0,
+                files: inputs,
+            }],
+            0,
+            CompactionReason::FifoTtl,
+            true,
+        ))
+    }
+
+    fn pick_size_compaction(
+        &self,
+        version: &Version,
+        files: &[Arc<FileMetaData>],
+    ) -> Option<Compaction> {
+        let mut total = version.level_size(0);
+        if total <= self.opts.max_table_files_size {
+            return None;
+        }
+
+        let mut inputs = Vec::new();
+
+        for f in files.iter().rev() {
+            if total <= self.opts.max_table_files_size {
+                break;
+            }
+            total -= f.file_size;
+            inputs.push(f.clone());
+        }
+
+        Some(Compaction::new(
+            vec![CompactionInputFiles {
+                level: 0,
+                files: inputs,
+            }],
+            0,
+            CompactionReason::FifoMaxSize,
+            true,
+        ))
+    }
+
+    fn pick_intra_l0_compaction(&self, files: &[Arc<FileMetaData>]) -> Option<Compaction> {
+        if !self.opts.allow_compaction || files.len() < self.opts.intra_l0_file_trigger {
+            return None;
+        }
+
+        let mut inputs = Vec::new();
+        let mut bytes = 0u64;
+
+        // Merge the newest files first - they are the smallest (freshly flushed) and the cheapest to rewrite. The inputs must be a
+        // contiguous run so the output can take their place in the L0 ordering.
+        for f in files {
+            if bytes + f.file_size > self.opts.max_compaction_bytes {
+                break;
+            }
+            bytes += f.file_size;
+            inputs.push(f.clone());
+        }
+
+        if inputs.len() < self.opts.intra_l0_file_trigger {
+            return None;
+        }
+
+        Some(Compaction::new(
+            vec![CompactionInputFiles {
+                level: 0,
+                files: inputs,
+            }],
+            0,
+            CompactionReason::FifoReduceNumFiles,
+            false,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key::comparator::InternalKeyComparator;
+    use crate::key::internal_key::{OperationType, encode_trailer};
+    use crate::table::properties::TableProperties;
+
+    fn file(number: u64, seq: u64, size: u64, creation_time: u64) -> Arc<FileMetaData> {
+        let mut key = format!("key{number}").into_bytes();
+        key.extend_from_slice(&encode_trailer(seq, OperationType::Put));
+
+        Arc::new(FileMetaData::new(
+            number,
+            size,
+            key.clone(),
+            key,
+            seq,
+            seq,
+            TableProperties {
+                creation_time,
+                ..Default::default()
+            },
+        ))
+    }
+
+    fn l0(files: Vec<Arc<FileMetaData>>) -> Version {
+        Version::from_levels(vec![files], InternalKeyComparator::new())
+    }
+
+    fn numbers(c: &Compaction) -> Vec<u64> {
+        c.inputs()[0].files.iter().map(|f| f.number).collect()
+    }
+
+    #[test]
+    fn ttl_deletes_expired_files_oldest_first() {
+        let opts = CompactionOptionsFifo {
+            ttl: 100,
+            ..Default::default()
+        };
+        let picker = FifoCompactionPicker::new(&opts);
+
+        // File 1 is the oldest (lowest seq) and was created first
+        let version = l0(vec![
+            file(1, 10, 10, 1000),
+            file(2, 20, 10, 1050),
+            file(3, 30, 10, 1150),
+        ]);
+
+        // now - ttl = 1100 so files 1 and 2 have expired
+        let c = picker.pick_compaction(&version, 1200).unwrap();
+        assert_eq!(c.reason(), CompactionReason::FifoTtl);
+        assert!(c.is_deletion_compaction());
+        assert_eq!(numbers(&c), vec![1, 2]);
+
+        // Nothing has expired yet
+        drop(c);
+        assert!(picker.pick_compaction(&version, 1060).is_none());
+    }
+
+    #[test]
+    fn ttl_skips_files_without_time() {
+        let opts = CompactionOptionsFifo {
+            ttl: 10,
+            ..Default::default()
+        };
+        let picker = FifoCompactionPicker::new(&opts);
+
+        let version = l0(vec![file(1, 10, 10, 0)]);
+        assert!(!picker.needs_compaction(&version, 1000));
+        assert!(picker.pick_compaction(&version, 1000).is_none());
+    }
+
+    #[test]
+    fn size_cap_deletes_oldest_until_under_cap() {
+        let opts = CompactionOptionsFifo {
+            max_table_files_size: 250,
+            ..Default::default()
+        };
+        let picker = FifoCompactionPicker::new(&opts);
+
+        let version = l0(vec![
+            file(4, 40, 100, 0),
+            file(1, 10, 100, 0),
+            file(3, 30, 100, 0),
+            file(2, 20, 100, 0),
+        ]);
+
+        assert!(picker.needs_compaction(&version, 0));
+
+        let c = picker.pick_compaction(&version, 0).unwrap();
+        assert_eq!(c.reason(), CompactionReason::FifoMaxSize);
+        // 400 bytes total, cap of 250 -> drop the two oldest
+        assert_eq!(numbers(&c), vec![1, 2]);
+        assert_eq!(c.input_size(), 200);
+    }
+
+    #[test]
+    fn intra_l0_merges_newest_files() {
+        let opts = CompactionOptionsFifo {
+            allow_compaction: true,
+            intra_l0_file_trigger: 3,
+            max_compaction_bytes: 35,
+            ..Default::default()
+        };
+        let picker = FifoCompactionPicker::new(&opts);
+
+        let version = l0(vec![
+            file(1, 10, 10, 0),
+            file(2, 20, 10, 0),
+            file(3, 30, 10, 0),
+            file(4, 40, 10, 0),
+        ]);
+
+        let c = picker.pick_compaction(&version, 0).unwrap();
+        assert_eq!(c.reason(), CompactionReason::FifoReduceNumFiles);
+        assert!(!c.is_deletion_compaction());
+        assert_eq!(c.output_level(), 0);
+        // Bounded by max_compaction_bytes
+        assert_eq!(numbers(&c), vec![4, 3, 2]);
+
+        // Files stay marked while the compaction is alive so nothing else is picked
+        assert!(picker.pick_compaction(&version, 0).is_none());
+
+        drop(c);
+        assert!(version.level_files(0).iter().all(|f| !f.being_compacted()));
+        assert!(picker.pick_compaction(&version, 0).is_some());
+    }
+
+    #[test]
+    fn no_compaction_under_limits() {
+        let opts = CompactionOptionsFifo::default();
+        let picker = FifoCompactionPicker::new(&opts);
+
+        let version = l0(vec![file(1, 10, 10, 5), file(2, 20, 10, 6)]);
+        assert!(!picker.needs_compaction(&version, 100));
+        assert!(picker.pick_compaction(&version, 100).is_none());
+    }
+}
@@ -0,0 +1,82 @@
+//
+//
+//
+// Compaction Filter
+//
+// A CompactionFilter lets the application inspect every user key/value which is rewritten by a flush or compaction and decide to keep it,
+// drop it, or rewrite the value. It is the mechanism for lazily expiring application level data without issuing explicit deletes.
+//
+// The filter is registered per column family (ColumnFamilyOptions::compaction_filter) and is called by the CompactionIterator.
+//
+// Snapshot rules:
+//
+// The filter is only consulted for the newest version of a user key and only when that version is not visible to any live snapshot.
+// Versions which a snapshot can still read are always passed through unchanged, so a reader holding a snapshot never sees a filter decision.
+//
+// Decisions:
+//
+// Keep                    - Entry is written unchanged
+// Remove                  - Entry is replaced by a Delete tombstone at the same sequence number so older versions stay hidden
+// ChangeValue(v)          - Entry is written with the new value
+// RemoveAndSkipUntil(k)   - Entry, and every following user key < k, are dropped without reaching the filter.
+//                           No tombstones are written - older versions of the skipped keys in lower levels may become visible again.
+//                           Entries which are visible to a snapshot are still kept.
+//
+// An ExpiringValue is passed with its expiry still encoded (see ttl.rs) and a ChangeValue of it must keep that encoding.
+
+use std::fmt::Display;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FilterDecision {
+    Keep,
+    Remove,
+    ChangeValue(Vec<u8>),
+    RemoveAndSkipUntil(Vec<u8>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterValueType {
+    Value,
+    MergeOperand,
+    // Value of a Put with an expiry
+    ExpiringValue,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TableFileCreationReason {
+    Flush,
+    Compaction,
+}
+
+impl Display for TableFileCreationReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TableFileCreationReason::Flush => write!(f, "Flush"),
+            TableFileCreationReason::Compaction => write!(f, "Compaction"),
+        }
+    }
+}
+
+/// Context handed to the filter for every call. It describes the job which is running the filter.
+#[derive(Debug, Clone, Copy)]
+pub struct CompactionFilterContext {
+    pub column_family_id: u32,
+    // Level the output is written to
+    pub level: usize,
+    // True if the job reads every file in the column family
+    pub is_full_compaction: bool,
+    pub is_manual_compaction: bool,
+    pub reason: TableFileCreationReason,
+}
+
+pub trait CompactionFilter: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn filter(
+        &self,
+        ctx: &CompactionFilterContext,
+        user_key: &[u8],
+        value_type: FilterValueType,
+        value: &[u8],
+    ) -> FilterDecision;
+}
@@ -0,0 +1,115 @@
+pub(crate) mod compaction_iter;
+pub(crate) mod fifo;
+pub(crate) mod filter;
+pub(crate) mod output;
+
+use std::fmt::Display;
+use std::sync::Arc;
+
+use crate::versioning::file_version::FileMetaData;
+
+//
+//
+//
+// A Compaction is the unit of work produced by a compaction picker. It describes the input files (per level), where the output goes
+// and why the work was picked. Pickers only read a Version, they never mutate it - the result of running a compaction is applied as a
+// new Version.
+//
+// While a Compaction is alive its input files are marked as being compacted so that concurrent pickers skip them. The mark is
+// released when the Compaction is dropped.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CompactionReason {
+    Unknown,
+    // FIFO: files older than the configured TTL
+    FifoTtl,
+    // FIFO: total size of all files exceeded max_table_files_size
+    FifoMaxSize,
+    // FIFO: intra-L0 merge to bound the number of files
+    FifoReduceNumFiles,
+}
+
+impl Display for CompactionReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CompactionReason::Unknown => write!(f, "Unknown"),
+            CompactionReason::FifoTtl => write!(f, "FifoTtl"),
+            CompactionReason::FifoMaxSize => write!(f, "FifoMaxSize"),
+            CompactionReason::FifoReduceNumFiles => write!(f, "FifoReduceNumFiles"),
+        }
+    }
+}
+
+pub(crate) struct CompactionInputFiles {
+    pub(crate) level: usize,
+    pub(crate) files: Vec<Arc<FileMetaData>>,
+}
+
+pub(crate) struct Compaction {
+    inputs: Vec<CompactionInputFiles>,
+    output_level: usize,
+    reason: CompactionReason,
+    // A deletion compaction drops its inputs without reading them or producing any output
+    deletion_compaction: bool,
+}
+
+impl Compaction {
+    pub(crate) fn new(
+        inputs: Vec<CompactionInputFiles>,
+        output_level: usize,
+        reason: CompactionReason,
+        deletion_compaction: bool,
+    ) -> Self {
+        for input in &inputs {
+            for f in &input.files {
+                debug_assert!(!f.being_compacted());
+                f.set_being_compacted(true);
+            }
+        }
+
+        Self {
+            inputs,
+            output_level,
+            reason,
+            deletion_compaction,
+        }
+    }
+
+    pub(crate) fn inputs(&self) -> &[CompactionInputFiles] {
+        &self.inputs
+    }
+
+    pub(crate) fn output_level(&self) -> usize {
+        self.output_level
+    }
+
+    pub(crate) fn reason(&self) -> CompactionReason {
+        self.reason
+    }
+
+    pub(crate) fn is_deletion_compaction(&self) -> bool {
+        self.deletion_compaction
+    }
+
+    pub(crate) fn num_input_files(&self) -> usize {
+        self.inputs.iter().map(|i| i.files.len()).sum()
+    }
+
+    pub(crate) fn input_size(&self) -> u64 {
+        self.inputs
+            .iter()
+            .flat_map(|i| i.files.iter())
+            .map(|f| f.file_size)
+            .sum()
+    }
+}
+
+impl Drop for Compaction {
+    fn drop(&mut self) {
+        for input in &self.inputs {
+            for f in &input.files {
+                f.set_being_compacted(false);
+            }
+        }
+    }
+}
@@ -0,0 +1,231 @@
+//
+//
+//
+// Compaction Output
+//
+// Writes what a CompactionIterator returns into table files at the output level. Given a target file size, a table is cut once it
+// reaches it and the next file starts at the next user key - every version of a user key stays in one file. The range tombstones of the
+// iterator are truncated to the user keys each file owns, [first user key, first user key of the next file), so the files don't overlap.
+// With enable_blob_files every Put whose value is at least min_blob_size has its value appended to a new blob file and goes into the
+// table as a BlobIndex entry pointing at it. Blob references the iterator carries over are written unchanged and counted per blob file
+// - after a full compaction they are every reference left to the blob files, which is how their garbage is found. The tables keep the
+// samples of `seqno_to_time` covering their seq nos and are stamped with the OutputTimes of the job.
+
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::blob::blob_file::{BlobFile, BlobFileBuilder, blob_record_size};
+use crate::blob::blob_index::BlobIndex;
+use crate::compaction::compaction_iter::CompactionIterator;
+use crate::db::filename::{blob_file, table_file};
+use crate::error::Result;
+use crate::iterator::internal_iterator::InternalIterator;
+use crate::key::comparator::InternalKeyComparator;
+use crate::key::internal_key::{InternalKeyRef, OperationType, encode_trailer};
+use crate::options::ColumnFamilyOptions;
+use crate::table::table_builder::TableBuilder;
+use crate::table::table_reader::Table;
+use crate::utils::random_access_file::RandomAccessFile;
+use crate::versioning::file_version::{BlobFileMetaData, FileMetaData, FileNumber};
+use crate::versioning::seqno_to_time::SeqnoToTimeMapping;
+
+/// Blob count and bytes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct BlobCount {
+    pub(crate) count: u64,
+    pub(crate) bytes: u64,
+}
+
+/// Unix times (secs) stamped into the properties of the tables written (see table/properties.rs).
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct OutputTimes {
+    // When the oldest data of the job was first written - the flush itself, or the oldest input of a compaction
+    pub(crate) creation_time: u64,
+    // When the job runs
+    pub(crate) file_creation_time: u64,
+}
+
+/// The files written for a flush or compaction, opened. Nothing is written for an output without entries.
+#[derive(Default)]
+pub(crate) struct CompactionOutput {
+    // In key order
+    pub(crate) tables: Vec<Arc<FileMetaData>>,
+    pub(crate) blob_file: Option<Arc<BlobFileMetaData>>,
+    // References to blob files which existed before, by blob file
+    pub(crate) blob_references: BTreeMap<FileNumber, BlobCount>,
+}
+
+/// Writes the output of `iter` as table files (and a blob file `blob_number`) of the DB in `db_path`, numbered by `new_file_number`.
+/// Without `target_file_size` everything goes into one table. The files written are deleted again when it fails.
+pub(crate) fn write_output<I: InternalIterator>(
+    iter: &mut CompactionIterator<'_, I>,
+    db_path: &Path,
+    new_file_number: &mut dyn FnMut() -> FileNumber,
+    options: &ColumnFamilyOptions,
+    target_file_size: Option<u64>,
+    seqno_to_time: Arc<SeqnoToTimeMapping>,
+    times: OutputTimes,
+) -> Result<CompactionOutput> {
+    let mut output = CompactionOutput::default();
+    let blob_number = new_file_number();
+    let mut writer = OutputWriter {
+        db_path,
+        new_file_number,
+        options,
+        seqno_to_time,
+        times,
+    };
+    writer
+        .write(iter, blob_number, target_file_size, &mut output)
+        .inspect_err(|_| {
+            output.remove_files(db_path);
+            let _ = fs::remove_file(blob_file(db_path, blob_number));
+        })?;
+    Ok(output)
+}
+
+struct OutputWriter<'a> {
+    db_path: &'a Path,
+    new_file_number: &'a mut dyn FnMut() -> FileNumber,
+    options: &'a ColumnFamilyOptions,
+    seqno_to_time: Arc<SeqnoToTimeMapping>,
+    times: OutputTimes,
+}
+
+impl OutputWriter<'_> {
+    fn write<I: InternalIterator>(
+        &mut self,
+        iter: &mut CompactionIterator<'_, I>,
+        blob_number: FileNumber,
+        target_file_size: Option<u64>,
+        output: &mut CompactionOutput,
+    ) -> Result<()> {
+        let user_comparator = self.options.comparator.as_ref();
+        let mut builder = self.table_builder();
+        let mut blob_builder = BlobFileBuilder::new(blob_number);
+        // The first user key of the current table (none for the first) and the last one added to it
+        let mut lower: Option<Vec<u8>> = None;
+        let mut last_user_key: Vec<u8> = Vec::new();
+
+        iter.seek_to_first();
+        while iter.valid() {
+            let ikey = InternalKeyRef::from(iter.key());
+            if builder.num_entries() > 0
+                && target_file_size
+                    .is_some_and(|target| builder.file_size_estimate() as u64 >= target)
+                && user_comparator.compare_without_timestamp(
+                    &last_user_key,
+                    true,
+                    ikey.user_key,
+                    true,
+                ) != Ordering::Equal
+            {
+                let full = std::mem::replace(&mut builder, self.table_builder());
+                self.finish_table(iter, full, lower.as_deref(), Some(ikey.user_key), output)?;
+                lower = Some(ikey.user_key.to_vec());
+            }
+            last_user_key.clear();
+            last_user_key.extend_from_slice(ikey.user_key);
+
+            match OperationType::from(ikey.op) {
+                OperationType::Put
+                    if self.options.enable_blob_files
+                        && iter.value().len() >= self.options.min_blob_size =>
+                {
+                    let index = blob_builder.add(ikey.user_key, iter.value());
+                    let trailer = encode_trailer(ikey.seq_no, OperationType::BlobIndex);
+                    builder.add(&[ikey.user_key, &trailer].concat(), &index.encode());
+                }
+                OperationType::BlobIndex => {
+                    let index = BlobIndex::decode(iter.value())?;
+                    let references = output.blob_references.entry(index.file_number).or_default();
+                    references.count += 1;
+                    references.bytes += blob_record_size(ikey.user_key, index.size);
+                    builder.add(iter.key(), iter.value());
+                }
+                _ => builder.add(iter.key(), iter.value()),
+            }
+            iter.next();
+        }
+        if let Some(e) = iter.status() {
+            return Err(e.clone());
+        }
+        self.finish_table(iter, builder, lower.as_deref(), None, output)?;
+
+        if !blob_builder.is_empty() {
+            let built = blob_builder.finish();
+            let path = blob_file(self.db_path, blob_number);
+            write_file(&path, &built.data)?;
+            let meta = BlobFileMetaData::new(blob_number, built.blob_count, built.blob_bytes);
+            meta.set_blob_reader(BlobFile::open(blob_number, RandomAccessFile::open(&path)?)?);
+            output.blob_file = Some(Arc::new(meta));
+        }
+        Ok(())
+    }
+
+    fn table_builder(&self) -> TableBuilder {
+        TableBuilder::default()
+            .with_comparator(InternalKeyComparator::with_user_comparator(Arc::clone(
+                &self.options.comparator,
+            )))
+            .with_seqno_to_time(Arc::clone(&self.seqno_to_time))
+            .with_creation_time(self.times.creation_time, self.times.file_creation_time)
+    }
+
+    // Adds the range tombstones of the user keys [lower, upper) and writes the table, unless it is left empty
+    fn finish_table<I: InternalIterator>(
+        &mut self,
+        iter: &CompactionIterator<'_, I>,
+        mut builder: TableBuilder,
+        lower: Option<&[u8]>,
+        upper: Option<&[u8]>,
+        output: &mut CompactionOutput,
+    ) -> Result<()> {
+        let tombstones = iter.output_range_tombstones(lower, upper);
+        for tombstone in &tombstones {
+            builder.add_range_tombstone(&tombstone.start, &tombstone.end, tombstone.seq);
+        }
+        if builder.num_entries() == 0 && tombstones.is_empty() {
+            return Ok(());
+        }
+
+        let number = (self.new_file_number)();
+        let table = builder.finish();
+        let path = table_file(self.db_path, number);
+        write_file(&path, &table.data)?;
+        let meta = Arc::new(table.file_meta(number));
+        // Added before the file is opened, so a failure deletes it with the rest
+        output.tables.push(Arc::clone(&meta));
+        meta.set_table_reader(Table::open(RandomAccessFile::open(&path)?)?);
+        Ok(())
+    }
+}
+
+impl CompactionOutput {
+    /// Deletes the files written - for a flush or compaction which failed after writing them.
+    pub(crate) fn remove_files(&self, db_path: &Path) {
+        for table in &self.tables {
+            let _ = fs::remove_file(table_file(db_path, table.number));
+        }
+        if let Some(blob) = &self.blob_file {
+            let _ = fs::remove_file(blob_file(db_path, blob.number));
+        }
+    }
+}
+
+// A file which could not be written completely is deleted
+fn write_file(path: &Path, data: &[u8]) -> Result<()> {
+    let write = || -> Result<()> {
+        let mut file = File::create(path)?;
+        file.write_all(data)?;
+        file.sync_all()?;
+        Ok(())
+    };
+    write().inspect_err(|_| {
+        let _ = fs::remove_file(path);
+    })
+}
@@ -1,33 +1,1516 @@
-use crate::db::write_batch::Batch;
+use crate::column_family::cf::{
+    ColumnFamilyData, ColumnFamilyDescriptor, ColumnFamilyHandle, DEFAULT_COLUMN_FAMILY_ID,
+};
+use crate::compaction::Compaction;
+use crate::compaction::compaction_iter::CompactionIterator;
+use crate::compaction::fifo::FifoCompactionPicker;
+use crate::compaction::filter::{CompactionFilterContext, TableFileCreationReason};
+use crate::compaction::output::{CompactionOutput, OutputTimes, write_output};
+use crate::db::external_file_ingestion::{
+    first_overlapping_level, open_external_files, pick_level,
+};
+use crate::db::filename;
+use crate::db::read_path::{
+    GetSource, get_merge_operands, get_with_range_del, get_with_timestamp, get_write_sequence,
+};
+use crate::db::wal::{self, LogsWithPrep, RecoveredPrepared, Wal};
+use crate::db::write_batch::{Batch, BatchOpType, BatchRecord};
+use crate::db::write_callback::WriteCallback;
+use crate::error;
+use crate::iterator::bounds::IterBounds;
+use crate::iterator::db_iter::{DBIter, DBIterBuilder};
+use crate::iterator::internal_iterator::InternalIterator;
+use crate::iterator::iter_alloc::{IterAllocPool, PooledIter};
+use crate::iterator::merge_iterator::MergingIterator;
+use crate::key::internal_key::OperationType;
+use crate::key::lookup_key::LookUpInternalKey;
+use crate::memtable::memtable::{Memtable, Mutable};
+use crate::multi_get::{MultiGetSource, multi_get_with_range_del, multi_get_with_timestamp};
+use crate::options::{
+    ColumnFamilyOptions, CompactionStyle, DbOptions, IngestExternalFileOptions, ReadOptions,
+};
+use crate::utils::clock::Clock;
+use crate::versioning::file_version::{
+    BlobFileMetaData, FileMetaData, FileNumber, NUM_LEVELS, Version,
+};
+use crate::versioning::seqno_to_time::{MAX_SAMPLES, SeqnoToTimeMapping};
+use crate::versioning::snapshot::{Snapshot, SnapshotList};
+use crate::versioning::version_edit::{BlobFileCount, NewFile, VersionEdit};
+use crate::versioning::version_set::VersionSet;
 
-use super::write_thread::WriteThread;
+use super::write_thread::{WriteGroup, WriteThread};
+use super::writer::Writer;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fs;
 use std::marker::PhantomData;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 
 pub(crate) struct DbImpl {
     _p: PhantomData<()>,
     write_thread: WriteThread,
+    // Seq no of the newest write visible to readers - published by the write leader once a group is in the memtables
+    last_sequence: AtomicU64,
+    snapshots: Arc<SnapshotList>,
+    // Column families and the MANIFEST - held while a column family is created or dropped
+    versions: Mutex<VersionSet>,
+    // The live WAL (none for an in-memory DB) - only written with the versions lock held
+    wal: Mutex<Option<Wal>>,
+    // WALs with prepared transactions which are not decided yet
+    logs_with_prep: Mutex<LogsWithPrep>,
+    // Undecided prepared transactions found by recovery, until the application takes them
+    recovered_prepared: Mutex<Vec<RecoveredPrepared>>,
+    // Nothing is deleted from the DB directory while this is above 0 (see disable_file_deletions)
+    file_deletions_disabled: AtomicUsize,
+    clock: Arc<dyn Clock>,
+    // Samples of the newest seq no with the time, copied on write - superversions and flushes share a snapshot of it
+    seqno_to_time: Mutex<Arc<SeqnoToTimeMapping>>,
+    // Secs between samples, 0 if sampling is off
+    seqno_time_sample_period: u64,
+    // Buffers and arenas iterators are built in - None if pooling is off
+    iter_pool: Option<IterAllocPool>,
+    // Why a logged batch could not be applied - the memtables may hold part of it, so every later write fails with it
+    write_error: Mutex<Option<error::Error>>,
+}
+
+/// The files of the DB a copy of it needs besides the MANIFEST (see DbImpl::live_files).
+pub(crate) struct LiveFiles {
+    // Seq no of the newest write the files hold
+    pub(crate) sequence: u64,
+    pub(crate) tables: Vec<u64>,
+    pub(crate) blob_files: Vec<u64>,
+    // WAL number and the size to copy - the live WAL keeps growing
+    pub(crate) wals: Vec<(u64, u64)>,
 }
 
 impl DbImpl {
-    //
-    //
-    //
-    pub(crate) fn write(&self, batch: &Batch /* Other params? */) -> Result<(), ()> {
-        // What would i like?
-        //
-
-        // let writer = Writer::new(batch);
-        //
-        // self.write_thread.join(&writer);
-        //
-        // if writer.is_leader() {
-        //
-        // // We are leader
-        // // Continue with the write
-        //
-        // }
-        //
+    /// An in-memory DB with only the default column family. Nothing is persisted.
+    pub(crate) fn new() -> Self {
+        Self::with_versions(
+            VersionSet::in_memory(ColumnFamilyOptions::default()),
+            &DbOptions::default(),
+        )
+    }
+
+    /// Opens (or with create_if_missing, creates) the DB in `path`. Every existing column family must be opened. The WALs left by the
+    /// last run are replayed and a new one is started.
+    pub(crate) fn open(
+        path: &Path,
+        options: &DbOptions,
+        column_families: Vec<ColumnFamilyDescriptor>,
+    ) -> error::Result<Self> {
+        let mut versions = VersionSet::open(path, options, column_families)?;
+        let last_sequence = versions.last_sequence();
+        let recovered = wal::recover(path, versions.column_families_mut(), last_sequence)?;
+        versions.set_last_sequence(recovered.last_sequence);
+
+        // The MANIFEST must know the WAL's number before the file exists, or the next run could hand it out again
+        let number = versions.new_file_number();
+        versions.log_and_apply(VersionEdit::default())?;
+        let wal = Wal::create(path, number)?;
+
+        // The table files keep the samples of their seq nos, the rest were lost with the last run
+        let mut seqno_to_time = SeqnoToTimeMapping::default();
+        for cfd in versions.column_families().iter() {
+            for (_, f) in cfd.current_version().files() {
+                seqno_to_time.merge(&f.properties.seqno_to_time);
+            }
+        }
+        seqno_to_time.truncate(MAX_SAMPLES);
+
+        let db = Self::with_versions(versions, options);
+        *db.seqno_to_time.lock().unwrap() = Arc::new(seqno_to_time);
+        db.sample_seqno_time();
+        *db.wal.lock().unwrap() = Some(wal);
+        let mut logs_with_prep = db.logs_with_prep.lock().unwrap();
+        for prepared in &recovered.prepared {
+            logs_with_prep.add(prepared.log_number);
+        }
+        drop(logs_with_prep);
+        *db.recovered_prepared.lock().unwrap() = recovered.prepared;
+        Ok(db)
+    }
+
+    fn with_versions(versions: VersionSet, options: &DbOptions) -> Self {
+        Self {
+            _p: PhantomData,
+            write_thread: WriteThread::new(),
+            last_sequence: AtomicU64::new(versions.last_sequence()),
+            snapshots: SnapshotList::new(),
+            versions: Mutex::new(versions),
+            wal: Mutex::new(None),
+            logs_with_prep: Mutex::new(LogsWithPrep::default()),
+            recovered_prepared: Mutex::new(Vec::new()),
+            file_deletions_disabled: AtomicUsize::new(0),
+            clock: Arc::clone(&options.clock),
+            seqno_to_time: Mutex::new(Arc::default()),
+            seqno_time_sample_period: options.seqno_time_sample_period.as_secs(),
+            iter_pool: (options.max_pooled_iter_allocs > 0)
+                .then(|| IterAllocPool::new(options.max_pooled_iter_allocs)),
+            write_error: Mutex::new(None),
+        }
+    }
+
+    pub(crate) fn clock(&self) -> &Arc<dyn Clock> {
+        &self.clock
+    }
+
+    /// The current samples of the newest seq no with the time (see versioning/seqno_to_time.rs).
+    pub(crate) fn seqno_to_time_mapping(&self) -> Arc<SeqnoToTimeMapping> {
+        Arc::clone(&self.seqno_to_time.lock().unwrap())
+    }
+
+    /// Roughly when the key was last written: a unix time (secs) the newest write of the key visible to the read happened at or after.
+    /// None if the key does not exist or was written before the oldest sample.
+    pub(crate) fn approximate_write_time(
+        &self,
+        read_options: &ReadOptions,
+        column_family: &ColumnFamilyHandle,
+        user_key: &[u8],
+    ) -> error::Result<Option<u64>> {
+        let cfd = column_family.data();
+        let range_del = cfd.range_del().version();
+        let sequence = read_options.read_sequence(self.last_sequence());
+        let lookup_key = LookUpInternalKey::new(user_key, sequence, OperationType::Max);
+        let sources: [&dyn GetSource; 1] = [cfd.superversion()];
+        let seq = get_write_sequence(
+            &sources,
+            &range_del,
+            lookup_key.as_ref(),
+            cfd.options().merge_operator.as_deref(),
+            self.clock.now(),
+        )?;
+        let seqno_to_time = self.seqno_to_time_mapping();
+        Ok(seq.and_then(|seq| seqno_to_time.proximal_time_before(seq)))
+    }
+
+    /// The directory of the DB - None for an in-memory DB.
+    pub(crate) fn db_path(&self) -> Option<PathBuf> {
+        self.versions
+            .lock()
+            .unwrap()
+            .db_path()
+            .map(Path::to_path_buf)
+    }
+
+    /// Names of the column families of the DB in `path` without opening it.
+    pub(crate) fn list_column_families(path: &Path) -> error::Result<Vec<String>> {
+        VersionSet::list_column_families(path)
+    }
+
+    pub(crate) fn create_column_family(
+        &self,
+        name: &str,
+        options: ColumnFamilyOptions,
+    ) -> error::Result<ColumnFamilyHandle> {
+        let mut versions = self.versions.lock().unwrap();
+        versions.set_last_sequence(self.last_sequence());
+        let cfd = versions.create_column_family(name, options)?;
+        Ok(ColumnFamilyHandle::new(cfd))
+    }
+
+    /// Drops the column family for good. Handles to it keep working until they are dropped, which is when its data is reclaimed.
+    pub(crate) fn drop_column_family(&self, handle: &ColumnFamilyHandle) -> error::Result<()> {
+        let mut versions = self.versions.lock().unwrap();
+        versions.set_last_sequence(self.last_sequence());
+        versions.drop_column_family(handle.id())
+    }
+
+    pub(crate) fn column_family(&self, name: &str) -> Option<ColumnFamilyHandle> {
+        let versions = self.versions.lock().unwrap();
+        versions
+            .column_families()
+            .get_by_name(name)
+            .map(|cfd| ColumnFamilyHandle::new(Arc::clone(cfd)))
+    }
+
+    pub(crate) fn default_column_family(&self) -> ColumnFamilyHandle {
+        let versions = self.versions.lock().unwrap();
+        let cfd = versions
+            .column_families()
+            .get(DEFAULT_COLUMN_FAMILY_ID)
+            .expect("the default column family is never dropped");
+        ColumnFamilyHandle::new(Arc::clone(cfd))
+    }
+
+    /// Names of the live column families, by id.
+    pub(crate) fn column_family_names(&self) -> Vec<String> {
+        let versions = self.versions.lock().unwrap();
+        versions
+            .column_families()
+            .iter()
+            .map(|cfd| cfd.name().to_string())
+            .collect()
+    }
+
+    /// Dropped column families whose data is still held by a handle.
+    pub(crate) fn num_dropped_column_families_pending(&self) -> usize {
+        let mut versions = self.versions.lock().unwrap();
+        versions.column_families_mut().num_pending_reclaim()
+    }
+
+    #[inline]
+    pub(crate) fn last_sequence(&self) -> u64 {
+        self.last_sequence.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn set_last_sequence(&self, sequence: u64) {
+        debug_assert!(sequence >= self.last_sequence());
+        self.last_sequence.store(sequence, Ordering::Release);
+    }
+
+    /// Pins the current visible seq no. Reads with the snapshot in ReadOptions::snapshot see the DB as of now until every reference to
+    /// it is dropped (or given to release_snapshot).
+    pub(crate) fn get_snapshot(&self) -> Arc<Snapshot> {
+        Arc::new(self.snapshots.new_snapshot_with(|| self.last_sequence()))
+    }
+
+    pub(crate) fn release_snapshot(&self, snapshot: Arc<Snapshot>) {
+        drop(snapshot);
+    }
+
+    /// Live snapshots - flushes and compactions keep every version these can still read.
+    #[inline]
+    pub(crate) fn snapshots(&self) -> &SnapshotList {
+        &self.snapshots
+    }
+
+    /// Deletes every key in [start, end) of the column family. The tombstone is logged like any write and goes into the column family's
+    /// RangeDelIndex, or with per_file_range_deletions into the memtable and the table files flushed from it.
+    pub(crate) fn delete_range(
+        &self,
+        column_family: &ColumnFamilyHandle,
+        start: &[u8],
+        end: &[u8],
+    ) -> error::Result<()> {
+        let mut batch = Batch::new();
+        batch.delete_range_cf(column_family.id(), start, end);
+        self.write(&mut batch)
+    }
+
+    /// Drops the range tombstones of the column family which can no longer hide anything. `horizon` is the smallest seq no of any data
+    /// left in the column family - compaction raises it as it rewrites covered keys away.
+    pub(crate) fn collect_range_del_garbage(
+        &self,
+        column_family: &ColumnFamilyHandle,
+        horizon: u64,
+    ) -> usize {
+        // Keeps delete_range from publishing a newer seq no until the index is rebuilt
+        let _versions = self.versions.lock().unwrap();
+        self.collect_range_del_garbage_locked(column_family.data(), horizon)
+    }
+
+    fn collect_range_del_garbage_locked(&self, cfd: &ColumnFamilyData, horizon: u64) -> usize {
+        let mut read_at = self.snapshots.sequence_numbers();
+        read_at.push(self.last_sequence());
+        cfd.range_del().collect_garbage(horizon, &read_at)
+    }
+
+    /// Lets flushes and compactions trim the versions of the column family's keys with a timestamp below `ts` (see key/timestamp.rs) -
+    /// reads at an older timestamp may no longer find them. It can only be increased and is kept in the MANIFEST.
+    pub(crate) fn increase_full_history_ts_low(
+        &self,
+        column_family: &ColumnFamilyHandle,
+        ts: &[u8],
+    ) -> error::Result<()> {
+        let mut versions = self.versions.lock().unwrap();
+        column_family.data().increase_full_history_ts_low(ts)?;
+        versions.set_last_sequence(self.last_sequence());
+        versions.log_and_apply(VersionEdit {
+            column_family: column_family.id(),
+            full_history_ts_low: Some(ts.to_vec()),
+            ..Default::default()
+        })
+    }
+
+    /// Reads the newest value of the key visible to the read options, with the merge operands above it applied.
+    pub(crate) fn get(
+        &self,
+        read_options: &ReadOptions,
+        column_family: &ColumnFamilyHandle,
+        user_key: &[u8],
+    ) -> error::Result<Option<Vec<u8>>> {
+        let cfd = column_family.data();
+        check_read_timestamp(read_options, cfd)?;
+        // The range deletions are loaded before the read seq no (see RangeDelIndex::collect_garbage)
+        let range_del = cfd.range_del().version();
+        let sequence = read_options.read_sequence(self.last_sequence());
+        let now = self.clock.now();
+        let sources: [&dyn GetSource; 1] = [cfd.superversion()];
+        let merge_operator = cfd.options().merge_operator.as_deref();
+        // Column families with timestamps have no range deletions
+        if let Some(timestamp) = &read_options.timestamp {
+            let lookup_key = LookUpInternalKey::new(
+                &[user_key, timestamp].concat(),
+                sequence,
+                OperationType::Max,
+            );
+            return get_with_timestamp(
+                &sources,
+                lookup_key.as_ref(),
+                timestamp.len(),
+                merge_operator,
+                now,
+            );
+        }
+        let lookup_key = LookUpInternalKey::new(user_key, sequence, OperationType::Max);
+        get_with_range_del(
+            &sources,
+            &range_del,
+            lookup_key.as_ref(),
+            merge_operator,
+            now,
+        )
+    }
+
+    /// The merge operands of the key visible to the read options (oldest first) without merging them, after the base value if there is
+    /// one. Intended for debugging merge operators.
+    pub(crate) fn get_merge_operands(
+        &self,
+        read_options: &ReadOptions,
+        column_family: &ColumnFamilyHandle,
+        user_key: &[u8],
+    ) -> error::Result<Vec<Vec<u8>>> {
+        let cfd = column_family.data();
+        check_read_timestamp(read_options, cfd)?;
+        let range_del = cfd.range_del().version();
+        let sequence = read_options.read_sequence(self.last_sequence());
+        let sources: [&dyn GetSource; 1] = [cfd.superversion()];
+        let timestamp = read_options.timestamp.as_deref().unwrap_or_default();
+        let lookup_key = LookUpInternalKey::new(
+            &[user_key, timestamp].concat(),
+            sequence,
+            OperationType::Max,
+        );
+        get_merge_operands(&sources, &range_del, lookup_key.as_ref(), timestamp.len())
+    }
+
+    /// Looks up a batch of keys in one superversion of the column family at one read seq no. Results are in the order of `keys`.
+    pub(crate) fn multi_get(
+        &self,
+        read_options: &ReadOptions,
+        column_family: &ColumnFamilyHandle,
+        keys: &[&[u8]],
+    ) -> Vec<error::Result<Option<Vec<u8>>>> {
+        let cfd = column_family.data();
+        if let Err(e) = check_read_timestamp(read_options, cfd) {
+            return keys.iter().map(|_| Err(e.clone())).collect();
+        }
+        let range_del = cfd.range_del().version();
+        let sequence = read_options.read_sequence(self.last_sequence());
+        let sources: [&dyn MultiGetSource; 1] = [cfd.superversion()];
+        let user_comparator = Arc::clone(cfd.user_comparator());
+        let merge_operator = cfd.options().merge_operator.as_deref();
+        if let Some(timestamp) = &read_options.timestamp {
+            return multi_get_with_timestamp(
+                &sources,
+                keys,
+                sequence,
+                timestamp,
+                user_comparator,
+                merge_operator,
+                self.clock.now(),
+            );
+        }
+        multi_get_with_range_del(
+            &sources,
+            &range_del,
+            keys,
+            sequence,
+            user_comparator,
+            merge_operator,
+            self.clock.now(),
+        )
+    }
+
+    /// Iterates the column family as of the read options - its memtables and table files at the time of the call.
+    pub(crate) fn new_iterator<'a>(
+        &'a self,
+        read_options: &ReadOptions,
+        column_family: &'a ColumnFamilyHandle,
+    ) -> error::Result<DBIter<'a, PooledIter<'a>>> {
+        let cfd = column_family.data();
+        // Without a read timestamp the iterator sees the newest version of every key
+        if read_options.timestamp.is_some() {
+            check_read_timestamp(read_options, cfd)?;
+        }
+        let user_comparator = Arc::clone(cfd.user_comparator());
+        let bounds = IterBounds::from_read_options(read_options, Arc::clone(&user_comparator));
+        let prefix_extractor = cfd
+            .options()
+            .prefix_extractor
+            .clone()
+            .filter(|_| read_options.prefix_same_as_start);
+        let range_del = cfd.range_del().version();
+        let sequence = read_options.read_sequence(self.last_sequence());
+
+        let mut builder = DBIterBuilder::new(PooledIter::new(self.iter_pool.as_ref()));
+        cfd.superversion()
+            .add_iterators(&mut builder, bounds.as_ref())?;
+        Ok(builder
+            .build(
+                cfd.internal_comparator().clone(),
+                user_comparator,
+                cfd.options().merge_operator.as_deref(),
+                sequence,
+                bounds,
+                prefix_extractor,
+            )
+            .with_now(self.clock.now())
+            .with_timestamp(read_options.timestamp.clone())
+            .with_range_del(range_del))
+    }
+
+    /// Applies the batch atomically: its records get consecutive seq nos and become visible together. The batch's sequence is set to
+    /// the first of them.
+    pub(crate) fn write(&self, batch: &mut Batch) -> error::Result<()> {
+        self.write_with_callback(batch, None)
+    }
+
+    /// Like write, but `callback` runs first with no other write in between. If it fails nothing of the batch is applied.
+    pub(crate) fn write_with_callback(
+        &self,
+        batch: &mut Batch,
+        callback: Option<&dyn WriteCallback>,
+    ) -> error::Result<()> {
+        self.write_impl(batch, callback, None)
+    }
+
+    // Writes the batch through the write thread. The leader of a group writes the batches of its followers with its own, as one WAL
+    // record with consecutive seq nos. Writers with a callback or a commit marker lead a group of their own, so nothing is written
+    // between the callback and the batch.
+    fn write_impl(
+        &self,
+        batch: &mut Batch,
+        callback: Option<&dyn WriteCallback>,
+        commit: Option<&str>,
+    ) -> error::Result<()> {
+        let mut writer = Writer::new(batch);
+        if callback.is_some() || commit.is_some() {
+            writer = writer.without_batching();
+        }
+        self.write_thread.join_batch_group(&writer);
+        if !writer.is_leader() {
+            // The leader of our group wrote the batch
+            batch.set_sequence(writer.result()?);
+            return Ok(());
+        }
+
+        let group = self.write_thread.enter_as_batch_group_leader(&writer);
+        let result = {
+            let mut versions = self.versions.lock().unwrap();
+            if group.len() == 1 {
+                callback
+                    .map_or(Ok(()), |callback| callback.callback(self))
+                    .and_then(|()| self.write_locked(&mut versions, batch, commit))
+            } else {
+                self.write_group_locked(&mut versions, &group);
+                writer.result().map(|sequence| batch.set_sequence(sequence))
+            }
+        };
+        self.write_thread.exit_as_batch_group_leader(group);
+        result
+    }
+
+    /// Adds table files written by SstFileWriter to the column family (see db/external_file_ingestion.rs). Either every file is
+    /// ingested or none is.
+    pub(crate) fn ingest_external_file(
+        &self,
+        column_family: &ColumnFamilyHandle,
+        paths: &[PathBuf],
+        options: &IngestExternalFileOptions,
+    ) -> error::Result<()> {
+        let cfd = column_family.data();
+        let user_comparator = Arc::clone(cfd.user_comparator());
+        let files = open_external_files(paths, user_comparator.as_ref())?;
+
+        let mut versions = self.versions.lock().unwrap();
+        let Some(db_path) = versions.db_path().map(Path::to_path_buf) else {
+            return Err(error::Error::InvalidArgument(
+                "an in-memory DB has no files to ingest into".into(),
+            ));
+        };
+        if cfd.is_dropped() {
+            return Err(error::Error::InvalidArgument(format!(
+                "column family {} was dropped",
+                cfd.name()
+            )));
+        }
+
+        // The ingested entries must be newer than the memtable's, which a table below them can't be once it is flushed
+        let smallest = files[0].user_key_range().0;
+        let largest = files[files.len() - 1].user_key_range().1;
+        if cfd
+            .superversion()
+            .memtables_overlap(smallest, largest, user_comparator.as_ref())
+        {
+            self.flush_locked(&mut versions, cfd)?;
+        }
+
+        // Needed by every file, whatever it overlaps
+        let seqno_for_all = (options.snapshot_consistency && !self.snapshots.is_empty())
+            || !cfd.range_del().version().is_empty();
+        let version = cfd.current_version();
+        let placements = files
+            .iter()
+            .map(|file| {
+                let overlaps = first_overlapping_level(&version, file, user_comparator.as_ref());
+                (pick_level(overlaps), seqno_for_all || overlaps.is_some())
+            })
+            .collect::<Vec<_>>();
+        let uses_global_seqno = placements.iter().any(|&(_, seqno)| seqno);
+        if uses_global_seqno && !options.allow_global_seqno {
+            return Err(error::Error::InvalidArgument(
+                "the files need a global seq no but allow_global_seqno is false".into(),
+            ));
+        }
+        let global_seqno = self.last_sequence() + 1;
+
+        let mut levels: Vec<_> = (0..NUM_LEVELS)
+            .map(|level| version.level_files(level).to_vec())
+            .collect();
+        let mut edit = VersionEdit {
+            column_family: cfd.id(),
+            ..Default::default()
+        };
+        let mut installed = Vec::new();
+        let result = files
+            .iter()
+            .zip(&placements)
+            .try_for_each(|(file, &(level, needs_seqno))| {
+                let number = versions.new_file_number();
+                installed.push(number);
+                let seqno = if needs_seqno { global_seqno } else { 0 };
+                let meta = Arc::new(file.install(
+                    &db_path,
+                    number,
+                    seqno,
+                    options.move_files,
+                    cfd.internal_comparator(),
+                )?);
+                edit.new_files.push(NewFile::from_meta(level, &meta));
+                levels[level].push(meta);
+                Ok(())
+            })
+            .and_then(|()| {
+                fs::File::open(&db_path)?.sync_all()?;
+                versions.set_last_sequence(self.last_sequence());
+                if uses_global_seqno {
+                    versions.set_last_sequence(global_seqno);
+                }
+                versions.log_and_apply(edit)
+            });
+        if let Err(e) = result {
+            for number in installed {
+                let _ = fs::remove_file(filename::table_file(&db_path, number));
+            }
+            return Err(e);
+        }
+
+        cfd.install_version(Arc::new(Version::from_levels(
+            levels,
+            Arc::clone(cfd.internal_comparator()),
+        )));
+        if uses_global_seqno {
+            self.set_last_sequence(global_seqno);
+        }
+        if options.move_files {
+            for file in &files {
+                let _ = fs::remove_file(file.path());
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the memtable of the column family to an L0 table file and starts a new WAL, so the column family no longer needs the WALs
+    /// before it. With enable_blob_files the values of at least min_blob_size go to a new blob file instead (see compaction/output.rs).
+    /// An empty memtable is left as it is.
+    pub(crate) fn flush(&self, column_family: &ColumnFamilyHandle) -> error::Result<()> {
+        let mut versions = self.versions.lock().unwrap();
+        self.flush_locked(&mut versions, column_family.data())
+    }
+
+    // Flushes the memtable of the column family - the caller holds the versions lock
+    fn flush_locked(&self, versions: &mut VersionSet, cfd: &ColumnFamilyData) -> error::Result<()> {
+        let Some(db_path) = versions.db_path().map(Path::to_path_buf) else {
+            return Err(error::Error::InvalidArgument(
+                "an in-memory DB has no files to flush to".into(),
+            ));
+        };
+        if cfd.is_dropped() {
+            return Err(error::Error::InvalidArgument(format!(
+                "column family {} was dropped",
+                cfd.name()
+            )));
+        }
+
+        let version = cfd.current_version();
+        let mem = cfd.mem();
+        let imm = cfd.immutable_memtables();
+        let mut children: Vec<Box<dyn InternalIterator>> = vec![Box::new(mem.iter())];
+        children.extend(
+            imm.memtables()
+                .iter()
+                .map(|imm| Box::new(imm.iter()) as Box<dyn InternalIterator>),
+        );
+        let mut iter = MergingIterator::new(cfd.internal_comparator().clone(), children);
+        iter.seek_to_first();
+        let mut range_tombstones = Vec::new();
+        iter.range_tombstones(&mut range_tombstones)?;
+        if !iter.valid() && range_tombstones.is_empty() {
+            return Ok(());
+        }
+        let ctx = CompactionFilterContext {
+            column_family_id: cfd.id(),
+            level: 0,
+            is_full_compaction: false,
+            is_manual_compaction: false,
+            reason: TableFileCreationReason::Flush,
+        };
+        let output = self.write_job_output(versions, cfd, iter, &version, &BTreeSet::new(), ctx)?;
+        drop(mem);
+        drop(imm);
+
+        let mut levels: Vec<_> = (0..NUM_LEVELS)
+            .map(|level| version.level_files(level).to_vec())
+            .collect();
+        let mut blob_files: Vec<_> = version.blob_files().values().cloned().collect();
+        // The range deletions written since the oldest memtable was created are only in the WALs the edit releases
+        let logged = cfd.superversion().earliest_sequence_in_memtables();
+        let mut edit = VersionEdit {
+            column_family: cfd.id(),
+            range_tombstones: cfd
+                .range_del()
+                .version()
+                .tombstones()
+                .filter(|tombstone| tombstone.seq > logged)
+                .collect(),
+            ..Default::default()
+        };
+        add_output(&mut edit, &mut levels, &mut blob_files, 0, &output);
+
+        // The WAL is created before the edit naming it is logged, so a failed edit leaves nothing behind
+        let wal_number = versions.new_file_number();
+        edit.log_number = Some(wal_number);
+        versions.set_last_sequence(self.last_sequence());
+        let wal = Wal::create(&db_path, wal_number).and_then(|wal| {
+            versions.log_and_apply(edit).map(|()| wal).inspect_err(|_| {
+                let _ = fs::remove_file(filename::log_file(&db_path, wal_number));
+            })
+        });
+        let wal = match wal {
+            Ok(wal) => wal,
+            Err(e) => {
+                output.remove_files(&db_path);
+                return Err(e);
+            }
+        };
+
+        // The entries are in the Version before they leave the memtable, so a reader never misses them
+        cfd.install_version(Arc::new(
+            Version::from_levels(levels, Arc::clone(cfd.internal_comparator()))
+                .with_blob_files(blob_files),
+        ));
+        let mem_id = versions.column_families_mut().assign_memtable_id();
+        cfd.switch_memtable(mem_id, self.last_sequence(), self.seqno_to_time_mapping());
+        *self.wal.lock().unwrap() = Some(wal);
+        self.compact_fifo_locked(versions, cfd, &db_path)
+    }
 
+    // Moves the memtable out of the write path - into a table file, or into the immutable memtables of an in-memory DB
+    fn switch_memtable_locked(
+        &self,
+        versions: &mut VersionSet,
+        cfd: &ColumnFamilyData,
+    ) -> error::Result<()> {
+        if versions.db_path().is_some() {
+            return self.flush_locked(versions, cfd);
+        }
+        let mem_id = versions.column_families_mut().assign_memtable_id();
+        cfd.switch_to_immutable_memtable(
+            mem_id,
+            self.last_sequence(),
+            self.seqno_to_time_mapping(),
+        );
         Ok(())
     }
+
+    /// Compacts every table file of the column family into one file at the bottom level, or at L0 for a FIFO column family. With
+    /// enable_blob_garbage_collection the blobs still referenced in blob files whose live ratio is below
+    /// blob_garbage_collection_live_ratio are moved to a new blob file. A blob file nothing references anymore leaves the Version, and
+    /// files no Version holds are deleted unless file deletions are disabled.
+    pub(crate) fn compact_range(&self, column_family: &ColumnFamilyHandle) -> error::Result<()> {
+        let cfd = column_family.data();
+        let mut versions = self.versions.lock().unwrap();
+        let Some(db_path) = versions.db_path().map(Path::to_path_buf) else {
+            return Err(error::Error::InvalidArgument(
+                "an in-memory DB has no files to compact".into(),
+            ));
+        };
+        if cfd.is_dropped() {
+            return Err(error::Error::InvalidArgument(format!(
+                "column family {} was dropped",
+                cfd.name()
+            )));
+        }
+
+        let version = cfd.current_version();
+        if version.files().next().is_none() {
+            return Ok(());
+        }
+        let options = cfd.options();
+        let relocated: BTreeSet<FileNumber> = version
+            .blob_files()
+            .values()
+            .filter(|b| {
+                options.enable_blob_garbage_collection
+                    && b.live_ratio() < options.blob_garbage_collection_live_ratio
+            })
+            .map(|b| b.number)
+            .collect();
+
+        let input = MergingIterator::new(
+            cfd.internal_comparator().clone(),
+            table_iterators(cfd, &version)?,
+        );
+        let output_level = match options.compaction_style {
+            CompactionStyle::Level => NUM_LEVELS - 1,
+            CompactionStyle::Fifo => 0,
+        };
+        let ctx = CompactionFilterContext {
+            column_family_id: cfd.id(),
+            level: output_level,
+            is_full_compaction: true,
+            is_manual_compaction: true,
+            reason: TableFileCreationReason::Compaction,
+        };
+        let output = self.write_job_output(&mut versions, cfd, input, &version, &relocated, ctx)?;
+
+        let mut levels = vec![Vec::new(); NUM_LEVELS];
+        let mut blob_files = Vec::new();
+        let mut edit = VersionEdit {
+            column_family: cfd.id(),
+            deleted_files: version
+                .files()
+                .map(|(level, f)| (level as u8, f.number))
+                .collect(),
+            ..Default::default()
+        };
+        // Every table was an input, so the output holds every reference left to the blob files
+        for blob_file in version.blob_files().values() {
+            let live = output
+                .blob_references
+                .get(&blob_file.number)
+                .copied()
+                .unwrap_or_default();
+            let garbage = BlobFileCount {
+                number: blob_file.number,
+                blob_count: (blob_file.total_blob_count - blob_file.garbage_blob_count)
+                    .saturating_sub(live.count),
+                blob_bytes: (blob_file.total_blob_bytes - blob_file.garbage_blob_bytes)
+                    .saturating_sub(live.bytes),
+            };
+            if garbage.blob_count == 0 {
+                blob_files.push(Arc::clone(blob_file));
+                continue;
+            }
+            blob_files.push(Arc::new(
+                blob_file.with_garbage(garbage.blob_count, garbage.blob_bytes),
+            ));
+            edit.blob_garbage.push(garbage);
+        }
+        add_output(
+            &mut edit,
+            &mut levels,
+            &mut blob_files,
+            output_level,
+            &output,
+        );
+
+        versions.set_last_sequence(self.last_sequence());
+        if let Err(e) = versions.log_and_apply(edit) {
+            output.remove_files(&db_path);
+            return Err(e);
+        }
+        cfd.install_version(Arc::new(
+            Version::from_levels(levels, Arc::clone(cfd.internal_comparator()))
+                .with_blob_files(blob_files),
+        ));
+
+        // Every table was an input, so the data left is the output and the memtables - range tombstones no older than all of it have
+        // nothing left to hide
+        let horizon = output
+            .tables
+            .iter()
+            .map(|table| table.smallest_seqno)
+            .min()
+            .unwrap_or(u64::MAX)
+            .min(cfd.superversion().earliest_sequence_in_memtables() + 1);
+        self.collect_range_del_garbage_locked(cfd, horizon);
+        self.delete_obsolete_files_locked(&versions, &db_path)?;
+        self.compact_fifo_locked(&mut versions, cfd, &db_path)
+    }
+
+    // Runs the FIFO compactions (see compaction/fifo.rs) a FIFO column family needs after a flush or compaction - the caller holds the
+    // versions lock. Expired and oldest files are dropped until nothing is picked anymore, an intra-L0 merge ends the run.
+    fn compact_fifo_locked(
+        &self,
+        versions: &mut VersionSet,
+        cfd: &ColumnFamilyData,
+        db_path: &Path,
+    ) -> error::Result<()> {
+        let options = cfd.options();
+        if options.compaction_style != CompactionStyle::Fifo {
+            return Ok(());
+        }
+        let picker = FifoCompactionPicker::new(&options.compaction_options_fifo);
+        let mut ran = false;
+        loop {
+            let version = cfd.current_version();
+            let Some(compaction) = picker.pick_compaction(&version, self.clock.now()) else {
+                break;
+            };
+            self.run_compaction_locked(versions, cfd, &version, &compaction, db_path)?;
+            ran = true;
+            if !compaction.is_deletion_compaction() {
+                break;
+            }
+        }
+        if !ran {
+            return Ok(());
+        }
+        self.delete_obsolete_files_locked(versions, db_path)
+    }
+
+    // Replaces the inputs of the compaction with what they compact to, nothing for a deletion compaction, and installs the new Version.
+    // The files left behind are not deleted.
+    fn run_compaction_locked(
+        &self,
+        versions: &mut VersionSet,
+        cfd: &ColumnFamilyData,
+        version: &Version,
+        compaction: &Compaction,
+        db_path: &Path,
+    ) -> error::Result<()> {
+        let mut edit = VersionEdit {
+            column_family: cfd.id(),
+            ..Default::default()
+        };
+        let mut inputs = vec![Vec::new(); NUM_LEVELS];
+        for input in compaction.inputs() {
+            inputs[input.level].extend(input.files.iter().cloned());
+            edit.deleted_files
+                .extend(input.files.iter().map(|f| (input.level as u8, f.number)));
+        }
+        let mut levels: Vec<_> = (0..NUM_LEVELS)
+            .map(|level| {
+                version
+                    .level_files(level)
+                    .iter()
+                    .filter(|f| !edit.deleted_files.contains(&(level as u8, f.number)))
+                    .cloned()
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        let mut blob_files: Vec<_> = version.blob_files().values().cloned().collect();
+
+        let output = if compaction.is_deletion_compaction() {
+            CompactionOutput::default()
+        } else {
+            // Only the inputs are read, the job sees them as its Version
+            let inputs = Version::from_levels(inputs, Arc::clone(cfd.internal_comparator()))
+                .with_blob_files(blob_files.iter().cloned());
+            let input = MergingIterator::new(
+                cfd.internal_comparator().clone(),
+                table_iterators(cfd, &inputs)?,
+            );
+            let ctx = CompactionFilterContext {
+                column_family_id: cfd.id(),
+                level: compaction.output_level(),
+                is_full_compaction: false,
+                is_manual_compaction: false,
+                reason: TableFileCreationReason::Compaction,
+            };
+            self.write_job_output(versions, cfd, input, &inputs, &BTreeSet::new(), ctx)?
+        };
+        add_output(
+            &mut edit,
+            &mut levels,
+            &mut blob_files,
+            compaction.output_level(),
+            &output,
+        );
+
+        versions.set_last_sequence(self.last_sequence());
+        if let Err(e) = versions.log_and_apply(edit) {
+            output.remove_files(db_path);
+            return Err(e);
+        }
+        cfd.install_version(Arc::new(
+            Version::from_levels(levels, Arc::clone(cfd.internal_comparator()))
+                .with_blob_files(blob_files),
+        ));
+        Ok(())
+    }
+
+    /// Logs the batch as the prepared transaction `name` without applying it, and syncs the WAL. Returns the number of the WAL holding
+    /// it, which is kept until commit_prepared or rollback_prepared decides the transaction.
+    pub(crate) fn prepare(&self, name: &str, batch: &Batch) -> error::Result<u64> {
+        let _versions = self.versions.lock().unwrap();
+        let log_number = match self.wal.lock().unwrap().as_mut() {
+            Some(wal) => {
+                wal.add(&Batch::prepare_record(name, batch), true)?;
+                wal.number()
+            }
+            None => 0,
+        };
+        self.logs_with_prep.lock().unwrap().add(log_number);
+        Ok(log_number)
+    }
+
+    /// Applies the writes of the prepared transaction `name` (logged in `prepared_log`) like write does, behind a synced Commit marker.
+    pub(crate) fn commit_prepared(
+        &self,
+        name: &str,
+        batch: &mut Batch,
+        prepared_log: u64,
+    ) -> error::Result<()> {
+        self.write_impl(batch, None, Some(name))?;
+        self.logs_with_prep.lock().unwrap().remove(prepared_log);
+        Ok(())
+    }
+
+    /// Discards the prepared transaction `name` (logged in `prepared_log`) with a synced Rollback marker.
+    pub(crate) fn rollback_prepared(&self, name: &str, prepared_log: u64) -> error::Result<()> {
+        let _versions = self.versions.lock().unwrap();
+        if let Some(wal) = self.wal.lock().unwrap().as_mut() {
+            wal.add(&Batch::marker_record(BatchOpType::Rollback, name), true)?;
+        }
+        self.logs_with_prep.lock().unwrap().remove(prepared_log);
+        Ok(())
+    }
+
+    /// The prepared transactions recovery found undecided, oldest first. Only the first call gets them.
+    pub(crate) fn take_recovered_prepared(&self) -> Vec<RecoveredPrepared> {
+        mem::take(&mut *self.recovered_prepared.lock().unwrap())
+    }
+
+    /// WALs older than this are no longer needed: every column family has their writes in table files and none holds an undecided
+    /// prepared transaction.
+    pub(crate) fn min_log_to_keep(&self) -> u64 {
+        let versions = self.versions.lock().unwrap();
+        self.min_log_to_keep_locked(&versions)
+    }
+
+    /// Keeps every file of the DB directory until enable_file_deletions is called as many times, so they can be copied.
+    pub(crate) fn disable_file_deletions(&self) {
+        // Taken under the versions lock so no purge is deleting files once this returns
+        let _versions = self.versions.lock().unwrap();
+        self.file_deletions_disabled.fetch_add(1, Ordering::AcqRel);
+    }
+
+    pub(crate) fn enable_file_deletions(&self) {
+        let disabled = self.file_deletions_disabled.fetch_sub(1, Ordering::AcqRel);
+        debug_assert!(disabled > 0);
+    }
+
+    /// Writes a MANIFEST (and CURRENT) describing the DB as of now into `dir` and returns the other files a copy of the DB in `dir`
+    /// needs. No write happens in between, so the copied WALs end where the MANIFEST's seq no does.
+    pub(crate) fn live_files(&self, dir: &Path) -> error::Result<LiveFiles> {
+        let mut versions = self.versions.lock().unwrap();
+        let Some(db_path) = versions.db_path().map(Path::to_path_buf) else {
+            return Err(error::Error::InvalidArgument(
+                "an in-memory DB has no files".into(),
+            ));
+        };
+        versions.set_last_sequence(self.last_sequence());
+        versions.write_manifest(dir, versions.manifest_number())?;
+
+        let min_log = self.min_log_to_keep_locked(&versions);
+        let wal = self.wal.lock().unwrap();
+        let mut files = LiveFiles {
+            sequence: versions.last_sequence(),
+            tables: Vec::new(),
+            blob_files: Vec::new(),
+            wals: Vec::new(),
+        };
+        for cfd in versions.column_families().iter() {
+            let version = cfd.current_version();
+            files.tables.extend(version.files().map(|(_, f)| f.number));
+            files
+                .blob_files
+                .extend(version.blob_files().keys().copied());
+        }
+        for entry in fs::read_dir(&db_path)? {
+            let entry = entry?;
+            match entry
+                .file_name()
+                .to_str()
+                .and_then(filename::parse_file_name)
+            {
+                Some((filename::FileType::Log, number)) if number >= min_log => {
+                    let size = match wal.as_ref() {
+                        Some(wal) if wal.number() == number => wal.size(),
+                        _ => entry.metadata()?.len(),
+                    };
+                    files.wals.push((number, size));
+                }
+                _ => {}
+            }
+        }
+        files.tables.sort_unstable();
+        files.blob_files.sort_unstable();
+        files.wals.sort_unstable();
+        Ok(files)
+    }
+
+    /// Deletes the WAL files older than min_log_to_keep, unless file deletions are disabled. Returns how many were deleted.
+    pub(crate) fn purge_obsolete_wal_files(&self) -> error::Result<usize> {
+        let versions = self.versions.lock().unwrap();
+        let Some(db_path) = versions.db_path() else {
+            return Ok(0);
+        };
+        if self.file_deletions_disabled.load(Ordering::Acquire) > 0 {
+            return Ok(0);
+        }
+        let min = self.min_log_to_keep_locked(&versions);
+        let mut purged = 0;
+        for number in wal::wal_files(db_path)? {
+            if number >= min {
+                break;
+            }
+            fs::remove_file(filename::log_file(db_path, number))?;
+            purged += 1;
+        }
+        Ok(purged)
+    }
+
+    fn min_log_to_keep_locked(&self, versions: &VersionSet) -> u64 {
+        let mut min = versions.column_families().oldest_log_to_keep();
+        if let Some(prepared) = self.logs_with_prep.lock().unwrap().min() {
+            min = min.min(prepared);
+        }
+        if let Some(wal) = self.wal.lock().unwrap().as_ref() {
+            min = min.min(wal.number());
+        }
+        min
+    }
+
+    // Runs the input of a flush or compaction through a CompactionIterator with the column family's options and writes the result into
+    // new files at ctx.level (see compaction/output.rs). Blob references are read from `version`, those into `relocated` blob files are
+    // moved to the new blob file. The files of `version` are the inputs of a compaction, its output is as old as the oldest of them.
+    fn write_job_output<I: InternalIterator>(
+        &self,
+        versions: &mut VersionSet,
+        cfd: &ColumnFamilyData,
+        input: I,
+        version: &Version,
+        relocated: &BTreeSet<FileNumber>,
+        ctx: CompactionFilterContext,
+    ) -> error::Result<CompactionOutput> {
+        let Some(db_path) = versions.db_path().map(Path::to_path_buf) else {
+            return Err(error::Error::InvalidArgument(
+                "an in-memory DB has no files".into(),
+            ));
+        };
+        let options = cfd.options();
+        let snapshots = self.snapshots.sequence_numbers();
+        let range_del = cfd.range_del().version();
+        let full_history_ts_low = cfd.full_history_ts_low();
+        let mut iter = CompactionIterator::new(
+            input,
+            options.comparator.as_ref(),
+            &snapshots,
+            options.compaction_filter.as_deref(),
+            ctx,
+            // Nothing is below a job which reads every file
+            ctx.is_full_compaction,
+        )
+        .with_range_del(&range_del)
+        .with_blob_files(version, relocated);
+        if let Some(merge_operator) = options.merge_operator.as_deref() {
+            iter = iter.with_merge_operator(merge_operator);
+        }
+        if let Some(ts) = full_history_ts_low.as_deref() {
+            iter = iter.with_full_history_ts_low(ts);
+        }
+
+        // Samples of old data may have left memory but are still in the properties of the inputs
+        let mut seqno_to_time = self.seqno_to_time_mapping();
+        let now = self.clock.now();
+        let mut times = OutputTimes {
+            creation_time: now,
+            file_creation_time: now,
+        };
+        if ctx.reason == TableFileCreationReason::Compaction {
+            let mapping = Arc::make_mut(&mut seqno_to_time);
+            for (_, f) in version.files() {
+                mapping.merge(&f.properties.seqno_to_time);
+                let created = f.properties.oldest_ancestor_time();
+                if created != 0 {
+                    times.creation_time = times.creation_time.min(created);
+                }
+            }
+        }
+
+        // L0 files may overlap, a flush or compaction into L0 writes a single file
+        let target_file_size = (ctx.level > 0).then_some(options.target_file_size_base);
+        write_output(
+            &mut iter,
+            &db_path,
+            &mut || versions.new_file_number(),
+            options,
+            target_file_size,
+            seqno_to_time,
+            times,
+        )
+    }
+
+    // Samples the newest seq no with the time once seqno_time_sample_period passed since the last sample
+    fn sample_seqno_time(&self) {
+        if self.seqno_time_sample_period == 0 {
+            return;
+        }
+        let now = self.clock.now();
+        let mut seqno_to_time = self.seqno_to_time.lock().unwrap();
+        if seqno_to_time
+            .last_time()
+            .is_some_and(|last| now < last.saturating_add(self.seqno_time_sample_period))
+        {
+            return;
+        }
+        Arc::make_mut(&mut seqno_to_time).append(self.last_sequence(), now);
+    }
+
+    // Deletes the table and blob files of the DB directory which no column family's Version holds, unless file deletions are disabled.
+    // A dropped column family keeps its files until the last handle to it is gone.
+    // Readers of an older Version keep working - the tables and blob files it holds stay open, and an open file can still be read once
+    // its name is removed.
+    fn delete_obsolete_files_locked(
+        &self,
+        versions: &VersionSet,
+        db_path: &Path,
+    ) -> error::Result<()> {
+        if self.file_deletions_disabled.load(Ordering::Acquire) > 0 {
+            return Ok(());
+        }
+        let mut live = HashSet::new();
+        let column_families = versions.column_families();
+        for cfd in column_families
+            .iter()
+            .cloned()
+            .chain(column_families.iter_dropped())
+        {
+            let version = cfd.current_version();
+            live.extend(version.files().map(|(_, f)| f.number));
+            live.extend(version.blob_files().keys().copied());
+        }
+
+        for entry in fs::read_dir(db_path)? {
+            let entry = entry?;
+            if let Some((filename::FileType::Table | filename::FileType::Blob, number)) = entry
+                .file_name()
+                .to_str()
+                .and_then(filename::parse_file_name)
+                && !live.contains(&number)
+            {
+                fs::remove_file(entry.path())?;
+            }
+        }
+        Ok(())
+    }
+
+    // Logs and applies the batch - the caller holds the versions lock. Committing the prepared transaction `commit` logs the batch
+    // behind its Commit marker, synced.
+    fn write_locked(
+        &self,
+        versions: &mut VersionSet,
+        batch: &mut Batch,
+        commit: Option<&str>,
+    ) -> error::Result<()> {
+        let column_families = self.resolve_batch_locked(versions, batch)?;
+        self.apply_locked(versions, batch, &column_families, commit)
+    }
+
+    // Writes the batches of a group as one batch - the caller holds the versions lock. A batch which does not resolve fails on its
+    // own, the others are still written. Sets the result of every writer of the group.
+    fn write_group_locked(&self, versions: &mut VersionSet, group: &WriteGroup) {
+        let mut grouped = Batch::new();
+        let mut column_families = Vec::new();
+        let mut written = Vec::with_capacity(group.len());
+        for writer in group.iter() {
+            match self.resolve_batch_locked(versions, writer.batch()) {
+                Ok(resolved) => {
+                    written.push((writer, column_families.len() as u64));
+                    column_families.extend(resolved);
+                    grouped.append(writer.batch());
+                }
+                // SAFETY: We lead the writer's group, it is not COMPLETE until we exit it
+                Err(e) => unsafe { writer.set_result(Err(e)) },
+            }
+        }
+
+        let result = self.apply_locked(versions, &mut grouped, &column_families, None);
+        for (writer, offset) in written {
+            let result = result.clone().map(|()| grouped.sequence() + offset);
+            // SAFETY: As above
+            unsafe { writer.set_result(result) };
+        }
+    }
+
+    // The column family (and memtable entry size) of every record of the batch. Every record is resolved before any is logged or
+    // applied so a bad batch is rejected as a whole.
+    fn resolve_batch_locked(
+        &self,
+        versions: &VersionSet,
+        batch: &Batch,
+    ) -> error::Result<Vec<(Arc<ColumnFamilyData>, usize)>> {
+        batch
+            .iter()
+            .map(|record| {
+                let record = record?;
+                if record.op.is_marker() {
+                    return Err(error::Error::InvalidArgument(format!(
+                        "{} is not a write",
+                        record.op
+                    )));
+                }
+                let cfd = versions
+                    .column_families()
+                    .get(record.cf_id)
+                    .filter(|cfd| !cfd.is_dropped())
+                    .ok_or_else(|| {
+                        error::Error::InvalidArgument(format!(
+                            "column family {} does not exist",
+                            record.cf_id
+                        ))
+                    })?;
+                if record.op == BatchOpType::DeleteRange {
+                    check_range_deletion(&record, cfd)?;
+                    // A tombstone for the RangeDelIndex takes no memtable space
+                    if !cfd.options().per_file_range_deletions {
+                        return Ok((Arc::clone(cfd), 0));
+                    }
+                } else if record.key.len() < cfd.user_comparator().timestamp_size() {
+                    return Err(error::Error::InvalidArgument(format!(
+                        "key of column family {} has no timestamp",
+                        record.cf_id
+                    )));
+                }
+                // Even an empty memtable could not take an entry larger than an arena block
+                let entry_size =
+                    Memtable::<Mutable>::max_entry_size(record.key.len() + 8, record.value.len());
+                let block_size = cfd.options().write_buffer_size.arena_policy().block_size;
+                if entry_size > block_size {
+                    return Err(error::Error::InvalidArgument(format!(
+                        "entry of {entry_size} bytes is larger than the {block_size} byte memtable blocks of column family {}",
+                        record.cf_id
+                    )));
+                }
+                Ok((Arc::clone(cfd), entry_size))
+            })
+            .collect()
+    }
+
+    // Logs and applies the resolved batch, its records at consecutive seq nos from the last one on. A batch which fails once it is
+    // logged still uses up its seq nos, and fails every later write.
+    fn apply_locked(
+        &self,
+        versions: &mut VersionSet,
+        batch: &mut Batch,
+        column_families: &[(Arc<ColumnFamilyData>, usize)],
+        commit: Option<&str>,
+    ) -> error::Result<()> {
+        if let Some(e) = self.write_error.lock().unwrap().as_ref() {
+            return Err(e.clone());
+        }
+        // A prepared transaction without writes still needs its decision logged
+        if column_families.is_empty() && commit.is_none() {
+            return Ok(());
+        }
+
+        // Memtables without room for their part of the batch are switched before it is logged. The last arena block is left for the
+        // space lost at the end of the others, so the batch only switches a memtable itself (see ColumnFamilyData::insert) when it is
+        // about as large as a whole write buffer
+        let mut incoming: BTreeMap<u32, (&Arc<ColumnFamilyData>, usize)> = BTreeMap::new();
+        for (cfd, entry_size) in column_families {
+            incoming.entry(cfd.id()).or_insert((cfd, 0)).1 += entry_size;
+        }
+        for (cfd, size) in incoming.into_values() {
+            let policy = cfd.options().write_buffer_size.arena_policy();
+            let used = cfd.mem().approximate_memory_usage();
+            if used > 0 && used + size > policy.cap - policy.block_size {
+                self.switch_memtable_locked(versions, cfd)?;
+            }
+        }
+
+        let first = self.last_sequence() + 1;
+        batch.set_sequence(first);
+        if let Some(wal) = self.wal.lock().unwrap().as_mut() {
+            match commit {
+                Some(name) => {
+                    let mut record = Batch::marker_record(BatchOpType::Commit, name);
+                    record.append(batch);
+                    record.set_sequence(first);
+                    wal.add(&record, true)?;
+                }
+                None => wal.add(batch, false)?,
+            }
+        }
+        if let Err(e) = self.insert_batch_locked(versions, batch, column_families) {
+            // Part of the batch may be readable and all of it is replayed from the WAL, its seq nos are never handed out again
+            if !column_families.is_empty() {
+                self.set_last_sequence(first + column_families.len() as u64 - 1);
+            }
+            *self.write_error.lock().unwrap() = Some(e.clone());
+            return Err(e);
+        }
+        if !column_families.is_empty() {
+            self.set_last_sequence(first + column_families.len() as u64 - 1);
+            self.sample_seqno_time();
+        }
+        Ok(())
+    }
+
+    // Inserts the records of the logged batch into the memtables (and range deletion indexes) of their column families
+    fn insert_batch_locked(
+        &self,
+        versions: &mut VersionSet,
+        batch: &Batch,
+        column_families: &[(Arc<ColumnFamilyData>, usize)],
+    ) -> error::Result<()> {
+        let seqno_to_time = self.seqno_to_time_mapping();
+        for (sequence, (record, (cfd, _))) in
+            (batch.sequence()..).zip(batch.iter().zip(column_families))
+        {
+            let record = record?;
+            if record.op == BatchOpType::DeleteRange {
+                cfd.delete_range(
+                    record.key,
+                    record.value,
+                    sequence,
+                    || versions.column_families_mut().assign_memtable_id(),
+                    &seqno_to_time,
+                )?;
+                continue;
+            }
+            let key = LookUpInternalKey::new(record.key, sequence, OperationType::from(record.op));
+            cfd.insert(
+                key.as_ref(),
+                record.value,
+                || versions.column_families_mut().assign_memtable_id(),
+                &seqno_to_time,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// A range deletion needs a column family without timestamps and a start no greater than its end
+fn check_range_deletion(record: &BatchRecord<'_>, cfd: &ColumnFamilyData) -> error::Result<()> {
+    if cfd.user_comparator().timestamp_size() > 0 {
+        return Err(error::Error::NotSupported(
+            "range deletions on a column family with timestamps".into(),
+        ));
+    }
+    if cfd.user_comparator().compare(record.key, record.value) == std::cmp::Ordering::Greater {
+        return Err(error::Error::InvalidArgument(
+            "range deletion start is after its end".into(),
+        ));
+    }
+    Ok(())
+}
+
+// A read timestamp is required on a column family with timestamps and not allowed on others
+pub(crate) fn check_read_timestamp(
+    read_options: &ReadOptions,
+    cfd: &ColumnFamilyData,
+) -> error::Result<()> {
+    let timestamp_size = cfd.user_comparator().timestamp_size();
+    match &read_options.timestamp {
+        Some(timestamp) if timestamp.len() != timestamp_size || timestamp_size == 0 => {
+            Err(error::Error::InvalidArgument(format!(
+                "read timestamp of {} bytes on a column family with {timestamp_size} byte timestamps",
+                timestamp.len()
+            )))
+        }
+        None if timestamp_size > 0 => Err(error::Error::InvalidArgument(
+            "a read timestamp is required on a column family with timestamps".into(),
+        )),
+        _ => Ok(()),
+    }
+}
+
+// Iterators over every table file of the version
+fn table_iterators(
+    cfd: &ColumnFamilyData,
+    version: &Version,
+) -> error::Result<Vec<Box<dyn InternalIterator>>> {
+    version
+        .files()
+        .map(|(_, f)| -> error::Result<Box<dyn InternalIterator>> {
+            let table = f.table_reader().ok_or_else(|| {
+                error::Error::Corruption(format!("table file {} is not open", f.number))
+            })?;
+            Ok(Box::new(
+                table.iter(cfd.internal_comparator().clone(), None),
+            ))
+        })
+        .collect()
+}
+
+// Adds the files a flush or compaction wrote at `level` to the edit and to the files of the new Version
+fn add_output(
+    edit: &mut VersionEdit,
+    levels: &mut [Vec<Arc<FileMetaData>>],
+    blob_files: &mut Vec<Arc<BlobFileMetaData>>,
+    level: usize,
+    output: &CompactionOutput,
+) {
+    for table in &output.tables {
+        edit.new_files.push(NewFile::from_meta(level, table));
+        levels[level].push(Arc::clone(table));
+    }
+    if let Some(blob_file) = &output.blob_file {
+        edit.new_blob_files.push(BlobFileCount {
+            number: blob_file.number,
+            blob_count: blob_file.total_blob_count,
+            blob_bytes: blob_file.total_blob_bytes,
+        });
+        blob_files.push(Arc::clone(blob_file));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn failed_apply_uses_up_its_seq_nos_and_fails_later_writes() {
+        let db = DbImpl::new();
+        let mut versions = db.versions.lock().unwrap();
+        let cfd = Arc::clone(versions.column_families().get(0).unwrap());
+
+        // resolve_batch_locked would reject the range deletion, applied as it is it fails after the first put
+        let mut batch = Batch::new();
+        batch.put_cf(0, b"a", b"v");
+        batch.delete_range_cf(0, b"z", b"b");
+        batch.put_cf(0, b"c", b"v");
+        let column_families = vec![(cfd, 0); 3];
+        let result = db.apply_locked(&mut versions, &mut batch, &column_families, None);
+        assert!(matches!(result, Err(error::Error::InvalidArgument(_))));
+        drop(versions);
+        assert_eq!(db.last_sequence(), 3);
+
+        let mut batch = Batch::new();
+        batch.put_cf(0, b"d", b"v");
+        assert!(matches!(
+            db.write(&mut batch),
+            Err(error::Error::InvalidArgument(_))
+        ));
+        assert_eq!(db.last_sequence(), 3);
+    }
 }
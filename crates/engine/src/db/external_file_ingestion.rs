@@ -0,0 +1,234 @@
+//
+//
+//
+// External File Ingestion
+//
+// Adds table files written by an SstFileWriter to a column family without going through the memtables. Each file is opened and checked
+// before the DB is locked: every entry must be at seq no 0 with user keys strictly increasing, and the files must not overlap each other.
+//
+// With the versions lock held (no write in between), the files are placed:
+//
+//   - A file whose keys overlap nothing in the column family keeps seq no 0 - it is older than everything, so it goes to the bottom level.
+//   - Otherwise its entries must be newer than the data they overlap. The whole ingestion gets one global seq no (the next one) and each
+//     file goes to the level above the first level it overlaps, L0 if it overlaps L0. A live snapshot (with snapshot_consistency) or a
+//     range tombstone of the column family also needs the global seq no, or reads would see the entries as if they were always there.
+//
+// The table format has no global seq no field, so a file which gets one is rewritten with its entries at that seq no. A file which keeps
+// seq no 0 is linked (move_files) or copied into the DB as is.
+
+use std::cmp::Ordering;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::checkpoint::link_or_copy;
+use crate::db::filename::table_file;
+use crate::error::{Error, Result};
+use crate::iterator::internal_iterator::InternalIterator;
+use crate::key::comparator::{Comparator, InternalKeyComparator};
+use crate::key::internal_key::{InternalKeyRef, OperationType, encode_trailer};
+use crate::table::table_builder::TableBuilder;
+use crate::table::table_reader::Table;
+use crate::utils::random_access_file::RandomAccessFile;
+use crate::versioning::file_version::{FileMetaData, NUM_LEVELS, Version};
+
+/// An external table file, opened and checked.
+pub(crate) struct ExternalFile {
+    path: PathBuf,
+    table: Arc<Table>,
+    file_size: u64,
+    // Smallest and largest internal keys in the file
+    smallest: Vec<u8>,
+    largest: Vec<u8>,
+}
+
+impl ExternalFile {
+    fn open(path: &Path, user_comparator: &dyn Comparator) -> Result<Self> {
+        let file = RandomAccessFile::open(path)?;
+        let file_size = file.size();
+        let table = Table::open(file)?;
+        if !table.range_tombstones().is_empty() {
+            return Err(Error::NotSupported(format!(
+                "{} holds range tombstones",
+                path.display()
+            )));
+        }
+
+        let mut iter = table.iter(InternalKeyComparator::new(), None);
+        let mut smallest = None;
+        let mut largest: Option<Vec<u8>> = None;
+        iter.seek_to_first();
+        while iter.valid() {
+            let ikey = InternalKeyRef::from(iter.key());
+            if ikey.seq_no != 0 {
+                return Err(Error::InvalidArgument(format!(
+                    "{} has entries with a seq no - it was not written by an SstFileWriter",
+                    path.display()
+                )));
+            }
+            if let Some(last) = &largest
+                && user_comparator.compare(
+                    InternalKeyRef::from(last.as_slice()).user_key,
+                    ikey.user_key,
+                ) != Ordering::Less
+            {
+                return Err(Error::InvalidArgument(format!(
+                    "keys of {} are not in strictly increasing order",
+                    path.display()
+                )));
+            }
+
+            if smallest.is_none() {
+                smallest = Some(iter.key().to_vec());
+            }
+            largest = Some(iter.key().to_vec());
+            iter.next();
+        }
+        if let Some(e) = iter.status() {
+            return Err(e.clone());
+        }
+
+        let (Some(smallest), Some(largest)) = (smallest, largest) else {
+            return Err(Error::InvalidArgument(format!(
+                "{} has no entries",
+                path.display()
+            )));
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            table,
+            file_size,
+            smallest,
+            largest,
+        })
+    }
+
+    #[inline]
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Smallest and largest user keys in the file.
+    pub(crate) fn user_key_range(&self) -> (&[u8], &[u8]) {
+        (
+            InternalKeyRef::from(self.smallest.as_slice()).user_key,
+            InternalKeyRef::from(self.largest.as_slice()).user_key,
+        )
+    }
+
+    /// Adds the file to the DB in `db_path` as table file `number`, rewritten with its entries at `global_seqno` unless that is 0.
+    /// `comparator` is the column family's, the rewritten table shortens its index separators with it.
+    pub(crate) fn install(
+        &self,
+        db_path: &Path,
+        number: u64,
+        global_seqno: u64,
+        move_files: bool,
+        comparator: &Arc<InternalKeyComparator>,
+    ) -> Result<FileMetaData> {
+        let target = table_file(db_path, number);
+        if global_seqno == 0 {
+            if move_files {
+                link_or_copy(&self.path, &target)?;
+            } else {
+                fs::copy(&self.path, &target)?;
+                File::open(&target)?.sync_all()?;
+            }
+            let meta = FileMetaData::new(
+                number,
+                self.file_size,
+                self.smallest.clone(),
+                self.largest.clone(),
+                0,
+                0,
+                self.table.properties().clone(),
+            );
+            meta.set_table_reader(Table::open(RandomAccessFile::open(&target)?)?);
+            return Ok(meta);
+        }
+
+        let mut builder = TableBuilder::default().with_comparator(Arc::clone(comparator));
+        let mut iter = self.table.iter(comparator.clone(), None);
+        iter.seek_to_first();
+        while iter.valid() {
+            let ikey = InternalKeyRef::from(iter.key());
+            let trailer = encode_trailer(global_seqno, OperationType::from(ikey.op));
+            builder.add(&[ikey.user_key, &trailer].concat(), iter.value());
+            iter.next();
+        }
+        if let Some(e) = iter.status() {
+            return Err(e.clone());
+        }
+
+        let table = builder.finish();
+        let mut file = File::create(&target)?;
+        file.write_all(&table.data)?;
+        file.sync_all()?;
+
+        let meta = table.file_meta(number);
+        meta.set_table_reader(Table::open(RandomAccessFile::open(&target)?)?);
+        Ok(meta)
+    }
+}
+
+/// Opens and checks the files, sorted by smallest key. The files must not overlap each other.
+pub(crate) fn open_external_files(
+    paths: &[PathBuf],
+    user_comparator: &dyn Comparator,
+) -> Result<Vec<ExternalFile>> {
+    if paths.is_empty() {
+        return Err(Error::InvalidArgument("no files to ingest".into()));
+    }
+
+    let mut files = paths
+        .iter()
+        .map(|path| ExternalFile::open(path, user_comparator))
+        .collect::<Result<Vec<_>>>()?;
+    files.sort_by(|a, b| user_comparator.compare(a.user_key_range().0, b.user_key_range().0));
+
+    for pair in files.windows(2) {
+        if user_comparator.compare(pair[0].user_key_range().1, pair[1].user_key_range().0)
+            != Ordering::Less
+        {
+            return Err(Error::InvalidArgument(format!(
+                "{} and {} overlap",
+                pair[0].path.display(),
+                pair[1].path.display()
+            )));
+        }
+    }
+    Ok(files)
+}
+
+/// The level the file goes to (see the top of this file). `overlaps` is the first level of the version the file overlaps.
+pub(crate) fn pick_level(overlaps: Option<usize>) -> usize {
+    match overlaps {
+        Some(0) => 0,
+        Some(level) => level - 1,
+        None => NUM_LEVELS - 1,
+    }
+}
+
+/// The first level of the version with a file overlapping the external file.
+pub(crate) fn first_overlapping_level(
+    version: &Version,
+    file: &ExternalFile,
+    user_comparator: &dyn Comparator,
+) -> Option<usize> {
+    let (smallest, largest) = file.user_key_range();
+    (0..NUM_LEVELS).find(|&level| version.overlaps_level(level, smallest, largest, user_comparator))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn files_go_above_the_first_level_they_overlap() {
+        assert_eq!(pick_level(None), NUM_LEVELS - 1);
+        assert_eq!(pick_level(Some(0)), 0);
+        assert_eq!(pick_level(Some(1)), 0);
+        assert_eq!(pick_level(Some(4)), 3);
+    }
+}
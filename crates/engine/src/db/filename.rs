@@ -0,0 +1,137 @@
+//
+//
+//
+// File Names
+//
+// Every file in a DB directory is named after its kind and a file number allocated by the VersionSet:
+//
+//   CURRENT            - name of the live MANIFEST, replaced atomically (written to a temp file and renamed)
+//   MANIFEST-000005    - log of VersionEdits describing the column families and their files
+//   000012.log         - write ahead log
+//   000013.sst         - table file
+//   000015.blob        - blob file, values a table file only holds a reference to
+//   000014.dbtmp       - temp file, deleted on open
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::error::{Error, Result};
+
+pub(crate) const CURRENT: &str = "CURRENT";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FileType {
+    Current,
+    Manifest,
+    Log,
+    Table,
+    Blob,
+    Temp,
+}
+
+pub(crate) fn current_file(db_path: &Path) -> PathBuf {
+    db_path.join(CURRENT)
+}
+
+pub(crate) fn manifest_file_name(number: u64) -> String {
+    format!("MANIFEST-{number:06}")
+}
+
+pub(crate) fn manifest_file(db_path: &Path, number: u64) -> PathBuf {
+    db_path.join(manifest_file_name(number))
+}
+
+pub(crate) fn log_file(db_path: &Path, number: u64) -> PathBuf {
+    db_path.join(format!("{number:06}.log"))
+}
+
+pub(crate) fn table_file_name(number: u64) -> String {
+    format!("{number:06}.sst")
+}
+
+pub(crate) fn table_file(db_path: &Path, number: u64) -> PathBuf {
+    db_path.join(table_file_name(number))
+}
+
+pub(crate) fn blob_file(db_path: &Path, number: u64) -> PathBuf {
+    db_path.join(format!("{number:06}.blob"))
+}
+
+pub(crate) fn temp_file(db_path: &Path, number: u64) -> PathBuf {
+    db_path.join(format!("{number:06}.dbtmp"))
+}
+
+/// Parses a file name of the DB directory. Returns None for files the DB does not own.
+pub(crate) fn parse_file_name(name: &str) -> Option<(FileType, u64)> {
+    if name == CURRENT {
+        return Some((FileType::Current, 0));
+    }
+    if let Some(number) = name.strip_prefix("MANIFEST-") {
+        return Some((FileType::Manifest, number.parse().ok()?));
+    }
+
+    let (number, extension) = name.split_once('.')?;
+    let number = number.parse().ok()?;
+    let file_type = match extension {
+        "log" => FileType::Log,
+        "sst" => FileType::Table,
+        "blob" => FileType::Blob,
+        "dbtmp" => FileType::Temp,
+        _ => return None,
+    };
+    Some((file_type, number))
+}
+
+/// Points CURRENT at the MANIFEST with the given number. The new contents are written to a temp file and renamed over CURRENT so a
+/// crash leaves either the old or the new MANIFEST current.
+pub(crate) fn set_current_file(db_path: &Path, manifest_number: u64) -> Result<()> {
+    let temp = temp_file(db_path, manifest_number);
+    {
+        let mut file = fs::File::create(&temp)?;
+        file.write_all(format!("{}\n", manifest_file_name(manifest_number)).as_bytes())?;
+        file.sync_all()?;
+    }
+    fs::rename(&temp, current_file(db_path))?;
+    Ok(())
+}
+
+/// Reads CURRENT and returns the number of the live MANIFEST.
+pub(crate) fn read_current_file(db_path: &Path) -> Result<u64> {
+    let contents = fs::read_to_string(current_file(db_path))?;
+    let name = contents
+        .strip_suffix('\n')
+        .ok_or_else(|| Error::Corruption("CURRENT file does not end with a newline".into()))?;
+
+    match parse_file_name(name) {
+        Some((FileType::Manifest, number)) => Ok(number),
+        _ => Err(Error::Corruption(format!(
+            "CURRENT names an invalid MANIFEST: {name}"
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_round_trip() {
+        let db = Path::new("/db");
+        for (path, file_type, number) in [
+            (manifest_file(db, 5), FileType::Manifest, 5),
+            (log_file(db, 12), FileType::Log, 12),
+            (table_file(db, 13), FileType::Table, 13),
+            (blob_file(db, 15), FileType::Blob, 15),
+            (temp_file(db, 14), FileType::Temp, 14),
+        ] {
+            let name = path.file_name().unwrap().to_str().unwrap();
+            assert_eq!(parse_file_name(name), Some((file_type, number)));
+        }
+
+        assert_eq!(parse_file_name("CURRENT"), Some((FileType::Current, 0)));
+        assert_eq!(parse_file_name("LOCK"), None);
+        assert_eq!(parse_file_name("abc.sst"), None);
+        assert_eq!(parse_file_name("000001.txt"), None);
+    }
+}
@@ -0,0 +1,168 @@
+//
+//
+//
+// Indexed Batch
+//
+// A Batch with an index from (column family, user key) to the offsets of its records so pending writes can be read back before the batch
+// is written. A lookup through the batch feeds its records into the GetContext like any other source, newest first and above every
+// written entry (MAX_SEQUENCE_NUMBER), so reads see the batch on top of the DB. A Merge in the batch leaves the lookup going into the DB
+// for older operands or a base value.
+
+use std::collections::BTreeMap;
+
+use crate::db::read_path::{GetContext, GetSource};
+use crate::db::write_batch::{Batch, BatchOpType};
+use crate::error::{Error, Result};
+use crate::key::internal_key::{InternalKeyRef, MAX_SEQUENCE_NUMBER, OperationType};
+
+pub(crate) struct IndexedBatch {
+    batch: Batch,
+    // Column family id -> user key -> offsets of its records, oldest first
+    index: BTreeMap<u32, BTreeMap<Vec<u8>, Vec<usize>>>,
+}
+
+impl Default for IndexedBatch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IndexedBatch {
+    pub(crate) fn new() -> Self {
+        Self {
+            batch: Batch::new(),
+            index: BTreeMap::new(),
+        }
+    }
+
+    /// Indexes the writes of an existing batch (e.g a prepared transaction read back from the WAL).
+    pub(crate) fn from_batch(batch: &Batch) -> Result<Self> {
+        let mut indexed = Self::new();
+        for record in batch.iter() {
+            let record = record?;
+            if record.op.is_marker() {
+                return Err(Error::InvalidArgument(format!(
+                    "{} is not a write",
+                    record.op
+                )));
+            }
+            indexed.add(record.op, record.cf_id, record.key, record.value);
+        }
+        Ok(indexed)
+    }
+
+    pub(crate) fn put(&mut self, cf_id: u32, key: &[u8], value: &[u8]) {
+        self.add(BatchOpType::Put, cf_id, key, value)
+    }
+
+    pub(crate) fn delete(&mut self, cf_id: u32, key: &[u8]) {
+        self.add(BatchOpType::Delete, cf_id, key, &[])
+    }
+
+    pub(crate) fn merge(&mut self, cf_id: u32, key: &[u8], value: &[u8]) {
+        self.add(BatchOpType::Merge, cf_id, key, value)
+    }
+
+    fn add(&mut self, op: BatchOpType, cf_id: u32, key: &[u8], value: &[u8]) {
+        let offset = self.batch.batch_size();
+        match op {
+            BatchOpType::Put => self.batch.put_cf(cf_id, key, value),
+            BatchOpType::Delete => self.batch.delete_cf(cf_id, key),
+            BatchOpType::Merge => self.batch.merge_cf(cf_id, key, value),
+            _ => unreachable!("only writes are indexed"),
+        }
+        self.index
+            .entry(cf_id)
+            .or_default()
+            .entry(key.to_vec())
+            .or_default()
+            .push(offset);
+    }
+
+    #[inline]
+    pub(crate) fn batch(&self) -> &Batch {
+        &self.batch
+    }
+
+    pub(crate) fn into_batch(self) -> Batch {
+        self.batch
+    }
+
+    #[inline]
+    pub(crate) fn is_empty(&self) -> bool {
+        self.batch.is_empty()
+    }
+
+    /// The writes to the column family as a read source - searched before the column family's own.
+    pub(crate) fn source(&self, cf_id: u32) -> IndexedBatchSource<'_> {
+        IndexedBatchSource { batch: self, cf_id }
+    }
+}
+
+pub(crate) struct IndexedBatchSource<'a> {
+    batch: &'a IndexedBatch,
+    cf_id: u32,
+}
+
+impl GetSource for IndexedBatchSource<'_> {
+    fn get(&self, _lookup_key: &[u8], ctx: &mut GetContext<'_>) -> bool {
+        let Some(offsets) = self
+            .batch
+            .index
+            .get(&self.cf_id)
+            .and_then(|keys| keys.get(ctx.user_key()))
+        else {
+            return false;
+        };
+
+        for offset in offsets.iter().rev() {
+            let record = match self.batch.batch.record_at(*offset) {
+                Ok((record, _)) => record,
+                Err(e) => {
+                    ctx.set_error(e);
+                    return true;
+                }
+            };
+            let ikey = InternalKeyRef {
+                user_key: record.key,
+                seq_no: MAX_SEQUENCE_NUMBER,
+                op: OperationType::from(record.op) as u8,
+            };
+            if !ctx.save_value(ikey, record.value) {
+                return true;
+            }
+        }
+
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::db::read_path::get;
+    use crate::key::lookup_key::LookUpInternalKey;
+
+    #[test]
+    fn newest_write_wins() {
+        let mut batch = IndexedBatch::new();
+        batch.put(0, b"a", b"1");
+        batch.put(0, b"a", b"2");
+        batch.put(1, b"b", b"other cf");
+        batch.delete(0, b"b");
+
+        let lookup = |cf_id: u32, key: &[u8]| {
+            let source = batch.source(cf_id);
+            let sources: [&dyn GetSource; 1] = [&source];
+            let lookup_key = LookUpInternalKey::new(key, 1, OperationType::Max);
+            get(&sources, lookup_key.as_ref(), None, 0).unwrap()
+        };
+
+        assert_eq!(lookup(0, b"a"), Some(b"2".to_vec()));
+        assert_eq!(lookup(0, b"b"), None);
+        assert_eq!(lookup(1, b"b"), Some(b"other cf".to_vec()));
+        assert_eq!(lookup(1, b"a"), None);
+        assert_eq!(batch.batch().batch_count(), 4);
+    }
+}
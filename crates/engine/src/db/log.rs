@@ -0,0 +1,180 @@
+//
+//
+//
+// Log Files
+//
+// Record log shared by the MANIFEST (and the WAL). A log is a sequence of records, each framed with its length and a checksum:
+//
+// | crc32c (4 bytes LE) | length (4 bytes LE) | payload ... |
+//
+// The checksum covers the length and the payload. Records are only ever appended, so a crash can only leave a torn record at the very
+// end of the log. The reader treats a truncated last record as the end of the log. A checksum mismatch is a Corruption - the bytes were
+// fully written and have changed since.
+
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::error::{Error, Result};
+use crate::utils::crc32c;
+
+pub(crate) const RECORD_HEADER_SIZE: usize = 8;
+
+fn record_crc(len: [u8; 4], payload: &[u8]) -> u32 {
+    crc32c::extend(crc32c::value(&len), payload)
+}
+
+pub(crate) struct LogWriter {
+    file: BufWriter<File>,
+    // Bytes written so far
+    size: u64,
+}
+
+impl LogWriter {
+    /// Creates (or truncates) the log at `path`.
+    pub(crate) fn create(path: &Path) -> Result<Self> {
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        Ok(Self {
+            file: BufWriter::new(file),
+            size: 0,
+        })
+    }
+
+    pub(crate) fn add_record(&mut self, payload: &[u8]) -> Result<()> {
+        let len = u32::try_from(payload.len())
+            .map_err(|_| Error::InvalidArgument("log record larger than 4GiB".into()))?
+            .to_le_bytes();
+
+        self.file
+            .write_all(&record_crc(len, payload).to_le_bytes())?;
+        self.file.write_all(&len)?;
+        self.file.write_all(payload)?;
+        self.size += (RECORD_HEADER_SIZE + payload.len()) as u64;
+        Ok(())
+    }
+
+    /// Hands buffered records to the OS without syncing - they survive a crash of the process but not of the machine.
+    pub(crate) fn flush(&mut self) -> Result<()> {
+        self.file.flush()?;
+        Ok(())
+    }
+
+    /// Flushes buffered records and syncs them to disk.
+    pub(crate) fn sync(&mut self) -> Result<()> {
+        self.file.flush()?;
+        self.file.get_ref().sync_data()?;
+        Ok(())
+    }
+
+    #[inline]
+    pub(crate) fn size(&self) -> u64 {
+        self.size
+    }
+}
+
+pub(crate) struct LogReader {
+    data: Vec<u8>,
+    offset: usize,
+    // Set once a torn record was found at the end of the log
+    truncated_tail: bool,
+}
+
+impl LogReader {
+    pub(crate) fn new(data: Vec<u8>) -> Self {
+        Self {
+            data,
+            offset: 0,
+            truncated_tail: false,
+        }
+    }
+
+    pub(crate) fn open(path: &Path) -> Result<Self> {
+        Ok(Self::new(std::fs::read(path)?))
+    }
+
+    /// Returns the next record, or None at the end of the log.
+    pub(crate) fn read_record(&mut self) -> Result<Option<&[u8]>> {
+        let rest = &self.data[self.offset..];
+        if rest.is_empty() {
+            return Ok(None);
+        }
+        if rest.len() < RECORD_HEADER_SIZE {
+            self.truncated_tail = true;
+            return Ok(None);
+        }
+
+        let crc = u32::from_le_bytes(rest[..4].try_into().unwrap());
+        let len: [u8; 4] = rest[4..8].try_into().unwrap();
+        let payload_len = u32::from_le_bytes(len) as usize;
+
+        if rest.len() - RECORD_HEADER_SIZE < payload_len {
+            self.truncated_tail = true;
+            return Ok(None);
+        }
+
+        let start = self.offset + RECORD_HEADER_SIZE;
+        let payload = &self.data[start..start + payload_len];
+        if record_crc(len, payload) != crc {
+            return Err(Error::Corruption(format!(
+                "log record checksum mismatch at offset {}",
+                self.offset
+            )));
+        }
+
+        self.offset = start + payload_len;
+        Ok(Some(payload))
+    }
+
+    #[inline]
+    pub(crate) fn truncated_tail(&self) -> bool {
+        self.truncated_tail
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(records: &[&[u8]]) -> Vec<u8> {
+        let mut out = Vec::new();
+        for payload in records {
+            let len = (payload.len() as u32).to_le_bytes();
+            out.extend_from_slice(&record_crc(len, payload).to_le_bytes());
+            out.extend_from_slice(&len);
+            out.extend_from_slice(payload);
+        }
+        out
+    }
+
+    #[test]
+    fn reads_records_and_stops_at_a_torn_tail() {
+        let data = encode(&[b"first", b"", b"third"]);
+
+        let mut reader = LogReader::new(data.clone());
+        assert_eq!(reader.read_record().unwrap(), Some(b"first".as_slice()));
+        assert_eq!(reader.read_record().unwrap(), Some(b"".as_slice()));
+        assert_eq!(reader.read_record().unwrap(), Some(b"third".as_slice()));
+        assert_eq!(reader.read_record().unwrap(), None);
+        assert!(!reader.truncated_tail());
+
+        let mut reader = LogReader::new(data[..data.len() - 2].to_vec());
+        reader.read_record().unwrap();
+        reader.read_record().unwrap();
+        assert_eq!(reader.read_record().unwrap(), None);
+        assert!(reader.truncated_tail());
+    }
+
+    #[test]
+    fn checksum_mismatch_is_corruption() {
+        let mut data = encode(&[b"record"]);
+        let last = data.len() - 1;
+        data[last] ^= 1;
+
+        let mut reader = LogReader::new(data);
+        assert!(matches!(reader.read_record(), Err(Error::Corruption(_))));
+    }
+}
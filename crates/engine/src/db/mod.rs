@@ -1,5 +1,11 @@
 pub(crate) mod db_impl;
+pub(crate) mod external_file_ingestion;
+pub(crate) mod filename;
+pub(crate) mod indexed_batch;
+pub(crate) mod log;
 pub(crate) mod read_path;
+pub(crate) mod wal;
 pub(crate) mod write_batch;
+pub(crate) mod write_callback;
 pub(crate) mod write_thread;
 pub(crate) mod writer;
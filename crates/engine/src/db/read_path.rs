@@ -1 +1,340 @@
+//
+//
+//
+// Read Path (Get)
+//
+// A point lookup walks the sources of a column family from newest to oldest:
+//
+//   mutable memtable -> immutable memtables (newest first) -> L0 files (newest first) -> L1 .. Ln
+//
+// Every source feeds the entries it finds for the user key (newest version first, at or below the read seq no) into a GetContext.
+// The GetContext is the state machine which decides when the lookup is complete:
+//
+//   Put     - a base value. If merge operands were collected they are applied on top of it.
+//   Delete  - the key is deleted (SingleDelete is read the same way). If merge operands were collected they are applied with no base.
+//   Merge   - push the operand and keep walking older entries/sources.
+//
+// A PutWithExpiry is read as a Put of its value until it expires and as a Delete from then on (see ttl.rs).
+//
+// Sources only need to call save_value() for each matching entry and stop as soon as it returns false, which keeps memtables and table
+// readers unaware of merge semantics.
+//
+// Range tombstones come from two places: the column family's RangeDelIndex, asked once per lookup, and the fragmented tombstones a
+// memtable or SST file keeps next to its entries, which the source applies before feeding its own entries. The context keeps the newest
+// covering tombstone seen so far and reads every entry below it as a Delete.
+//
+// On a column family with user-defined timestamps (see key/timestamp.rs) the lookup key carries the read timestamp, so sources start at
+// the newest version at or below it. Every version of the key matches the context whatever its timestamp, and versions written after the
+// read seq no are skipped - their timestamp alone does not hide them.
 
+use crate::error::{Error, Result};
+use crate::key::internal_key::{InternalKeyRef, OperationType};
+use crate::key::timestamp::strip_timestamp;
+use crate::merge::context::MergeContext;
+use crate::merge::helper::full_merge;
+use crate::merge::operator::MergeOperator;
+use crate::range::index::RangeDelVersion;
+use crate::ttl::read_expiring_value;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum GetState {
+    NotFound,
+    Found,
+    Deleted,
+    // Operands collected, still looking for a base value
+    Merge,
+    Corrupt,
+}
+
+pub(crate) struct GetContext<'a> {
+    user_key: &'a [u8],
+    merge_operator: Option<&'a dyn MergeOperator>,
+    state: GetState,
+    value: Vec<u8>,
+    merge_context: MergeContext,
+    // When false, operands and the base value are collected but never merged (get_merge_operands)
+    do_merge: bool,
+    base: Option<Vec<u8>>,
+    error: Option<Error>,
+    // Seq no of the newest range tombstone covering the key - entries below it are deleted
+    covering_seq: u64,
+    // Unix time the lookup reads at - entries which expired by then are deleted
+    now: u64,
+    // Seq no of the first entry saved - the newest write of the key the lookup reads
+    newest_seq: Option<u64>,
+    // Size of the timestamp the user keys of the entries end in - user_key has none
+    timestamp_size: usize,
+    // Entries written after this seq no are skipped
+    sequence: u64,
+}
+
+impl<'a> GetContext<'a> {
+    pub(crate) fn new(
+        user_key: &'a [u8],
+        merge_operator: Option<&'a dyn MergeOperator>,
+        now: u64,
+    ) -> Self {
+        Self {
+            user_key,
+            merge_operator,
+            state: GetState::NotFound,
+            value: Vec::new(),
+            merge_context: MergeContext::new(),
+            do_merge: true,
+            base: None,
+            error: None,
+            covering_seq: 0,
+            now,
+            newest_seq: None,
+            timestamp_size: 0,
+            sequence: u64::MAX,
+        }
+    }
+
+    /// Reads a column family whose user keys end in a `timestamp_size` byte timestamp at `sequence` - `user_key` is given without one.
+    pub(crate) fn with_timestamp(mut self, timestamp_size: usize, sequence: u64) -> Self {
+        self.timestamp_size = timestamp_size;
+        self.sequence = sequence;
+        self
+    }
+
+    // Expiry is not applied - an expiring base is returned as long as its value can be decoded
+    fn new_operand_collector(user_key: &'a [u8]) -> Self {
+        Self {
+            do_merge: false,
+            ..Self::new(user_key, None, 0)
+        }
+    }
+
+    #[inline]
+    pub(crate) fn state(&self) -> GetState {
+        self.state
+    }
+
+    #[inline]
+    pub(crate) fn user_key(&self) -> &[u8] {
+        self.user_key
+    }
+
+    /// True if the user key of an entry is a version of the key the lookup reads.
+    #[inline]
+    pub(crate) fn key_matches(&self, user_key: &[u8]) -> bool {
+        strip_timestamp(user_key, self.timestamp_size) == self.user_key
+    }
+
+    /// Entries of the key below `seq` are deleted by a range tombstone. Sources raise it as they are searched, a lower seq no is ignored.
+    #[inline]
+    pub(crate) fn raise_covering_seq(&mut self, seq: u64) {
+        self.covering_seq = self.covering_seq.max(seq);
+    }
+
+    /// Called by a source for each entry of the user key, newest first. Returns true if older entries are still needed.
+    pub(crate) fn save_value(&mut self, ikey: InternalKeyRef<'_>, value: &[u8]) -> bool {
+        debug_assert!(self.key_matches(ikey.user_key));
+        debug_assert!(matches!(self.state, GetState::NotFound | GetState::Merge));
+        if ikey.seq_no > self.sequence {
+            return true;
+        }
+        self.newest_seq.get_or_insert(ikey.seq_no);
+
+        let mut op = if ikey.seq_no < self.covering_seq {
+            OperationType::Delete
+        } else {
+            OperationType::from(ikey.op)
+        };
+        let mut value = value;
+        if op == OperationType::PutWithExpiry {
+            match read_expiring_value(value, self.now) {
+                Ok(Some(live)) => (op, value) = (OperationType::Put, live),
+                Ok(None) => op = OperationType::Delete,
+                Err(e) => {
+                    self.set_error(e);
+                    return false;
+                }
+            }
+        }
+
+        match op {
+            OperationType::Put => {
+                if !self.do_merge {
+                    self.base = Some(value.to_vec());
+                    self.state = GetState::Found;
+                } else if self.state == GetState::Merge {
+                    self.merge(Some(value));
+                } else {
+                    self.value.clear();
+                    self.value.extend_from_slice(value);
+                    self.state = GetState::Found;
+                }
+                false
+            }
+            OperationType::Delete | OperationType::SingleDelete => {
+                if self.do_merge && self.state == GetState::Merge {
+                    self.merge(None);
+                } else {
+                    self.state = GetState::Deleted;
+                }
+                false
+            }
+            OperationType::Merge => {
+                if self.do_merge && self.merge_operator.is_none() {
+                    self.state = GetState::Corrupt;
+                    self.error = Some(Error::NotSupported(
+                        "merge operand found but no merge operator is configured".into(),
+                    ));
+                    return false;
+                }
+                self.merge_context.push_operand(value);
+                self.state = GetState::Merge;
+                true
+            }
+            // Sources resolve blob references before saving them (see Version::get)
+            OperationType::BlobIndex => {
+                self.state = GetState::Corrupt;
+                self.error = Some(Error::Corruption("unresolved blob reference".into()));
+                false
+            }
+            OperationType::PutWithExpiry | OperationType::RangeDelete | OperationType::Max => {
+                unreachable!()
+            }
+        }
+    }
+
+    /// Fails the lookup - used when a source can't be read.
+    pub(crate) fn set_error(&mut self, error: Error) {
+        self.error = Some(error);
+        self.state = GetState::Corrupt;
+    }
+
+    fn merge(&mut self, base: Option<&[u8]>) {
+        let operands = self.merge_context.operands_oldest_first();
+        match full_merge(self.merge_operator, self.user_key, base, &operands) {
+            Ok(v) => {
+                self.value = v;
+                self.state = GetState::Found;
+            }
+            Err(e) => {
+                self.error = Some(e);
+                self.state = GetState::Corrupt;
+            }
+        }
+    }
+
+    /// Completes the lookup once every source has been searched (or a source finished it).
+    pub(crate) fn finish(mut self) -> Result<Option<Vec<u8>>> {
+        if self.state == GetState::Merge {
+            // Ran out of sources with operands and no base
+            self.merge(None);
+        }
+
+        match self.state {
+            GetState::Found => Ok(Some(self.value)),
+            GetState::NotFound | GetState::Deleted => Ok(None),
+            GetState::Corrupt => Err(self
+                .error
+                .unwrap_or_else(|| Error::Corruption("get failed".into()))),
+            GetState::Merge => unreachable!(),
+        }
+    }
+}
+
+/// A source of entries for point lookups (memtable, list of immutable memtables, table reader...).
+pub(crate) trait GetSource {
+    /// Feeds every entry of the lookup key at or below its seq no into the context, newest first.
+    /// Returns true if the context has finished (no older sources need to be searched).
+    fn get(&self, lookup_key: &[u8], ctx: &mut GetContext<'_>) -> bool;
+}
+
+/// Runs a point lookup over the sources in order (newest first). Entries which expired by `now` (unix seconds) are deleted.
+pub(crate) fn get(
+    sources: &[&dyn GetSource],
+    lookup_key: &[u8],
+    merge_operator: Option<&dyn MergeOperator>,
+    now: u64,
+) -> Result<Option<Vec<u8>>> {
+    let user_key = InternalKeyRef::from(lookup_key).user_key;
+    let mut ctx = GetContext::new(user_key, merge_operator, now);
+    search(sources, lookup_key, &mut ctx);
+    ctx.finish()
+}
+
+/// Runs a point lookup over the sources in order (newest first) with the range tombstones of the column family applied.
+pub(crate) fn get_with_range_del(
+    sources: &[&dyn GetSource],
+    range_del: &RangeDelVersion,
+    lookup_key: &[u8],
+    merge_operator: Option<&dyn MergeOperator>,
+    now: u64,
+) -> Result<Option<Vec<u8>>> {
+    let ikey = InternalKeyRef::from(lookup_key);
+    let mut ctx = GetContext::new(ikey.user_key, merge_operator, now);
+    ctx.raise_covering_seq(range_del.max_covering_seq(ikey.user_key, ikey.seq_no));
+    search(sources, lookup_key, &mut ctx);
+    ctx.finish()
+}
+
+/// Runs a point lookup on a column family with user-defined timestamps. The user key of `lookup_key` ends in the read timestamp - the
+/// newest version at or below it (and at or below the seq no) is read.
+pub(crate) fn get_with_timestamp(
+    sources: &[&dyn GetSource],
+    lookup_key: &[u8],
+    timestamp_size: usize,
+    merge_operator: Option<&dyn MergeOperator>,
+    now: u64,
+) -> Result<Option<Vec<u8>>> {
+    let ikey = InternalKeyRef::from(lookup_key);
+    let user_key = strip_timestamp(ikey.user_key, timestamp_size);
+    let mut ctx =
+        GetContext::new(user_key, merge_operator, now).with_timestamp(timestamp_size, ikey.seq_no);
+    search(sources, lookup_key, &mut ctx);
+    ctx.finish()
+}
+
+/// Seq no of the newest write of the key the lookup key reads - None if the key does not exist there.
+pub(crate) fn get_write_sequence(
+    sources: &[&dyn GetSource],
+    range_del: &RangeDelVersion,
+    lookup_key: &[u8],
+    merge_operator: Option<&dyn MergeOperator>,
+    now: u64,
+) -> Result<Option<u64>> {
+    let ikey = InternalKeyRef::from(lookup_key);
+    let mut ctx = GetContext::new(ikey.user_key, merge_operator, now);
+    ctx.raise_covering_seq(range_del.max_covering_seq(ikey.user_key, ikey.seq_no));
+    search(sources, lookup_key, &mut ctx);
+    let newest_seq = ctx.newest_seq;
+    Ok(ctx.finish()?.and(newest_seq))
+}
+
+fn search(sources: &[&dyn GetSource], lookup_key: &[u8], ctx: &mut GetContext<'_>) {
+    for source in sources {
+        if source.get(lookup_key, ctx) {
+            break;
+        }
+    }
+}
+
+/// Returns the merge operands of the key (oldest -> newest) without merging them. If a base value is found it is returned as the first
+/// element. Operands below a range tombstone are deleted. On a column family with timestamps the user key of `lookup_key` ends in the
+/// read timestamp. Intended for debugging merge operators.
+pub(crate) fn get_merge_operands(
+    sources: &[&dyn GetSource],
+    range_del: &RangeDelVersion,
+    lookup_key: &[u8],
+    timestamp_size: usize,
+) -> Result<Vec<Vec<u8>>> {
+    let ikey = InternalKeyRef::from(lookup_key);
+    let user_key = strip_timestamp(ikey.user_key, timestamp_size);
+    let mut ctx =
+        GetContext::new_operand_collector(user_key).with_timestamp(timestamp_size, ikey.seq_no);
+    ctx.raise_covering_seq(range_del.max_covering_seq(user_key, ikey.seq_no));
+    search(sources, lookup_key, &mut ctx);
+
+    let mut out = Vec::with_capacity(ctx.merge_context.num_operands() + 1);
+    if let Some(base) = ctx.base.take() {
+        out.push(base);
+    }
+    out.extend(ctx.merge_context.into_operands_oldest_first());
+
+    Ok(out)
+}
@@ -0,0 +1,270 @@
+//
+//
+//
+// Write Ahead Log
+//
+// Every batch is appended to the live WAL (a log file, see log.rs) before it is applied to the memtables: one record per batch, in its
+// binary form, with the seq no of its first write in the header. Range deletions are replayed into the RangeDelIndex of their column
+// family - a flush logs the ones it releases the WAL of to the MANIFEST. On open every WAL left in the directory is replayed into the memtables
+// (skipping column families which are gone, or whose log number says the log's writes are already in their files) and a new WAL is
+// started. WAL files are deleted once nothing needs them (DbImpl::purge_obsolete_wal_files).
+//
+// Two phase commit
+//
+// Preparing a transaction logs its writes between BeginPrepare and EndPrepare(name) without applying them. The decision is logged later:
+// Rollback(name), or Commit(name) followed by the writes again, at the seq nos they are applied with. Replay keeps prepared sections
+// aside until their decision and drops them once it is read - a commit record replays on its own, so a decided section is never needed
+// again. Sections still undecided at the end of the last WAL are handed to the application (RecoveredPrepared), which decides them.
+//
+// A WAL holding an undecided prepared section is needed whatever the column families say - LogsWithPrep counts the sections per log.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::column_family::cf::ColumnFamilySet;
+use crate::db::filename::{FileType, log_file, parse_file_name};
+use crate::db::log::{LogReader, LogWriter};
+use crate::db::write_batch::{Batch, BatchOpType, BatchRecord};
+use crate::error::{Error, Result};
+use crate::key::internal_key::OperationType;
+use crate::key::lookup_key::LookUpInternalKey;
+
+pub(crate) struct Wal {
+    writer: LogWriter,
+    number: u64,
+}
+
+impl Wal {
+    pub(crate) fn create(db_path: &Path, number: u64) -> Result<Self> {
+        Ok(Self {
+            writer: LogWriter::create(&log_file(db_path, number))?,
+            number,
+        })
+    }
+
+    #[inline]
+    pub(crate) fn number(&self) -> u64 {
+        self.number
+    }
+
+    /// Bytes appended so far - every one of them has been handed to the OS.
+    #[inline]
+    pub(crate) fn size(&self) -> u64 {
+        self.writer.size()
+    }
+
+    /// Appends the batch. Without `sync` it is only handed to the OS.
+    pub(crate) fn add(&mut self, batch: &Batch, sync: bool) -> Result<()> {
+        self.writer.add_record(batch.data())?;
+        if sync {
+            self.writer.sync()
+        } else {
+            self.writer.flush()
+        }
+    }
+}
+
+/// Number of undecided prepared sections per WAL.
+#[derive(Default)]
+pub(crate) struct LogsWithPrep {
+    counts: BTreeMap<u64, usize>,
+}
+
+impl LogsWithPrep {
+    pub(crate) fn add(&mut self, log_number: u64) {
+        *self.counts.entry(log_number).or_default() += 1;
+    }
+
+    pub(crate) fn remove(&mut self, log_number: u64) {
+        if let Some(count) = self.counts.get_mut(&log_number) {
+            *count -= 1;
+            if *count == 0 {
+                self.counts.remove(&log_number);
+            }
+        }
+    }
+
+    /// The oldest WAL with an undecided prepared section.
+    pub(crate) fn min(&self) -> Option<u64> {
+        self.counts.keys().next().copied()
+    }
+}
+
+/// A transaction prepared before the DB was closed which was neither committed nor rolled back.
+pub(crate) struct RecoveredPrepared {
+    pub(crate) name: String,
+    pub(crate) batch: Batch,
+    // The WAL holding the prepared section
+    pub(crate) log_number: u64,
+}
+
+pub(crate) struct RecoveredWal {
+    pub(crate) last_sequence: u64,
+    // Oldest first
+    pub(crate) prepared: Vec<RecoveredPrepared>,
+}
+
+/// Numbers of the WAL files in the directory, oldest first.
+pub(crate) fn wal_files(db_path: &Path) -> Result<Vec<u64>> {
+    let mut numbers = Vec::new();
+    for entry in fs::read_dir(db_path)? {
+        let name = entry?.file_name();
+        if let Some((FileType::Log, number)) = name.to_str().and_then(parse_file_name) {
+            numbers.push(number);
+        }
+    }
+    numbers.sort_unstable();
+    Ok(numbers)
+}
+
+/// Replays the WAL files of the DB into the memtables of the column families. `last_sequence` is the MANIFEST's.
+pub(crate) fn recover(
+    db_path: &Path,
+    column_families: &mut ColumnFamilySet,
+    last_sequence: u64,
+) -> Result<RecoveredWal> {
+    let mut last_sequence = last_sequence;
+    let mut prepared: HashMap<String, RecoveredPrepared> = HashMap::new();
+
+    for number in wal_files(db_path)? {
+        let mut reader = LogReader::open(&log_file(db_path, number))?;
+        // A torn record at the end of a WAL is a write which was never acknowledged
+        while let Some(payload) = reader.read_record()? {
+            let batch = Batch::from_data(payload.to_vec())?;
+            if let Some(last) = replay_batch(&batch, number, column_families, &mut prepared)? {
+                last_sequence = last_sequence.max(last);
+            }
+        }
+    }
+
+    let mut prepared: Vec<RecoveredPrepared> = prepared.into_values().collect();
+    prepared.sort_by(|a, b| (a.log_number, &a.name).cmp(&(b.log_number, &b.name)));
+    Ok(RecoveredWal {
+        last_sequence,
+        prepared,
+    })
+}
+
+// Returns the seq no of the last write the batch applied, if it applied any
+fn replay_batch(
+    batch: &Batch,
+    log_number: u64,
+    column_families: &mut ColumnFamilySet,
+    prepared: &mut HashMap<String, RecoveredPrepared>,
+) -> Result<Option<u64>> {
+    let corrupt = |msg: &str| Error::Corruption(format!("WAL {log_number}: {msg}"));
+
+    let mut sequence = batch.sequence();
+    // Writes of the prepare section being read
+    let mut section: Option<Batch> = None;
+
+    for record in batch.iter() {
+        let record = record?;
+        match record.op {
+            BatchOpType::BeginPrepare => {
+                if section.replace(Batch::new()).is_some() {
+                    return Err(corrupt("nested BeginPrepare"));
+                }
+            }
+            BatchOpType::EndPrepare => {
+                let writes = section
+                    .take()
+                    .ok_or_else(|| corrupt("EndPrepare without BeginPrepare"))?;
+                let name =
+                    marker_name(&record).ok_or_else(|| corrupt("invalid transaction name"))?;
+                prepared.insert(
+                    name.clone(),
+                    RecoveredPrepared {
+                        name,
+                        batch: writes,
+                        log_number,
+                    },
+                );
+            }
+            // The writes of a commit follow its marker and are applied like any others
+            BatchOpType::Commit | BatchOpType::Rollback => {
+                let name =
+                    marker_name(&record).ok_or_else(|| corrupt("invalid transaction name"))?;
+                prepared.remove(&name);
+            }
+            BatchOpType::Put
+            | BatchOpType::Delete
+            | BatchOpType::Merge
+            | BatchOpType::PutWithExpiry
+            | BatchOpType::DeleteRange => match section.as_mut() {
+                Some(writes) => writes.push(&record),
+                None => {
+                    apply(column_families, log_number, &record, sequence)?;
+                    sequence += 1;
+                }
+            },
+        }
+    }
+
+    if section.is_some() {
+        return Err(corrupt("BeginPrepare without EndPrepare"));
+    }
+    Ok((sequence > batch.sequence()).then(|| sequence - 1))
+}
+
+fn marker_name(record: &BatchRecord<'_>) -> Option<String> {
+    String::from_utf8(record.key.to_vec()).ok()
+}
+
+fn apply(
+    column_families: &mut ColumnFamilySet,
+    log_number: u64,
+    record: &BatchRecord<'_>,
+    sequence: u64,
+) -> Result<()> {
+    // Column families created after the log never have writes in it, dropped ones are skipped
+    if let Some(cfd) = column_families.get(record.cf_id).cloned()
+        && cfd.log_number() <= log_number
+    {
+        if record.op == BatchOpType::DeleteRange {
+            return cfd.delete_range(
+                record.key,
+                record.value,
+                sequence,
+                || column_families.assign_memtable_id(),
+                &Arc::default(),
+            );
+        }
+        let key = LookUpInternalKey::new(record.key, sequence, OperationType::from(record.op));
+        cfd.insert(
+            key.as_ref(),
+            record.value,
+            || column_families.assign_memtable_id(),
+            &Arc::default(),
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn logs_with_prep_counts_sections() {
+        let mut logs = LogsWithPrep::default();
+        assert_eq!(logs.min(), None);
+
+        logs.add(7);
+        logs.add(4);
+        logs.add(4);
+        assert_eq!(logs.min(), Some(4));
+
+        logs.remove(4);
+        assert_eq!(logs.min(), Some(4));
+        logs.remove(4);
+        assert_eq!(logs.min(), Some(7));
+        // Unknown logs are ignored
+        logs.remove(1);
+        logs.remove(7);
+        assert_eq!(logs.min(), None);
+    }
+}
@@ -4,6 +4,10 @@ use std::{
     ptr,
 };
 
+use crate::column_family::cf::DEFAULT_COLUMN_FAMILY_ID;
+use crate::error::{Error, Result};
+use crate::key::internal_key::OperationType;
+use crate::ttl::encode_expiring_value;
 use crate::utils::{self, var_int::VarInt};
 
 //
@@ -24,6 +28,13 @@ use crate::utils::{self, var_int::VarInt};
 // Operation:
 // | op_type (1 byte) | cf_if (4 bytes) | key_len (VarInt) | key ... | value_len (VarInt) | value ... |
 //
+// Two phase commit markers use the same layout with the transaction name as the key and no value. They are only ever written to the WAL
+// and are never applied to a memtable:
+//
+//   prepare:  | BeginPrepare | op ... op | EndPrepare(name) |     the transaction's writes, logged but not applied
+//   commit:   | Commit(name) | op ... op |                      the writes again, applied from the header seq no
+//   rollback: | Rollback(name) |
+//
 //
 // A batch holds a set of operations to be committed atomically as part of the write path.
 // Each operation is binary encoded and appended to a contiguous Vec<u8> buffer.
@@ -97,12 +108,19 @@ const BATCH_COUNT_OFFSET: usize = size_of::<u64>(); // count starts at byte 8
 const HEADER_SIZE: usize = size_of::<u64>() + size_of::<u32>(); // = 12
 
 #[repr(align(8))]
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub(crate) enum BatchOpType {
     Put = 1,
     Delete = 2,
     Merge = 3,
-    // XXXX:
+    PutWithExpiry = 6,
+    // Range tombstone - the key is the start and the value the (exclusive) end. Applied to the column family's RangeDelIndex
+    DeleteRange = 15,
+    // Two phase commit markers - after the OperationType range so they are never mistaken for an entry
+    BeginPrepare = 16,
+    EndPrepare = 17,
+    Commit = 18,
+    Rollback = 19,
 }
 
 impl fmt::Display for BatchOpType {
@@ -117,16 +135,74 @@ impl fmt::Display for BatchOpType {
             Self::Merge => {
                 write!(f, "Merge")
             }
+            Self::PutWithExpiry => {
+                write!(f, "PutWithExpiry")
+            }
+            Self::DeleteRange => {
+                write!(f, "DeleteRange")
+            }
+            Self::BeginPrepare => {
+                write!(f, "BeginPrepare")
+            }
+            Self::EndPrepare => {
+                write!(f, "EndPrepare")
+            }
+            Self::Commit => {
+                write!(f, "Commit")
+            }
+            Self::Rollback => {
+                write!(f, "Rollback")
+            }
         }
     }
 }
 
 impl BatchOpType {
     pub(crate) fn into(self) -> u8 {
-        match self {
-            Self::Put => 1,
-            Self::Delete => 2,
-            Self::Merge => 3,
+        self as u8
+    }
+
+    /// Two phase commit markers carry no write.
+    #[inline]
+    pub(crate) fn is_marker(self) -> bool {
+        !matches!(
+            self,
+            Self::Put | Self::Delete | Self::Merge | Self::PutWithExpiry | Self::DeleteRange
+        )
+    }
+}
+
+impl From<BatchOpType> for OperationType {
+    fn from(op: BatchOpType) -> Self {
+        match op {
+            BatchOpType::Put => OperationType::Put,
+            BatchOpType::Delete => OperationType::Delete,
+            BatchOpType::Merge => OperationType::Merge,
+            BatchOpType::PutWithExpiry => OperationType::PutWithExpiry,
+            BatchOpType::DeleteRange => OperationType::RangeDelete,
+            BatchOpType::BeginPrepare
+            | BatchOpType::EndPrepare
+            | BatchOpType::Commit
+            | BatchOpType::Rollback => unreachable!("two phase commit markers are never applied"),
+        }
+    }
+}
+
+impl TryFrom<u8> for BatchOpType {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self> {
+        match value {
+            1 => Ok(Self::Put),
+            2 => Ok(Self::Delete),
+            3 => Ok(Self::Merge),
+            6 => Ok(Self::PutWithExpiry),
+            15 => Ok(Self::DeleteRange),
+            16 => Ok(Self::BeginPrepare),
+            17 => Ok(Self::EndPrepare),
+            18 => Ok(Self::Commit),
+            19 => Ok(Self::Rollback),
+            _ => Err(Error::Corruption(format!("unknown batch op type {value}"))),
         }
     }
 }
@@ -210,9 +286,81 @@ impl Batch {
     }
 
     pub(crate) fn put_bytes(&mut self, key: &[u8], value: &[u8]) {
+        self.push_record(BatchOpType::Put, DEFAULT_COLUMN_FAMILY_ID, key, value)
+    }
+
+    pub(crate) fn put_cf(&mut self, cf_id: u32, key: &[u8], value: &[u8]) {
+        self.push_record(BatchOpType::Put, cf_id, key, value)
+    }
+
+    // The value stops being visible at `expires_at` (unix seconds) - see ttl.rs
+    pub(crate) fn put_cf_with_expiry(
+        &mut self,
+        cf_id: u32,
+        key: &[u8],
+        value: &[u8],
+        expires_at: u64,
+    ) {
+        let value = encode_expiring_value(value, expires_at);
+        self.push_record(BatchOpType::PutWithExpiry, cf_id, key, &value)
+    }
+
+    // The key gets `ts` as its timestamp - the column family's comparator must have timestamps of its size (see key/timestamp.rs)
+    pub(crate) fn put_cf_with_timestamp(
+        &mut self,
+        cf_id: u32,
+        key: &[u8],
+        ts: &[u8],
+        value: &[u8],
+    ) {
+        self.push_record(BatchOpType::Put, cf_id, &[key, ts].concat(), value)
+    }
+
+    pub(crate) fn delete<K: AsRef<[u8]>>(&mut self, key: K) {
+        self.push_record(
+            BatchOpType::Delete,
+            DEFAULT_COLUMN_FAMILY_ID,
+            key.as_ref(),
+            &[],
+        )
+    }
+
+    pub(crate) fn delete_cf(&mut self, cf_id: u32, key: &[u8]) {
+        self.push_record(BatchOpType::Delete, cf_id, key, &[])
+    }
+
+    // Deletes every key in [start, end) written before the batch
+    pub(crate) fn delete_range_cf(&mut self, cf_id: u32, start: &[u8], end: &[u8]) {
+        self.push_record(BatchOpType::DeleteRange, cf_id, start, end)
+    }
+
+    // Hides the versions of the key up to `ts` from reads at or above it
+    pub(crate) fn delete_cf_with_timestamp(&mut self, cf_id: u32, key: &[u8], ts: &[u8]) {
+        self.push_record(BatchOpType::Delete, cf_id, &[key, ts].concat(), &[])
+    }
+
+    // Merge records an operand for the key which is resolved by the column family's MergeOperator on read
+    pub(crate) fn merge<K, V>(&mut self, key: K, value: V)
+    where
+        K: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+    {
+        self.push_record(
+            BatchOpType::Merge,
+            DEFAULT_COLUMN_FAMILY_ID,
+            key.as_ref(),
+            value.as_ref(),
+        )
+    }
+
+    pub(crate) fn merge_cf(&mut self, cf_id: u32, key: &[u8], value: &[u8]) {
+        self.push_record(BatchOpType::Merge, cf_id, key, value)
+    }
+
+    fn push_record(&mut self, op: BatchOpType, cf_id: u32, key: &[u8], value: &[u8]) {
         // Write to batch buffer
-        self.data.push(BatchOpType::Put.into());
-        self.data.extend_from_slice(&0u32.to_le_bytes());
+        self.data.push(op.into());
+        self.data.extend_from_slice(&cf_id.to_le_bytes());
         self.data
             .extend_from_slice(VarInt::new(key.len() as u32).as_slice());
         self.data.extend_from_slice(key);
@@ -245,19 +393,158 @@ impl Batch {
     }
 
     pub(crate) fn is_empty(&self) -> bool {
-        self.data.len() == 0
+        self.batch_count() == 0
+    }
+
+    /// A batch read back from its binary representation (e.g a WAL record).
+    pub(crate) fn from_data(data: Vec<u8>) -> Result<Self> {
+        if data.len() < HEADER_SIZE {
+            return Err(Error::Corruption(format!(
+                "batch of {} bytes is smaller than its header",
+                data.len()
+            )));
+        }
+        Ok(Self {
+            data,
+            max_bytes: Self::MAX_BATCH_SIZE,
+        })
+    }
+
+    /// The binary representation - header and records.
+    #[inline]
+    pub(crate) fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Appends the records of `other` (its header is not copied).
+    pub(crate) fn append(&mut self, other: &Batch) {
+        self.data.extend_from_slice(&other.data[HEADER_SIZE..]);
+        self.set_count(self.batch_count() + other.batch_count());
+    }
+
+    /// Appends one record - used to copy records from another batch.
+    pub(crate) fn push(&mut self, record: &BatchRecord<'_>) {
+        self.push_record(record.op, record.cf_id, record.key, record.value)
+    }
+
+    /// The WAL record of a prepared transaction: its records between BeginPrepare and EndPrepare(name).
+    pub(crate) fn prepare_record(name: &str, batch: &Batch) -> Batch {
+        let mut record = Batch::new();
+        record.push_record(BatchOpType::BeginPrepare, 0, &[], &[]);
+        record.append(batch);
+        record.push_record(BatchOpType::EndPrepare, 0, name.as_bytes(), &[]);
+        record
+    }
+
+    /// The marker deciding a prepared transaction - Commit or Rollback. A commit's writes are appended after it.
+    pub(crate) fn marker_record(op: BatchOpType, name: &str) -> Batch {
+        debug_assert!(matches!(op, BatchOpType::Commit | BatchOpType::Rollback));
+        let mut record = Batch::new_with_capacity(HEADER_SIZE + name.len() + 16);
+        record.push_record(op, 0, name.as_bytes(), &[]);
+        record
+    }
+
+    fn set_count(&mut self, count: u32) {
+        self.data[BATCH_COUNT_OFFSET..BATCH_COUNT_OFFSET + 4].copy_from_slice(&count.to_le_bytes());
+    }
+
+    /// Seq no of the first record - the write path assigns the records consecutive seq nos from it.
+    pub(crate) fn sequence(&self) -> u64 {
+        u64::from_le_bytes(
+            self.data[SEQ_NO_OFFSET..SEQ_NO_OFFSET + 8]
+                .try_into()
+                .unwrap(),
+        )
+    }
+
+    pub(crate) fn set_sequence(&mut self, sequence: u64) {
+        self.data[SEQ_NO_OFFSET..SEQ_NO_OFFSET + 8].copy_from_slice(&sequence.to_le_bytes());
+    }
+
+    /// The records in the order they were added.
+    pub(crate) fn iter(&self) -> BatchIter<'_> {
+        BatchIter {
+            batch: self,
+            offset: HEADER_SIZE,
+        }
+    }
+
+    /// Decodes the record starting at `offset` (the batch size before it was added). Returns it with the offset of the next record.
+    pub(crate) fn record_at(&self, offset: usize) -> Result<(BatchRecord<'_>, usize)> {
+        let corrupt = || Error::Corruption(format!("malformed batch record at offset {offset}"));
+
+        let buf = self.data.get(offset..).ok_or_else(corrupt)?;
+        if buf.len() < 5 {
+            return Err(corrupt());
+        }
+        let op = BatchOpType::try_from(buf[0])?;
+        let cf_id = u32::from_le_bytes(buf[1..5].try_into().unwrap());
+
+        let mut pos = 5;
+        let slice = |pos: &mut usize| -> Result<&[u8]> {
+            let (len, read) = VarInt::try_decode(&buf[*pos..]).ok_or_else(corrupt)?;
+            let start = *pos + read;
+            let end = start + len as usize;
+            let bytes = buf.get(start..end).ok_or_else(corrupt)?;
+            *pos = end;
+            Ok(bytes)
+        };
+        let key = slice(&mut pos)?;
+        let value = slice(&mut pos)?;
+
+        Ok((
+            BatchRecord {
+                op,
+                cf_id,
+                key,
+                value,
+            },
+            offset + pos,
+        ))
     }
 
     // TOOD: Apply_batch()
 
     pub(crate) fn apply_batch(&self /*column family resolver, seq_no, flush_scheduler */) {}
 
-    //
-    // TODO: Batch Iterator
-
     // NOTE: Can we defer creation until commit and then build the vec?
 }
 
+/// One operation of a batch. Delete records have an empty value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct BatchRecord<'a> {
+    pub(crate) op: BatchOpType,
+    pub(crate) cf_id: u32,
+    pub(crate) key: &'a [u8],
+    pub(crate) value: &'a [u8],
+}
+
+pub(crate) struct BatchIter<'a> {
+    batch: &'a Batch,
+    offset: usize,
+}
+
+impl<'a> Iterator for BatchIter<'a> {
+    type Item = Result<BatchRecord<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.offset >= self.batch.data.len() {
+            return None;
+        }
+        match self.batch.record_at(self.offset) {
+            Ok((record, next)) => {
+                self.offset = next;
+                Some(Ok(record))
+            }
+            Err(e) => {
+                // Nothing after a malformed record can be trusted
+                self.offset = self.batch.data.len();
+                Some(Err(e))
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -279,4 +566,100 @@ mod tests {
 
         // DB::put(word, value: "");
     }
+
+    #[test]
+    fn merge_record() {
+        let mut batch = Batch::new();
+
+        batch.put("counter", "1");
+        batch.merge("counter", "2");
+
+        assert_eq!(batch.batch_count(), 2);
+
+        // | op | cf_id (4) | key_len | key (7) | value_len | value (1) |
+        let second = HEADER_SIZE + 1 + 4 + 1 + 7 + 1 + 1;
+        assert_eq!(batch.data[second], BatchOpType::Merge.into());
+    }
+
+    #[test]
+    fn iterate_records() {
+        let mut batch = Batch::new();
+        assert!(batch.is_empty());
+
+        batch.put("a", "1");
+        batch.delete_cf(2, b"b");
+        batch.merge_cf(7, b"c", &[b'x'; 200]);
+        batch.set_sequence(42);
+
+        assert_eq!(batch.sequence(), 42);
+        assert_eq!(batch.batch_count(), 3);
+
+        let records: Vec<BatchRecord<'_>> = batch.iter().map(|r| r.unwrap()).collect();
+        assert_eq!(
+            records,
+            vec![
+                BatchRecord {
+                    op: BatchOpType::Put,
+                    cf_id: 0,
+                    key: b"a",
+                    value: b"1",
+                },
+                BatchRecord {
+                    op: BatchOpType::Delete,
+                    cf_id: 2,
+                    key: b"b",
+                    value: b"",
+                },
+                BatchRecord {
+                    op: BatchOpType::Merge,
+                    cf_id: 7,
+                    key: b"c",
+                    value: &[b'x'; 200],
+                },
+            ]
+        );
+
+        // A truncated record is reported once and ends the iteration
+        batch.data.truncate(batch.data.len() - 1);
+        let mut iter = batch.iter();
+        assert!(iter.next().unwrap().is_ok());
+        assert!(iter.next().unwrap().is_ok());
+        assert!(matches!(iter.next(), Some(Err(Error::Corruption(_)))));
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn prepare_and_commit_records() {
+        let mut writes = Batch::new();
+        writes.put("a", "1");
+        writes.delete_cf(2, b"b");
+
+        let prepare =
+            Batch::from_data(Batch::prepare_record("txn", &writes).data().to_vec()).unwrap();
+        let ops: Vec<(BatchOpType, Vec<u8>)> = prepare
+            .iter()
+            .map(|r| r.map(|r| (r.op, r.key.to_vec())).unwrap())
+            .collect();
+        assert_eq!(
+            ops,
+            vec![
+                (BatchOpType::BeginPrepare, Vec::new()),
+                (BatchOpType::Put, b"a".to_vec()),
+                (BatchOpType::Delete, b"b".to_vec()),
+                (BatchOpType::EndPrepare, b"txn".to_vec()),
+            ]
+        );
+
+        let mut commit = Batch::marker_record(BatchOpType::Commit, "txn");
+        commit.append(&writes);
+        assert_eq!(commit.batch_count(), 3);
+        let first = commit.iter().next().unwrap().unwrap();
+        assert!(first.op.is_marker());
+        assert_eq!(first.key, b"txn");
+
+        assert!(matches!(
+            Batch::from_data(vec![0; 3]),
+            Err(Error::Corruption(_))
+        ));
+    }
 }
@@ -0,0 +1,16 @@
+//
+//
+//
+// Write Callback
+//
+// A callback runs in the write path once the writer holds the right to assign seq nos and before its batch is applied. No other write
+// can land in between, so the callback sees the DB exactly as the batch will be applied on top of it. An error fails the write and
+// nothing of the batch is applied - optimistic transactions validate their reads this way.
+
+use crate::db::db_impl::DbImpl;
+use crate::error::Result;
+
+pub(crate) trait WriteCallback {
+    /// Must not write to the DB or take its locks (the column families are reachable through the handles the caller holds).
+    fn callback(&self, db: &DbImpl) -> Result<()>;
+}
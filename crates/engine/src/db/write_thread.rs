@@ -39,7 +39,6 @@ use std::{ptr, sync::atomic::AtomicPtr};
 
 use crate::db::writer::WriterState;
 
-use super::write_batch::Batch;
 use super::writer::Writer;
 
 pub(crate) struct WriteGroup {
@@ -61,6 +60,22 @@ impl WriteGroup {
             writers: 0,
         }
     }
+
+    /// Number of writers in the group, the leader included.
+    pub(crate) fn len(&self) -> usize {
+        self.writers as usize
+    }
+
+    /// The writers of the group in the order they joined, the leader first.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &Writer> {
+        let last_writer = self.last_writer;
+        std::iter::successors(Some(self.leader.as_ptr()), move |&w| {
+            // SAFETY: The writers up to `last_writer` were linked by the leader and are alive until it exits the group
+            (w != last_writer).then(|| unsafe { *(*w).group_next.get() })
+        })
+        // SAFETY: As above
+        .map(|w| unsafe { &*w })
+    }
 }
 
 /// WriteThread is the coordination mechanism for multiple writes. Each calling thread will creater a writer holding a batch of operations and try to join
@@ -177,7 +192,7 @@ impl WriteThread {
             if older.is_null()
                 // # SAFETY:
                 // if older was null we will have hit the first conditional check, therefore, older is safe to dereference here
-                || !(unsafe { (*older).group_next.get().is_null() })
+                || !(unsafe { (*(*older).group_next.get()).is_null() })
             {
                 debug_assert!(
                     (older.is_null()) || unsafe { *(*older).group_next.get() == current }
@@ -192,143 +207,128 @@ impl WriteThread {
         }
     }
 
+    /// Links the writer into the queue. The first writer in the queue leads the next group, any other waits until its group's leader
+    /// completed its write or handed it the leadership of the group after it.
+    pub(crate) fn join_batch_group(&self, writer: &Writer) {
+        // Raw pointer form used for the intrusive queue. Lifetime is governed by the stack-writer invariant above.
+        let w = ptr::from_ref(writer).cast_mut();
+
+        if self.link_writer(w) {
+            writer.set_state(WriterState::LEADER);
+        } else {
+            writer.wait();
+        }
+
+        debug_assert!(
+            writer.is_leader() || writer.state.load(Ordering::Acquire) & WriterState::COMPLETE != 0
+        );
+    }
+
     // Method to enter group as leader
     // https://github.com/facebook/rocksdb/blob/763401b5/db/write_thread.cc#L440
-    pub(crate) fn EnterBatchGroup(&self, leader: NonNull<Writer>, write_group: &mut WriteGroup) {
-        //
-        //
+    //
+    /// Forms the leader's group - the leader and the writers which joined after it, in order, up to the first which can't be
+    /// batched with it. That writer leads the next group once this one exits.
+    pub(crate) fn enter_as_batch_group_leader(&self, leader: &Writer) -> WriteGroup {
+        debug_assert!(leader.is_leader());
 
-        // SAFETY:
-        // `leader` is `NonNull`, and `batch` is initialized during writer
-        // construction and immutable after publication. Reading batch metadata
-        // does not race with any concurrent mutation.
-        let size = unsafe { leader.as_ref().batch.as_ref().batch_size() };
+        let leader_ptr = ptr::from_ref(leader).cast_mut();
+        let mut write_group = WriteGroup::new(leader_ptr);
+
+        let size = leader.batch().batch_size();
 
         // Limit the max size if the leader's batch is smaller than MIN_BATCH_GROUP_SIZE so that small writes are not
         // slowed by group mechanics
         let mut max_size = WriteThread::MAX_BATCH_SIZE_PER_GROUP;
-        if size <= WriteThread::MAX_BATCH_SIZE_PER_GROUP {
+        if size <= WriteThread::MIN_BATCH_SIZE_PER_GROUP {
             max_size = size + WriteThread::MIN_BATCH_SIZE_PER_GROUP;
         }
 
-        write_group.size = 1;
+        write_group.size = size as u64;
         write_group.writers = 1;
         // Set last writer as leader for now until we process next writers in the group and reach newest_writer (last in group) to then set last_writer.
-        write_group.last_writer = leader.as_ptr();
+        write_group.last_writer = leader_ptr;
+
+        if !leader.allow_batching {
+            return write_group;
+        }
 
         // Get the newest_writer to use to link newer writers in the group
         let newest_writer = self.newest_writer.load(Ordering::Acquire);
 
         self.set_new_links(newest_writer);
 
-        // Traverse the WriteGroup in contextual order (oldest->newest) and decide if we need to remove writers and append to end (next group)
-
-        let mut w = leader.as_ptr();
-        let mut we = leader.as_ptr();
-        let mut r: *mut Writer = ptr::null_mut();
-        let mut re: *mut Writer = ptr::null_mut();
-
+        // Traverse the writers in contextual order (oldest->newest) and stop at the first which is not compatible with the leader
+        let mut w = leader_ptr;
         while w != newest_writer {
-            debug_assert!(!unsafe { *(*w).group_next.get() }.is_null());
-            //
             // SAFETY:
-            // `w` is part of the current materialized execution chain.
-            // `group_next` has been initialized by `set_new_links()` before
-            // entering this loop, so reading it yields either the next writer
-            // in this group or null at the group boundary.
-            w = unsafe { *(*w).group_next.get() };
-
-            // Compatibility checks
-
-            // SAFETY:
-            //
-            // `w` traverses the materialized execution chain for this batch group,
-            // starting at `leader` and advancing through `group_next` until the
-            // snapshot `newest_writer` is reached.
-            //
-            // All writers in this chain remain live while linked into `WriteThread`,
-            // and writer metadata (`batch`, `sync`, write options) is immutable after
-            // publication, so reading these fields is race-free.
-            //
-            // This method is executed by the sole current batch-group leader. No other
-            // thread mutates `link_older` or `group_next` for writers in this selected
-            // group while this loop is active.
-            //
-            // Therefore it is sound to:
-            //
-            // - traverse writers via `group_next`
-            // - inspect writer metadata for compatibility checks
-            // - splice rejected writers out of the execution chain by rewiring
-            //   `link_older` and `group_next`
-            // - append rejected writers to `r_list` for handoff into the next group.
-            unsafe {
-                // Don't group empty batches
-                if (*w).batch.as_ref().is_empty() ||
-                    // Remove batches which breach our max size
-                    (*w).batch.as_ref().batch_size() > max_size ||
-                    // If sync modes do not match with leader, remove
-                    (*w).sync != (*leader.as_ptr()).sync
-                // TODO: Add other conditions
-                {
-                    //
-                    // Remove from the list by
-                    //
-                    // We take the next and previous writer's of current and re-link them so that they each skip the current writer
-                    //
-                    // Linking the current's older writer to current's newer writer so current's older skips current
-                    // W4 --------> W3 --------> W2 --------> W1
-                    //              |           |           |
-                    //         link_newer <- current -> link_older
-                    //               <---------<------------┚
-                    //               link so W1 skips current
-
-                    let older = *(*w).link_older.get();
-                    let newer = *(*w).group_next.get();
-
-                    // Set the current's older writer's group_next to the writer after current so current is skipped
-                    *(*older).group_next.get() = newer;
-
-                    // Do the inverse of above
-                    if !newer.is_null() {
-                        *(*newer).link_older.get() = older;
-                    }
-
-                    // Insert current into r_list
-
-                    break;
-                };
+            // `w` is part of the current materialized execution chain. `group_next` has been initialized by `set_new_links()` up
+            // to the snapshot `newest_writer`, and every writer in the chain stays alive until its group's leader exits - which is
+            // us or a leader after us. Writer metadata (`batch`, `sync`, `allow_batching`) is immutable after publication.
+            let next = unsafe { &**(*w).group_next.get() };
+            let batch = next.batch();
+
+            // Don't group empty batches, batches which breach our max size, writers which must write alone or whose sync mode
+            // does not match with the leader's
+            if !next.allow_batching
+                || batch.is_empty()
+                || next.sync != leader.sync
+                || write_group.size as usize + batch.batch_size() > max_size
+            {
+                break;
             }
 
-            // compatable check
-            // append rejected_list
-            // fix r_list links
-            // update write group
-            //
-
-            break;
+            w = ptr::from_ref(next).cast_mut();
+            write_group.size += batch.batch_size() as u64;
+            write_group.writers += 1;
+            write_group.last_writer = w;
         }
 
-        todo!()
+        write_group
     }
 
-    pub(crate) fn join(&self, writer: &Writer) {
-        //
-        // Raw pointer form used for the intrusive queue. Lifetime is governed by
-        // WriteThread::join's stack-writer invariant.
-        let w = ptr::from_ref(writer).cast_mut();
-
-        let linked_writer = self.link_writer(w);
-
-        if linked_writer {
-            debug_assert!(writer.is_leader());
+    /// Hands the leadership to the writer after the group - if one joined since - and completes the followers of the group, whose
+    /// results the leader set before.
+    pub(crate) fn exit_as_batch_group_leader(&self, write_group: WriteGroup) {
+        let leader = write_group.leader.as_ptr();
+        let last_writer = write_group.last_writer;
+
+        // No writer joined after the group, the queue is empty and the next writer to join leads
+        if self
+            .newest_writer
+            .compare_exchange(
+                last_writer,
+                ptr::null_mut(),
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            )
+            .is_err()
+        {
+            let newest_writer = self.newest_writer.load(Ordering::Acquire);
+            self.set_new_links(newest_writer);
 
-            let mut write_group = WriteGroup::new(w);
+            // SAFETY:
+            // The last writer is alive until we complete it below, and `set_new_links()` linked it to the writer after it. That
+            // writer is waiting, so it is alive until we make it leader - its `link_older` is cut first so its group never walks
+            // back into ours.
+            unsafe {
+                let next = *(*last_writer).group_next.get();
+                debug_assert!(!next.is_null());
+                *(*next).link_older.get() = ptr::null_mut();
+                (*next).set_state(WriterState::LEADER);
+            }
+        }
 
-            // Continue as Leader
-            //
-            //
-        } else {
-            writer.wait();
+        // Complete the followers newest first. A follower may return (and drop its writer) as soon as it is COMPLETE, so its older
+        // link is read before.
+        let mut w = last_writer;
+        while w != leader {
+            // SAFETY: `w` is a follower of the group which is not COMPLETE yet
+            unsafe {
+                let older = *(*w).link_older.get();
+                (*w).set_state(WriterState::COMPLETE);
+                w = older;
+            }
         }
     }
 }
@@ -337,13 +337,115 @@ impl WriteThread {
 
 #[cfg(test)]
 mod tests {
+    use crate::db::write_batch::Batch;
     use crate::db::writer::WriterState;
 
     use super::*;
-    use std::sync::atomic::{AtomicU8, Ordering};
+    use std::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
     use std::thread::{self};
     use std::time::Duration;
 
+    fn put_batch() -> Batch {
+        let mut batch = Batch::new();
+        batch.put_cf(0, b"key", b"value");
+        batch
+    }
+
+    // Spins until a writer joined after `newest`
+    fn wait_for_join(write_thread: &WriteThread, newest: *mut Writer) {
+        while write_thread.newest_writer.load(Ordering::Acquire) == newest {
+            thread::yield_now();
+        }
+    }
+
+    #[test]
+    fn every_writer_is_completed_by_its_leader() {
+        const THREADS: usize = 8;
+        const WRITES: usize = 500;
+
+        let write_thread = WriteThread::new();
+        let written = AtomicUsize::new(0);
+        let groups = AtomicUsize::new(0);
+
+        thread::scope(|t| {
+            for _ in 0..THREADS {
+                t.spawn(|| {
+                    for _ in 0..WRITES {
+                        let batch = put_batch();
+                        let writer = Writer::new(&batch);
+                        write_thread.join_batch_group(&writer);
+                        if writer.is_leader() {
+                            let group = write_thread.enter_as_batch_group_leader(&writer);
+                            assert_eq!(group.iter().count(), group.len());
+                            for (i, w) in group.iter().enumerate() {
+                                unsafe { w.set_result(Ok(i as u64)) };
+                            }
+                            written.fetch_add(group.len(), Ordering::Relaxed);
+                            groups.fetch_add(1, Ordering::Relaxed);
+                            write_thread.exit_as_batch_group_leader(group);
+                            assert_eq!(writer.result().unwrap(), 0);
+                        } else {
+                            // A follower is never first in its group
+                            assert!(writer.result().unwrap() > 0);
+                        }
+                    }
+                });
+            }
+        });
+
+        assert_eq!(written.load(Ordering::Relaxed), THREADS * WRITES);
+        assert!(groups.load(Ordering::Relaxed) <= THREADS * WRITES);
+        assert!(write_thread.newest_writer.load(Ordering::Acquire).is_null());
+    }
+
+    #[test]
+    fn writers_without_batching_lead_their_own_group() {
+        let write_thread = WriteThread::new();
+        let led_alone = AtomicUsize::new(0);
+
+        let batch = put_batch();
+        let leader = Writer::new(&batch);
+        write_thread.join_batch_group(&leader);
+        assert!(leader.is_leader());
+        let leader_ptr = ptr::from_ref(&leader).cast_mut();
+
+        let write = |writer: Writer| {
+            write_thread.join_batch_group(&writer);
+            assert!(writer.is_leader());
+            let group = write_thread.enter_as_batch_group_leader(&writer);
+            if group.len() == 1 {
+                led_alone.fetch_add(1, Ordering::Relaxed);
+            }
+            write_thread.exit_as_batch_group_leader(group);
+        };
+
+        thread::scope(|t| {
+            // Queue: leader <- alone <- batched
+            let joined = |newest| wait_for_join(&write_thread, newest);
+            let write = &write;
+
+            t.spawn(move || {
+                let batch = put_batch();
+                write(Writer::new(&batch).without_batching());
+            });
+            joined(leader_ptr);
+            let alone = write_thread.newest_writer.load(Ordering::Acquire);
+            t.spawn(move || {
+                let batch = put_batch();
+                write(Writer::new(&batch));
+            });
+            joined(alone);
+
+            // The leader stops at the writer which can't be batched, which leads next and stops before the one after it
+            let group = write_thread.enter_as_batch_group_leader(&leader);
+            assert_eq!(group.len(), 1);
+            write_thread.exit_as_batch_group_leader(group);
+        });
+
+        assert_eq!(led_alone.load(Ordering::Relaxed), 2);
+        assert!(write_thread.newest_writer.load(Ordering::Acquire).is_null());
+    }
+
     // TODO: Need to make this deterministic with while loop so we can enforce thread join order
     #[test]
     fn writer_follower_to_leader() {
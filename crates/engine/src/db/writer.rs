@@ -9,6 +9,7 @@ use crate::db::{
     write_batch::Batch,
     write_thread::{WriteGroup, WriteThread},
 };
+use crate::error::Result;
 
 #[non_exhaustive]
 pub(super) struct WriterState;
@@ -42,9 +43,12 @@ pub(crate) struct Writer {
     // Thread handle to unpark waiting followers
     pub(super) thread_handle: Thread,
     pub(super) write_group: *const WriteGroup,
+    // Seq no of the first record of the batch, or why it was not written - set by the leader before the writer is COMPLETE
+    result: UnsafeCell<Result<u64>>,
     // Options
-    // seq_no_first: u64,
-    // status: WriterStatus,
+    // A writer which is not batched leads a group of its own, so its caller can run work (e.g a WriteCallback) with no other write
+    // in between
+    pub(super) allow_batching: bool,
     pub(super) sync: bool,
     // slow_down: bool,
     // disable_wal: bool,
@@ -70,15 +74,50 @@ impl Writer {
     pub(crate) fn new(batch: &Batch) -> Self {
         Self {
             batch: NonNull::from(batch),
-            state: AtomicU8::new(0),
+            state: AtomicU8::new(WriterState::INIT),
             link_older: UnsafeCell::new(ptr::null_mut()),
             group_next: UnsafeCell::new(ptr::null_mut()),
             thread_handle: thread::current(),
             write_group: ptr::null(),
+            result: UnsafeCell::new(Ok(0)),
+            allow_batching: true,
             sync: true,
         }
     }
 
+    /// The writer leads a group of its own instead of joining another leader's.
+    pub(crate) fn without_batching(mut self) -> Self {
+        self.allow_batching = false;
+        self
+    }
+
+    #[inline]
+    pub(crate) fn batch(&self) -> &Batch {
+        // SAFETY: The caller of new() keeps the batch alive and unchanged while the writer is in the write thread
+        unsafe { self.batch.as_ref() }
+    }
+
+    /// Seq no the leader wrote the batch at, or the error it failed with. Only read once the writer led its group or is COMPLETE.
+    pub(crate) fn result(&self) -> Result<u64> {
+        // SAFETY: Written by the leader before it sets COMPLETE (or by this thread as the leader), never again afterwards
+        unsafe { (*self.result.get()).clone() }
+    }
+
+    /// # Safety
+    ///
+    /// Only the leader of the writer's group calls this, before the writer is COMPLETE.
+    pub(crate) unsafe fn set_result(&self, result: Result<u64>) {
+        unsafe { *self.result.get() = result };
+    }
+
+    /// Sets the state bits and wakes the writer's thread if it blocked. The writer may be gone once COMPLETE is set.
+    pub(super) fn set_state(&self, state: u8) {
+        let thread = self.thread_handle.clone();
+        if self.state.fetch_or(state, Ordering::AcqRel) & WriterState::LOCKED_WAITING != 0 {
+            thread.unpark();
+        }
+    }
+
     /// wait() is used when the calling thread of a write has joined the write_thread and becomes a follower in the group.
     ///
     /// It must wait and block until the leader completes the write pipeline.
@@ -103,8 +142,11 @@ impl Writer {
         //
         // This is inspired by Rocks code see: https://github.com/facebook/rocksdb/blob/763401b595c8c1647908356e42525aadd0b90eae/db/write_thread.cc#L64
 
-        for _ in 0..200 {
-            if self.state.load(Ordering::Acquire) & WriterState::COMPLETE != 0 {
+        // A follower either has its write completed by the leader or is handed the leadership of the next group
+        let done = WriterState::COMPLETE | WriterState::LEADER;
+
+        for _ in 0..WriteThread::WAIT_PAUSE_ITERATIONS {
+            if self.state.load(Ordering::Acquire) & done != 0 {
                 return;
             }
             std::hint::spin_loop();
@@ -115,7 +157,7 @@ impl Writer {
         for _ in 0..WriteThread::YIELD_PAUSE_ITERATIONS {
             // XXX: Later if benchmarking shows contention, we can do what rocks did and add a predictive credit
             // based yield to determine if we should yield or fall through to block
-            if self.state.load(Ordering::Acquire) & WriterState::COMPLETE != 0 {
+            if self.state.load(Ordering::Acquire) & done != 0 {
                 return;
             }
             thread::yield_now();
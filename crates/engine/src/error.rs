@@ -0,0 +1,45 @@
+//
+//
+//
+// Error is the status type returned across the engine. Variants carry a short message describing where the error was raised so that
+// errors surfaced from deep inside an iterator tree or a compaction are still actionable.
+
+use std::fmt::Display;
+
+#[derive(Debug, Clone)]
+pub enum Error {
+    // Data read from memory or disk does not match what was written, or an operation on it failed (e.g a merge)
+    Corruption(String),
+    InvalidArgument(String),
+    NotSupported(String),
+    // A file system operation failed
+    IO(String),
+    // The operation conflicted with a concurrent one and can be retried (e.g a transaction whose reads were overwritten, or whose lock
+    // would deadlock)
+    Busy(String),
+    // A wait (e.g for a lock) gave up
+    TimedOut(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Corruption(msg) => write!(f, "Corruption: {msg}"),
+            Error::InvalidArgument(msg) => write!(f, "Invalid argument: {msg}"),
+            Error::NotSupported(msg) => write!(f, "Not supported: {msg}"),
+            Error::IO(msg) => write!(f, "IO error: {msg}"),
+            Error::Busy(msg) => write!(f, "Resource busy: {msg}"),
+            Error::TimedOut(msg) => write!(f, "Operation timed out: {msg}"),
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::IO(e.to_string())
+    }
+}
+
+impl std::error::Error for Error {}
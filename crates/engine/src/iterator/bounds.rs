@@ -0,0 +1,150 @@
+//
+//
+//
+// IterBounds
+//
+// The user key range [lower, upper) of an iterator (ReadOptions::iterate_lower_bound / iterate_upper_bound). One IterBounds is shared by
+// the whole iterator tree so every layer can stop early on its own:
+//
+//   DBIter        - never returns a key outside the range and stops as soon as the inner iterator leaves it
+//   LevelIterator - doesn't open files which are entirely outside the range (file smallest / largest keys)
+//   TableIter     - doesn't read data blocks which are entirely outside the range (index separators)
+//
+// The layers below DBIter may still return a few keys outside the range (e.g the rest of a block which is already loaded), the bounds
+// only save them the IO. DBIter is the layer which enforces them.
+
+use std::cmp::Ordering;
+use std::sync::Arc;
+
+use crate::key::comparator::Comparator;
+use crate::options::ReadOptions;
+
+pub(crate) struct IterBounds {
+    user_comparator: Arc<dyn Comparator>,
+    // The user keys checked end in a timestamp, the bounds don't (see key/timestamp.rs)
+    has_timestamp: bool,
+    lower: Option<Vec<u8>>,
+    upper: Option<Vec<u8>>,
+}
+
+impl IterBounds {
+    pub(crate) fn new(
+        user_comparator: Arc<dyn Comparator>,
+        lower: Option<Vec<u8>>,
+        upper: Option<Vec<u8>>,
+    ) -> Self {
+        Self {
+            has_timestamp: user_comparator.timestamp_size() > 0,
+            user_comparator,
+            lower,
+            upper,
+        }
+    }
+
+    /// Returns None if the options don't bound the iterator.
+    pub(crate) fn from_read_options(
+        options: &ReadOptions,
+        user_comparator: Arc<dyn Comparator>,
+    ) -> Option<Arc<Self>> {
+        if options.iterate_lower_bound.is_none() && options.iterate_upper_bound.is_none() {
+            return None;
+        }
+        Some(Arc::new(Self::new(
+            user_comparator,
+            options.iterate_lower_bound.clone(),
+            options.iterate_upper_bound.clone(),
+        )))
+    }
+
+    #[inline]
+    pub(crate) fn lower(&self) -> Option<&[u8]> {
+        self.lower.as_deref()
+    }
+
+    #[inline]
+    pub(crate) fn upper(&self) -> Option<&[u8]> {
+        self.upper.as_deref()
+    }
+
+    /// True if the user key is at or past the (exclusive) upper bound.
+    #[inline]
+    pub(crate) fn past_upper(&self, user_key: &[u8]) -> bool {
+        self.upper
+            .as_deref()
+            .is_some_and(|upper| self.compare(user_key, upper) != Ordering::Less)
+    }
+
+    /// True if the user key is before the (inclusive) lower bound.
+    #[inline]
+    pub(crate) fn before_lower(&self, user_key: &[u8]) -> bool {
+        self.lower
+            .as_deref()
+            .is_some_and(|lower| self.compare(user_key, lower) == Ordering::Less)
+    }
+
+    #[inline]
+    fn compare(&self, user_key: &[u8], bound: &[u8]) -> Ordering {
+        self.user_comparator
+            .compare_without_timestamp(user_key, self.has_timestamp, bound, false)
+    }
+
+    /// True if a file or block spanning the internal keys [smallest, largest] may hold keys inside the bounds.
+    pub(crate) fn overlaps(&self, smallest: &[u8], largest: &[u8]) -> bool {
+        !self.past_upper(user_key(smallest)) && !self.before_lower(user_key(largest))
+    }
+}
+
+#[inline]
+pub(crate) fn user_key(internal_key: &[u8]) -> &[u8] {
+    &internal_key[..internal_key.len() - 8]
+}
+
+// Helpers for the iterators which take an optional bounds
+#[inline]
+pub(crate) fn past_upper(bounds: &Option<Arc<IterBounds>>, internal_key: &[u8]) -> bool {
+    bounds
+        .as_ref()
+        .is_some_and(|b| b.past_upper(user_key(internal_key)))
+}
+
+#[inline]
+pub(crate) fn before_lower(bounds: &Option<Arc<IterBounds>>, internal_key: &[u8]) -> bool {
+    bounds
+        .as_ref()
+        .is_some_and(|b| b.before_lower(user_key(internal_key)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key::comparator::DefaultComparator;
+    use crate::key::internal_key::{OperationType, encode_trailer};
+
+    fn ikey(user_key: &[u8]) -> Vec<u8> {
+        [user_key, &encode_trailer(1, OperationType::Put)].concat()
+    }
+
+    #[test]
+    fn bounds() {
+        let bounds = IterBounds::new(
+            DefaultComparator::new(),
+            Some(b"c".to_vec()),
+            Some(b"f".to_vec()),
+        );
+
+        assert!(bounds.before_lower(b"b"));
+        assert!(!bounds.before_lower(b"c"));
+        assert!(!bounds.past_upper(b"e"));
+        assert!(bounds.past_upper(b"f"));
+
+        assert!(bounds.overlaps(&ikey(b"a"), &ikey(b"c")));
+        assert!(bounds.overlaps(&ikey(b"e"), &ikey(b"z")));
+        assert!(!bounds.overlaps(&ikey(b"a"), &ikey(b"bz")));
+        assert!(!bounds.overlaps(&ikey(b"f"), &ikey(b"z")));
+
+        assert!(
+            IterBounds::from_read_options(&ReadOptions::default(), DefaultComparator::new())
+                .is_none()
+        );
+    }
+}
@@ -5,8 +5,8 @@
 //
 // The first option is simple and something to be used purely for testing.
 //
-// With the other two options, I would like to use feature flags for static compilation, however, we can also use a runtime approach by
-// passing in an arena if we want the iterator to use an arena for allocation
+// The choice is made at runtime rather than with feature flags: DbImpl builds its iterators with a PooledIter over its IterAllocPool,
+// and DbOptions::max_pooled_iter_allocs = 0 turns the pool off so every iterator allocates like the first (see iterator/iter_alloc.rs)
 //
 // DBIter (top-level)
 //     ├── MergeIterator
@@ -14,22 +14,844 @@
 //     │       ├── ChildIter 2
 //     │       └── ...
 
-use std::marker::PhantomData;
+//
+// DBIter is the user-facing iterator. The merging iterator below it yields every version of every key in internal key order, DBIter
+// collapses that stream into user keys:
+//
+//  - entries newer than the read sequence are invisible
+//  - only the newest visible version of each user key is returned, older versions are skipped
+//  - Delete / SingleDelete hide the key (and everything older)
+//  - a version covered by a range tombstone is read as a Delete. The tombstones come from the column family's RangeDelIndex and from
+//    the memtables and SST files below the merging iterator, which are collected once when the DBIter is created
+//  - Merge operands are collected down to a base value (or a tombstone / the end of the key) and resolved with the MergeOperator
+//  - a PutWithExpiry is a Put of its value until it expires and a Delete from then on (see ttl.rs). Its value is copied out without
+//    the expiry
+//
+// Reverse iteration sees the versions of a user key oldest first, so prev() walks every entry of the key and keeps the newest visible
+// one (resolving merges as it goes) before deciding whether the key is live. The value is copied out as the inner iterator has moved on.
+//
+// Bounds (ReadOptions::iterate_lower_bound / iterate_upper_bound) are enforced here - a seek below the lower bound starts at it, and the
+// scan stops at the first user key outside the bounds without reading any further. With ReadOptions::prefix_same_as_start the scan
+// also stops at the first key whose prefix differs from the seek target's (seek_to_first / seek_to_last take the prefix of the first
+// key found).
+//
+// On a column family with user-defined timestamps (see key/timestamp.rs) the versions of a user key differ in timestamp as well as seq no.
+// A version is visible if both are at or below the read ones (ReadOptions::timestamp, every timestamp without one) - key() returns the
+// user key without its timestamp and timestamp() the timestamp of the version read.
+//
+// The current user key is saved in an IterKey which is re-used across the whole scan, so moving the iterator doesn't allocate unless a
+// merge has to be resolved or the scan is in reverse.
+
+use std::cmp::Ordering;
+use std::mem;
+use std::sync::Arc;
+
+use crate::error::Error;
+use crate::iterator::bounds::IterBounds;
+use crate::iterator::internal_iterator::InternalIterator;
+use crate::iterator::iter_alloc::{HeapIter, IterAlloc, IterAllocStrategy, IterBox};
+use crate::iterator::merge_iterator::MergingIterator;
+use crate::key::comparator::Comparator;
+use crate::key::internal_key::{InternalKeyRef, MAX_SEQUENCE_NUMBER, OperationType};
+use crate::key::iter_key::InternalIterKey;
+use crate::key::prefix_extractor::PrefixExtractor;
+use crate::key::timestamp::{strip_timestamp, timestamp_of};
+use crate::merge::context::MergeContext;
+use crate::merge::helper::full_merge;
+use crate::merge::operator::MergeOperator;
+use crate::range::fragment::FragmentedTombstones;
+use crate::range::index::RangeDelVersion;
+use crate::ttl::read_expiring_value;
+
+// Where the inner iterator is relative to the current user key:
+//   Forward - on the current entry, or past the key if the value is saved
+//   Reverse - on the last entry of the previous user key (or invalid)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    Forward,
+    Reverse,
+}
+
+pub(crate) struct DBIter<'a, S: IterAllocStrategy = HeapIter> {
+    strategy: S,
+    // Arena the children of iter live in (and the buffers below when they are not in use) - handed back to the strategy on drop
+    alloc: IterAlloc,
+    iter: MergingIterator<'a>,
+    user_comparator: Arc<dyn Comparator>,
+    merge_operator: Option<&'a dyn MergeOperator>,
+    // Read sequence - newer entries are invisible
+    sequence: u64,
+    // Size of the timestamp at the end of every user key (0 without timestamps)
+    timestamp_size: usize,
+    // Read timestamp - versions with a newer timestamp are invisible
+    timestamp: Vec<u8>,
+    // Unix time the scan reads at - entries which expired by then are deleted
+    now: u64,
+    // Pinned for the life of the iterator
+    range_del: Option<Arc<RangeDelVersion>>,
+    // Tombstones of the sources below iter, one entry per memtable / file which has any
+    range_tombstones: Vec<Arc<FragmentedTombstones>>,
+    // Set if the tombstones of a source couldn't be read - the iterator is never valid
+    range_tombstones_error: Option<Error>,
+    bounds: Option<Arc<IterBounds>>,
+    // Only set with prefix_same_as_start
+    prefix_extractor: Option<Arc<dyn PrefixExtractor>>,
+    // Prefix the scan is restricted to - only read when prefix_set is set
+    prefix: Vec<u8>,
+    prefix_set: bool,
+    direction: Direction,
+    // Internal key of the current entry (user key + trailer). Also used to build seek keys
+    saved_key: InternalIterKey,
+    // Value copied out of the inner iterator (merge result or reverse scan) - only read when value_saved is set
+    saved_value: Vec<u8>,
+    value_saved: bool,
+    merge_context: MergeContext,
+    valid: bool,
+    status: Option<Error>,
+}
+
+impl<'a> DBIter<'a, HeapIter> {
+    /// `bounds` must be the same bounds the children of `iter` were built with. `prefix_extractor` is only given when the read options
+    /// set prefix_same_as_start.
+    pub(crate) fn new(
+        iter: MergingIterator<'a>,
+        user_comparator: Arc<dyn Comparator>,
+        merge_operator: Option<&'a dyn MergeOperator>,
+        sequence: u64,
+        bounds: Option<Arc<IterBounds>>,
+        prefix_extractor: Option<Arc<dyn PrefixExtractor>>,
+    ) -> Self {
+        let mut db_iter = Self::from_alloc(
+            HeapIter::default(),
+            IterAlloc::new(),
+            iter,
+            user_comparator,
+            merge_operator,
+            sequence,
+        );
+        db_iter.bounds = bounds;
+        db_iter.prefix_extractor = prefix_extractor;
+        db_iter
+    }
+}
+
+impl<'a, S: IterAllocStrategy> DBIter<'a, S> {
+    fn from_alloc(
+        strategy: S,
+        mut alloc: IterAlloc,
+        iter: MergingIterator<'a>,
+        user_comparator: Arc<dyn Comparator>,
+        merge_operator: Option<&'a dyn MergeOperator>,
+        sequence: u64,
+    ) -> Self {
+        let mut range_tombstones = Vec::new();
+        let range_tombstones_error = iter.range_tombstones(&mut range_tombstones).err();
+        let timestamp_size = user_comparator.timestamp_size();
+
+        Self {
+            saved_key: mem::take(&mut alloc.saved_key),
+            saved_value: mem::take(&mut alloc.saved_value),
+            prefix: mem::take(&mut alloc.prefix),
+            merge_context: mem::take(&mut alloc.merge_context),
+            strategy,
+            alloc,
+            iter,
+            user_comparator,
+            merge_operator,
+            sequence,
+            timestamp_size,
+            timestamp: vec![u8::MAX; timestamp_size],
+            now: 0,
+            range_del: None,
+            range_tombstones,
+            range_tombstones_error,
+            bounds: None,
+            prefix_extractor: None,
+            prefix_set: false,
+            direction: Direction::Forward,
+            value_saved: false,
+            valid: false,
+            status: None,
+        }
+    }
+
+    /// Reads through the range tombstones of the version.
+    pub(crate) fn with_range_del(mut self, range_del: Arc<RangeDelVersion>) -> Self {
+        self.range_del = (!range_del.is_empty()).then_some(range_del);
+        self
+    }
+
+    /// Reads the versions at or below `timestamp` - without one every version up to the read sequence is visible.
+    pub(crate) fn with_timestamp(mut self, timestamp: Option<Vec<u8>>) -> Self {
+        if let Some(timestamp) = timestamp {
+            debug_assert_eq!(timestamp.len(), self.timestamp_size);
+            self.timestamp = timestamp;
+        }
+        self
+    }
+
+    /// Reads expiring entries at unix time `now`. Without it nothing has expired.
+    pub(crate) fn with_now(mut self, now: u64) -> Self {
+        self.now = now;
+        self
+    }
+
+    // Copies the value of the PutWithExpiry the inner iterator is on into saved_value. Returns Delete if it has expired, None (with the
+    // status set) if it can't be read
+    fn save_expiring_value(&mut self) -> Option<OperationType> {
+        match read_expiring_value(self.iter.value(), self.now) {
+            Ok(Some(value)) => {
+                self.saved_value.clear();
+                self.saved_value.extend_from_slice(value);
+                Some(OperationType::Put)
+            }
+            Ok(None) => Some(OperationType::Delete),
+            Err(e) => {
+                self.status = Some(e);
+                None
+            }
+        }
+    }
+
+    // Versions of the user key below this seq no are range deleted
+    #[inline]
+    fn covering_seq(&self, user_key: &[u8]) -> u64 {
+        let cmp = self.user_comparator.as_ref();
+        self.range_tombstones
+            .iter()
+            .map(|t| t.max_covering_seq(cmp, user_key, self.sequence))
+            .chain(
+                self.range_del
+                    .as_ref()
+                    .map(|r| r.max_covering_seq(user_key, self.sequence)),
+            )
+            .max()
+            .unwrap_or(0)
+    }
+
+    // User key of the saved entry, with its timestamp
+    #[inline]
+    fn saved_user_key(&self) -> &[u8] {
+        let key = self.saved_key.as_slice();
+        &key[..key.len() - 8]
+    }
+
+    // Orders user keys which end in a timestamp by the key alone
+    #[inline]
+    fn compare_user_keys(&self, a: &[u8], b: &[u8]) -> Ordering {
+        let has_ts = self.timestamp_size > 0;
+        self.user_comparator
+            .compare_without_timestamp(a, has_ts, b, has_ts)
+    }
+
+    // True if the entry is at or below the read sequence and timestamp
+    #[inline]
+    fn visible(&self, ikey: &InternalKeyRef<'_>) -> bool {
+        ikey.seq_no <= self.sequence
+            && (self.timestamp_size == 0
+                || timestamp_of(ikey.user_key, self.timestamp_size) <= self.timestamp.as_slice())
+    }
+
+    #[inline]
+    fn reset(&mut self, direction: Direction) {
+        self.status = None;
+        self.value_saved = false;
+        self.direction = direction;
+    }
+
+    pub(crate) fn seek_to_first(&mut self) {
+        self.reset(Direction::Forward);
+        self.prefix_set = false;
+
+        match self.bounds.as_ref().and_then(|b| b.lower()) {
+            Some(lower) => {
+                set_seek_key(
+                    &mut self.saved_key,
+                    lower,
+                    &self.timestamp,
+                    self.sequence,
+                    OperationType::Max,
+                );
+                self.iter.seek(self.saved_key.as_slice());
+            }
+            None => self.iter.seek_to_first(),
+        }
+        self.find_next_user_entry(false);
+
+        if self.valid {
+            self.set_prefix_from_saved_key();
+        }
+    }
+
+    pub(crate) fn seek_to_last(&mut self) {
+        self.reset(Direction::Reverse);
+        self.prefix_set = false;
+
+        match self.bounds.as_ref().and_then(|b| b.upper()) {
+            Some(upper) => {
+                // Sorts before every entry of the (exclusive) upper bound
+                set_seek_key(
+                    &mut self.saved_key,
+                    upper,
+                    &vec![u8::MAX; self.timestamp_size],
+                    MAX_SEQUENCE_NUMBER,
+                    OperationType::Max,
+                );
+                self.iter.seek_for_prev(self.saved_key.as_slice());
+            }
+            None => self.iter.seek_to_last(),
+        }
+        self.find_prev_user_entry();
+
+        if self.valid {
+            self.set_prefix_from_saved_key();
+        }
+    }
+
+    /// Positions at the first user key >= `user_key`.
+    pub(crate) fn seek(&mut self, user_key: &[u8]) {
+        self.reset(Direction::Forward);
+        self.set_prefix(user_key);
+
+        let target = match self.bounds.as_ref().and_then(|b| b.lower()) {
+            Some(lower)
+                if self
+                    .user_comparator
+                    .compare_without_timestamp(user_key, false, lower, false)
+                    == Ordering::Less =>
+            {
+                lower
+            }
+            _ => user_key,
+        };
+        // Max sorts before every entry of the user key at the read sequence (and timestamp) so the seek lands on the newest visible
+        // version
+        set_seek_key(
+            &mut self.saved_key,
+            target,
+            &self.timestamp,
+            self.sequence,
+            OperationType::Max,
+        );
+        self.iter.seek(self.saved_key.as_slice());
+        self.find_next_user_entry(false);
+    }
+
+    /// Positions at the last user key <= `user_key`.
+    pub(crate) fn seek_for_prev(&mut self, user_key: &[u8]) {
+        self.reset(Direction::Reverse);
+        self.set_prefix(user_key);
+
+        match self.bounds.as_ref().and_then(|b| b.upper()) {
+            Some(upper)
+                if self
+                    .user_comparator
+                    .compare_without_timestamp(user_key, false, upper, false)
+                    != Ordering::Less =>
+            {
+                set_seek_key(
+                    &mut self.saved_key,
+                    upper,
+                    &vec![u8::MAX; self.timestamp_size],
+                    MAX_SEQUENCE_NUMBER,
+                    OperationType::Max,
+                );
+            }
+            // Seq 0 (and the smallest timestamp) sorts after every entry of the user key so the inner iterator lands on its oldest entry
+            _ => set_seek_key(
+                &mut self.saved_key,
+                user_key,
+                &vec![0; self.timestamp_size],
+                0,
+                OperationType::Put,
+            ),
+        }
+        self.iter.seek_for_prev(self.saved_key.as_slice());
+        self.find_prev_user_entry();
+    }
+
+    #[inline]
+    pub(crate) fn valid(&self) -> bool {
+        self.valid && self.range_tombstones_error.is_none()
+    }
+
+    pub(crate) fn next(&mut self) {
+        debug_assert!(self.valid);
+
+        if self.direction == Direction::Reverse {
+            // Move the inner iterator back onto the current user key, the skip below then moves past it
+            self.direction = Direction::Forward;
+            self.value_saved = false;
+            self.seek_inner_to_user_key_start();
+            self.find_next_user_entry(true);
+            return;
+        }
+
+        // A merge already moved the inner iterator past the current key
+        if !self.value_saved {
+            self.iter.next();
+        }
+        self.value_saved = false;
+        self.find_next_user_entry(true);
+    }
+
+    pub(crate) fn prev(&mut self) {
+        debug_assert!(self.valid);
+
+        if self.direction == Direction::Forward {
+            // Move the inner iterator to the last entry before the current user key
+            self.direction = Direction::Reverse;
+            self.seek_inner_to_user_key_start();
+            if self.iter.valid() {
+                self.iter.prev();
+            } else if self.iter.status().is_none() {
+                self.iter.seek_to_last();
+            }
+        }
+
+        self.find_prev_user_entry();
+    }
+
+    /// Current user key (without its timestamp).
+    #[inline]
+    pub(crate) fn key(&self) -> &[u8] {
+        debug_assert!(self.valid);
+        strip_timestamp(self.saved_user_key(), self.timestamp_size)
+    }
+
+    /// Timestamp of the current version - empty without timestamps.
+    #[inline]
+    pub(crate) fn timestamp(&self) -> &[u8] {
+        debug_assert!(self.valid);
+        timestamp_of(self.saved_user_key(), self.timestamp_size)
+    }
+
+    #[inline]
+    pub(crate) fn value(&self) -> &[u8] {
+        debug_assert!(self.valid);
+        if self.value_saved {
+            &self.saved_value
+        } else {
+            self.iter.value()
+        }
+    }
 
-use crate::iterator::iter_alloc::IterAlloc;
-use mem::arena::Arena;
+    /// Error which stopped the iterator (a failed merge or an error from the tables below). A scan which ends with an error is
+    /// incomplete.
+    pub(crate) fn status(&self) -> Option<&Error> {
+        self.range_tombstones_error
+            .as_ref()
+            .or(self.status.as_ref())
+            .or_else(|| self.iter.status())
+    }
 
-pub(crate) trait IterAllocStrategy {}
+    // Restricts the scan to the prefix of `key`. Keys outside the extractor's domain don't restrict it
+    #[inline]
+    fn set_prefix(&mut self, key: &[u8]) {
+        self.prefix_set = copy_prefix(self.prefix_extractor.as_deref(), key, &mut self.prefix);
+    }
 
-pub(crate) struct ArenaIter {
-    arena: Arena,
+    #[inline]
+    fn set_prefix_from_saved_key(&mut self) {
+        let key = self.saved_key.as_slice();
+        self.prefix_set = copy_prefix(
+            self.prefix_extractor.as_deref(),
+            strip_timestamp(&key[..key.len() - 8], self.timestamp_size),
+            &mut self.prefix,
+        );
+    }
+
+    // True if the user key has a different prefix than the one the scan is restricted to
+    #[inline]
+    fn prefix_mismatch(&self, user_key: &[u8]) -> bool {
+        if !self.prefix_set {
+            return false;
+        }
+        let Some(extractor) = self.prefix_extractor.as_ref() else {
+            return false;
+        };
+        let user_key = strip_timestamp(user_key, self.timestamp_size);
+        !extractor.in_domain(user_key) || extractor.transform(user_key) != self.prefix.as_slice()
+    }
+
+    // Seeks the inner iterator to the first (newest) entry of the saved user key
+    fn seek_inner_to_user_key_start(&mut self) {
+        if self.timestamp_size > 0 {
+            // The newest version has the largest timestamp
+            let user_key = strip_timestamp(self.saved_user_key(), self.timestamp_size).to_vec();
+            set_seek_key(
+                &mut self.saved_key,
+                &user_key,
+                &vec![u8::MAX; self.timestamp_size],
+                MAX_SEQUENCE_NUMBER,
+                OperationType::Max,
+            );
+        } else {
+            self.saved_key
+                .update_trailer(MAX_SEQUENCE_NUMBER, OperationType::Max);
+        }
+        self.iter.seek(self.saved_key.as_slice());
+    }
+
+    // Moves the inner iterator to the newest visible version of the next user key which isn't deleted. With `skipping` set, every
+    // remaining entry of the saved user key is skipped first
+    fn find_next_user_entry(&mut self, mut skipping: bool) {
+        self.valid = false;
+
+        while self.iter.valid() {
+            let ikey = InternalKeyRef::from(self.iter.key());
+
+            // Stop without reading anything past the bounds
+            if self
+                .bounds
+                .as_ref()
+                .is_some_and(|b| b.past_upper(ikey.user_key))
+                || self.prefix_mismatch(ikey.user_key)
+            {
+                return;
+            }
+
+            if !self.visible(&ikey)
+                || (skipping
+                    && self.compare_user_keys(ikey.user_key, self.saved_user_key())
+                        != Ordering::Greater)
+            {
+                self.iter.next();
+                continue;
+            }
+
+            let op = if ikey.seq_no < self.covering_seq(ikey.user_key) {
+                OperationType::Delete
+            } else {
+                OperationType::from(ikey.op)
+            };
+            self.saved_key.set(ikey.user_key, ikey.seq_no, op);
+
+            match op {
+                OperationType::Put => {
+                    self.valid = true;
+                    return;
+                }
+                OperationType::Delete | OperationType::SingleDelete => {
+                    skipping = true;
+                    self.iter.next();
+                }
+                OperationType::Merge => {
+                    self.merge_values_forward();
+                    return;
+                }
+                // A live value is saved, which leaves the inner iterator past the key like a merge
+                OperationType::PutWithExpiry => match self.save_expiring_value() {
+                    Some(OperationType::Put) => {
+                        self.iter.next();
+                        self.value_saved = true;
+                        self.valid = true;
+                        return;
+                    }
+                    Some(_) => {
+                        skipping = true;
+                        self.iter.next();
+                    }
+                    None => return,
+                },
+                // Blob references are resolved below the DBIter (see blob/blob_iter.rs)
+                OperationType::BlobIndex | OperationType::RangeDelete | OperationType::Max => {
+                    unreachable!()
+                }
+            }
+        }
+    }
+
+    // Collects the operands of the saved user key down to its base and merges them. Leaves the inner iterator past the base (or on
+    // the next user key)
+    fn merge_values_forward(&mut self) {
+        self.merge_context.clear();
+        self.merge_context.push_operand(self.iter.value());
+        self.iter.next();
+
+        let mut has_base = false;
+        let covering_seq = self.covering_seq(self.saved_user_key());
+
+        while self.iter.valid() {
+            let ikey = InternalKeyRef::from(self.iter.key());
+            if self.compare_user_keys(ikey.user_key, self.saved_user_key()) != Ordering::Equal {
+                break;
+            }
+            // An older timestamp may have been written after the read sequence
+            if !self.visible(&ikey) {
+                self.iter.next();
+                continue;
+            }
+
+            let op = if ikey.seq_no < covering_seq {
+                OperationType::Delete
+            } else {
+                OperationType::from(ikey.op)
+            };
+
+            match op {
+                OperationType::Merge => {
+                    self.merge_context.push_operand(self.iter.value());
+                    self.iter.next();
+                }
+                OperationType::Put => {
+                    self.saved_value.clear();
+                    self.saved_value.extend_from_slice(self.iter.value());
+                    has_base = true;
+                    self.iter.next();
+                    break;
+                }
+                // A base until it expires, a Delete after
+                OperationType::PutWithExpiry => {
+                    let Some(op) = self.save_expiring_value() else {
+                        return;
+                    };
+                    has_base = op == OperationType::Put;
+                    self.iter.next();
+                    break;
+                }
+                OperationType::Delete | OperationType::SingleDelete => {
+                    self.iter.next();
+                    break;
+                }
+                OperationType::BlobIndex | OperationType::RangeDelete | OperationType::Max => {
+                    unreachable!()
+                }
+            }
+        }
+
+        // The inner iterator stopping early means the operands may be incomplete
+        if self.iter.status().is_some() {
+            return;
+        }
+
+        self.resolve_merge(has_base);
+    }
+
+    // Walks back over every entry of the user key the inner iterator is on (oldest first) keeping the newest visible version. Moves on
+    // to older user keys while the current one is deleted or has no visible version
+    fn find_prev_user_entry(&mut self) {
+        self.valid = false;
+        self.value_saved = false;
+
+        while self.iter.valid() {
+            let ikey = InternalKeyRef::from(self.iter.key());
+            if self
+                .bounds
+                .as_ref()
+                .is_some_and(|b| b.before_lower(ikey.user_key))
+                || self.prefix_mismatch(ikey.user_key)
+            {
+                return;
+            }
+
+            let covering_seq = self.covering_seq(ikey.user_key);
+            self.saved_key
+                .set(ikey.user_key, ikey.seq_no, OperationType::from(ikey.op));
+            self.merge_context.clear();
+
+            // Newest visible Put / Delete - merge operands newer than it are in the merge context
+            let mut base: Option<OperationType> = None;
+
+            while self.iter.valid() {
+                let ikey = InternalKeyRef::from(self.iter.key());
+                if self.compare_user_keys(ikey.user_key, self.saved_user_key()) != Ordering::Equal {
+                    break;
+                }
+
+                if self.visible(&ikey) {
+                    // Ends on the newest visible version, so timestamp() is the one read
+                    if self.timestamp_size > 0 {
+                        self.saved_key.set(
+                            ikey.user_key,
+                            ikey.seq_no,
+                            OperationType::from(ikey.op),
+                        );
+                    }
+                    let op = if ikey.seq_no < covering_seq {
+                        OperationType::Delete
+                    } else {
+                        OperationType::from(ikey.op)
+                    };
+
+                    match op {
+                        OperationType::Put => {
+                            self.saved_value.clear();
+                            self.saved_value.extend_from_slice(self.iter.value());
+                            self.merge_context.clear();
+                            base = Some(OperationType::Put);
+                        }
+                        op @ (OperationType::Delete | OperationType::SingleDelete) => {
+                            self.merge_context.clear();
+                            base = Some(op);
+                        }
+                        OperationType::PutWithExpiry => {
+                            let Some(op) = self.save_expiring_value() else {
+                                return;
+                            };
+                            self.merge_context.clear();
+                            base = Some(op);
+                        }
+                        OperationType::Merge => {
+                            self.merge_context.push_newer_operand(self.iter.value())
+                        }
+                        OperationType::BlobIndex
+                        | OperationType::RangeDelete
+                        | OperationType::Max => {
+                            unreachable!()
+                        }
+                    }
+                }
+
+                self.iter.prev();
+            }
+
+            if self.iter.status().is_some() {
+                return;
+            }
+
+            if !self.merge_context.is_empty() {
+                self.resolve_merge(base == Some(OperationType::Put));
+                return;
+            }
+
+            if base == Some(OperationType::Put) {
+                self.value_saved = true;
+                self.valid = true;
+                return;
+            }
+            // Deleted or nothing visible - the inner iterator is already on the previous user key
+        }
+    }
+
+    // Merges the collected operands on top of saved_value (when has_base) and saves the result as the current value
+    fn resolve_merge(&mut self, has_base: bool) {
+        let operands = self.merge_context.operands_oldest_first();
+        let base = has_base.then_some(self.saved_value.as_slice());
+
+        let user_key = strip_timestamp(self.saved_user_key(), self.timestamp_size);
+        match full_merge(self.merge_operator, user_key, base, &operands) {
+            Ok(v) => {
+                self.saved_value = v;
+                self.value_saved = true;
+                self.valid = true;
+            }
+            Err(e) => self.status = Some(e),
+        }
+    }
+}
+
+// Builds the internal key of `user_key` at `timestamp` (empty without timestamps) in `key`
+fn set_seek_key(
+    key: &mut InternalIterKey,
+    user_key: &[u8],
+    timestamp: &[u8],
+    seq_no: u64,
+    op: OperationType,
+) {
+    if timestamp.is_empty() {
+        key.set(user_key, seq_no, op);
+    } else {
+        key.set(&[user_key, timestamp].concat(), seq_no, op);
+    }
+}
+
+// Copies the prefix of the key into `prefix`. Returns false if there is no extractor or the key is outside its domain
+fn copy_prefix(extractor: Option<&dyn PrefixExtractor>, key: &[u8], prefix: &mut Vec<u8>) -> bool {
+    match extractor {
+        Some(extractor) if extractor.in_domain(key) => {
+            prefix.clear();
+            prefix.extend_from_slice(extractor.transform(key));
+            true
+        }
+        _ => false,
+    }
+}
+
+impl<S: IterAllocStrategy> Drop for DBIter<'_, S> {
+    fn drop(&mut self) {
+        // The children may live in the arena so they go first
+        self.iter.release_into(&mut self.alloc);
+
+        self.alloc.saved_key = mem::take(&mut self.saved_key);
+        self.alloc.saved_value = mem::take(&mut self.saved_value);
+        self.alloc.prefix = mem::take(&mut self.prefix);
+        self.alloc.merge_context = mem::take(&mut self.merge_context);
+
+        self.strategy.release(mem::take(&mut self.alloc));
+    }
+}
+
+// DBIterBuilder
+//
+// Builds a DBIter whose children are placed in the arena of an IterAlloc from the strategy. The builder owns the alloc and the children
+// until build() moves them into the DBIter together, so a child can never outlive the arena it lives in.
+
+pub(crate) struct DBIterBuilder<'a, S: IterAllocStrategy> {
+    // Taken by build()
+    strategy: Option<S>,
+    alloc: IterAlloc,
+    children: Vec<IterBox<'a>>,
 }
-impl IterAllocStrategy for ArenaIter {}
 
-pub(crate) struct HeapIter {}
-impl IterAllocStrategy for HeapIter {}
+impl<'a, S: IterAllocStrategy> DBIterBuilder<'a, S> {
+    pub(crate) fn new(strategy: S) -> Self {
+        let mut alloc = strategy.acquire();
+        let children = alloc.take_children();
+        Self {
+            strategy: Some(strategy),
+            alloc,
+            children,
+        }
+    }
+
+    /// Children are added newest source first.
+    pub(crate) fn add_child<T: InternalIterator + 'a>(&mut self, iter: T) {
+        // SAFETY: the children and the alloc are only ever moved together (into the DBIter) and both drop paths drop the children
+        // before the alloc is released
+        let child = unsafe { self.alloc.arena.alloc(iter) };
+        self.children.push(child);
+    }
+
+    #[inline]
+    pub(crate) fn num_children(&self) -> usize {
+        self.children.len()
+    }
+
+    /// `comparator` is the internal key comparator the children are ordered by.
+    pub(crate) fn build(
+        mut self,
+        comparator: Arc<dyn Comparator>,
+        user_comparator: Arc<dyn Comparator>,
+        merge_operator: Option<&'a dyn MergeOperator>,
+        sequence: u64,
+        bounds: Option<Arc<IterBounds>>,
+        prefix_extractor: Option<Arc<dyn PrefixExtractor>>,
+    ) -> DBIter<'a, S> {
+        // Leaves nothing for the builder's drop to release
+        let strategy = self.strategy.take().unwrap();
+        let mut alloc = mem::take(&mut self.alloc);
+        let iter = MergingIterator::new_in(comparator, mem::take(&mut self.children), &mut alloc);
+
+        let mut db_iter = DBIter::from_alloc(
+            strategy,
+            alloc,
+            iter,
+            user_comparator,
+            merge_operator,
+            sequence,
+        );
+        db_iter.bounds = bounds;
+        db_iter.prefix_extractor = prefix_extractor;
+        db_iter
+    }
+}
 
-pub(crate) struct DBIter<'a, I: IterAllocStrategy> {
-    _state: PhantomData<I>,
-    iter_alloc: &'a IterAlloc,
+impl<S: IterAllocStrategy> Drop for DBIterBuilder<'_, S> {
+    fn drop(&mut self) {
+        if let Some(strategy) = self.strategy.take() {
+            self.alloc.put_children(mem::take(&mut self.children));
+            strategy.release(mem::take(&mut self.alloc));
+        }
+    }
 }
@@ -1,12 +1,34 @@
+use std::sync::Arc;
+
+use crate::error::{Error, Result};
+use crate::range::fragment::FragmentedTombstones;
+
 // Internal Iterator is the trait for which all internal iterators must implement.
 //
 pub(crate) trait InternalIterator {
     fn seek_to_first(&mut self);
+    fn seek_to_last(&mut self);
+    // Positions at the first entry >= key
     fn seek(&mut self, key: &[u8]);
+    // Positions at the last entry <= key
+    fn seek_for_prev(&mut self, key: &[u8]);
     fn valid(&self) -> bool;
 
     // Relative methods
     fn next(&mut self);
+    fn prev(&mut self);
     fn key(&self) -> &[u8];
     fn value(&self) -> &[u8];
+
+    // Error hit while positioning (corrupt block, failed table open...). An iterator with an error is not valid.
+    // Iterators over memory (memtables) can't fail so the default is no error
+    fn status(&self) -> Option<&Error> {
+        None
+    }
+
+    // Range tombstones stored next to the entries (memtables, SST files), fragmented per source. They are appended to `out` so that
+    // iterators over several sources (merging, level) can hand over those of every source. Most iterators have none
+    fn range_tombstones(&self, _out: &mut Vec<Arc<FragmentedTombstones>>) -> Result<()> {
+        Ok(())
+    }
 }
@@ -1,16 +1,580 @@
-use std::sync::Mutex;
+//
+//
+//
+// Iterator allocation
+//
+// Opening an iterator builds a small tree (DBIter -> MergingIterator -> one child per memtable / L0 file / level) and every node owns a
+// few buffers. With short lived iterators opened at a high rate the allocator churn of building and tearing down the tree shows up, so
+// everything a tree allocates can come from an IterAlloc which is handed back to a pool when the iterator is dropped:
+//
+//   IterArena - bump allocator the child iterators are placed in. Resetting it keeps its blocks so the next tree re-uses them
+//   buffers   - the DBIter keys / values / merge operands and the MergingIterator heap, cleared but with their capacity kept
+//
+// IterAllocPool lives in DBImpl. Each thread keeps one IterAlloc in its ThreadCtx in front of the shared Mutex<Vec<IterAlloc>> so
+// a thread which opens iterators back to back never touches the lock. The cached alloc belongs to the pool which released it - other
+// pools don't take it, and one releasing an alloc on the thread replaces it. Every alloc a pool keeps, shared or cached by a thread,
+// counts towards its max_pooled.
+//
+// The strategy is picked per iterator through the DBIter type parameter:
+//
+//   HeapIter   - fresh buffers, every child boxed on the heap. Simple, used by tests
+//   PooledIter - buffers and the arena come from a pool. Without a pool (None) it behaves like HeapIter, so pooling can be switched off
+//                at runtime (DbOptions::max_pooled_iter_allocs)
+//
+// Child iterators which don't fit in an arena block fall back to the heap - IterBox hides where a child lives.
+
+use std::alloc::{Layout, alloc, dealloc, handle_alloc_error};
+use std::marker::PhantomData;
+use std::mem::{self, ManuallyDrop};
+use std::ops::{Deref, DerefMut};
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::iterator::internal_iterator::InternalIterator;
+use crate::key::iter_key::InternalIterKey;
+use crate::merge::context::MergeContext;
+use crate::thread_ctx::thread_ctx;
 
-// Re-usable heap allocation for iterators
+const ARENA_BLOCK_SIZE: usize = 4096;
+const ARENA_BLOCK_ALIGN: usize = 64;
+// Blocks kept by an arena going back to the pool
+const MAX_RETAINED_BLOCKS: usize = 4;
+// Buffers which grew past this (e.g a scan over huge values) are shrunk before going back to the pool
+const MAX_RETAINED_BYTES: usize = 64 * 1024;
+
+pub(crate) const DEFAULT_MAX_POOLED_ALLOCS: usize = 64;
+
+// Iter Arena
 //
-// To begin with we will have a simple Vec-based pool. Each DBIter will borrow from this pool.
-// As a future optimization, we will implement a thread-local iter pool as a cache of reusable iter allocs.
+
+pub(crate) struct IterArena {
+    blocks: Vec<NonNull<u8>>,
+    // Block being allocated from and the offset into it
+    current: usize,
+    offset: usize,
+}
+
+impl IterArena {
+    pub(crate) fn new() -> Self {
+        Self {
+            blocks: Vec::new(),
+            current: 0,
+            offset: 0,
+        }
+    }
+
+    #[inline]
+    fn block_layout() -> Layout {
+        Layout::from_size_align(ARENA_BLOCK_SIZE, ARENA_BLOCK_ALIGN).unwrap()
+    }
+
+    // Returns None if the layout can never fit in a block
+    fn alloc_raw(&mut self, layout: Layout) -> Option<NonNull<u8>> {
+        if layout.size() > ARENA_BLOCK_SIZE || layout.align() > ARENA_BLOCK_ALIGN {
+            return None;
+        }
+
+        loop {
+            if self.current < self.blocks.len() {
+                let aligned = self.offset.next_multiple_of(layout.align());
+                if aligned + layout.size() <= ARENA_BLOCK_SIZE {
+                    self.offset = aligned + layout.size();
+                    // SAFETY: aligned + size is within the block
+                    return Some(unsafe { self.blocks[self.current].add(aligned) });
+                }
+                self.current += 1;
+                self.offset = 0;
+                continue;
+            }
+
+            // SAFETY: the block layout has a non zero size
+            let block = unsafe { alloc(Self::block_layout()) };
+            let Some(block) = NonNull::new(block) else {
+                handle_alloc_error(Self::block_layout());
+            };
+            self.blocks.push(block);
+        }
+    }
+
+    /// Places the iterator in the arena (or on the heap if it doesn't fit in a block).
+    ///
+    /// # Safety
+    ///
+    /// The arena must not be reset or dropped while the returned IterBox is alive.
+    pub(crate) unsafe fn alloc<'a, T: InternalIterator + 'a>(&mut self, iter: T) -> IterBox<'a> {
+        let Some(ptr) = self.alloc_raw(Layout::new::<T>()) else {
+            return IterBox::from(Box::new(iter) as Box<dyn InternalIterator + 'a>);
+        };
+
+        let ptr = ptr.cast::<T>();
+        // SAFETY: the memory is reserved for a T and correctly aligned
+        unsafe { ptr.write(iter) };
+
+        IterBox {
+            ptr: ptr as NonNull<dyn InternalIterator + 'a>,
+            in_arena: true,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Makes every block available again. Every IterBox allocated from the arena must have been dropped.
+    fn reset(&mut self) {
+        for block in self
+            .blocks
+            .drain(MAX_RETAINED_BLOCKS.min(self.blocks.len())..)
+        {
+            // SAFETY: allocated in alloc_raw with the block layout
+            unsafe { dealloc(block.as_ptr(), Self::block_layout()) };
+        }
+        self.current = 0;
+        self.offset = 0;
+    }
+
+    #[inline]
+    pub(crate) fn num_blocks(&self) -> usize {
+        self.blocks.len()
+    }
+}
+
+impl Default for IterArena {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for IterArena {
+    fn drop(&mut self) {
+        for block in self.blocks.drain(..) {
+            // SAFETY: allocated in alloc_raw with the block layout
+            unsafe { dealloc(block.as_ptr(), Self::block_layout()) };
+        }
+    }
+}
+
+// Owning pointer to a child iterator which lives either in an IterArena or on the heap
+pub(crate) struct IterBox<'a> {
+    ptr: NonNull<dyn InternalIterator + 'a>,
+    in_arena: bool,
+    _marker: PhantomData<dyn InternalIterator + 'a>,
+}
+
+impl<'a> From<Box<dyn InternalIterator + 'a>> for IterBox<'a> {
+    fn from(iter: Box<dyn InternalIterator + 'a>) -> Self {
+        Self {
+            // SAFETY: Box::into_raw never returns null
+            ptr: unsafe { NonNull::new_unchecked(Box::into_raw(iter)) },
+            in_arena: false,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a> Deref for IterBox<'a> {
+    type Target = dyn InternalIterator + 'a;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: the pointer is valid until the box is dropped
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+impl<'a> DerefMut for IterBox<'a> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // SAFETY: the box owns the iterator
+        unsafe { self.ptr.as_mut() }
+    }
+}
+
+impl<'a> Drop for IterBox<'a> {
+    fn drop(&mut self) {
+        if self.in_arena {
+            // SAFETY: the arena memory outlives the box (contract of IterArena::alloc), the memory itself is reclaimed by a reset
+            unsafe { self.ptr.drop_in_place() };
+        } else {
+            // SAFETY: created from a Box in From<Box<..>>
+            drop(unsafe { Box::from_raw(self.ptr.as_ptr()) });
+        }
+    }
+}
+
+// Iter Alloc
+//
+
+// Everything one iterator tree allocates. A new IterAlloc doesn't allocate, buffers grow as the first tree using it needs them
+pub(crate) struct IterAlloc {
+    pub(crate) arena: IterArena,
+    // MergingIterator
+    pub(crate) heap: Vec<usize>,
+    pub(crate) switch_key: Vec<u8>,
+    // Capacity of the MergingIterator children vec - always empty
+    children: Vec<IterBox<'static>>,
+    // DBIter
+    pub(crate) saved_key: InternalIterKey,
+    pub(crate) saved_value: Vec<u8>,
+    pub(crate) prefix: Vec<u8>,
+    pub(crate) merge_context: MergeContext,
+}
+
+// SAFETY: an IterAlloc owns all of its memory - the arena blocks, the key buffer the IterKey points into and an empty children vec
+unsafe impl Send for IterAlloc {}
+
+impl IterAlloc {
+    pub(crate) fn new() -> Self {
+        Self {
+            arena: IterArena::new(),
+            heap: Vec::new(),
+            switch_key: Vec::new(),
+            children: Vec::new(),
+            saved_key: InternalIterKey::unallocated(),
+            saved_value: Vec::new(),
+            prefix: Vec::new(),
+            merge_context: MergeContext::new(),
+        }
+    }
+
+    /// An empty children vec re-using the capacity of the last tree.
+    pub(crate) fn take_children<'a>(&mut self) -> Vec<IterBox<'a>> {
+        let mut children = ManuallyDrop::new(std::mem::take(&mut self.children));
+        debug_assert!(children.is_empty());
+        // SAFETY: the vec is empty and IterBox<'static> and IterBox<'a> only differ in lifetime so they have the same layout
+        unsafe { Vec::from_raw_parts(children.as_mut_ptr().cast(), 0, children.capacity()) }
+    }
+
+    /// Keeps the capacity of a children vec. Drops the children which are left.
+    pub(crate) fn put_children(&mut self, mut children: Vec<IterBox<'_>>) {
+        children.clear();
+        let mut children = ManuallyDrop::new(children);
+        // SAFETY: as in take_children
+        self.children =
+            unsafe { Vec::from_raw_parts(children.as_mut_ptr().cast(), 0, children.capacity()) };
+    }
+
+    // Clears every buffer for the next tree. The arena is only reset here, once every child placed in it has been dropped
+    fn recycle(&mut self) {
+        self.arena.reset();
+
+        self.heap.clear();
+        self.heap.shrink_to(MAX_RETAINED_BYTES / size_of::<usize>());
+        self.switch_key.clear();
+        self.switch_key.shrink_to(MAX_RETAINED_BYTES);
+        self.saved_value.clear();
+        self.saved_value.shrink_to(MAX_RETAINED_BYTES);
+        self.prefix.clear();
+        self.merge_context.clear();
+    }
+}
+
+impl Default for IterAlloc {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Iter Alloc Pool
 //
-// IterAllocPool will live in DBImpl
+
 pub(crate) struct IterAllocPool {
-    iters: Mutex<Vec<IterAlloc>>,
+    iters: Mutex<Vec<PooledAlloc>>,
+    // Allocs kept by the pool, in the shared Vec and in the thread local caches - shared with each of them
+    retained: Arc<AtomicUsize>,
+    max_pooled: usize,
 }
 
-// IterAlloc is a re-usable heap allocation for an iterator. Lives in DBIter
-pub(crate) struct IterAlloc {
-    // TOOD: Create fields for the vecs we'll need
+impl IterAllocPool {
+    pub(crate) fn new(max_pooled: usize) -> Self {
+        Self {
+            iters: Mutex::new(Vec::new()),
+            retained: Arc::new(AtomicUsize::new(0)),
+            max_pooled,
+        }
+    }
+
+    pub(crate) fn acquire(&self) -> IterAlloc {
+        if let Some(alloc) = thread_ctx(|ctx| ctx.take_iter_alloc(self)) {
+            return alloc;
+        }
+        let pooled = self.iters.lock().unwrap().pop();
+        pooled.map(PooledAlloc::into_alloc).unwrap_or_default()
+    }
+
+    /// Every child allocated from the alloc's arena must have been dropped.
+    pub(crate) fn release(&self, mut alloc: IterAlloc) {
+        alloc.recycle();
+
+        let reserved = self
+            .retained
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| {
+                (n < self.max_pooled).then_some(n + 1)
+            });
+        if reserved.is_err() {
+            return;
+        }
+        let alloc = PooledAlloc {
+            alloc,
+            retained: Arc::clone(&self.retained),
+        };
+        if let Some(alloc) = thread_ctx(|ctx| ctx.put_iter_alloc(alloc)) {
+            self.iters.lock().unwrap().push(alloc);
+        }
+    }
+
+    /// Allocs in the shared pool (not counting the thread local caches).
+    pub(crate) fn num_pooled(&self) -> usize {
+        self.iters.lock().unwrap().len()
+    }
+}
+
+/// An alloc kept by an IterAllocPool - its place in the pool is given back when it is taken out or dropped (e.g with the thread
+/// caching it).
+pub(crate) struct PooledAlloc {
+    alloc: IterAlloc,
+    retained: Arc<AtomicUsize>,
+}
+
+impl PooledAlloc {
+    #[inline]
+    pub(crate) fn is_from(&self, pool: &IterAllocPool) -> bool {
+        Arc::ptr_eq(&self.retained, &pool.retained)
+    }
+
+    #[inline]
+    pub(crate) fn is_from_same_pool(&self, other: &PooledAlloc) -> bool {
+        Arc::ptr_eq(&self.retained, &other.retained)
+    }
+
+    pub(crate) fn into_alloc(mut self) -> IterAlloc {
+        mem::take(&mut self.alloc)
+    }
+}
+
+impl Drop for PooledAlloc {
+    fn drop(&mut self) {
+        self.retained.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+impl Default for IterAllocPool {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_POOLED_ALLOCS)
+    }
+}
+
+// Strategies
+//
+
+pub(crate) trait IterAllocStrategy {
+    fn acquire(&self) -> IterAlloc;
+
+    /// Every child allocated from the alloc's arena must have been dropped.
+    fn release(&self, alloc: IterAlloc);
+}
+
+#[derive(Default)]
+pub(crate) struct HeapIter {}
+
+impl IterAllocStrategy for HeapIter {
+    #[inline]
+    fn acquire(&self) -> IterAlloc {
+        IterAlloc::new()
+    }
+
+    #[inline]
+    fn release(&self, _alloc: IterAlloc) {}
+}
+
+#[derive(Clone, Copy)]
+pub(crate) struct PooledIter<'p> {
+    pool: Option<&'p IterAllocPool>,
+}
+
+impl<'p> PooledIter<'p> {
+    pub(crate) fn new(pool: Option<&'p IterAllocPool>) -> Self {
+        Self { pool }
+    }
+}
+
+impl IterAllocStrategy for PooledIter<'_> {
+    fn acquire(&self) -> IterAlloc {
+        self.pool.map_or_else(IterAlloc::new, |pool| pool.acquire())
+    }
+
+    fn release(&self, alloc: IterAlloc) {
+        if let Some(pool) = self.pool {
+            pool.release(alloc);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::error::Error;
+
+    // Counts drops so the tests can check arena children are dropped exactly once
+    struct CountingIter {
+        drops: Rc<Cell<usize>>,
+        _padding: [u64; 8],
+    }
+
+    impl InternalIterator for CountingIter {
+        fn seek_to_first(&mut self) {}
+        fn seek_to_last(&mut self) {}
+        fn seek(&mut self, _key: &[u8]) {}
+        fn seek_for_prev(&mut self, _key: &[u8]) {}
+        fn valid(&self) -> bool {
+            false
+        }
+        fn next(&mut self) {}
+        fn prev(&mut self) {}
+        fn key(&self) -> &[u8] {
+            &[]
+        }
+        fn value(&self) -> &[u8] {
+            &[]
+        }
+        fn status(&self) -> Option<&Error> {
+            None
+        }
+    }
+
+    impl Drop for CountingIter {
+        fn drop(&mut self) {
+            self.drops.set(self.drops.get() + 1);
+        }
+    }
+
+    #[test]
+    fn arena_children_are_dropped_and_blocks_reused() {
+        let drops = Rc::new(Cell::new(0));
+        let mut alloc = IterAlloc::new();
+
+        for round in 0..3 {
+            let mut children = alloc.take_children();
+            for _ in 0..100 {
+                let iter = CountingIter {
+                    drops: Rc::clone(&drops),
+                    _padding: [0; 8],
+                };
+                // SAFETY: the children are dropped before the alloc is recycled
+                children.push(unsafe { alloc.arena.alloc(iter) });
+            }
+            assert!(children.iter().all(|c| c.in_arena));
+            assert!(!children[0].valid());
+
+            let capacity = children.capacity();
+            alloc.put_children(children);
+            assert_eq!(drops.get(), 100 * (round + 1));
+
+            // 100 * 80 bytes needs two blocks - recycling keeps them
+            let blocks = alloc.arena.num_blocks();
+            alloc.recycle();
+            assert_eq!(alloc.arena.num_blocks(), blocks);
+            let children = alloc.take_children();
+            assert_eq!(children.capacity(), capacity);
+            alloc.put_children(children);
+        }
+    }
+
+    #[test]
+    fn oversized_children_fall_back_to_the_heap() {
+        struct Huge([u8; ARENA_BLOCK_SIZE + 1], CountingIter);
+        impl InternalIterator for Huge {
+            fn seek_to_first(&mut self) {}
+            fn seek_to_last(&mut self) {}
+            fn seek(&mut self, _key: &[u8]) {}
+            fn seek_for_prev(&mut self, _key: &[u8]) {}
+            fn valid(&self) -> bool {
+                self.0[0] == 0
+            }
+            fn next(&mut self) {}
+            fn prev(&mut self) {}
+            fn key(&self) -> &[u8] {
+                &[]
+            }
+            fn value(&self) -> &[u8] {
+                &[]
+            }
+        }
+
+        let drops = Rc::new(Cell::new(0));
+        let mut arena = IterArena::new();
+        let iter = Huge(
+            [0; ARENA_BLOCK_SIZE + 1],
+            CountingIter {
+                drops: Rc::clone(&drops),
+                _padding: [0; 8],
+            },
+        );
+        // SAFETY: dropped before the arena
+        let boxed = unsafe { arena.alloc(iter) };
+        assert!(!boxed.in_arena);
+        assert!(boxed.valid());
+        assert_eq!(arena.num_blocks(), 0);
+
+        drop(boxed);
+        assert_eq!(drops.get(), 1);
+    }
+
+    #[test]
+    fn pool_caches_per_thread_then_shared() {
+        let pool = IterAllocPool::new(2);
+
+        let a = pool.acquire();
+        let b = pool.acquire();
+        let c = pool.acquire();
+
+        // The first release fills this thread's cache, the rest go to the shared pool until the pool keeps max_pooled allocs and are
+        // dropped after
+        pool.release(a);
+        assert_eq!(pool.num_pooled(), 0);
+        pool.release(b);
+        pool.release(c);
+        assert_eq!(pool.num_pooled(), 1);
+        assert_eq!(pool.retained.load(Ordering::Acquire), 2);
+
+        let _a = pool.acquire();
+        assert_eq!(pool.num_pooled(), 1);
+        let _b = pool.acquire();
+        assert_eq!(pool.num_pooled(), 0);
+        assert_eq!(pool.retained.load(Ordering::Acquire), 0);
+
+        // Other threads only see the shared pool - a thread's cache is dropped with it, which join waits for
+        let pool = IterAllocPool::new(4);
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                let (x, y) = (pool.acquire(), pool.acquire());
+                pool.release(x);
+                pool.release(y);
+            })
+            .join()
+            .unwrap();
+        });
+        assert_eq!(pool.num_pooled(), 1);
+        assert_eq!(pool.retained.load(Ordering::Acquire), 1);
+    }
+
+    #[test]
+    fn thread_cache_is_scoped_to_its_pool() {
+        let first = IterAllocPool::new(4);
+        let second = IterAllocPool::new(4);
+
+        first.release(IterAlloc::new());
+        assert_eq!(first.retained.load(Ordering::Acquire), 1);
+
+        // The cached alloc is not handed to another pool
+        let alloc = second.acquire();
+        assert_eq!(first.retained.load(Ordering::Acquire), 1);
+
+        // Releasing on the same thread replaces it
+        second.release(alloc);
+        assert_eq!(first.retained.load(Ordering::Acquire), 0);
+        assert_eq!(second.retained.load(Ordering::Acquire), 1);
+        assert_eq!(first.num_pooled() + second.num_pooled(), 0);
+    }
 }
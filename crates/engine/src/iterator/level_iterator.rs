@@ -0,0 +1,236 @@
+//
+//
+//
+// LevelIterator
+//
+// Iterates a sorted run of non-overlapping files (a level >= 1) as if it were one table. Only one file is open at a time - a seek
+// binary searches the file metadata for the first file whose largest key is >= target (seek_for_prev: the last file whose smallest key
+// is <= target) and only that table is opened.
+//
+// Opening a table is delegated to the caller (the table cache) so the level iterator works with any table iterator.
+//
+// With iterator bounds, files entirely outside the bounds are never opened - going forward the level ends at the first file which starts
+// at or past the upper bound, in reverse at the first file which ends before the lower bound.
+
+use std::cmp::Ordering;
+use std::sync::Arc;
+
+use crate::error::{Error, Result};
+use crate::iterator::bounds::{IterBounds, before_lower, past_upper};
+use crate::iterator::internal_iterator::InternalIterator;
+use crate::key::comparator::Comparator;
+use crate::range::fragment::FragmentedTombstones;
+use crate::versioning::file_version::FileMetaData;
+
+pub(crate) type TableIterOpener<'a> =
+    dyn Fn(&FileMetaData) -> Result<Box<dyn InternalIterator + 'a>> + 'a;
+
+pub(crate) struct LevelIterator<'a> {
+    files: &'a [Arc<FileMetaData>],
+    // Internal key comparator
+    comparator: Arc<dyn Comparator>,
+    open_table: Box<TableIterOpener<'a>>,
+    bounds: Option<Arc<IterBounds>>,
+    // Index of the open file - files.len() when exhausted
+    file_index: usize,
+    file_iter: Option<Box<dyn InternalIterator + 'a>>,
+    error: Option<Error>,
+}
+
+impl<'a> LevelIterator<'a> {
+    pub(crate) fn new(
+        files: &'a [Arc<FileMetaData>],
+        comparator: Arc<dyn Comparator>,
+        open_table: Box<TableIterOpener<'a>>,
+        bounds: Option<Arc<IterBounds>>,
+    ) -> Self {
+        Self {
+            files,
+            comparator,
+            open_table,
+            bounds,
+            file_index: files.len(),
+            file_iter: None,
+            error: None,
+        }
+    }
+
+    fn open_file(&mut self, index: usize) {
+        self.file_index = index;
+        self.file_iter = None;
+
+        if index >= self.files.len() {
+            return;
+        }
+
+        match (self.open_table)(&self.files[index]) {
+            Ok(iter) => self.file_iter = Some(iter),
+            Err(e) => {
+                self.file_index = self.files.len();
+                self.error = Some(e);
+            }
+        }
+    }
+
+    // Opens a file the iterator is moving forward into. This and every later file is skipped if it starts at or past the upper bound
+    fn open_file_forward(&mut self, index: usize) {
+        if index < self.files.len() && past_upper(&self.bounds, &self.files[index].smallest) {
+            self.open_file(self.files.len());
+            return;
+        }
+        self.open_file(index);
+    }
+
+    // Opens a file the iterator is moving backward into. This and every earlier file is skipped if it ends before the lower bound
+    fn open_file_backward(&mut self, index: usize) {
+        if index < self.files.len() && before_lower(&self.bounds, &self.files[index].largest) {
+            self.open_file(self.files.len());
+            return;
+        }
+        self.open_file(index);
+    }
+
+    // Moves forward over exhausted files until positioned on an entry, the level ends or a file fails
+    fn skip_empty_files_forward(&mut self) {
+        loop {
+            let Some(iter) = self.file_iter.as_ref() else {
+                return;
+            };
+            if iter.valid() || iter.status().is_some() {
+                return;
+            }
+            self.open_file_forward(self.file_index + 1);
+            if let Some(iter) = self.file_iter.as_mut() {
+                iter.seek_to_first();
+            }
+        }
+    }
+
+    // Moves backward over exhausted files until positioned on an entry, the level ends or a file fails
+    fn skip_empty_files_backward(&mut self) {
+        loop {
+            let Some(iter) = self.file_iter.as_ref() else {
+                return;
+            };
+            if iter.valid() || iter.status().is_some() {
+                return;
+            }
+            if self.file_index == 0 {
+                self.open_file(self.files.len());
+                return;
+            }
+            self.open_file_backward(self.file_index - 1);
+            if let Some(iter) = self.file_iter.as_mut() {
+                iter.seek_to_last();
+            }
+        }
+    }
+}
+
+impl<'a> InternalIterator for LevelIterator<'a> {
+    fn seek_to_first(&mut self) {
+        self.error = None;
+        self.open_file_forward(0);
+        if let Some(iter) = self.file_iter.as_mut() {
+            iter.seek_to_first();
+        }
+        self.skip_empty_files_forward();
+    }
+
+    fn seek(&mut self, key: &[u8]) {
+        self.error = None;
+
+        let index = self
+            .files
+            .partition_point(|f| self.comparator.compare(&f.largest, key) == Ordering::Less);
+
+        self.open_file_forward(index);
+        if let Some(iter) = self.file_iter.as_mut() {
+            iter.seek(key);
+        }
+        self.skip_empty_files_forward();
+    }
+
+    fn seek_to_last(&mut self) {
+        self.error = None;
+        if self.files.is_empty() {
+            self.open_file(0);
+            return;
+        }
+        self.open_file_backward(self.files.len() - 1);
+        if let Some(iter) = self.file_iter.as_mut() {
+            iter.seek_to_last();
+        }
+        self.skip_empty_files_backward();
+    }
+
+    fn seek_for_prev(&mut self, key: &[u8]) {
+        self.error = None;
+
+        // Files which start at or before the target
+        let count = self
+            .files
+            .partition_point(|f| self.comparator.compare(&f.smallest, key) != Ordering::Greater);
+
+        if count == 0 {
+            self.open_file(self.files.len());
+            return;
+        }
+
+        self.open_file_backward(count - 1);
+        if let Some(iter) = self.file_iter.as_mut() {
+            iter.seek_for_prev(key);
+        }
+        self.skip_empty_files_backward();
+    }
+
+    #[inline]
+    fn valid(&self) -> bool {
+        self.file_iter.as_ref().is_some_and(|iter| iter.valid())
+    }
+
+    fn next(&mut self) {
+        debug_assert!(self.valid());
+        if let Some(iter) = self.file_iter.as_mut() {
+            iter.next();
+        }
+        self.skip_empty_files_forward();
+    }
+
+    fn prev(&mut self) {
+        debug_assert!(self.valid());
+        if let Some(iter) = self.file_iter.as_mut() {
+            iter.prev();
+        }
+        self.skip_empty_files_backward();
+    }
+
+    #[inline]
+    fn key(&self) -> &[u8] {
+        debug_assert!(self.valid());
+        self.file_iter.as_ref().unwrap().key()
+    }
+
+    #[inline]
+    fn value(&self) -> &[u8] {
+        debug_assert!(self.valid());
+        self.file_iter.as_ref().unwrap().value()
+    }
+
+    fn status(&self) -> Option<&Error> {
+        self.error
+            .as_ref()
+            .or_else(|| self.file_iter.as_ref().and_then(|iter| iter.status()))
+    }
+
+    // A tombstone of one file can cover keys of the levels below anywhere in the file's range, not just where this iterator is, so the
+    // tombstones of every file are handed over. Only files which have any are opened
+    fn range_tombstones(&self, out: &mut Vec<Arc<FragmentedTombstones>>) -> Result<()> {
+        for file in self.files {
+            if file.properties.num_range_deletions > 0 {
+                (self.open_table)(file)?.range_tombstones(out)?;
+            }
+        }
+        Ok(())
+    }
+}
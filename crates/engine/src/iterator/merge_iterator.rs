@@ -0,0 +1,291 @@
+//
+//
+//
+// MergingIterator
+//
+// K-way merge over child iterators (memtables, L0 tables, level iterators...) producing one stream of internal keys in
+// InternalKeyComparator order. Valid children are kept in a binary min-heap keyed by their current key so the smallest entry is
+// always at the top:
+//
+//   next()  - advance the top child and sift it down (or pop it when exhausted): O(log k)
+//   seek()  - seek every child and rebuild the heap: O(k)
+//
+// Reverse iteration uses the same heap as a max-heap. Switching direction repositions every child other than the current one on the
+// other side of the current key (seek + next / seek_for_prev + prev) and rebuilds the heap, so a direction switch costs O(k) seeks.
+//
+// Children are given newest source first. Two children can only hold the same internal key if something was ingested twice, in which
+// case the lower child index (newer source) wins so the output is deterministic.
+//
+// Children are IterBoxes so a tree built from an IterAlloc can place them in its arena (see iter_alloc.rs).
+//
+// Errors: a child which becomes invalid with a status stops the merge. valid() returns false and status() returns the child's error,
+// which is important as silently skipping a corrupt child would surface older (deleted or overwritten) versions.
+
+use std::cmp::Ordering;
+use std::sync::Arc;
+
+use crate::error::{Error, Result};
+use crate::iterator::internal_iterator::InternalIterator;
+use crate::iterator::iter_alloc::{IterAlloc, IterBox};
+use crate::key::comparator::Comparator;
+use crate::range::fragment::FragmentedTombstones;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    Forward,
+    Reverse,
+}
+
+pub(crate) struct MergingIterator<'a> {
+    comparator: Arc<dyn Comparator>,
+    children: Vec<IterBox<'a>>,
+    // Indexes of the valid children - a min-heap on their current key going forward, a max-heap in reverse
+    heap: Vec<usize>,
+    direction: Direction,
+    has_error: bool,
+    // Copy of the current key while children are repositioned for a direction switch
+    switch_key: Vec<u8>,
+}
+
+impl<'a> MergingIterator<'a> {
+    pub(crate) fn new(
+        comparator: Arc<dyn Comparator>,
+        children: Vec<Box<dyn InternalIterator + 'a>>,
+    ) -> Self {
+        let heap = Vec::with_capacity(children.len());
+        Self {
+            comparator,
+            children: children.into_iter().map(IterBox::from).collect(),
+            heap,
+            direction: Direction::Forward,
+            has_error: false,
+            switch_key: Vec::new(),
+        }
+    }
+
+    /// Builds the iterator with the heap and key buffers of an IterAlloc. `children` should come from the same alloc.
+    pub(crate) fn new_in(
+        comparator: Arc<dyn Comparator>,
+        children: Vec<IterBox<'a>>,
+        alloc: &mut IterAlloc,
+    ) -> Self {
+        Self {
+            comparator,
+            children,
+            heap: std::mem::take(&mut alloc.heap),
+            direction: Direction::Forward,
+            has_error: false,
+            switch_key: std::mem::take(&mut alloc.switch_key),
+        }
+    }
+
+    /// Drops every child and hands the buffers back to the alloc. The iterator is empty afterwards.
+    pub(crate) fn release_into(&mut self, alloc: &mut IterAlloc) {
+        self.heap.clear();
+        alloc.put_children(std::mem::take(&mut self.children));
+        alloc.heap = std::mem::take(&mut self.heap);
+        alloc.switch_key = std::mem::take(&mut self.switch_key);
+    }
+
+    #[inline]
+    pub(crate) fn num_children(&self) -> usize {
+        self.children.len()
+    }
+
+    // True if child a's entry comes before child b's in the current direction
+    #[inline]
+    fn less(&self, a: usize, b: usize) -> bool {
+        let ord = self
+            .comparator
+            .compare(self.children[a].key(), self.children[b].key())
+            .then(a.cmp(&b));
+        match self.direction {
+            Direction::Forward => ord == Ordering::Less,
+            Direction::Reverse => ord == Ordering::Greater,
+        }
+    }
+
+    fn sift_down(&mut self, mut pos: usize) {
+        let len = self.heap.len();
+        loop {
+            let left = 2 * pos + 1;
+            if left >= len {
+                return;
+            }
+            let right = left + 1;
+            let first = if right < len && self.less(self.heap[right], self.heap[left]) {
+                right
+            } else {
+                left
+            };
+            if !self.less(self.heap[first], self.heap[pos]) {
+                return;
+            }
+            self.heap.swap(pos, first);
+            pos = first;
+        }
+    }
+
+    // Rebuilds the heap from every valid child after the children have been repositioned
+    fn rebuild_heap(&mut self) {
+        self.heap.clear();
+        self.has_error = false;
+
+        for (i, child) in self.children.iter().enumerate() {
+            if child.valid() {
+                self.heap.push(i);
+            } else if child.status().is_some() {
+                self.has_error = true;
+            }
+        }
+
+        for pos in (0..self.heap.len() / 2).rev() {
+            self.sift_down(pos);
+        }
+    }
+
+    // Called after the top child moved - restores the heap or drops the child if it is exhausted
+    fn fix_top(&mut self) {
+        let child = &self.children[self.heap[0]];
+        if child.valid() {
+            self.sift_down(0);
+            return;
+        }
+
+        if child.status().is_some() {
+            self.has_error = true;
+        }
+        self.heap.swap_remove(0);
+        if !self.heap.is_empty() {
+            self.sift_down(0);
+        }
+    }
+
+    // Puts every child other than the current one on the far side of the current key for the new direction
+    fn switch_direction(&mut self, direction: Direction) {
+        let current = self.heap[0];
+        self.switch_key.clear();
+        self.switch_key
+            .extend_from_slice(self.children[current].key());
+
+        for (i, child) in self.children.iter_mut().enumerate() {
+            if i == current {
+                continue;
+            }
+            match direction {
+                Direction::Forward => {
+                    child.seek(&self.switch_key);
+                    if child.valid()
+                        && self.comparator.compare(child.key(), &self.switch_key) == Ordering::Equal
+                    {
+                        child.next();
+                    }
+                }
+                Direction::Reverse => {
+                    child.seek_for_prev(&self.switch_key);
+                    if child.valid()
+                        && self.comparator.compare(child.key(), &self.switch_key) == Ordering::Equal
+                    {
+                        child.prev();
+                    }
+                }
+            }
+        }
+
+        self.direction = direction;
+        self.rebuild_heap();
+        debug_assert!(self.has_error || self.heap[0] == current);
+    }
+}
+
+impl<'a> InternalIterator for MergingIterator<'a> {
+    fn seek_to_first(&mut self) {
+        for child in self.children.iter_mut() {
+            child.seek_to_first();
+        }
+        self.direction = Direction::Forward;
+        self.rebuild_heap();
+    }
+
+    fn seek_to_last(&mut self) {
+        for child in self.children.iter_mut() {
+            child.seek_to_last();
+        }
+        self.direction = Direction::Reverse;
+        self.rebuild_heap();
+    }
+
+    fn seek(&mut self, key: &[u8]) {
+        for child in self.children.iter_mut() {
+            child.seek(key);
+        }
+        self.direction = Direction::Forward;
+        self.rebuild_heap();
+    }
+
+    fn seek_for_prev(&mut self, key: &[u8]) {
+        for child in self.children.iter_mut() {
+            child.seek_for_prev(key);
+        }
+        self.direction = Direction::Reverse;
+        self.rebuild_heap();
+    }
+
+    #[inline]
+    fn valid(&self) -> bool {
+        !self.heap.is_empty() && !self.has_error
+    }
+
+    fn next(&mut self) {
+        debug_assert!(self.valid());
+
+        if self.direction == Direction::Reverse {
+            self.switch_direction(Direction::Forward);
+            if !self.valid() {
+                return;
+            }
+        }
+
+        self.children[self.heap[0]].next();
+        self.fix_top();
+    }
+
+    fn prev(&mut self) {
+        debug_assert!(self.valid());
+
+        if self.direction == Direction::Forward {
+            self.switch_direction(Direction::Reverse);
+            if !self.valid() {
+                return;
+            }
+        }
+
+        self.children[self.heap[0]].prev();
+        self.fix_top();
+    }
+
+    #[inline]
+    fn key(&self) -> &[u8] {
+        debug_assert!(self.valid());
+        self.children[self.heap[0]].key()
+    }
+
+    #[inline]
+    fn value(&self) -> &[u8] {
+        debug_assert!(self.valid());
+        self.children[self.heap[0]].value()
+    }
+
+    fn status(&self) -> Option<&Error> {
+        if !self.has_error {
+            return None;
+        }
+        self.children.iter().find_map(|child| child.status())
+    }
+
+    fn range_tombstones(&self, out: &mut Vec<Arc<FragmentedTombstones>>) -> Result<()> {
+        self.children
+            .iter()
+            .try_for_each(|child| child.range_tombstones(out))
+    }
+}
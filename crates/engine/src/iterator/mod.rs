@@ -1,6 +1,8 @@
+pub(crate) mod bounds;
 pub(crate) mod db_iter;
 pub(crate) mod internal_iterator;
 pub(crate) mod iter_alloc;
+pub(crate) mod level_iterator;
 pub(crate) mod merge_iterator;
 
 // +-------------------------------+
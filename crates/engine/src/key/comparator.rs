@@ -2,13 +2,39 @@
 use std::cmp::Ordering;
 use std::sync::Arc;
 
+use crate::key::internal_key::{MAX_SEQUENCE_NUMBER, OperationType, encode_trailer};
+
 /// Comparator uses a base lexicographical comparison by first comparing the user key and then the sequence ordering
 /// for the correct sequence ordering to work (desc) we need to use big_endian encoding.
 /// This must be adhered to for internal key encoding
 
 pub trait Comparator: Send + Sync {
     fn compare(&self, a: &[u8], b: &[u8]) -> Ordering;
-    // TODO: Add separator and successor and other signatures we may need
+
+    /// Name of the ordering. It is persisted in the MANIFEST and a DB can only be reopened with a comparator of the same name.
+    fn name(&self) -> &str;
+
+    /// Changes `start` to a short key in [start, limit) if there is one. Used for the index separators of table files.
+    fn find_shortest_separator(&self, _start: &mut Vec<u8>, _limit: &[u8]) {}
+
+    /// Changes `key` to a short key >= key. Used for the index separator of the last block of a table file.
+    fn find_short_successor(&self, _key: &mut Vec<u8>) {}
+
+    /// Size of the timestamp at the end of every user key - 0 if keys have none (see key/timestamp.rs).
+    fn timestamp_size(&self) -> usize {
+        0
+    }
+
+    /// Compares the keys without their timestamps. A key whose flag is false has no timestamp.
+    fn compare_without_timestamp(
+        &self,
+        a: &[u8],
+        _a_has_ts: bool,
+        b: &[u8],
+        _b_has_ts: bool,
+    ) -> Ordering {
+        self.compare(a, b)
+    }
 }
 
 pub(crate) struct DefaultComparator {}
@@ -25,15 +51,70 @@ impl Comparator for DefaultComparator {
     fn compare(&self, a: &[u8], b: &[u8]) -> Ordering {
         a.cmp(b)
     }
+
+    fn name(&self) -> &str {
+        "BytewiseComparator"
+    }
+
+    // Keeps the common prefix and the first differing byte of start, incremented if that is still below limit's
+    fn find_shortest_separator(&self, start: &mut Vec<u8>, limit: &[u8]) {
+        let prefix = start.iter().zip(limit).take_while(|(a, b)| a == b).count();
+        // One key is a prefix of the other
+        if prefix >= start.len().min(limit.len()) {
+            return;
+        }
+
+        let byte = start[prefix];
+        if byte < u8::MAX && byte + 1 < limit[prefix] {
+            start[prefix] += 1;
+            start.truncate(prefix + 1);
+        }
+    }
+
+    // Increments the first byte which can be incremented. A key of 0xff bytes is left as it is
+    fn find_short_successor(&self, key: &mut Vec<u8>) {
+        if let Some(i) = key.iter().position(|&b| b != u8::MAX) {
+            key[i] += 1;
+            key.truncate(i + 1);
+        }
+    }
 }
 
-pub(crate) struct InternalKeyComparator {}
+/// Orders internal keys by the user key with the user comparator, then by the trailer (seq no and op type) descending.
+pub(crate) struct InternalKeyComparator {
+    user_comparator: Arc<dyn Comparator>,
+}
 
 pub(crate) type InternalKeyComparatorArc = Arc<InternalKeyComparator>;
 
 impl InternalKeyComparator {
+    /// Orders user keys bytewise.
     pub(crate) fn new() -> InternalKeyComparatorArc {
-        Arc::new(InternalKeyComparator {})
+        Self::with_user_comparator(DefaultComparator::new())
+    }
+
+    pub(crate) fn with_user_comparator(
+        user_comparator: Arc<dyn Comparator>,
+    ) -> InternalKeyComparatorArc {
+        Arc::new(InternalKeyComparator { user_comparator })
+    }
+
+    #[inline]
+    pub(crate) fn user_comparator(&self) -> &Arc<dyn Comparator> {
+        &self.user_comparator
+    }
+
+    // A shortened user key as an internal key. The trailer sorts it before every entry of the user key
+    fn shortened(&self, user_key: &[u8], shortened: Vec<u8>) -> Option<Vec<u8>> {
+        (shortened.len() < user_key.len()
+            && self.user_comparator.compare(user_key, &shortened) == Ordering::Less)
+            .then(|| {
+                [
+                    shortened.as_slice(),
+                    &encode_trailer(MAX_SEQUENCE_NUMBER, OperationType::Max),
+                ]
+                .concat()
+            })
     }
 }
 
@@ -42,12 +123,100 @@ impl Comparator for InternalKeyComparator {
         let (a_user, a_trailer) = a.split_at(a.len() - 8);
         let (b_user, b_trailer) = b.split_at(b.len() - 8);
 
-        match a_user.cmp(b_user) {
-            Ordering::Equal => {
-                // reverse ordering for seq/op
-                b_trailer.cmp(a_trailer)
-            }
+        match self.user_comparator.compare(a_user, b_user) {
+            // reverse ordering for seq/op
+            Ordering::Equal => b_trailer.cmp(a_trailer),
             other => other,
         }
     }
+
+    fn name(&self) -> &str {
+        "InternalKeyComparator"
+    }
+
+    fn find_shortest_separator(&self, start: &mut Vec<u8>, limit: &[u8]) {
+        let user_start = &start[..start.len() - 8];
+        let mut separator = user_start.to_vec();
+        self.user_comparator
+            .find_shortest_separator(&mut separator, &limit[..limit.len() - 8]);
+        if let Some(separator) = self.shortened(user_start, separator) {
+            *start = separator;
+        }
+    }
+
+    fn find_short_successor(&self, key: &mut Vec<u8>) {
+        let user_key = &key[..key.len() - 8];
+        let mut successor = user_key.to_vec();
+        self.user_comparator.find_short_successor(&mut successor);
+        if let Some(successor) = self.shortened(user_key, successor) {
+            *key = successor;
+        }
+    }
+
+    fn timestamp_size(&self) -> usize {
+        self.user_comparator.timestamp_size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key::internal_key::InternalKeyRef;
+
+    fn separator(cmp: &dyn Comparator, start: &[u8], limit: &[u8]) -> Vec<u8> {
+        let mut start = start.to_vec();
+        cmp.find_shortest_separator(&mut start, limit);
+        start
+    }
+
+    fn successor(cmp: &dyn Comparator, key: &[u8]) -> Vec<u8> {
+        let mut key = key.to_vec();
+        cmp.find_short_successor(&mut key);
+        key
+    }
+
+    fn ikey(user_key: &[u8], seq: u64) -> Vec<u8> {
+        [user_key, &encode_trailer(seq, OperationType::Put)].concat()
+    }
+
+    #[test]
+    fn bytewise_separators_are_shortened() {
+        let cmp = DefaultComparator::new();
+        assert_eq!(separator(cmp.as_ref(), b"abcdef", b"abzz"), b"abd");
+        // Nothing shorter fits between them
+        assert_eq!(separator(cmp.as_ref(), b"abc", b"abd"), b"abc");
+        assert_eq!(separator(cmp.as_ref(), b"ab", b"abcd"), b"ab");
+        assert_eq!(separator(cmp.as_ref(), b"a\xff\xff", b"b"), b"a\xff\xff");
+
+        assert_eq!(successor(cmp.as_ref(), b"abc"), b"b");
+        assert_eq!(successor(cmp.as_ref(), b"\xff\xffa"), b"\xff\xffb");
+        assert_eq!(successor(cmp.as_ref(), b"\xff\xff"), b"\xff\xff");
+    }
+
+    #[test]
+    fn internal_separators_sort_between_their_keys() {
+        let icmp = InternalKeyComparator::new();
+        let start = ikey(b"apple", 5);
+        let limit = ikey(b"cherry", 9);
+        let sep = separator(icmp.as_ref(), &start, &limit);
+        assert_eq!(
+            sep,
+            [
+                b"b".as_slice(),
+                &encode_trailer(MAX_SEQUENCE_NUMBER, OperationType::Max)
+            ]
+            .concat()
+        );
+        assert_eq!(icmp.compare(&start, &sep), Ordering::Less);
+        assert_eq!(icmp.compare(&sep, &limit), Ordering::Less);
+
+        // The user key can't be shortened: the key is kept, trailer included
+        let limit = ikey(b"applf", 9);
+        assert_eq!(separator(icmp.as_ref(), &start, &limit), start);
+        assert_eq!(separator(icmp.as_ref(), &start, &ikey(b"apple", 1)), start);
+
+        let sep = successor(icmp.as_ref(), &start);
+        assert_eq!(icmp.compare(&start, &sep), Ordering::Less);
+        assert_eq!(InternalKeyRef::from(sep.as_slice()).user_key, b"b");
+    }
 }
@@ -42,6 +42,16 @@ impl<const N: usize> InnerKey<N> {
         }
     }
 
+    pub(super) fn as_mut_slice(&mut self) -> &mut [u8] {
+        match self.external {
+            // # Safety
+            //
+            // Same as as_slice - external always points at a live buffer of len bytes owned by the wrapper
+            Some(n) => unsafe { std::slice::from_raw_parts_mut(n.as_ptr(), self.len) },
+            None => self._inline[..self.len].as_mut(),
+        }
+    }
+
     pub(super) fn set_inline(&mut self, len: usize) {
         debug_assert!(len <= N);
 
@@ -21,12 +21,24 @@ use std::fmt::Display;
 
 pub(super) const INLINE_IK_SIZE: usize = 20;
 
+// Sequence numbers are packed into the top 56 bits of the trailer
+pub(crate) const MAX_SEQUENCE_NUMBER: u64 = (1 << 56) - 1;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 #[repr(u8)]
 pub(crate) enum OperationType {
     Put = 1,
     Delete = 2,
     Merge = 3, // TODO: Implement Merge Operation into the system
+    // Deletes a key which was Put exactly once. Reads treat it the same as Delete
+    SingleDelete = 4,
+    // Put whose value is in a blob file - the value stored in the LSM is an encoded BlobIndex (see blob/blob_index.rs)
+    BlobIndex = 5,
+    // Put which is only visible until a wall clock time - the value is followed by the expiry (see ttl.rs)
+    PutWithExpiry = 6,
+    // Range tombstone - the user key is the start and the value the (exclusive) end. Only stored in the range deletion skiplist of a
+    // memtable and the range deletion block of an SST, never next to point entries
+    RangeDelete = 15,
     Max = 255,
 }
 
@@ -42,6 +54,10 @@ impl From<u8> for OperationType {
             1 => OperationType::Put,
             2 => OperationType::Delete,
             3 => OperationType::Merge,
+            4 => OperationType::SingleDelete,
+            5 => OperationType::BlobIndex,
+            6 => OperationType::PutWithExpiry,
+            15 => OperationType::RangeDelete,
             255 => OperationType::Max,
             _ => unreachable!(),
         }
@@ -54,6 +70,10 @@ impl Display for OperationType {
             OperationType::Put => write!(f, "Put"),
             OperationType::Delete => write!(f, "Delete"),
             OperationType::Merge => write!(f, "Merge"),
+            OperationType::SingleDelete => write!(f, "SingleDelete"),
+            OperationType::BlobIndex => write!(f, "BlobIndex"),
+            OperationType::PutWithExpiry => write!(f, "PutWithExpiry"),
+            OperationType::RangeDelete => write!(f, "RangeDelete"),
             OperationType::Max => write!(f, "Max"),
         }
     }
@@ -62,7 +82,7 @@ impl Display for OperationType {
 // A Pack function to take the seq_no and operation type and pack them into a trailer u64
 #[inline(always)]
 fn pack_trailer(seq_no: u64, op: OperationType) -> u64 {
-    debug_assert!(seq_no < (1 << 56)); // Enforce that seq_no is less than 2^56
+    debug_assert!(seq_no <= MAX_SEQUENCE_NUMBER); // Enforce that seq_no is less than 2^56
     (seq_no << 8) | u64::from(op)
 }
 
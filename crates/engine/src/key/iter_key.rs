@@ -4,6 +4,7 @@ use super::OperationType;
 use super::encode_into;
 use super::inner_key::ITER_INLINE;
 use super::inner_key::InnerKey;
+use super::internal_key::encode_trailer;
 
 // Iter Key - owned by an iterator and re-used across iterations
 
@@ -22,6 +23,14 @@ impl<const N: usize> IterKey<N> {
         }
     }
 
+    /// A key with no heap buffer yet - it is allocated by the first key which doesn't fit inline.
+    pub(crate) fn unallocated() -> Self {
+        Self {
+            _inner: InnerKey::new(),
+            heap: Vec::new(),
+        }
+    }
+
     pub(crate) fn set(&mut self, user_key: &[u8], seq_no: u64, op: OperationType) {
         let total = user_key.len() + 8;
         debug_assert!(total <= MAX_KEY_SIZE);
@@ -34,9 +43,10 @@ impl<const N: usize> IterKey<N> {
 
         // Use heap
 
+        // reserve() is relative to the length, which is 0 after the clear
         self.heap.clear();
         if self.heap.capacity() < total {
-            self.heap.reserve(total - self.heap.capacity());
+            self.heap.reserve(total);
         }
 
         // Safety
@@ -61,7 +71,7 @@ impl<const N: usize> IterKey<N> {
 
         self.heap.clear();
         if self.heap.capacity() < len {
-            self.heap.reserve(len - self.heap.capacity());
+            self.heap.reserve(len);
         }
 
         unsafe {
@@ -72,11 +82,25 @@ impl<const N: usize> IterKey<N> {
         self.heap[..len].copy_from_slice(slice);
     }
 
+    /// Rewrites the trailer of the saved internal key in place, keeping the user key.
+    pub(crate) fn update_trailer(&mut self, seq_no: u64, op: OperationType) {
+        let key = self._inner.as_mut_slice();
+        let len = key.len();
+        debug_assert!(len >= 8);
+        key[len - 8..].copy_from_slice(&encode_trailer(seq_no, op));
+    }
+
     pub(crate) fn as_slice(&self) -> &[u8] {
         self._inner.as_slice()
     }
 }
 
+impl<const N: usize> Default for IterKey<N> {
+    fn default() -> Self {
+        Self::unallocated()
+    }
+}
+
 impl<const N: usize> AsRef<[u8]> for IterKey<N> {
     fn as_ref(&self) -> &[u8] {
         self.as_slice()
@@ -124,4 +148,36 @@ mod tests {
 
         assert_eq!(ptr, ptr_2);
     }
+
+    #[test]
+    fn iter_key_grows_past_its_buffer() {
+        let mut iter_key = InternalIterKey::unallocated();
+
+        for len in [40, 60, 700, 2000] {
+            let user_key = vec![b'k'; len];
+            iter_key.set(&user_key, 1, OperationType::Put);
+            assert_eq!(InternalKeyRef::from(iter_key.as_ref()).user_key, user_key);
+
+            let slice = vec![b's'; len + 8];
+            iter_key.set_from_slice(&slice);
+            assert_eq!(iter_key.as_slice(), slice);
+        }
+    }
+
+    #[test]
+    fn iter_key_update_trailer() {
+        let short = b"User".as_slice();
+        let long = b"Large-User-Key-Which-Should-Cause-A-Heap-Allocation".as_slice();
+
+        for user_key in [short, long] {
+            let mut iter_key = InternalIterKey::new();
+            iter_key.set(user_key, 1, OperationType::Put);
+            iter_key.update_trailer(9, OperationType::Delete);
+
+            let ik = InternalKeyRef::from(iter_key.as_ref());
+            assert_eq!(ik.user_key, user_key);
+            assert_eq!(ik.seq_no, 9);
+            assert_eq!(ik.op, OperationType::Delete as u8);
+        }
+    }
 }
@@ -5,6 +5,8 @@ pub(crate) mod inner_key;
 pub(crate) mod internal_key;
 pub(crate) mod iter_key;
 pub(crate) mod lookup_key;
+pub(crate) mod prefix_extractor;
+pub(crate) mod timestamp;
 
 // TODO: Handling User key allocation
 // NOTE: On the write path we simply encode the internal key and write directly into memtable arena
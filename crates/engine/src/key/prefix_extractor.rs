@@ -0,0 +1,63 @@
+//
+//
+//
+// PrefixExtractor
+//
+// Maps a user key to its prefix - the part of the key which groups related keys together (e.g a tenant or table id). With
+// ReadOptions::prefix_same_as_start an iterator only returns keys with the same prefix as its seek target.
+//
+// Keys outside the domain of the extractor (e.g shorter than a fixed prefix) have no prefix.
+
+pub trait PrefixExtractor: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// True if the key has a prefix.
+    fn in_domain(&self, key: &[u8]) -> bool;
+
+    /// Prefix of a key which is in the domain.
+    fn transform<'k>(&self, key: &'k [u8]) -> &'k [u8];
+}
+
+/// The first `len` bytes of the key. Shorter keys are outside the domain.
+pub(crate) struct FixedPrefixExtractor {
+    len: usize,
+}
+
+impl FixedPrefixExtractor {
+    pub(crate) fn new(len: usize) -> Self {
+        Self { len }
+    }
+}
+
+impl PrefixExtractor for FixedPrefixExtractor {
+    fn name(&self) -> &str {
+        "victory.FixedPrefix"
+    }
+
+    #[inline]
+    fn in_domain(&self, key: &[u8]) -> bool {
+        key.len() >= self.len
+    }
+
+    #[inline]
+    fn transform<'k>(&self, key: &'k [u8]) -> &'k [u8] {
+        debug_assert!(self.in_domain(key));
+        &key[..self.len]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed_prefix() {
+        let extractor = FixedPrefixExtractor::new(3);
+
+        assert!(extractor.in_domain(b"abc"));
+        assert!(extractor.in_domain(b"abcdef"));
+        assert!(!extractor.in_domain(b"ab"));
+
+        assert_eq!(extractor.transform(b"abcdef"), b"abc");
+    }
+}
@@ -0,0 +1,149 @@
+//
+//
+//
+// User-Defined Timestamps
+//
+// A column family whose comparator has a timestamp size (UserTimestampComparator) keeps a fixed-size timestamp at the end of every user
+// key. The application picks the timestamps, e.g. from its own hybrid logical clock, and writes each version of a key under its own
+// timestamp (Batch::put_cf_with_timestamp):
+//
+// | user key ... | timestamp (timestamp_size bytes) | 8-byte trailer |
+//
+// Keys are ordered by the user key ascending, then the timestamp descending, then the trailer (seq no descending) - the newest version
+// of a key by timestamp comes first. Timestamps compare bytewise, so numbers must be encoded big-endian (encode_u64_timestamp).
+//
+// A read with ReadOptions::timestamp sees the newest version of each key whose timestamp is at or below it (and whose seq no is at or
+// below the read seq no). Point lookups need one, an iterator without one sees the newest version of each key.
+//
+// Versions of a key below the column family's full_history_ts_low (DbImpl::increase_full_history_ts_low) are trimmed by compactions:
+// only the newest of them is kept, which is what a read at full_history_ts_low or later sees. Reads below it may see an incomplete
+// history. Range deletions are not supported on a column family with timestamps.
+
+use std::cmp::Ordering;
+use std::sync::Arc;
+
+use crate::key::comparator::Comparator;
+
+/// The user key without its timestamp.
+#[inline]
+pub(crate) fn strip_timestamp(user_key: &[u8], timestamp_size: usize) -> &[u8] {
+    &user_key[..user_key.len() - timestamp_size]
+}
+
+/// The timestamp of the user key.
+#[inline]
+pub(crate) fn timestamp_of(user_key: &[u8], timestamp_size: usize) -> &[u8] {
+    &user_key[user_key.len() - timestamp_size..]
+}
+
+/// A u64 timestamp in the byte order timestamps compare in.
+#[inline]
+pub(crate) fn encode_u64_timestamp(ts: u64) -> [u8; 8] {
+    ts.to_be_bytes()
+}
+
+/// Orders user keys with a timestamp of `timestamp_size` bytes: by the user key with `user_comparator`, then by the timestamp, newest
+/// first.
+pub(crate) struct UserTimestampComparator {
+    user_comparator: Arc<dyn Comparator>,
+    timestamp_size: usize,
+    // The user comparator's name and the timestamp size, a DB written with another timestamp size can't be read
+    name: String,
+}
+
+impl UserTimestampComparator {
+    pub(crate) fn new(user_comparator: Arc<dyn Comparator>, timestamp_size: usize) -> Arc<Self> {
+        let name = format!("{}.u{}ts", user_comparator.name(), timestamp_size * 8);
+        Arc::new(Self {
+            user_comparator,
+            timestamp_size,
+            name,
+        })
+    }
+}
+
+impl Comparator for UserTimestampComparator {
+    fn compare(&self, a: &[u8], b: &[u8]) -> Ordering {
+        self.compare_without_timestamp(a, true, b, true)
+            .then_with(|| {
+                timestamp_of(b, self.timestamp_size).cmp(timestamp_of(a, self.timestamp_size))
+            })
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn timestamp_size(&self) -> usize {
+        self.timestamp_size
+    }
+
+    fn compare_without_timestamp(
+        &self,
+        a: &[u8],
+        a_has_ts: bool,
+        b: &[u8],
+        b_has_ts: bool,
+    ) -> Ordering {
+        let a = if a_has_ts {
+            strip_timestamp(a, self.timestamp_size)
+        } else {
+            a
+        };
+        let b = if b_has_ts {
+            strip_timestamp(b, self.timestamp_size)
+        } else {
+            b
+        };
+        self.user_comparator.compare(a, b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key::comparator::{DefaultComparator, InternalKeyComparator};
+    use crate::key::internal_key::{OperationType, encode_trailer};
+
+    fn key(user_key: &[u8], ts: u64) -> Vec<u8> {
+        [user_key, &encode_u64_timestamp(ts)].concat()
+    }
+
+    #[test]
+    fn keys_order_by_user_key_then_newest_timestamp() {
+        let cmp = UserTimestampComparator::new(DefaultComparator::new(), 8);
+        assert_eq!(cmp.compare(&key(b"a", 5), &key(b"a", 9)), Ordering::Greater);
+        assert_eq!(cmp.compare(&key(b"a", 9), &key(b"ab", 1)), Ordering::Less);
+        assert_eq!(cmp.compare(&key(b"a", 1), &key(b"a", 1)), Ordering::Equal);
+        assert_eq!(
+            cmp.compare_without_timestamp(&key(b"a", 5), true, &key(b"a", 9), true),
+            Ordering::Equal
+        );
+        assert_eq!(
+            cmp.compare_without_timestamp(&key(b"b", 5), true, b"a", false),
+            Ordering::Greater
+        );
+
+        // Timestamp before the trailer
+        let icmp = InternalKeyComparator::with_user_comparator(cmp);
+        let ikey = |user_key: &[u8], ts, seq| {
+            [
+                key(user_key, ts).as_slice(),
+                &encode_trailer(seq, OperationType::Put),
+            ]
+            .concat()
+        };
+        assert_eq!(
+            icmp.compare(&ikey(b"a", 9, 1), &ikey(b"a", 5, 7)),
+            Ordering::Less
+        );
+        assert_eq!(
+            icmp.compare(&ikey(b"a", 5, 7), &ikey(b"a", 5, 3)),
+            Ordering::Less
+        );
+        assert_eq!(
+            icmp.compare(&ikey(b"a", 1, 1), &ikey(b"ab", 9, 9)),
+            Ordering::Less
+        );
+    }
+}
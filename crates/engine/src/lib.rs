@@ -1,11 +1,21 @@
+mod backup;
+mod blob;
+mod checkpoint;
 mod column_family;
+mod compaction;
 mod db;
+mod error;
 mod iterator;
 mod key;
 mod memtable;
+mod merge;
+mod multi_get;
 mod options;
 mod range;
+mod table;
 mod thread_ctx;
+mod transaction;
+mod ttl;
 mod versioning;
 
 pub mod block;
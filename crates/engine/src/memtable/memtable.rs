@@ -1,21 +1,32 @@
 // Memtable
+//
+// Range tombstones are kept apart from the point entries in a second skiplist over the same arena: the key is the start with the
+// RangeDelete op type, the value the (exclusive) end. Reads need them fragmented (range/fragment.rs), which is done on first use and
+// cached until another tombstone is added.
 
 use std::fmt::Display;
 use std::marker::PhantomData;
 use std::ptr::{self, NonNull};
 use std::slice;
-use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, AtomicPtr, AtomicU64, Ordering};
 use std::sync::atomic::{AtomicU8, AtomicU16};
+use std::sync::{Arc, Mutex};
 
+use crate::db::read_path::{GetContext, GetSource};
 use crate::db::write_batch::Batch;
+use crate::error::{Error, Result};
 use crate::iterator::internal_iterator::InternalIterator;
-use crate::key::comparator::Comparator;
-use crate::key::internal_key::{InternalKeyRef, OperationType, encode_trailer};
-use crate::memtable::skip_list::{Iter, Node, SkipList};
+use crate::key::comparator::{Comparator, InternalKeyComparator};
+use crate::key::internal_key::{
+    InternalKeyRef, MAX_SEQUENCE_NUMBER, OperationType, encode_trailer,
+};
+use crate::key::lookup_key::LookUpInternalKey;
+use crate::memtable::skip_list::{Iter, Node, SkipList, SkipListError};
+use crate::multi_get::{MultiGetBatch, MultiGetSource};
+use crate::range::fragment::{FragmentedTombstones, RangeTombstone};
 use mem::allocator::Allocator;
 use mem::arena::ArenaSize;
-use mem::arena::{Arena, ArenaPolicy};
+use mem::arena::{Arena, ArenaError, ArenaPolicy};
 
 pub(crate) type MemID = u64;
 
@@ -111,6 +122,30 @@ impl<S: MemtableState> Clone for Memtable<S> {
 }
 
 impl<S: MemtableState> Memtable<S> {
+    pub(crate) fn iter(&self) -> MemtableIterator<'_> {
+        self.inner.iter()
+    }
+
+    /// The range tombstones of the memtable, fragmented. None if it has none.
+    pub(crate) fn range_tombstones(&self) -> Option<Arc<FragmentedTombstones>> {
+        self.inner.range_tombstones()
+    }
+
+    /// Seq no of the last write before the memtable was created. Writes after it are in this memtable or a newer one.
+    pub(crate) fn earliest_sequence(&self) -> u64 {
+        self.inner.earliest_sequence.load(Ordering::Acquire)
+    }
+
+    /// Bytes of the arena taken by the entries so far.
+    pub(crate) fn approximate_memory_usage(&self) -> usize {
+        self.inner.arena.memory_used()
+    }
+
+    /// Seq no of the newest write to the user key in the memtable (a point entry or a range tombstone covering it). None if there is none.
+    pub(crate) fn newest_sequence(&self, user_key: &[u8]) -> Option<u64> {
+        self.inner.newest_sequence(user_key)
+    }
+
     unsafe fn encode_key(&self, ptr: *mut Node, user_key: &[u8], seq_no: u32, op_type: u32) {
         todo!()
     }
@@ -128,6 +163,42 @@ pub(crate) struct ReadableMemtable {
 impl ReadableMemtable {
     // TODO:
     // Safe readable methods
+
+    pub(crate) fn iter(&self) -> MemtableIterator<'_> {
+        self.inner.iter()
+    }
+
+    pub(crate) fn newest_sequence(&self, user_key: &[u8]) -> Option<u64> {
+        self.inner.newest_sequence(user_key)
+    }
+
+    pub(crate) fn earliest_sequence(&self) -> u64 {
+        self.inner.earliest_sequence.load(Ordering::Acquire)
+    }
+}
+
+impl GetSource for ReadableMemtable {
+    fn get(&self, lookup_key: &[u8], ctx: &mut GetContext<'_>) -> bool {
+        self.inner.get_with_context(lookup_key, ctx)
+    }
+}
+
+impl<S: MemtableState> GetSource for Memtable<S> {
+    fn get(&self, lookup_key: &[u8], ctx: &mut GetContext<'_>) -> bool {
+        self.inner.get_with_context(lookup_key, ctx)
+    }
+}
+
+impl MultiGetSource for ReadableMemtable {
+    fn multi_get(&self, batch: &mut MultiGetBatch<'_>) {
+        batch.walk(&mut self.inner.iter());
+    }
+}
+
+impl<S: MemtableState> MultiGetSource for Memtable<S> {
+    fn multi_get(&self, batch: &mut MultiGetBatch<'_>) {
+        batch.walk(&mut self.inner.iter());
+    }
 }
 
 impl Memtable<Mutable> {
@@ -138,7 +209,7 @@ impl Memtable<Mutable> {
         id: MemID,
         arena_size: ArenaPolicy,
         allocator: Allocator,
-        comp: Arc<dyn Comparator>,
+        comp: Arc<InternalKeyComparator>,
     ) -> Self {
         Self {
             _state: PhantomData,
@@ -146,17 +217,58 @@ impl Memtable<Mutable> {
         }
     }
 
+    /// Sets the seq no of the last write before the memtable was created (0 by default).
+    pub(crate) fn with_earliest_sequence(self, sequence: u64) -> Self {
+        self.inner
+            .earliest_sequence
+            .store(sequence, Ordering::Release);
+        self
+    }
+
     pub(crate) fn readable_memtable(&self) -> ReadableMemtable {
         ReadableMemtable {
             inner: Arc::clone(&self.inner),
         }
     }
 
-    pub(crate) fn insert(&self, key: &[u8], value: &[u8]) {
-        self.inner.insert(key, value)
+    /// Fails with Busy once the arena has no room left for the entry - the memtable has to be switched before it is retried.
+    pub(crate) fn insert(&self, key: &[u8], value: &[u8]) -> Result<()> {
+        self.inner.insert(key, value).map_err(insert_error)
+    }
+
+    /// Largest number of bytes an entry of the internal key and value can take in the arena.
+    pub(crate) fn max_entry_size(key_len: usize, value_len: usize) -> usize {
+        Node::max_size(key_len, value_len)
+    }
+
+    /// Deletes [start, end) below `seq`. An empty range is a no-op.
+    pub(crate) fn delete_range(&self, start: &[u8], end: &[u8], seq: u64) -> Result<()> {
+        match self.inner.user_comparator.compare(start, end) {
+            std::cmp::Ordering::Greater => Err(Error::InvalidArgument(
+                "range deletion start is after its end".into(),
+            )),
+            std::cmp::Ordering::Equal => Ok(()),
+            std::cmp::Ordering::Less => self
+                .inner
+                .delete_range(start, end, seq)
+                .map_err(insert_error),
+        }
+    }
+
+    /// Moves the memtable out of the write path. The returned handle shares the same skiplist and can only be read.
+    pub(crate) fn freeze(self) -> Memtable<Immutable> {
+        self.inner
+            .lifecycle
+            .store(MemLifeCycle::Frozen as u8, Ordering::Release);
+        Memtable {
+            _state: PhantomData,
+            inner: self.inner,
+        }
     }
 
     // TODO: Do we want the Value(v) to include the key and value?
+    // NOTE: Only looks at the newest entry - a Merge result means it needs resolving through the read path (GetContext), which is also
+    // where expiry is applied
     pub(crate) fn get(&self, key: &[u8]) -> MemReturn<'_> {
         if let Some((skip_key, v)) = self.inner.first_ge(key) {
             let sk = InternalKeyRef::from(skip_key);
@@ -168,28 +280,40 @@ impl Memtable<Mutable> {
 
             match sk.op.into() {
                 OperationType::Put => MemReturn::Value(v),
-                OperationType::Delete => MemReturn::Deleted,
-                OperationType::Merge => MemReturn::Merge,
+                OperationType::Delete | OperationType::SingleDelete => MemReturn::Deleted,
+                OperationType::Merge | OperationType::PutWithExpiry => MemReturn::Merge,
                 _ => unreachable!(),
             }
         } else {
             MemReturn::NotFound
         }
     }
+}
 
-    pub(crate) fn iter(&self) -> MemtableIterator<'_> {
-        self.inner.iter()
+// A full arena means the memtable is full, anything else is a bug in the node layout or the arena
+fn insert_error(e: SkipListError) -> Error {
+    match e {
+        SkipListError::Arena(ArenaError::ArenaFull) => Error::Busy("memtable is full".into()),
+        e => Error::Corruption(format!("memtable allocation failed: {e:?}")),
     }
 }
 
 pub(super) struct MemtableInner {
     id: MemID,
     highest_seqno: AtomicU64,
+    // Seq no of the last write before the memtable was created - every entry it holds is newer
+    earliest_sequence: AtomicU64,
     size: AtomicU64,
     requested_rotation: AtomicBool,
     lifecycle: AtomicU8,
     arena: Arena,
     skiplist: SkipList,
+    // Orders the range tombstones, the user comparator of the internal key comparator
+    user_comparator: Arc<dyn Comparator>,
+    range_del: SkipList,
+    num_range_deletes: AtomicU64,
+    // Fragmented range_del and the number of tombstones it was built from
+    fragmented_range_del: Mutex<Option<(u64, Arc<FragmentedTombstones>)>>,
 }
 
 impl Display for MemtableInner {
@@ -207,18 +331,25 @@ impl MemtableInner {
         id: MemID,
         arena_size: ArenaPolicy,
         allocator: Allocator,
-        comp: Arc<dyn Comparator>,
+        comp: Arc<InternalKeyComparator>,
     ) -> Self {
         let arena = Arena::new(arena_size, allocator);
+        let user_comparator = Arc::clone(comp.user_comparator());
         let skiplist = SkipList::new(comp.clone(), &arena);
+        let range_del = SkipList::new(comp, &arena);
         Self {
             id,
             highest_seqno: AtomicU64::new(0),
+            earliest_sequence: AtomicU64::new(0),
             size: AtomicU64::new(0),
             requested_rotation: AtomicBool::new(false),
             lifecycle: AtomicU8::new(MemLifeCycle::Active as u8),
             arena: arena,
             skiplist,
+            user_comparator,
+            range_del,
+            num_range_deletes: AtomicU64::new(0),
+            fragmented_range_del: Mutex::new(None),
         }
     }
 
@@ -233,19 +364,129 @@ impl MemtableInner {
         None
     }
 
-    fn insert(&self, key: &[u8], value: &[u8]) {
-        let _ = unsafe { self.skiplist.insert(key, value, &self.arena) };
+    // Walks every entry of the lookup key's user key (newest first, starting at the lookup seq no) into the context.
+    // Returns true once the context has everything it needs.
+    fn get_with_context(&self, key: &[u8], ctx: &mut GetContext<'_>) -> bool {
+        if let Some(tombstones) = self.range_tombstones() {
+            let lookup = InternalKeyRef::from(key);
+            ctx.raise_covering_seq(tombstones.max_covering_seq(
+                self.user_comparator.as_ref(),
+                lookup.user_key,
+                lookup.seq_no,
+            ));
+        }
+
+        let mut node = self.skiplist.search_node(key);
+
+        while !node.is_null() {
+            let ik = InternalKeyRef::from(Node::get_key_bytes(node));
+            if !ctx.key_matches(ik.user_key) {
+                break;
+            }
+
+            if !ctx.save_value(ik, Node::get_value_bytes(node)) {
+                return true;
+            }
+
+            node = self.skiplist.load_next(node);
+        }
+
+        false
+    }
+
+    fn newest_sequence(&self, user_key: &[u8]) -> Option<u64> {
+        // The newest entry of a user key is the first at or after the key with the largest trailer
+        let lookup = LookUpInternalKey::new(user_key, MAX_SEQUENCE_NUMBER, OperationType::Max);
+        let node = self.skiplist.search_node(lookup.as_ref());
+        let point = (!node.is_null())
+            .then(|| InternalKeyRef::from(Node::get_key_bytes(node)))
+            .filter(|ik| ik.user_key == user_key)
+            .map(|ik| ik.seq_no);
+
+        let covering = self.range_tombstones().map(|tombstones| {
+            tombstones.max_covering_seq(
+                self.user_comparator.as_ref(),
+                user_key,
+                MAX_SEQUENCE_NUMBER,
+            )
+        });
+
+        point
+            .into_iter()
+            .chain(covering.filter(|seq| *seq > 0))
+            .max()
+    }
+
+    fn insert(&self, key: &[u8], value: &[u8]) -> std::result::Result<(), SkipListError> {
+        unsafe { self.skiplist.insert(key, value, &self.arena) }?;
+        Ok(())
+    }
+
+    fn delete_range(
+        &self,
+        start: &[u8],
+        end: &[u8],
+        seq: u64,
+    ) -> std::result::Result<(), SkipListError> {
+        let key = [start, &encode_trailer(seq, OperationType::RangeDelete)].concat();
+        unsafe { self.range_del.insert(&key, end, &self.arena) }?;
+        self.num_range_deletes.fetch_add(1, Ordering::Release);
+        Ok(())
+    }
+
+    // Fragments the tombstones again only when some were added since the last call
+    fn range_tombstones(&self) -> Option<Arc<FragmentedTombstones>> {
+        let count = self.num_range_deletes.load(Ordering::Acquire);
+        if count == 0 {
+            return None;
+        }
+
+        let mut cached = self.fragmented_range_del.lock().unwrap();
+        if let Some((built_from, tombstones)) = cached.as_ref()
+            && *built_from == count
+        {
+            return Some(Arc::clone(tombstones));
+        }
+
+        // Tombstones added while we walk are picked up too - the cache is only ever a superset of what `count` says
+        let mut node = Node::load_next(self.range_del.head(), 0, Ordering::Acquire);
+        let mut tombstones = Vec::with_capacity(count as usize);
+        while !node.is_null() {
+            let start = InternalKeyRef::from(Node::get_key_bytes(node));
+            tombstones.push(RangeTombstone::new(
+                start.user_key,
+                Node::get_value_bytes(node),
+                start.seq_no,
+            ));
+            node = self.range_del.load_next(node);
+        }
+
+        let fragmented = Arc::new(FragmentedTombstones::new(
+            tombstones,
+            self.user_comparator.as_ref(),
+        ));
+        *cached = Some((count, Arc::clone(&fragmented)));
+        Some(fragmented)
     }
 
     // NOTE: If we insert direct we have to make sure that the internal key seq no is greater than the highest seq no so we don't fail on insert and alloc
     // A dead node
     // TODO: We could create a fallback where if we need to we can use the TLS Ephemeral buffer to allocate the internal key and insert
-    fn insert_direct(&self, user_key: &[u8], seq_no: u64, op_type: OperationType, value: &[u8]) {
+    fn insert_direct(
+        &self,
+        user_key: &[u8],
+        seq_no: u64,
+        op_type: OperationType,
+        value: &[u8],
+    ) -> std::result::Result<(), SkipListError> {
         let user_key_len = user_key.len();
 
         unsafe {
-            self.skiplist
-                .insert_with((user_key_len + 8) as u16, value, &self.arena, |node_ptr| {
+            self.skiplist.insert_with(
+                (user_key_len + 8) as u16,
+                value,
+                &self.arena,
+                |node_ptr| {
                     // Insert the user key
                     ptr::copy_nonoverlapping(
                         user_key.as_ptr(),
@@ -258,8 +499,10 @@ impl MemtableInner {
                         Node::key_ptr(node_ptr).add(user_key_len),
                         8,
                     );
-                });
+                },
+            )?;
         }
+        Ok(())
     }
 
     fn iter(&self) -> MemtableIterator<'_> {
@@ -267,6 +510,7 @@ impl MemtableInner {
             sl: &self.skiplist,
             item: Node::load_next(self.skiplist.head(), 0, Ordering::Relaxed),
             current: None,
+            range_tombstones: self.range_tombstones(),
         }
     }
 
@@ -275,6 +519,7 @@ impl MemtableInner {
             sl: &self.skiplist,
             item: self.skiplist.search_node(key),
             current: None,
+            range_tombstones: self.range_tombstones(),
         }
     }
 }
@@ -283,6 +528,8 @@ pub(crate) struct MemtableIterator<'a> {
     sl: &'a SkipList,
     item: *mut Node,
     current: Option<NonNull<Node>>,
+    // Tombstones of the memtable when the iterator was created
+    range_tombstones: Option<Arc<FragmentedTombstones>>,
 }
 
 impl<'a> MemtableIterator<'a> {
@@ -302,11 +549,25 @@ impl<'a> InternalIterator for MemtableIterator<'a> {
         self.current = NonNull::new(self.item)
     }
 
+    fn seek_to_last(&mut self) {
+        self.item = self.sl.find_last();
+        self.current = NonNull::new(self.item)
+    }
+
     fn seek(&mut self, key: &[u8]) {
         self.item = self.sl.search_node(key);
         self.current = NonNull::new(self.item)
     }
 
+    fn seek_for_prev(&mut self, key: &[u8]) {
+        self.seek(key);
+        match self.current {
+            Some(curr) if Node::get_key_bytes(curr.as_ptr()) == key => {}
+            Some(_) => self.prev(),
+            None => self.seek_to_last(),
+        }
+    }
+
     fn next(&mut self) {
         if let Some(curr) = self.current {
             self.current = NonNull::new(Node::load_next(curr.as_ptr(), 0, Ordering::Relaxed))
@@ -315,6 +576,14 @@ impl<'a> InternalIterator for MemtableIterator<'a> {
         }
     }
 
+    // No back pointers - search for the predecessor of the current key
+    fn prev(&mut self) {
+        if let Some(curr) = self.current {
+            self.item = self.sl.find_less_than(Node::get_key_bytes(curr.as_ptr()));
+            self.current = NonNull::new(self.item)
+        }
+    }
+
     fn key(&self) -> &[u8] {
         debug_assert!(self.valid());
         //TODO: We should maybe do a valid check before calling key() does this need to be unsafe?
@@ -326,6 +595,11 @@ impl<'a> InternalIterator for MemtableIterator<'a> {
         debug_assert!(self.valid());
         Node::get_value_bytes(self.current.unwrap().as_ptr())
     }
+
+    fn range_tombstones(&self, out: &mut Vec<Arc<FragmentedTombstones>>) -> Result<()> {
+        out.extend(self.range_tombstones.iter().cloned());
+        Ok(())
+    }
 }
 
 #[cfg(test)]
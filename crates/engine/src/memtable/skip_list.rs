@@ -230,6 +230,28 @@ impl Node {
         }
     }
 
+    // Like alloc, but a full arena is returned as an error instead of panicking
+    unsafe fn try_alloc(
+        arena: &Arena,
+        height: u16,
+        key_len: u16,
+        value_len: u32,
+    ) -> Result<*mut Node, SkipListError> {
+        debug_assert!(height as usize <= MAX_HEAD_HEIGHT);
+        let layout = Self::build_layout(height as usize, key_len as usize, value_len as usize)?;
+        unsafe {
+            let ptr = arena.alloc_raw_fallback(layout)?;
+            Self::init_node(ptr, height, key_len, value_len);
+            Ok(ptr.as_ptr() as *mut Node)
+        }
+    }
+
+    /// Largest number of arena bytes a node of the key and value can take (a node of the maximum height).
+    pub(super) fn max_size(key_len: usize, value_len: usize) -> usize {
+        Self::build_layout(MAX_HEAD_HEIGHT, key_len, value_len)
+            .map_or(usize::MAX, |layout| layout.size())
+    }
+
     pub(super) fn get_key_bytes<'a>(node: *mut Node) -> &'a [u8] {
         unsafe { slice::from_raw_parts(Node::key_ptr(node), (*node).key_len as usize) }
     }
@@ -357,6 +379,11 @@ pub(super) struct SkipList {
 ///                                         ↓
 /// Level 1 :             A        B -----> C   (found)
 
+// SAFETY: Nodes are published with CAS on atomic links and are never moved or freed before the arena (owned alongside the list by the
+// memtable) is dropped, so the list can be searched and inserted into from any thread
+unsafe impl Send for SkipList {}
+unsafe impl Sync for SkipList {}
+
 impl SkipList {
     pub(super) fn new(comparator: Arc<dyn Comparator>, arena: &Arena) -> Self {
         let data = CachePadded {
@@ -501,6 +528,40 @@ impl SkipList {
         self.search(key).successors[0] as *mut Node
     }
 
+    /// Returns the last node with a key < `key`, or null if there is none. There are no back pointers so reverse iteration is a search
+    /// for the predecessor from the head - O(log n) per step.
+    pub(super) fn find_less_than(&self, key: &[u8]) -> *mut Node {
+        let pred = self.search(key).predecessors[0];
+        if pred == self.head() {
+            ptr::null_mut()
+        } else {
+            pred
+        }
+    }
+
+    /// Returns the last node in the list, or null if the list is empty.
+    pub(super) fn find_last(&self) -> *mut Node {
+        let mut node = self.head();
+        let mut level = self.data.max_level.load(Ordering::Acquire);
+
+        while level >= 1 {
+            level -= 1;
+            loop {
+                let next = Node::load_next(node, level, Ordering::Acquire);
+                if next.is_null() {
+                    break;
+                }
+                node = next;
+            }
+        }
+
+        if node == self.head() {
+            ptr::null_mut()
+        } else {
+            node
+        }
+    }
+
     pub(super) fn load_next(&self, current: *mut Node) -> *mut Node {
         Node::load_next(current, 0, Ordering::Relaxed)
     }
@@ -512,22 +573,27 @@ impl SkipList {
     /// Inserts a key-value pair into the skip list.
     /// This function is unsafe because it returns a raw pointer to the inserted node and it is the caller's responsibility to ensure that the pointer
     /// is used correctly and not leaked.
-    pub(super) unsafe fn insert(&self, key: &[u8], value: &[u8], arena: &Arena) -> *mut Node {
+    /// Fails without changing the skip list when the arena has no room left for the node.
+    pub(super) unsafe fn insert(
+        &self,
+        key: &[u8],
+        value: &[u8],
+        arena: &Arena,
+    ) -> Result<*mut Node, SkipListError> {
         let mut traversal_ctx = self.search(key);
 
         if let Some(node) = traversal_ctx.searched_node {
-            return node.as_ptr();
+            return Ok(node.as_ptr());
         }
 
         // Build the new node to insert into the searched position
-        self.data.entries.fetch_add(1, Ordering::Relaxed);
-
         let height = self.generate_random_level();
         debug_assert!(height <= MAX_HEAD_HEIGHT);
         debug_assert!(height <= u16::MAX as usize);
 
         let node_ptr =
-            unsafe { Node::alloc(arena, height as u16, key.len() as u16, value.len() as u32) };
+            unsafe { Node::try_alloc(arena, height as u16, key.len() as u16, value.len() as u32)? };
+        self.data.entries.fetch_add(1, Ordering::Relaxed);
 
         unsafe {
             // Write the key and value into the node
@@ -565,7 +631,7 @@ impl SkipList {
                 traversal_ctx = self.search(key);
 
                 if let Some(node) = traversal_ctx.searched_node {
-                    return node.as_ptr();
+                    return Ok(node.as_ptr());
                 }
             }
         }
@@ -602,7 +668,7 @@ impl SkipList {
                 traversal_ctx = self.search(key);
             }
         }
-        node_ptr
+        Ok(node_ptr)
     }
 
     /// insert_with pre-emptively allocates a node using it's layout into the arena and calls a closure with the node pointer to write directly
@@ -613,20 +679,20 @@ impl SkipList {
         value: &[u8],
         arena: &Arena,
         f: F,
-    ) -> *mut Node
+    ) -> Result<*mut Node, SkipListError>
     where
         F: FnOnce(*mut Node),
     {
         debug_assert!(key_len <= u16::MAX);
 
         // Build the new node to insert into the searched position
-        self.data.entries.fetch_add(1, Ordering::Relaxed);
-
         let height = self.generate_random_level();
         debug_assert!(height <= MAX_HEAD_HEIGHT);
         debug_assert!(height <= u16::MAX as usize);
 
-        let node_ptr = unsafe { Node::alloc(arena, height as u16, key_len, value.len() as u32) };
+        let node_ptr =
+            unsafe { Node::try_alloc(arena, height as u16, key_len, value.len() as u32)? };
+        self.data.entries.fetch_add(1, Ordering::Relaxed);
 
         unsafe {
             // Write the key and value into the node
@@ -641,7 +707,7 @@ impl SkipList {
         let mut traversal_ctx = self.search(key);
 
         if let Some(node) = traversal_ctx.searched_node {
-            return node.as_ptr();
+            return Ok(node.as_ptr());
         }
         //
         // Enter into the CAS loop to insert the node at the base level
@@ -673,7 +739,7 @@ impl SkipList {
                 traversal_ctx = self.search(key);
 
                 if let Some(node) = traversal_ctx.searched_node {
-                    return node.as_ptr();
+                    return Ok(node.as_ptr());
                 }
             }
         }
@@ -710,7 +776,7 @@ impl SkipList {
                 traversal_ctx = self.search(key);
             }
         }
-        node_ptr
+        Ok(node_ptr)
     }
 
     pub(super) fn iter(&self) -> Iter<'_> {
@@ -958,9 +1024,9 @@ mod tests {
         // Mango
         // Pear
 
-        unsafe { skip.insert(b"Apple", b"Green", &arena) };
-        unsafe { skip.insert(b"Mango", b"Yellow", &arena) };
-        unsafe { skip.insert(b"Pear", b"Brown", &arena) };
+        unsafe { skip.insert(b"Apple", b"Green", &arena).unwrap() };
+        unsafe { skip.insert(b"Mango", b"Yellow", &arena).unwrap() };
+        unsafe { skip.insert(b"Pear", b"Brown", &arena).unwrap() };
 
         let ctx = skip.search(b"Apple");
 
@@ -990,9 +1056,9 @@ mod tests {
         let skip = SkipList::new(Arc::new(DefaultComparator {}), &arena);
 
         //
-        unsafe { skip.insert(b"Apple", b"Green", &arena) };
-        unsafe { skip.insert(b"Mango", b"Yellow", &arena) };
-        unsafe { skip.insert(b"Pear", b"Brown", &arena) };
+        unsafe { skip.insert(b"Apple", b"Green", &arena).unwrap() };
+        unsafe { skip.insert(b"Mango", b"Yellow", &arena).unwrap() };
+        unsafe { skip.insert(b"Pear", b"Brown", &arena).unwrap() };
 
         // Search for Apple should give us Apple
         let result = unsafe { skip.search(b"Apple") };
@@ -1125,9 +1191,9 @@ mod tests {
         let skip = SkipList::new(Arc::new(DefaultComparator {}), &arena);
 
         //
-        unsafe { skip.insert(b"Apple", b"Green", &arena) };
-        unsafe { skip.insert(b"Mango", b"Yellow", &arena) };
-        unsafe { skip.insert(b"Pear", b"Brown", &arena) };
+        unsafe { skip.insert(b"Apple", b"Green", &arena).unwrap() };
+        unsafe { skip.insert(b"Mango", b"Yellow", &arena).unwrap() };
+        unsafe { skip.insert(b"Pear", b"Brown", &arena).unwrap() };
 
         let mut keys: Vec<&[u8]> = Vec::with_capacity(3);
 
@@ -1166,9 +1232,9 @@ mod tests {
         let skip = SkipList::new(Arc::new(DefaultComparator {}), &arena);
 
         //
-        unsafe { skip.insert(b"Apple", b"Green", &arena) };
-        unsafe { skip.insert(b"Mango", b"Yellow", &arena) };
-        unsafe { skip.insert(b"Strawberry", b"Brown", &arena) };
+        unsafe { skip.insert(b"Apple", b"Green", &arena).unwrap() };
+        unsafe { skip.insert(b"Mango", b"Yellow", &arena).unwrap() };
+        unsafe { skip.insert(b"Strawberry", b"Brown", &arena).unwrap() };
 
         let mut result = Vec::with_capacity(2);
 
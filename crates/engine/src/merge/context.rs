@@ -0,0 +1,59 @@
+//
+//
+//
+// MergeContext collects the merge operands found for a single key while a read walks from the newest source to the oldest.
+// Operands are pushed newest first (the order they are found in) and handed to the MergeOperator oldest first.
+
+pub(crate) struct MergeContext {
+    // Newest -> oldest
+    operands: Vec<Vec<u8>>,
+}
+
+impl MergeContext {
+    pub(crate) fn new() -> Self {
+        Self {
+            operands: Vec::new(),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn push_operand(&mut self, operand: &[u8]) {
+        self.operands.push(operand.to_vec());
+    }
+
+    /// Adds an operand newer than every operand collected so far (reverse scans find operands oldest first).
+    #[inline]
+    pub(crate) fn push_newer_operand(&mut self, operand: &[u8]) {
+        self.operands.insert(0, operand.to_vec());
+    }
+
+    #[inline]
+    pub(crate) fn num_operands(&self) -> usize {
+        self.operands.len()
+    }
+
+    #[inline]
+    pub(crate) fn is_empty(&self) -> bool {
+        self.operands.is_empty()
+    }
+
+    /// Operands ordered oldest -> newest, the order MergeOperator expects.
+    pub(crate) fn operands_oldest_first(&self) -> Vec<&[u8]> {
+        self.operands.iter().rev().map(|o| o.as_slice()).collect()
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.operands.clear();
+    }
+
+    pub(crate) fn into_operands_oldest_first(mut self) -> Vec<Vec<u8>> {
+        self.operands.reverse();
+        self.operands
+    }
+}
+
+impl Default for MergeContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,39 @@
+//
+//
+//
+// Helpers shared by the read path and the compaction path for running a MergeOperator and turning its failures into errors.
+
+use crate::error::{Error, Result};
+use crate::merge::operator::MergeOperator;
+
+/// Applies `operands` (oldest -> newest) on top of `base`.
+pub(crate) fn full_merge(
+    merge_operator: Option<&dyn MergeOperator>,
+    key: &[u8],
+    base: Option<&[u8]>,
+    operands: &[&[u8]],
+) -> Result<Vec<u8>> {
+    let op = merge_operator.ok_or_else(|| {
+        Error::NotSupported("merge operand found but no merge operator is configured".into())
+    })?;
+
+    op.full_merge(key, base, operands).ok_or_else(|| {
+        Error::Corruption(format!(
+            "merge operator {} failed a full merge of {} operands",
+            op.name(),
+            operands.len()
+        ))
+    })
+}
+
+/// Tries to collapse `operands` (oldest -> newest) into a single operand. Returns None if there is nothing to gain or the operator can't.
+pub(crate) fn partial_merge(
+    merge_operator: &dyn MergeOperator,
+    key: &[u8],
+    operands: &[&[u8]],
+) -> Option<Vec<u8>> {
+    if operands.len() < 2 {
+        return None;
+    }
+    merge_operator.partial_merge_multi(key, operands)
+}
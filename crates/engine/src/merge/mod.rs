@@ -0,0 +1,4 @@
+pub(crate) mod context;
+pub(crate) mod helper;
+pub(crate) mod operator;
+pub(crate) mod operators;
@@ -0,0 +1,50 @@
+//
+//
+//
+// MergeOperator
+//
+// A Merge is a write which records an operand against a key instead of a full value. The value of the key is only materialised when it is
+// read (or when a flush/compaction can see enough of the history) by applying the operands, oldest first, on top of the newest base value.
+//
+// k@5 Merge(+1)
+// k@4 Merge(+2)       full_merge(k, Some(10), [+2, +1]) = 13
+// k@3 Put(10)
+//
+// full_merge      - Applies every operand on top of an existing value (None if there is no base or the base is a Delete).
+//                   Must always be able to produce a value unless the operands are corrupt.
+//
+// partial_merge   - Combines two adjacent operands into a single operand without knowing the base. This is an optimisation which lets
+//                   flush and compaction shrink long operand chains. Return None if the operands cannot be combined - they will be kept
+//                   as they are. Partial merges must be associative: partial(partial(a, b), c) == partial(a, partial(b, c)).
+//
+// Operands passed to both methods are ordered oldest -> newest.
+
+pub trait MergeOperator: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Returns None if the merge fails. This is surfaced to the reader as a Corruption error.
+    fn full_merge(
+        &self,
+        key: &[u8],
+        existing_value: Option<&[u8]>,
+        operands: &[&[u8]],
+    ) -> Option<Vec<u8>>;
+
+    /// Combines `left` (older) and `right` (newer) into a single operand.
+    fn partial_merge(&self, _key: &[u8], _left: &[u8], _right: &[u8]) -> Option<Vec<u8>> {
+        None
+    }
+
+    /// Combines a run of operands (oldest -> newest) into a single operand. The default folds `partial_merge` from the left and gives up as
+    /// soon as one pair cannot be combined.
+    fn partial_merge_multi(&self, key: &[u8], operands: &[&[u8]]) -> Option<Vec<u8>> {
+        let (first, rest) = operands.split_first()?;
+        let mut acc = first.to_vec();
+
+        for operand in rest {
+            acc = self.partial_merge(key, &acc, operand)?;
+        }
+
+        Some(acc)
+    }
+}
@@ -0,0 +1,132 @@
+//
+//
+//
+// Counter merge operators
+//
+// Values and operands are fixed 8 byte little-endian integers. A missing base counts as 0 and addition wraps on overflow.
+// Operands of the wrong width are treated as corruption.
+
+use crate::merge::operator::MergeOperator;
+
+#[inline]
+fn decode_u64(bytes: &[u8]) -> Option<u64> {
+    Some(u64::from_le_bytes(bytes.try_into().ok()?))
+}
+
+#[inline]
+fn decode_i64(bytes: &[u8]) -> Option<i64> {
+    Some(i64::from_le_bytes(bytes.try_into().ok()?))
+}
+
+pub(crate) struct U64AddOperator;
+
+impl MergeOperator for U64AddOperator {
+    fn name(&self) -> &str {
+        "victory.U64Add"
+    }
+
+    fn full_merge(
+        &self,
+        _key: &[u8],
+        existing_value: Option<&[u8]>,
+        operands: &[&[u8]],
+    ) -> Option<Vec<u8>> {
+        let mut acc = match existing_value {
+            Some(v) => decode_u64(v)?,
+            None => 0,
+        };
+        for op in operands {
+            acc = acc.wrapping_add(decode_u64(op)?);
+        }
+        Some(acc.to_le_bytes().to_vec())
+    }
+
+    fn partial_merge(&self, _key: &[u8], left: &[u8], right: &[u8]) -> Option<Vec<u8>> {
+        let sum = decode_u64(left)?.wrapping_add(decode_u64(right)?);
+        Some(sum.to_le_bytes().to_vec())
+    }
+}
+
+pub(crate) struct I64AddOperator;
+
+impl MergeOperator for I64AddOperator {
+    fn name(&self) -> &str {
+        "victory.I64Add"
+    }
+
+    fn full_merge(
+        &self,
+        _key: &[u8],
+        existing_value: Option<&[u8]>,
+        operands: &[&[u8]],
+    ) -> Option<Vec<u8>> {
+        let mut acc = match existing_value {
+            Some(v) => decode_i64(v)?,
+            None => 0,
+        };
+        for op in operands {
+            acc = acc.wrapping_add(decode_i64(op)?);
+        }
+        Some(acc.to_le_bytes().to_vec())
+    }
+
+    fn partial_merge(&self, _key: &[u8], left: &[u8], right: &[u8]) -> Option<Vec<u8>> {
+        let sum = decode_i64(left)?.wrapping_add(decode_i64(right)?);
+        Some(sum.to_le_bytes().to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merge::operators::prop::check_associative;
+
+    #[test]
+    fn u64_add() {
+        let op = U64AddOperator;
+        let one = 1u64.to_le_bytes();
+        let two = 2u64.to_le_bytes();
+
+        let v = op
+            .full_merge(b"k", Some(&10u64.to_le_bytes()), &[&one, &two])
+            .unwrap();
+        assert_eq!(v, 13u64.to_le_bytes());
+
+        let v = op.full_merge(b"k", None, &[&one]).unwrap();
+        assert_eq!(v, 1u64.to_le_bytes());
+
+        // Wraps instead of failing
+        let v = op
+            .full_merge(b"k", Some(&u64::MAX.to_le_bytes()), &[&one])
+            .unwrap();
+        assert_eq!(v, 0u64.to_le_bytes());
+
+        // Wrong width is corruption
+        assert!(op.full_merge(b"k", None, &[b"abc"]).is_none());
+        assert!(op.partial_merge(b"k", &one, b"abc").is_none());
+    }
+
+    #[test]
+    fn i64_add() {
+        let op = I64AddOperator;
+
+        let v = op
+            .full_merge(
+                b"k",
+                Some(&5i64.to_le_bytes()),
+                &[&(-7i64).to_le_bytes(), &1i64.to_le_bytes()],
+            )
+            .unwrap();
+        assert_eq!(v, (-1i64).to_le_bytes());
+    }
+
+    #[test]
+    fn counters_are_associative() {
+        check_associative(&U64AddOperator, 500, |rng| {
+            rng.next_u64().to_le_bytes().to_vec()
+        });
+        check_associative(&I64AddOperator, 500, |rng| {
+            (rng.next_u64() as i64).to_le_bytes().to_vec()
+        });
+    }
+}
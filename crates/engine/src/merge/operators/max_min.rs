@@ -0,0 +1,185 @@
+//
+//
+//
+// Max / Min merge operators
+//
+// Keep the largest (or smallest) of the base value and every operand as ordered by a Comparator. Defaults to bytewise ordering.
+
+use std::cmp::Ordering;
+use std::sync::Arc;
+
+use crate::key::comparator::{Comparator, DefaultComparator};
+use crate::merge::operator::MergeOperator;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Keep {
+    Max,
+    Min,
+}
+
+// Picks the winner of two values. Ties keep the newer value.
+#[inline]
+fn pick<'a>(comp: &dyn Comparator, keep: Keep, older: &'a [u8], newer: &'a [u8]) -> &'a [u8] {
+    match (keep, comp.compare(older, newer)) {
+        (Keep::Max, Ordering::Greater) | (Keep::Min, Ordering::Less) => older,
+        _ => newer,
+    }
+}
+
+fn fold<'a>(
+    comp: &dyn Comparator,
+    keep: Keep,
+    existing_value: Option<&'a [u8]>,
+    operands: &[&'a [u8]],
+) -> Option<&'a [u8]> {
+    operands.iter().fold(existing_value, |acc, op| match acc {
+        Some(acc) => Some(pick(comp, keep, acc, op)),
+        None => Some(op),
+    })
+}
+
+pub(crate) struct MaxOperator {
+    comparator: Arc<dyn Comparator>,
+}
+
+impl MaxOperator {
+    pub(crate) fn new(comparator: Arc<dyn Comparator>) -> Self {
+        Self { comparator }
+    }
+}
+
+impl Default for MaxOperator {
+    fn default() -> Self {
+        Self::new(DefaultComparator::new())
+    }
+}
+
+impl MergeOperator for MaxOperator {
+    fn name(&self) -> &str {
+        "victory.Max"
+    }
+
+    fn full_merge(
+        &self,
+        _key: &[u8],
+        existing_value: Option<&[u8]>,
+        operands: &[&[u8]],
+    ) -> Option<Vec<u8>> {
+        let winner = fold(
+            self.comparator.as_ref(),
+            Keep::Max,
+            existing_value,
+            operands,
+        );
+        Some(winner.map(|v| v.to_vec()).unwrap_or_default())
+    }
+
+    fn partial_merge(&self, _key: &[u8], left: &[u8], right: &[u8]) -> Option<Vec<u8>> {
+        Some(pick(self.comparator.as_ref(), Keep::Max, left, right).to_vec())
+    }
+}
+
+pub(crate) struct MinOperator {
+    comparator: Arc<dyn Comparator>,
+}
+
+impl MinOperator {
+    pub(crate) fn new(comparator: Arc<dyn Comparator>) -> Self {
+        Self { comparator }
+    }
+}
+
+impl Default for MinOperator {
+    fn default() -> Self {
+        Self::new(DefaultComparator::new())
+    }
+}
+
+impl MergeOperator for MinOperator {
+    fn name(&self) -> &str {
+        "victory.Min"
+    }
+
+    fn full_merge(
+        &self,
+        _key: &[u8],
+        existing_value: Option<&[u8]>,
+        operands: &[&[u8]],
+    ) -> Option<Vec<u8>> {
+        let winner = fold(
+            self.comparator.as_ref(),
+            Keep::Min,
+            existing_value,
+            operands,
+        );
+        Some(winner.map(|v| v.to_vec()).unwrap_or_default())
+    }
+
+    fn partial_merge(&self, _key: &[u8], left: &[u8], right: &[u8]) -> Option<Vec<u8>> {
+        Some(pick(self.comparator.as_ref(), Keep::Min, left, right).to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merge::operators::prop::check_associative;
+
+    // Orders 8 byte values by their little-endian u64 value instead of bytewise
+    struct U64LeComparator;
+
+    impl Comparator for U64LeComparator {
+        fn compare(&self, a: &[u8], b: &[u8]) -> Ordering {
+            u64::from_le_bytes(a.try_into().unwrap())
+                .cmp(&u64::from_le_bytes(b.try_into().unwrap()))
+        }
+
+        fn name(&self) -> &str {
+            "U64LeComparator"
+        }
+    }
+
+    #[test]
+    fn max_and_min() {
+        let max = MaxOperator::default();
+        let min = MinOperator::default();
+
+        let v = max
+            .full_merge(b"k", Some(b"m"), &[b"a", b"z", b"q"])
+            .unwrap();
+        assert_eq!(v, b"z");
+
+        let v = min
+            .full_merge(b"k", Some(b"m"), &[b"a", b"z", b"q"])
+            .unwrap();
+        assert_eq!(v, b"a");
+
+        let v = max.full_merge(b"k", None, &[b"b"]).unwrap();
+        assert_eq!(v, b"b");
+    }
+
+    #[test]
+    fn uses_the_comparator() {
+        let max = MaxOperator::new(Arc::new(U64LeComparator));
+
+        // Bytewise 256 (00 01 ..) sorts before 1 (01 00 ..), by value it is larger
+        let v = max
+            .full_merge(b"k", None, &[&256u64.to_le_bytes(), &1u64.to_le_bytes()])
+            .unwrap();
+        assert_eq!(v, 256u64.to_le_bytes());
+    }
+
+    #[test]
+    fn max_min_are_associative() {
+        let gen_operand = |rng: &mut crate::merge::operators::prop::Rng| {
+            let len = 1 + rng.below(4) as usize;
+            (0..len).map(|_| rng.below(4) as u8).collect::<Vec<u8>>()
+        };
+
+        check_associative(&MaxOperator::default(), 500, gen_operand);
+        check_associative(&MinOperator::default(), 500, gen_operand);
+        check_associative(&MaxOperator::new(Arc::new(U64LeComparator)), 500, |rng| {
+            rng.next_u64().to_le_bytes().to_vec()
+        });
+    }
+}
@@ -0,0 +1,86 @@
+// Built-in merge operators
+//
+// Operators which most applications end up writing themselves. Each one supports partial merges so flush and compaction can collapse
+// operand chains, and each is tested for associativity of its partial merges.
+//
+// counter         - little-endian u64/i64 add
+// string_append   - append with a configurable delimiter
+// max_min         - keep the max or min value by a comparator
+// set_union       - union of sorted sets of varint-length-prefixed elements
+
+pub(crate) mod counter;
+pub(crate) mod max_min;
+pub(crate) mod set_union;
+pub(crate) mod string_append;
+
+#[cfg(test)]
+pub(super) mod prop {
+    use crate::merge::operator::MergeOperator;
+
+    // Xorshift generator so property tests are deterministic without pulling in a dependency
+    pub(crate) struct Rng(u64);
+
+    impl Rng {
+        pub(crate) fn new(seed: u64) -> Self {
+            Self(seed.max(1))
+        }
+
+        pub(crate) fn next_u64(&mut self) -> u64 {
+            let mut x = self.0;
+            x ^= x << 13;
+            x ^= x >> 7;
+            x ^= x << 17;
+            self.0 = x;
+            x
+        }
+
+        pub(crate) fn below(&mut self, n: u64) -> u64 {
+            self.next_u64() % n
+        }
+    }
+
+    /// Checks over `rounds` random inputs that:
+    /// - partial(partial(a, b), c) == partial(a, partial(b, c))
+    /// - full_merge(base, ops) == full_merge(base, [partial_multi(ops)])
+    pub(crate) fn check_associative<F>(op: &dyn MergeOperator, rounds: usize, mut gen_operand: F)
+    where
+        F: FnMut(&mut Rng) -> Vec<u8>,
+    {
+        let mut rng = Rng::new(0x5EED);
+        let key = b"key";
+
+        for _ in 0..rounds {
+            let a = gen_operand(&mut rng);
+            let b = gen_operand(&mut rng);
+            let c = gen_operand(&mut rng);
+
+            let ab = op.partial_merge(key, &a, &b).unwrap();
+            let ab_c = op.partial_merge(key, &ab, &c).unwrap();
+
+            let bc = op.partial_merge(key, &b, &c).unwrap();
+            let a_bc = op.partial_merge(key, &a, &bc).unwrap();
+
+            assert_eq!(ab_c, a_bc, "{} partial merge is not associative", op.name());
+
+            let base = if rng.below(2) == 0 {
+                Some(gen_operand(&mut rng))
+            } else {
+                None
+            };
+            let ops = [a.as_slice(), b.as_slice(), c.as_slice()];
+
+            let full = op.full_merge(key, base.as_deref(), &ops).unwrap();
+            let collapsed = op.partial_merge_multi(key, &ops).unwrap();
+            let via_partial = op
+                .full_merge(key, base.as_deref(), &[collapsed.as_slice()])
+                .unwrap();
+
+            assert_eq!(
+                full,
+                via_partial,
+                "{} partial merge changes the full merge result",
+                op.name()
+            );
+        }
+    }
+}
@@ -0,0 +1,216 @@
+//
+//
+//
+// SetUnionOperator
+//
+// Values and operands are sorted sets of elements, each element length prefixed:
+//
+// | elem_len (VarInt) | elem ... | elem_len (VarInt) | elem ... | ...
+//
+// Elements are kept sorted and unique by the comparator, so merging is a k-way union of the base and operand sets. An operand which
+// isn't a valid set (truncated, unsorted or duplicate elements) is treated as corruption.
+
+use std::cmp::Ordering;
+use std::sync::Arc;
+
+use crate::key::comparator::{Comparator, DefaultComparator};
+use crate::merge::operator::MergeOperator;
+use crate::utils::var_int::VarInt;
+
+/// Encodes elements as a set value. Elements are sorted and de-duplicated by the comparator first.
+pub(crate) fn encode_set(comparator: &dyn Comparator, elements: &[&[u8]]) -> Vec<u8> {
+    let mut sorted = elements.to_vec();
+    sorted.sort_by(|a, b| comparator.compare(a, b));
+    sorted.dedup_by(|a, b| comparator.compare(a, b) == Ordering::Equal);
+
+    let mut out = Vec::new();
+    for elem in sorted {
+        push_element(&mut out, elem);
+    }
+    out
+}
+
+/// Decodes a set value. Returns None if the value isn't a valid set.
+pub(crate) fn decode_set<'a>(
+    comparator: &dyn Comparator,
+    mut src: &'a [u8],
+) -> Option<Vec<&'a [u8]>> {
+    let mut elements: Vec<&[u8]> = Vec::new();
+
+    while !src.is_empty() {
+        let (len, read) = VarInt::try_decode(src)?;
+        let len = len as usize;
+        src = &src[read..];
+
+        if src.len() < len {
+            return None;
+        }
+        let (elem, rest) = src.split_at(len);
+        src = rest;
+
+        if let Some(prev) = elements.last()
+            && comparator.compare(prev, elem) != Ordering::Less
+        {
+            return None;
+        }
+        elements.push(elem);
+    }
+
+    Some(elements)
+}
+
+#[inline]
+fn push_element(out: &mut Vec<u8>, elem: &[u8]) {
+    out.extend_from_slice(VarInt::new(elem.len() as u32).as_slice());
+    out.extend_from_slice(elem);
+}
+
+pub(crate) struct SetUnionOperator {
+    comparator: Arc<dyn Comparator>,
+}
+
+impl SetUnionOperator {
+    pub(crate) fn new(comparator: Arc<dyn Comparator>) -> Self {
+        Self { comparator }
+    }
+
+    fn union(&self, sets: &[&[u8]]) -> Option<Vec<u8>> {
+        let comp = self.comparator.as_ref();
+
+        let decoded = sets
+            .iter()
+            .map(|s| decode_set(comp, s))
+            .collect::<Option<Vec<_>>>()?;
+
+        // Sets are small and few per merge - a linear scan for the smallest head is enough
+        let mut heads = vec![0usize; decoded.len()];
+        let mut out = Vec::with_capacity(sets.iter().map(|s| s.len()).sum());
+
+        loop {
+            let mut min: Option<&[u8]> = None;
+            for (set, &head) in decoded.iter().zip(heads.iter()) {
+                if let Some(&elem) = set.get(head)
+                    && min.is_none_or(|m| comp.compare(elem, m) == Ordering::Less)
+                {
+                    min = Some(elem);
+                }
+            }
+
+            let Some(min) = min else {
+                break;
+            };
+            push_element(&mut out, min);
+
+            for (set, head) in decoded.iter().zip(heads.iter_mut()) {
+                if set
+                    .get(*head)
+                    .is_some_and(|&elem| comp.compare(elem, min) == Ordering::Equal)
+                {
+                    *head += 1;
+                }
+            }
+        }
+
+        Some(out)
+    }
+}
+
+impl Default for SetUnionOperator {
+    fn default() -> Self {
+        Self::new(DefaultComparator::new())
+    }
+}
+
+impl MergeOperator for SetUnionOperator {
+    fn name(&self) -> &str {
+        "victory.SetUnion"
+    }
+
+    fn full_merge(
+        &self,
+        _key: &[u8],
+        existing_value: Option<&[u8]>,
+        operands: &[&[u8]],
+    ) -> Option<Vec<u8>> {
+        let mut sets = Vec::with_capacity(operands.len() + 1);
+        sets.extend(existing_value);
+        sets.extend_from_slice(operands);
+        self.union(&sets)
+    }
+
+    fn partial_merge(&self, _key: &[u8], left: &[u8], right: &[u8]) -> Option<Vec<u8>> {
+        self.union(&[left, right])
+    }
+
+    fn partial_merge_multi(&self, _key: &[u8], operands: &[&[u8]]) -> Option<Vec<u8>> {
+        self.union(operands)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merge::operators::prop::check_associative;
+
+    fn set(elements: &[&[u8]]) -> Vec<u8> {
+        encode_set(&*DefaultComparator::new(), elements)
+    }
+
+    #[test]
+    fn union_of_sets() {
+        let op = SetUnionOperator::default();
+
+        let base = set(&[b"b", b"d"]);
+        let v = op
+            .full_merge(
+                b"k",
+                Some(&base),
+                &[&set(&[b"a", b"d"]), &set(&[b"c", b"e", b"b"])],
+            )
+            .unwrap();
+        assert_eq!(v, set(&[b"a", b"b", b"c", b"d", b"e"]));
+
+        let v = op.full_merge(b"k", None, &[&set(&[])]).unwrap();
+        assert!(v.is_empty());
+
+        let comp = DefaultComparator::new();
+        assert_eq!(decode_set(&*comp, &v).unwrap(), Vec::<&[u8]>::new());
+        assert_eq!(
+            decode_set(&*comp, &set(&[b"y", b"x", b"x"])).unwrap(),
+            vec![b"x".as_slice(), b"y"]
+        );
+    }
+
+    #[test]
+    fn invalid_sets_are_corruption() {
+        let op = SetUnionOperator::default();
+
+        // Truncated element
+        assert!(op.full_merge(b"k", None, &[&[3, b'a']]).is_none());
+        // Unsorted
+        assert!(op.full_merge(b"k", None, &[&[1, b'b', 1, b'a']]).is_none());
+        // Duplicate
+        assert!(
+            op.partial_merge(b"k", &set(&[b"a"]), &[1, b'a', 1, b'a'])
+                .is_none()
+        );
+        // Truncated length
+        assert!(op.full_merge(b"k", None, &[&[0x80]]).is_none());
+    }
+
+    #[test]
+    fn set_union_is_associative() {
+        let comp = DefaultComparator::new();
+        check_associative(&SetUnionOperator::default(), 300, |rng| {
+            let n = rng.below(6) as usize;
+            let elements: Vec<Vec<u8>> = (0..n)
+                .map(|_| {
+                    let len = rng.below(3) as usize;
+                    (0..len).map(|_| b'a' + rng.below(4) as u8).collect()
+                })
+                .collect();
+            let refs: Vec<&[u8]> = elements.iter().map(|e| e.as_slice()).collect();
+            encode_set(&*comp, &refs)
+        });
+    }
+}
@@ -0,0 +1,105 @@
+//
+//
+//
+// StringAppendOperator
+//
+// Appends every operand to the existing value separated by a delimiter. The delimiter may be empty for plain concatenation.
+//
+// base = "a", operands = ["b", "c"], delimiter = "," -> "a,b,c"
+//
+// With no base the first operand starts the value (no leading delimiter).
+
+use crate::merge::operator::MergeOperator;
+
+pub(crate) struct StringAppendOperator {
+    delimiter: Vec<u8>,
+}
+
+impl StringAppendOperator {
+    pub(crate) fn new(delimiter: &[u8]) -> Self {
+        Self {
+            delimiter: delimiter.to_vec(),
+        }
+    }
+}
+
+impl Default for StringAppendOperator {
+    fn default() -> Self {
+        Self::new(b",")
+    }
+}
+
+impl MergeOperator for StringAppendOperator {
+    fn name(&self) -> &str {
+        "victory.StringAppend"
+    }
+
+    fn full_merge(
+        &self,
+        _key: &[u8],
+        existing_value: Option<&[u8]>,
+        operands: &[&[u8]],
+    ) -> Option<Vec<u8>> {
+        let size = existing_value.map_or(0, |v| v.len())
+            + operands.iter().map(|o| o.len()).sum::<usize>()
+            + operands.len() * self.delimiter.len();
+
+        let mut out = Vec::with_capacity(size);
+        let mut first = true;
+
+        if let Some(v) = existing_value {
+            out.extend_from_slice(v);
+            first = false;
+        }
+
+        for op in operands {
+            if !first {
+                out.extend_from_slice(&self.delimiter);
+            }
+            out.extend_from_slice(op);
+            first = false;
+        }
+
+        Some(out)
+    }
+
+    fn partial_merge(&self, _key: &[u8], left: &[u8], right: &[u8]) -> Option<Vec<u8>> {
+        let mut out = Vec::with_capacity(left.len() + self.delimiter.len() + right.len());
+        out.extend_from_slice(left);
+        out.extend_from_slice(&self.delimiter);
+        out.extend_from_slice(right);
+        Some(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merge::operators::prop::check_associative;
+
+    #[test]
+    fn appends_with_delimiter() {
+        let op = StringAppendOperator::new(b", ");
+
+        let v = op.full_merge(b"k", Some(b"a"), &[b"b", b"c"]).unwrap();
+        assert_eq!(v, b"a, b, c");
+
+        let v = op.full_merge(b"k", None, &[b"b", b"c"]).unwrap();
+        assert_eq!(v, b"b, c");
+
+        let concat = StringAppendOperator::new(b"");
+        let v = concat.full_merge(b"k", Some(b"a"), &[b"b"]).unwrap();
+        assert_eq!(v, b"ab");
+    }
+
+    #[test]
+    fn string_append_is_associative() {
+        for delimiter in [b"".as_slice(), b",", b"--"] {
+            let op = StringAppendOperator::new(delimiter);
+            check_associative(&op, 200, |rng| {
+                let len = rng.below(8) as usize;
+                (0..len).map(|_| b'a' + rng.below(26) as u8).collect()
+            });
+        }
+    }
+}
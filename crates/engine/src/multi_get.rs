@@ -1 +1,376 @@
-// https://github.com/facebook/rocksdb/blob/763401b5/include/rocksdb/db.h#L794
+//
+//
+//
+// MultiGet
+//
+// Batched point lookups. The batch is resolved against one set of sources (one superversion) and one read seq no, so every key sees the
+// same view of the DB. Keys are sorted (and deduplicated) up front so each source is walked once, front to back:
+//
+//   memtables - one iterator per memtable, seeking to each key in turn. The seek is skipped when the iterator already stands on or past
+//               the key, which happens whenever the previous key had no entries or all of its entries were read.
+//   SST files - the keys are grouped by file using the file's key range, and each file is opened once for its group. The table iterator
+//               keeps its current data block while seeks land in it, so a block shared by several keys is read and decoded once.
+//
+// Every key keeps its own GetContext (see read_path.rs) so merge operands, tombstones and snapshots resolve exactly as they do for a
+// single get. A key drops out of the walk as soon as its context is complete. Range tombstones are looked up in the RangeDelIndex once per
+// key before the walk, and the tombstones a memtable or SST file keeps itself are applied to its keys before its entries are walked.
+//
+// With a read timestamp (see key/timestamp.rs) the keys are given without one and looked up at key | read timestamp. An iterator left
+// past a key may still stand on versions of the next key newer than the read timestamp, so every key is sought.
+//
+// Reference: https://github.com/facebook/rocksdb/blob/763401b5/include/rocksdb/db.h#L794
+
+use std::cmp::Ordering;
+use std::ops::Range;
+use std::sync::Arc;
+
+use crate::db::read_path::GetContext;
+use crate::error::{Error, Result};
+use crate::iterator::internal_iterator::InternalIterator;
+use crate::iterator::level_iterator::TableIterOpener;
+use crate::key::comparator::Comparator;
+use crate::key::internal_key::{InternalKeyRef, OperationType};
+use crate::key::lookup_key::LookUpInternalKey;
+use crate::merge::operator::MergeOperator;
+use crate::range::index::RangeDelVersion;
+use crate::versioning::file_version::FileMetaData;
+
+/// A source of entries for batched lookups - the batched counterpart of GetSource.
+pub(crate) trait MultiGetSource {
+    /// Feeds the entries of every unfinished key of the batch into its context.
+    fn multi_get(&self, batch: &mut MultiGetBatch<'_>);
+}
+
+pub(crate) struct MultiGetBatch<'a> {
+    user_comparator: Arc<dyn Comparator>,
+    sequence: u64,
+    // Size of the timestamp the user keys of the entries end in - the keys of the batch have none
+    timestamp_size: usize,
+    // Distinct keys in user key order, with a lookup key and context each
+    user_keys: Vec<&'a [u8]>,
+    lookup_keys: Vec<LookUpInternalKey>,
+    contexts: Vec<GetContext<'a>>,
+    done: Vec<bool>,
+    num_done: usize,
+    // Index into user_keys of every key as it was given
+    slots: Vec<usize>,
+}
+
+impl<'a> MultiGetBatch<'a> {
+    pub(crate) fn new(
+        keys: &[&'a [u8]],
+        sequence: u64,
+        user_comparator: Arc<dyn Comparator>,
+        merge_operator: Option<&'a dyn MergeOperator>,
+        now: u64,
+    ) -> Self {
+        let mut order: Vec<usize> = (0..keys.len()).collect();
+        order.sort_by(|&a, &b| {
+            user_comparator.compare_without_timestamp(keys[a], false, keys[b], false)
+        });
+
+        let mut user_keys: Vec<&'a [u8]> = Vec::with_capacity(keys.len());
+        let mut slots = vec![0; keys.len()];
+        for i in order {
+            let is_new = user_keys.last().is_none_or(|last| {
+                user_comparator.compare_without_timestamp(last, false, keys[i], false)
+                    != Ordering::Equal
+            });
+            if is_new {
+                user_keys.push(keys[i]);
+            }
+            slots[i] = user_keys.len() - 1;
+        }
+
+        Self {
+            lookup_keys: user_keys
+                .iter()
+                .map(|key| LookUpInternalKey::new(key, sequence, OperationType::Max))
+                .collect(),
+            contexts: user_keys
+                .iter()
+                .map(|key| GetContext::new(key, merge_operator, now))
+                .collect(),
+            done: vec![false; user_keys.len()],
+            num_done: 0,
+            user_comparator,
+            sequence,
+            timestamp_size: 0,
+            user_keys,
+            slots,
+        }
+    }
+
+    /// Reads the newest version of every key at or below `timestamp` (see key/timestamp.rs).
+    pub(crate) fn with_timestamp(mut self, timestamp: &[u8]) -> Self {
+        self.timestamp_size = timestamp.len();
+        for (key, lookup_key) in self.user_keys.iter().zip(self.lookup_keys.iter_mut()) {
+            let key_with_ts = [key, timestamp].concat();
+            *lookup_key = LookUpInternalKey::new(&key_with_ts, self.sequence, OperationType::Max);
+        }
+        let contexts = std::mem::take(&mut self.contexts);
+        self.contexts = contexts
+            .into_iter()
+            .map(|ctx| ctx.with_timestamp(timestamp.len(), self.sequence))
+            .collect();
+        self
+    }
+
+    /// Applies the range tombstones of the column family to every key.
+    pub(crate) fn set_range_del(&mut self, range_del: &RangeDelVersion) {
+        for (key, ctx) in self.user_keys.iter().zip(self.contexts.iter_mut()) {
+            ctx.raise_covering_seq(range_del.max_covering_seq(key, self.sequence));
+        }
+    }
+
+    #[inline]
+    pub(crate) fn is_done(&self) -> bool {
+        self.num_done == self.user_keys.len()
+    }
+
+    // Compares a key of the batch with the user key of an entry
+    fn compare_key(&self, key: &[u8], user_key: &[u8]) -> Ordering {
+        self.user_comparator.compare_without_timestamp(
+            key,
+            false,
+            user_key,
+            self.timestamp_size > 0,
+        )
+    }
+
+    // Keys whose user key is within [smallest, largest] - a range of the sorted keys
+    fn key_range(&self, smallest: &[u8], largest: &[u8]) -> Range<usize> {
+        let start = self
+            .user_keys
+            .partition_point(|key| self.compare_key(key, smallest) == Ordering::Less);
+        let end = self
+            .user_keys
+            .partition_point(|key| self.compare_key(key, largest) != Ordering::Greater);
+        start..end.max(start)
+    }
+
+    fn has_pending(&self, range: Range<usize>) -> bool {
+        self.done[range].iter().any(|done| !done)
+    }
+
+    fn finish_key(&mut self, i: usize) {
+        debug_assert!(!self.done[i]);
+        self.done[i] = true;
+        self.num_done += 1;
+    }
+
+    // True if the internal key sorts at or after key i's lookup key - the iterator needs no seek to reach the key's first entry
+    fn at_or_past(&self, ikey: &[u8], i: usize) -> bool {
+        if self.timestamp_size > 0 {
+            return false;
+        }
+        let ikey = InternalKeyRef::from(ikey);
+        match self.compare_key(self.user_keys[i], ikey.user_key) {
+            Ordering::Greater => false,
+            Ordering::Equal => ikey.seq_no <= self.sequence,
+            Ordering::Less => true,
+        }
+    }
+
+    /// Looks up every unfinished key with one iterator over a source, in key order.
+    pub(crate) fn walk<I: InternalIterator + ?Sized>(&mut self, iter: &mut I) {
+        self.walk_range(iter, 0..self.user_keys.len());
+    }
+
+    fn walk_range<I: InternalIterator + ?Sized>(&mut self, iter: &mut I, range: Range<usize>) {
+        let mut tombstones = Vec::new();
+        if let Err(e) = iter.range_tombstones(&mut tombstones) {
+            self.fail_range(range, e);
+            return;
+        }
+        for tombstones in &tombstones {
+            for i in range.clone() {
+                if !self.done[i] {
+                    self.contexts[i].raise_covering_seq(tombstones.max_covering_seq(
+                        self.user_comparator.as_ref(),
+                        self.user_keys[i],
+                        self.sequence,
+                    ));
+                }
+            }
+        }
+
+        let mut positioned = false;
+
+        for i in range.clone() {
+            if self.done[i] {
+                continue;
+            }
+
+            // Whatever the previous key left unread belongs to smaller user keys, so an iterator past this key's lookup key is already
+            // on its first entry
+            if !(positioned && iter.valid() && self.at_or_past(iter.key(), i)) {
+                iter.seek(self.lookup_keys[i].as_ref());
+                positioned = true;
+            }
+
+            while iter.valid() {
+                let ikey = InternalKeyRef::from(iter.key());
+                if self.compare_key(self.user_keys[i], ikey.user_key) != Ordering::Equal {
+                    break;
+                }
+                if !self.contexts[i].save_value(ikey, iter.value()) {
+                    self.finish_key(i);
+                    break;
+                }
+                iter.next();
+            }
+
+            if let Some(e) = iter.status() {
+                // The source can't be read past this point - fail every key which could still have entries in it
+                self.fail_range(i..range.end, e.clone());
+                return;
+            }
+        }
+    }
+
+    fn fail_range(&mut self, range: Range<usize>, error: Error) {
+        for i in range {
+            if !self.done[i] {
+                self.contexts[i].set_error(error.clone());
+                self.finish_key(i);
+            }
+        }
+    }
+
+    /// Looks up the batch in SST files, opening each file (at most once) for the keys within its range. The files are searched in the
+    /// order given, so L0 files must be newest first.
+    pub(crate) fn walk_files(
+        &mut self,
+        files: &[Arc<FileMetaData>],
+        open_table: &TableIterOpener<'_>,
+    ) {
+        for file in files {
+            if self.is_done() {
+                return;
+            }
+            // Every entry of the file is newer than the read seq no
+            if file.smallest_seqno > self.sequence {
+                continue;
+            }
+
+            let range = self.key_range(
+                InternalKeyRef::from(file.smallest.as_slice()).user_key,
+                InternalKeyRef::from(file.largest.as_slice()).user_key,
+            );
+            if !self.has_pending(range.clone()) {
+                continue;
+            }
+
+            match open_table(file) {
+                Ok(mut iter) => self.walk_range(iter.as_mut(), range),
+                Err(e) => self.fail_range(range, e),
+            }
+        }
+    }
+
+    /// Completes every lookup. The results are in the order the keys were given, duplicates get the same result.
+    pub(crate) fn finish(self) -> Vec<Result<Option<Vec<u8>>>> {
+        let results: Vec<Result<Option<Vec<u8>>>> =
+            self.contexts.into_iter().map(|ctx| ctx.finish()).collect();
+        self.slots.iter().map(|&i| results[i].clone()).collect()
+    }
+}
+
+/// Files of one level (or the L0 files, newest first) as a batched lookup source.
+pub(crate) struct LevelFiles<'a> {
+    files: &'a [Arc<FileMetaData>],
+    open_table: Box<TableIterOpener<'a>>,
+}
+
+impl<'a> LevelFiles<'a> {
+    pub(crate) fn new(
+        files: &'a [Arc<FileMetaData>],
+        open_table: Box<TableIterOpener<'a>>,
+    ) -> Self {
+        Self { files, open_table }
+    }
+}
+
+impl<'a> MultiGetSource for LevelFiles<'a> {
+    fn multi_get(&self, batch: &mut MultiGetBatch<'_>) {
+        batch.walk_files(self.files, self.open_table.as_ref());
+    }
+}
+
+/// Runs a batched lookup over the sources in order (newest first) at `sequence` and unix time `now`. Sources are only searched while some
+/// key is unresolved.
+pub(crate) fn multi_get(
+    sources: &[&dyn MultiGetSource],
+    keys: &[&[u8]],
+    sequence: u64,
+    user_comparator: Arc<dyn Comparator>,
+    merge_operator: Option<&dyn MergeOperator>,
+    now: u64,
+) -> Vec<Result<Option<Vec<u8>>>> {
+    let batch = MultiGetBatch::new(keys, sequence, user_comparator, merge_operator, now);
+    search(sources, batch)
+}
+
+/// multi_get with the range tombstones of the column family applied.
+pub(crate) fn multi_get_with_range_del(
+    sources: &[&dyn MultiGetSource],
+    range_del: &RangeDelVersion,
+    keys: &[&[u8]],
+    sequence: u64,
+    user_comparator: Arc<dyn Comparator>,
+    merge_operator: Option<&dyn MergeOperator>,
+    now: u64,
+) -> Vec<Result<Option<Vec<u8>>>> {
+    let mut batch = MultiGetBatch::new(keys, sequence, user_comparator, merge_operator, now);
+    batch.set_range_del(range_del);
+    search(sources, batch)
+}
+
+/// multi_get of the newest version of every key at or below `timestamp` on a column family with user-defined timestamps.
+pub(crate) fn multi_get_with_timestamp(
+    sources: &[&dyn MultiGetSource],
+    keys: &[&[u8]],
+    sequence: u64,
+    timestamp: &[u8],
+    user_comparator: Arc<dyn Comparator>,
+    merge_operator: Option<&dyn MergeOperator>,
+    now: u64,
+) -> Vec<Result<Option<Vec<u8>>>> {
+    let batch = MultiGetBatch::new(keys, sequence, user_comparator, merge_operator, now)
+        .with_timestamp(timestamp);
+    search(sources, batch)
+}
+
+fn search(
+    sources: &[&dyn MultiGetSource],
+    mut batch: MultiGetBatch<'_>,
+) -> Vec<Result<Option<Vec<u8>>>> {
+    for source in sources {
+        if batch.is_done() {
+            break;
+        }
+        source.multi_get(&mut batch);
+    }
+
+    batch.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key::comparator::DefaultComparator;
+
+    #[test]
+    fn keys_are_sorted_and_deduplicated() {
+        let keys: [&[u8]; 5] = [b"c", b"a", b"c", b"b", b"a"];
+        let batch = MultiGetBatch::new(&keys, 9, DefaultComparator::new(), None, 0);
+
+        assert_eq!(batch.user_keys, vec![b"a", b"b", b"c"]);
+        assert_eq!(batch.slots, vec![2, 0, 2, 1, 0]);
+        assert_eq!(batch.key_range(b"b", b"z"), 1..3);
+        assert_eq!(batch.key_range(b"a0", b"b"), 1..2);
+        assert_eq!(batch.key_range(b"d", b"e"), 3..3);
+        assert!(!batch.is_done());
+    }
+}
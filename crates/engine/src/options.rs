@@ -1,8 +1,19 @@
 // Memtable Options
 //
 
+use std::sync::Arc;
+use std::time::Duration;
+
 use mem::arena::ArenaPolicy;
 
+use crate::compaction::filter::CompactionFilter;
+use crate::iterator::iter_alloc::DEFAULT_MAX_POOLED_ALLOCS;
+use crate::key::comparator::{Comparator, DefaultComparator};
+use crate::key::prefix_extractor::PrefixExtractor;
+use crate::merge::operator::MergeOperator;
+use crate::utils::clock::{Clock, SystemClock};
+use crate::versioning::snapshot::Snapshot;
+
 const MB: usize = 1024;
 
 pub(crate) const SMALL_16MB: usize = 16 * MB;
@@ -15,6 +26,7 @@ const MEDIUM_BLOCK: usize = 4 * MB;
 const DEFAULT_BLOCK: usize = 4 * MB;
 const LARGE_BLOCK: usize = 8 * MB;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub(crate) enum WriteBufferSize {
     Small,
     Medium,
@@ -54,5 +66,203 @@ impl WriteBufferSize {
     }
 }
 
+// Compaction Options
+//
+
+const GB: u64 = 1024 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CompactionStyle {
+    Level,
+    // All files live in L0 and the oldest files are deleted once the configured caps are exceeded.
+    // Intended for time-series and log style data which is never updated.
+    Fifo,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct CompactionOptionsFifo {
+    // Once the total size of all files exceeds this, the oldest files are deleted
+    pub(crate) max_table_files_size: u64,
+    // Files whose oldest ancestor time is older than now - ttl (secs) are deleted. 0 disables TTL deletion.
+    pub(crate) ttl: u64,
+    // If true, small L0 files are merged together (intra-L0) to keep the file count bounded
+    pub(crate) allow_compaction: bool,
+    // Min number of L0 files before an intra-L0 merge is picked
+    pub(crate) intra_l0_file_trigger: usize,
+    // Upper bound on the input size of a single intra-L0 merge
+    pub(crate) max_compaction_bytes: u64,
+}
+
+impl Default for CompactionOptionsFifo {
+    fn default() -> Self {
+        Self {
+            max_table_files_size: GB,
+            ttl: 0,
+            allow_compaction: false,
+            intra_l0_file_trigger: 4,
+            max_compaction_bytes: DEFAULT_64MB as u64 * 25,
+        }
+    }
+}
+
+// Column Family Options
+//
+
+#[derive(Clone)]
+pub(crate) struct ColumnFamilyOptions {
+    // Orders the user keys of the column family. Its name is in the MANIFEST, a reopen with a differently named comparator fails
+    pub(crate) comparator: Arc<dyn Comparator>,
+    // Size of each memtable (and the arena policy backing it)
+    pub(crate) write_buffer_size: WriteBufferSize,
+    pub(crate) compaction_style: CompactionStyle,
+    pub(crate) compaction_options_fifo: CompactionOptionsFifo,
+    // Compactions into the levels below L0 cut their output into files of about this size
+    pub(crate) target_file_size_base: u64,
+    // Range deletions go into the memtable and from there into the table files holding the keys they cover, instead of the column
+    // family's RangeDelIndex (see range/index.rs)
+    pub(crate) per_file_range_deletions: bool,
+    // Called for every entry rewritten by a flush or compaction of this column family
+    pub(crate) compaction_filter: Option<Arc<dyn CompactionFilter>>,
+    // Resolves OperationType::Merge entries on reads, flushes and compactions
+    pub(crate) merge_operator: Option<Arc<dyn MergeOperator>>,
+    // Groups keys by prefix - needed for ReadOptions::prefix_same_as_start
+    pub(crate) prefix_extractor: Option<Arc<dyn PrefixExtractor>>,
+    // Flushes and compactions write values of at least min_blob_size to blob files and keep a reference to them in the table
+    pub(crate) enable_blob_files: bool,
+    pub(crate) min_blob_size: usize,
+    // Compactions rewrite the live blobs of blob files whose live ratio is below blob_garbage_collection_live_ratio, so the
+    // files can be deleted
+    pub(crate) enable_blob_garbage_collection: bool,
+    pub(crate) blob_garbage_collection_live_ratio: f64,
+}
+
+impl Default for ColumnFamilyOptions {
+    fn default() -> Self {
+        Self {
+            comparator: DefaultComparator::new(),
+            write_buffer_size: WriteBufferSize::Default,
+            compaction_style: CompactionStyle::Level,
+            compaction_options_fifo: CompactionOptionsFifo::default(),
+            target_file_size_base: DEFAULT_64MB as u64,
+            per_file_range_deletions: false,
+            compaction_filter: None,
+            merge_operator: None,
+            prefix_extractor: None,
+            enable_blob_files: false,
+            min_blob_size: 4096,
+            enable_blob_garbage_collection: true,
+            blob_garbage_collection_live_ratio: 0.5,
+        }
+    }
+}
+
+// DB Options
+//
+
+#[derive(Debug, Clone)]
+pub(crate) struct DbOptions {
+    // Create the DB when the directory holds none
+    pub(crate) create_if_missing: bool,
+    // Create column families which are opened but do not exist yet
+    pub(crate) create_missing_column_families: bool,
+    // Wall clock expiring entries are read at and the seq no to time mapping is sampled with
+    pub(crate) clock: Arc<dyn Clock>,
+    // How often the newest seq no is sampled with the time (see versioning/seqno_to_time.rs) - zero turns sampling off
+    pub(crate) seqno_time_sample_period: Duration,
+    // Iterator allocations (see iterator/iter_alloc.rs) kept for re-use - zero builds every iterator on the heap
+    pub(crate) max_pooled_iter_allocs: usize,
+}
+
+impl Default for DbOptions {
+    fn default() -> Self {
+        Self {
+            create_if_missing: false,
+            create_missing_column_families: false,
+            clock: SystemClock::new(),
+            seqno_time_sample_period: Duration::from_secs(600),
+            max_pooled_iter_allocs: DEFAULT_MAX_POOLED_ALLOCS,
+        }
+    }
+}
+
+// Read Options
+//
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ReadOptions {
+    // Reads see the DB as of this snapshot instead of the latest write
+    pub(crate) snapshot: Option<Arc<Snapshot>>,
+    // Iterators start at this user key (inclusive) - a seek to a smaller key is moved up to the bound
+    pub(crate) iterate_lower_bound: Option<Vec<u8>>,
+    // Iterators stop before this user key (exclusive)
+    pub(crate) iterate_upper_bound: Option<Vec<u8>>,
+    // Iterators only return keys which share the prefix (ColumnFamilyOptions::prefix_extractor) of the key they were positioned with
+    pub(crate) prefix_same_as_start: bool,
+    // Reads see the newest version of each key at or below this timestamp - required (and only allowed) on column families whose comparator
+    // has a timestamp (see key/timestamp.rs)
+    pub(crate) timestamp: Option<Vec<u8>>,
+}
+
+// Ingestion Options
+//
+
+#[derive(Debug, Clone)]
+pub(crate) struct IngestExternalFileOptions {
+    // Hard link the files into the DB (copying only across filesystems) and delete the originals once they are ingested
+    pub(crate) move_files: bool,
+    // Give the entries a global seq no when a live snapshot exists, so reads at the snapshot don't see them
+    pub(crate) snapshot_consistency: bool,
+    // Allow the files to be rewritten with a global seq no - without it an ingestion which needs one fails
+    pub(crate) allow_global_seqno: bool,
+}
+
+impl Default for IngestExternalFileOptions {
+    fn default() -> Self {
+        Self {
+            move_files: false,
+            snapshot_consistency: true,
+            allow_global_seqno: true,
+        }
+    }
+}
+
+// Transaction Options
+//
+
+#[derive(Debug, Clone)]
+pub(crate) struct TransactionDbOptions {
+    // Number of mutexes the point locks are striped over (by key hash)
+    pub(crate) num_stripes: usize,
+    // How long a transaction waits for a lock another transaction holds before failing with TimedOut - zero fails at once
+    pub(crate) lock_timeout: Duration,
+}
+
+impl Default for TransactionDbOptions {
+    fn default() -> Self {
+        Self {
+            num_stripes: 16,
+            lock_timeout: Duration::from_secs(1),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TransactionOptions {
+    // Overrides TransactionDbOptions::lock_timeout
+    pub(crate) lock_timeout: Option<Duration>,
+    // Fail a lock which would close a cycle in the wait-for graph with Busy instead of waiting for the timeout
+    pub(crate) deadlock_detect: bool,
+}
+
+impl ReadOptions {
+    /// Seq no reads are pinned at - the snapshot's if one is set, otherwise `last_sequence`.
+    #[inline]
+    pub(crate) fn read_sequence(&self, last_sequence: u64) -> u64 {
+        self.snapshot
+            .as_ref()
+            .map_or(last_sequence, |snapshot| snapshot.sequence())
+    }
+}
+
 #[test]
 fn const_test() {}
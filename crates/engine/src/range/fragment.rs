@@ -0,0 +1,368 @@
+//
+//
+//
+// Fragmented Range Tombstones
+//
+// A range tombstone deletes every version of every user key in [start, end) with a seq no below its own. Tombstones can overlap
+// arbitrarily, which makes "is this key covered" a scan over all of them. Fragmenting splits the key space at every start and end so
+// that the pieces never overlap:
+//
+//   [a ------------ e)@5                     [a - c)@5  [c - e)@9,5  [e - g)@9
+//            [c ------------ g)@9     ->
+//
+// Each fragment lists the seq nos of every tombstone covering it (newest first), so a lookup is a binary search for the fragment and
+// another for the newest seq no visible at the read seq no. Neighbouring fragments with the same seq nos are joined back together.
+
+use std::cmp::Ordering;
+
+use crate::key::comparator::Comparator;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct RangeTombstone {
+    pub(crate) start: Vec<u8>,
+    // Exclusive
+    pub(crate) end: Vec<u8>,
+    pub(crate) seq: u64,
+}
+
+impl RangeTombstone {
+    pub(crate) fn new(start: &[u8], end: &[u8], seq: u64) -> Self {
+        Self {
+            start: start.to_vec(),
+            end: end.to_vec(),
+            seq,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct TombstoneFragment {
+    pub(crate) start: Vec<u8>,
+    pub(crate) end: Vec<u8>,
+    // Newest first, distinct
+    pub(crate) seqs: Vec<u64>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct FragmentedTombstones {
+    // Ordered by start, never overlapping
+    fragments: Vec<TombstoneFragment>,
+    // Number of (fragment, seq) pairs
+    num_entries: usize,
+    smallest_seq: u64,
+    largest_seq: u64,
+}
+
+impl FragmentedTombstones {
+    /// Empty ranges (start >= end) are ignored.
+    pub(crate) fn new(
+        tombstones: impl IntoIterator<Item = RangeTombstone>,
+        user_comparator: &dyn Comparator,
+    ) -> Self {
+        let cmp = |a: &[u8], b: &[u8]| user_comparator.compare(a, b);
+
+        let mut tombstones: Vec<RangeTombstone> = tombstones
+            .into_iter()
+            .filter(|t| cmp(&t.start, &t.end) == Ordering::Less)
+            .collect();
+        if tombstones.is_empty() {
+            return Self::default();
+        }
+        tombstones.sort_by(|a, b| cmp(&a.start, &b.start));
+
+        let mut bounds: Vec<&[u8]> = tombstones
+            .iter()
+            .flat_map(|t| [t.start.as_slice(), t.end.as_slice()])
+            .collect();
+        bounds.sort_by(|a, b| cmp(a, b));
+        bounds.dedup_by(|a, b| cmp(a, b) == Ordering::Equal);
+
+        let mut fragments: Vec<TombstoneFragment> = Vec::new();
+        // Tombstones which started at or before the current bound
+        let mut active: Vec<&RangeTombstone> = Vec::new();
+        let mut next = 0;
+
+        for w in bounds.windows(2) {
+            let (lo, hi) = (w[0], w[1]);
+
+            while next < tombstones.len() && cmp(&tombstones[next].start, lo) != Ordering::Greater {
+                active.push(&tombstones[next]);
+                next += 1;
+            }
+            // Every bound is a start or an end, so whatever reaches past lo covers all of [lo, hi)
+            active.retain(|t| cmp(&t.end, lo) == Ordering::Greater);
+            if active.is_empty() {
+                continue;
+            }
+
+            let mut seqs: Vec<u64> = active.iter().map(|t| t.seq).collect();
+            seqs.sort_unstable_by(|a, b| b.cmp(a));
+            seqs.dedup();
+
+            push_joined(&mut fragments, lo, hi, seqs, user_comparator);
+        }
+
+        Self::from_fragments(fragments)
+    }
+
+    fn from_fragments(fragments: Vec<TombstoneFragment>) -> Self {
+        let seqs = || fragments.iter().flat_map(|f| f.seqs.iter().copied());
+        Self {
+            num_entries: fragments.iter().map(|f| f.seqs.len()).sum(),
+            smallest_seq: seqs().min().unwrap_or(0),
+            largest_seq: seqs().max().unwrap_or(0),
+            fragments,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn fragments(&self) -> &[TombstoneFragment] {
+        &self.fragments
+    }
+
+    #[inline]
+    pub(crate) fn is_empty(&self) -> bool {
+        self.fragments.is_empty()
+    }
+
+    #[inline]
+    pub(crate) fn num_entries(&self) -> usize {
+        self.num_entries
+    }
+
+    #[inline]
+    pub(crate) fn smallest_seq(&self) -> u64 {
+        self.smallest_seq
+    }
+
+    #[inline]
+    pub(crate) fn largest_seq(&self) -> u64 {
+        self.largest_seq
+    }
+
+    /// Seq no of the newest tombstone visible at `read_seq` which covers the user key, 0 if there is none.
+    pub(crate) fn max_covering_seq(
+        &self,
+        user_comparator: &dyn Comparator,
+        user_key: &[u8],
+        read_seq: u64,
+    ) -> u64 {
+        if self.fragments.is_empty() || self.smallest_seq > read_seq {
+            return 0;
+        }
+
+        let idx = self
+            .fragments
+            .partition_point(|f| user_comparator.compare(&f.start, user_key) != Ordering::Greater);
+        let Some(fragment) = idx.checked_sub(1).map(|i| &self.fragments[i]) else {
+            return 0;
+        };
+        if user_comparator.compare(user_key, &fragment.end) != Ordering::Less {
+            return 0;
+        }
+
+        let i = fragment.seqs.partition_point(|seq| *seq > read_seq);
+        fragment.seqs.get(i).copied().unwrap_or(0)
+    }
+
+    /// Every fragment as a tombstone per seq no - fragmenting the output again gives the same fragments.
+    pub(crate) fn tombstones(&self) -> impl Iterator<Item = RangeTombstone> + '_ {
+        self.fragments.iter().flat_map(|f| {
+            f.seqs
+                .iter()
+                .map(|seq| RangeTombstone::new(&f.start, &f.end, *seq))
+        })
+    }
+
+    /// Every tombstone clipped to [lower, upper) - the key range an output file owns when compaction splits its output. A missing
+    /// bound leaves that side open. Tombstones entirely outside the range are left out.
+    pub(crate) fn truncated<'a>(
+        &'a self,
+        user_comparator: &'a dyn Comparator,
+        lower: Option<&'a [u8]>,
+        upper: Option<&'a [u8]>,
+    ) -> impl Iterator<Item = RangeTombstone> + 'a {
+        self.tombstones().filter_map(move |mut t| {
+            if let Some(lower) = lower
+                && user_comparator.compare(&t.start, lower) == Ordering::Less
+            {
+                t.start = lower.to_vec();
+            }
+            if let Some(upper) = upper
+                && user_comparator.compare(&t.end, upper) == Ordering::Greater
+            {
+                t.end = upper.to_vec();
+            }
+            (user_comparator.compare(&t.start, &t.end) == Ordering::Less).then_some(t)
+        })
+    }
+
+    /// Keeps only what a reader can still see: tombstones newer than `horizon`, and in each fragment only the newest tombstone of every
+    /// snapshot stripe (a reader anywhere in the stripe finds that one first). `snapshots` are ascending.
+    pub(crate) fn compact(
+        &self,
+        horizon: u64,
+        snapshots: &[u64],
+        user_comparator: &dyn Comparator,
+    ) -> Self {
+        let stripe_of = |seq: u64| {
+            let idx = snapshots.partition_point(|s| *s < seq);
+            snapshots.get(idx).copied().unwrap_or(u64::MAX)
+        };
+
+        let mut fragments: Vec<TombstoneFragment> = Vec::with_capacity(self.fragments.len());
+        for f in &self.fragments {
+            let mut seqs: Vec<u64> = Vec::with_capacity(f.seqs.len());
+            let mut last_stripe = None;
+            for &seq in f.seqs.iter().take_while(|seq| **seq > horizon) {
+                let stripe = stripe_of(seq);
+                if last_stripe != Some(stripe) {
+                    seqs.push(seq);
+                    last_stripe = Some(stripe);
+                }
+            }
+            if !seqs.is_empty() {
+                push_joined(&mut fragments, &f.start, &f.end, seqs, user_comparator);
+            }
+        }
+
+        Self::from_fragments(fragments)
+    }
+}
+
+// Appends the fragment, extending the last one instead when it ends at `start` with the same seq nos
+fn push_joined(
+    fragments: &mut Vec<TombstoneFragment>,
+    start: &[u8],
+    end: &[u8],
+    seqs: Vec<u64>,
+    user_comparator: &dyn Comparator,
+) {
+    if let Some(last) = fragments.last_mut()
+        && last.seqs == seqs
+        && user_comparator.compare(&last.end, start) == Ordering::Equal
+    {
+        last.end = end.to_vec();
+        return;
+    }
+    fragments.push(TombstoneFragment {
+        start: start.to_vec(),
+        end: end.to_vec(),
+        seqs,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key::comparator::DefaultComparator;
+
+    fn fragment(tombstones: &[(&str, &str, u64)]) -> FragmentedTombstones {
+        FragmentedTombstones::new(
+            tombstones
+                .iter()
+                .map(|(s, e, seq)| RangeTombstone::new(s.as_bytes(), e.as_bytes(), *seq)),
+            DefaultComparator::new().as_ref(),
+        )
+    }
+
+    #[test]
+    fn overlapping_tombstones_are_split() {
+        let frags = fragment(&[("a", "e", 5), ("c", "g", 9), ("x", "x", 3)]);
+
+        let out: Vec<(&[u8], &[u8], Vec<u64>)> = frags
+            .fragments()
+            .iter()
+            .map(|f| (f.start.as_slice(), f.end.as_slice(), f.seqs.clone()))
+            .collect();
+        assert_eq!(
+            out,
+            vec![
+                (b"a".as_slice(), b"c".as_slice(), vec![5]),
+                (b"c", b"e", vec![9, 5]),
+                (b"e", b"g", vec![9]),
+            ]
+        );
+        assert_eq!((frags.smallest_seq(), frags.largest_seq()), (5, 9));
+    }
+
+    #[test]
+    fn covering_seq() {
+        let cmp = DefaultComparator::new();
+        let frags = fragment(&[("a", "e", 5), ("c", "g", 9), ("m", "p", 2)]);
+
+        assert_eq!(frags.max_covering_seq(cmp.as_ref(), b"d", 100), 9);
+        assert_eq!(frags.max_covering_seq(cmp.as_ref(), b"d", 8), 5);
+        assert_eq!(frags.max_covering_seq(cmp.as_ref(), b"d", 4), 0);
+        assert_eq!(frags.max_covering_seq(cmp.as_ref(), b"g", 100), 0);
+        assert_eq!(frags.max_covering_seq(cmp.as_ref(), b"h", 100), 0);
+        assert_eq!(frags.max_covering_seq(cmp.as_ref(), b"0", 100), 0);
+        assert_eq!(frags.max_covering_seq(cmp.as_ref(), b"o", 100), 2);
+    }
+
+    #[test]
+    fn adjacent_fragments_are_joined() {
+        let frags = fragment(&[("a", "c", 4), ("c", "e", 4), ("b", "d", 4)]);
+        assert_eq!(frags.fragments().len(), 1);
+        assert_eq!(frags.fragments()[0].end, b"e");
+
+        let cmp = DefaultComparator::new();
+        let newer = fragment(&[("a", "e", 5), ("c", "g", 9)]).compact(5, &[], cmp.as_ref());
+        assert_eq!(newer.fragments().len(), 1);
+        assert_eq!(newer.fragments()[0].start, b"c");
+        assert_eq!(newer.num_entries(), 1);
+    }
+
+    #[test]
+    fn compaction_keeps_the_newest_seq_per_stripe() {
+        let cmp = DefaultComparator::new();
+        let frags = fragment(&[("a", "e", 3), ("a", "e", 5), ("c", "g", 9), ("c", "g", 12)]);
+
+        // No snapshots - every reader sees the newest tombstone first
+        let tip = frags.compact(0, &[], cmp.as_ref());
+        let seqs: Vec<Vec<u64>> = tip.fragments().iter().map(|f| f.seqs.clone()).collect();
+        assert_eq!(seqs, vec![vec![5], vec![12]]);
+
+        // A snapshot at 6 still reads 5 under [c, e)
+        let striped = frags.compact(0, &[6], cmp.as_ref());
+        let seqs: Vec<Vec<u64>> = striped.fragments().iter().map(|f| f.seqs.clone()).collect();
+        assert_eq!(seqs, vec![vec![5], vec![12, 5], vec![12]]);
+        assert_eq!(striped.max_covering_seq(cmp.as_ref(), b"d", 6), 5);
+        assert_eq!(striped.max_covering_seq(cmp.as_ref(), b"d", 4), 0);
+    }
+
+    #[test]
+    fn truncation_at_file_boundaries() {
+        let cmp = DefaultComparator::new();
+        let frags = fragment(&[("a", "e", 5), ("c", "m", 9), ("p", "t", 2)]);
+
+        let clip = |lower: Option<&[u8]>, upper: Option<&[u8]>| -> Vec<(String, String, u64)> {
+            frags
+                .truncated(cmp.as_ref(), lower, upper)
+                .map(|t| {
+                    (
+                        String::from_utf8(t.start).unwrap(),
+                        String::from_utf8(t.end).unwrap(),
+                        t.seq,
+                    )
+                })
+                .collect()
+        };
+        let owned = |v: &[(&str, &str, u64)]| -> Vec<(String, String, u64)> {
+            v.iter()
+                .map(|(s, e, seq)| (s.to_string(), e.to_string(), *seq))
+                .collect()
+        };
+
+        assert_eq!(
+            clip(None, Some(b"d")),
+            owned(&[("a", "c", 5), ("c", "d", 9), ("c", "d", 5)])
+        );
+        assert_eq!(
+            clip(Some(b"d"), Some(b"q")),
+            owned(&[("d", "e", 9), ("d", "e", 5), ("e", "m", 9), ("p", "q", 2)])
+        );
+        assert_eq!(clip(Some(b"q"), None), owned(&[("q", "t", 2)]));
+        assert!(clip(Some(b"m"), Some(b"p")).is_empty());
+    }
+}
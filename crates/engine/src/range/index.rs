@@ -0,0 +1,317 @@
+//
+//
+//
+// RangeDelIndex
+//
+// The global range deletion index of a column family. Every range tombstone of the column family lives here instead of next to the point
+// data in memtables and SST files, so whether a key is covered is answered by one index no matter how many files the key's versions are
+// spread over.
+//
+// The index is an LSM of its own:
+//
+//   buffer   - the newest tombstones, fragmented again on every insert. Bounded by buffer_capacity.
+//   L0 .. Ln - fragmented runs. A full buffer is flushed into L0, and a run which outgrows its level (buffer_capacity * size_ratio^(n+1)
+//              entries) is merged into the next one.
+//
+// Readers never block writers: every change publishes a new RangeDelVersion (the buffer and the runs behind Arcs) and a reader works on
+// the version it loaded. Iterators hold on to their version so the tombstones they read with can't be garbage collected under them.
+//
+// Garbage collection: a tombstone only hides versions with a smaller seq no. Once no live data (memtables or SST files) has a seq no
+// below the tombstone's there is nothing left for it to hide and it is dropped - compaction reports that horizon after it has rewritten
+// the covered keys away. A tombstone shadowed by a newer one in the same snapshot stripe is dropped too, and whatever is left is merged
+// into a single run at the bottom level.
+
+use std::sync::{Arc, Mutex, RwLock};
+
+use crate::error::{Error, Result};
+use crate::key::comparator::Comparator;
+use crate::range::fragment::{FragmentedTombstones, RangeTombstone};
+
+const DEFAULT_BUFFER_CAPACITY: usize = 64;
+const DEFAULT_SIZE_RATIO: usize = 4;
+
+/// Immutable view of the index.
+pub(crate) struct RangeDelVersion {
+    user_comparator: Arc<dyn Comparator>,
+    buffer: Arc<FragmentedTombstones>,
+    // L0 first, only levels which hold a run
+    runs: Vec<Arc<FragmentedTombstones>>,
+}
+
+impl RangeDelVersion {
+    fn empty(user_comparator: Arc<dyn Comparator>) -> Self {
+        Self {
+            user_comparator,
+            buffer: Arc::default(),
+            runs: Vec::new(),
+        }
+    }
+
+    /// Seq no of the newest tombstone visible at `read_seq` which covers the user key, 0 if there is none. Every version of the key
+    /// below it is deleted.
+    pub(crate) fn max_covering_seq(&self, user_key: &[u8], read_seq: u64) -> u64 {
+        let cmp = self.user_comparator.as_ref();
+        std::iter::once(&self.buffer)
+            .chain(self.runs.iter())
+            .map(|run| run.max_covering_seq(cmp, user_key, read_seq))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// True if the version of the key at `seq` is deleted by a tombstone visible at `read_seq`.
+    #[inline]
+    pub(crate) fn is_covered(&self, user_key: &[u8], seq: u64, read_seq: u64) -> bool {
+        self.max_covering_seq(user_key, read_seq) > seq
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.buffer.is_empty() && self.runs.is_empty()
+    }
+
+    /// Number of (fragment, seq no) entries across the buffer and every run.
+    pub(crate) fn num_entries(&self) -> usize {
+        self.buffer.num_entries() + self.runs.iter().map(|r| r.num_entries()).sum::<usize>()
+    }
+
+    #[inline]
+    pub(crate) fn num_runs(&self) -> usize {
+        self.runs.len()
+    }
+
+    /// Every (fragment, seq no) entry as a tombstone of its own - they hide the same versions as the tombstones inserted.
+    pub(crate) fn tombstones(&self) -> impl Iterator<Item = RangeTombstone> + '_ {
+        std::iter::once(&self.buffer)
+            .chain(self.runs.iter())
+            .flat_map(|run| run.tombstones())
+    }
+}
+
+struct IndexState {
+    buffer: Vec<RangeTombstone>,
+    levels: Vec<Option<Arc<FragmentedTombstones>>>,
+}
+
+pub(crate) struct RangeDelIndex {
+    user_comparator: Arc<dyn Comparator>,
+    buffer_capacity: usize,
+    size_ratio: usize,
+    // Writers are serialized here
+    state: Mutex<IndexState>,
+    current: RwLock<Arc<RangeDelVersion>>,
+}
+
+impl RangeDelIndex {
+    pub(crate) fn new(user_comparator: Arc<dyn Comparator>) -> Self {
+        Self::with_shape(user_comparator, DEFAULT_BUFFER_CAPACITY, DEFAULT_SIZE_RATIO)
+    }
+
+    pub(crate) fn with_shape(
+        user_comparator: Arc<dyn Comparator>,
+        buffer_capacity: usize,
+        size_ratio: usize,
+    ) -> Self {
+        debug_assert!(buffer_capacity > 0 && size_ratio > 1);
+        Self {
+            current: RwLock::new(Arc::new(RangeDelVersion::empty(Arc::clone(
+                &user_comparator,
+            )))),
+            user_comparator,
+            buffer_capacity,
+            size_ratio,
+            state: Mutex::new(IndexState {
+                buffer: Vec::new(),
+                levels: Vec::new(),
+            }),
+        }
+    }
+
+    /// The current view - tombstones added later are not in it.
+    pub(crate) fn version(&self) -> Arc<RangeDelVersion> {
+        Arc::clone(&self.current.read().unwrap())
+    }
+
+    /// Deletes [start, end) below `seq`. An empty range is a no-op.
+    pub(crate) fn insert(&self, start: &[u8], end: &[u8], seq: u64) -> Result<()> {
+        match self.user_comparator.compare(start, end) {
+            std::cmp::Ordering::Greater => {
+                return Err(Error::InvalidArgument(
+                    "range deletion start is after its end".into(),
+                ));
+            }
+            std::cmp::Ordering::Equal => return Ok(()),
+            std::cmp::Ordering::Less => {}
+        }
+
+        let mut state = self.state.lock().unwrap();
+        state.buffer.push(RangeTombstone::new(start, end, seq));
+
+        if state.buffer.len() >= self.buffer_capacity {
+            let run = FragmentedTombstones::new(
+                std::mem::take(&mut state.buffer),
+                self.user_comparator.as_ref(),
+            );
+            self.push_run(&mut state, 0, run);
+        }
+
+        self.publish(&state);
+        Ok(())
+    }
+
+    // Places the run at the level, merging with what is there and moving down while the result outgrows the level
+    fn push_run(&self, state: &mut IndexState, mut level: usize, mut run: FragmentedTombstones) {
+        loop {
+            if state.levels.len() <= level {
+                state.levels.resize(level + 1, None);
+            }
+
+            if let Some(existing) = state.levels[level].take() {
+                run = FragmentedTombstones::new(
+                    existing.tombstones().chain(run.tombstones()),
+                    self.user_comparator.as_ref(),
+                );
+            }
+
+            if run.num_entries() <= self.level_capacity(level) {
+                state.levels[level] = (!run.is_empty()).then(|| Arc::new(run));
+                return;
+            }
+            level += 1;
+        }
+    }
+
+    #[inline]
+    fn level_capacity(&self, level: usize) -> usize {
+        self.buffer_capacity
+            .saturating_mul(self.size_ratio.saturating_pow(level as u32 + 1))
+    }
+
+    fn publish(&self, state: &IndexState) {
+        let version = RangeDelVersion {
+            user_comparator: Arc::clone(&self.user_comparator),
+            buffer: Arc::new(FragmentedTombstones::new(
+                state.buffer.iter().cloned(),
+                self.user_comparator.as_ref(),
+            )),
+            runs: state.levels.iter().flatten().cloned().collect(),
+        };
+        *self.current.write().unwrap() = Arc::new(version);
+    }
+
+    /// Drops every tombstone with a seq no <= `horizon`, the smallest seq no of any live data of the column family - such a tombstone
+    /// has nothing left to hide. Runs also keep only the newest tombstone of each snapshot stripe per fragment, so `snapshots` (ascending)
+    /// must hold every seq no the index may still be read at: the live snapshots and the last published seq no. Readers load their
+    /// version before their read seq no for this to hold.
+    ///
+    /// Returns the number of (fragment, seq no) entries of the merged run the collection dropped.
+    pub(crate) fn collect_garbage(&self, horizon: u64, snapshots: &[u64]) -> usize {
+        debug_assert!(snapshots.windows(2).all(|w| w[0] <= w[1]));
+
+        let mut state = self.state.lock().unwrap();
+        let cmp = self.user_comparator.as_ref();
+
+        let runs: Vec<Arc<FragmentedTombstones>> = state.levels.iter().flatten().cloned().collect();
+        if state.buffer.is_empty() && runs.is_empty() {
+            return 0;
+        }
+
+        // Every tombstone is merged into one run at the bottom level, so the stripes collapse across runs as well. Both sides are
+        // counted in fragments of that run - fragmenting overlapping tombstones of different runs can add entries
+        let fragmented = FragmentedTombstones::new(
+            state
+                .buffer
+                .drain(..)
+                .chain(runs.iter().flat_map(|r| r.tombstones())),
+            cmp,
+        );
+        let merged = fragmented.compact(horizon, snapshots, cmp);
+        let dropped = fragmented
+            .num_entries()
+            .saturating_sub(merged.num_entries());
+
+        let bottom = state.levels.len().saturating_sub(1);
+        state.levels.iter_mut().for_each(|slot| *slot = None);
+        if !merged.is_empty() {
+            state.levels.resize(bottom + 1, None);
+            state.levels[bottom] = Some(Arc::new(merged));
+        }
+
+        self.publish(&state);
+        dropped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key::comparator::DefaultComparator;
+
+    #[test]
+    fn full_buffers_cascade_into_levels() {
+        let index = RangeDelIndex::with_shape(DefaultComparator::new(), 2, 2);
+
+        for i in 0..16u64 {
+            let start = format!("k{:02}", i);
+            let end = format!("k{:02}a", i);
+            index
+                .insert(start.as_bytes(), end.as_bytes(), i + 1)
+                .unwrap();
+        }
+
+        let version = index.version();
+        assert_eq!(version.num_entries(), 16);
+        assert!(version.num_runs() >= 2);
+        for i in 0..16u64 {
+            let key = format!("k{:02}", i);
+            assert_eq!(version.max_covering_seq(key.as_bytes(), u64::MAX), i + 1);
+        }
+    }
+
+    #[test]
+    fn versions_are_stable() {
+        let index = RangeDelIndex::new(DefaultComparator::new());
+        index.insert(b"a", b"c", 5).unwrap();
+        let before = index.version();
+
+        index.insert(b"b", b"z", 9).unwrap();
+        assert_eq!(before.max_covering_seq(b"d", 100), 0);
+        assert_eq!(index.version().max_covering_seq(b"d", 100), 9);
+
+        assert!(index.insert(b"z", b"a", 10).is_err());
+        assert!(index.insert(b"q", b"q", 10).is_ok());
+        assert_eq!(index.version().max_covering_seq(b"q", 100), 9);
+    }
+
+    #[test]
+    fn garbage_collection() {
+        let index = RangeDelIndex::with_shape(DefaultComparator::new(), 2, 2);
+        index.insert(b"a", b"c", 3).unwrap();
+        index.insert(b"a", b"f", 7).unwrap();
+        index.insert(b"x", b"y", 4).unwrap();
+        let pinned = index.version();
+
+        // Drops x@4 from the buffer and a@3 from L0
+        assert_eq!(index.collect_garbage(4, &[10]), 3);
+        let version = index.version();
+        assert_eq!(version.max_covering_seq(b"b", 100), 7);
+        assert_eq!(version.max_covering_seq(b"x", 100), 0);
+        assert_eq!(version.num_entries(), 1);
+
+        // A pinned version keeps its tombstones
+        assert_eq!(pinned.max_covering_seq(b"x", 100), 4);
+        assert_eq!(pinned.max_covering_seq(b"b", 5), 3);
+        assert_eq!(index.collect_garbage(4, &[10]), 0);
+    }
+
+    #[test]
+    fn garbage_collection_keeps_what_snapshots_read() {
+        let index = RangeDelIndex::with_shape(DefaultComparator::new(), 3, 2);
+        index.insert(b"a", b"m", 2).unwrap();
+        index.insert(b"a", b"m", 4).unwrap();
+        index.insert(b"a", b"m", 6).unwrap();
+
+        assert_eq!(index.collect_garbage(0, &[5, 10]), 1);
+        let version = index.version();
+        assert_eq!(version.max_covering_seq(b"c", 10), 6);
+        assert_eq!(version.max_covering_seq(b"c", 5), 4);
+    }
+}
@@ -1,4 +1,10 @@
-// TODO: After completing the main LSM - Implement RangeDelIndex
+// Range deletions
 //
-// RangeDelIndex will be the GLORAN Implementation based on the paper: https://arxiv.org/pdf/2511.06061v1
-// For this, a separate LSM sub system is needed with a global index
+// RangeDelIndex is the GLORAN style global range deletion index (https://arxiv.org/pdf/2511.06061v1): range tombstones are kept in a
+// separate LSM sub system per column family instead of being interleaved with the point data. See range_del.md for the design.
+//
+// Memtables and SST files can also hold range tombstones of their own (a range deletion skiplist / meta block). Both layouts share the
+// fragmented representation in fragment.rs.
+
+pub(crate) mod fragment;
+pub(crate) mod index;
@@ -0,0 +1,126 @@
+//
+//
+//
+// Table format
+//
+// An SST file is a sequence of blocks followed by a fixed size footer:
+//
+// | data block 0 | data block 1 | ... | index block | properties block | range deletion block | footer |
+//
+// Data blocks   - sorted internal keys (BlockBuilder format)
+// Index block   - one entry per data block. The key is a separator >= every key in the block and < every key in the next block, the value
+//                 is the BlockHandle of the data block. Readers binary search the index so only the blocks which can hold a key are read.
+// Properties    - TableProperties
+// Range deletion block - the range tombstones of the file (BlockBuilder format). The key is the start as an internal key with the
+//                 RangeDelete op type, the value is the exclusive end user key. Only written when the file has range tombstones, otherwise
+//                 the handle is empty.
+//
+// Footer:
+// | index handle (16) | properties handle (16) | range deletion handle (16) | magic (u64 LE) |
+//
+// BlockHandle:
+// | offset (u64 LE) | size (u64 LE) |
+
+use crate::error::{Error, Result};
+
+pub(crate) const TABLE_MAGIC: u64 = 0x7669_6374_6f72_7931;
+
+pub(crate) const BLOCK_HANDLE_SIZE: usize = 16;
+pub(crate) const FOOTER_SIZE: usize = 3 * BLOCK_HANDLE_SIZE + 8;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct BlockHandle {
+    pub(crate) offset: u64,
+    pub(crate) size: u64,
+}
+
+impl BlockHandle {
+    pub(crate) fn new(offset: u64, size: u64) -> Self {
+        Self { offset, size }
+    }
+
+    pub(crate) fn encode(&self) -> [u8; BLOCK_HANDLE_SIZE] {
+        let mut buf = [0u8; BLOCK_HANDLE_SIZE];
+        buf[..8].copy_from_slice(&self.offset.to_le_bytes());
+        buf[8..].copy_from_slice(&self.size.to_le_bytes());
+        buf
+    }
+
+    pub(crate) fn decode(src: &[u8]) -> Result<Self> {
+        if src.len() != BLOCK_HANDLE_SIZE {
+            return Err(Error::Corruption(format!(
+                "bad block handle length {}",
+                src.len()
+            )));
+        }
+        Ok(Self {
+            offset: u64::from_le_bytes(src[..8].try_into().unwrap()),
+            size: u64::from_le_bytes(src[8..].try_into().unwrap()),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Footer {
+    pub(crate) index: BlockHandle,
+    pub(crate) properties: BlockHandle,
+    // Size 0 when the file has no range tombstones
+    pub(crate) range_del: BlockHandle,
+}
+
+impl Footer {
+    pub(crate) fn encode(&self) -> [u8; FOOTER_SIZE] {
+        let mut buf = [0u8; FOOTER_SIZE];
+        buf[..BLOCK_HANDLE_SIZE].copy_from_slice(&self.index.encode());
+        buf[BLOCK_HANDLE_SIZE..2 * BLOCK_HANDLE_SIZE].copy_from_slice(&self.properties.encode());
+        buf[2 * BLOCK_HANDLE_SIZE..3 * BLOCK_HANDLE_SIZE].copy_from_slice(&self.range_del.encode());
+        buf[3 * BLOCK_HANDLE_SIZE..].copy_from_slice(&TABLE_MAGIC.to_le_bytes());
+        buf
+    }
+
+    /// Decodes the footer from the end of a file.
+    pub(crate) fn decode(file: &[u8]) -> Result<Self> {
+        if file.len() < FOOTER_SIZE {
+            return Err(Error::Corruption(format!(
+                "file of {} bytes is too small to be a table",
+                file.len()
+            )));
+        }
+
+        let footer = &file[file.len() - FOOTER_SIZE..];
+        let magic = u64::from_le_bytes(footer[3 * BLOCK_HANDLE_SIZE..].try_into().unwrap());
+        if magic != TABLE_MAGIC {
+            return Err(Error::Corruption(format!("bad table magic {magic:#x}")));
+        }
+
+        Ok(Self {
+            index: BlockHandle::decode(&footer[..BLOCK_HANDLE_SIZE])?,
+            properties: BlockHandle::decode(&footer[BLOCK_HANDLE_SIZE..2 * BLOCK_HANDLE_SIZE])?,
+            range_del: BlockHandle::decode(&footer[2 * BLOCK_HANDLE_SIZE..3 * BLOCK_HANDLE_SIZE])?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn footer_round_trip() {
+        let footer = Footer {
+            index: BlockHandle::new(4096, 120),
+            properties: BlockHandle::new(4216, 300),
+            range_del: BlockHandle::new(4516, 48),
+        };
+
+        let mut file = vec![0u8; 10];
+        file.extend_from_slice(&footer.encode());
+        assert_eq!(Footer::decode(&file).unwrap(), footer);
+
+        // Bad magic
+        let last = file.len() - 1;
+        file[last] ^= 0xFF;
+        assert!(matches!(Footer::decode(&file), Err(Error::Corruption(_))));
+        assert!(Footer::decode(&file[..FOOTER_SIZE - 1]).is_err());
+    }
+}
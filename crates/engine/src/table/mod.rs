@@ -0,0 +1,5 @@
+pub(crate) mod format;
+pub(crate) mod properties;
+pub(crate) mod sst_file_writer;
+pub(crate) mod table_builder;
+pub(crate) mod table_reader;
@@ -0,0 +1,183 @@
+//
+//
+//
+// TableProperties are the per-SST statistics which are collected while a table is built and written into the
+// properties meta block. They are loaded alongside FileMetaData when a table is opened so that compaction pickers
+// can make decisions (file age, entry counts etc) without having to read any data blocks.
+//
+// Encoding:
+//
+// Each property is encoded as a named entry so new properties can be added without breaking older files.
+// Unknown names are skipped on decode.
+//
+// | name_len (VarInt) | name ... | value (8 bytes LE) |
+//
+// The seq no to time samples of the file (see versioning/seqno_to_time.rs) are one property each, named by the seq no with the time as
+// the value: `victory.seqno.time.<seq no>`.
+
+use crate::utils::var_int::VarInt;
+use crate::versioning::seqno_to_time::SeqnoToTimeMapping;
+
+pub(crate) const PROP_NUM_ENTRIES: &[u8] = b"victory.num.entries";
+pub(crate) const PROP_NUM_DELETIONS: &[u8] = b"victory.num.deletions";
+pub(crate) const PROP_NUM_MERGE_OPERANDS: &[u8] = b"victory.num.merge.operands";
+pub(crate) const PROP_NUM_RANGE_DELETIONS: &[u8] = b"victory.num.range.deletions";
+pub(crate) const PROP_RAW_KEY_SIZE: &[u8] = b"victory.raw.key.size";
+pub(crate) const PROP_RAW_VALUE_SIZE: &[u8] = b"victory.raw.value.size";
+pub(crate) const PROP_DATA_SIZE: &[u8] = b"victory.data.size";
+pub(crate) const PROP_CREATION_TIME: &[u8] = b"victory.creation.time";
+pub(crate) const PROP_OLDEST_KEY_TIME: &[u8] = b"victory.oldest.key.time";
+pub(crate) const PROP_FILE_CREATION_TIME: &[u8] = b"victory.file.creation.time";
+pub(crate) const PROP_SEQNO_TIME_PREFIX: &[u8] = b"victory.seqno.time.";
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct TableProperties {
+    pub(crate) num_entries: u64,
+    pub(crate) num_deletions: u64,
+    pub(crate) num_merge_operands: u64,
+    // Range tombstones in the range deletion block - not counted in num_entries
+    pub(crate) num_range_deletions: u64,
+    pub(crate) raw_key_size: u64,
+    pub(crate) raw_value_size: u64,
+    pub(crate) data_size: u64,
+    // Unix time (secs) of the oldest ancestor of this file. For a flushed file this is the time the memtable was created,
+    // for a compaction output it is the minimum creation_time of all inputs. 0 means unknown.
+    pub(crate) creation_time: u64,
+    // Unix time (secs) of the oldest key in the file. 0 means unknown.
+    pub(crate) oldest_key_time: u64,
+    // Unix time (secs) at which the file itself was written. 0 means unknown.
+    pub(crate) file_creation_time: u64,
+    // Samples covering the seq nos of the file
+    pub(crate) seqno_to_time: SeqnoToTimeMapping,
+}
+
+impl TableProperties {
+    /// Best estimate of when the data in this file was first written. Falls back through the time properties until one is known.
+    /// Returns 0 if the file carries no time information at all.
+    pub(crate) fn oldest_ancestor_time(&self) -> u64 {
+        if self.creation_time != 0 {
+            return self.creation_time;
+        }
+        if self.oldest_key_time != 0 {
+            return self.oldest_key_time;
+        }
+        self.file_creation_time
+    }
+
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(256);
+
+        let props: [(&[u8], u64); 10] = [
+            (PROP_NUM_ENTRIES, self.num_entries),
+            (PROP_NUM_DELETIONS, self.num_deletions),
+            (PROP_NUM_MERGE_OPERANDS, self.num_merge_operands),
+            (PROP_NUM_RANGE_DELETIONS, self.num_range_deletions),
+            (PROP_RAW_KEY_SIZE, self.raw_key_size),
+            (PROP_RAW_VALUE_SIZE, self.raw_value_size),
+            (PROP_DATA_SIZE, self.data_size),
+            (PROP_CREATION_TIME, self.creation_time),
+            (PROP_OLDEST_KEY_TIME, self.oldest_key_time),
+            (PROP_FILE_CREATION_TIME, self.file_creation_time),
+        ];
+
+        let mut put = |name: &[u8], value: u64| {
+            buf.extend_from_slice(VarInt::new(name.len() as u32).as_slice());
+            buf.extend_from_slice(name);
+            buf.extend_from_slice(&value.to_le_bytes());
+        };
+        for (name, value) in props {
+            put(name, value);
+        }
+        for pair in self.seqno_to_time.pairs() {
+            let name = [PROP_SEQNO_TIME_PREFIX, pair.seq.to_string().as_bytes()].concat();
+            put(&name, pair.time);
+        }
+
+        buf
+    }
+
+    /// Decodes a properties block. Returns None if the block is truncated.
+    pub(crate) fn decode(mut src: &[u8]) -> Option<Self> {
+        let mut props = TableProperties::default();
+
+        while !src.is_empty() {
+            let (name_len, read) = VarInt::decode(src);
+            let name_len = name_len as usize;
+            src = &src[read..];
+
+            if src.len() < name_len + 8 {
+                return None;
+            }
+
+            let (name, rest) = src.split_at(name_len);
+            let value = u64::from_le_bytes(rest[..8].try_into().unwrap());
+            src = &rest[8..];
+
+            if let Some(seq) = name
+                .strip_prefix(PROP_SEQNO_TIME_PREFIX)
+                .and_then(|seq| std::str::from_utf8(seq).ok()?.parse().ok())
+            {
+                props.seqno_to_time.append(seq, value);
+                continue;
+            }
+            match name {
+                PROP_NUM_ENTRIES => props.num_entries = value,
+                PROP_NUM_DELETIONS => props.num_deletions = value,
+                PROP_NUM_MERGE_OPERANDS => props.num_merge_operands = value,
+                PROP_NUM_RANGE_DELETIONS => props.num_range_deletions = value,
+                PROP_RAW_KEY_SIZE => props.raw_key_size = value,
+                PROP_RAW_VALUE_SIZE => props.raw_value_size = value,
+                PROP_DATA_SIZE => props.data_size = value,
+                PROP_CREATION_TIME => props.creation_time = value,
+                PROP_OLDEST_KEY_TIME => props.oldest_key_time = value,
+                PROP_FILE_CREATION_TIME => props.file_creation_time = value,
+                // Written by a newer version - skip
+                _ => {}
+            }
+        }
+
+        Some(props)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn properties_round_trip() {
+        let mut props = TableProperties {
+            num_entries: 100,
+            num_deletions: 3,
+            num_range_deletions: 2,
+            data_size: 4096,
+            creation_time: 1_700_000_000,
+            file_creation_time: 1_700_000_100,
+            ..Default::default()
+        };
+        props.seqno_to_time.append(7, 1_700_000_000);
+        props.seqno_to_time.append(90, 1_700_000_060);
+
+        let decoded = TableProperties::decode(&props.encode()).unwrap();
+        assert_eq!(decoded, props);
+
+        // Truncated blocks are rejected
+        let encoded = props.encode();
+        assert!(TableProperties::decode(&encoded[..encoded.len() - 3]).is_none());
+    }
+
+    #[test]
+    fn oldest_ancestor_time_fallback() {
+        let mut props = TableProperties {
+            file_creation_time: 30,
+            ..Default::default()
+        };
+        assert_eq!(props.oldest_ancestor_time(), 30);
+
+        props.oldest_key_time = 20;
+        assert_eq!(props.oldest_ancestor_time(), 20);
+
+        props.creation_time = 10;
+        assert_eq!(props.oldest_ancestor_time(), 10);
+    }
+}
@@ -0,0 +1,117 @@
+//
+//
+//
+// SstFileWriter
+//
+// Builds a table file outside of any DB, to be added to one with DbImpl::ingest_external_file. Keys are added in strictly increasing
+// order of the column family's comparator, so a file holds at most one entry per user key. Every entry is written at seq no 0: ingestion
+// rewrites the file with a global seq no when its entries must be newer than something the DB already holds.
+//
+// NOTE: Crate-internal like DbImpl and the rest of the DB API - the engine crate exports no DB types yet. It becomes public together
+// with them.
+
+use std::cmp::Ordering;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::error::{Error, Result};
+use crate::key::comparator::{Comparator, InternalKeyComparator};
+use crate::key::internal_key::{OperationType, encode_trailer};
+use crate::options::ColumnFamilyOptions;
+use crate::table::table_builder::TableBuilder;
+
+pub(crate) struct SstFileWriter {
+    path: PathBuf,
+    user_comparator: Arc<dyn Comparator>,
+    builder: TableBuilder,
+    smallest_key: Option<Vec<u8>>,
+    // Last user key added
+    largest_key: Option<Vec<u8>>,
+}
+
+/// What SstFileWriter::finish wrote.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ExternalSstFileInfo {
+    pub(crate) file_path: PathBuf,
+    pub(crate) smallest_key: Vec<u8>,
+    pub(crate) largest_key: Vec<u8>,
+    pub(crate) num_entries: u64,
+    pub(crate) file_size: u64,
+}
+
+impl SstFileWriter {
+    /// A writer of the table file `path` for a column family with `options`. Nothing is written before finish.
+    pub(crate) fn create(path: &Path, options: &ColumnFamilyOptions) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            user_comparator: Arc::clone(&options.comparator),
+            builder: TableBuilder::default().with_comparator(
+                InternalKeyComparator::with_user_comparator(Arc::clone(&options.comparator)),
+            ),
+            smallest_key: None,
+            largest_key: None,
+        }
+    }
+
+    pub(crate) fn put<K, V>(&mut self, key: K, value: V) -> Result<()>
+    where
+        K: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+    {
+        self.add(key.as_ref(), value.as_ref(), OperationType::Put)
+    }
+
+    pub(crate) fn merge<K, V>(&mut self, key: K, value: V) -> Result<()>
+    where
+        K: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+    {
+        self.add(key.as_ref(), value.as_ref(), OperationType::Merge)
+    }
+
+    pub(crate) fn delete<K: AsRef<[u8]>>(&mut self, key: K) -> Result<()> {
+        self.add(key.as_ref(), &[], OperationType::Delete)
+    }
+
+    fn add(&mut self, key: &[u8], value: &[u8], op: OperationType) -> Result<()> {
+        if let Some(last) = &self.largest_key
+            && self.user_comparator.compare(key, last) != Ordering::Greater
+        {
+            return Err(Error::InvalidArgument(
+                "keys must be added in strictly increasing order".into(),
+            ));
+        }
+
+        self.builder
+            .add(&[key, &encode_trailer(0, op)].concat(), value);
+        if self.smallest_key.is_none() {
+            self.smallest_key = Some(key.to_vec());
+        }
+        self.largest_key = Some(key.to_vec());
+        Ok(())
+    }
+
+    /// Writes and syncs the file. A file without entries is not written.
+    pub(crate) fn finish(self) -> Result<ExternalSstFileInfo> {
+        let (Some(smallest_key), Some(largest_key)) = (self.smallest_key, self.largest_key) else {
+            return Err(Error::InvalidArgument(
+                "an external table file needs at least one entry".into(),
+            ));
+        };
+
+        let table = self.builder.finish();
+        let mut file = File::create(&self.path)?;
+        file.write_all(&table.data)?;
+        file.sync_all()?;
+
+        Ok(ExternalSstFileInfo {
+            file_path: self.path,
+            smallest_key,
+            largest_key,
+            num_entries: table.properties.num_entries,
+            file_size: table.data.len() as u64,
+        })
+    }
+}
@@ -0,0 +1,303 @@
+//
+//
+//
+// TableBuilder
+//
+// Writes sorted internal keys into the table format (see table/format.rs). Entries are added to a data block until it reaches
+// `block_size`, the block is then cut and an index entry is added for it. The index separator must be >= every key in the block and <
+// every key in the next one, so the index entry waits for the first key of the next block and the comparator shortens the last key of
+// the block to the shortest separator between them (find_shortest_separator). The last block gets a short successor of its last key.
+//
+// Properties (entry counts, raw sizes) and the key / seq no range of the table are collected as entries are added so the caller can
+// build the FileMetaData without reading the table back. Given the DB's seq no to time mapping, the table keeps the samples covering its
+// seq nos and estimates oldest_key_time from them. The caller stamps creation_time and file_creation_time, a creation_time after the
+// oldest key time is moved back to it.
+//
+// Range tombstones are collected separately (in any order) and written into the range deletion block on finish. They widen the key
+// range of the table: the smallest key is at most the start of the first tombstone and the largest key at least the end of the last one,
+// as `end@MAX_SEQUENCE_NUMBER` with the RangeDelete op type. That key sorts before every real entry of the end user key, so a file whose
+// tombstones were truncated at the next file's first key still doesn't overlap it.
+
+use std::cmp::Ordering;
+use std::sync::Arc;
+
+use crate::block::block_builder::{BlockBuilder, DEFAULT_RESTART_INTERVAL};
+use crate::key::comparator::{Comparator, InternalKeyComparator};
+use crate::key::internal_key::{
+    InternalKeyRef, MAX_SEQUENCE_NUMBER, OperationType, encode_trailer,
+};
+use crate::range::fragment::RangeTombstone;
+use crate::table::format::{BlockHandle, Footer};
+use crate::table::properties::TableProperties;
+use crate::versioning::file_version::{FileMetaData, FileNumber};
+use crate::versioning::seqno_to_time::SeqnoToTimeMapping;
+
+pub(crate) const DEFAULT_BLOCK_SIZE: usize = 4096;
+
+pub(crate) struct TableBuilder {
+    buf: Vec<u8>,
+    block_size: usize,
+    comparator: Arc<InternalKeyComparator>,
+    data_block: BlockBuilder,
+    // Every index entry is a restart point so the index can be binary searched on every separator
+    index_block: BlockBuilder,
+    // Handle of the last cut data block, its index entry is added once the separator is known
+    pending_index_entry: Option<BlockHandle>,
+    last_key: Vec<u8>,
+    properties: TableProperties,
+    range_tombstones: Vec<RangeTombstone>,
+    smallest: Vec<u8>,
+    smallest_seqno: u64,
+    largest_seqno: u64,
+    seqno_to_time: Option<Arc<SeqnoToTimeMapping>>,
+}
+
+/// A finished table and the metadata collected while it was built.
+pub(crate) struct BuiltTable {
+    pub(crate) data: Vec<u8>,
+    pub(crate) properties: TableProperties,
+    pub(crate) smallest: Vec<u8>,
+    pub(crate) largest: Vec<u8>,
+    pub(crate) smallest_seqno: u64,
+    pub(crate) largest_seqno: u64,
+}
+
+impl BuiltTable {
+    pub(crate) fn file_meta(&self, number: FileNumber) -> FileMetaData {
+        FileMetaData::new(
+            number,
+            self.data.len() as u64,
+            self.smallest.clone(),
+            self.largest.clone(),
+            self.smallest_seqno,
+            self.largest_seqno,
+            self.properties.clone(),
+        )
+    }
+}
+
+impl TableBuilder {
+    pub(crate) fn new(block_size: usize, restart_interval: usize) -> Self {
+        Self {
+            buf: Vec::new(),
+            block_size,
+            comparator: InternalKeyComparator::new(),
+            data_block: BlockBuilder::new(restart_interval),
+            index_block: BlockBuilder::new(1),
+            pending_index_entry: None,
+            last_key: Vec::new(),
+            properties: TableProperties::default(),
+            range_tombstones: Vec::new(),
+            smallest: Vec::new(),
+            smallest_seqno: u64::MAX,
+            largest_seqno: 0,
+            seqno_to_time: None,
+        }
+    }
+
+    /// Orders the keys and shortens the index separators with `comparator` instead of bytewise. Must be the order keys are added in.
+    pub(crate) fn with_comparator(mut self, comparator: Arc<InternalKeyComparator>) -> Self {
+        self.comparator = comparator;
+        self
+    }
+
+    /// Stores the samples of `mapping` covering the seq nos of the table in its properties.
+    pub(crate) fn with_seqno_to_time(mut self, mapping: Arc<SeqnoToTimeMapping>) -> Self {
+        self.seqno_to_time = Some(mapping);
+        self
+    }
+
+    /// Stamps the unix times (secs) the oldest data of the table was first written at and the table itself is written at.
+    pub(crate) fn with_creation_time(
+        mut self,
+        creation_time: u64,
+        file_creation_time: u64,
+    ) -> Self {
+        self.properties.creation_time = creation_time;
+        self.properties.file_creation_time = file_creation_time;
+        self
+    }
+
+    /// Keys must be added in internal key order.
+    pub(crate) fn add(&mut self, key: &[u8], value: &[u8]) {
+        let ikey = InternalKeyRef::from(key);
+
+        if self.properties.num_entries == 0 {
+            self.smallest.extend_from_slice(key);
+        }
+        self.smallest_seqno = self.smallest_seqno.min(ikey.seq_no);
+        self.largest_seqno = self.largest_seqno.max(ikey.seq_no);
+
+        self.properties.num_entries += 1;
+        self.properties.raw_key_size += key.len() as u64;
+        self.properties.raw_value_size += value.len() as u64;
+        match OperationType::from(ikey.op) {
+            OperationType::Delete | OperationType::SingleDelete => {
+                self.properties.num_deletions += 1
+            }
+            OperationType::Merge => self.properties.num_merge_operands += 1,
+            _ => {}
+        }
+
+        if let Some(handle) = self.pending_index_entry.take() {
+            self.comparator
+                .find_shortest_separator(&mut self.last_key, key);
+            self.index_block.add(&self.last_key, &handle.encode());
+        }
+        self.last_key.clear();
+        self.last_key.extend_from_slice(key);
+
+        self.data_block.add(key, value);
+        if self.data_block.current_size_estimate() >= self.block_size {
+            self.flush_data_block();
+        }
+    }
+
+    /// Adds a tombstone deleting [start, end) below `seq`. An empty range is ignored.
+    pub(crate) fn add_range_tombstone(&mut self, start: &[u8], end: &[u8], seq: u64) {
+        if self.comparator.user_comparator().compare(start, end) != Ordering::Less {
+            return;
+        }
+        self.smallest_seqno = self.smallest_seqno.min(seq);
+        self.largest_seqno = self.largest_seqno.max(seq);
+        self.properties.num_range_deletions += 1;
+        self.range_tombstones
+            .push(RangeTombstone::new(start, end, seq));
+    }
+
+    #[inline]
+    pub(crate) fn num_entries(&self) -> u64 {
+        self.properties.num_entries
+    }
+
+    /// Estimated size of the table if it were finished now.
+    pub(crate) fn file_size_estimate(&self) -> usize {
+        self.buf.len() + self.data_block.current_size_estimate()
+    }
+
+    fn flush_data_block(&mut self) {
+        if self.data_block.is_empty() {
+            return;
+        }
+
+        let block = self.data_block.finish();
+        let handle = BlockHandle::new(self.buf.len() as u64, block.len() as u64);
+        self.buf.extend_from_slice(block);
+        self.data_block.reset();
+        self.pending_index_entry = Some(handle);
+    }
+
+    // Writes the range deletion block and widens [smallest, largest] to cover the tombstones
+    fn write_range_tombstones(&mut self, largest: &mut Vec<u8>) -> BlockHandle {
+        if self.range_tombstones.is_empty() {
+            return BlockHandle::default();
+        }
+
+        let cmp = Arc::clone(&self.comparator);
+        let start_key = |t: &RangeTombstone| {
+            [
+                t.start.as_slice(),
+                &encode_trailer(t.seq, OperationType::RangeDelete),
+            ]
+            .concat()
+        };
+
+        self.range_tombstones
+            .sort_by(|a, b| cmp.compare(&start_key(a), &start_key(b)));
+        let mut block = BlockBuilder::new(DEFAULT_RESTART_INTERVAL);
+        for t in &self.range_tombstones {
+            block.add(&start_key(t), &t.end);
+        }
+
+        let smallest = start_key(&self.range_tombstones[0]);
+        if self.smallest.is_empty() || cmp.compare(&smallest, &self.smallest) == Ordering::Less {
+            self.smallest = smallest;
+        }
+        let end = self
+            .range_tombstones
+            .iter()
+            .map(|t| &t.end)
+            .max_by(|a, b| cmp.user_comparator().compare(a, b))
+            .unwrap();
+        let end_key = [
+            end.as_slice(),
+            &encode_trailer(MAX_SEQUENCE_NUMBER, OperationType::RangeDelete),
+        ]
+        .concat();
+        if largest.is_empty() || cmp.compare(&end_key, largest) == Ordering::Greater {
+            *largest = end_key;
+        }
+
+        let block = block.finish();
+        let handle = BlockHandle::new(self.buf.len() as u64, block.len() as u64);
+        self.buf.extend_from_slice(block);
+        handle
+    }
+
+    pub(crate) fn finish(mut self) -> BuiltTable {
+        self.flush_data_block();
+        self.properties.data_size = self.buf.len() as u64;
+
+        let mut largest = self.last_key.clone();
+        if let Some(handle) = self.pending_index_entry.take() {
+            let mut successor = self.last_key.clone();
+            self.comparator.find_short_successor(&mut successor);
+            self.index_block.add(&successor, &handle.encode());
+        }
+        let smallest_seqno =
+            if self.properties.num_entries == 0 && self.properties.num_range_deletions == 0 {
+                0
+            } else {
+                self.smallest_seqno
+            };
+        // An empty table has no seq nos to cover
+        if let Some(mapping) = self.seqno_to_time.take()
+            && self.largest_seqno > 0
+        {
+            self.properties.seqno_to_time = mapping.for_range(smallest_seqno, self.largest_seqno);
+            if self.properties.oldest_key_time == 0 {
+                self.properties.oldest_key_time =
+                    mapping.proximal_time_before(smallest_seqno).unwrap_or(0);
+            }
+        }
+        // The data of the table is no younger than its oldest key
+        if self.properties.creation_time != 0 && self.properties.oldest_key_time != 0 {
+            self.properties.creation_time = self
+                .properties
+                .creation_time
+                .min(self.properties.oldest_key_time);
+        }
+
+        let index = self.index_block.finish();
+        let index_handle = BlockHandle::new(self.buf.len() as u64, index.len() as u64);
+        self.buf.extend_from_slice(index);
+
+        let properties = self.properties.encode();
+        let properties_handle = BlockHandle::new(self.buf.len() as u64, properties.len() as u64);
+        self.buf.extend_from_slice(&properties);
+
+        let range_del_handle = self.write_range_tombstones(&mut largest);
+
+        let footer = Footer {
+            index: index_handle,
+            properties: properties_handle,
+            range_del: range_del_handle,
+        };
+        self.buf.extend_from_slice(&footer.encode());
+
+        BuiltTable {
+            data: self.buf,
+            properties: self.properties,
+            smallest: self.smallest,
+            largest,
+            smallest_seqno,
+            largest_seqno: self.largest_seqno,
+        }
+    }
+}
+
+impl Default for TableBuilder {
+    fn default() -> Self {
+        Self::new(DEFAULT_BLOCK_SIZE, DEFAULT_RESTART_INTERVAL)
+    }
+}
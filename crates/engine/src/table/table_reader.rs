@@ -0,0 +1,621 @@
+//
+//
+//
+// Table + TableIter
+//
+// A Table is an opened SST file: the footer, index block and properties are read and decoded on open, data blocks are read from the file
+// by offset on demand - the table is never held in memory as a whole. There is no block cache yet so every data block an iterator moves
+// into is read from the file again.
+//
+// The range deletion block is read and fragmented once on open. Every lookup or iterator over the table then binary searches the
+// fragments for the newest tombstone covering a key (see range/fragment.rs).
+//
+// TableIter is a two level iterator - an iterator over the index block picks the data block and a BlockIter walks it. With iterator
+// bounds the index separators are used to stop before reading a data block which is entirely outside the bounds:
+//
+//   forward - the separator of the current block is >= every key in the next block. Once it is at or past the upper bound the next block
+//             can't hold a key inside the bounds.
+//   reverse - the separator of the previous block is >= every key in it. If it is before the lower bound so is the whole block.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::block::data_block::{Block, BlockIter};
+use crate::error::{Error, Result};
+use crate::iterator::bounds::{IterBounds, before_lower, past_upper};
+use crate::iterator::internal_iterator::InternalIterator;
+use crate::key::comparator::{Comparator, DefaultComparator, InternalKeyComparator};
+use crate::key::internal_key::InternalKeyRef;
+use crate::range::fragment::{FragmentedTombstones, RangeTombstone};
+use crate::table::format::{BlockHandle, FOOTER_SIZE, Footer};
+use crate::table::properties::TableProperties;
+use crate::utils::random_access_file::RandomAccessFile;
+
+pub(crate) struct Table {
+    file: RandomAccessFile,
+    index: Arc<Block>,
+    properties: TableProperties,
+    range_tombstones: Arc<FragmentedTombstones>,
+    // Number of data blocks read from the file
+    data_block_reads: AtomicU64,
+}
+
+impl Table {
+    pub(crate) fn open(file: impl Into<RandomAccessFile>) -> Result<Arc<Self>> {
+        let file = file.into();
+        let size = file.size();
+        if size < FOOTER_SIZE as u64 {
+            return Err(Error::Corruption(format!(
+                "file of {size} bytes is too small to be a table"
+            )));
+        }
+        let footer = Footer::decode(&file.read(size - FOOTER_SIZE as u64, FOOTER_SIZE)?)?;
+
+        let index = Arc::new(Block::new(read_contents(&file, &footer.index)?)?);
+        let properties = TableProperties::decode(&read_contents(&file, &footer.properties)?)
+            .ok_or_else(|| Error::Corruption("truncated table properties block".into()))?;
+        let range_tombstones = Arc::new(Self::read_range_tombstones(&file, &footer.range_del)?);
+
+        Ok(Arc::new(Self {
+            file,
+            index,
+            properties,
+            range_tombstones,
+            data_block_reads: AtomicU64::new(0),
+        }))
+    }
+
+    #[inline]
+    pub(crate) fn properties(&self) -> &TableProperties {
+        &self.properties
+    }
+
+    // NOTE: Fragmented bytewise, the order InternalKeyComparator gives user keys, until tables know their user comparator
+    fn read_range_tombstones(
+        file: &RandomAccessFile,
+        handle: &BlockHandle,
+    ) -> Result<FragmentedTombstones> {
+        if handle.size == 0 {
+            return Ok(FragmentedTombstones::default());
+        }
+
+        let block = Arc::new(Block::new(read_contents(file, handle)?)?);
+        let mut iter = block.iter(InternalKeyComparator::new());
+        let mut tombstones = Vec::new();
+        iter.seek_to_first();
+        while iter.valid() {
+            let start = InternalKeyRef::from(iter.key());
+            tombstones.push(RangeTombstone::new(
+                start.user_key,
+                iter.value(),
+                start.seq_no,
+            ));
+            iter.next();
+        }
+        if let Some(e) = iter.status() {
+            return Err(e.clone());
+        }
+
+        Ok(FragmentedTombstones::new(
+            tombstones,
+            DefaultComparator::new().as_ref(),
+        ))
+    }
+
+    /// The range tombstones of the table, fragmented.
+    #[inline]
+    pub(crate) fn range_tombstones(&self) -> &Arc<FragmentedTombstones> {
+        &self.range_tombstones
+    }
+
+    #[inline]
+    pub(crate) fn data_block_reads(&self) -> u64 {
+        self.data_block_reads.load(Ordering::Relaxed)
+    }
+
+    fn read_block(&self, handle: &BlockHandle) -> Result<Arc<Block>> {
+        self.data_block_reads.fetch_add(1, Ordering::Relaxed);
+        Ok(Arc::new(Block::new(read_contents(&self.file, handle)?)?))
+    }
+
+    /// `comparator` is the internal key comparator. Bounds let the iterator skip data blocks outside them.
+    pub(crate) fn iter(
+        self: &Arc<Self>,
+        comparator: Arc<dyn Comparator>,
+        bounds: Option<Arc<IterBounds>>,
+    ) -> TableIter {
+        TableIter {
+            table: Arc::clone(self),
+            index_iter: self.index.iter(Arc::clone(&comparator)),
+            comparator,
+            bounds,
+            data_iter: None,
+            data_handle: BlockHandle::default(),
+            error: None,
+        }
+    }
+}
+
+// The bytes of the block the handle points at
+fn read_contents(file: &RandomAccessFile, handle: &BlockHandle) -> Result<Vec<u8>> {
+    let size = usize::try_from(handle.size).map_err(|_| {
+        Error::Corruption(format!(
+            "block handle {}+{} is too large",
+            handle.offset, handle.size
+        ))
+    })?;
+    file.read(handle.offset, size)
+}
+
+pub(crate) struct TableIter {
+    table: Arc<Table>,
+    comparator: Arc<dyn Comparator>,
+    bounds: Option<Arc<IterBounds>>,
+    index_iter: BlockIter,
+    // Iterator over the data block the index iterator is on
+    data_iter: Option<BlockIter>,
+    data_handle: BlockHandle,
+    error: Option<Error>,
+}
+
+impl TableIter {
+    // Opens the data block the index iterator is on. Keeps the current block if it is the same one
+    fn init_data_block(&mut self) {
+        if !self.index_iter.valid() {
+            self.data_iter = None;
+            return;
+        }
+
+        let handle = match BlockHandle::decode(self.index_iter.value()) {
+            Ok(handle) => handle,
+            Err(e) => {
+                self.error = Some(e);
+                self.data_iter = None;
+                return;
+            }
+        };
+
+        if self.data_iter.is_some() && handle == self.data_handle {
+            return;
+        }
+
+        match self.table.read_block(&handle) {
+            Ok(block) => {
+                self.data_iter = Some(block.iter(Arc::clone(&self.comparator)));
+                self.data_handle = handle;
+            }
+            Err(e) => {
+                self.error = Some(e);
+                self.data_iter = None;
+            }
+        }
+    }
+
+    // Moves forward over exhausted data blocks until positioned on an entry, the table (or the bounds) end or a block fails
+    fn skip_empty_blocks_forward(&mut self) {
+        loop {
+            let Some(iter) = self.data_iter.as_ref() else {
+                return;
+            };
+            if iter.valid() || iter.status().is_some() {
+                return;
+            }
+            if past_upper(&self.bounds, self.index_iter.key()) {
+                self.data_iter = None;
+                return;
+            }
+            self.index_iter.next();
+            self.init_data_block();
+            if let Some(iter) = self.data_iter.as_mut() {
+                iter.seek_to_first();
+            }
+        }
+    }
+
+    // Moves backward over exhausted data blocks until positioned on an entry, the table (or the bounds) end or a block fails
+    fn skip_empty_blocks_backward(&mut self) {
+        loop {
+            let Some(iter) = self.data_iter.as_ref() else {
+                return;
+            };
+            if iter.valid() || iter.status().is_some() {
+                return;
+            }
+            self.index_iter.prev();
+            if !self.index_iter.valid() || before_lower(&self.bounds, self.index_iter.key()) {
+                self.data_iter = None;
+                return;
+            }
+            self.init_data_block();
+            if let Some(iter) = self.data_iter.as_mut() {
+                iter.seek_to_last();
+            }
+        }
+    }
+}
+
+impl InternalIterator for TableIter {
+    fn seek_to_first(&mut self) {
+        self.error = None;
+        self.index_iter.seek_to_first();
+        self.init_data_block();
+        if let Some(iter) = self.data_iter.as_mut() {
+            iter.seek_to_first();
+        }
+        self.skip_empty_blocks_forward();
+    }
+
+    fn seek_to_last(&mut self) {
+        self.error = None;
+        self.index_iter.seek_to_last();
+        if self.index_iter.valid() && before_lower(&self.bounds, self.index_iter.key()) {
+            self.data_iter = None;
+            return;
+        }
+        self.init_data_block();
+        if let Some(iter) = self.data_iter.as_mut() {
+            iter.seek_to_last();
+        }
+        self.skip_empty_blocks_backward();
+    }
+
+    fn seek(&mut self, key: &[u8]) {
+        self.error = None;
+        self.index_iter.seek(key);
+        self.init_data_block();
+        if let Some(iter) = self.data_iter.as_mut() {
+            iter.seek(key);
+        }
+        self.skip_empty_blocks_forward();
+    }
+
+    fn seek_for_prev(&mut self, key: &[u8]) {
+        self.error = None;
+
+        // The first block whose separator is >= key is the last one which can hold a key <= key
+        self.index_iter.seek(key);
+        if !self.index_iter.valid() {
+            if self.index_iter.status().is_none() {
+                self.index_iter.seek_to_last();
+            }
+        } else if before_lower(&self.bounds, self.index_iter.key()) {
+            self.data_iter = None;
+            return;
+        }
+
+        self.init_data_block();
+        if let Some(iter) = self.data_iter.as_mut() {
+            iter.seek_for_prev(key);
+        }
+        self.skip_empty_blocks_backward();
+    }
+
+    #[inline]
+    fn valid(&self) -> bool {
+        self.data_iter.as_ref().is_some_and(|iter| iter.valid())
+    }
+
+    fn next(&mut self) {
+        debug_assert!(self.valid());
+        if let Some(iter) = self.data_iter.as_mut() {
+            iter.next();
+        }
+        self.skip_empty_blocks_forward();
+    }
+
+    fn prev(&mut self) {
+        debug_assert!(self.valid());
+        if let Some(iter) = self.data_iter.as_mut() {
+            iter.prev();
+        }
+        self.skip_empty_blocks_backward();
+    }
+
+    #[inline]
+    fn key(&self) -> &[u8] {
+        debug_assert!(self.valid());
+        self.data_iter.as_ref().unwrap().key()
+    }
+
+    #[inline]
+    fn value(&self) -> &[u8] {
+        debug_assert!(self.valid());
+        self.data_iter.as_ref().unwrap().value()
+    }
+
+    fn status(&self) -> Option<&Error> {
+        self.error
+            .as_ref()
+            .or_else(|| self.index_iter.status())
+            .or_else(|| self.data_iter.as_ref().and_then(|iter| iter.status()))
+    }
+
+    fn range_tombstones(&self, out: &mut Vec<Arc<FragmentedTombstones>>) -> Result<()> {
+        if !self.table.range_tombstones.is_empty() {
+            out.push(Arc::clone(&self.table.range_tombstones));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key::comparator::{DefaultComparator, InternalKeyComparator};
+    use crate::key::internal_key::{InternalKeyRef, OperationType, encode_trailer};
+    use crate::table::table_builder::TableBuilder;
+    use crate::tests::temp_dir::TempDir;
+
+    fn ikey(user_key: &[u8], seq: u64) -> Vec<u8> {
+        [user_key, &encode_trailer(seq, OperationType::Put)].concat()
+    }
+
+    fn build(n: usize, block_size: usize) -> Arc<Table> {
+        let mut builder = TableBuilder::new(block_size, 4);
+        for i in 0..n {
+            builder.add(
+                &ikey(format!("key-{:04}", i * 2).as_bytes(), 1),
+                format!("value-{i}").as_bytes(),
+            );
+        }
+        Table::open(builder.finish().data).unwrap()
+    }
+
+    fn user_key_of(iter: &TableIter) -> String {
+        String::from_utf8(InternalKeyRef::from(iter.key()).user_key.to_vec()).unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut builder = TableBuilder::new(128, 4);
+        builder.add(&ikey(b"a", 5), b"a5");
+        builder.add(
+            &[b"b".as_slice(), &encode_trailer(4, OperationType::Delete)].concat(),
+            b"",
+        );
+        builder.add(
+            &[b"c".as_slice(), &encode_trailer(3, OperationType::Merge)].concat(),
+            b"+1",
+        );
+        let built = builder.finish();
+
+        assert_eq!(built.smallest, ikey(b"a", 5));
+        assert_eq!(
+            InternalKeyRef::from(built.largest.as_slice()).user_key,
+            b"c"
+        );
+        assert_eq!((built.smallest_seqno, built.largest_seqno), (3, 5));
+
+        let table = Table::open(built.data).unwrap();
+        assert_eq!(table.properties(), &built.properties);
+        assert_eq!(table.properties().num_entries, 3);
+        assert_eq!(table.properties().num_deletions, 1);
+        assert_eq!(table.properties().num_merge_operands, 1);
+    }
+
+    #[test]
+    fn range_deletion_block() {
+        let mut builder = TableBuilder::new(128, 4);
+        builder.add(&ikey(b"d", 2), b"d2");
+        // Added out of order, the empty one is dropped
+        builder.add_range_tombstone(b"m", b"x", 7);
+        builder.add_range_tombstone(b"b", b"f", 4);
+        builder.add_range_tombstone(b"q", b"q", 9);
+        let built = builder.finish();
+
+        assert_eq!(built.properties.num_range_deletions, 2);
+        assert_eq!(built.properties.num_entries, 1);
+        assert_eq!(
+            built.smallest,
+            [
+                b"b".as_slice(),
+                &encode_trailer(4, OperationType::RangeDelete)
+            ]
+            .concat()
+        );
+        assert_eq!(
+            InternalKeyRef::from(built.largest.as_slice()).user_key,
+            b"x"
+        );
+        assert_eq!((built.smallest_seqno, built.largest_seqno), (2, 7));
+
+        let table = Table::open(built.data).unwrap();
+        let cmp = DefaultComparator::new();
+        let tombstones = table.range_tombstones();
+        assert_eq!(tombstones.max_covering_seq(cmp.as_ref(), b"d", 10), 4);
+        assert_eq!(tombstones.max_covering_seq(cmp.as_ref(), b"w", 10), 7);
+        assert_eq!(tombstones.max_covering_seq(cmp.as_ref(), b"x", 10), 0);
+
+        // Tombstones never show up as entries
+        let mut iter = table.iter(InternalKeyComparator::new(), None);
+        iter.seek_to_first();
+        assert_eq!(user_key_of(&iter), "d");
+        iter.next();
+        assert!(!iter.valid());
+
+        let mut out = Vec::new();
+        iter.range_tombstones(&mut out).unwrap();
+        assert_eq!(out.len(), 1);
+        assert!(build(10, 128).range_tombstones().is_empty());
+    }
+
+    #[test]
+    fn iterate_across_blocks() {
+        let table = build(200, 256);
+        let mut iter = table.iter(InternalKeyComparator::new(), None);
+
+        iter.seek_to_first();
+        for i in 0..200 {
+            assert!(iter.valid());
+            assert_eq!(user_key_of(&iter), format!("key-{:04}", i * 2));
+            assert_eq!(iter.value(), format!("value-{i}").as_bytes());
+            iter.next();
+        }
+        assert!(!iter.valid());
+        assert!(iter.status().is_none());
+        // More than one data block, each read once
+        let reads = table.data_block_reads();
+        assert!(reads > 5);
+
+        iter.seek_to_last();
+        for i in (0..200).rev() {
+            assert_eq!(user_key_of(&iter), format!("key-{:04}", i * 2));
+            iter.prev();
+        }
+        assert!(!iter.valid());
+
+        // Between keys
+        iter.seek(&ikey(b"key-0101", 1));
+        assert_eq!(user_key_of(&iter), "key-0102");
+        iter.seek_for_prev(&ikey(b"key-0101", 1));
+        assert_eq!(user_key_of(&iter), "key-0100");
+
+        iter.seek(&ikey(b"z", 1));
+        assert!(!iter.valid());
+        iter.seek_for_prev(&ikey(b"z", 1));
+        assert_eq!(user_key_of(&iter), "key-0398");
+        iter.seek_for_prev(&ikey(b"a", 1));
+        assert!(!iter.valid());
+    }
+
+    #[test]
+    fn data_blocks_are_read_from_the_file() {
+        let mut builder = TableBuilder::new(256, 4);
+        for i in 0..200 {
+            builder.add(&ikey(format!("key-{i:04}").as_bytes(), 1), b"value");
+        }
+        let dir = TempDir::new("table-reader");
+        let path = dir.path().join("1.sst");
+        std::fs::write(&path, builder.finish().data).unwrap();
+
+        let table = Table::open(RandomAccessFile::open(&path).unwrap()).unwrap();
+        let mut iter = table.iter(InternalKeyComparator::new(), None);
+        iter.seek(&ikey(b"key-0150", 1));
+        assert_eq!(user_key_of(&iter), "key-0150");
+        // Only the block holding the key was read
+        assert_eq!(table.data_block_reads(), 1);
+
+        iter.seek_to_first();
+        let mut count = 0;
+        while iter.valid() {
+            count += 1;
+            iter.next();
+        }
+        assert_eq!(count, 200);
+        assert!(iter.status().is_none());
+    }
+
+    #[test]
+    fn index_separators_are_shortened() {
+        // One entry per data block: "a-long-key", "c-long-key", ...
+        let user_keys: Vec<_> = (b'a'..=b'y')
+            .step_by(2)
+            .map(|c| [&[c], b"-long-key".as_slice()].concat())
+            .collect();
+        let mut builder = TableBuilder::new(1, 4);
+        for key in &user_keys {
+            builder.add(&ikey(key, 1), key);
+        }
+        let built = builder.finish();
+        assert_eq!(
+            InternalKeyRef::from(built.largest.as_slice()).user_key,
+            b"y-long-key"
+        );
+
+        let table = Table::open(built.data).unwrap();
+        let mut index = table.index.iter(InternalKeyComparator::new());
+        index.seek_to_first();
+        let mut separators = Vec::new();
+        while index.valid() {
+            separators.push(InternalKeyRef::from(index.key()).user_key.to_vec());
+            index.next();
+        }
+        let expected: Vec<_> = (b'b'..=b'z').step_by(2).map(|c| vec![c]).collect();
+        assert_eq!(separators, expected);
+
+        let mut iter = table.iter(InternalKeyComparator::new(), None);
+        for key in &user_keys {
+            iter.seek(&ikey(key, 1));
+            assert_eq!(iter.value(), key.as_slice());
+        }
+        iter.seek(&ikey(b"b", 1));
+        assert_eq!(user_key_of(&iter), "c-long-key");
+        iter.seek_for_prev(&ikey(b"b", 1));
+        assert_eq!(user_key_of(&iter), "a-long-key");
+        iter.seek(&ikey(b"z", 1));
+        assert!(!iter.valid());
+    }
+
+    #[test]
+    fn bounds_skip_data_blocks() {
+        let table = build(200, 256);
+        let bounds = Arc::new(IterBounds::new(
+            DefaultComparator::new(),
+            Some(b"key-0100".to_vec()),
+            Some(b"key-0120".to_vec()),
+        ));
+        let mut iter = table.iter(InternalKeyComparator::new(), Some(bounds));
+
+        // Forward - stops after the block holding the upper bound
+        iter.seek(&ikey(b"key-0100", 1));
+        while iter.valid() {
+            iter.next();
+        }
+        assert!(iter.status().is_none());
+        assert!(table.data_block_reads() <= 2);
+
+        // Reverse - stops after the block holding the lower bound
+        let before = table.data_block_reads();
+        iter.seek_for_prev(&ikey(b"key-0119", 1));
+        while iter.valid() {
+            iter.prev();
+        }
+        assert!(table.data_block_reads() - before <= 2);
+
+        // Table entirely past the bounds in reverse
+        let bounds = Arc::new(IterBounds::new(
+            DefaultComparator::new(),
+            Some(b"z".to_vec()),
+            None,
+        ));
+        let mut iter = table.iter(InternalKeyComparator::new(), Some(bounds));
+        let before = table.data_block_reads();
+        iter.seek_to_last();
+        assert!(!iter.valid());
+        assert_eq!(table.data_block_reads(), before);
+    }
+
+    #[test]
+    fn handle_outside_file() {
+        let file = RandomAccessFile::from(vec![0u8; 32]);
+        assert_eq!(
+            read_contents(&file, &BlockHandle::new(8, 8)).unwrap().len(),
+            8
+        );
+        assert!(read_contents(&file, &BlockHandle::new(30, 8)).is_err());
+        assert!(read_contents(&file, &BlockHandle::new(u64::MAX, 8)).is_err());
+    }
+
+    #[test]
+    fn corruption() {
+        let mut file = TableBuilder::default().finish().data;
+        assert!(Table::open(file[..10].to_vec()).is_err());
+
+        // Point the index handle past the end of the file
+        let len = file.len();
+        let index_at = len - crate::table::format::FOOTER_SIZE;
+        file[index_at..index_at + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(matches!(Table::open(file), Err(Error::Corruption(_))));
+
+        // A data block which fails to decode surfaces through status()
+        let mut builder = TableBuilder::new(4096, 4);
+        builder.add(&ikey(b"a", 1), b"value");
+        let mut file = builder.finish().data;
+        file[2] = 100;
+        let table = Table::open(file).unwrap();
+        let mut iter = table.iter(InternalKeyComparator::new(), None);
+        iter.seek_to_first();
+        assert!(!iter.valid());
+        assert!(matches!(iter.status(), Some(Error::Corruption(_))));
+    }
+}
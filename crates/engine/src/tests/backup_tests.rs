@@ -0,0 +1,260 @@
+#[cfg(test)]
+mod tests {
+
+    use std::fs;
+
+    use crate::backup::BackupEngine;
+    use crate::db::db_impl::DbImpl;
+    use crate::error::Error;
+    use crate::options::{ColumnFamilyOptions, DbOptions, IngestExternalFileOptions};
+    use crate::table::sst_file_writer::SstFileWriter;
+    use crate::tests::helpers::{create_options, put, read_cf};
+    use crate::tests::temp_dir::TempDir;
+    use crate::utils::clock::ManualClock;
+
+    // Adds a table file holding the one key to the DB
+    fn ingest(db: &DbImpl, scratch: &TempDir, key: &str, value: &str) {
+        let path = scratch.path().join(format!("{key}.sst"));
+        let mut writer = SstFileWriter::create(&path, &ColumnFamilyOptions::default());
+        writer.put(key, value).unwrap();
+        writer.finish().unwrap();
+        db.ingest_external_file(
+            &db.default_column_family(),
+            &[path],
+            &IngestExternalFileOptions::default(),
+        )
+        .unwrap();
+    }
+
+    fn shared_files(backup_dir: &TempDir) -> usize {
+        fs::read_dir(backup_dir.path().join("shared_checksum"))
+            .unwrap()
+            .count()
+    }
+
+    #[test]
+    fn backups_restore_to_their_point_in_time() {
+        let dir = TempDir::new("backup-db");
+        let backup_dir = TempDir::new("backup-dir");
+        let restore_dir = TempDir::new("backup-restore");
+
+        let db = DbImpl::open(dir.path(), &create_options(), Vec::new()).unwrap();
+        let mut engine = BackupEngine::open(backup_dir.path()).unwrap();
+
+        put(&db, "a", "1");
+        let first = engine.create_new_backup(&db).unwrap();
+        put(&db, "a", "2");
+        put(&db, "b", "2");
+        let second = engine.create_new_backup(&db).unwrap();
+        assert_eq!((first, second), (1, 2));
+
+        let info = engine.backup_info();
+        assert_eq!(info.len(), 2);
+        assert!(info[0].sequence < info[1].sequence);
+        assert_eq!(info[1].sequence, db.last_sequence());
+        engine.verify_backup(first).unwrap();
+        engine.verify_backup(second).unwrap();
+
+        for (id, a, b) in [(first, "1", None), (second, "2", Some("2"))] {
+            let target = restore_dir.path().join(id.to_string());
+            engine.restore_db_from_backup(id, &target).unwrap();
+            let restored = DbImpl::open(&target, &DbOptions::default(), Vec::new()).unwrap();
+            let cf = restored.default_column_family();
+            assert_eq!(read_cf(&restored, &cf, "a").as_deref(), Some(a));
+            assert_eq!(read_cf(&restored, &cf, "b").as_deref(), b);
+            drop(restored);
+
+            // A DB is never restored over
+            assert!(matches!(
+                engine.restore_db_from_backup(id, &target),
+                Err(Error::InvalidArgument(_))
+            ));
+        }
+        assert!(matches!(
+            engine.verify_backup(3),
+            Err(Error::InvalidArgument(_))
+        ));
+    }
+
+    #[test]
+    fn backups_are_timestamped_by_the_db_clock() {
+        let dir = TempDir::new("backup-clock-db");
+        let backup_dir = TempDir::new("backup-clock-dir");
+
+        let clock = ManualClock::new(1_000);
+        let options = DbOptions {
+            clock: clock.clone(),
+            ..create_options()
+        };
+        let db = DbImpl::open(dir.path(), &options, Vec::new()).unwrap();
+        let mut engine = BackupEngine::open(backup_dir.path()).unwrap();
+
+        put(&db, "a", "1");
+        engine.create_new_backup(&db).unwrap();
+        clock.set(2_500);
+        engine.create_new_backup(&db).unwrap();
+
+        // The timestamps are in the metadata files
+        drop(engine);
+        let engine = BackupEngine::open(backup_dir.path()).unwrap();
+        let timestamps: Vec<_> = engine.backup_info().iter().map(|b| b.timestamp).collect();
+        assert_eq!(timestamps, vec![1_000, 2_500]);
+    }
+
+    #[test]
+    fn backups_keep_range_deletions() {
+        let dir = TempDir::new("backup-range-del-db");
+        let backup_dir = TempDir::new("backup-range-del-dir");
+        let restore_dir = TempDir::new("backup-range-del-restore");
+
+        let db = DbImpl::open(dir.path(), &create_options(), Vec::new()).unwrap();
+        let cf = db.default_column_family();
+        let mut engine = BackupEngine::open(backup_dir.path()).unwrap();
+        for key in ["a", "b", "c"] {
+            put(&db, key, "1");
+        }
+        db.flush(&cf).unwrap();
+        db.delete_range(&cf, b"a", b"c").unwrap();
+        let id = engine.create_new_backup(&db).unwrap();
+
+        let target = restore_dir.path().join("restored");
+        engine.restore_db_from_backup(id, &target).unwrap();
+        let restored = DbImpl::open(&target, &DbOptions::default(), Vec::new()).unwrap();
+        let cf = restored.default_column_family();
+        assert_eq!(read_cf(&restored, &cf, "a"), None);
+        assert_eq!(read_cf(&restored, &cf, "b"), None);
+        assert_eq!(read_cf(&restored, &cf, "c").as_deref(), Some("1"));
+    }
+
+    #[test]
+    fn table_files_are_shared_between_backups() {
+        let dir = TempDir::new("backup-shared-db");
+        let other_dir = TempDir::new("backup-shared-other-db");
+        let scratch = TempDir::new("backup-shared-scratch");
+        let backup_dir = TempDir::new("backup-shared");
+
+        let db = DbImpl::open(dir.path(), &create_options(), Vec::new()).unwrap();
+        let mut engine = BackupEngine::open(backup_dir.path()).unwrap();
+        ingest(&db, &scratch, "a", "1");
+
+        engine.create_new_backup(&db).unwrap();
+        ingest(&db, &scratch, "b", "1");
+        engine.create_new_backup(&db).unwrap();
+        assert_eq!(shared_files(&backup_dir), 2);
+
+        // The first table file of another DB has the same number, other contents - a different file
+        let other = DbImpl::open(other_dir.path(), &create_options(), Vec::new()).unwrap();
+        ingest(&other, &scratch, "c", "1");
+        let third = engine.create_new_backup(&other).unwrap();
+        assert_eq!(
+            db.default_column_family()
+                .data()
+                .current_version()
+                .level_files(6)[0]
+                .number,
+            other
+                .default_column_family()
+                .data()
+                .current_version()
+                .level_files(6)[0]
+                .number
+        );
+        assert_eq!(shared_files(&backup_dir), 3);
+
+        // Reopening finds the backups again
+        drop(engine);
+        let mut engine = BackupEngine::open(backup_dir.path()).unwrap();
+        assert_eq!(engine.backup_info().len(), 3);
+
+        engine.purge_old_backups(1).unwrap();
+        assert_eq!(
+            engine
+                .backup_info()
+                .iter()
+                .map(|info| info.id)
+                .collect::<Vec<_>>(),
+            vec![third]
+        );
+        // The files of the first DB were only held by the purged backups
+        assert_eq!(shared_files(&backup_dir), 1);
+        assert_eq!(
+            fs::read_dir(backup_dir.path().join("private"))
+                .unwrap()
+                .count(),
+            1
+        );
+
+        let target = TempDir::new("backup-shared-restore");
+        let restore_path = target.path().join("db");
+        engine.restore_db_from_backup(third, &restore_path).unwrap();
+        let restored = DbImpl::open(&restore_path, &DbOptions::default(), Vec::new()).unwrap();
+        let cf = restored.default_column_family();
+        assert_eq!(read_cf(&restored, &cf, "c").as_deref(), Some("1"));
+        assert_eq!(read_cf(&restored, &cf, "a"), None);
+    }
+
+    #[test]
+    fn damaged_backups_fail_verification() {
+        let dir = TempDir::new("backup-damaged-db");
+        let scratch = TempDir::new("backup-damaged-scratch");
+        let backup_dir = TempDir::new("backup-damaged");
+
+        let db = DbImpl::open(dir.path(), &create_options(), Vec::new()).unwrap();
+        ingest(&db, &scratch, "t", "1");
+        put(&db, "a", "1");
+        let mut engine = BackupEngine::open(backup_dir.path()).unwrap();
+        let id = engine.create_new_backup(&db).unwrap();
+
+        let shared = fs::read_dir(backup_dir.path().join("shared_checksum"))
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap()
+            .path();
+        fs::write(&shared, b"tablX").unwrap();
+        assert!(matches!(
+            engine.verify_backup(id),
+            Err(Error::Corruption(_))
+        ));
+        assert!(matches!(
+            engine.restore_db_from_backup(id, &backup_dir.path().join("restore")),
+            Err(Error::Corruption(_))
+        ));
+
+        fs::remove_file(&shared).unwrap();
+        assert!(matches!(
+            engine.verify_backup(id),
+            Err(Error::Corruption(_))
+        ));
+    }
+
+    #[test]
+    fn leftovers_of_failed_backups_are_removed() {
+        let backup_dir = TempDir::new("backup-leftovers");
+        drop(BackupEngine::open(backup_dir.path()).unwrap());
+
+        let private = backup_dir.path().join("private").join("7");
+        fs::create_dir_all(&private).unwrap();
+        fs::write(private.join("CURRENT"), b"x").unwrap();
+        fs::write(backup_dir.path().join("meta").join("7.tmp"), b"x").unwrap();
+        fs::write(
+            backup_dir
+                .path()
+                .join("shared_checksum")
+                .join("000001_1_1.sst"),
+            b"x",
+        )
+        .unwrap();
+
+        let engine = BackupEngine::open(backup_dir.path()).unwrap();
+        assert!(engine.backup_info().is_empty());
+        assert!(!private.exists());
+        assert_eq!(shared_files(&backup_dir), 0);
+        assert_eq!(
+            fs::read_dir(backup_dir.path().join("meta"))
+                .unwrap()
+                .count(),
+            0
+        );
+    }
+}
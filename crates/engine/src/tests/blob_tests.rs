@@ -0,0 +1,180 @@
+#[cfg(test)]
+mod tests {
+
+    use std::sync::Arc;
+
+    use crate::db::db_impl::DbImpl;
+    use crate::db::filename::blob_file;
+    use crate::db::write_batch::Batch;
+    use crate::error::Error;
+    use crate::merge::operators::string_append::StringAppendOperator;
+    use crate::options::{ColumnFamilyOptions, ReadOptions};
+    use crate::tests::helpers::{open, put, read, scan};
+    use crate::tests::temp_dir::TempDir;
+    use crate::versioning::file_version::NUM_LEVELS;
+
+    fn blob_options() -> ColumnFamilyOptions {
+        ColumnFamilyOptions {
+            enable_blob_files: true,
+            min_blob_size: 64,
+            merge_operator: Some(Arc::new(StringAppendOperator::new(b","))),
+            ..Default::default()
+        }
+    }
+
+    fn big(fill: char) -> String {
+        fill.to_string().repeat(200)
+    }
+
+    fn blob_file_numbers(db: &DbImpl) -> Vec<u64> {
+        let version = db.default_column_family().data().current_version();
+        version.blob_files().keys().copied().collect()
+    }
+
+    #[test]
+    fn large_values_are_read_from_blob_files() {
+        let dir = TempDir::new("blob-read-db");
+        let db = open(&dir, blob_options());
+        let cf = db.default_column_family();
+
+        put(&db, "big", &big('b'));
+        put(&db, "small", "s");
+        put(&db, "zed", &big('z'));
+        db.flush(&cf).unwrap();
+
+        let version = cf.data().current_version();
+        assert_eq!(version.num_level_files(0), 1);
+        let table = &version.level_files(0)[0];
+        // Only the small value stays in the table
+        assert!(table.properties.raw_value_size < 100);
+        let blobs = blob_file_numbers(&db);
+        assert_eq!(blobs.len(), 1);
+        assert_eq!(version.blob_files()[&blobs[0]].total_blob_count, 2);
+        assert!(blob_file(dir.path(), blobs[0]).exists());
+
+        assert_eq!(read(&db, "big"), Some(big('b')));
+        assert_eq!(read(&db, "small").as_deref(), Some("s"));
+        assert_eq!(read(&db, "missing"), None);
+
+        let keys: [&[u8]; 3] = [b"zed", b"small", b"big"];
+        let results: Vec<_> = db
+            .multi_get(&ReadOptions::default(), &cf, &keys)
+            .into_iter()
+            .map(|r| r.unwrap().map(|v| String::from_utf8(v).unwrap()))
+            .collect();
+        assert_eq!(
+            results,
+            vec![Some(big('z')), Some("s".into()), Some(big('b'))]
+        );
+
+        // A newer write in the memtable hides the blob
+        put(&db, "big", "overwritten");
+        assert_eq!(
+            scan(&db, &ReadOptions::default(), &cf),
+            vec![
+                ("big".into(), "overwritten".into()),
+                ("small".into(), "s".into()),
+                ("zed".into(), big('z')),
+            ]
+        );
+
+        // The flushed data comes back from the table and blob files, the overwrite from the WAL
+        drop(cf);
+        drop(db);
+        let db = open(&dir, blob_options());
+        assert_eq!(blob_file_numbers(&db), blobs);
+        assert_eq!(read(&db, "big").as_deref(), Some("overwritten"));
+        assert_eq!(read(&db, "zed"), Some(big('z')));
+    }
+
+    #[test]
+    fn merges_and_flushes_without_blob_files() {
+        let dir = TempDir::new("blob-merge-db");
+        let db = open(&dir, blob_options());
+        let cf = db.default_column_family();
+
+        // An empty memtable is not flushed
+        db.flush(&cf).unwrap();
+        assert_eq!(cf.data().current_version().num_level_files(0), 0);
+
+        put(&db, "k", &big('a'));
+        db.flush(&cf).unwrap();
+        let mut batch = Batch::new();
+        batch.merge("k", "tail");
+        db.write(&mut batch).unwrap();
+        db.flush(&cf).unwrap();
+        let expected = format!("{},tail", big('a'));
+        assert_eq!(read(&db, "k"), Some(expected.clone()));
+
+        // The compaction reads the blob as the base of the merge and separates the result again
+        let old_blobs = blob_file_numbers(&db);
+        db.compact_range(&cf).unwrap();
+        assert_eq!(read(&db, "k"), Some(expected));
+        let version = cf.data().current_version();
+        assert_eq!(version.num_level_files(NUM_LEVELS - 1), 1);
+        let blobs = blob_file_numbers(&db);
+        assert_eq!(blobs.len(), 1);
+        assert!(!old_blobs.contains(&blobs[0]));
+        for number in old_blobs {
+            assert!(!blob_file(dir.path(), number).exists());
+        }
+
+        let in_memory = DbImpl::new();
+        assert!(matches!(
+            in_memory.flush(&in_memory.default_column_family()),
+            Err(Error::InvalidArgument(_))
+        ));
+    }
+
+    #[test]
+    fn compaction_collects_blob_garbage() {
+        let dir = TempDir::new("blob-gc-db");
+        let db = open(&dir, blob_options());
+        let cf = db.default_column_family();
+
+        for key in ["a", "b", "c", "d"] {
+            put(&db, key, &big('1'));
+        }
+        db.flush(&cf).unwrap();
+        let first = blob_file_numbers(&db)[0];
+        for key in ["a", "b", "c"] {
+            put(&db, key, &big('2'));
+        }
+        db.flush(&cf).unwrap();
+        assert_eq!(blob_file_numbers(&db).len(), 2);
+
+        // Three of the four blobs of the first file are no longer referenced
+        db.compact_range(&cf).unwrap();
+        let version = cf.data().current_version();
+        let meta = &version.blob_files()[&first];
+        assert_eq!(meta.garbage_blob_count, 3);
+        assert!(meta.live_ratio() < 0.5);
+        assert!(blob_file(dir.path(), first).exists());
+
+        // The garbage is in the MANIFEST
+        drop(version);
+        drop(cf);
+        drop(db);
+        let db = open(&dir, blob_options());
+        let cf = db.default_column_family();
+        let version = cf.data().current_version();
+        assert_eq!(version.blob_files()[&first].garbage_blob_count, 3);
+        drop(version);
+
+        // The next compaction moves the last live blob out and the file goes away
+        db.compact_range(&cf).unwrap();
+        let blobs = blob_file_numbers(&db);
+        assert!(!blobs.contains(&first));
+        assert!(!blob_file(dir.path(), first).exists());
+        for key in ["a", "b", "c"] {
+            assert_eq!(read(&db, key), Some(big('2')));
+        }
+        assert_eq!(read(&db, "d"), Some(big('1')));
+
+        drop(cf);
+        drop(db);
+        let db = open(&dir, blob_options());
+        assert_eq!(blob_file_numbers(&db), blobs);
+        assert_eq!(read(&db, "d"), Some(big('1')));
+    }
+}
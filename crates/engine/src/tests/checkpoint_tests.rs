@@ -0,0 +1,128 @@
+#[cfg(test)]
+mod tests {
+
+    use crate::checkpoint::Checkpoint;
+    use crate::column_family::cf::ColumnFamilyDescriptor;
+    use crate::db::db_impl::DbImpl;
+    use crate::db::filename::table_file;
+    use crate::db::wal::wal_files;
+    use crate::error::Error;
+    use crate::options::{ColumnFamilyOptions, DbOptions, IngestExternalFileOptions};
+    use crate::table::sst_file_writer::SstFileWriter;
+    use crate::tests::helpers::{create_options, put_cf, read_cf};
+    use crate::tests::temp_dir::TempDir;
+
+    #[test]
+    fn checkpoint_opens_as_a_db() {
+        let dir = TempDir::new("checkpoint-src");
+        let target = TempDir::new("checkpoint-dst");
+        let copy = target.path().join("copy");
+
+        let db = DbImpl::open(dir.path(), &create_options(), Vec::new()).unwrap();
+        let users = db
+            .create_column_family("users", ColumnFamilyOptions::default())
+            .unwrap();
+        put_cf(&db, &db.default_column_family(), "a", "1");
+        put_cf(&db, &users, "u", "1");
+        // A table file of the DB is linked into the checkpoint
+        let external = target.path().join("external.sst");
+        let mut writer = SstFileWriter::create(&external, &ColumnFamilyOptions::default());
+        writer.put("t", "table").unwrap();
+        writer.finish().unwrap();
+        db.ingest_external_file(&users, &[external], &IngestExternalFileOptions::default())
+            .unwrap();
+        let table = users.data().current_version().level_files(6)[0].number;
+
+        let sequence = Checkpoint::new(&db).create(&copy).unwrap();
+        assert_eq!(sequence, db.last_sequence());
+        assert!(table_file(&copy, table).exists());
+
+        // Later writes to the DB are not in the checkpoint
+        put_cf(&db, &db.default_column_family(), "a", "2");
+        put_cf(&db, &db.default_column_family(), "b", "2");
+
+        let checkpoint = DbImpl::open(
+            &copy,
+            &DbOptions::default(),
+            vec![ColumnFamilyDescriptor::new(
+                "users",
+                ColumnFamilyOptions::default(),
+            )],
+        )
+        .unwrap();
+        let default = checkpoint.default_column_family();
+        let users = checkpoint.column_family("users").unwrap();
+        assert_eq!(read_cf(&checkpoint, &default, "a").as_deref(), Some("1"));
+        assert_eq!(read_cf(&checkpoint, &default, "b"), None);
+        assert_eq!(read_cf(&checkpoint, &users, "u").as_deref(), Some("1"));
+        assert_eq!(read_cf(&checkpoint, &users, "t").as_deref(), Some("table"));
+        assert_eq!(checkpoint.last_sequence(), sequence);
+
+        // The checkpoint is a DB of its own
+        put_cf(&checkpoint, &default, "c", "3");
+        assert_eq!(read_cf(&db, &db.default_column_family(), "c"), None);
+        assert_eq!(
+            read_cf(&db, &db.default_column_family(), "a").as_deref(),
+            Some("2")
+        );
+    }
+
+    #[test]
+    fn checkpoints_keep_range_deletions() {
+        let dir = TempDir::new("checkpoint-range-del-src");
+        let target = TempDir::new("checkpoint-range-del-dst");
+        let copy = target.path().join("copy");
+
+        let db = DbImpl::open(dir.path(), &create_options(), Vec::new()).unwrap();
+        let cf = db.default_column_family();
+        for key in ["a", "b", "c", "d"] {
+            put_cf(&db, &cf, key, "1");
+        }
+        // One tombstone in the MANIFEST, the other only in the WAL
+        db.delete_range(&cf, b"a", b"b").unwrap();
+        db.flush(&cf).unwrap();
+        db.delete_range(&cf, b"c", b"d").unwrap();
+        Checkpoint::new(&db).create(&copy).unwrap();
+
+        let checkpoint = DbImpl::open(&copy, &DbOptions::default(), Vec::new()).unwrap();
+        let default = checkpoint.default_column_family();
+        for (key, value) in [("a", None), ("b", Some("1")), ("c", None), ("d", Some("1"))] {
+            assert_eq!(read_cf(&checkpoint, &default, key).as_deref(), value);
+        }
+    }
+
+    #[test]
+    fn target_must_not_exist() {
+        let dir = TempDir::new("checkpoint-exists");
+        let db = DbImpl::open(dir.path(), &create_options(), Vec::new()).unwrap();
+        assert!(matches!(
+            Checkpoint::new(&db).create(dir.path()),
+            Err(Error::InvalidArgument(_))
+        ));
+
+        let db = DbImpl::new();
+        assert!(matches!(
+            Checkpoint::new(&db).create(&dir.path().join("copy")),
+            Err(Error::InvalidArgument(_))
+        ));
+    }
+
+    #[test]
+    fn file_deletions_can_be_paused() {
+        let dir = TempDir::new("checkpoint-deletions");
+        drop(DbImpl::open(dir.path(), &create_options(), Vec::new()).unwrap());
+        let db = DbImpl::open(dir.path(), &create_options(), Vec::new()).unwrap();
+        let wals = wal_files(dir.path()).unwrap();
+        assert_eq!(wals.len(), 2);
+
+        // As if a flush had put every write of the older WAL into a table file
+        db.default_column_family().data().set_log_number(wals[1]);
+        db.disable_file_deletions();
+        db.disable_file_deletions();
+        assert_eq!(db.purge_obsolete_wal_files().unwrap(), 0);
+        db.enable_file_deletions();
+        assert_eq!(db.purge_obsolete_wal_files().unwrap(), 0);
+        db.enable_file_deletions();
+        assert_eq!(db.purge_obsolete_wal_files().unwrap(), 1);
+    }
+}
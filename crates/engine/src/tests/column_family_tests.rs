@@ -0,0 +1,229 @@
+#[cfg(test)]
+mod tests {
+
+    use std::fs;
+
+    use crate::column_family::cf::ColumnFamilyDescriptor;
+    use crate::db::db_impl::DbImpl;
+    use crate::db::filename::{manifest_file, read_current_file, table_file};
+    use crate::db::read_path::{GetSource, get};
+    use crate::db::write_batch::Batch;
+    use crate::error::Error;
+    use crate::key::internal_key::OperationType;
+    use crate::key::lookup_key::LookUpInternalKey;
+    use crate::options::{ColumnFamilyOptions, DbOptions, ReadOptions};
+    use crate::tests::temp_dir::TempDir;
+
+    fn create_options() -> DbOptions {
+        DbOptions {
+            create_if_missing: true,
+            ..Default::default()
+        }
+    }
+
+    fn descriptors(names: &[&str]) -> Vec<ColumnFamilyDescriptor> {
+        names
+            .iter()
+            .map(|name| ColumnFamilyDescriptor::new(*name, ColumnFamilyOptions::default()))
+            .collect()
+    }
+
+    #[test]
+    fn column_families_are_persisted_in_the_manifest() {
+        let dir = TempDir::new("cf-persist");
+
+        {
+            let db = DbImpl::open(dir.path(), &create_options(), Vec::new()).unwrap();
+            let users = db
+                .create_column_family("users", ColumnFamilyOptions::default())
+                .unwrap();
+            let orders = db
+                .create_column_family("orders", ColumnFamilyOptions::default())
+                .unwrap();
+            assert_eq!((users.id(), orders.id()), (1, 2));
+
+            db.drop_column_family(&orders).unwrap();
+            assert_eq!(db.column_family_names(), vec!["default", "users"]);
+        }
+
+        assert_eq!(
+            DbImpl::list_column_families(dir.path()).unwrap(),
+            vec!["default", "users"]
+        );
+
+        // Every existing column family must be opened
+        assert!(matches!(
+            DbImpl::open(dir.path(), &DbOptions::default(), Vec::new()),
+            Err(Error::InvalidArgument(_))
+        ));
+
+        let db = DbImpl::open(dir.path(), &DbOptions::default(), descriptors(&["users"])).unwrap();
+        assert_eq!(db.column_family("users").unwrap().id(), 1);
+        assert!(db.column_family("orders").is_none());
+
+        // Ids of dropped column families are never reused
+        let events = db
+            .create_column_family("events", ColumnFamilyOptions::default())
+            .unwrap();
+        assert_eq!(events.id(), 3);
+        drop(db);
+
+        // Reopening starts a new MANIFEST and removes the old one
+        let manifest = read_current_file(dir.path()).unwrap();
+        let db = DbImpl::open(
+            dir.path(),
+            &DbOptions::default(),
+            descriptors(&["events", "users"]),
+        )
+        .unwrap();
+        assert_eq!(db.column_family_names(), vec!["default", "users", "events"]);
+        assert!(!manifest_file(dir.path(), manifest).exists());
+        assert!(read_current_file(dir.path()).unwrap() > manifest);
+    }
+
+    #[test]
+    fn open_options() {
+        let dir = TempDir::new("cf-open");
+        let missing = dir.path().join("db");
+
+        assert!(DbImpl::open(&missing, &DbOptions::default(), Vec::new()).is_err());
+
+        // Unknown column families are only created on request
+        assert!(DbImpl::open(&missing, &create_options(), descriptors(&["logs"])).is_err());
+
+        let options = DbOptions {
+            create_if_missing: true,
+            create_missing_column_families: true,
+            ..Default::default()
+        };
+        let db = DbImpl::open(&missing, &options, descriptors(&["logs"])).unwrap();
+        assert_eq!(db.column_family_names(), vec!["default", "logs"]);
+
+        assert!(matches!(
+            db.create_column_family("logs", ColumnFamilyOptions::default()),
+            Err(Error::InvalidArgument(_))
+        ));
+        assert!(matches!(
+            db.drop_column_family(&db.default_column_family()),
+            Err(Error::InvalidArgument(_))
+        ));
+    }
+
+    #[test]
+    fn each_column_family_has_its_own_memtables() {
+        let db = DbImpl::new();
+        let users = db
+            .create_column_family("users", ColumnFamilyOptions::default())
+            .unwrap();
+        let default = db.default_column_family();
+
+        users
+            .data()
+            .mem()
+            .insert(
+                LookUpInternalKey::new(b"k", 1, OperationType::Put).as_ref(),
+                b"user",
+            )
+            .unwrap();
+        let lookup = LookUpInternalKey::new(b"k", 1, OperationType::Max);
+
+        let sources: [&dyn GetSource; 1] = [users.data().superversion()];
+        assert_eq!(
+            get(&sources, lookup.as_ref(), None, 0).unwrap(),
+            Some(b"user".to_vec())
+        );
+
+        let sources: [&dyn GetSource; 1] = [default.data().superversion()];
+        assert_eq!(get(&sources, lookup.as_ref(), None, 0).unwrap(), None);
+    }
+
+    #[test]
+    fn dropped_column_families_live_until_the_last_handle() {
+        let db = DbImpl::new();
+        let handle = db
+            .create_column_family("tmp", ColumnFamilyOptions::default())
+            .unwrap();
+        let other = handle.clone();
+
+        handle
+            .data()
+            .mem()
+            .insert(
+                LookUpInternalKey::new(b"k", 1, OperationType::Put).as_ref(),
+                b"v",
+            )
+            .unwrap();
+
+        db.drop_column_family(&handle).unwrap();
+        assert!(handle.is_dropped());
+        assert!(db.column_family("tmp").is_none());
+        assert!(db.drop_column_family(&other).is_err());
+
+        // Still readable through the handles
+        let lookup = LookUpInternalKey::new(b"k", 1, OperationType::Max);
+        let sources: [&dyn GetSource; 1] = [other.data().superversion()];
+        assert_eq!(
+            get(&sources, lookup.as_ref(), None, 0).unwrap(),
+            Some(b"v".to_vec())
+        );
+
+        drop(handle);
+        assert_eq!(db.num_dropped_column_families_pending(), 1);
+        drop(other);
+        assert_eq!(db.num_dropped_column_families_pending(), 0);
+    }
+
+    #[test]
+    fn dropped_column_families_keep_their_files_until_the_last_handle() {
+        let dir = TempDir::new("cf-drop-files");
+        let db = DbImpl::open(dir.path(), &create_options(), Vec::new()).unwrap();
+        let handle = db
+            .create_column_family("tmp", ColumnFamilyOptions::default())
+            .unwrap();
+        let mut batch = Batch::new();
+        batch.put_cf(handle.id(), b"k", b"v");
+        batch.put_cf(0, b"k", b"v");
+        db.write(&mut batch).unwrap();
+        db.flush(&handle).unwrap();
+        let number = handle.data().current_version().level_files(0)[0].number;
+
+        // A compaction deletes the files no Version holds, the one of the dropped column family is still live
+        db.drop_column_family(&handle).unwrap();
+        db.flush(&db.default_column_family()).unwrap();
+        db.compact_range(&db.default_column_family()).unwrap();
+        assert!(table_file(dir.path(), number).exists());
+        assert_eq!(
+            db.get(&ReadOptions::default(), &handle, b"k").unwrap(),
+            Some(b"v".to_vec())
+        );
+
+        drop(handle);
+        let mut batch = Batch::new();
+        batch.put_cf(0, b"k2", b"v");
+        db.write(&mut batch).unwrap();
+        db.flush(&db.default_column_family()).unwrap();
+        db.compact_range(&db.default_column_family()).unwrap();
+        assert!(!table_file(dir.path(), number).exists());
+    }
+
+    #[test]
+    fn corrupt_manifest_fails_the_open() {
+        let dir = TempDir::new("cf-corrupt");
+        {
+            let db = DbImpl::open(dir.path(), &create_options(), Vec::new()).unwrap();
+            db.create_column_family("users", ColumnFamilyOptions::default())
+                .unwrap();
+        }
+
+        let path = manifest_file(dir.path(), read_current_file(dir.path()).unwrap());
+        let mut data = fs::read(&path).unwrap();
+        let last = data.len() - 1;
+        data[last] ^= 0xff;
+        fs::write(&path, data).unwrap();
+
+        assert!(matches!(
+            DbImpl::open(dir.path(), &DbOptions::default(), descriptors(&["users"])),
+            Err(Error::Corruption(_))
+        ));
+    }
+}
@@ -0,0 +1,125 @@
+#[cfg(test)]
+mod tests {
+
+    use std::cmp::Ordering;
+    use std::sync::Arc;
+
+    use crate::column_family::cf::ColumnFamilyDescriptor;
+    use crate::db::db_impl::DbImpl;
+    use crate::error::Error;
+    use crate::key::comparator::Comparator;
+    use crate::options::{ColumnFamilyOptions, DbOptions, ReadOptions};
+    use crate::tests::helpers::{put, read_cf, scan_keys};
+    use crate::tests::temp_dir::TempDir;
+
+    // Orders keys bytewise, largest first
+    struct ReverseComparator;
+
+    impl Comparator for ReverseComparator {
+        fn compare(&self, a: &[u8], b: &[u8]) -> Ordering {
+            b.cmp(a)
+        }
+
+        fn name(&self) -> &str {
+            "ReverseBytewiseComparator"
+        }
+    }
+
+    fn reverse_options() -> ColumnFamilyOptions {
+        ColumnFamilyOptions {
+            comparator: Arc::new(ReverseComparator),
+            ..Default::default()
+        }
+    }
+
+    fn open(dir: &TempDir, descriptors: Vec<ColumnFamilyDescriptor>) -> Result<DbImpl, Error> {
+        let options = DbOptions {
+            create_if_missing: true,
+            create_missing_column_families: true,
+            ..Default::default()
+        };
+        DbImpl::open(dir.path(), &options, descriptors)
+    }
+
+    #[test]
+    fn keys_are_ordered_by_the_user_comparator() {
+        let dir = TempDir::new("comparator-order-db");
+        let db = open(
+            &dir,
+            vec![ColumnFamilyDescriptor::new("default", reverse_options())],
+        )
+        .unwrap();
+        let cf = db.default_column_family();
+
+        for key in ["c", "apple", "e"] {
+            put(&db, key, &format!("{key}1"));
+        }
+        assert_eq!(
+            scan_keys(&db, &ReadOptions::default(), &cf),
+            vec!["e", "c", "apple"]
+        );
+
+        db.flush(&cf).unwrap();
+        for key in ["d", "banana", "a"] {
+            put(&db, key, &format!("{key}2"));
+        }
+        db.flush(&cf).unwrap();
+        assert_eq!(
+            scan_keys(&db, &ReadOptions::default(), &cf),
+            vec!["e", "d", "c", "banana", "apple", "a"]
+        );
+
+        db.compact_range(&cf).unwrap();
+        assert_eq!(
+            scan_keys(&db, &ReadOptions::default(), &cf),
+            vec!["e", "d", "c", "banana", "apple", "a"]
+        );
+        assert_eq!(read_cf(&db, &cf, "banana").as_deref(), Some("banana2"));
+        assert_eq!(read_cf(&db, &cf, "c").as_deref(), Some("c1"));
+        assert_eq!(read_cf(&db, &cf, "b"), None);
+    }
+
+    #[test]
+    fn reopening_with_another_comparator_is_rejected() {
+        let dir = TempDir::new("comparator-mismatch-db");
+        let db = open(
+            &dir,
+            vec![
+                ColumnFamilyDescriptor::new("default", reverse_options()),
+                ColumnFamilyDescriptor::new("plain", ColumnFamilyOptions::default()),
+            ],
+        )
+        .unwrap();
+        put(&db, "a", "a1");
+        drop(db);
+
+        // Either column family opened with the other's comparator
+        for (default, plain) in [
+            (
+                ColumnFamilyOptions::default(),
+                ColumnFamilyOptions::default(),
+            ),
+            (reverse_options(), reverse_options()),
+        ] {
+            let result = open(
+                &dir,
+                vec![
+                    ColumnFamilyDescriptor::new("default", default),
+                    ColumnFamilyDescriptor::new("plain", plain),
+                ],
+            );
+            assert!(matches!(result, Err(Error::InvalidArgument(_))));
+        }
+
+        let db = open(
+            &dir,
+            vec![
+                ColumnFamilyDescriptor::new("default", reverse_options()),
+                ColumnFamilyDescriptor::new("plain", ColumnFamilyOptions::default()),
+            ],
+        )
+        .unwrap();
+        let cf = db.default_column_family();
+        assert_eq!(read_cf(&db, &cf, "a").as_deref(), Some("a1"));
+    }
+}
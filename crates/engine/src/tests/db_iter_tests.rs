@@ -0,0 +1,414 @@
+#[cfg(test)]
+mod tests {
+
+    use std::sync::Arc;
+
+    use crate::block::block_builder::BlockBuilder;
+    use crate::block::data_block::Block;
+    use crate::error::Error;
+    use crate::iterator::db_iter::{DBIter, DBIterBuilder};
+    use crate::iterator::internal_iterator::InternalIterator;
+    use crate::iterator::iter_alloc::{HeapIter, IterAllocPool, IterAllocStrategy, PooledIter};
+    use crate::iterator::merge_iterator::MergingIterator;
+    use crate::key::comparator::{Comparator, DefaultComparator, InternalKeyComparator};
+    use crate::key::internal_key::{OperationType, encode_trailer};
+    use crate::merge::operator::MergeOperator;
+    use crate::merge::operators::string_append::StringAppendOperator;
+    use crate::tests::helpers::memtable_with;
+
+    type Entry<'a> = (&'a [u8], u64, OperationType, &'a [u8]);
+
+    fn block(entries: &[Entry]) -> Arc<Block> {
+        let comp = InternalKeyComparator::new();
+        let mut sorted: Vec<(Vec<u8>, Vec<u8>)> = entries
+            .iter()
+            .map(|(k, seq, op, v)| ([*k, &encode_trailer(*seq, *op)].concat(), v.to_vec()))
+            .collect();
+        sorted.sort_by(|a, b| comp.compare(&a.0, &b.0));
+
+        let mut builder = BlockBuilder::new(4);
+        for (k, v) in &sorted {
+            builder.add(k, v);
+        }
+        Arc::new(Block::new(builder.finish().to_vec()).unwrap())
+    }
+
+    fn db_iter<'a>(
+        children: Vec<Box<dyn InternalIterator + 'a>>,
+        merge_operator: Option<&'a dyn MergeOperator>,
+        sequence: u64,
+    ) -> DBIter<'a, HeapIter> {
+        let merging = MergingIterator::new(InternalKeyComparator::new(), children);
+        DBIter::new(
+            merging,
+            DefaultComparator::new(),
+            merge_operator,
+            sequence,
+            None,
+            None,
+        )
+    }
+
+    fn scan<S: IterAllocStrategy>(iter: &mut DBIter<'_, S>) -> Vec<(String, String)> {
+        let mut out = Vec::new();
+        while iter.valid() {
+            out.push((
+                String::from_utf8(iter.key().to_vec()).unwrap(),
+                String::from_utf8(iter.value().to_vec()).unwrap(),
+            ));
+            iter.next();
+        }
+        out
+    }
+
+    fn scan_reverse(iter: &mut DBIter<'_>) -> Vec<(String, String)> {
+        let mut out = Vec::new();
+        while iter.valid() {
+            out.push((
+                String::from_utf8(iter.key().to_vec()).unwrap(),
+                String::from_utf8(iter.value().to_vec()).unwrap(),
+            ));
+            iter.prev();
+        }
+        out
+    }
+
+    fn pairs(expected: &[(&str, &str)]) -> Vec<(String, String)> {
+        expected
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn newest_visible_version_per_key() {
+        use OperationType::*;
+
+        let mem = memtable_with(&[
+            (b"a", 10, Put, b"a10"),
+            (b"b", 11, Delete, b""),
+            (b"d", 12, SingleDelete, b""),
+            (b"e", 13, Put, b"e13"),
+        ]);
+        let table = block(&[
+            (b"a", 1, Put, b"a1"),
+            (b"b", 2, Put, b"b2"),
+            (b"c", 3, Put, b"c3"),
+            (b"c", 2, Put, b"c2"),
+            (b"d", 4, Put, b"d4"),
+        ]);
+
+        let comp: Arc<dyn Comparator> = InternalKeyComparator::new();
+
+        // Latest - deletes hide b and d
+        let mut iter = db_iter(
+            vec![
+                Box::new(mem.iter()),
+                Box::new(table.iter(Arc::clone(&comp))),
+            ],
+            None,
+            100,
+        );
+        iter.seek_to_first();
+        assert_eq!(
+            scan(&mut iter),
+            pairs(&[("a", "a10"), ("c", "c3"), ("e", "e13")])
+        );
+        assert!(iter.status().is_none());
+
+        // Read at seq 10 - older versions reappear and newer ones are invisible
+        let mut iter = db_iter(
+            vec![
+                Box::new(mem.iter()),
+                Box::new(table.iter(Arc::clone(&comp))),
+            ],
+            None,
+            10,
+        );
+        iter.seek_to_first();
+        assert_eq!(
+            scan(&mut iter),
+            pairs(&[("a", "a10"), ("b", "b2"), ("c", "c3"), ("d", "d4")])
+        );
+
+        // Read at seq 2
+        let mut iter = db_iter(
+            vec![
+                Box::new(mem.iter()),
+                Box::new(table.iter(Arc::clone(&comp))),
+            ],
+            None,
+            2,
+        );
+        iter.seek_to_first();
+        assert_eq!(
+            scan(&mut iter),
+            pairs(&[("a", "a1"), ("b", "b2"), ("c", "c2")])
+        );
+    }
+
+    #[test]
+    fn seek_skips_deleted_keys() {
+        use OperationType::*;
+
+        let mem = memtable_with(&[
+            (b"apple", 5, Put, b"1"),
+            (b"banana", 6, Delete, b""),
+            (b"banana", 4, Put, b"2"),
+            (b"cherry", 7, Put, b"3"),
+        ]);
+
+        let mut iter = db_iter(vec![Box::new(mem.iter())], None, 100);
+
+        iter.seek(b"b");
+        assert!(iter.valid());
+        assert_eq!(iter.key(), b"cherry");
+
+        iter.seek(b"apple");
+        assert_eq!(iter.key(), b"apple");
+        iter.next();
+        assert_eq!(iter.key(), b"cherry");
+        iter.next();
+        assert!(!iter.valid());
+
+        // Seeking below a newer invisible version still finds the visible one
+        let mut iter = db_iter(vec![Box::new(mem.iter())], None, 5);
+        iter.seek(b"banana");
+        assert_eq!(iter.key(), b"banana");
+        assert_eq!(iter.value(), b"2");
+    }
+
+    #[test]
+    fn resolves_merge_operands() {
+        use OperationType::*;
+
+        let mem = memtable_with(&[
+            (b"list", 9, Merge, b"d"),
+            (b"list", 8, Merge, b"c"),
+            (b"reset", 9, Merge, b"y"),
+            (b"only", 9, Merge, b"z"),
+            (b"plain", 9, Put, b"p"),
+        ]);
+        let table = block(&[
+            (b"list", 4, Merge, b"b"),
+            (b"list", 3, Put, b"a"),
+            (b"list", 2, Put, b"old"),
+            (b"reset", 5, Delete, b""),
+            (b"reset", 4, Put, b"x"),
+        ]);
+
+        let comp: Arc<dyn Comparator> = InternalKeyComparator::new();
+        let op = StringAppendOperator::new(b",");
+
+        let mut iter = db_iter(
+            vec![
+                Box::new(mem.iter()),
+                Box::new(table.iter(Arc::clone(&comp))),
+            ],
+            Some(&op),
+            100,
+        );
+        iter.seek_to_first();
+        assert_eq!(
+            scan(&mut iter),
+            pairs(&[
+                ("list", "a,b,c,d"),
+                ("only", "z"),
+                ("plain", "p"),
+                ("reset", "y"),
+            ])
+        );
+
+        // Operands newer than the read sequence are not applied
+        let mut iter = db_iter(
+            vec![
+                Box::new(mem.iter()),
+                Box::new(table.iter(Arc::clone(&comp))),
+            ],
+            Some(&op),
+            8,
+        );
+        iter.seek(b"list");
+        assert_eq!(iter.value(), b"a,b,c");
+        // only, plain and the reset operand are newer than the read, reset is deleted below them
+        iter.next();
+        assert!(!iter.valid());
+    }
+
+    #[test]
+    fn reverse_matches_forward() {
+        use OperationType::*;
+
+        let mem = memtable_with(&[
+            (b"a", 10, Put, b"a10"),
+            (b"b", 11, Delete, b""),
+            (b"d", 12, SingleDelete, b""),
+            (b"e", 13, Put, b"e13"),
+            (b"list", 9, Merge, b"d"),
+            (b"list", 8, Merge, b"c"),
+        ]);
+        let table = block(&[
+            (b"a", 1, Put, b"a1"),
+            (b"b", 2, Put, b"b2"),
+            (b"c", 3, Put, b"c3"),
+            (b"c", 2, Put, b"c2"),
+            (b"d", 4, Put, b"d4"),
+            (b"list", 4, Merge, b"b"),
+            (b"list", 3, Put, b"a"),
+        ]);
+
+        let comp: Arc<dyn Comparator> = InternalKeyComparator::new();
+        let op = StringAppendOperator::new(b",");
+
+        for sequence in [100, 10, 8, 2] {
+            let mut iter = db_iter(
+                vec![
+                    Box::new(mem.iter()),
+                    Box::new(table.iter(Arc::clone(&comp))),
+                ],
+                Some(&op),
+                sequence,
+            );
+
+            iter.seek_to_first();
+            let forward = scan(&mut iter);
+
+            iter.seek_to_last();
+            let mut reverse = scan_reverse(&mut iter);
+            reverse.reverse();
+            assert_eq!(forward, reverse, "sequence {sequence}");
+            assert!(iter.status().is_none());
+        }
+
+        let mut iter = db_iter(
+            vec![
+                Box::new(mem.iter()),
+                Box::new(table.iter(Arc::clone(&comp))),
+            ],
+            Some(&op),
+            100,
+        );
+
+        iter.seek_to_last();
+        assert_eq!(
+            scan_reverse(&mut iter),
+            pairs(&[("list", "a,b,c,d"), ("e", "e13"), ("c", "c3"), ("a", "a10"),])
+        );
+
+        // Exact hit, deleted key and between keys all land on the last visible key <= target
+        iter.seek_for_prev(b"c");
+        assert_eq!((iter.key(), iter.value()), (&b"c"[..], &b"c3"[..]));
+        iter.seek_for_prev(b"d");
+        assert_eq!(iter.key(), b"c");
+        iter.seek_for_prev(b"b");
+        assert_eq!(iter.key(), b"a");
+        iter.seek_for_prev(b"0");
+        assert!(!iter.valid());
+
+        // Switching direction
+        iter.seek(b"c");
+        iter.prev();
+        assert_eq!((iter.key(), iter.value()), (&b"a"[..], &b"a10"[..]));
+        iter.next();
+        assert_eq!(iter.key(), b"c");
+        iter.next();
+        assert_eq!(iter.key(), b"e");
+        iter.next();
+        assert_eq!(iter.value(), b"a,b,c,d");
+        iter.prev();
+        assert_eq!(iter.key(), b"e");
+        iter.prev();
+        assert_eq!((iter.key(), iter.value()), (&b"c"[..], &b"c3"[..]));
+    }
+
+    #[test]
+    fn pooled_iterators_reuse_allocations() {
+        use OperationType::*;
+
+        let mem = memtable_with(&[
+            (b"a", 10, Put, b"a10"),
+            (b"list", 9, Merge, b"b"),
+            (b"z", 11, Delete, b""),
+        ]);
+        let table = block(&[
+            (b"list", 3, Put, b"a"),
+            (b"m", 2, Put, b"m2"),
+            (b"z", 1, Put, b"z1"),
+        ]);
+
+        let comp: Arc<dyn Comparator> = InternalKeyComparator::new();
+        let op = StringAppendOperator::new(b",");
+        let expected = pairs(&[("a", "a10"), ("list", "a,b"), ("m", "m2")]);
+
+        let pool = IterAllocPool::new(4);
+
+        for strategy in [PooledIter::new(Some(&pool)), PooledIter::new(None)] {
+            for _ in 0..3 {
+                let mut builder = DBIterBuilder::new(strategy);
+                builder.add_child(mem.iter());
+                builder.add_child(table.iter(Arc::clone(&comp)));
+                assert_eq!(builder.num_children(), 2);
+
+                let mut iter = builder.build(
+                    Arc::clone(&comp),
+                    DefaultComparator::new(),
+                    Some(&op),
+                    100,
+                    None,
+                    None,
+                );
+                iter.seek_to_first();
+                assert_eq!(scan(&mut iter), expected);
+            }
+        }
+
+        // The alloc went back to this thread's cache with its arena blocks
+        let alloc = pool.acquire();
+        assert!(alloc.arena.num_blocks() > 0);
+        pool.release(alloc);
+
+        // A builder dropped without building hands its alloc back too
+        let mut builder = DBIterBuilder::new(PooledIter::new(Some(&pool)));
+        builder.add_child(mem.iter());
+        drop(builder);
+        let alloc = pool.acquire();
+        assert!(alloc.arena.num_blocks() > 0);
+        assert_eq!(pool.num_pooled(), 0);
+    }
+
+    #[test]
+    fn merge_without_operator_stops_the_scan() {
+        use OperationType::*;
+
+        let mem = memtable_with(&[(b"a", 1, Put, b"a"), (b"b", 2, Merge, b"b")]);
+
+        let mut iter = db_iter(vec![Box::new(mem.iter())], None, 100);
+        iter.seek_to_first();
+        assert_eq!(scan(&mut iter), pairs(&[("a", "a")]));
+        assert!(matches!(iter.status(), Some(Error::NotSupported(_))));
+    }
+
+    #[test]
+    fn surfaces_table_errors() {
+        use OperationType::*;
+
+        let mem = memtable_with(&[(b"a", 1, Put, b"a")]);
+
+        // Corrupt the only entry of the table
+        let mut builder = BlockBuilder::new(4);
+        builder.add(&[b"b".as_slice(), &encode_trailer(1, Put)].concat(), b"b");
+        let mut raw = builder.finish().to_vec();
+        raw[2] = 200;
+        let table = Arc::new(Block::new(raw).unwrap());
+
+        let comp: Arc<dyn Comparator> = InternalKeyComparator::new();
+        let mut iter = db_iter(
+            vec![Box::new(mem.iter()), Box::new(table.iter(comp))],
+            None,
+            100,
+        );
+        iter.seek_to_first();
+        assert!(!iter.valid());
+        assert!(matches!(iter.status(), Some(Error::Corruption(_))));
+    }
+}
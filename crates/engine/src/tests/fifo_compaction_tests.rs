@@ -0,0 +1,163 @@
+#[cfg(test)]
+mod tests {
+
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use crate::column_family::cf::ColumnFamilyDescriptor;
+    use crate::db::db_impl::DbImpl;
+    use crate::db::filename::table_file;
+    use crate::options::{ColumnFamilyOptions, CompactionOptionsFifo, CompactionStyle, DbOptions};
+    use crate::tests::helpers::{put, read};
+    use crate::tests::temp_dir::TempDir;
+    use crate::utils::clock::ManualClock;
+    use crate::versioning::file_version::NUM_LEVELS;
+
+    const START: u64 = 1_000_000;
+
+    fn open(dir: &TempDir, clock: &Arc<ManualClock>, fifo: CompactionOptionsFifo) -> DbImpl {
+        let options = DbOptions {
+            create_if_missing: true,
+            clock: clock.clone(),
+            // Without samples the tables have no oldest key time, so their creation time is the time they were flushed at
+            seqno_time_sample_period: Duration::ZERO,
+            ..Default::default()
+        };
+        let cf_options = ColumnFamilyOptions {
+            compaction_style: CompactionStyle::Fifo,
+            compaction_options_fifo: fifo,
+            ..Default::default()
+        };
+        DbImpl::open(
+            dir.path(),
+            &options,
+            vec![ColumnFamilyDescriptor::new("default", cf_options)],
+        )
+        .unwrap()
+    }
+
+    fn put_and_flush(db: &DbImpl, key: &str, value: &[u8]) {
+        put(db, key, value);
+        db.flush(&db.default_column_family()).unwrap();
+    }
+
+    // (number, creation_time, file_creation_time) of the L0 files, newest first
+    fn l0_files(db: &DbImpl) -> Vec<(u64, u64, u64)> {
+        let version = db.default_column_family().data().current_version();
+        assert!((1..NUM_LEVELS).all(|level| version.num_level_files(level) == 0));
+        version
+            .level_files(0)
+            .iter()
+            .map(|f| {
+                (
+                    f.number,
+                    f.properties.creation_time,
+                    f.properties.file_creation_time,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn ttl_drops_expired_files() {
+        let dir = TempDir::new("fifo-ttl-db");
+        let clock = ManualClock::new(START);
+        let fifo = CompactionOptionsFifo {
+            ttl: 100,
+            ..Default::default()
+        };
+        let db = open(&dir, &clock, fifo.clone());
+
+        put_and_flush(&db, "a", b"v");
+        clock.set(START + 50);
+        put_and_flush(&db, "b", b"v");
+        let files = l0_files(&db);
+        assert_eq!(files.len(), 2);
+        assert_eq!((files[0].1, files[0].2), (START + 50, START + 50));
+        assert_eq!((files[1].1, files[1].2), (START, START));
+
+        // The flush at START + 120 finds the file of START expired
+        let expired = files[1].0;
+        clock.set(START + 120);
+        put_and_flush(&db, "c", b"v");
+        assert_eq!(l0_files(&db).len(), 2);
+        assert_eq!(read(&db, "a"), None);
+        assert!(read(&db, "b").is_some() && read(&db, "c").is_some());
+        assert!(!table_file(dir.path(), expired).exists());
+
+        // The creation times are in the table files
+        drop(db);
+        let db = open(&dir, &clock, fifo);
+        assert_eq!(l0_files(&db)[1].1, START + 50);
+        clock.set(START + 200);
+        put_and_flush(&db, "d", b"v");
+        assert_eq!(read(&db, "b"), None);
+        assert!(read(&db, "c").is_some() && read(&db, "d").is_some());
+    }
+
+    #[test]
+    fn size_cap_drops_the_oldest_files() {
+        let dir = TempDir::new("fifo-size-db");
+        let clock = ManualClock::new(START);
+        let db = open(
+            &dir,
+            &clock,
+            CompactionOptionsFifo {
+                max_table_files_size: 4000,
+                ..Default::default()
+            },
+        );
+
+        for i in 0..5 {
+            put_and_flush(&db, &format!("k{i}"), &[b'v'; 1000]);
+            let version = db.default_column_family().data().current_version();
+            assert!(version.level_size(0) <= 4000);
+        }
+        let files = l0_files(&db).len();
+        assert!(files > 1 && files < 5);
+        // The newest files are kept
+        for i in 0..5 {
+            assert_eq!(read(&db, &format!("k{i}")).is_some(), i >= 5 - files);
+        }
+    }
+
+    #[test]
+    fn small_files_are_merged_within_l0() {
+        let dir = TempDir::new("fifo-intra-l0-db");
+        let clock = ManualClock::new(START);
+        let db = open(
+            &dir,
+            &clock,
+            CompactionOptionsFifo {
+                allow_compaction: true,
+                intra_l0_file_trigger: 3,
+                ..Default::default()
+            },
+        );
+
+        put_and_flush(&db, "a", b"1");
+        clock.set(START + 10);
+        put_and_flush(&db, "b", b"2");
+        let inputs: Vec<_> = l0_files(&db).iter().map(|f| f.0).collect();
+        assert_eq!(inputs.len(), 2);
+
+        // The third file triggers the merge, its output is as old as the oldest input
+        clock.set(START + 20);
+        put_and_flush(&db, "a", b"3");
+        let files = l0_files(&db);
+        assert_eq!(files.len(), 1);
+        assert_eq!((files[0].1, files[0].2), (START, START + 20));
+        assert_eq!(read(&db, "a").as_deref(), Some("3"));
+        assert_eq!(read(&db, "b").as_deref(), Some("2"));
+        assert!(inputs.iter().all(|&n| !table_file(dir.path(), n).exists()));
+
+        // A manual compaction keeps the files of a FIFO column family in L0
+        clock.set(START + 30);
+        put_and_flush(&db, "c", b"4");
+        db.compact_range(&db.default_column_family()).unwrap();
+        let files = l0_files(&db);
+        assert_eq!(files.len(), 1);
+        assert_eq!((files[0].1, files[0].2), (START, START + 30));
+        assert_eq!(read(&db, "c").as_deref(), Some("4"));
+    }
+}
@@ -0,0 +1,138 @@
+#![cfg(test)]
+
+// Fixtures shared by the tests: options and open for a DB in a TempDir, writes and reads by key, and a small memtable.
+
+use mem::allocator::{Allocator, SystemAllocator};
+use mem::arena::ArenaPolicy;
+
+use crate::column_family::cf::{ColumnFamilyDescriptor, ColumnFamilyHandle};
+use crate::db::db_impl::DbImpl;
+use crate::db::write_batch::Batch;
+use crate::key::comparator::InternalKeyComparator;
+use crate::key::internal_key::OperationType;
+use crate::key::lookup_key::LookUpInternalKey;
+use crate::memtable::memtable::{MemID, Memtable, Mutable};
+use crate::options::{ColumnFamilyOptions, DbOptions, ReadOptions};
+use crate::tests::temp_dir::TempDir;
+
+pub(crate) fn create_options() -> DbOptions {
+    DbOptions {
+        create_if_missing: true,
+        ..Default::default()
+    }
+}
+
+// Opens (or creates) the DB in `dir` with only the default column family
+pub(crate) fn open(dir: &TempDir, cf_options: ColumnFamilyOptions) -> DbImpl {
+    DbImpl::open(
+        dir.path(),
+        &create_options(),
+        vec![ColumnFamilyDescriptor::new("default", cf_options)],
+    )
+    .unwrap()
+}
+
+// An empty memtable of a single 4KB arena block
+pub(crate) fn memtable(id: MemID) -> Memtable<Mutable> {
+    Memtable::new(
+        id,
+        ArenaPolicy {
+            block_size: 4096,
+            cap: 4096,
+        },
+        Allocator::System(SystemAllocator::new()),
+        InternalKeyComparator::new(),
+    )
+}
+
+// A memtable holding the (user key, seq no, op, value) entries
+pub(crate) fn memtable_with(entries: &[(&[u8], u64, OperationType, &[u8])]) -> Memtable<Mutable> {
+    let mem = memtable(1);
+    for (key, seq, op, value) in entries {
+        mem.insert(LookUpInternalKey::new(key, *seq, *op).as_ref(), value)
+            .unwrap();
+    }
+    mem
+}
+
+pub(crate) fn put(db: &DbImpl, key: impl AsRef<[u8]>, value: impl AsRef<[u8]>) {
+    put_cf(db, &db.default_column_family(), key, value);
+}
+
+pub(crate) fn put_cf(
+    db: &DbImpl,
+    cf: &ColumnFamilyHandle,
+    key: impl AsRef<[u8]>,
+    value: impl AsRef<[u8]>,
+) {
+    let mut batch = Batch::new();
+    batch.put_cf(cf.id(), key.as_ref(), value.as_ref());
+    db.write(&mut batch).unwrap();
+}
+
+pub(crate) fn read(db: &DbImpl, key: impl AsRef<[u8]>) -> Option<String> {
+    read_cf(db, &db.default_column_family(), key)
+}
+
+pub(crate) fn read_cf(
+    db: &DbImpl,
+    cf: &ColumnFamilyHandle,
+    key: impl AsRef<[u8]>,
+) -> Option<String> {
+    read_with(db, &ReadOptions::default(), cf, key)
+}
+
+pub(crate) fn read_with(
+    db: &DbImpl,
+    options: &ReadOptions,
+    cf: &ColumnFamilyHandle,
+    key: impl AsRef<[u8]>,
+) -> Option<String> {
+    db.get(options, cf, key.as_ref())
+        .unwrap()
+        .map(|v| String::from_utf8(v).unwrap())
+}
+
+pub(crate) fn multi_read_with(
+    db: &DbImpl,
+    options: &ReadOptions,
+    cf: &ColumnFamilyHandle,
+    keys: &[&[u8]],
+) -> Vec<Option<String>> {
+    db.multi_get(options, cf, keys)
+        .into_iter()
+        .map(|r| r.unwrap().map(|v| String::from_utf8(v).unwrap()))
+        .collect()
+}
+
+// Every (key, value) of the column family in order
+pub(crate) fn scan(
+    db: &DbImpl,
+    options: &ReadOptions,
+    cf: &ColumnFamilyHandle,
+) -> Vec<(String, String)> {
+    let mut iter = db.new_iterator(options, cf).unwrap();
+
+    let mut out = Vec::new();
+    iter.seek_to_first();
+    while iter.valid() {
+        out.push((
+            String::from_utf8(iter.key().to_vec()).unwrap(),
+            String::from_utf8(iter.value().to_vec()).unwrap(),
+        ));
+        iter.next();
+    }
+    assert!(iter.status().is_none());
+    out
+}
+
+pub(crate) fn scan_keys(
+    db: &DbImpl,
+    options: &ReadOptions,
+    cf: &ColumnFamilyHandle,
+) -> Vec<String> {
+    scan(db, options, cf)
+        .into_iter()
+        .map(|(key, _)| key)
+        .collect()
+}
@@ -0,0 +1,232 @@
+#[cfg(test)]
+mod tests {
+
+    use std::path::PathBuf;
+    use std::slice;
+    use std::sync::Arc;
+
+    use crate::column_family::cf::ColumnFamilyHandle;
+    use crate::db::db_impl::DbImpl;
+    use crate::db::write_batch::Batch;
+    use crate::error::Error;
+    use crate::options::{ColumnFamilyOptions, IngestExternalFileOptions, ReadOptions};
+    use crate::table::sst_file_writer::SstFileWriter;
+    use crate::tests::helpers::{create_options, read, read_with};
+    use crate::tests::temp_dir::TempDir;
+    use crate::versioning::file_version::NUM_LEVELS;
+    use crate::versioning::snapshot::Snapshot;
+
+    // Writes a file with a put of each key (in the order given)
+    fn write_file(dir: &TempDir, name: &str, entries: &[(&str, &str)]) -> PathBuf {
+        let path = dir.path().join(name);
+        let mut writer = SstFileWriter::create(&path, &ColumnFamilyOptions::default());
+        for (key, value) in entries {
+            writer.put(key, value).unwrap();
+        }
+        writer.finish().unwrap();
+        path
+    }
+
+    fn ingest(db: &DbImpl, paths: &[PathBuf]) -> Result<(), Error> {
+        db.ingest_external_file(
+            &db.default_column_family(),
+            paths,
+            &IngestExternalFileOptions::default(),
+        )
+    }
+
+    fn read_at(
+        db: &DbImpl,
+        cf: &ColumnFamilyHandle,
+        key: &str,
+        snapshot: Option<Arc<Snapshot>>,
+    ) -> Option<String> {
+        let read_options = ReadOptions {
+            snapshot,
+            ..Default::default()
+        };
+        read_with(db, &read_options, cf, key)
+    }
+
+    // Number of files in each level of the default column family
+    fn files_per_level(db: &DbImpl) -> Vec<usize> {
+        let version = db.default_column_family().data().current_version();
+        (0..NUM_LEVELS)
+            .map(|level| version.num_level_files(level))
+            .collect()
+    }
+
+    #[test]
+    fn ingested_files_are_read_and_recovered() {
+        let dir = TempDir::new("ingest-db");
+        let scratch = TempDir::new("ingest-scratch");
+        let first = write_file(&scratch, "1.sst", &[("a", "1"), ("b", "1")]);
+        let second = write_file(&scratch, "2.sst", &[("m", "2"), ("n", "2")]);
+
+        let db = DbImpl::open(dir.path(), &create_options(), Vec::new()).unwrap();
+        ingest(&db, &[second, first.clone()]).unwrap();
+        // Nothing overlapped, so the entries keep seq no 0 and go to the bottom level
+        assert_eq!(db.last_sequence(), 0);
+        assert_eq!(files_per_level(&db), vec![0, 0, 0, 0, 0, 0, 2]);
+        // The originals are copied, not moved
+        assert!(first.exists());
+
+        assert_eq!(read(&db, "a").as_deref(), Some("1"));
+        assert_eq!(read(&db, "n").as_deref(), Some("2"));
+        assert_eq!(read(&db, "c"), None);
+
+        let cf = db.default_column_family();
+        let keys: [&[u8]; 4] = [b"n", b"a", b"z", b"b"];
+        let results = db.multi_get(&ReadOptions::default(), &cf, &keys);
+        let results: Vec<_> = results.into_iter().map(|r| r.unwrap()).collect();
+        assert_eq!(
+            results,
+            vec![
+                Some(b"2".to_vec()),
+                Some(b"1".to_vec()),
+                None,
+                Some(b"1".to_vec())
+            ]
+        );
+
+        // The MANIFEST records the files
+        drop(db);
+        let db = DbImpl::open(dir.path(), &create_options(), Vec::new()).unwrap();
+        assert_eq!(files_per_level(&db), vec![0, 0, 0, 0, 0, 0, 2]);
+        assert_eq!(read(&db, "b").as_deref(), Some("1"));
+        assert_eq!(read(&db, "m").as_deref(), Some("2"));
+    }
+
+    #[test]
+    fn overlapping_files_get_a_global_seqno() {
+        let dir = TempDir::new("ingest-overlap-db");
+        let scratch = TempDir::new("ingest-overlap-scratch");
+        let db = DbImpl::open(dir.path(), &create_options(), Vec::new()).unwrap();
+
+        // Each file overlaps the last one, so it goes one level up until it reaches L0
+        for i in 0..NUM_LEVELS + 1 {
+            let value = i.to_string();
+            let path = write_file(&scratch, &format!("{i}.sst"), &[("k", &value)]);
+            ingest(&db, &[path]).unwrap();
+            assert_eq!(read(&db, "k"), Some(value));
+            assert_eq!(db.last_sequence(), i as u64);
+        }
+        assert_eq!(files_per_level(&db), vec![2, 1, 1, 1, 1, 1, 1]);
+
+        let path = write_file(&scratch, "strict.sst", &[("k", "strict")]);
+        let strict = IngestExternalFileOptions {
+            allow_global_seqno: false,
+            ..Default::default()
+        };
+        assert!(matches!(
+            db.ingest_external_file(&db.default_column_family(), &[path], &strict),
+            Err(Error::InvalidArgument(_))
+        ));
+
+        drop(db);
+        let db = DbImpl::open(dir.path(), &create_options(), Vec::new()).unwrap();
+        assert_eq!(read(&db, "k").as_deref(), Some("7"));
+        assert_eq!(db.last_sequence(), NUM_LEVELS as u64);
+    }
+
+    #[test]
+    fn snapshots_do_not_see_ingested_files() {
+        let dir = TempDir::new("ingest-snapshot-db");
+        let scratch = TempDir::new("ingest-snapshot-scratch");
+        let db = DbImpl::open(dir.path(), &create_options(), Vec::new()).unwrap();
+        let cf = db.default_column_family();
+
+        let snapshot = db.get_snapshot();
+        ingest(&db, &[write_file(&scratch, "1.sst", &[("a", "1")])]).unwrap();
+        assert_eq!(read_at(&db, &cf, "a", Some(Arc::clone(&snapshot))), None);
+        assert_eq!(read_at(&db, &cf, "a", None).as_deref(), Some("1"));
+
+        // Without snapshot_consistency the snapshot sees the file as if it was always there
+        let relaxed = IngestExternalFileOptions {
+            snapshot_consistency: false,
+            ..Default::default()
+        };
+        let path = write_file(&scratch, "2.sst", &[("b", "2")]);
+        db.ingest_external_file(&cf, &[path], &relaxed).unwrap();
+        assert_eq!(read_at(&db, &cf, "b", Some(snapshot)).as_deref(), Some("2"));
+    }
+
+    #[test]
+    fn invalid_ingestions_are_rejected() {
+        let dir = TempDir::new("ingest-invalid-db");
+        let scratch = TempDir::new("ingest-invalid-scratch");
+        let db = DbImpl::open(dir.path(), &create_options(), Vec::new()).unwrap();
+
+        let mut writer = SstFileWriter::create(
+            &scratch.path().join("x.sst"),
+            &ColumnFamilyOptions::default(),
+        );
+        writer.put("b", "1").unwrap();
+        assert!(matches!(
+            writer.put("a", "1"),
+            Err(Error::InvalidArgument(_))
+        ));
+        assert!(matches!(writer.delete("b"), Err(Error::InvalidArgument(_))));
+        let empty = SstFileWriter::create(
+            &scratch.path().join("y.sst"),
+            &ColumnFamilyOptions::default(),
+        );
+        assert!(matches!(empty.finish(), Err(Error::InvalidArgument(_))));
+
+        // The files of one ingestion may not overlap each other
+        let first = write_file(&scratch, "1.sst", &[("a", "1"), ("c", "1")]);
+        let second = write_file(&scratch, "2.sst", &[("c", "2"), ("d", "2")]);
+        assert!(matches!(
+            ingest(&db, &[first.clone(), second]),
+            Err(Error::InvalidArgument(_))
+        ));
+        assert!(matches!(ingest(&db, &[]), Err(Error::InvalidArgument(_))));
+        assert!(matches!(
+            ingest(&db, &[scratch.path().join("missing.sst")]),
+            Err(Error::IO(_))
+        ));
+
+        assert_eq!(files_per_level(&db), vec![0; NUM_LEVELS]);
+
+        // move_files takes the original
+        let path = write_file(&scratch, "3.sst", &[("x", "3")]);
+        let options = IngestExternalFileOptions {
+            move_files: true,
+            ..Default::default()
+        };
+        db.ingest_external_file(
+            &db.default_column_family(),
+            slice::from_ref(&path),
+            &options,
+        )
+        .unwrap();
+        assert!(!path.exists());
+        assert_eq!(read(&db, "x").as_deref(), Some("3"));
+    }
+
+    #[test]
+    fn overlapping_memtables_are_flushed_first() {
+        let dir = TempDir::new("ingest-flush-db");
+        let scratch = TempDir::new("ingest-flush-scratch");
+        let db = DbImpl::open(dir.path(), &create_options(), Vec::new()).unwrap();
+
+        let mut batch = Batch::new();
+        batch.put("a", "memtable");
+        batch.put("b", "memtable");
+        db.write(&mut batch).unwrap();
+
+        let path = write_file(&scratch, "1.sst", &[("a", "1"), ("c", "1")]);
+        ingest(&db, &[path]).unwrap();
+        // The flushed memtable and above it the ingested file, at a seq no newer than the memtable's
+        assert_eq!(files_per_level(&db)[0], 2);
+        assert_eq!(db.last_sequence(), 3);
+        assert_eq!(read(&db, "a").as_deref(), Some("1"));
+        assert_eq!(read(&db, "b").as_deref(), Some("memtable"));
+        assert_eq!(read(&db, "c").as_deref(), Some("1"));
+
+        drop(db);
+        let db = DbImpl::open(dir.path(), &create_options(), Vec::new()).unwrap();
+        assert_eq!(read(&db, "a").as_deref(), Some("1"));
+        assert_eq!(read(&db, "b").as_deref(), Some("memtable"));
+    }
+}
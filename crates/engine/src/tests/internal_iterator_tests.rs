@@ -27,11 +27,11 @@ mod tests {
         let k4 = LookUpInternalKey::new(b"51.1.User1001", 4, OperationType::Delete);
         let k_other = LookUpInternalKey::new(b"51.1.User1002", 5, OperationType::Put);
 
-        mem.insert(k1.as_ref(), b"value_1");
-        mem.insert(k2.as_ref(), b"value_2");
-        mem.insert(k3.as_ref(), b"value_3");
-        mem.insert(k4.as_ref(), b"");
-        mem.insert(k_other.as_ref(), b"value_4");
+        mem.insert(k1.as_ref(), b"value_1").unwrap();
+        mem.insert(k2.as_ref(), b"value_2").unwrap();
+        mem.insert(k3.as_ref(), b"value_3").unwrap();
+        mem.insert(k4.as_ref(), b"").unwrap();
+        mem.insert(k_other.as_ref(), b"value_4").unwrap();
 
         fn ik(k: &LookUpInternalKey) -> InternalKeyRef<'_> {
             InternalKeyRef::from(k.as_ref())
@@ -107,5 +107,45 @@ mod tests {
             assert!(iter.valid());
             assert_eq!(iter.internal_key().unwrap(), ik(&k_other));
         }
+
+        // Reverse ordering
+        {
+            let mut iter = mem.iter();
+            iter.seek_to_last();
+
+            let expected = [&k_other, &k1, &k2, &k3, &k4];
+
+            for k in expected {
+                assert!(iter.valid());
+                assert_eq!(iter.internal_key().unwrap(), ik(k));
+                iter.prev();
+            }
+
+            assert!(!iter.valid());
+        }
+
+        // Seek for prev lands on last ≤ key
+        {
+            let mut iter = mem.iter();
+
+            iter.seek_for_prev(k2.as_ref());
+            assert_eq!(iter.internal_key().unwrap(), ik(&k2));
+
+            // seq=0 sorts after every User1001 entry → should land on the oldest (k1)
+            iter.seek_for_prev(
+                LookUpInternalKey::new(b"51.1.User1001", 0, OperationType::Put).as_ref(),
+            );
+            assert_eq!(iter.internal_key().unwrap(), ik(&k1));
+
+            // Past the end → last entry
+            iter.seek_for_prev(
+                LookUpInternalKey::new(b"51.1.User9", 0, OperationType::Put).as_ref(),
+            );
+            assert_eq!(iter.internal_key().unwrap(), ik(&k_other));
+
+            // Before the start → invalid
+            iter.seek_for_prev(LookUpInternalKey::new(b"51.0", 0, OperationType::Put).as_ref());
+            assert!(!iter.valid());
+        }
     }
 }
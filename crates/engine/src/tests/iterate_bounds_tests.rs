@@ -0,0 +1,224 @@
+#[cfg(test)]
+mod tests {
+
+    use std::cell::Cell;
+    use std::sync::Arc;
+
+    use crate::iterator::bounds::IterBounds;
+    use crate::iterator::db_iter::DBIter;
+    use crate::iterator::internal_iterator::InternalIterator;
+    use crate::iterator::iter_alloc::HeapIter;
+    use crate::iterator::level_iterator::LevelIterator;
+    use crate::iterator::merge_iterator::MergingIterator;
+    use crate::key::comparator::{Comparator, DefaultComparator, InternalKeyComparator};
+    use crate::key::internal_key::{OperationType, encode_trailer};
+    use crate::key::prefix_extractor::{FixedPrefixExtractor, PrefixExtractor};
+    use crate::memtable::memtable::*;
+    use crate::options::ReadOptions;
+    use crate::table::table_builder::TableBuilder;
+    use crate::table::table_reader::Table;
+    use crate::tests::helpers::memtable_with;
+    use crate::versioning::file_version::FileMetaData;
+
+    // A level of 4 files with 100 keys each ("k-000" .. "k-399") in blocks of ~10 keys
+    fn level_tables() -> (Vec<Arc<Table>>, Vec<Arc<FileMetaData>>) {
+        let mut tables = Vec::new();
+        let mut files = Vec::new();
+
+        for file in 0..4 {
+            let mut builder = TableBuilder::new(256, 4);
+            for i in file * 100..(file + 1) * 100 {
+                builder.add(
+                    &[
+                        format!("k-{i:03}").as_bytes(),
+                        &encode_trailer(1, OperationType::Put),
+                    ]
+                    .concat(),
+                    format!("v{i}").as_bytes(),
+                );
+            }
+            let built = builder.finish();
+            files.push(Arc::new(built.file_meta(file as u64)));
+            tables.push(Table::open(built.data).unwrap());
+        }
+
+        (tables, files)
+    }
+
+    fn db_iter<'a>(
+        mem: &'a Memtable<Mutable>,
+        tables: &'a [Arc<Table>],
+        files: &'a [Arc<FileMetaData>],
+        options: &ReadOptions,
+        prefix_extractor: Option<Arc<dyn PrefixExtractor>>,
+        opened: &'a Cell<usize>,
+    ) -> DBIter<'a, HeapIter> {
+        let comp: Arc<dyn Comparator> = InternalKeyComparator::new();
+        let bounds = IterBounds::from_read_options(options, DefaultComparator::new());
+
+        let table_comp = Arc::clone(&comp);
+        let table_bounds = bounds.clone();
+        let level = LevelIterator::new(
+            files,
+            Arc::clone(&comp),
+            Box::new(move |f: &FileMetaData| {
+                opened.set(opened.get() + 1);
+                let iter =
+                    tables[f.number as usize].iter(Arc::clone(&table_comp), table_bounds.clone());
+                Ok(Box::new(iter) as Box<dyn InternalIterator>)
+            }),
+            bounds.clone(),
+        );
+
+        let merging = MergingIterator::new(comp, vec![Box::new(mem.iter()), Box::new(level)]);
+        let prefix_extractor = prefix_extractor.filter(|_| options.prefix_same_as_start);
+        DBIter::new(
+            merging,
+            DefaultComparator::new(),
+            None,
+            100,
+            bounds,
+            prefix_extractor,
+        )
+    }
+
+    fn keys(iter: &mut DBIter<'_>, forward: bool) -> Vec<String> {
+        let mut out = Vec::new();
+        while iter.valid() {
+            out.push(String::from_utf8(iter.key().to_vec()).unwrap());
+            if forward {
+                iter.next();
+            } else {
+                iter.prev();
+            }
+        }
+        out
+    }
+
+    fn block_reads(tables: &[Arc<Table>]) -> u64 {
+        tables.iter().map(|t| t.data_block_reads()).sum()
+    }
+
+    fn range(from: usize, to: usize) -> Vec<String> {
+        (from..to).map(|i| format!("k-{i:03}")).collect()
+    }
+
+    #[test]
+    fn bounds_limit_the_scan() {
+        let (tables, files) = level_tables();
+        let mem = memtable_with(&[
+            (b"k-150", 5, OperationType::Delete, b""),
+            (b"k-160x", 5, OperationType::Put, b"mem"),
+            (b"k-999", 5, OperationType::Put, b"mem"),
+        ]);
+        let options = ReadOptions {
+            iterate_lower_bound: Some(b"k-140".to_vec()),
+            iterate_upper_bound: Some(b"k-170".to_vec()),
+            ..Default::default()
+        };
+        let opened = Cell::new(0);
+
+        let mut expected: Vec<String> = range(140, 170)
+            .into_iter()
+            .filter(|k| k != "k-150")
+            .collect();
+        expected.insert(
+            expected.iter().position(|k| k == "k-161").unwrap(),
+            "k-160x".into(),
+        );
+
+        let mut iter = db_iter(&mem, &tables, &files, &options, None, &opened);
+
+        iter.seek_to_first();
+        assert_eq!(keys(&mut iter, true), expected);
+        assert!(iter.status().is_none());
+
+        // Only the file holding the range is opened, and only the blocks around the bounds are read
+        assert_eq!(opened.get(), 1);
+        assert!(block_reads(&tables) <= 5, "{}", block_reads(&tables));
+
+        iter.seek_to_last();
+        let mut reversed = keys(&mut iter, false);
+        reversed.reverse();
+        assert_eq!(reversed, expected);
+        assert_eq!(opened.get(), 2);
+
+        // Seeks outside the bounds are clamped to them
+        iter.seek(b"k-000");
+        assert_eq!(iter.key(), b"k-140");
+        iter.seek_for_prev(b"z");
+        assert_eq!(iter.key(), b"k-169");
+        iter.seek(b"k-170");
+        assert!(!iter.valid());
+        iter.seek_for_prev(b"k-139");
+        assert!(!iter.valid());
+    }
+
+    #[test]
+    fn upper_bound_at_a_file_boundary_skips_later_files() {
+        let (tables, files) = level_tables();
+        let mem = memtable_with(&[]);
+        let options = ReadOptions {
+            iterate_upper_bound: Some(b"k-200".to_vec()),
+            ..Default::default()
+        };
+        let opened = Cell::new(0);
+
+        let mut iter = db_iter(&mem, &tables, &files, &options, None, &opened);
+        iter.seek(b"k-190");
+        assert_eq!(keys(&mut iter, true), range(190, 200));
+        // The file starting at the upper bound is never opened
+        assert_eq!(opened.get(), 1);
+    }
+
+    #[test]
+    fn prefix_same_as_start() {
+        let (tables, files) = level_tables();
+        let mem = memtable_with(&[(b"k-0", 5, OperationType::Put, b"mem")]);
+        let extractor: Arc<dyn PrefixExtractor> = Arc::new(FixedPrefixExtractor::new(3));
+        let opened = Cell::new(0);
+
+        let options = ReadOptions {
+            prefix_same_as_start: true,
+            ..Default::default()
+        };
+        let mut iter = db_iter(
+            &mem,
+            &tables,
+            &files,
+            &options,
+            Some(Arc::clone(&extractor)),
+            &opened,
+        );
+
+        iter.seek(b"k-250");
+        assert_eq!(keys(&mut iter, true), range(250, 300));
+
+        iter.seek_for_prev(b"k-105");
+        let mut expected = range(100, 106);
+        expected.reverse();
+        assert_eq!(keys(&mut iter, false), expected);
+
+        // seek_to_first takes the prefix of the first key
+        iter.seek_to_first();
+        let mut expected = vec!["k-0".to_string()];
+        expected.extend(range(0, 100));
+        assert_eq!(keys(&mut iter, true), expected);
+
+        // A target outside the extractor's domain doesn't restrict the scan
+        iter.seek(b"k");
+        assert_eq!(keys(&mut iter, true).len(), 401);
+
+        // Without prefix_same_as_start the extractor doesn't restrict the scan
+        let mut iter = db_iter(
+            &mem,
+            &tables,
+            &files,
+            &ReadOptions::default(),
+            Some(extractor),
+            &opened,
+        );
+        iter.seek(b"k-295");
+        assert_eq!(keys(&mut iter, true), range(295, 400));
+    }
+}
@@ -1,11 +1,18 @@
 #[cfg(test)]
 mod tests {
 
+    use crate::column_family::cf::ColumnFamilyDescriptor;
+    use crate::db::db_impl::DbImpl;
+    use crate::db::write_batch::Batch;
+    use crate::error::Error;
     use crate::key::comparator::InternalKeyComparator;
     use crate::key::internal_key::OperationType;
     use crate::key::lookup_key::{LookUpInternalKey, LookUpKey};
 
     use crate::memtable::memtable::*;
+    use crate::options::{ColumnFamilyOptions, DbOptions, ReadOptions, WriteBufferSize};
+    use crate::tests::helpers::{put, read};
+    use crate::tests::temp_dir::TempDir;
     use mem::allocator::*;
     use mem::arena::*;
 
@@ -29,11 +36,11 @@ mod tests {
         let k_4: LookUpInternalKey = LookUpKey::new(b"51.1.User1001", 4, OperationType::Delete);
         let k_wrong: LookUpInternalKey = LookUpKey::new(b"51.1.User1002", 5, OperationType::Put);
 
-        mem.insert(k_1.as_ref(), b"value_1");
-        mem.insert(k_2.as_ref(), b"value_2");
-        mem.insert(k_3.as_ref(), b"value_3");
-        mem.insert(k_4.as_ref(), b"");
-        mem.insert(k_wrong.as_ref(), b"value_4");
+        mem.insert(k_1.as_ref(), b"value_1").unwrap();
+        mem.insert(k_2.as_ref(), b"value_2").unwrap();
+        mem.insert(k_3.as_ref(), b"value_3").unwrap();
+        mem.insert(k_4.as_ref(), b"").unwrap();
+        mem.insert(k_wrong.as_ref(), b"value_4").unwrap();
 
         // Get the value for most recent seq no of 5
         let search_key: LookUpInternalKey = LookUpKey::new(b"51.1.User1001", 8, OperationType::Max);
@@ -48,7 +55,130 @@ mod tests {
 
     #[test]
     fn memtable_memory_usage() {
-
         // Test filling up a memtable and checking chunk usage is working
+        let mem = Memtable::new(
+            0,
+            ArenaPolicy {
+                block_size: 1024,
+                cap: 4096,
+            },
+            Allocator::System(SystemAllocator::new()),
+            InternalKeyComparator::new(),
+        );
+
+        let mut inserted = 0;
+        let full = loop {
+            let key: LookUpInternalKey = LookUpKey::new(
+                format!("key{inserted:04}").as_bytes(),
+                1,
+                OperationType::Put,
+            );
+            let used = mem.approximate_memory_usage();
+            match mem.insert(key.as_ref(), &[b'v'; 100]) {
+                Ok(()) => assert!(mem.approximate_memory_usage() > used),
+                Err(e) => break e,
+            }
+            inserted += 1;
+        };
+        assert!(matches!(full, Error::Busy(_)));
+        assert!(inserted > 0);
+        assert!(mem.approximate_memory_usage() <= 4096);
+
+        // A failed insert leaves every entry before it readable
+        for i in 0..inserted {
+            let key: LookUpInternalKey =
+                LookUpKey::new(format!("key{i:04}").as_bytes(), 1, OperationType::Max);
+            assert!(matches!(mem.get(key.as_ref()), MemReturn::Value(_)));
+        }
+    }
+
+    fn fill(db: &DbImpl, n: usize) {
+        for i in 0..n {
+            put(db, format!("key{i:05}"), [b'v'; 100]);
+        }
+    }
+
+    fn assert_filled(db: &DbImpl, n: usize) {
+        let cf = db.default_column_family();
+        for i in 0..n {
+            let value = db
+                .get(
+                    &ReadOptions::default(),
+                    &cf,
+                    format!("key{i:05}").as_bytes(),
+                )
+                .unwrap();
+            assert_eq!(value.as_deref(), Some(&[b'v'; 100][..]), "key{i:05}");
+        }
+    }
+
+    #[test]
+    fn full_memtables_are_switched_in_memory() {
+        let db = DbImpl::new();
+        fill(&db, 5000);
+
+        let cf = db.default_column_family();
+        assert!(!cf.data().immutable_memtables().memtables().is_empty());
+        assert_filled(&db, 5000);
+
+        let mut iter = db.new_iterator(&ReadOptions::default(), &cf).unwrap();
+        iter.seek_to_first();
+        let mut count = 0;
+        while iter.valid() {
+            count += 1;
+            iter.next();
+        }
+        assert_eq!(count, 5000);
+    }
+
+    #[test]
+    fn full_memtables_are_flushed_on_disk() {
+        let dir = TempDir::new("memtable-full-db");
+        let options = DbOptions {
+            create_if_missing: true,
+            ..Default::default()
+        };
+        let db = DbImpl::open(
+            dir.path(),
+            &options,
+            vec![ColumnFamilyDescriptor::new(
+                "default",
+                ColumnFamilyOptions::default(),
+            )],
+        )
+        .unwrap();
+        fill(&db, 5000);
+
+        let cf = db.default_column_family();
+        assert!(cf.data().current_version().files().next().is_some());
+        assert_filled(&db, 5000);
+
+        drop(cf);
+        drop(db);
+        let db = DbImpl::open(
+            dir.path(),
+            &options,
+            vec![ColumnFamilyDescriptor::new(
+                "default",
+                ColumnFamilyOptions::default(),
+            )],
+        )
+        .unwrap();
+        assert_filled(&db, 5000);
+    }
+
+    #[test]
+    fn entries_larger_than_a_memtable_block_are_rejected() {
+        let db = DbImpl::new();
+        let block_size = WriteBufferSize::Default.arena_policy().block_size;
+        let mut batch = Batch::new();
+        batch.put_cf(0, b"big", &vec![b'v'; block_size]);
+        assert!(matches!(
+            db.write(&mut batch),
+            Err(Error::InvalidArgument(_))
+        ));
+
+        assert_eq!(read(&db, "big"), None);
+        put(&db, "small", "v");
     }
 }
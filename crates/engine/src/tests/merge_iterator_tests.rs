@@ -0,0 +1,386 @@
+#[cfg(test)]
+mod tests {
+
+    use std::sync::Arc;
+
+    use crate::block::block_builder::BlockBuilder;
+    use crate::block::data_block::{Block, BlockIter};
+    use crate::error::{Error, Result};
+    use crate::iterator::internal_iterator::InternalIterator;
+    use crate::iterator::level_iterator::LevelIterator;
+    use crate::iterator::merge_iterator::MergingIterator;
+    use crate::key::comparator::{Comparator, InternalKeyComparator};
+    use crate::key::internal_key::{InternalKeyRef, OperationType, encode_trailer};
+    use crate::key::lookup_key::LookUpInternalKey;
+    use crate::memtable::memtable::*;
+    use crate::table::properties::TableProperties;
+    use crate::tests::helpers;
+    use crate::versioning::file_version::FileMetaData;
+
+    fn ikey(user_key: &[u8], seq: u64, op: OperationType) -> Vec<u8> {
+        [user_key, &encode_trailer(seq, op)].concat()
+    }
+
+    fn memtable(id: u64, entries: &[(&[u8], u64, &[u8])]) -> Memtable<Mutable> {
+        let mem = helpers::memtable(id);
+        for (k, seq, v) in entries {
+            mem.insert(
+                LookUpInternalKey::new(k, *seq, OperationType::Put).as_ref(),
+                v,
+            )
+            .unwrap();
+        }
+        mem
+    }
+
+    fn block(entries: &[(&[u8], u64, &[u8])]) -> Arc<Block> {
+        let mut sorted: Vec<(Vec<u8>, Vec<u8>)> = entries
+            .iter()
+            .map(|(k, seq, v)| (ikey(k, *seq, OperationType::Put), v.to_vec()))
+            .collect();
+        let comp = InternalKeyComparator::new();
+        sorted.sort_by(|a, b| comp.compare(&a.0, &b.0));
+
+        let mut builder = BlockBuilder::new(2);
+        for (k, v) in &sorted {
+            builder.add(k, v);
+        }
+        Arc::new(Block::new(builder.finish().to_vec()).unwrap())
+    }
+
+    fn file(number: u64, block: &Block, smallest: Vec<u8>, largest: Vec<u8>) -> Arc<FileMetaData> {
+        Arc::new(FileMetaData::new(
+            number,
+            block.size() as u64,
+            smallest,
+            largest,
+            0,
+            0,
+            TableProperties::default(),
+        ))
+    }
+
+    fn collect(iter: &mut dyn InternalIterator) -> Vec<(Vec<u8>, u64, Vec<u8>)> {
+        let mut out = Vec::new();
+        while iter.valid() {
+            let ik = InternalKeyRef::from(iter.key());
+            out.push((ik.user_key.to_vec(), ik.seq_no, iter.value().to_vec()));
+            iter.next();
+        }
+        out
+    }
+
+    fn collect_reverse(iter: &mut dyn InternalIterator) -> Vec<(Vec<u8>, u64, Vec<u8>)> {
+        let mut out = Vec::new();
+        while iter.valid() {
+            let ik = InternalKeyRef::from(iter.key());
+            out.push((ik.user_key.to_vec(), ik.seq_no, iter.value().to_vec()));
+            iter.prev();
+        }
+        out
+    }
+
+    // Yields the given entries and then fails instead of ending (in either direction)
+    struct FailingIter {
+        entries: Vec<(Vec<u8>, Vec<u8>)>,
+        pos: usize,
+        error: Option<Error>,
+    }
+
+    impl FailingIter {
+        fn new(entries: &[(&[u8], u64, &[u8])]) -> Self {
+            Self {
+                entries: entries
+                    .iter()
+                    .map(|(k, seq, v)| (ikey(k, *seq, OperationType::Put), v.to_vec()))
+                    .collect(),
+                pos: 0,
+                error: None,
+            }
+        }
+
+        fn check_end(&mut self) {
+            if self.pos >= self.entries.len() {
+                self.error = Some(Error::Corruption("bad block checksum".into()));
+            }
+        }
+    }
+
+    impl InternalIterator for FailingIter {
+        fn seek_to_first(&mut self) {
+            self.pos = 0;
+            self.check_end();
+        }
+
+        fn seek(&mut self, key: &[u8]) {
+            let comp = InternalKeyComparator::new();
+            self.pos = self
+                .entries
+                .partition_point(|(k, _)| comp.compare(k, key).is_lt());
+            self.check_end();
+        }
+
+        fn seek_to_last(&mut self) {
+            self.pos = self.entries.len().wrapping_sub(1);
+            self.check_end();
+        }
+
+        fn seek_for_prev(&mut self, key: &[u8]) {
+            let comp = InternalKeyComparator::new();
+            let count = self
+                .entries
+                .partition_point(|(k, _)| comp.compare(k, key).is_le());
+            self.pos = count.wrapping_sub(1);
+            self.check_end();
+        }
+
+        fn valid(&self) -> bool {
+            self.error.is_none() && self.pos < self.entries.len()
+        }
+
+        fn next(&mut self) {
+            self.pos += 1;
+            self.check_end();
+        }
+
+        fn prev(&mut self) {
+            self.pos = self.pos.wrapping_sub(1);
+            self.check_end();
+        }
+
+        fn key(&self) -> &[u8] {
+            &self.entries[self.pos].0
+        }
+
+        fn value(&self) -> &[u8] {
+            &self.entries[self.pos].1
+        }
+
+        fn status(&self) -> Option<&Error> {
+            self.error.as_ref()
+        }
+    }
+
+    #[test]
+    fn merges_memtables_blocks_and_levels() {
+        let comp: Arc<dyn Comparator> = InternalKeyComparator::new();
+
+        let mem = memtable(3, &[(b"b", 9, b"mem-b9"), (b"f", 8, b"mem-f8")]);
+        let imm = memtable(2, &[(b"b", 7, b"imm-b7"), (b"d", 6, b"imm-d6")]).freeze();
+
+        // An L0 table
+        let l0 = block(&[
+            (b"a", 5, b"l0-a5"),
+            (b"b", 4, b"l0-b4"),
+            (b"g", 5, b"l0-g5"),
+        ]);
+
+        // A level of three non-overlapping tables
+        let tables = [
+            block(&[(b"a", 1, b"l1-a1"), (b"c", 1, b"l1-c1")]),
+            block(&[(b"d", 2, b"l1-d2"), (b"e", 1, b"l1-e1")]),
+            block(&[(b"h", 3, b"l1-h3")]),
+        ];
+        let files = vec![
+            file(
+                10,
+                &tables[0],
+                ikey(b"a", 1, OperationType::Put),
+                ikey(b"c", 1, OperationType::Put),
+            ),
+            file(
+                11,
+                &tables[1],
+                ikey(b"d", 2, OperationType::Put),
+                ikey(b"e", 1, OperationType::Put),
+            ),
+            file(
+                12,
+                &tables[2],
+                ikey(b"h", 3, OperationType::Put),
+                ikey(b"h", 3, OperationType::Put),
+            ),
+        ];
+
+        let table_comp = Arc::clone(&comp);
+        let level = LevelIterator::new(
+            &files,
+            Arc::clone(&comp),
+            Box::new(
+                move |f: &FileMetaData| -> Result<Box<dyn InternalIterator>> {
+                    let iter: BlockIter =
+                        tables[(f.number - 10) as usize].iter(Arc::clone(&table_comp));
+                    Ok(Box::new(iter))
+                },
+            ),
+            None,
+        );
+
+        let mut iter = MergingIterator::new(
+            Arc::clone(&comp),
+            vec![
+                Box::new(mem.iter()),
+                Box::new(imm.iter()),
+                Box::new(l0.iter(Arc::clone(&comp))),
+                Box::new(level),
+            ],
+        );
+        assert_eq!(iter.num_children(), 4);
+
+        let expected: Vec<(Vec<u8>, u64, Vec<u8>)> = [
+            (b"a", 5, "l0-a5"),
+            (b"a", 1, "l1-a1"),
+            (b"b", 9, "mem-b9"),
+            (b"b", 7, "imm-b7"),
+            (b"b", 4, "l0-b4"),
+            (b"c", 1, "l1-c1"),
+            (b"d", 6, "imm-d6"),
+            (b"d", 2, "l1-d2"),
+            (b"e", 1, "l1-e1"),
+            (b"f", 8, "mem-f8"),
+            (b"g", 5, "l0-g5"),
+            (b"h", 3, "l1-h3"),
+        ]
+        .iter()
+        .map(|(k, seq, v)| (k.to_vec(), *seq, v.as_bytes().to_vec()))
+        .collect();
+
+        iter.seek_to_first();
+        assert_eq!(collect(&mut iter), expected);
+        assert!(iter.status().is_none());
+
+        // Seek into the middle of a version chain
+        iter.seek(&ikey(b"b", 6, OperationType::Max));
+        assert_eq!(collect(&mut iter), expected[4..]);
+
+        // Seek between files of the level
+        iter.seek(&ikey(b"cc", u64::MAX >> 8, OperationType::Max));
+        assert_eq!(collect(&mut iter), expected[6..]);
+
+        iter.seek(&ikey(b"z", 0, OperationType::Max));
+        assert!(!iter.valid());
+
+        // Reverse
+        let reversed: Vec<_> = expected.iter().rev().cloned().collect();
+
+        iter.seek_to_last();
+        assert_eq!(collect_reverse(&mut iter), reversed);
+
+        // Between files of the level
+        iter.seek_for_prev(&ikey(b"cc", 0, OperationType::Put));
+        assert_eq!(collect_reverse(&mut iter), reversed[6..]);
+
+        // Exact hit
+        iter.seek_for_prev(&ikey(b"b", 7, OperationType::Put));
+        assert_eq!(collect_reverse(&mut iter), reversed[8..]);
+
+        iter.seek_for_prev(&ikey(b"a", u64::MAX >> 8, OperationType::Max));
+        assert!(!iter.valid());
+
+        // Switch direction at every position - prev after next (and next after prev) returns to the same entry
+        for i in 0..expected.len() {
+            iter.seek_to_first();
+            for _ in 0..i {
+                iter.next();
+            }
+
+            if i + 1 < expected.len() {
+                iter.next();
+                iter.prev();
+                let ik = InternalKeyRef::from(iter.key());
+                assert_eq!(
+                    (ik.user_key, ik.seq_no),
+                    (&expected[i].0[..], expected[i].1)
+                );
+            }
+
+            if i > 0 {
+                iter.prev();
+                let ik = InternalKeyRef::from(iter.key());
+                assert_eq!(
+                    (ik.user_key, ik.seq_no),
+                    (&expected[i - 1].0[..], expected[i - 1].1)
+                );
+                iter.next();
+                let ik = InternalKeyRef::from(iter.key());
+                assert_eq!(
+                    (ik.user_key, ik.seq_no),
+                    (&expected[i].0[..], expected[i].1)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn child_error_stops_the_merge() {
+        let comp: Arc<dyn Comparator> = InternalKeyComparator::new();
+
+        let mem = memtable(1, &[(b"a", 9, b"a"), (b"c", 9, b"c"), (b"e", 9, b"e")]);
+
+        // Fails after "b" - nothing after it can be trusted
+        let mut iter = MergingIterator::new(
+            Arc::clone(&comp),
+            vec![
+                Box::new(mem.iter()),
+                Box::new(FailingIter::new(&[(b"b", 1, b"b")])),
+            ],
+        );
+
+        iter.seek_to_first();
+        let seen = collect(&mut iter);
+        assert_eq!(seen.len(), 2);
+        assert!(matches!(iter.status(), Some(Error::Corruption(_))));
+
+        // A child which fails while seeking invalidates the merge immediately
+        iter.seek(&ikey(b"d", 9, OperationType::Max));
+        assert!(!iter.valid());
+        assert!(iter.status().is_some());
+    }
+
+    #[test]
+    fn level_iterator_surfaces_open_errors() {
+        let comp: Arc<dyn Comparator> = InternalKeyComparator::new();
+
+        let table = block(&[(b"a", 1, b"a")]);
+        let files = vec![
+            file(
+                1,
+                &table,
+                ikey(b"a", 1, OperationType::Put),
+                ikey(b"a", 1, OperationType::Put),
+            ),
+            file(
+                2,
+                &table,
+                ikey(b"b", 1, OperationType::Put),
+                ikey(b"b", 1, OperationType::Put),
+            ),
+        ];
+
+        let table_comp = Arc::clone(&comp);
+        let mut level = LevelIterator::new(
+            &files,
+            Arc::clone(&comp),
+            Box::new(
+                move |f: &FileMetaData| -> Result<Box<dyn InternalIterator>> {
+                    if f.number == 2 {
+                        return Err(Error::Corruption("missing file 2".into()));
+                    }
+                    Ok(Box::new(table.iter(Arc::clone(&table_comp))))
+                },
+            ),
+            None,
+        );
+
+        level.seek_to_first();
+        assert!(level.valid());
+        level.next();
+        assert!(!level.valid());
+        assert!(matches!(level.status(), Some(Error::Corruption(_))));
+
+        // Seek straight to the broken file
+        level.seek(&ikey(b"b", 5, OperationType::Max));
+        assert!(!level.valid());
+        assert!(level.status().is_some());
+    }
+}
@@ -1,2 +1,26 @@
+pub mod backup_tests;
+pub mod blob_tests;
+pub mod checkpoint_tests;
+pub mod column_family_tests;
+pub mod comparator_tests;
+pub mod db_iter_tests;
+pub mod fifo_compaction_tests;
+pub mod helpers;
+pub mod ingest_external_file_tests;
 pub mod internal_iterator_tests;
+pub mod iterate_bounds_tests;
 pub mod memtable_tests;
+pub mod merge_iterator_tests;
+pub mod multi_get_tests;
+pub mod optimistic_transaction_tests;
+pub mod pessimistic_transaction_tests;
+pub mod range_del_tests;
+pub mod range_tombstone_tests;
+pub mod read_path_tests;
+pub mod seqno_to_time_tests;
+pub mod snapshot_tests;
+pub mod temp_dir;
+pub mod timestamp_tests;
+pub mod ttl_tests;
+pub mod two_phase_commit_tests;
+pub mod write_thread_tests;
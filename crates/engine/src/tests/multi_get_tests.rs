@@ -0,0 +1,196 @@
+#[cfg(test)]
+mod tests {
+
+    use std::cell::Cell;
+    use std::sync::Arc;
+
+    use crate::error::{Error, Result};
+    use crate::iterator::internal_iterator::InternalIterator;
+    use crate::key::comparator::{DefaultComparator, InternalKeyComparator};
+    use crate::key::internal_key::{OperationType, encode_trailer};
+    use crate::merge::operators::string_append::StringAppendOperator;
+    use crate::multi_get::{LevelFiles, MultiGetSource, multi_get};
+    use crate::table::table_builder::TableBuilder;
+    use crate::table::table_reader::Table;
+    use crate::tests::helpers::memtable_with;
+    use crate::versioning::file_version::FileMetaData;
+    use crate::versioning::memtable_list::MemListVersion;
+
+    // A level of 4 files with 100 keys each ("k-000" .. "k-399") in blocks of ~10 keys
+    fn level_tables() -> (Vec<Arc<Table>>, Vec<Arc<FileMetaData>>) {
+        let mut tables = Vec::new();
+        let mut files = Vec::new();
+
+        for file in 0..4 {
+            let mut builder = TableBuilder::new(256, 4);
+            for i in file * 100..(file + 1) * 100 {
+                builder.add(
+                    &[
+                        format!("k-{i:03}").as_bytes(),
+                        &encode_trailer(1, OperationType::Put),
+                    ]
+                    .concat(),
+                    format!("v{i}").as_bytes(),
+                );
+            }
+            let built = builder.finish();
+            files.push(Arc::new(built.file_meta(file as u64)));
+            tables.push(Table::open(built.data).unwrap());
+        }
+
+        (tables, files)
+    }
+
+    fn values(results: Vec<Result<Option<Vec<u8>>>>) -> Vec<Option<String>> {
+        results
+            .into_iter()
+            .map(|r| r.unwrap().map(|v| String::from_utf8(v).unwrap()))
+            .collect()
+    }
+
+    fn some(v: &str) -> Option<String> {
+        Some(v.to_string())
+    }
+
+    #[test]
+    fn resolves_keys_across_memtables_and_files() {
+        let (tables, files) = level_tables();
+        let level = LevelFiles::new(
+            &files,
+            Box::new(|f: &FileMetaData| {
+                Ok(
+                    Box::new(tables[f.number as usize].iter(InternalKeyComparator::new(), None))
+                        as Box<dyn InternalIterator>,
+                )
+            }),
+        );
+
+        let imm = memtable_with(&[
+            (b"k-010", 5, OperationType::Delete, b""),
+            (b"k-020", 6, OperationType::Merge, b"x"),
+        ]);
+        let imm = MemListVersion::new(vec![imm.freeze()]);
+        let mem = memtable_with(&[
+            (b"k-030", 8, OperationType::Put, b"new"),
+            // Newer than the read seq no
+            (b"k-040", 9, OperationType::Put, b"late"),
+            (b"zzz", 7, OperationType::Put, b"z"),
+        ]);
+
+        let sources: [&dyn MultiGetSource; 3] = [&mem, &imm, &level];
+        let keys: [&[u8]; 9] = [
+            b"k-020", b"missing", b"k-010", b"k-030", b"k-040", b"zzz", b"k-399", b"k-020",
+            b"k-000",
+        ];
+        let op = StringAppendOperator::new(b",");
+
+        let results = multi_get(&sources, &keys, 8, DefaultComparator::new(), Some(&op), 0);
+        assert_eq!(
+            values(results),
+            vec![
+                some("v20,x"),
+                None,
+                None,
+                some("new"),
+                some("v40"),
+                some("z"),
+                some("v399"),
+                some("v20,x"),
+                some("v0"),
+            ]
+        );
+    }
+
+    #[test]
+    fn each_file_and_data_block_is_read_once() {
+        let (tables, files) = level_tables();
+        let opens = Cell::new(0);
+        let level = LevelFiles::new(
+            &files,
+            Box::new(|f: &FileMetaData| {
+                opens.set(opens.get() + 1);
+                Ok(
+                    Box::new(tables[f.number as usize].iter(InternalKeyComparator::new(), None))
+                        as Box<dyn InternalIterator>,
+                )
+            }),
+        );
+
+        // Every 3rd key of the first three files, shuffled - several keys per data block
+        let mut owned: Vec<Vec<u8>> = (0..300)
+            .step_by(3)
+            .map(|i| format!("k-{i:03}").into_bytes())
+            .collect();
+        owned.reverse();
+        owned.swap(3, 60);
+        let keys: Vec<&[u8]> = owned.iter().map(|k| k.as_slice()).collect();
+
+        let sources: [&dyn MultiGetSource; 1] = [&level];
+        let results = values(multi_get(
+            &sources,
+            &keys,
+            1,
+            DefaultComparator::new(),
+            None,
+            0,
+        ));
+        for (key, value) in keys.iter().zip(results) {
+            let i: usize = std::str::from_utf8(&key[2..]).unwrap().parse().unwrap();
+            assert_eq!(value, Some(format!("v{i}")));
+        }
+
+        // The last file holds none of the keys
+        assert_eq!(opens.get(), 3);
+        assert_eq!(tables[3].data_block_reads(), 0);
+
+        // A full scan of a fresh copy of each file counts its blocks. Blocks holding none of the keys are never read
+        let (fresh, _) = level_tables();
+        for (table, fresh) in tables.iter().zip(&fresh).take(3) {
+            let mut iter = fresh.iter(InternalKeyComparator::new(), None);
+            iter.seek_to_first();
+            while iter.valid() {
+                iter.next();
+            }
+            assert!(fresh.data_block_reads() > 1);
+            assert!(table.data_block_reads() <= fresh.data_block_reads());
+            assert!(table.data_block_reads() > 0);
+        }
+    }
+
+    #[test]
+    fn a_failing_file_only_fails_its_keys() {
+        let (tables, files) = level_tables();
+        let level = LevelFiles::new(
+            &files,
+            Box::new(|f: &FileMetaData| {
+                if f.number == 1 {
+                    return Err(Error::Corruption("bad file".into()));
+                }
+                Ok(
+                    Box::new(tables[f.number as usize].iter(InternalKeyComparator::new(), None))
+                        as Box<dyn InternalIterator>,
+                )
+            }),
+        );
+        // Resolved before the files are searched
+        let mem = memtable_with(&[(b"k-150", 2, OperationType::Put, b"mem")]);
+
+        let sources: [&dyn MultiGetSource; 2] = [&mem, &level];
+        let keys: [&[u8]; 4] = [b"k-050", b"k-120", b"k-150", b"k-250"];
+
+        let results = multi_get(&sources, &keys, 2, DefaultComparator::new(), None, 0);
+        assert_eq!(
+            results[0].as_ref().unwrap().as_deref(),
+            Some(b"v50".as_slice())
+        );
+        assert!(matches!(results[1], Err(Error::Corruption(_))));
+        assert_eq!(
+            results[2].as_ref().unwrap().as_deref(),
+            Some(b"mem".as_slice())
+        );
+        assert_eq!(
+            results[3].as_ref().unwrap().as_deref(),
+            Some(b"v250".as_slice())
+        );
+    }
+}
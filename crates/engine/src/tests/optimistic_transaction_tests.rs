@@ -0,0 +1,184 @@
+#[cfg(test)]
+mod tests {
+
+    use std::thread;
+
+    use crate::column_family::cf::ColumnFamilyDescriptor;
+    use crate::db::db_impl::DbImpl;
+    use crate::error::Error;
+    use crate::options::{ColumnFamilyOptions, DbOptions, ReadOptions};
+    use crate::tests::helpers::{put_cf, read_cf};
+    use crate::tests::temp_dir::TempDir;
+    use crate::transaction::optimistic::OptimisticTransaction;
+
+    #[test]
+    fn reads_see_own_writes_until_commit() {
+        let db = DbImpl::new();
+        let cf = db.default_column_family();
+        put_cf(&db, &cf, "a", "a0");
+        put_cf(&db, &cf, "b", "b0");
+
+        let mut txn = OptimisticTransaction::new(&db);
+        txn.put(&cf, b"a", b"a1");
+        txn.delete(&cf, b"b");
+        txn.put(&cf, b"c", b"c1");
+
+        let options = ReadOptions::default();
+        assert_eq!(txn.get(&options, &cf, b"a").unwrap(), Some(b"a1".to_vec()));
+        assert_eq!(txn.get(&options, &cf, b"b").unwrap(), None);
+        assert_eq!(txn.get(&options, &cf, b"d").unwrap(), None);
+
+        // Nothing is visible outside the transaction before it commits
+        assert_eq!(read_cf(&db, &cf, "a").as_deref(), Some("a0"));
+        assert_eq!(read_cf(&db, &cf, "c"), None);
+
+        txn.commit().unwrap();
+        assert_eq!(db.last_sequence(), 5);
+        assert_eq!(read_cf(&db, &cf, "a").as_deref(), Some("a1"));
+        assert_eq!(read_cf(&db, &cf, "b"), None);
+        assert_eq!(read_cf(&db, &cf, "c").as_deref(), Some("c1"));
+    }
+
+    #[test]
+    fn overwritten_reads_fail_the_commit() {
+        let db = DbImpl::new();
+        let cf = db.default_column_family();
+        let other = db
+            .create_column_family("other", ColumnFamilyOptions::default())
+            .unwrap();
+        put_cf(&db, &cf, "k", "0");
+
+        let mut txn = OptimisticTransaction::new(&db);
+        assert_eq!(
+            txn.get(&ReadOptions::default(), &cf, b"k").unwrap(),
+            Some(b"0".to_vec())
+        );
+        txn.put(&cf, b"k", b"from txn");
+
+        // Writes to other keys and other column families don't conflict
+        put_cf(&db, &cf, "j", "x");
+        put_cf(&db, &other, "k", "x");
+        let last = db.last_sequence();
+
+        put_cf(&db, &cf, "k", "1");
+        assert!(matches!(txn.commit(), Err(Error::Busy(_))));
+        assert_eq!(read_cf(&db, &cf, "k").as_deref(), Some("1"));
+        assert_eq!(db.last_sequence(), last + 1);
+
+        // Blind writes are validated too
+        let mut txn = OptimisticTransaction::new(&db);
+        txn.put(&cf, b"k", b"from txn");
+        put_cf(&db, &cf, "k", "2");
+        assert!(matches!(txn.commit(), Err(Error::Busy(_))));
+
+        // Running the transaction again succeeds
+        let mut txn = OptimisticTransaction::new(&db);
+        txn.get(&ReadOptions::default(), &cf, b"k").unwrap();
+        txn.put(&cf, b"k", b"from txn");
+        put_cf(&db, &cf, "j", "y");
+        txn.commit().unwrap();
+        assert_eq!(read_cf(&db, &cf, "k").as_deref(), Some("from txn"));
+    }
+
+    #[test]
+    fn snapshot_and_range_deletion_conflicts() {
+        let db = DbImpl::new();
+        let cf = db.default_column_family();
+        put_cf(&db, &cf, "k", "0");
+
+        // With a snapshot, a write after it conflicts even though it happened before the read
+        let mut txn = OptimisticTransaction::new(&db);
+        txn.set_snapshot();
+        put_cf(&db, &cf, "k", "1");
+        let options = ReadOptions {
+            snapshot: txn.snapshot().cloned(),
+            ..Default::default()
+        };
+        assert_eq!(txn.get(&options, &cf, b"k").unwrap(), Some(b"0".to_vec()));
+        assert!(matches!(txn.commit(), Err(Error::Busy(_))));
+
+        // A range deletion over a read key is a write to it
+        let mut txn = OptimisticTransaction::new(&db);
+        txn.get(&ReadOptions::default(), &cf, b"k").unwrap();
+        txn.put(&cf, b"z", b"1");
+        db.delete_range(&cf, b"a", b"m").unwrap();
+        assert!(matches!(txn.commit(), Err(Error::Busy(_))));
+        assert_eq!(read_cf(&db, &cf, "z"), None);
+    }
+
+    #[test]
+    fn writes_flushed_since_a_read_fail_the_commit() {
+        let dir = TempDir::new("optimistic-flush-db");
+        let options = DbOptions {
+            create_if_missing: true,
+            ..Default::default()
+        };
+        let db = DbImpl::open(
+            dir.path(),
+            &options,
+            vec![ColumnFamilyDescriptor::new(
+                "default",
+                ColumnFamilyOptions::default(),
+            )],
+        )
+        .unwrap();
+        let cf = db.default_column_family();
+        put_cf(&db, &cf, "k", "0");
+
+        // The newer write to k is only in a table file when the commit checks the memtables
+        let mut txn = OptimisticTransaction::new(&db);
+        txn.get(&ReadOptions::default(), &cf, b"k").unwrap();
+        txn.put(&cf, b"k", b"from txn");
+        put_cf(&db, &cf, "k", "1");
+        db.flush(&cf).unwrap();
+        assert!(matches!(txn.commit(), Err(Error::Busy(_))));
+        assert_eq!(read_cf(&db, &cf, "k").as_deref(), Some("1"));
+
+        // A transaction which starts after the flush is validated against the new memtable
+        let mut txn = OptimisticTransaction::new(&db);
+        txn.get(&ReadOptions::default(), &cf, b"k").unwrap();
+        txn.put(&cf, b"k", b"from txn");
+        txn.commit().unwrap();
+        assert_eq!(read_cf(&db, &cf, "k").as_deref(), Some("from txn"));
+    }
+
+    #[test]
+    fn concurrent_increments_are_serialized() {
+        const THREADS: usize = 4;
+        const INCREMENTS: usize = 50;
+
+        let db = DbImpl::new();
+        let cf = db.default_column_family();
+        put_cf(&db, &cf, "counter", "0");
+
+        thread::scope(|s| {
+            for _ in 0..THREADS {
+                s.spawn(|| {
+                    for _ in 0..INCREMENTS {
+                        loop {
+                            let mut txn = OptimisticTransaction::new(&db);
+                            let value = txn
+                                .get(&ReadOptions::default(), &cf, b"counter")
+                                .unwrap()
+                                .unwrap();
+                            let n: usize = String::from_utf8(value).unwrap().parse().unwrap();
+                            txn.put(&cf, b"counter", (n + 1).to_string().as_bytes());
+                            match txn.commit() {
+                                Ok(()) => break,
+                                Err(Error::Busy(_)) => continue,
+                                Err(e) => panic!("{e}"),
+                            }
+                        }
+                    }
+                });
+            }
+        });
+
+        // No increment is lost - every conflicting commit was retried
+        assert_eq!(
+            read_cf(&db, &cf, "counter"),
+            Some((THREADS * INCREMENTS).to_string())
+        );
+        assert_eq!(db.last_sequence(), (1 + THREADS * INCREMENTS) as u64);
+    }
+}
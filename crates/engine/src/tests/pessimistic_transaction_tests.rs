@@ -0,0 +1,195 @@
+#[cfg(test)]
+mod tests {
+
+    use std::thread;
+    use std::time::Duration;
+
+    use crate::db::db_impl::DbImpl;
+    use crate::error::Error;
+    use crate::options::{ReadOptions, TransactionDbOptions, TransactionOptions};
+    use crate::tests::helpers::read_cf;
+    use crate::transaction::lock_manager::TransactionId;
+    use crate::transaction::transaction_db::TransactionDB;
+
+    fn txn_db() -> TransactionDB {
+        TransactionDB::new(
+            DbImpl::new(),
+            TransactionDbOptions {
+                num_stripes: 4,
+                lock_timeout: Duration::from_secs(10),
+            },
+        )
+    }
+
+    fn detect() -> TransactionOptions {
+        TransactionOptions {
+            deadlock_detect: true,
+            ..Default::default()
+        }
+    }
+
+    // Blocks until `txn` waits for `holder` in the lock manager
+    fn wait_until_blocked(db: &TransactionDB, txn: TransactionId, holder: TransactionId) {
+        while db.lock_manager().waiting_for(txn) != Some(holder) {
+            thread::yield_now();
+        }
+    }
+
+    #[test]
+    fn locked_keys_block_other_writers() {
+        let db = txn_db();
+        let cf = db.db().default_column_family();
+        db.put(&cf, b"k", b"0").unwrap();
+
+        let mut txn = db.begin_transaction(&TransactionOptions::default());
+        assert_eq!(
+            txn.get_for_update(&ReadOptions::default(), &cf, b"k")
+                .unwrap(),
+            Some(b"0".to_vec())
+        );
+        txn.put(&cf, b"k", b"1").unwrap();
+        assert_eq!(txn.num_locks(), 1);
+
+        // A writer with a short timeout gives up, the key is unchanged
+        let mut other = db.begin_transaction(&TransactionOptions {
+            lock_timeout: Some(Duration::from_millis(10)),
+            ..Default::default()
+        });
+        assert!(matches!(
+            other.put(&cf, b"k", b"2"),
+            Err(Error::TimedOut(_))
+        ));
+        // Keys nobody holds are not affected
+        other.put(&cf, b"j", b"2").unwrap();
+        other.commit().unwrap();
+
+        thread::scope(|s| {
+            let writer = s.spawn(|| db.put(&cf, b"k", b"2"));
+            thread::sleep(Duration::from_millis(20));
+            assert_eq!(read_cf(db.db(), &cf, "k").as_deref(), Some("0"));
+            txn.commit().unwrap();
+            writer.join().unwrap().unwrap();
+        });
+        assert_eq!(read_cf(db.db(), &cf, "k").as_deref(), Some("2"));
+        assert_eq!(read_cf(db.db(), &cf, "j").as_deref(), Some("2"));
+    }
+
+    #[test]
+    fn deadlock_is_detected_and_broken() {
+        let db = txn_db();
+        let cf = db.db().default_column_family();
+
+        let mut t1 = db.begin_transaction(&detect());
+        let mut t2 = db.begin_transaction(&detect());
+        let (id1, id2) = (t1.id(), t2.id());
+        t1.put(&cf, b"a", b"t1").unwrap();
+        t2.put(&cf, b"b", b"t2").unwrap();
+
+        thread::scope(|s| {
+            let waiter = s.spawn(|| {
+                // Waits for t2 until the deadlock is broken
+                t1.put(&cf, b"b", b"t1")?;
+                t1.commit()
+            });
+            wait_until_blocked(&db, id1, id2);
+
+            assert!(matches!(t2.put(&cf, b"a", b"t2"), Err(Error::Busy(_))));
+            // t2 still holds b, rolling it back lets t1 go on
+            t2.rollback().unwrap();
+            waiter.join().unwrap().unwrap();
+        });
+
+        assert_eq!(read_cf(db.db(), &cf, "a").as_deref(), Some("t1"));
+        assert_eq!(read_cf(db.db(), &cf, "b").as_deref(), Some("t1"));
+        assert_eq!(db.lock_manager().waiting_for(id1), None);
+    }
+
+    #[test]
+    fn longer_cycles_are_detected() {
+        let db = txn_db();
+        let cf = db.db().default_column_family();
+
+        let mut txns: Vec<_> = (0..3).map(|_| db.begin_transaction(&detect())).collect();
+        let ids: Vec<TransactionId> = txns.iter().map(|t| t.id()).collect();
+        for (txn, key) in txns.iter_mut().zip(["a", "b", "c"]) {
+            txn.put(&cf, key.as_bytes(), b"0").unwrap();
+        }
+        let mut t3 = txns.pop().unwrap();
+        let mut t2 = txns.pop().unwrap();
+        let mut t1 = txns.pop().unwrap();
+
+        let cf = &cf;
+        thread::scope(|s| {
+            // t1 -> t2 -> t3. The waiters hand their transaction back once they have the lock
+            let w1 = s.spawn(move || {
+                t1.get_for_update(&ReadOptions::default(), cf, b"b")
+                    .map(|_| t1)
+            });
+            wait_until_blocked(&db, ids[0], ids[1]);
+            let w2 = s.spawn(move || {
+                t2.get_for_update(&ReadOptions::default(), cf, b"c")
+                    .map(|_| t2)
+            });
+            wait_until_blocked(&db, ids[1], ids[2]);
+
+            // t3 -> t1 closes the cycle
+            assert!(matches!(
+                t3.get_for_update(&ReadOptions::default(), cf, b"a"),
+                Err(Error::Busy(_))
+            ));
+            drop(t3);
+            let t2 = w2.join().unwrap().unwrap();
+            assert_eq!(t2.num_locks(), 2);
+            drop(t2);
+            let t1 = w1.join().unwrap().unwrap();
+            assert_eq!(t1.num_locks(), 2);
+        });
+    }
+
+    #[test]
+    fn concurrent_transfers_survive_deadlocks() {
+        const THREADS: usize = 4;
+        const TRANSFERS: usize = 40;
+
+        let db = txn_db();
+        let cf = db.db().default_column_family();
+        db.put(&cf, b"x", b"0").unwrap();
+        db.put(&cf, b"y", b"0").unwrap();
+
+        thread::scope(|s| {
+            for n in 0..THREADS {
+                let (db, cf) = (&db, &cf);
+                s.spawn(move || {
+                    // Half of the threads lock in the opposite order, so cycles keep forming
+                    let keys: [&[u8]; 2] = if n % 2 == 0 {
+                        [b"x", b"y"]
+                    } else {
+                        [b"y", b"x"]
+                    };
+                    for _ in 0..TRANSFERS {
+                        'retry: loop {
+                            let mut txn = db.begin_transaction(&detect());
+                            for key in keys {
+                                let value =
+                                    match txn.get_for_update(&ReadOptions::default(), cf, key) {
+                                        Ok(value) => value.unwrap(),
+                                        // The transaction is dropped, which releases its locks
+                                        Err(Error::Busy(_)) => continue 'retry,
+                                        Err(e) => panic!("{e}"),
+                                    };
+                                let n: u64 = String::from_utf8(value).unwrap().parse().unwrap();
+                                txn.put(cf, key, (n + 1).to_string().as_bytes()).unwrap();
+                            }
+                            txn.commit().unwrap();
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+
+        let total = (THREADS * TRANSFERS).to_string();
+        assert_eq!(read_cf(db.db(), &cf, "x"), Some(total.clone()));
+        assert_eq!(read_cf(db.db(), &cf, "y"), Some(total));
+    }
+}
@@ -0,0 +1,505 @@
+#[cfg(test)]
+mod tests {
+
+    use std::cmp::Ordering;
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    use crate::column_family::cf::ColumnFamilyDescriptor;
+    use crate::compaction::compaction_iter::CompactionIterator;
+    use crate::compaction::filter::{CompactionFilterContext, TableFileCreationReason};
+    use crate::db::db_impl::DbImpl;
+    use crate::db::wal::wal_files;
+    use crate::db::write_batch::Batch;
+    use crate::iterator::internal_iterator::InternalIterator;
+    use crate::key::comparator::{Comparator, DefaultComparator};
+    use crate::key::internal_key::{InternalKeyRef, OperationType};
+    use crate::key::lookup_key::LookUpInternalKey;
+    use crate::options::{ColumnFamilyOptions, DbOptions, ReadOptions, WriteBufferSize};
+    use crate::range::index::RangeDelIndex;
+    use crate::tests::helpers::{memtable, put, read_with, scan_keys};
+    use crate::tests::temp_dir::TempDir;
+    use crate::versioning::file_version::NUM_LEVELS;
+
+    // The keys of the default column family from the last one back
+    fn reverse_keys(db: &DbImpl, options: &ReadOptions) -> Vec<String> {
+        let cf = db.default_column_family();
+        let mut iter = db.new_iterator(options, &cf).unwrap();
+
+        let mut out = Vec::new();
+        iter.seek_to_last();
+        while iter.valid() {
+            out.push(String::from_utf8(iter.key().to_vec()).unwrap());
+            iter.prev();
+        }
+        out
+    }
+
+    #[test]
+    fn reads_skip_range_deleted_keys() {
+        let db = DbImpl::new();
+        let cf = db.default_column_family();
+
+        for key in ["a", "b", "c", "d", "e"] {
+            put(&db, key.as_bytes(), b"old");
+        }
+        let before = db.get_snapshot();
+
+        db.delete_range(&cf, b"b", b"d").unwrap();
+        // Written after the tombstone - not covered by it
+        put(&db, b"c", b"new");
+
+        let options = ReadOptions::default();
+        assert_eq!(read_with(&db, &options, &cf, b"a"), Some("old".to_string()));
+        assert_eq!(read_with(&db, &options, &cf, b"b"), None);
+        assert_eq!(read_with(&db, &options, &cf, b"c"), Some("new".to_string()));
+        assert_eq!(read_with(&db, &options, &cf, b"d"), Some("old".to_string()));
+
+        assert_eq!(scan_keys(&db, &options, &cf), vec!["a", "c", "d", "e"]);
+        assert_eq!(reverse_keys(&db, &options), vec!["e", "d", "c", "a"]);
+
+        // The snapshot was taken before the tombstone
+        let snapshot = ReadOptions {
+            snapshot: Some(before),
+            ..Default::default()
+        };
+        assert_eq!(
+            read_with(&db, &snapshot, &cf, b"b"),
+            Some("old".to_string())
+        );
+        assert_eq!(scan_keys(&db, &snapshot, &cf).len(), 5);
+
+        let keys: [&[u8]; 3] = [b"a", b"b", b"c"];
+        let results: Vec<Option<Vec<u8>>> = db
+            .multi_get(&options, &cf, &keys)
+            .into_iter()
+            .map(|r| r.unwrap())
+            .collect();
+        assert_eq!(
+            results,
+            vec![Some(b"old".to_vec()), None, Some(b"new".to_vec())]
+        );
+
+        assert!(db.delete_range(&cf, b"z", b"a").is_err());
+    }
+
+    #[test]
+    fn range_deletions_survive_a_reopen() {
+        let dir = TempDir::new("range-del-recovery-db");
+        let options = DbOptions {
+            create_if_missing: true,
+            ..Default::default()
+        };
+        let open = || DbImpl::open(dir.path(), &options, Vec::new()).unwrap();
+        let read_options = ReadOptions::default();
+
+        let db = open();
+        let cf = db.default_column_family();
+        for key in ["a", "b", "c", "d"] {
+            put(&db, key.as_bytes(), b"v");
+        }
+        db.flush(&cf).unwrap();
+        db.delete_range(&cf, b"a", b"c").unwrap();
+        put(&db, b"a", b"new");
+        let sequence = db.last_sequence();
+
+        // Replayed from the WAL
+        drop(cf);
+        drop(db);
+        let db = open();
+        let cf = db.default_column_family();
+        assert_eq!(db.last_sequence(), sequence);
+        assert_eq!(scan_keys(&db, &read_options, &cf), vec!["a", "c", "d"]);
+        assert_eq!(
+            read_with(&db, &read_options, &cf, b"a"),
+            Some("new".to_string())
+        );
+
+        // Logged to the MANIFEST by the flush which releases the WAL
+        db.flush(&cf).unwrap();
+        db.delete_range(&cf, b"d", b"e").unwrap();
+        put(&db, b"e", b"v");
+        db.flush(&cf).unwrap();
+        assert!(db.purge_obsolete_wal_files().unwrap() > 0);
+        assert_eq!(wal_files(dir.path()).unwrap().len(), 1);
+        drop(cf);
+        drop(db);
+        let db = open();
+        let cf = db.default_column_family();
+        assert_eq!(scan_keys(&db, &read_options, &cf), vec!["a", "c", "e"]);
+        assert_eq!(read_with(&db, &read_options, &cf, b"b"), None);
+    }
+
+    #[test]
+    fn compaction_collects_tombstones_older_than_every_key() {
+        let dir = TempDir::new("range-del-compaction-db");
+        let options = DbOptions {
+            create_if_missing: true,
+            ..Default::default()
+        };
+        let db = DbImpl::open(dir.path(), &options, Vec::new()).unwrap();
+        let cf = db.default_column_family();
+        let read_options = ReadOptions::default();
+
+        put(&db, b"b", b"old");
+        put(&db, b"c", b"old");
+        let before = db.get_snapshot();
+        db.delete_range(&cf, b"b", b"d").unwrap();
+        put(&db, b"a", b"v");
+        put(&db, b"d", b"v");
+        db.flush(&cf).unwrap();
+
+        // The snapshot still reads b and c, which the tombstone has to keep hiding from everyone else
+        db.compact_range(&cf).unwrap();
+        assert!(!cf.data().range_del().version().is_empty());
+        assert_eq!(scan_keys(&db, &read_options, &cf), vec!["a", "d"]);
+
+        drop(before);
+        db.compact_range(&cf).unwrap();
+        assert!(cf.data().range_del().version().is_empty());
+        assert_eq!(scan_keys(&db, &read_options, &cf), vec!["a", "d"]);
+    }
+
+    #[test]
+    fn compaction_collects_overlapping_tombstones() {
+        let dir = TempDir::new("range-del-overlap-gc-db");
+        let options = DbOptions {
+            create_if_missing: true,
+            ..Default::default()
+        };
+        let db = DbImpl::open(dir.path(), &options, Vec::new()).unwrap();
+        let cf = db.default_column_family();
+
+        put(&db, b"z", b"v");
+        db.flush(&cf).unwrap();
+        db.delete_range(&cf, b"a", b"d").unwrap();
+        db.delete_range(&cf, b"b", b"e").unwrap();
+        let _snapshot = db.get_snapshot();
+        db.compact_range(&cf).unwrap();
+
+        // Fragmenting these against the run left by the last collection yields more entries than were buffered
+        db.delete_range(&cf, b"c", b"f").unwrap();
+        db.delete_range(&cf, b"a", b"c").unwrap();
+        db.compact_range(&cf).unwrap();
+        assert_eq!(
+            scan_keys(&db, &ReadOptions::default(), &cf),
+            vec!["z".to_string()]
+        );
+    }
+
+    #[test]
+    fn per_file_range_deletions_go_into_the_table_files() {
+        let dir = TempDir::new("range-del-per-file-db");
+        let options = DbOptions {
+            create_if_missing: true,
+            ..Default::default()
+        };
+        let cf_options = ColumnFamilyOptions {
+            per_file_range_deletions: true,
+            target_file_size_base: 512,
+            ..Default::default()
+        };
+        let open = || {
+            DbImpl::open(
+                dir.path(),
+                &options,
+                vec![ColumnFamilyDescriptor::new("default", cf_options.clone())],
+            )
+            .unwrap()
+        };
+        let read_options = ReadOptions::default();
+        let key = |i: u32| format!("k{i:03}").into_bytes();
+
+        let db = open();
+        let cf = db.default_column_family();
+        for i in 0..200 {
+            put(&db, &key(i), b"value");
+        }
+        db.flush(&cf).unwrap();
+        db.delete_range(&cf, &key(190), &key(200)).unwrap();
+        assert!(cf.data().range_del().version().is_empty());
+        assert_eq!(read_with(&db, &read_options, &cf, &key(195)), None);
+
+        // Replayed into the memtable from the WAL
+        drop(cf);
+        drop(db);
+        let db = open();
+        let cf = db.default_column_family();
+        assert_eq!(read_with(&db, &read_options, &cf, &key(195)), None);
+        assert_eq!(scan_keys(&db, &read_options, &cf).len(), 190);
+
+        let before = db.get_snapshot();
+        db.delete_range(&cf, &key(50), &key(150)).unwrap();
+        assert_eq!(read_with(&db, &read_options, &cf, &key(100)), None);
+
+        // A memtable holding only tombstones is flushed into a file of its own
+        db.flush(&cf).unwrap();
+        let version = cf.data().current_version();
+        let flushed = &version.level_files(0)[0];
+        assert_eq!(flushed.properties.num_entries, 0);
+        assert_eq!(flushed.properties.num_range_deletions, 2);
+        assert_eq!(read_with(&db, &read_options, &cf, &key(149)), None);
+        assert_eq!(
+            read_with(&db, &read_options, &cf, &key(150)),
+            Some("value".to_string())
+        );
+
+        // The snapshot keeps the covered keys and the tombstone, which is cut at the boundaries of the output files
+        db.compact_range(&cf).unwrap();
+        let version = cf.data().current_version();
+        let files = version.level_files(NUM_LEVELS - 1);
+        assert!(files.len() > 2);
+        let icmp = version.internal_comparator();
+        assert!(
+            files
+                .windows(2)
+                .all(|w| icmp.compare(&w[0].largest, &w[1].smallest) == Ordering::Less)
+        );
+        let with_tombstones = files
+            .iter()
+            .filter(|f| f.properties.num_range_deletions > 0)
+            .count();
+        assert!(with_tombstones > 1);
+        for i in [0, 49, 150, 189] {
+            assert_eq!(
+                read_with(&db, &read_options, &cf, &key(i)),
+                Some("value".to_string())
+            );
+        }
+        for i in [50, 100, 149, 190] {
+            assert_eq!(read_with(&db, &read_options, &cf, &key(i)), None);
+        }
+        assert_eq!(scan_keys(&db, &read_options, &cf).len(), 90);
+        assert_eq!(reverse_keys(&db, &read_options).len(), 90);
+        let snapshot = ReadOptions {
+            snapshot: Some(Arc::clone(&before)),
+            ..Default::default()
+        };
+        assert_eq!(
+            read_with(&db, &snapshot, &cf, &key(100)),
+            Some("value".to_string())
+        );
+        assert_eq!(scan_keys(&db, &snapshot, &cf).len(), 190);
+
+        // Nothing needs the tombstone anymore, the bottommost compaction drops it with what it covers
+        drop(snapshot);
+        drop(before);
+        db.compact_range(&cf).unwrap();
+        let version = cf.data().current_version();
+        let files = version.level_files(NUM_LEVELS - 1);
+        assert!(files.iter().all(|f| f.properties.num_range_deletions == 0));
+        assert_eq!(
+            files.iter().map(|f| f.properties.num_entries).sum::<u64>(),
+            90
+        );
+        assert_eq!(scan_keys(&db, &read_options, &cf).len(), 90);
+    }
+
+    fn ctx() -> CompactionFilterContext {
+        CompactionFilterContext {
+            column_family_id: 0,
+            level: 1,
+            is_full_compaction: false,
+            is_manual_compaction: false,
+            reason: TableFileCreationReason::Compaction,
+        }
+    }
+
+    #[test]
+    fn compaction_drops_covered_keys_and_gc_drops_tombstones() {
+        let mem = memtable(1);
+        for (key, seq) in [("a", 1), ("b", 2), ("b", 6), ("c", 3), ("d", 4)] {
+            mem.insert(
+                LookUpInternalKey::new(key.as_bytes(), seq, OperationType::Put).as_ref(),
+                b"v",
+            )
+            .unwrap();
+        }
+
+        let index = RangeDelIndex::new(DefaultComparator::new());
+        index.insert(b"b", b"d", 5).unwrap();
+        let version = index.version();
+
+        // A snapshot at 4 still reads c@3 and b@2, so only versions in the tombstone's stripe go
+        let comp = DefaultComparator {};
+        let snapshots = [4];
+        let mut iter = CompactionIterator::new(mem.iter(), &comp, &snapshots, None, ctx(), true)
+            .with_range_del(&version);
+        let kept = collect(&mut iter);
+        assert_eq!(kept, vec![("a", 1), ("b", 6), ("b", 2), ("c", 3), ("d", 4)]);
+        assert_eq!(iter.stats().num_dropped_range_del, 0);
+
+        let mut iter = CompactionIterator::new(mem.iter(), &comp, &[], None, ctx(), true)
+            .with_range_del(&version);
+        let kept = collect(&mut iter);
+        assert_eq!(kept, vec![("a", 1), ("b", 6), ("d", 4)]);
+        // b@2 is already hidden by b@6
+        assert_eq!(iter.stats().num_dropped_hidden, 1);
+        assert_eq!(iter.stats().num_dropped_range_del, 1);
+
+        // Once nothing older than the tombstone is left it has nothing to hide
+        assert_eq!(index.collect_garbage(4, &[6]), 0);
+        assert_eq!(index.collect_garbage(5, &[6]), 1);
+        assert!(index.version().is_empty());
+    }
+
+    fn collect<I: InternalIterator>(
+        iter: &mut CompactionIterator<'_, I>,
+    ) -> Vec<(&'static str, u64)> {
+        let mut out = Vec::new();
+        iter.seek_to_first();
+        while iter.valid() {
+            let ik = InternalKeyRef::from(iter.key());
+            let key: &'static str = String::from_utf8(ik.user_key.to_vec()).unwrap().leak();
+            out.push((key, ik.seq_no));
+            iter.next();
+        }
+        out
+    }
+
+    // Benchmark: one global index against tombstones stored with the SST files they overlap.
+    //
+    // The same keys and tombstones are written into two DBs, one keeping its tombstones in the RangeDelIndex and one with
+    // per_file_range_deletions, and both are compacted into small files. A snapshot older than the tombstones keeps them and the keys
+    // they cover. Only the point lookups are timed - a lookup in the first DB queries the index, in the second it checks the tombstones
+    // of the files it reads. Run with:
+    //
+    //   cargo test -p engine --release range_del_benchmark -- --ignored --nocapture
+
+    const BENCH_TARGET_FILE_SIZE: u64 = 16 * 1024;
+
+    fn bench_key(i: u64) -> Vec<u8> {
+        format!("{:08}", i).into_bytes()
+    }
+
+    // Deterministic xorshift so the runs are comparable
+    fn next_random(state: &mut u64) -> u64 {
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+        *state
+    }
+
+    // Writes every key of the key space and the tombstones, and compacts them into files of BENCH_TARGET_FILE_SIZE
+    fn open_bench_db(
+        dir: &TempDir,
+        per_file_range_deletions: bool,
+        key_space: u64,
+        tombstones: &[(Vec<u8>, Vec<u8>)],
+    ) -> DbImpl {
+        let options = DbOptions {
+            create_if_missing: true,
+            ..Default::default()
+        };
+        let cf_options = ColumnFamilyOptions {
+            per_file_range_deletions,
+            target_file_size_base: BENCH_TARGET_FILE_SIZE,
+            write_buffer_size: WriteBufferSize::Large,
+            ..Default::default()
+        };
+        let db = DbImpl::open(
+            dir.path(),
+            &options,
+            vec![ColumnFamilyDescriptor::new("default", cf_options)],
+        )
+        .unwrap();
+        let cf = db.default_column_family();
+
+        for chunk in (0..key_space).collect::<Vec<_>>().chunks(1000) {
+            let mut batch = Batch::new();
+            for i in chunk {
+                batch.put_cf(0, &bench_key(*i), b"value");
+            }
+            db.write(&mut batch).unwrap();
+        }
+        db.flush(&cf).unwrap();
+        let _before = db.get_snapshot();
+        for (start, end) in tombstones {
+            db.delete_range(&cf, start, end).unwrap();
+        }
+        db.flush(&cf).unwrap();
+        db.compact_range(&cf).unwrap();
+        db
+    }
+
+    // Times `lookups` point reads of the DB, returns the time and how many keys were deleted
+    fn time_lookups(db: &DbImpl, keys: &[Vec<u8>]) -> (Duration, u64) {
+        let cf = db.default_column_family();
+        let read_options = ReadOptions::default();
+        let start = Instant::now();
+        let mut deleted = 0;
+        for key in keys {
+            deleted += db.get(&read_options, &cf, key).unwrap().is_none() as u64;
+        }
+        (start.elapsed(), deleted)
+    }
+
+    fn run_benchmark(
+        key_space: u64,
+        num_tombstones: u64,
+        max_width: u64,
+        num_lookups: u64,
+    ) -> (u64, u64) {
+        let mut rng = 0x9e37_79b9_7f4a_7c15u64;
+        let tombstones: Vec<(Vec<u8>, Vec<u8>)> = (0..num_tombstones)
+            .map(|_| {
+                let start = next_random(&mut rng) % key_space;
+                let end = (start + 1 + next_random(&mut rng) % max_width).min(key_space);
+                (bench_key(start), bench_key(end))
+            })
+            .collect();
+        let keys: Vec<Vec<u8>> = (0..num_lookups)
+            .map(|_| bench_key(next_random(&mut rng) % key_space))
+            .collect();
+
+        let global_dir = TempDir::new("range-del-bench-global");
+        let global_db = open_bench_db(&global_dir, false, key_space, &tombstones);
+        let per_file_dir = TempDir::new("range-del-bench-per-file");
+        let per_file_db = open_bench_db(&per_file_dir, true, key_space, &tombstones);
+
+        let (global, global_deleted) = time_lookups(&global_db, &keys);
+        let (per_file, per_file_deleted) = time_lookups(&per_file_db, &keys);
+
+        let index = global_db
+            .default_column_family()
+            .data()
+            .range_del()
+            .version();
+        let version = per_file_db.default_column_family().data().current_version();
+        let files = version.level_files(NUM_LEVELS - 1);
+        println!(
+            "tombstones={num_tombstones:>6} width<={max_width:>6}: global {:>9.1?} ({} entries, {} runs) | per-SST {:>9.1?} ({} entries, {} files)",
+            global,
+            index.num_entries(),
+            index.num_runs(),
+            per_file,
+            files
+                .iter()
+                .map(|f| f.properties.num_range_deletions)
+                .sum::<u64>(),
+            files.len(),
+        );
+        (global_deleted, per_file_deleted)
+    }
+
+    #[test]
+    fn range_del_lookups_agree_with_per_file_tombstones() {
+        let (global, per_file) = run_benchmark(5_000, 50, 200, 2_000);
+        assert_eq!(global, per_file);
+        assert!(global > 0);
+    }
+
+    #[test]
+    #[ignore]
+    fn range_del_benchmark() {
+        for (num_tombstones, max_width) in [
+            (1_000, 100),
+            (1_000, 50_000),
+            (20_000, 1_000),
+            (20_000, 50_000),
+        ] {
+            let (global, per_file) = run_benchmark(1_000_000, num_tombstones, max_width, 200_000);
+            assert_eq!(global, per_file);
+        }
+    }
+}
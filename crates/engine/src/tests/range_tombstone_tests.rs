@@ -0,0 +1,308 @@
+#[cfg(test)]
+mod tests {
+
+    use std::sync::Arc;
+
+    use crate::compaction::compaction_iter::CompactionIterator;
+    use crate::compaction::filter::{CompactionFilterContext, TableFileCreationReason};
+    use crate::db::read_path::{GetSource, get};
+    use crate::iterator::db_iter::DBIter;
+    use crate::iterator::internal_iterator::InternalIterator;
+    use crate::iterator::level_iterator::LevelIterator;
+    use crate::iterator::merge_iterator::MergingIterator;
+    use crate::key::comparator::{DefaultComparator, InternalKeyComparator};
+    use crate::key::internal_key::{InternalKeyRef, OperationType};
+    use crate::key::lookup_key::LookUpInternalKey;
+    use crate::memtable::memtable::*;
+    use crate::multi_get::{LevelFiles, MultiGetSource, multi_get};
+    use crate::table::table_builder::{BuiltTable, TableBuilder};
+    use crate::table::table_reader::Table;
+    use crate::tests::helpers;
+    use crate::versioning::file_version::FileMetaData;
+    use crate::versioning::memtable_list::MemListVersion;
+
+    fn memtable(entries: &[(&str, u64)]) -> Memtable<Mutable> {
+        let mem = helpers::memtable(1);
+        for (key, seq) in entries {
+            mem.insert(
+                LookUpInternalKey::new(key.as_bytes(), *seq, OperationType::Put).as_ref(),
+                format!("{key}@{seq}").as_bytes(),
+            )
+            .unwrap();
+        }
+        mem
+    }
+
+    fn build(entries: &[(&str, u64)], tombstones: &[(&str, &str, u64)]) -> BuiltTable {
+        let mut builder = TableBuilder::new(256, 4);
+        for (key, seq) in entries {
+            builder.add(
+                LookUpInternalKey::new(key.as_bytes(), *seq, OperationType::Put).as_ref(),
+                format!("{key}@{seq}").as_bytes(),
+            );
+        }
+        for (start, end, seq) in tombstones {
+            builder.add_range_tombstone(start.as_bytes(), end.as_bytes(), *seq);
+        }
+        builder.finish()
+    }
+
+    fn table(entries: &[(&str, u64)], tombstones: &[(&str, &str, u64)]) -> Arc<Table> {
+        Table::open(build(entries, tombstones).data).unwrap()
+    }
+
+    fn lookup(sources: &[&dyn GetSource], key: &str, seq: u64) -> Option<String> {
+        let lookup_key = LookUpInternalKey::new(key.as_bytes(), seq, OperationType::Max);
+        get(sources, lookup_key.as_ref(), None, 0)
+            .unwrap()
+            .map(|v| String::from_utf8(v).unwrap())
+    }
+
+    fn scan(children: Vec<Box<dyn InternalIterator + '_>>, seq: u64, reverse: bool) -> Vec<String> {
+        let merging = MergingIterator::new(InternalKeyComparator::new(), children);
+        let mut iter = DBIter::new(merging, DefaultComparator::new(), None, seq, None, None);
+
+        let mut out = Vec::new();
+        if reverse {
+            iter.seek_to_last();
+        } else {
+            iter.seek_to_first();
+        }
+        while iter.valid() {
+            out.push(String::from_utf8(iter.value().to_vec()).unwrap());
+            if reverse {
+                iter.prev();
+            } else {
+                iter.next();
+            }
+        }
+        assert!(iter.status().is_none());
+        out
+    }
+
+    #[test]
+    fn memtable_tombstones_cover_older_sources() {
+        let imm = memtable(&[("a", 1), ("b", 2), ("c", 3), ("d", 4)]);
+        let imm = MemListVersion::new(vec![imm.freeze()]);
+
+        let mem = memtable(&[("c", 6)]);
+        mem.delete_range(b"b", b"d", 5).unwrap();
+        // The tombstone also covers the older version in its own memtable
+        mem.insert(
+            LookUpInternalKey::new(b"a", 2, OperationType::Put).as_ref(),
+            b"a@2",
+        )
+        .unwrap();
+        mem.delete_range(b"a", b"b", 3).unwrap();
+        assert!(mem.delete_range(b"z", b"a", 9).is_err());
+
+        let sources: [&dyn GetSource; 2] = [&mem, &imm];
+        assert_eq!(lookup(&sources, "a", 10), None);
+        assert_eq!(lookup(&sources, "b", 10), None);
+        assert_eq!(lookup(&sources, "c", 10).as_deref(), Some("c@6"));
+        assert_eq!(lookup(&sources, "d", 10).as_deref(), Some("d@4"));
+
+        // Below the tombstones
+        assert_eq!(lookup(&sources, "b", 4).as_deref(), Some("b@2"));
+        assert_eq!(lookup(&sources, "a", 2).as_deref(), Some("a@2"));
+
+        let fragments = mem.range_tombstones().unwrap();
+        assert_eq!(fragments.fragments().len(), 2);
+        assert!(Arc::ptr_eq(&fragments, &mem.range_tombstones().unwrap()));
+    }
+
+    #[test]
+    fn file_tombstones_apply_to_multi_get_and_iterators() {
+        // L1 holds the old data, L0 a newer file which deletes part of it
+        let built = [
+            build(&[("k4", 5)], &[("k2", "k5", 3)]),
+            build(
+                &[("k1", 1), ("k2", 1), ("k3", 1), ("k4", 1), ("k5", 1)],
+                &[],
+            ),
+        ];
+        // The tombstone widens the key range of the file
+        assert_eq!(
+            InternalKeyRef::from(built[0].smallest.as_slice()).user_key,
+            b"k2"
+        );
+        assert_eq!(
+            InternalKeyRef::from(built[0].largest.as_slice()).user_key,
+            b"k5"
+        );
+        assert_eq!((built[0].smallest_seqno, built[0].largest_seqno), (3, 5));
+
+        let files: Vec<Arc<FileMetaData>> = (0..2)
+            .map(|n| Arc::new(built[n].file_meta(n as u64)))
+            .collect();
+        let tables: Vec<Arc<Table>> = built
+            .into_iter()
+            .map(|b| Table::open(b.data).unwrap())
+            .collect();
+        assert_eq!(tables[0].properties().num_range_deletions, 1);
+        assert!(tables[1].range_tombstones().is_empty());
+
+        let mem = memtable(&[("k3", 7)]);
+
+        let open = |f: &FileMetaData| {
+            Ok(
+                Box::new(tables[f.number as usize].iter(InternalKeyComparator::new(), None))
+                    as Box<dyn InternalIterator>,
+            )
+        };
+        let level0 = LevelFiles::new(&files[..1], Box::new(open));
+        let level1 = LevelFiles::new(&files[1..], Box::new(open));
+
+        let sources: [&dyn MultiGetSource; 3] = [&mem, &level0, &level1];
+        let keys: [&[u8]; 5] = [b"k1", b"k2", b"k3", b"k4", b"k5"];
+        let values: Vec<Option<String>> =
+            multi_get(&sources, &keys, 10, DefaultComparator::new(), None, 0)
+                .into_iter()
+                .map(|r| r.unwrap().map(|v| String::from_utf8(v).unwrap()))
+                .collect();
+        assert_eq!(
+            values,
+            vec![
+                Some("k1@1".to_string()),
+                None,
+                Some("k3@7".to_string()),
+                Some("k4@5".to_string()),
+                Some("k5@1".to_string()),
+            ]
+        );
+
+        let children = || -> Vec<Box<dyn InternalIterator + '_>> {
+            vec![
+                Box::new(mem.iter()),
+                Box::new(LevelIterator::new(
+                    &files[..1],
+                    InternalKeyComparator::new(),
+                    Box::new(open),
+                    None,
+                )),
+                Box::new(LevelIterator::new(
+                    &files[1..],
+                    InternalKeyComparator::new(),
+                    Box::new(open),
+                    None,
+                )),
+            ]
+        };
+        let live = ["k1@1", "k3@7", "k4@5", "k5@1"];
+        assert_eq!(scan(children(), 10, false), live);
+        assert_eq!(
+            scan(children(), 10, true),
+            live.iter().rev().copied().collect::<Vec<_>>()
+        );
+        // Read below the tombstone
+        assert_eq!(
+            scan(children(), 2, false),
+            ["k1@1", "k2@1", "k3@1", "k4@1", "k5@1"]
+        );
+    }
+
+    fn ctx() -> CompactionFilterContext {
+        CompactionFilterContext {
+            column_family_id: 0,
+            level: 1,
+            is_full_compaction: false,
+            is_manual_compaction: false,
+            reason: TableFileCreationReason::Compaction,
+        }
+    }
+
+    #[test]
+    fn compaction_truncates_tombstones_at_output_boundaries() {
+        let upper = table(&[("b", 8), ("q", 9)], &[("c", "s", 6)]);
+        let lower = table(
+            &[("a", 1), ("d", 2), ("h", 3), ("n", 4), ("r", 5), ("t", 5)],
+            &[],
+        );
+
+        let children: Vec<Box<dyn InternalIterator + '_>> = vec![
+            Box::new(upper.iter(InternalKeyComparator::new(), None)),
+            Box::new(lower.iter(InternalKeyComparator::new(), None)),
+        ];
+        let input = MergingIterator::new(InternalKeyComparator::new(), children);
+        let comp = DefaultComparator {};
+        // A snapshot at 4 still reads what the tombstone covers at or below it
+        let snapshots = [4];
+        let mut iter = CompactionIterator::new(input, &comp, &snapshots, None, ctx(), false);
+
+        // Outputs are cut at "m": [.., m) and [m, ..)
+        let mut outputs = [TableBuilder::new(256, 4), TableBuilder::new(256, 4)];
+        iter.seek_to_first();
+        while iter.valid() {
+            let user_key = InternalKeyRef::from(iter.key()).user_key;
+            let out = usize::from(user_key >= b"m".as_slice());
+            outputs[out].add(iter.key(), iter.value());
+            iter.next();
+        }
+        assert!(iter.status().is_none());
+        // r@5 is in the tombstone's stripe, the versions at or below the snapshot are kept
+        assert_eq!(iter.stats().num_dropped_range_del, 1);
+
+        let bounds: [(Option<&[u8]>, Option<&[u8]>); 2] = [(None, Some(b"m")), (Some(b"m"), None)];
+        let mut built = Vec::new();
+        for (mut builder, (lower, upper)) in outputs.into_iter().zip(bounds) {
+            for t in iter.output_range_tombstones(lower, upper) {
+                builder.add_range_tombstone(&t.start, &t.end, t.seq);
+            }
+            built.push(builder.finish());
+        }
+
+        let tombstones: Vec<Vec<(Vec<u8>, Vec<u8>, u64)>> = built
+            .iter()
+            .map(|b| {
+                Table::open(b.data.clone())
+                    .unwrap()
+                    .range_tombstones()
+                    .tombstones()
+                    .map(|t| (t.start, t.end, t.seq))
+                    .collect()
+            })
+            .collect();
+        assert_eq!(
+            tombstones,
+            vec![
+                vec![(b"c".to_vec(), b"m".to_vec(), 6)],
+                vec![(b"m".to_vec(), b"s".to_vec(), 6)],
+            ]
+        );
+
+        // The first file now ends at the tombstone's truncated end, which sorts before every real entry of "m"
+        let first_largest = InternalKeyRef::from(built[0].largest.as_slice());
+        assert_eq!(first_largest.user_key, b"m");
+        assert_eq!(first_largest.op, OperationType::RangeDelete as u8);
+        assert_eq!((built[0].smallest_seqno, built[0].largest_seqno), (1, 8));
+
+        // Reading the outputs gives what the inputs gave
+        let outputs: Vec<Arc<Table>> = built
+            .into_iter()
+            .map(|b| Table::open(b.data).unwrap())
+            .collect();
+        let children: Vec<Box<dyn InternalIterator + '_>> = outputs
+            .iter()
+            .map(|t| {
+                Box::new(t.iter(InternalKeyComparator::new(), None)) as Box<dyn InternalIterator>
+            })
+            .collect();
+        assert_eq!(scan(children, 10, false), ["a@1", "b@8", "q@9", "t@5"]);
+
+        // A bottommost compaction without snapshots drops the covered versions and the tombstone
+        let children: Vec<Box<dyn InternalIterator + '_>> = vec![
+            Box::new(upper.iter(InternalKeyComparator::new(), None)),
+            Box::new(lower.iter(InternalKeyComparator::new(), None)),
+        ];
+        let input = MergingIterator::new(InternalKeyComparator::new(), children);
+        let mut iter = CompactionIterator::new(input, &comp, &[], None, ctx(), true);
+        iter.seek_to_first();
+        let mut kept = 0;
+        while iter.valid() {
+            kept += 1;
+            iter.next();
+        }
+        assert_eq!(kept, 4);
+        assert!(iter.output_range_tombstones(None, None).is_empty());
+    }
+}
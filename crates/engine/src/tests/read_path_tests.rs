@@ -0,0 +1,171 @@
+#[cfg(test)]
+mod tests {
+
+    use std::sync::Arc;
+
+    use crate::column_family::cf::ColumnFamilyDescriptor;
+    use crate::db::db_impl::DbImpl;
+    use crate::db::read_path::{GetSource, get, get_merge_operands};
+    use crate::db::write_batch::Batch;
+    use crate::error::Error;
+    use crate::key::comparator::DefaultComparator;
+    use crate::key::internal_key::OperationType;
+    use crate::key::lookup_key::LookUpInternalKey;
+    use crate::memtable::memtable::*;
+    use crate::merge::operator::MergeOperator;
+    use crate::options::{ColumnFamilyOptions, DbOptions, ReadOptions};
+    use crate::range::index::RangeDelIndex;
+    use crate::tests::helpers::memtable;
+    use crate::tests::temp_dir::TempDir;
+    use crate::versioning::memtable_list::MemListVersion;
+
+    // Joins operands with ',' - partial merges are plain concatenation so they are associative
+    struct Append;
+
+    impl MergeOperator for Append {
+        fn name(&self) -> &str {
+            "Append"
+        }
+
+        fn full_merge(
+            &self,
+            _key: &[u8],
+            existing_value: Option<&[u8]>,
+            operands: &[&[u8]],
+        ) -> Option<Vec<u8>> {
+            let mut out = existing_value.map(|v| v.to_vec()).unwrap_or_default();
+            for op in operands {
+                if !out.is_empty() {
+                    out.push(b',');
+                }
+                out.extend_from_slice(op);
+            }
+            Some(out)
+        }
+    }
+
+    fn insert(mem: &Memtable<Mutable>, key: &[u8], seq: u64, op: OperationType, value: &[u8]) {
+        mem.insert(LookUpInternalKey::new(key, seq, op).as_ref(), value)
+            .unwrap();
+    }
+
+    fn lookup(key: &[u8], seq: u64) -> LookUpInternalKey {
+        LookUpInternalKey::new(key, seq, OperationType::Max)
+    }
+
+    #[test]
+    fn merge_operands_across_memtables() {
+        // Oldest immutable memtable holds the base value
+        let imm_old = memtable(1);
+        insert(&imm_old, b"list", 1, OperationType::Put, b"a");
+        insert(&imm_old, b"gone", 2, OperationType::Put, b"x");
+
+        let imm_new = memtable(2);
+        insert(&imm_new, b"list", 3, OperationType::Merge, b"b");
+        insert(&imm_new, b"gone", 4, OperationType::Delete, b"");
+
+        let mem = memtable(3);
+        insert(&mem, b"list", 5, OperationType::Merge, b"c");
+        insert(&mem, b"list", 6, OperationType::Merge, b"d");
+        insert(&mem, b"gone", 7, OperationType::Merge, b"y");
+        insert(&mem, b"fresh", 8, OperationType::Merge, b"z");
+
+        let imm = MemListVersion::new(vec![imm_new.freeze(), imm_old.freeze()]);
+        let sources: [&dyn GetSource; 2] = [&mem, &imm];
+        let op = Append;
+
+        // Operands from all three memtables on top of the base
+        let v = get(&sources, lookup(b"list", 100).as_ref(), Some(&op), 0).unwrap();
+        assert_eq!(v.as_deref(), Some(b"a,b,c,d".as_slice()));
+
+        // Reading at an older seq no only sees older operands
+        let v = get(&sources, lookup(b"list", 5).as_ref(), Some(&op), 0).unwrap();
+        assert_eq!(v.as_deref(), Some(b"a,b,c".as_slice()));
+
+        // A Delete stops the walk - operands are merged with no base
+        let v = get(&sources, lookup(b"gone", 100).as_ref(), Some(&op), 0).unwrap();
+        assert_eq!(v.as_deref(), Some(b"y".as_slice()));
+
+        // Operands and no base anywhere
+        let v = get(&sources, lookup(b"fresh", 100).as_ref(), Some(&op), 0).unwrap();
+        assert_eq!(v.as_deref(), Some(b"z".as_slice()));
+
+        assert_eq!(
+            get(&sources, lookup(b"missing", 100).as_ref(), Some(&op), 0).unwrap(),
+            None
+        );
+
+        // Debug API returns the raw operands with the base first
+        let no_range_del = RangeDelIndex::new(DefaultComparator::new()).version();
+        let operands =
+            get_merge_operands(&sources, &no_range_del, lookup(b"list", 100).as_ref(), 0).unwrap();
+        assert_eq!(
+            operands,
+            vec![b"a".to_vec(), b"b".to_vec(), b"c".to_vec(), b"d".to_vec()]
+        );
+    }
+
+    #[test]
+    fn db_merge_operands_are_read_from_the_column_family() {
+        let dir = TempDir::new("merge-operands-db");
+        let options = DbOptions {
+            create_if_missing: true,
+            ..Default::default()
+        };
+        let cf_options = ColumnFamilyOptions {
+            merge_operator: Some(Arc::new(Append)),
+            ..Default::default()
+        };
+        let db = DbImpl::open(
+            dir.path(),
+            &options,
+            vec![ColumnFamilyDescriptor::new("default", cf_options)],
+        )
+        .unwrap();
+        let cf = db.default_column_family();
+        let write = |f: &dyn Fn(&mut Batch)| {
+            let mut batch = Batch::new();
+            f(&mut batch);
+            db.write(&mut batch).unwrap();
+        };
+
+        write(&|b| b.put_cf(0, b"list", b"a"));
+        write(&|b| b.merge_cf(0, b"list", b"b"));
+        // The flush merges the operand into the base
+        db.flush(&cf).unwrap();
+        let snapshot = db.get_snapshot();
+        write(&|b| b.merge_cf(0, b"list", b"c"));
+
+        let latest = ReadOptions::default();
+        let operands = db.get_merge_operands(&latest, &cf, b"list").unwrap();
+        assert_eq!(operands, vec![b"a,b".to_vec(), b"c".to_vec()]);
+        let at_snapshot = ReadOptions {
+            snapshot: Some(snapshot),
+            ..Default::default()
+        };
+        let operands = db.get_merge_operands(&at_snapshot, &cf, b"list").unwrap();
+        assert_eq!(operands, vec![b"a,b".to_vec()]);
+
+        // Operands below a range deletion are gone
+        db.delete_range(&cf, b"l", b"m").unwrap();
+        write(&|b| b.merge_cf(0, b"list", b"d"));
+        let operands = db.get_merge_operands(&latest, &cf, b"list").unwrap();
+        assert_eq!(operands, vec![b"d".to_vec()]);
+    }
+
+    #[test]
+    fn merge_without_operator_is_an_error() {
+        let mem = memtable(1);
+        insert(&mem, b"k", 1, OperationType::Put, b"a");
+        insert(&mem, b"k", 2, OperationType::Merge, b"b");
+
+        let sources: [&dyn GetSource; 1] = [&mem];
+
+        let err = get(&sources, lookup(b"k", 10).as_ref(), None, 0).unwrap_err();
+        assert!(matches!(err, Error::NotSupported(_)));
+
+        // Plain values don't need an operator
+        let v = get(&sources, lookup(b"k", 1).as_ref(), None, 0).unwrap();
+        assert_eq!(v.as_deref(), Some(b"a".as_slice()));
+    }
+}
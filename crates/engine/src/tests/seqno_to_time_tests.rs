@@ -0,0 +1,142 @@
+#[cfg(test)]
+mod tests {
+
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use crate::column_family::cf::ColumnFamilyDescriptor;
+    use crate::db::db_impl::DbImpl;
+    use crate::db::write_batch::Batch;
+    use crate::options::{ColumnFamilyOptions, DbOptions, ReadOptions};
+    use crate::tests::helpers::put;
+    use crate::tests::temp_dir::TempDir;
+    use crate::utils::clock::ManualClock;
+    use crate::versioning::seqno_to_time::SeqnoToTimeMapping;
+
+    const START: u64 = 1_000_000;
+    const MINUTE: Duration = Duration::from_secs(60);
+
+    fn open(dir: &TempDir, clock: &Arc<ManualClock>, sample_period: Duration) -> DbImpl {
+        let options = DbOptions {
+            create_if_missing: true,
+            clock: clock.clone(),
+            seqno_time_sample_period: sample_period,
+            ..Default::default()
+        };
+        DbImpl::open(
+            dir.path(),
+            &options,
+            vec![ColumnFamilyDescriptor::new(
+                "default",
+                ColumnFamilyOptions::default(),
+            )],
+        )
+        .unwrap()
+    }
+
+    fn write_time(db: &DbImpl, key: &str) -> Option<u64> {
+        let cf = db.default_column_family();
+        db.approximate_write_time(&ReadOptions::default(), &cf, key.as_bytes())
+            .unwrap()
+    }
+
+    fn samples(mapping: &SeqnoToTimeMapping) -> Vec<(u64, u64)> {
+        mapping.pairs().iter().map(|p| (p.seq, p.time)).collect()
+    }
+
+    #[test]
+    fn writes_are_sampled_and_kept_by_table_files() {
+        let dir = TempDir::new("seqno-time-flush-db");
+        let clock = ManualClock::new(START);
+        let db = open(&dir, &clock, MINUTE);
+        let cf = db.default_column_family();
+
+        // Opening the DB takes the first sample, writes within the period take none
+        put(&db, "a", "v");
+        put(&db, "b", "v");
+        clock.advance(MINUTE);
+        put(&db, "c", "v");
+        clock.advance(MINUTE);
+        put(&db, "d", "v");
+        let expected = vec![(0, START), (3, START + 60), (4, START + 120)];
+        assert_eq!(samples(&db.seqno_to_time_mapping()), expected);
+
+        assert_eq!(write_time(&db, "a"), Some(START));
+        assert_eq!(write_time(&db, "d"), Some(START + 60));
+        assert_eq!(write_time(&db, "missing"), None);
+
+        db.flush(&cf).unwrap();
+        let version = cf.data().current_version();
+        let table = &version.level_files(0)[0];
+        assert_eq!(samples(&table.properties.seqno_to_time), expected);
+        assert_eq!(table.properties.oldest_key_time, START);
+        assert_eq!(table.properties.oldest_ancestor_time(), START);
+        // The new superversion shares the DB's mapping
+        assert!(Arc::ptr_eq(
+            cf.data().superversion().seqno_to_time(),
+            &db.seqno_to_time_mapping()
+        ));
+
+        // A deleted key has no write time
+        let mut batch = Batch::new();
+        batch.delete_cf(0, b"a");
+        db.write(&mut batch).unwrap();
+        assert_eq!(write_time(&db, "a"), None);
+
+        // The samples come back from the table file
+        drop(version);
+        drop(cf);
+        drop(db);
+        clock.advance(MINUTE * 10);
+        let db = open(&dir, &clock, MINUTE);
+        let mapping = db.seqno_to_time_mapping();
+        assert_eq!(samples(&mapping)[..3], expected[..]);
+        assert_eq!(mapping.proximal_seqno_before_time(START + 90), Some(3));
+        assert_eq!(write_time(&db, "b"), Some(START));
+        assert_eq!(write_time(&db, "d"), Some(START + 60));
+    }
+
+    #[test]
+    fn compactions_keep_the_samples_of_their_inputs() {
+        let dir = TempDir::new("seqno-time-compaction-db");
+        let clock = ManualClock::new(START);
+        let db = open(&dir, &clock, MINUTE);
+        let cf = db.default_column_family();
+
+        put(&db, "a", "v");
+        db.flush(&cf).unwrap();
+        for i in 0..5 {
+            clock.advance(MINUTE);
+            put(&db, &format!("k{i}"), "v");
+        }
+        db.flush(&cf).unwrap();
+        db.compact_range(&cf).unwrap();
+
+        let version = cf.data().current_version();
+        let (_, table) = version.files().next().unwrap();
+        let table_samples = samples(&table.properties.seqno_to_time);
+        assert_eq!(table_samples.first(), Some(&(0, START)));
+        assert_eq!(table_samples.last(), Some(&(6, START + 300)));
+        assert_eq!(table.properties.oldest_key_time, START);
+    }
+
+    #[test]
+    fn sampling_can_be_turned_off() {
+        let dir = TempDir::new("seqno-time-off-db");
+        let clock = ManualClock::new(START);
+        let db = open(&dir, &clock, Duration::ZERO);
+        let cf = db.default_column_family();
+
+        put(&db, "a", "v");
+        clock.advance(MINUTE);
+        put(&db, "b", "v");
+        db.flush(&cf).unwrap();
+
+        assert!(db.seqno_to_time_mapping().is_empty());
+        let version = cf.data().current_version();
+        let table = &version.level_files(0)[0];
+        assert!(table.properties.seqno_to_time.is_empty());
+        assert_eq!(table.properties.oldest_key_time, 0);
+        assert_eq!(write_time(&db, "a"), None);
+    }
+}
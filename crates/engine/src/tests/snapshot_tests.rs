@@ -0,0 +1,147 @@
+#[cfg(test)]
+mod tests {
+
+    use std::sync::Arc;
+
+    use crate::compaction::compaction_iter::CompactionIterator;
+    use crate::compaction::filter::{CompactionFilterContext, TableFileCreationReason};
+    use crate::db::db_impl::DbImpl;
+    use crate::db::write_batch::Batch;
+    use crate::key::comparator::DefaultComparator;
+    use crate::key::internal_key::{InternalKeyRef, OperationType};
+    use crate::key::lookup_key::LookUpInternalKey;
+    use crate::memtable::memtable::*;
+    use crate::options::ReadOptions;
+    use crate::tests::helpers::{memtable, put, read_with, scan};
+
+    // Writes the entry and publishes its seq no like the write path does
+    fn write(db: &DbImpl, mem: &Memtable<Mutable>, key: &[u8], op: OperationType, value: &[u8]) {
+        let seq = db.last_sequence() + 1;
+        mem.insert(LookUpInternalKey::new(key, seq, op).as_ref(), value)
+            .unwrap();
+        db.set_last_sequence(seq);
+    }
+
+    fn delete(db: &DbImpl, key: &[u8]) {
+        let mut batch = Batch::new();
+        batch.delete_cf(0, key);
+        db.write(&mut batch).unwrap();
+    }
+
+    fn kv(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn reads_through_a_snapshot_ignore_later_writes() {
+        let db = DbImpl::new();
+        let cf = db.default_column_family();
+
+        put(&db, b"a", b"a1");
+        put(&db, b"b", b"b1");
+
+        let snapshot = db.get_snapshot();
+        assert_eq!(snapshot.sequence(), 2);
+
+        put(&db, b"a", b"a2");
+        delete(&db, b"b");
+        put(&db, b"c", b"c1");
+
+        let at_snapshot = ReadOptions {
+            snapshot: Some(Arc::clone(&snapshot)),
+            ..Default::default()
+        };
+        let latest = ReadOptions::default();
+
+        assert_eq!(
+            read_with(&db, &at_snapshot, &cf, b"a").as_deref(),
+            Some("a1")
+        );
+        assert_eq!(
+            read_with(&db, &at_snapshot, &cf, b"b").as_deref(),
+            Some("b1")
+        );
+        assert_eq!(read_with(&db, &at_snapshot, &cf, b"c"), None);
+        assert_eq!(read_with(&db, &latest, &cf, b"a").as_deref(), Some("a2"));
+        assert_eq!(read_with(&db, &latest, &cf, b"b"), None);
+
+        assert_eq!(
+            scan(&db, &at_snapshot, &cf),
+            kv(&[("a", "a1"), ("b", "b1")])
+        );
+        assert_eq!(scan(&db, &latest, &cf), kv(&[("a", "a2"), ("c", "c1")]));
+    }
+
+    #[test]
+    fn releasing_unregisters_the_snapshot() {
+        let db = DbImpl::new();
+
+        put(&db, b"a", b"a1");
+        let first = db.get_snapshot();
+        put(&db, b"a", b"a2");
+        let second = db.get_snapshot();
+        let second_again = db.get_snapshot();
+
+        assert_eq!(db.snapshots().sequence_numbers(), vec![1, 2]);
+        assert_eq!(db.snapshots().len(), 3);
+
+        // Explicit release
+        db.release_snapshot(first);
+        assert_eq!(db.snapshots().sequence_numbers(), vec![2]);
+
+        // A snapshot held by read options stays live until the last reference is dropped
+        let options = ReadOptions {
+            snapshot: Some(Arc::clone(&second)),
+            ..Default::default()
+        };
+        drop(second);
+        drop(second_again);
+        assert_eq!(db.snapshots().oldest(), Some(2));
+
+        drop(options);
+        assert!(db.snapshots().is_empty());
+    }
+
+    #[test]
+    fn compaction_keeps_versions_visible_to_live_snapshots() {
+        let db = DbImpl::new();
+        let mem = memtable(1);
+
+        write(&db, &mem, b"k", OperationType::Put, b"v1");
+        write(&db, &mem, b"k", OperationType::Put, b"v2");
+        let snapshot = db.get_snapshot();
+        write(&db, &mem, b"k", OperationType::Put, b"v3");
+        write(&db, &mem, b"k", OperationType::Put, b"v4");
+
+        let compact = |db: &DbImpl| {
+            let snapshots = db.snapshots().sequence_numbers();
+            let comparator = DefaultComparator {};
+            let ctx = CompactionFilterContext {
+                column_family_id: 0,
+                level: 0,
+                is_full_compaction: false,
+                is_manual_compaction: false,
+                reason: TableFileCreationReason::Flush,
+            };
+            let mut iter =
+                CompactionIterator::new(mem.iter(), &comparator, &snapshots, None, ctx, false);
+
+            let mut seqs = Vec::new();
+            iter.seek_to_first();
+            while iter.valid() {
+                seqs.push(InternalKeyRef::from(iter.key()).seq_no);
+                iter.next();
+            }
+            seqs
+        };
+
+        // The snapshot still reads v2
+        assert_eq!(compact(&db), vec![4, 2]);
+
+        drop(snapshot);
+        assert_eq!(compact(&db), vec![4]);
+    }
+}
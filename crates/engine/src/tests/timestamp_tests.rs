@@ -0,0 +1,325 @@
+#[cfg(test)]
+mod tests {
+
+    use std::sync::Arc;
+
+    use crate::column_family::cf::ColumnFamilyHandle;
+    use crate::db::db_impl::DbImpl;
+    use crate::db::write_batch::Batch;
+    use crate::error::Error;
+    use crate::iterator::db_iter::DBIter;
+    use crate::iterator::internal_iterator::InternalIterator;
+    use crate::iterator::iter_alloc::PooledIter;
+    use crate::key::comparator::DefaultComparator;
+    use crate::key::internal_key::InternalKeyRef;
+    use crate::key::timestamp::{UserTimestampComparator, encode_u64_timestamp};
+    use crate::options::{ColumnFamilyOptions, ReadOptions};
+    use crate::tests::helpers::{multi_read_with, open, read_with};
+    use crate::tests::temp_dir::TempDir;
+    use crate::transaction::optimistic::OptimisticTransaction;
+    use crate::versioning::snapshot::Snapshot;
+
+    fn timestamp_options() -> ColumnFamilyOptions {
+        ColumnFamilyOptions {
+            comparator: UserTimestampComparator::new(DefaultComparator::new(), 8),
+            ..Default::default()
+        }
+    }
+
+    fn put(db: &DbImpl, key: &str, ts: u64, value: &str) {
+        let mut batch = Batch::new();
+        batch.put_cf_with_timestamp(
+            0,
+            key.as_bytes(),
+            &encode_u64_timestamp(ts),
+            value.as_bytes(),
+        );
+        db.write(&mut batch).unwrap();
+    }
+
+    fn delete(db: &DbImpl, key: &str, ts: u64) {
+        let mut batch = Batch::new();
+        batch.delete_cf_with_timestamp(0, key.as_bytes(), &encode_u64_timestamp(ts));
+        db.write(&mut batch).unwrap();
+    }
+
+    fn read_options(ts: u64, snapshot: Option<&Arc<Snapshot>>) -> ReadOptions {
+        ReadOptions {
+            timestamp: Some(encode_u64_timestamp(ts).to_vec()),
+            snapshot: snapshot.cloned(),
+            ..Default::default()
+        }
+    }
+
+    fn read_at(
+        db: &DbImpl,
+        cf: &ColumnFamilyHandle,
+        key: &str,
+        ts: u64,
+        snapshot: Option<&Arc<Snapshot>>,
+    ) -> Option<String> {
+        read_with(db, &read_options(ts, snapshot), cf, key)
+    }
+
+    fn read(db: &DbImpl, cf: &ColumnFamilyHandle, key: &str, ts: u64) -> Option<String> {
+        read_at(db, cf, key, ts, None)
+    }
+
+    fn multi_read(
+        db: &DbImpl,
+        cf: &ColumnFamilyHandle,
+        keys: &[&[u8]],
+        ts: u64,
+    ) -> Vec<Option<String>> {
+        multi_read_with(db, &read_options(ts, None), cf, keys)
+    }
+
+    // Every key, its timestamp and value through a DBIter over the memtable and the table files, forwards and backwards
+    fn scan(db: &DbImpl, cf: &ColumnFamilyHandle, ts: Option<u64>) -> Vec<(String, u64, String)> {
+        let read_options = ReadOptions {
+            timestamp: ts.map(|ts| encode_u64_timestamp(ts).to_vec()),
+            ..Default::default()
+        };
+        let mut iter = db.new_iterator(&read_options, cf).unwrap();
+
+        let entry = |iter: &DBIter<'_, PooledIter<'_>>| {
+            (
+                String::from_utf8(iter.key().to_vec()).unwrap(),
+                u64::from_be_bytes(iter.timestamp().try_into().unwrap()),
+                String::from_utf8(iter.value().to_vec()).unwrap(),
+            )
+        };
+        let mut forward = Vec::new();
+        iter.seek_to_first();
+        while iter.valid() {
+            forward.push(entry(&iter));
+            iter.next();
+        }
+        assert!(iter.status().is_none());
+
+        let mut backward = Vec::new();
+        iter.seek_to_last();
+        while iter.valid() {
+            backward.push(entry(&iter));
+            iter.prev();
+        }
+        assert!(iter.status().is_none());
+        backward.reverse();
+        assert_eq!(forward, backward);
+        forward
+    }
+
+    // (key, timestamp) of every entry in the table files
+    fn table_entries(cf: &ColumnFamilyHandle) -> Vec<(String, u64)> {
+        let version = cf.data().current_version();
+        let mut entries = Vec::new();
+        for (_, f) in version.files() {
+            let table = f.table_reader().unwrap();
+            let mut iter = table.iter(cf.data().internal_comparator().clone(), None);
+            iter.seek_to_first();
+            while iter.valid() {
+                let user_key = InternalKeyRef::from(iter.key()).user_key;
+                let (key, ts) = user_key.split_at(user_key.len() - 8);
+                entries.push((
+                    String::from_utf8(key.to_vec()).unwrap(),
+                    u64::from_be_bytes(ts.try_into().unwrap()),
+                ));
+                iter.next();
+            }
+        }
+        entries
+    }
+
+    fn entries(entries: &[(&str, u64, &str)]) -> Vec<(String, u64, String)> {
+        entries
+            .iter()
+            .map(|(k, ts, v)| (k.to_string(), *ts, v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn reads_see_the_newest_version_at_the_read_timestamp() {
+        let dir = TempDir::new("timestamp-read-db");
+        let db = open(&dir, timestamp_options());
+        let cf = db.default_column_family();
+
+        put(&db, "a", 10, "a10");
+        put(&db, "b", 15, "b15");
+        put(&db, "a", 20, "a20");
+        delete(&db, "a", 30);
+
+        for flushed in [false, true] {
+            if flushed {
+                db.flush(&cf).unwrap();
+            }
+            assert_eq!(read(&db, &cf, "a", 5), None);
+            assert_eq!(read(&db, &cf, "a", 10).as_deref(), Some("a10"));
+            assert_eq!(read(&db, &cf, "a", 25).as_deref(), Some("a20"));
+            assert_eq!(read(&db, &cf, "a", 30), None);
+            assert_eq!(read(&db, &cf, "b", 14), None);
+            assert_eq!(read(&db, &cf, "b", u64::MAX).as_deref(), Some("b15"));
+
+            let keys: [&[u8]; 4] = [b"c", b"b", b"a", b"a"];
+            assert_eq!(
+                multi_read(&db, &cf, &keys, 25),
+                vec![
+                    None,
+                    Some("b15".into()),
+                    Some("a20".into()),
+                    Some("a20".into())
+                ]
+            );
+
+            assert_eq!(
+                scan(&db, &cf, Some(25)),
+                entries(&[("a", 20, "a20"), ("b", 15, "b15")])
+            );
+            assert_eq!(scan(&db, &cf, Some(12)), entries(&[("a", 10, "a10")]));
+            assert_eq!(scan(&db, &cf, None), entries(&[("b", 15, "b15")]));
+        }
+
+        // An older timestamp written after the snapshot is invisible to it
+        let snapshot = db.get_snapshot();
+        put(&db, "a", 12, "late");
+        assert_eq!(
+            read_at(&db, &cf, "a", 15, Some(&snapshot)).as_deref(),
+            Some("a10")
+        );
+        assert_eq!(read(&db, &cf, "a", 15).as_deref(), Some("late"));
+    }
+
+    #[test]
+    fn compaction_trims_history_below_full_history_ts_low() {
+        let dir = TempDir::new("timestamp-trim-db");
+        let db = open(&dir, timestamp_options());
+        let cf = db.default_column_family();
+
+        put(&db, "a", 10, "a10");
+        put(&db, "a", 20, "a20");
+        put(&db, "a", 30, "a30");
+        put(&db, "b", 5, "b5");
+        put(&db, "c", 1, "c1");
+        delete(&db, "c", 2);
+        db.flush(&cf).unwrap();
+
+        // Without full_history_ts_low every version is kept
+        db.compact_range(&cf).unwrap();
+        assert_eq!(table_entries(&cf).len(), 6);
+
+        let low = encode_u64_timestamp(25);
+        db.increase_full_history_ts_low(&cf, &low).unwrap();
+        db.compact_range(&cf).unwrap();
+        assert_eq!(
+            table_entries(&cf),
+            vec![("a".into(), 30), ("a".into(), 20), ("b".into(), 5)]
+        );
+        assert_eq!(read(&db, &cf, "a", 25).as_deref(), Some("a20"));
+        assert_eq!(read(&db, &cf, "a", u64::MAX).as_deref(), Some("a30"));
+        assert_eq!(read(&db, &cf, "b", 25).as_deref(), Some("b5"));
+        assert_eq!(read(&db, &cf, "c", 25), None);
+
+        // It never goes back, and it survives a reopen
+        assert!(matches!(
+            db.increase_full_history_ts_low(&cf, &encode_u64_timestamp(24)),
+            Err(Error::InvalidArgument(_))
+        ));
+        drop(cf);
+        drop(db);
+        let db = open(&dir, timestamp_options());
+        let cf = db.default_column_family();
+        assert_eq!(cf.data().full_history_ts_low(), Some(low.to_vec()));
+        assert_eq!(read(&db, &cf, "a", 25).as_deref(), Some("a20"));
+    }
+
+    #[test]
+    fn a_snapshot_keeps_the_versions_it_reads() {
+        let dir = TempDir::new("timestamp-trim-snapshot-db");
+        let db = open(&dir, timestamp_options());
+        let cf = db.default_column_family();
+
+        put(&db, "a", 10, "a10");
+        let snapshot = db.get_snapshot();
+        put(&db, "a", 20, "a20");
+        db.flush(&cf).unwrap();
+
+        db.increase_full_history_ts_low(&cf, &encode_u64_timestamp(25))
+            .unwrap();
+        db.compact_range(&cf).unwrap();
+        assert_eq!(
+            read_at(&db, &cf, "a", 25, Some(&snapshot)).as_deref(),
+            Some("a10")
+        );
+        assert_eq!(table_entries(&cf).len(), 2);
+
+        drop(snapshot);
+        db.compact_range(&cf).unwrap();
+        assert_eq!(table_entries(&cf), vec![("a".into(), 20)]);
+    }
+
+    #[test]
+    fn invalid_timestamp_operations_are_rejected() {
+        let dir = TempDir::new("timestamp-errors-db");
+        let db = open(&dir, timestamp_options());
+        let cf = db.default_column_family();
+
+        let mut batch = Batch::new();
+        batch.put_cf(0, b"short", b"v");
+        assert!(matches!(
+            db.write(&mut batch),
+            Err(Error::InvalidArgument(_))
+        ));
+        assert!(matches!(
+            db.increase_full_history_ts_low(&cf, b"1234"),
+            Err(Error::InvalidArgument(_))
+        ));
+        assert!(matches!(
+            db.delete_range(&cf, b"a", b"z"),
+            Err(Error::NotSupported(_))
+        ));
+
+        let plain = db
+            .create_column_family("plain", ColumnFamilyOptions::default())
+            .unwrap();
+        assert!(matches!(
+            db.increase_full_history_ts_low(&plain, &encode_u64_timestamp(1)),
+            Err(Error::InvalidArgument(_))
+        ));
+
+        // Reads need a timestamp of the column family's size - and none without timestamps
+        let no_timestamp = ReadOptions::default();
+        let short_timestamp = ReadOptions {
+            timestamp: Some(b"1234".to_vec()),
+            ..Default::default()
+        };
+        for (cf, read_options) in [
+            (&cf, &no_timestamp),
+            (&cf, &short_timestamp),
+            (&plain, &read_options(1, None)),
+        ] {
+            assert!(matches!(
+                db.get(read_options, cf, b"a"),
+                Err(Error::InvalidArgument(_))
+            ));
+            let results = db.multi_get(read_options, cf, &[b"a", b"b"]);
+            assert_eq!(results.len(), 2);
+            assert!(
+                results
+                    .iter()
+                    .all(|r| matches!(r, Err(Error::InvalidArgument(_))))
+            );
+            assert!(matches!(
+                OptimisticTransaction::new(&db).get(read_options, cf, b"a"),
+                Err(Error::InvalidArgument(_))
+            ));
+        }
+
+        // Iterators read the newest versions without a timestamp, but not with one which doesn't fit
+        for (cf, read_options) in [(&cf, &short_timestamp), (&plain, &read_options(1, None))] {
+            assert!(matches!(
+                db.new_iterator(read_options, cf),
+                Err(Error::InvalidArgument(_))
+            ));
+        }
+        assert!(db.new_iterator(&no_timestamp, &cf).is_ok());
+    }
+}
@@ -0,0 +1,194 @@
+#[cfg(test)]
+mod tests {
+
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use crate::column_family::cf::{ColumnFamilyDescriptor, ColumnFamilyHandle};
+    use crate::db::db_impl::DbImpl;
+    use crate::db::write_batch::Batch;
+    use crate::iterator::db_iter::DBIter;
+    use crate::iterator::iter_alloc::PooledIter;
+    use crate::merge::operators::string_append::StringAppendOperator;
+    use crate::options::{ColumnFamilyOptions, DbOptions, ReadOptions};
+    use crate::tests::helpers::{multi_read_with, read_cf};
+    use crate::tests::temp_dir::TempDir;
+    use crate::ttl::TtlCompactionFilter;
+    use crate::utils::clock::ManualClock;
+
+    const START: u64 = 1_000_000;
+
+    fn open(dir: &TempDir, clock: &Arc<ManualClock>) -> DbImpl {
+        let options = DbOptions {
+            create_if_missing: true,
+            clock: clock.clone(),
+            ..Default::default()
+        };
+        let cf_options = ColumnFamilyOptions {
+            compaction_filter: Some(TtlCompactionFilter::new(clock.clone())),
+            merge_operator: Some(Arc::new(StringAppendOperator::new(b","))),
+            ..Default::default()
+        };
+        DbImpl::open(
+            dir.path(),
+            &options,
+            vec![ColumnFamilyDescriptor::new("default", cf_options)],
+        )
+        .unwrap()
+    }
+
+    fn put_with_ttl(db: &DbImpl, key: &str, value: &str, ttl: u64) {
+        let mut batch = Batch::new();
+        let expires_at = db.clock().now() + ttl;
+        batch.put_cf_with_expiry(0, key.as_bytes(), value.as_bytes(), expires_at);
+        db.write(&mut batch).unwrap();
+    }
+
+    fn multi_read(db: &DbImpl, cf: &ColumnFamilyHandle, keys: &[&[u8]]) -> Vec<Option<String>> {
+        multi_read_with(db, &ReadOptions::default(), cf, keys)
+    }
+
+    // Every key and value through a DBIter over the memtable and the table files, forwards and backwards
+    fn scan(db: &DbImpl, cf: &ColumnFamilyHandle) -> Vec<(String, String)> {
+        let mut iter = db.new_iterator(&ReadOptions::default(), cf).unwrap();
+
+        let entry = |iter: &DBIter<'_, PooledIter<'_>>| {
+            (
+                String::from_utf8(iter.key().to_vec()).unwrap(),
+                String::from_utf8(iter.value().to_vec()).unwrap(),
+            )
+        };
+        let mut forward = Vec::new();
+        iter.seek_to_first();
+        while iter.valid() {
+            forward.push(entry(&iter));
+            iter.next();
+        }
+        assert!(iter.status().is_none());
+
+        let mut backward = Vec::new();
+        iter.seek_to_last();
+        while iter.valid() {
+            backward.push(entry(&iter));
+            iter.prev();
+        }
+        assert!(iter.status().is_none());
+        backward.reverse();
+        assert_eq!(forward, backward);
+        forward
+    }
+
+    fn pairs(entries: &[(&str, &str)]) -> Vec<(String, String)> {
+        entries
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn expired_entries_are_invisible() {
+        let dir = TempDir::new("ttl-read-db");
+        let clock = ManualClock::new(START);
+        let db = open(&dir, &clock);
+        let cf = db.default_column_family();
+
+        put_with_ttl(&db, "session-a", "alice", 60);
+        put_with_ttl(&db, "session-b", "bob", 120);
+        let mut batch = Batch::new();
+        batch.put_cf(0, b"user", b"carol");
+        db.write(&mut batch).unwrap();
+
+        assert_eq!(read_cf(&db, &cf, "session-a").as_deref(), Some("alice"));
+        assert_eq!(
+            scan(&db, &cf),
+            pairs(&[
+                ("session-a", "alice"),
+                ("session-b", "bob"),
+                ("user", "carol")
+            ])
+        );
+
+        // Expires at exactly its expiry
+        clock.advance(Duration::from_secs(60));
+        assert_eq!(read_cf(&db, &cf, "session-a"), None);
+        assert_eq!(read_cf(&db, &cf, "session-b").as_deref(), Some("bob"));
+        assert_eq!(
+            multi_read(&db, &cf, &[b"session-a", b"session-b", b"user"]),
+            vec![None, Some("bob".into()), Some("carol".into())]
+        );
+        assert_eq!(
+            scan(&db, &cf),
+            pairs(&[("session-b", "bob"), ("user", "carol")])
+        );
+
+        // A newer write replaces the expired one
+        put_with_ttl(&db, "session-a", "alice-again", 60);
+        assert_eq!(
+            read_cf(&db, &cf, "session-a").as_deref(),
+            Some("alice-again")
+        );
+
+        // Still hidden once it is in a table file and the DB is reopened
+        clock.advance(Duration::from_secs(60));
+        db.flush(&cf).unwrap();
+        drop(cf);
+        drop(db);
+        let db = open(&dir, &clock);
+        let cf = db.default_column_family();
+        assert_eq!(scan(&db, &cf), pairs(&[("user", "carol")]));
+        assert_eq!(read_cf(&db, &cf, "session-b"), None);
+    }
+
+    #[test]
+    fn merges_read_through_the_expiry_of_their_base() {
+        let dir = TempDir::new("ttl-merge-db");
+        let clock = ManualClock::new(START);
+        let db = open(&dir, &clock);
+        let cf = db.default_column_family();
+
+        put_with_ttl(&db, "list", "a", 60);
+        let mut batch = Batch::new();
+        batch.merge_cf(0, b"list", b"b");
+        db.write(&mut batch).unwrap();
+        assert_eq!(read_cf(&db, &cf, "list").as_deref(), Some("a,b"));
+        assert_eq!(scan(&db, &cf), pairs(&[("list", "a,b")]));
+
+        // The compaction keeps the base apart from the operand, so it still expires
+        db.flush(&cf).unwrap();
+        db.compact_range(&cf).unwrap();
+        assert_eq!(read_cf(&db, &cf, "list").as_deref(), Some("a,b"));
+
+        clock.advance(Duration::from_secs(60));
+        assert_eq!(read_cf(&db, &cf, "list").as_deref(), Some("b"));
+        assert_eq!(scan(&db, &cf), pairs(&[("list", "b")]));
+    }
+
+    #[test]
+    fn compaction_drops_expired_entries() {
+        let dir = TempDir::new("ttl-compaction-db");
+        let clock = ManualClock::new(START);
+        let db = open(&dir, &clock);
+        let cf = db.default_column_family();
+
+        for i in 0..10 {
+            put_with_ttl(&db, &format!("k{i}"), "v", if i < 5 { 60 } else { 600 });
+        }
+        db.flush(&cf).unwrap();
+        let version = cf.data().current_version();
+        assert_eq!(version.level_files(0)[0].properties.num_deletions, 0);
+
+        clock.advance(Duration::from_secs(60));
+        db.compact_range(&cf).unwrap();
+        let version = cf.data().current_version();
+        let (_, table) = version.files().next().unwrap();
+        // The expired entries are gone, not just hidden
+        assert_eq!(table.properties.num_entries, 5);
+        assert_eq!(table.properties.num_deletions, 0);
+        assert_eq!(read_cf(&db, &cf, "k0"), None);
+        assert_eq!(read_cf(&db, &cf, "k9").as_deref(), Some("v"));
+
+        clock.advance(Duration::from_secs(600));
+        db.compact_range(&cf).unwrap();
+        assert_eq!(cf.data().current_version().files().count(), 0);
+    }
+}
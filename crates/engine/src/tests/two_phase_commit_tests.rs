@@ -0,0 +1,191 @@
+#[cfg(test)]
+mod tests {
+
+    use std::time::Duration;
+
+    use crate::db::filename::log_file;
+    use crate::db::wal::wal_files;
+    use crate::db::write_batch::Batch;
+    use crate::error::Error;
+    use crate::options::{DbOptions, ReadOptions, TransactionDbOptions, TransactionOptions};
+    use crate::tests::helpers::read_cf;
+    use crate::tests::temp_dir::TempDir;
+    use crate::transaction::transaction_db::TransactionDB;
+
+    fn open(dir: &TempDir) -> TransactionDB {
+        TransactionDB::open(
+            dir.path(),
+            &DbOptions {
+                create_if_missing: true,
+                ..Default::default()
+            },
+            Vec::new(),
+            TransactionDbOptions {
+                lock_timeout: Duration::from_millis(10),
+                ..Default::default()
+            },
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn prepared_writes_apply_on_commit_only() {
+        let dir = TempDir::new("2pc-commit");
+        let db = open(&dir);
+        let cf = db.db().default_column_family();
+
+        let mut txn = db.begin_transaction(&TransactionOptions::default());
+        txn.put(&cf, b"a", b"1").unwrap();
+        txn.prepare("t1").unwrap();
+        assert_eq!(txn.name(), Some("t1"));
+        assert_eq!(read_cf(db.db(), &cf, "a"), None);
+
+        // A prepared transaction takes no more writes and can't be prepared again
+        assert!(matches!(
+            txn.put(&cf, b"b", b"1"),
+            Err(Error::InvalidArgument(_))
+        ));
+        assert!(matches!(txn.prepare("t2"), Err(Error::InvalidArgument(_))));
+        // Its locks are still held
+        assert!(matches!(db.put(&cf, b"a", b"2"), Err(Error::TimedOut(_))));
+
+        // Names are unique among undecided transactions
+        let mut other = db.begin_transaction(&TransactionOptions::default());
+        assert!(matches!(
+            other.prepare("t1"),
+            Err(Error::InvalidArgument(_))
+        ));
+
+        txn.commit().unwrap();
+        assert_eq!(read_cf(db.db(), &cf, "a").as_deref(), Some("1"));
+        other.prepare("t1").unwrap();
+        other.commit().unwrap();
+    }
+
+    #[test]
+    fn rollback_discards_prepared_writes() {
+        let dir = TempDir::new("2pc-rollback");
+        let db = open(&dir);
+        let cf = db.db().default_column_family();
+
+        let mut txn = db.begin_transaction(&TransactionOptions::default());
+        txn.put(&cf, b"a", b"1").unwrap();
+        txn.prepare("t1").unwrap();
+        txn.rollback().unwrap();
+        assert_eq!(read_cf(db.db(), &cf, "a"), None);
+        db.put(&cf, b"a", b"2").unwrap();
+        drop(db);
+
+        // Neither the rollback nor the later write are lost
+        let db = open(&dir);
+        let cf = db.db().default_column_family();
+        assert_eq!(read_cf(db.db(), &cf, "a").as_deref(), Some("2"));
+        assert!(db.prepared_transactions().unwrap().is_empty());
+    }
+
+    #[test]
+    fn undecided_transactions_are_recovered() {
+        let dir = TempDir::new("2pc-recover");
+        {
+            let db = open(&dir);
+            let cf = db.db().default_column_family();
+            db.put(&cf, b"plain", b"1").unwrap();
+
+            for (name, key) in [("commit-me", "a"), ("roll-me-back", "b")] {
+                let mut txn = db.begin_transaction(&TransactionOptions::default());
+                txn.put(&cf, key.as_bytes(), name.as_bytes()).unwrap();
+                txn.prepare(name).unwrap();
+                // Dropped undecided, as if the process died
+            }
+        }
+
+        let db = open(&dir);
+        let cf = db.db().default_column_family();
+        assert_eq!(read_cf(db.db(), &cf, "plain").as_deref(), Some("1"));
+        assert_eq!(read_cf(db.db(), &cf, "a"), None);
+
+        let mut prepared = db.prepared_transactions().unwrap();
+        assert_eq!(
+            prepared
+                .iter()
+                .map(|t| t.name().unwrap())
+                .collect::<Vec<_>>(),
+            vec!["commit-me", "roll-me-back"]
+        );
+        assert!(db.prepared_transactions().unwrap().is_empty());
+        // The recovered transactions hold their locks and names again
+        assert!(matches!(db.put(&cf, b"a", b"x"), Err(Error::TimedOut(_))));
+        let mut other = db.begin_transaction(&TransactionOptions::default());
+        assert!(matches!(
+            other.prepare("commit-me"),
+            Err(Error::InvalidArgument(_))
+        ));
+
+        let roll_back = prepared.pop().unwrap();
+        let commit = prepared.pop().unwrap();
+        assert_eq!(
+            commit
+                .get(&ReadOptions::default(), &cf, b"a")
+                .unwrap()
+                .as_deref(),
+            Some(b"commit-me".as_slice())
+        );
+        commit.commit().unwrap();
+        roll_back.rollback().unwrap();
+        let sequence = db.db().last_sequence();
+        drop((other, prepared));
+        drop(db);
+
+        let db = open(&dir);
+        let cf = db.db().default_column_family();
+        assert_eq!(read_cf(db.db(), &cf, "a").as_deref(), Some("commit-me"));
+        assert_eq!(read_cf(db.db(), &cf, "b"), None);
+        assert_eq!(db.db().last_sequence(), sequence);
+        assert!(db.prepared_transactions().unwrap().is_empty());
+    }
+
+    #[test]
+    fn wals_with_undecided_transactions_are_kept() {
+        let dir = TempDir::new("2pc-retain");
+        let db = open(&dir);
+        let cf = db.db().default_column_family();
+
+        let mut txn = db.begin_transaction(&TransactionOptions::default());
+        txn.put(&cf, b"a", b"1").unwrap();
+        txn.prepare("t1").unwrap();
+        drop(txn);
+        drop(db);
+
+        let db = open(&dir);
+        let cf = db.db().default_column_family();
+        let wals = wal_files(dir.path()).unwrap();
+        assert_eq!(wals.len(), 2);
+        let (prepared_log, live_log) = (wals[0], wals[1]);
+
+        // As if a flush had put every write of the older WAL into a table file
+        cf.data().set_log_number(live_log);
+        assert_eq!(db.db().min_log_to_keep(), prepared_log);
+        assert_eq!(db.db().purge_obsolete_wal_files().unwrap(), 0);
+
+        let txn = db.prepared_transactions().unwrap().pop().unwrap();
+        txn.commit().unwrap();
+        assert_eq!(db.db().min_log_to_keep(), live_log);
+        assert_eq!(db.db().purge_obsolete_wal_files().unwrap(), 1);
+        assert!(!log_file(dir.path(), prepared_log).exists());
+        assert_eq!(read_cf(db.db(), &cf, "a").as_deref(), Some("1"));
+    }
+
+    #[test]
+    fn markers_are_not_writes() {
+        let dir = TempDir::new("2pc-markers");
+        let db = open(&dir);
+
+        let mut batch = Batch::new();
+        batch.put("a", "1");
+        let mut prepare = Batch::prepare_record("t1", &batch);
+        assert!(matches!(
+            db.db().write(&mut prepare),
+            Err(Error::InvalidArgument(_))
+        ));
+    }
+}
@@ -0,0 +1,107 @@
+#[cfg(test)]
+mod tests {
+
+    use std::collections::HashSet;
+    use std::ops::Range;
+    use std::sync::Mutex;
+    use std::thread;
+
+    use crate::db::db_impl::DbImpl;
+    use crate::db::write_batch::Batch;
+    use crate::error::Error;
+    use crate::options::{ColumnFamilyOptions, ReadOptions};
+    use crate::tests::helpers::open;
+    use crate::tests::temp_dir::TempDir;
+
+    const THREADS: usize = 8;
+    const WRITES: usize = 200;
+
+    fn key(thread: usize, i: usize) -> String {
+        format!("t{thread}-{i:04}")
+    }
+
+    fn assert_written(db: &DbImpl, threads: Range<usize>) {
+        let cf = db.default_column_family();
+        for t in threads {
+            for i in 0..WRITES {
+                let value = db
+                    .get(&ReadOptions::default(), &cf, key(t, i).as_bytes())
+                    .unwrap();
+                assert_eq!(value, Some(key(t, i).into_bytes()));
+            }
+        }
+    }
+
+    #[test]
+    fn concurrent_writes_are_all_applied() {
+        let dir = TempDir::new("write-thread-concurrent-db");
+        let db = open(&dir, ColumnFamilyOptions::default());
+        let sequences = Mutex::new(HashSet::new());
+
+        thread::scope(|s| {
+            for t in 0..THREADS {
+                let db = &db;
+                let sequences = &sequences;
+                s.spawn(move || {
+                    for i in 0..WRITES {
+                        let mut batch = Batch::new();
+                        batch.put_cf(0, key(t, i).as_bytes(), key(t, i).as_bytes());
+                        db.write(&mut batch).unwrap();
+                        // Every batch is given a seq no of its own, whichever leader wrote it
+                        assert!(sequences.lock().unwrap().insert(batch.sequence()));
+                    }
+                });
+            }
+        });
+
+        let total = (THREADS * WRITES) as u64;
+        assert_eq!(db.last_sequence(), total);
+        assert_eq!(
+            sequences.into_inner().unwrap(),
+            (1..=total).collect::<HashSet<_>>()
+        );
+        assert_written(&db, 0..THREADS);
+
+        // Grouped batches are logged as one WAL record which recovery replays whole
+        drop(db);
+        let db = open(&dir, ColumnFamilyOptions::default());
+        assert_eq!(db.last_sequence(), total);
+        assert_written(&db, 0..THREADS);
+    }
+
+    #[test]
+    fn a_bad_batch_fails_without_its_group() {
+        let db = DbImpl::new();
+
+        thread::scope(|s| {
+            for t in 0..THREADS {
+                let db = &db;
+                s.spawn(move || {
+                    for i in 0..WRITES {
+                        let mut batch = Batch::new();
+                        // The first thread writes to a column family which does not exist
+                        let cf_id = if t == 0 { 7 } else { 0 };
+                        batch.put_cf(cf_id, key(t, i).as_bytes(), key(t, i).as_bytes());
+                        let result = db.write(&mut batch);
+                        if t == 0 {
+                            assert!(matches!(result, Err(Error::InvalidArgument(_))));
+                        } else {
+                            result.unwrap();
+                        }
+                    }
+                });
+            }
+        });
+
+        assert_eq!(db.last_sequence(), ((THREADS - 1) * WRITES) as u64);
+        assert_written(&db, 1..THREADS);
+        let cf = db.default_column_family();
+        for i in 0..WRITES {
+            assert_eq!(
+                db.get(&ReadOptions::default(), &cf, key(0, i).as_bytes())
+                    .unwrap(),
+                None
+            );
+        }
+    }
+}
@@ -8,10 +8,14 @@ use crate::thread_ctx::TCTX;
 use crate::versioning::superversion::SVCache;
 
 //
-use std::cell::UnsafeCell;
+use std::cell::{Cell, UnsafeCell};
+
+use crate::iterator::iter_alloc::{IterAlloc, IterAllocPool, PooledAlloc};
 
 pub(crate) struct ThreadCtx {
     // sv_cache: UnsafeCell<SVCache>,
+    // Cache in front of the IterAllocPool which last released an alloc on the thread
+    iter_alloc: Cell<Option<PooledAlloc>>,
     // NOTE: Add PerfContext/Metrics
     // NOTE: Add IOContext/Metrics
 }
@@ -20,6 +24,34 @@ impl ThreadCtx {
     pub(crate) fn new() -> Self {
         Self {
             // sv_cache: UnsafeCell::new(SVCache::new()),
+            iter_alloc: Cell::new(None),
+        }
+    }
+
+    /// Takes the cached alloc if it belongs to the pool.
+    #[inline]
+    pub(crate) fn take_iter_alloc(&self, pool: &IterAllocPool) -> Option<IterAlloc> {
+        let cached = self.iter_alloc.take()?;
+        if cached.is_from(pool) {
+            return Some(cached.into_alloc());
+        }
+        self.iter_alloc.set(Some(cached));
+        None
+    }
+
+    /// Caches the alloc unless one of the same pool is cached already, in which case it is handed back. An alloc of another pool is
+    /// dropped in its place.
+    #[inline]
+    pub(crate) fn put_iter_alloc(&self, alloc: PooledAlloc) -> Option<PooledAlloc> {
+        match self.iter_alloc.take() {
+            Some(cached) if cached.is_from_same_pool(&alloc) => {
+                self.iter_alloc.set(Some(cached));
+                Some(alloc)
+            }
+            _ => {
+                self.iter_alloc.set(Some(alloc));
+                None
+            }
         }
     }
 
@@ -0,0 +1,206 @@
+//
+//
+//
+// Point Lock Manager
+//
+// Exclusive locks on (column family, user key), held by pessimistic transactions until they commit or roll back. Keys are striped over
+// a fixed number of mutexes by hash so transactions locking unrelated keys rarely contend, and each stripe has a condvar which waiters
+// sleep on until a lock of the stripe is released or their timeout passes.
+//
+// Deadlock detection
+//
+// A transaction which has to wait records an edge `waiter -> holder` in the wait-for graph before sleeping. If following the edges from
+// the holder leads back to the waiter, waiting would never end: the lock fails with Busy instead and the caller is expected to roll the
+// transaction back, which releases its locks and lets the rest of the cycle make progress.
+//
+//   T1 holds a, waits for b   (T1 -> T2)
+//   T2 holds b, asks for a    T2 -> T1 -> T2   cycle - T2 gets Busy
+//
+// An edge is only as fresh as the last time its waiter woke up, so a cycle through a lock released a moment ago can be reported. The
+// walk stops after DEADLOCK_DETECT_DEPTH edges - longer cycles are left to the lock timeout.
+
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::error::{Error, Result};
+
+pub(crate) type TransactionId = u64;
+
+const DEADLOCK_DETECT_DEPTH: usize = 50;
+
+type LockKey = (u32, Vec<u8>);
+
+struct LockStripe {
+    // Locked key -> holder
+    locks: Mutex<HashMap<LockKey, TransactionId>>,
+    released: Condvar,
+}
+
+pub(crate) struct PointLockManager {
+    stripes: Vec<LockStripe>,
+    // Waiting transaction -> the transaction holding the lock it waits for
+    wait_for: Mutex<HashMap<TransactionId, TransactionId>>,
+}
+
+impl PointLockManager {
+    pub(crate) fn new(num_stripes: usize) -> Self {
+        let stripes = (0..num_stripes.max(1))
+            .map(|_| LockStripe {
+                locks: Mutex::new(HashMap::new()),
+                released: Condvar::new(),
+            })
+            .collect();
+        Self {
+            stripes,
+            wait_for: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn stripe(&self, cf_id: u32, key: &[u8]) -> &LockStripe {
+        let mut hasher = DefaultHasher::new();
+        cf_id.hash(&mut hasher);
+        key.hash(&mut hasher);
+        &self.stripes[hasher.finish() as usize % self.stripes.len()]
+    }
+
+    /// Locks the key for `txn`, waiting up to `timeout` if another transaction holds it. Locking a key twice is a no-op. Fails with
+    /// TimedOut once the timeout passes, or with Busy if `detect_deadlock` and waiting would deadlock.
+    pub(crate) fn lock(
+        &self,
+        txn: TransactionId,
+        cf_id: u32,
+        key: &[u8],
+        timeout: Duration,
+        detect_deadlock: bool,
+    ) -> Result<()> {
+        let stripe = self.stripe(cf_id, key);
+        let deadline = Instant::now() + timeout;
+        let lock_key = (cf_id, key.to_vec());
+
+        let mut locks = stripe.locks.lock().unwrap();
+        loop {
+            let holder = match locks.get(&lock_key) {
+                Some(holder) if *holder != txn => *holder,
+                Some(_) => break,
+                None => {
+                    locks.insert(lock_key, txn);
+                    break;
+                }
+            };
+
+            let now = Instant::now();
+            if now >= deadline {
+                self.stop_waiting(txn);
+                return Err(Error::TimedOut(format!(
+                    "lock held by transaction {holder} in column family {cf_id}"
+                )));
+            }
+            if detect_deadlock {
+                self.wait_for(txn, holder)?;
+            }
+
+            locks = stripe
+                .released
+                .wait_timeout(locks, deadline - now)
+                .unwrap()
+                .0;
+        }
+
+        self.stop_waiting(txn);
+        Ok(())
+    }
+
+    /// Releases the keys `txn` holds and wakes their waiters. Keys it does not hold are skipped.
+    pub(crate) fn unlock<'k>(
+        &self,
+        txn: TransactionId,
+        keys: impl IntoIterator<Item = (u32, &'k [u8])>,
+    ) {
+        for (cf_id, key) in keys {
+            let stripe = self.stripe(cf_id, key);
+            let mut locks = stripe.locks.lock().unwrap();
+            let lock_key = (cf_id, key.to_vec());
+            if locks.get(&lock_key) == Some(&txn) {
+                locks.remove(&lock_key);
+                drop(locks);
+                stripe.released.notify_all();
+            }
+        }
+    }
+
+    /// The transaction holding the lock `txn` is waiting for, if it is waiting (and deadlock detection is on).
+    pub(crate) fn waiting_for(&self, txn: TransactionId) -> Option<TransactionId> {
+        self.wait_for.lock().unwrap().get(&txn).copied()
+    }
+
+    // Adds the edge txn -> holder, unless it closes a cycle
+    fn wait_for(&self, txn: TransactionId, holder: TransactionId) -> Result<()> {
+        let mut graph = self.wait_for.lock().unwrap();
+
+        let mut next = holder;
+        for _ in 0..DEADLOCK_DETECT_DEPTH {
+            if next == txn {
+                graph.remove(&txn);
+                return Err(Error::Busy(format!(
+                    "deadlock detected: transaction {txn} waits for {holder} which waits for it"
+                )));
+            }
+            match graph.get(&next) {
+                Some(waits_for) => next = *waits_for,
+                None => break,
+            }
+        }
+
+        graph.insert(txn, holder);
+        Ok(())
+    }
+
+    fn stop_waiting(&self, txn: TransactionId) {
+        self.wait_for.lock().unwrap().remove(&txn);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn locks_are_exclusive_and_reentrant() {
+        let locks = PointLockManager::new(4);
+        let none = Duration::ZERO;
+
+        locks.lock(1, 0, b"k", none, true).unwrap();
+        locks.lock(1, 0, b"k", none, true).unwrap();
+        // Same key, other column family
+        locks.lock(2, 1, b"k", none, true).unwrap();
+        assert!(matches!(
+            locks.lock(2, 0, b"k", none, true),
+            Err(Error::TimedOut(_))
+        ));
+        assert_eq!(locks.waiting_for(2), None);
+
+        // Only the holder can release it
+        locks.unlock(2, [(0, b"k".as_slice())]);
+        assert!(locks.lock(2, 0, b"k", none, false).is_err());
+        locks.unlock(1, [(0, b"k".as_slice())]);
+        locks.lock(2, 0, b"k", none, false).unwrap();
+    }
+
+    #[test]
+    fn cycles_in_the_wait_for_graph() {
+        let locks = PointLockManager::new(1);
+
+        locks.wait_for(1, 2).unwrap();
+        locks.wait_for(2, 3).unwrap();
+        assert!(matches!(locks.wait_for(3, 1), Err(Error::Busy(_))));
+        assert_eq!(locks.waiting_for(3), None);
+
+        locks.stop_waiting(1);
+        locks.wait_for(3, 1).unwrap();
+        assert_eq!(locks.waiting_for(3), Some(1));
+    }
+}
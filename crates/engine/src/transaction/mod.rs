@@ -0,0 +1,66 @@
+// Transactions
+//
+// Transactions group reads and writes over any number of keys (and column families) into one atomic unit. Writes are buffered in an
+// IndexedBatch, which the transaction reads through first so it sees its own writes, and are written as a single batch on commit.
+//
+// OptimisticTransaction takes no locks: it tracks what it read and fails the commit with Busy if any of it was written in the meantime.
+// PessimisticTransaction (from a TransactionDB) locks every key it writes or reads for update, so its commit cannot conflict - waiting
+// for a lock can time out or, with deadlock detection, fail with Busy instead.
+
+use crate::column_family::cf::ColumnFamilyHandle;
+use crate::db::db_impl::{DbImpl, check_read_timestamp};
+use crate::db::indexed_batch::IndexedBatch;
+use crate::db::read_path::{GetSource, get_with_range_del, get_with_timestamp};
+use crate::error::Result;
+use crate::key::internal_key::OperationType;
+use crate::key::lookup_key::LookUpInternalKey;
+use crate::options::ReadOptions;
+
+pub(crate) mod lock_manager;
+pub(crate) mod optimistic;
+pub(crate) mod pessimistic;
+pub(crate) mod transaction_db;
+
+// Reads the key through the transaction's writes on top of the column family. Returns the value and the seq no the DB was read at.
+fn read_through_batch(
+    db: &DbImpl,
+    writes: &IndexedBatch,
+    read_options: &ReadOptions,
+    column_family: &ColumnFamilyHandle,
+    key: &[u8],
+) -> Result<(Option<Vec<u8>>, u64)> {
+    let cfd = column_family.data();
+    check_read_timestamp(read_options, cfd)?;
+    // The range deletions are loaded before the read seq no (see RangeDelIndex::collect_garbage)
+    let range_del = cfd.range_del().version();
+    let sequence = read_options.read_sequence(db.last_sequence());
+
+    let writes = writes.source(column_family.id());
+    let sources: [&dyn GetSource; 2] = [&writes, cfd.superversion()];
+    let merge_operator = cfd.options().merge_operator.as_deref();
+    // Column families with timestamps have no range deletions
+    let value = match &read_options.timestamp {
+        Some(timestamp) => {
+            let lookup_key =
+                LookUpInternalKey::new(&[key, timestamp].concat(), sequence, OperationType::Max);
+            get_with_timestamp(
+                &sources,
+                lookup_key.as_ref(),
+                timestamp.len(),
+                merge_operator,
+                db.clock().now(),
+            )?
+        }
+        None => {
+            let lookup_key = LookUpInternalKey::new(key, sequence, OperationType::Max);
+            get_with_range_del(
+                &sources,
+                &range_del,
+                lookup_key.as_ref(),
+                merge_operator,
+                db.clock().now(),
+            )?
+        }
+    };
+    Ok((value, sequence))
+}
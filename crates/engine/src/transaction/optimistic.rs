@@ -0,0 +1,196 @@
+//
+//
+//
+// Optimistic Transaction
+//
+// Reads and writes take no locks. Every key the transaction reads or writes is tracked with the seq no it was first seen at (the seq no
+// of the transaction's snapshot if it has one, otherwise the DB's latest), and writes are buffered in an IndexedBatch.
+//
+// Commit writes the batch with a WriteCallback which checks, with no other write in between, that no tracked key has a newer write in
+// the memtables (or a newer range tombstone covering it) than the seq no it is tracked at. If one has, the commit fails with Busy and
+// nothing is written - the caller can run the transaction again.
+//
+//   txn.get(k)  -> tracked k@10          other writer: put(k)@11
+//   txn.put(k)                           txn.commit() -> newest k is @11 > 10 -> Busy
+//
+// Only the memtables are searched, so a key tracked before the oldest memtable was created also fails with Busy: a newer write to it may
+// have been flushed since, where it can no longer be found cheaply.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use crate::column_family::cf::ColumnFamilyHandle;
+use crate::db::db_impl::DbImpl;
+use crate::db::indexed_batch::IndexedBatch;
+use crate::db::write_callback::WriteCallback;
+use crate::error::{Error, Result};
+use crate::key::internal_key::MAX_SEQUENCE_NUMBER;
+use crate::options::ReadOptions;
+use crate::transaction::read_through_batch;
+use crate::versioning::snapshot::Snapshot;
+
+struct TrackedKey {
+    column_family: ColumnFamilyHandle,
+    // Seq no the key was first read or written at - any newer write to it is a conflict
+    sequence: u64,
+}
+
+// (column family id, user key) -> TrackedKey
+type TrackedKeys = BTreeMap<(u32, Vec<u8>), TrackedKey>;
+
+pub(crate) struct OptimisticTransaction<'a> {
+    db: &'a DbImpl,
+    // Conflicts are checked from the snapshot's seq no instead of the seq no each key was first seen at
+    snapshot: Option<Arc<Snapshot>>,
+    writes: IndexedBatch,
+    tracked: TrackedKeys,
+}
+
+impl<'a> OptimisticTransaction<'a> {
+    pub(crate) fn new(db: &'a DbImpl) -> Self {
+        Self {
+            db,
+            snapshot: None,
+            writes: IndexedBatch::new(),
+            tracked: BTreeMap::new(),
+        }
+    }
+
+    /// Pins the DB's current seq no: keys touched from now on conflict with any write after it, even one made before they are read.
+    pub(crate) fn set_snapshot(&mut self) {
+        self.snapshot = Some(self.db.get_snapshot());
+    }
+
+    pub(crate) fn snapshot(&self) -> Option<&Arc<Snapshot>> {
+        self.snapshot.as_ref()
+    }
+
+    /// Reads the key through the transaction's own writes and tracks it. The read is at ReadOptions::snapshot if set, otherwise at the
+    /// latest write.
+    pub(crate) fn get(
+        &mut self,
+        read_options: &ReadOptions,
+        column_family: &ColumnFamilyHandle,
+        key: &[u8],
+    ) -> Result<Option<Vec<u8>>> {
+        let (value, sequence) =
+            read_through_batch(self.db, &self.writes, read_options, column_family, key)?;
+        self.track(column_family, key, sequence);
+        Ok(value)
+    }
+
+    pub(crate) fn put(&mut self, column_family: &ColumnFamilyHandle, key: &[u8], value: &[u8]) {
+        self.track_write(column_family, key);
+        self.writes.put(column_family.id(), key, value);
+    }
+
+    pub(crate) fn delete(&mut self, column_family: &ColumnFamilyHandle, key: &[u8]) {
+        self.track_write(column_family, key);
+        self.writes.delete(column_family.id(), key);
+    }
+
+    pub(crate) fn merge(&mut self, column_family: &ColumnFamilyHandle, key: &[u8], value: &[u8]) {
+        self.track_write(column_family, key);
+        self.writes.merge(column_family.id(), key, value);
+    }
+
+    /// Number of keys the commit validates.
+    pub(crate) fn num_tracked_keys(&self) -> usize {
+        self.tracked.len()
+    }
+
+    /// Writes the buffered writes atomically if no tracked key was written since it was tracked, otherwise fails with Busy and writes
+    /// nothing.
+    pub(crate) fn commit(self) -> Result<()> {
+        let check = ConflictCheck {
+            tracked: &self.tracked,
+        };
+        let mut batch = self.writes.into_batch();
+        self.db.write_with_callback(&mut batch, Some(&check))
+    }
+
+    fn track_write(&mut self, column_family: &ColumnFamilyHandle, key: &[u8]) {
+        let sequence = self.db.last_sequence();
+        self.track(column_family, key, sequence);
+    }
+
+    // A key keeps the seq no it was first tracked at (or the snapshot's) - later reads must not hide a write made in between
+    fn track(&mut self, column_family: &ColumnFamilyHandle, key: &[u8], sequence: u64) {
+        let sequence = self
+            .snapshot
+            .as_ref()
+            .map_or(sequence, |snapshot| snapshot.sequence());
+        self.tracked
+            .entry((column_family.id(), key.to_vec()))
+            .and_modify(|tracked| tracked.sequence = tracked.sequence.min(sequence))
+            .or_insert_with(|| TrackedKey {
+                column_family: column_family.clone(),
+                sequence,
+            });
+    }
+}
+
+struct ConflictCheck<'t> {
+    tracked: &'t TrackedKeys,
+}
+
+impl WriteCallback for ConflictCheck<'_> {
+    fn callback(&self, _db: &DbImpl) -> Result<()> {
+        for ((_, key), tracked) in self.tracked {
+            let cfd = tracked.column_family.data();
+            let superversion = cfd.superversion();
+            let earliest = superversion.earliest_sequence_in_memtables();
+            if tracked.sequence < earliest {
+                return Err(Error::Busy(format!(
+                    "column family {} has no memtable history back to seq {} (its memtables start after seq {earliest})",
+                    cfd.name(),
+                    tracked.sequence
+                )));
+            }
+            let newest = superversion
+                .newest_sequence_in_memtables(key)
+                .unwrap_or(0)
+                .max(
+                    cfd.range_del()
+                        .version()
+                        .max_covering_seq(key, MAX_SEQUENCE_NUMBER),
+                );
+            if newest > tracked.sequence {
+                return Err(Error::Busy(format!(
+                    "write conflict in column family {}: key written at seq {newest} after it was read at {}",
+                    cfd.name(),
+                    tracked.sequence
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn keys_keep_the_oldest_tracked_sequence() {
+        let db = DbImpl::new();
+        let cf = db.default_column_family();
+        let mut txn = OptimisticTransaction::new(&db);
+
+        txn.put(&cf, b"k", b"v");
+        db.set_last_sequence(5);
+        txn.get(&ReadOptions::default(), &cf, b"k").unwrap();
+        txn.delete(&cf, b"other");
+
+        assert_eq!(txn.num_tracked_keys(), 2);
+        assert_eq!(txn.tracked[&(0, b"k".to_vec())].sequence, 0);
+        assert_eq!(txn.tracked[&(0, b"other".to_vec())].sequence, 5);
+
+        let mut txn = OptimisticTransaction::new(&db);
+        txn.set_snapshot();
+        db.set_last_sequence(9);
+        txn.put(&cf, b"k", b"v");
+        assert_eq!(txn.tracked[&(0, b"k".to_vec())].sequence, 5);
+    }
+}
@@ -0,0 +1,238 @@
+//
+//
+//
+// Pessimistic Transaction
+//
+// Every key the transaction writes, or reads with get_for_update, is locked in the TransactionDB's lock manager until the transaction
+// commits, rolls back or is dropped. Once a key is locked no other transaction can write it, so the commit never conflicts: it writes
+// the buffered batch through the DB's write path and releases the locks.
+//
+// A lock held by another transaction is waited for up to the lock timeout (TimedOut after it). With deadlock detection, a wait which
+// would close a cycle fails at once with Busy - the transaction should then be rolled back so the others in the cycle can go on.
+//
+// Two phase commit
+//
+// prepare(name) logs the buffered writes to the WAL (synced) without applying them, after which the transaction can only be committed or
+// rolled back - both log their decision. A prepared transaction survives a crash: the TransactionDB opened on the DB hands it back with
+// its keys locked again (TransactionDB::prepared_transactions).
+//
+// NOTE: Plain get() takes no lock and reads the latest write (or ReadOptions::snapshot). A snapshot is not validated against the locked
+// keys, so read-modify-write must use get_for_update.
+
+use std::collections::BTreeSet;
+use std::mem;
+use std::time::Duration;
+
+use crate::column_family::cf::ColumnFamilyHandle;
+use crate::db::indexed_batch::IndexedBatch;
+use crate::db::wal::RecoveredPrepared;
+use crate::error::{Error, Result};
+use crate::options::{ReadOptions, TransactionOptions};
+use crate::transaction::lock_manager::TransactionId;
+use crate::transaction::read_through_batch;
+use crate::transaction::transaction_db::TransactionDB;
+
+pub(crate) struct PessimisticTransaction<'a> {
+    id: TransactionId,
+    txn_db: &'a TransactionDB,
+    writes: IndexedBatch,
+    // (column family id, user key) of every lock held
+    locked: BTreeSet<(u32, Vec<u8>)>,
+    lock_timeout: Duration,
+    deadlock_detect: bool,
+    // Set by prepare (or recovery): the transaction's name and the WAL holding its prepared writes
+    prepared: Option<(String, u64)>,
+}
+
+impl<'a> PessimisticTransaction<'a> {
+    pub(super) fn new(
+        txn_db: &'a TransactionDB,
+        id: TransactionId,
+        options: &TransactionOptions,
+    ) -> Self {
+        Self {
+            id,
+            txn_db,
+            writes: IndexedBatch::new(),
+            locked: BTreeSet::new(),
+            lock_timeout: options
+                .lock_timeout
+                .unwrap_or(txn_db.options().lock_timeout),
+            deadlock_detect: options.deadlock_detect,
+            prepared: None,
+        }
+    }
+
+    // A prepared transaction found by recovery, with its keys locked again
+    pub(super) fn recovered(
+        txn_db: &'a TransactionDB,
+        id: TransactionId,
+        prepared: RecoveredPrepared,
+    ) -> Result<Self> {
+        let mut txn = Self::new(txn_db, id, &TransactionOptions::default());
+        txn.writes = IndexedBatch::from_batch(&prepared.batch)?;
+        for record in prepared.batch.iter() {
+            let record = record?;
+            txn.lock(record.cf_id, record.key)?;
+        }
+        txn.prepared = Some((prepared.name, prepared.log_number));
+        Ok(txn)
+    }
+
+    #[inline]
+    pub(crate) fn id(&self) -> TransactionId {
+        self.id
+    }
+
+    /// The name given to prepare, once the transaction is prepared.
+    pub(crate) fn name(&self) -> Option<&str> {
+        self.prepared.as_ref().map(|(name, _)| name.as_str())
+    }
+
+    /// Reads the key through the transaction's own writes without locking it.
+    pub(crate) fn get(
+        &self,
+        read_options: &ReadOptions,
+        column_family: &ColumnFamilyHandle,
+        key: &[u8],
+    ) -> Result<Option<Vec<u8>>> {
+        read_through_batch(
+            self.txn_db.db(),
+            &self.writes,
+            read_options,
+            column_family,
+            key,
+        )
+        .map(|(value, _)| value)
+    }
+
+    /// Locks the key, then reads it. No other transaction can write it until this one ends.
+    pub(crate) fn get_for_update(
+        &mut self,
+        read_options: &ReadOptions,
+        column_family: &ColumnFamilyHandle,
+        key: &[u8],
+    ) -> Result<Option<Vec<u8>>> {
+        self.lock_for_write(column_family, key)?;
+        self.get(read_options, column_family, key)
+    }
+
+    pub(crate) fn put(
+        &mut self,
+        column_family: &ColumnFamilyHandle,
+        key: &[u8],
+        value: &[u8],
+    ) -> Result<()> {
+        self.lock_for_write(column_family, key)?;
+        self.writes.put(column_family.id(), key, value);
+        Ok(())
+    }
+
+    pub(crate) fn delete(&mut self, column_family: &ColumnFamilyHandle, key: &[u8]) -> Result<()> {
+        self.lock_for_write(column_family, key)?;
+        self.writes.delete(column_family.id(), key);
+        Ok(())
+    }
+
+    pub(crate) fn merge(
+        &mut self,
+        column_family: &ColumnFamilyHandle,
+        key: &[u8],
+        value: &[u8],
+    ) -> Result<()> {
+        self.lock_for_write(column_family, key)?;
+        self.writes.merge(column_family.id(), key, value);
+        Ok(())
+    }
+
+    /// Number of keys the transaction holds locks on.
+    pub(crate) fn num_locks(&self) -> usize {
+        self.locked.len()
+    }
+
+    /// Logs the buffered writes to the WAL as the prepared transaction `name` without applying them. The name must be unique among the
+    /// prepared transactions of the DB. Only commit or rollback can follow.
+    pub(crate) fn prepare(&mut self, name: &str) -> Result<()> {
+        if let Some((prepared, _)) = &self.prepared {
+            return Err(Error::InvalidArgument(format!(
+                "transaction is already prepared as {prepared}"
+            )));
+        }
+        if name.is_empty() {
+            return Err(Error::InvalidArgument("transaction name is empty".into()));
+        }
+        self.txn_db.register_prepared(name)?;
+
+        match self.txn_db.db().prepare(name, self.writes.batch()) {
+            Ok(log_number) => {
+                self.prepared = Some((name.to_string(), log_number));
+                Ok(())
+            }
+            Err(e) => {
+                self.txn_db.unregister_prepared(name);
+                Err(e)
+            }
+        }
+    }
+
+    /// Writes the buffered writes atomically and releases the locks.
+    pub(crate) fn commit(mut self) -> Result<()> {
+        let mut batch = mem::take(&mut self.writes).into_batch();
+        match self.prepared.take() {
+            Some((name, log_number)) => {
+                self.txn_db
+                    .db()
+                    .commit_prepared(&name, &mut batch, log_number)?;
+                self.txn_db.unregister_prepared(&name);
+                Ok(())
+            }
+            None => self.txn_db.db().write(&mut batch),
+        }
+    }
+
+    /// Discards the buffered writes and releases the locks. Dropping the transaction does the same, except that a prepared
+    /// transaction stays undecided in the WAL until the DB is reopened.
+    pub(crate) fn rollback(mut self) -> Result<()> {
+        if let Some((name, log_number)) = self.prepared.take() {
+            self.txn_db.db().rollback_prepared(&name, log_number)?;
+            self.txn_db.unregister_prepared(&name);
+        }
+        Ok(())
+    }
+
+    fn lock_for_write(&mut self, column_family: &ColumnFamilyHandle, key: &[u8]) -> Result<()> {
+        if let Some((name, _)) = &self.prepared {
+            return Err(Error::InvalidArgument(format!(
+                "transaction {name} is prepared"
+            )));
+        }
+        self.lock(column_family.id(), key)
+    }
+
+    fn lock(&mut self, cf_id: u32, key: &[u8]) -> Result<()> {
+        let lock_key = (cf_id, key.to_vec());
+        if self.locked.contains(&lock_key) {
+            return Ok(());
+        }
+        self.txn_db.lock_manager().lock(
+            self.id,
+            cf_id,
+            key,
+            self.lock_timeout,
+            self.deadlock_detect,
+        )?;
+        self.locked.insert(lock_key);
+        Ok(())
+    }
+}
+
+impl Drop for PessimisticTransaction<'_> {
+    fn drop(&mut self) {
+        self.txn_db.lock_manager().unlock(
+            self.id,
+            self.locked
+                .iter()
+                .map(|(cf_id, key)| (*cf_id, key.as_slice())),
+        );
+    }
+}
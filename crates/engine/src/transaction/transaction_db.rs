@@ -0,0 +1,131 @@
+//
+//
+//
+// Transaction DB
+//
+// A DB whose writes go through pessimistic transactions. It owns the point lock manager every transaction locks its keys with, so writes
+// made through the TransactionDB (including the single key put/delete below) wait for the locks of running transactions. Writes made
+// directly to the inner DbImpl bypass the locks.
+//
+// The names of prepared transactions are unique while they are undecided. Prepared transactions recovered from the WAL keep their names
+// reserved and wait in the TransactionDB until the application takes them with prepared_transactions.
+
+use std::collections::HashSet;
+use std::mem;
+use std::path::Path;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::column_family::cf::{ColumnFamilyDescriptor, ColumnFamilyHandle};
+use crate::db::db_impl::DbImpl;
+use crate::db::wal::RecoveredPrepared;
+use crate::error::{Error, Result};
+use crate::options::{DbOptions, TransactionDbOptions, TransactionOptions};
+use crate::transaction::lock_manager::{PointLockManager, TransactionId};
+use crate::transaction::pessimistic::PessimisticTransaction;
+
+pub(crate) struct TransactionDB {
+    db: DbImpl,
+    options: TransactionDbOptions,
+    lock_manager: PointLockManager,
+    next_transaction_id: AtomicU64,
+    // Names of the undecided prepared transactions
+    prepared_names: Mutex<HashSet<String>>,
+    recovered: Mutex<Vec<RecoveredPrepared>>,
+}
+
+impl TransactionDB {
+    pub(crate) fn new(db: DbImpl, options: TransactionDbOptions) -> Self {
+        let recovered = db.take_recovered_prepared();
+        Self {
+            db,
+            lock_manager: PointLockManager::new(options.num_stripes),
+            options,
+            next_transaction_id: AtomicU64::new(1),
+            prepared_names: Mutex::new(recovered.iter().map(|p| p.name.clone()).collect()),
+            recovered: Mutex::new(recovered),
+        }
+    }
+
+    pub(crate) fn open(
+        path: &Path,
+        db_options: &DbOptions,
+        column_families: Vec<ColumnFamilyDescriptor>,
+        options: TransactionDbOptions,
+    ) -> Result<Self> {
+        Ok(Self::new(
+            DbImpl::open(path, db_options, column_families)?,
+            options,
+        ))
+    }
+
+    #[inline]
+    pub(crate) fn db(&self) -> &DbImpl {
+        &self.db
+    }
+
+    #[inline]
+    pub(crate) fn options(&self) -> &TransactionDbOptions {
+        &self.options
+    }
+
+    #[inline]
+    pub(crate) fn lock_manager(&self) -> &PointLockManager {
+        &self.lock_manager
+    }
+
+    pub(crate) fn begin_transaction(
+        &self,
+        options: &TransactionOptions,
+    ) -> PessimisticTransaction<'_> {
+        PessimisticTransaction::new(self, self.next_transaction_id(), options)
+    }
+
+    /// The prepared transactions recovered from the WAL, oldest first, each with its keys locked. They must be committed or rolled
+    /// back. Only the first call gets them.
+    pub(crate) fn prepared_transactions(&self) -> Result<Vec<PessimisticTransaction<'_>>> {
+        let recovered = mem::take(&mut *self.recovered.lock().unwrap());
+        recovered
+            .into_iter()
+            .map(|prepared| {
+                PessimisticTransaction::recovered(self, self.next_transaction_id(), prepared)
+            })
+            .collect()
+    }
+
+    pub(super) fn register_prepared(&self, name: &str) -> Result<()> {
+        if !self.prepared_names.lock().unwrap().insert(name.to_string()) {
+            return Err(Error::InvalidArgument(format!(
+                "a transaction named {name} is already prepared"
+            )));
+        }
+        Ok(())
+    }
+
+    pub(super) fn unregister_prepared(&self, name: &str) {
+        self.prepared_names.lock().unwrap().remove(name);
+    }
+
+    fn next_transaction_id(&self) -> TransactionId {
+        self.next_transaction_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Writes the key once no transaction holds its lock.
+    pub(crate) fn put(
+        &self,
+        column_family: &ColumnFamilyHandle,
+        key: &[u8],
+        value: &[u8],
+    ) -> Result<()> {
+        let mut txn = self.begin_transaction(&TransactionOptions::default());
+        txn.put(column_family, key, value)?;
+        txn.commit()
+    }
+
+    /// Deletes the key once no transaction holds its lock.
+    pub(crate) fn delete(&self, column_family: &ColumnFamilyHandle, key: &[u8]) -> Result<()> {
+        let mut txn = self.begin_transaction(&TransactionOptions::default());
+        txn.delete(column_family, key)?;
+        txn.commit()
+    }
+}
@@ -0,0 +1,132 @@
+//
+//
+//
+// Expiring Entries (TTL)
+//
+// A write can carry the wall clock time it expires at (Batch::put_cf_with_expiry). It is stored as an OperationType::PutWithExpiry whose
+// value is followed by the expiry:
+//
+// | value ... | expires_at (u64 unix seconds, fixed LE) |
+//
+// Until the expiry the entry reads as a Put of the value. From the expiry on (expires_at <= now) it reads as a Delete - get, multi_get
+// and iterators take `now` from DbOptions::clock when the read starts, so a scan sees one point in time.
+//
+// Reads only hide expired entries. TtlCompactionFilter drops them for good: registered as the compaction filter of the column family it
+// removes every expired entry a flush or compaction rewrites (the snapshot rules of compaction/filter.rs apply). Merges never fold
+// operands into an expiring base (see CompactionIterator::merge_run), the result would outlive the base.
+//
+// Expiring values are not separated into blob files.
+
+use std::sync::Arc;
+
+use crate::compaction::filter::{
+    CompactionFilter, CompactionFilterContext, FilterDecision, FilterValueType,
+};
+use crate::error::{Error, Result};
+use crate::utils::clock::Clock;
+
+const EXPIRY_SIZE: usize = size_of::<u64>();
+
+pub(crate) fn encode_expiring_value(value: &[u8], expires_at: u64) -> Vec<u8> {
+    let mut out = Vec::with_capacity(value.len() + EXPIRY_SIZE);
+    out.extend_from_slice(value);
+    out.extend_from_slice(&expires_at.to_le_bytes());
+    out
+}
+
+/// Splits the stored value of a PutWithExpiry into the value and its expiry.
+pub(crate) fn decode_expiring_value(value: &[u8]) -> Result<(&[u8], u64)> {
+    if value.len() < EXPIRY_SIZE {
+        return Err(Error::Corruption("expiring value is too short".into()));
+    }
+    let (value, expiry) = value.split_at(value.len() - EXPIRY_SIZE);
+    Ok((value, u64::from_le_bytes(expiry.try_into().unwrap())))
+}
+
+/// The value of a PutWithExpiry read at `now` - None once it has expired.
+pub(crate) fn read_expiring_value(value: &[u8], now: u64) -> Result<Option<&[u8]>> {
+    let (value, expires_at) = decode_expiring_value(value)?;
+    Ok((expires_at > now).then_some(value))
+}
+
+/// Removes expired entries during flushes and compactions. Every other entry is kept.
+pub struct TtlCompactionFilter {
+    clock: Arc<dyn Clock>,
+}
+
+impl TtlCompactionFilter {
+    pub fn new(clock: Arc<dyn Clock>) -> Arc<Self> {
+        Arc::new(Self { clock })
+    }
+}
+
+impl CompactionFilter for TtlCompactionFilter {
+    fn name(&self) -> &str {
+        "TtlCompactionFilter"
+    }
+
+    fn filter(
+        &self,
+        _ctx: &CompactionFilterContext,
+        _user_key: &[u8],
+        value_type: FilterValueType,
+        value: &[u8],
+    ) -> FilterDecision {
+        match value_type {
+            // A corrupt value is left for the reads to report
+            FilterValueType::ExpiringValue => match read_expiring_value(value, self.clock.now()) {
+                Ok(None) => FilterDecision::Remove,
+                Ok(Some(_)) | Err(_) => FilterDecision::Keep,
+            },
+            FilterValueType::Value | FilterValueType::MergeOperand => FilterDecision::Keep,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compaction::filter::TableFileCreationReason;
+    use crate::utils::clock::ManualClock;
+
+    #[test]
+    fn expiring_values_round_trip() {
+        let encoded = encode_expiring_value(b"session", 1_000);
+        assert_eq!(
+            decode_expiring_value(&encoded).unwrap(),
+            (&b"session"[..], 1_000)
+        );
+        assert_eq!(
+            read_expiring_value(&encoded, 999).unwrap(),
+            Some(&b"session"[..])
+        );
+        assert_eq!(read_expiring_value(&encoded, 1_000).unwrap(), None);
+        assert!(decode_expiring_value(b"short").is_err());
+    }
+
+    #[test]
+    fn filter_removes_expired_entries() {
+        let clock = ManualClock::new(50);
+        let filter = TtlCompactionFilter::new(clock.clone());
+        let ctx = CompactionFilterContext {
+            column_family_id: 0,
+            level: 0,
+            is_full_compaction: false,
+            is_manual_compaction: false,
+            reason: TableFileCreationReason::Flush,
+        };
+        let value = encode_expiring_value(b"v", 100);
+
+        let decide = |value_type, value: &[u8]| filter.filter(&ctx, b"k", value_type, value);
+        assert_eq!(
+            decide(FilterValueType::ExpiringValue, &value),
+            FilterDecision::Keep
+        );
+        clock.set(100);
+        assert_eq!(
+            decide(FilterValueType::ExpiringValue, &value),
+            FilterDecision::Remove
+        );
+        assert_eq!(decide(FilterValueType::Value, &value), FilterDecision::Keep);
+    }
+}
@@ -0,0 +1,56 @@
+//
+//
+//
+// CRC32C (Castagnoli)
+//
+// Checksums for log records (MANIFEST, WAL) and backup files. Table driven, one byte at a time - the records it covers are small and
+// written once, so there is no need for the hardware instruction yet.
+
+const POLY: u32 = 0x82f6_3b78;
+
+const TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ POLY
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// Extends `crc` (the checksum of some earlier data) with `data`.
+pub(crate) fn extend(crc: u32, data: &[u8]) -> u32 {
+    let mut crc = !crc;
+    for &b in data {
+        crc = TABLE[((crc ^ b as u32) & 0xff) as usize] ^ (crc >> 8);
+    }
+    !crc
+}
+
+#[inline]
+pub(crate) fn value(data: &[u8]) -> u32 {
+    extend(0, data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_values() {
+        assert_eq!(value(b""), 0);
+        assert_eq!(value(b"123456789"), 0xe306_9283);
+        assert_eq!(value(&[0u8; 32]), 0x8a91_36aa);
+        assert_eq!(extend(value(b"1234"), b"56789"), value(b"123456789"));
+    }
+}
@@ -1,3 +1,6 @@
+pub(crate) mod clock;
+pub(crate) mod crc32c;
+pub(crate) mod random_access_file;
 pub(crate) mod var_int;
 
 #[inline]
@@ -0,0 +1,104 @@
+//
+//
+//
+// RandomAccessFile
+//
+// Positioned reads of a file which is never modified once written - table and blob files. Readers keep the file open and read a block
+// or a record at a time, so a file is never held in memory as a whole. Concurrent reads need no locking, every read names its offset.
+//
+// A file can also be backed by a buffer, for tables and blob files which are built in memory and never written to a directory.
+
+use std::fs::File;
+use std::path::Path;
+
+use crate::error::{Error, Result};
+
+pub(crate) enum RandomAccessFile {
+    File { file: File, size: u64 },
+    Buffer(Vec<u8>),
+}
+
+impl RandomAccessFile {
+    pub(crate) fn open(path: &Path) -> Result<Self> {
+        let file = File::open(path)?;
+        let size = file.metadata()?.len();
+        Ok(Self::File { file, size })
+    }
+
+    #[inline]
+    pub(crate) fn size(&self) -> u64 {
+        match self {
+            Self::File { size, .. } => *size,
+            Self::Buffer(data) => data.len() as u64,
+        }
+    }
+
+    /// Reads the `len` bytes at `offset`, or fails with a Corruption if they are not all inside the file.
+    pub(crate) fn read(&self, offset: u64, len: usize) -> Result<Vec<u8>> {
+        let end = offset.checked_add(len as u64);
+        if end.is_none_or(|end| end > self.size()) {
+            return Err(Error::Corruption(format!(
+                "read of {len} bytes at {offset} outside a file of {} bytes",
+                self.size()
+            )));
+        }
+
+        match self {
+            Self::File { file, .. } => {
+                let mut buf = vec![0; len];
+                read_exact_at(file, &mut buf, offset)?;
+                Ok(buf)
+            }
+            Self::Buffer(data) => Ok(data[offset as usize..offset as usize + len].to_vec()),
+        }
+    }
+}
+
+impl From<Vec<u8>> for RandomAccessFile {
+    fn from(data: Vec<u8>) -> Self {
+        Self::Buffer(data)
+    }
+}
+
+#[cfg(unix)]
+fn read_exact_at(file: &File, buf: &mut [u8], offset: u64) -> std::io::Result<()> {
+    std::os::unix::fs::FileExt::read_exact_at(file, buf, offset)
+}
+
+#[cfg(windows)]
+fn read_exact_at(file: &File, mut buf: &mut [u8], mut offset: u64) -> std::io::Result<()> {
+    while !buf.is_empty() {
+        match std::os::windows::fs::FileExt::seek_read(file, buf, offset) {
+            Ok(0) => return Err(std::io::ErrorKind::UnexpectedEof.into()),
+            Ok(n) => {
+                buf = &mut std::mem::take(&mut buf)[n..];
+                offset += n as u64;
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::temp_dir::TempDir;
+
+    #[test]
+    fn reads_by_offset() {
+        let dir = TempDir::new("random-access-file");
+        let path = dir.path().join("file");
+        let data: Vec<u8> = (0..=255).collect();
+        std::fs::write(&path, &data).unwrap();
+
+        for file in [RandomAccessFile::open(&path).unwrap(), data.clone().into()] {
+            assert_eq!(file.size(), 256);
+            assert_eq!(file.read(10, 3).unwrap(), vec![10, 11, 12]);
+            assert_eq!(file.read(256, 0).unwrap(), Vec::<u8>::new());
+            assert!(matches!(file.read(250, 7), Err(Error::Corruption(_))));
+            assert!(matches!(file.read(u64::MAX, 1), Err(Error::Corruption(_))));
+        }
+    }
+}
@@ -55,6 +55,15 @@ impl VarInt {
         (result, bytes_read)
     }
 
+    /// Like decode but returns None if the buffer ends before the last byte of the VarInt (or the VarInt is longer than 4 bytes).
+    pub(crate) fn try_decode(buf: &[u8]) -> Option<(u32, usize)> {
+        let (value, read) = Self::decode(buf);
+        if read == 0 || read > 4 || buf[read - 1] & MSB != 0 {
+            return None;
+        }
+        Some((value, read))
+    }
+
     pub(crate) fn as_slice(&self) -> &[u8] {
         match self {
             Self::One(buf) => buf.as_ref(),
@@ -82,4 +91,8 @@ fn want() {
 
     assert_eq!(result_3.as_slice().len(), 4);
     assert_eq!(VarInt::decode(result_3.as_slice()), (3000000, 4));
+
+    assert_eq!(VarInt::try_decode(result_3.as_slice()), Some((3000000, 4)));
+    assert_eq!(VarInt::try_decode(&result_3.as_slice()[..2]), None);
+    assert_eq!(VarInt::try_decode(&[]), None);
 }
@@ -1 +1,387 @@
+//
+//
+//
+// FileMetaData describes a single SST file which is live in the LSM. It is created when a memtable is flushed or a compaction
+// produces an output and is shared (Arc) between every Version which includes the file.
+//
+// Version is an immutable view of the files in each level at a point in time. Readers take a Version (through the Superversion)
+// and compaction pickers read a Version to decide what work to do. Writers never mutate a published Version, they build a new one.
+//
+// Level 0 files may overlap and are ordered newest -> oldest (by largest seq no).
+// Level 1+ files are non-overlapping and ordered by smallest key.
+//
+// A point lookup searches the L0 files which may hold the key newest first, then at most one file per deeper level, found by binary
+// search on the largest keys.
+//
+// A Version also holds the blob files its tables reference (see blob/blob_file.rs), with how much of each is garbage. Blob references
+// are resolved as the tables are read, so nothing above a Version ever sees one.
 
+use std::cmp::{self, Reverse};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+
+use crate::blob::blob_file::BlobFile;
+use crate::blob::blob_index::BlobIndex;
+use crate::db::read_path::{GetContext, GetSource};
+use crate::error::{Error, Result};
+use crate::iterator::internal_iterator::InternalIterator;
+use crate::key::comparator::{Comparator, DefaultComparator, InternalKeyComparator};
+use crate::key::internal_key::{InternalKeyRef, OperationType};
+use crate::table::properties::TableProperties;
+use crate::table::table_reader::Table;
+
+pub(crate) type FileNumber = u64;
+
+pub(crate) const NUM_LEVELS: usize = 7;
+
+pub(crate) struct FileMetaData {
+    pub(crate) number: FileNumber,
+    pub(crate) file_size: u64,
+    // Smallest and largest internal keys in the file
+    pub(crate) smallest: Vec<u8>,
+    pub(crate) largest: Vec<u8>,
+    pub(crate) smallest_seqno: u64,
+    pub(crate) largest_seqno: u64,
+    pub(crate) properties: TableProperties,
+    // Set by a compaction picker when the file is an input to a running compaction so it is not picked twice
+    being_compacted: AtomicBool,
+    // The opened file - set once for every file of a Version readers search
+    table_reader: OnceLock<Arc<Table>>,
+}
+
+impl FileMetaData {
+    pub(crate) fn new(
+        number: FileNumber,
+        file_size: u64,
+        smallest: Vec<u8>,
+        largest: Vec<u8>,
+        smallest_seqno: u64,
+        largest_seqno: u64,
+        properties: TableProperties,
+    ) -> Self {
+        Self {
+            number,
+            file_size,
+            smallest,
+            largest,
+            smallest_seqno,
+            largest_seqno,
+            properties,
+            being_compacted: AtomicBool::new(false),
+            table_reader: OnceLock::new(),
+        }
+    }
+
+    /// Smallest and largest user keys in the file.
+    pub(crate) fn user_key_range(&self) -> (&[u8], &[u8]) {
+        (
+            InternalKeyRef::from(self.smallest.as_slice()).user_key,
+            InternalKeyRef::from(self.largest.as_slice()).user_key,
+        )
+    }
+
+    #[inline]
+    pub(crate) fn table_reader(&self) -> Option<&Arc<Table>> {
+        self.table_reader.get()
+    }
+
+    pub(crate) fn set_table_reader(&self, table: Arc<Table>) {
+        let _ = self.table_reader.set(table);
+    }
+
+    // Feeds the entries of the lookup key in the file into the context, with blob references resolved through the version. Returns true
+    // if the context has finished.
+    fn get(&self, version: &Version, lookup_key: &[u8], ctx: &mut GetContext<'_>) -> bool {
+        let Some(table) = self.table_reader() else {
+            ctx.set_error(Error::Corruption(format!(
+                "table file {} is not open",
+                self.number
+            )));
+            return true;
+        };
+
+        let lookup = InternalKeyRef::from(lookup_key);
+        // NOTE: Tombstones are fragmented bytewise, see Table::read_range_tombstones
+        let bytewise = DefaultComparator::new();
+        ctx.raise_covering_seq(table.range_tombstones().max_covering_seq(
+            bytewise.as_ref(),
+            lookup.user_key,
+            lookup.seq_no,
+        ));
+
+        let mut iter = table.iter(version.icmp.clone(), None);
+        iter.seek(lookup_key);
+        while iter.valid() {
+            let ikey = InternalKeyRef::from(iter.key());
+            if !ctx.key_matches(ikey.user_key) {
+                break;
+            }
+            let saved = if OperationType::from(ikey.op) == OperationType::BlobIndex {
+                match version.resolve_blob(ikey.user_key, iter.value()) {
+                    Ok(value) => {
+                        let resolved = InternalKeyRef {
+                            op: OperationType::Put as u8,
+                            ..ikey
+                        };
+                        ctx.save_value(resolved, &value)
+                    }
+                    Err(e) => {
+                        ctx.set_error(e);
+                        return true;
+                    }
+                }
+            } else {
+                ctx.save_value(ikey, iter.value())
+            };
+            if !saved {
+                return true;
+            }
+            iter.next();
+        }
+
+        if let Some(e) = iter.status() {
+            ctx.set_error(e.clone());
+            return true;
+        }
+        false
+    }
+
+    #[inline]
+    pub(crate) fn being_compacted(&self) -> bool {
+        self.being_compacted.load(Ordering::Acquire)
+    }
+
+    #[inline]
+    pub(crate) fn set_being_compacted(&self, value: bool) {
+        self.being_compacted.store(value, Ordering::Release);
+    }
+}
+
+/// A blob file which is live in the column family. Garbage is what no table references anymore - a blob file whose every blob is garbage
+/// is dropped from the version.
+pub(crate) struct BlobFileMetaData {
+    pub(crate) number: FileNumber,
+    pub(crate) total_blob_count: u64,
+    pub(crate) total_blob_bytes: u64,
+    pub(crate) garbage_blob_count: u64,
+    pub(crate) garbage_blob_bytes: u64,
+    // The opened file - shared by every version of the blob file's metadata
+    blob_reader: OnceLock<Arc<BlobFile>>,
+}
+
+impl BlobFileMetaData {
+    pub(crate) fn new(number: FileNumber, total_blob_count: u64, total_blob_bytes: u64) -> Self {
+        Self {
+            number,
+            total_blob_count,
+            total_blob_bytes,
+            garbage_blob_count: 0,
+            garbage_blob_bytes: 0,
+            blob_reader: OnceLock::new(),
+        }
+    }
+
+    /// The metadata with more garbage, sharing the opened file.
+    pub(crate) fn with_garbage(&self, blob_count: u64, blob_bytes: u64) -> Self {
+        let meta = Self {
+            garbage_blob_count: self.garbage_blob_count + blob_count,
+            garbage_blob_bytes: self.garbage_blob_bytes + blob_bytes,
+            ..Self::new(self.number, self.total_blob_count, self.total_blob_bytes)
+        };
+        if let Some(blob_file) = self.blob_reader() {
+            meta.set_blob_reader(Arc::clone(blob_file));
+        }
+        meta
+    }
+
+    /// Share of the blob bytes still referenced.
+    pub(crate) fn live_ratio(&self) -> f64 {
+        if self.total_blob_bytes == 0 {
+            return 0.0;
+        }
+        1.0 - self.garbage_blob_bytes as f64 / self.total_blob_bytes as f64
+    }
+
+    #[inline]
+    pub(crate) fn is_all_garbage(&self) -> bool {
+        self.garbage_blob_count >= self.total_blob_count
+    }
+
+    #[inline]
+    pub(crate) fn blob_reader(&self) -> Option<&Arc<BlobFile>> {
+        self.blob_reader.get()
+    }
+
+    pub(crate) fn set_blob_reader(&self, blob_file: Arc<BlobFile>) {
+        let _ = self.blob_reader.set(blob_file);
+    }
+}
+
+pub(crate) struct Version {
+    files: [Vec<Arc<FileMetaData>>; NUM_LEVELS],
+    blob_files: BTreeMap<FileNumber, Arc<BlobFileMetaData>>,
+    // Orders the internal keys of the table files
+    icmp: Arc<InternalKeyComparator>,
+}
+
+impl Version {
+    pub(crate) fn new() -> Self {
+        Self {
+            files: Default::default(),
+            blob_files: BTreeMap::new(),
+            icmp: InternalKeyComparator::new(),
+        }
+    }
+
+    /// The version with the given blob files. Blob files which are all garbage are left out.
+    pub(crate) fn with_blob_files(
+        mut self,
+        blob_files: impl IntoIterator<Item = Arc<BlobFileMetaData>>,
+    ) -> Self {
+        self.blob_files = blob_files
+            .into_iter()
+            .filter(|b| !b.is_all_garbage())
+            .map(|b| (b.number, b))
+            .collect();
+        self
+    }
+
+    /// Builds a version from the given levels, enforcing the ordering invariants described at the top of this file.
+    pub(crate) fn from_levels(
+        mut levels: Vec<Vec<Arc<FileMetaData>>>,
+        icmp: Arc<InternalKeyComparator>,
+    ) -> Self {
+        debug_assert!(levels.len() <= NUM_LEVELS);
+
+        let mut version = Self {
+            icmp,
+            ..Self::new()
+        };
+
+        for (level, mut files) in levels.drain(..).enumerate() {
+            if level == 0 {
+                files.sort_by_key(|f| Reverse(f.largest_seqno));
+            } else {
+                files.sort_by(|a, b| version.icmp.compare(&a.smallest, &b.smallest));
+            }
+            version.files[level] = files;
+        }
+
+        version
+    }
+
+    /// Orders the internal keys of the table files.
+    #[inline]
+    pub(crate) fn internal_comparator(&self) -> &Arc<InternalKeyComparator> {
+        &self.icmp
+    }
+
+    #[inline]
+    pub(crate) fn level_files(&self, level: usize) -> &[Arc<FileMetaData>] {
+        &self.files[level]
+    }
+
+    #[inline]
+    pub(crate) fn num_level_files(&self, level: usize) -> usize {
+        self.files[level].len()
+    }
+
+    pub(crate) fn level_size(&self, level: usize) -> u64 {
+        self.files[level].iter().map(|f| f.file_size).sum()
+    }
+
+    pub(crate) fn total_size(&self) -> u64 {
+        (0..NUM_LEVELS).map(|l| self.level_size(l)).sum()
+    }
+
+    /// True if a file of the level holds a user key in [smallest, largest].
+    pub(crate) fn overlaps_level(
+        &self,
+        level: usize,
+        smallest: &[u8],
+        largest: &[u8],
+        user_comparator: &dyn Comparator,
+    ) -> bool {
+        self.files[level].iter().any(|f| {
+            let (file_smallest, file_largest) = f.user_key_range();
+            user_comparator.compare(file_largest, smallest) != cmp::Ordering::Less
+                && user_comparator.compare(file_smallest, largest) != cmp::Ordering::Greater
+        })
+    }
+
+    /// Blob files of the version by number.
+    #[inline]
+    pub(crate) fn blob_files(&self) -> &BTreeMap<FileNumber, Arc<BlobFileMetaData>> {
+        &self.blob_files
+    }
+
+    /// Reads the value the blob reference `blob_index` (the value of a BlobIndex entry of `user_key`) points to.
+    pub(crate) fn resolve_blob(&self, user_key: &[u8], blob_index: &[u8]) -> Result<Vec<u8>> {
+        let index = BlobIndex::decode(blob_index)?;
+        let blob_file = self
+            .blob_files
+            .get(&index.file_number)
+            .and_then(|b| b.blob_reader())
+            .ok_or_else(|| {
+                Error::Corruption(format!(
+                    "blob file {} is not part of the version",
+                    index.file_number
+                ))
+            })?;
+        blob_file.get(user_key, &index)
+    }
+
+    /// Every file of the version with its level.
+    pub(crate) fn files(&self) -> impl Iterator<Item = (usize, &Arc<FileMetaData>)> {
+        self.files
+            .iter()
+            .enumerate()
+            .flat_map(|(level, files)| files.iter().map(move |f| (level, f)))
+    }
+}
+
+impl GetSource for Version {
+    fn get(&self, lookup_key: &[u8], ctx: &mut GetContext<'_>) -> bool {
+        let lookup = InternalKeyRef::from(lookup_key);
+        // Versions of the key with any timestamp may be in the file
+        let user_comparator = self.icmp.user_comparator();
+        let contains = |f: &FileMetaData| {
+            let (smallest, largest) = f.user_key_range();
+            user_comparator.compare_without_timestamp(smallest, true, lookup.user_key, true)
+                != cmp::Ordering::Greater
+                && user_comparator.compare_without_timestamp(lookup.user_key, true, largest, true)
+                    != cmp::Ordering::Greater
+        };
+
+        for f in &self.files[0] {
+            // Every entry of the file is newer than the read seq no
+            if f.smallest_seqno > lookup.seq_no || !contains(f) {
+                continue;
+            }
+            if f.get(self, lookup_key, ctx) {
+                return true;
+            }
+        }
+
+        for files in &self.files[1..] {
+            let i = files.partition_point(|f| {
+                self.icmp.compare(&f.largest, lookup_key) == cmp::Ordering::Less
+            });
+            if let Some(f) = files.get(i)
+                && f.smallest_seqno <= lookup.seq_no
+                && contains(f)
+                && f.get(self, lookup_key, ctx)
+            {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+impl Default for Version {
+    fn default() -> Self {
+        Self::new()
+    }
+}
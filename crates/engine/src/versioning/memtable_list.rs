@@ -1,4 +1,6 @@
+use crate::db::read_path::{GetContext, GetSource};
 use crate::memtable::memtable::{Flushed, Immutable, Memtable, Mutable};
+use crate::multi_get::{MultiGetBatch, MultiGetSource};
 use std::{ptr::NonNull, sync::Arc};
 
 //------------------
@@ -8,14 +10,86 @@ use std::{ptr::NonNull, sync::Arc};
 //
 // MemtableList holds the immutable state and logic for Immutable Memtables
 pub(crate) struct MemTableList {
+    // Newest -> oldest
     imm: Vec<Memtable<Immutable>>,
-    current_version: MemListVersion,
+    current_version: Arc<MemListVersion>,
     flushed: Vec<Memtable<Flushed>>,
 }
 
+impl MemTableList {
+    pub(crate) fn new() -> Self {
+        Self {
+            imm: Vec::new(),
+            current_version: Arc::new(MemListVersion::new(Vec::new())),
+            flushed: Vec::new(),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn current(&self) -> Arc<MemListVersion> {
+        Arc::clone(&self.current_version)
+    }
+
+    /// Adds a memtable switched out of the write path as the newest and publishes a new version holding it.
+    pub(crate) fn add(&mut self, imm: Memtable<Immutable>) -> Arc<MemListVersion> {
+        self.imm.insert(0, imm);
+        self.current_version = Arc::new(MemListVersion::new(self.imm.clone()));
+        self.current()
+    }
+
+    /// Removes every memtable once a flush has written them to a table file. Readers of an older version keep them alive.
+    pub(crate) fn clear(&mut self) -> Arc<MemListVersion> {
+        self.imm.clear();
+        self.current_version = Arc::new(MemListVersion::new(Vec::new()));
+        self.current()
+    }
+}
+
 // Memtable List Version is a snapshot of the memtable registry at a given point in time
 // We centralise the memtable registry access for a particular point in time to give to a database snapshot which will allow readers to
 // access memtables without blocking or seeing conflicting states
 pub(crate) struct MemListVersion {
+    // Newest -> oldest
     imm_version_list: Vec<Memtable<Immutable>>,
 }
+
+// SAFETY: Immutable memtables are never written again and skip list reads are safe from any thread
+unsafe impl Send for MemListVersion {}
+unsafe impl Sync for MemListVersion {}
+
+impl MemListVersion {
+    pub(crate) fn new(imm_version_list: Vec<Memtable<Immutable>>) -> Self {
+        Self { imm_version_list }
+    }
+
+    pub(crate) fn memtables(&self) -> &[Memtable<Immutable>] {
+        &self.imm_version_list
+    }
+
+    /// Seq no of the newest write to the user key in any of the memtables.
+    pub(crate) fn newest_sequence(&self, user_key: &[u8]) -> Option<u64> {
+        // Newest first, so the first memtable with a write to the key has the newest one
+        self.imm_version_list
+            .iter()
+            .find_map(|imm| imm.newest_sequence(user_key))
+    }
+}
+
+impl GetSource for MemListVersion {
+    fn get(&self, lookup_key: &[u8], ctx: &mut GetContext<'_>) -> bool {
+        self.imm_version_list
+            .iter()
+            .any(|imm| imm.get(lookup_key, ctx))
+    }
+}
+
+impl MultiGetSource for MemListVersion {
+    fn multi_get(&self, batch: &mut MultiGetBatch<'_>) {
+        for imm in &self.imm_version_list {
+            if batch.is_done() {
+                return;
+            }
+            imm.multi_get(batch);
+        }
+    }
+}
@@ -1,2 +1,7 @@
+pub(crate) mod file_version;
 pub(crate) mod memtable_list;
+pub(crate) mod seqno_to_time;
+pub(crate) mod snapshot;
 pub(crate) mod superversion;
+pub(crate) mod version_edit;
+pub(crate) mod version_set;
@@ -0,0 +1,204 @@
+//
+//
+//
+// Seq No To Time Mapping
+//
+// Seq nos say in which order writes happened, not when. The DB samples its newest seq no with the wall clock every
+// DbOptions::seqno_time_sample_period, each sample (seq, time) saying "at `time` the newest write was `seq`". Between two samples
+// (s1, t1) and (s2, t2) a write with s1 < seq <= s2 happened in (t1, t2] - the mapping answers time questions with these bounds.
+//
+// Both the seq nos and the times of the samples only grow. The DB keeps the newest MAX_SAMPLES in memory, shared between superversions,
+// and every table file keeps the samples covering its own seq nos in its properties (see TableBuilder::with_seqno_to_time), so the age of
+// old data survives restarts and compactions long after the samples left memory.
+
+use std::mem;
+
+/// Samples the DB keeps in memory.
+pub(crate) const MAX_SAMPLES: usize = 1000;
+/// Samples a table file keeps.
+pub(crate) const MAX_SAMPLES_PER_FILE: usize = 100;
+
+/// A seq no and the unix time (secs) at which it was the newest write of the DB.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct SeqnoTimePair {
+    pub(crate) seq: u64,
+    pub(crate) time: u64,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct SeqnoToTimeMapping {
+    // Ordered by seq and by time
+    pairs: Vec<SeqnoTimePair>,
+}
+
+impl SeqnoToTimeMapping {
+    #[inline]
+    pub(crate) fn pairs(&self) -> &[SeqnoTimePair] {
+        &self.pairs
+    }
+
+    #[inline]
+    pub(crate) fn is_empty(&self) -> bool {
+        self.pairs.is_empty()
+    }
+
+    /// Time of the newest sample.
+    pub(crate) fn last_time(&self) -> Option<u64> {
+        self.pairs.last().map(|p| p.time)
+    }
+
+    /// Adds a sample newer than every other. A sample which goes back in seq no or time is ignored, one without writes since the last
+    /// sample moves its time forward - that bound is tighter. The oldest sample is dropped beyond MAX_SAMPLES.
+    pub(crate) fn append(&mut self, seq: u64, time: u64) {
+        self.push(seq, time);
+        self.truncate(MAX_SAMPLES);
+    }
+
+    /// Adds the samples of `other`, e.g. from the properties of a table file. Samples which contradict a kept one are dropped. Every
+    /// other sample is kept, so the result can hold more than MAX_SAMPLES.
+    pub(crate) fn merge(&mut self, other: &SeqnoToTimeMapping) {
+        if other.is_empty() {
+            return;
+        }
+        let mut pairs = mem::take(&mut self.pairs);
+        pairs.extend_from_slice(&other.pairs);
+        pairs.sort_by_key(|p| (p.seq, p.time));
+        for pair in pairs {
+            self.push(pair.seq, pair.time);
+        }
+    }
+
+    /// Drops the oldest samples beyond `max`.
+    pub(crate) fn truncate(&mut self, max: usize) {
+        if self.pairs.len() > max {
+            self.pairs.drain(..self.pairs.len() - max);
+        }
+    }
+
+    fn push(&mut self, seq: u64, time: u64) {
+        if let Some(last) = self.pairs.last_mut() {
+            if seq < last.seq || time < last.time {
+                return;
+            }
+            if seq == last.seq {
+                last.time = time;
+                return;
+            }
+        }
+        self.pairs.push(SeqnoTimePair { seq, time });
+    }
+
+    /// A time the write with `seq` happened at or after - None if it is older than every sample.
+    pub(crate) fn proximal_time_before(&self, seq: u64) -> Option<u64> {
+        let idx = self.pairs.partition_point(|p| p.seq < seq);
+        idx.checked_sub(1).map(|i| self.pairs[i].time)
+    }
+
+    /// The newest seq no every write up to which happened at or before `time` - None if `time` is before every sample.
+    pub(crate) fn proximal_seqno_before_time(&self, time: u64) -> Option<u64> {
+        let idx = self.pairs.partition_point(|p| p.time <= time);
+        idx.checked_sub(1).map(|i| self.pairs[i].seq)
+    }
+
+    /// The samples a table file with seq nos in [smallest_seqno, largest_seqno] keeps: those inside the range and the one on each side,
+    /// thinned evenly to MAX_SAMPLES_PER_FILE.
+    pub(crate) fn for_range(&self, smallest_seqno: u64, largest_seqno: u64) -> Self {
+        let start = self
+            .pairs
+            .partition_point(|p| p.seq < smallest_seqno)
+            .saturating_sub(1);
+        let end = (self.pairs.partition_point(|p| p.seq < largest_seqno) + 1).min(self.pairs.len());
+        let pairs = self.pairs.get(start..end).unwrap_or_default();
+        if pairs.len() <= MAX_SAMPLES_PER_FILE {
+            return Self {
+                pairs: pairs.to_vec(),
+            };
+        }
+
+        // The first and the last sample bound the whole file, so both are kept
+        let last = pairs.len() - 1;
+        let pairs = (0..MAX_SAMPLES_PER_FILE)
+            .map(|i| pairs[i * last / (MAX_SAMPLES_PER_FILE - 1)])
+            .collect();
+        Self { pairs }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mapping(pairs: &[(u64, u64)]) -> SeqnoToTimeMapping {
+        let mut mapping = SeqnoToTimeMapping::default();
+        for &(seq, time) in pairs {
+            mapping.append(seq, time);
+        }
+        mapping
+    }
+
+    #[test]
+    fn time_bounds_of_seq_nos() {
+        let m = mapping(&[(10, 100), (20, 200), (30, 300)]);
+
+        assert_eq!(m.proximal_time_before(5), None);
+        assert_eq!(m.proximal_time_before(10), None);
+        assert_eq!(m.proximal_time_before(11), Some(100));
+        assert_eq!(m.proximal_time_before(20), Some(100));
+        assert_eq!(m.proximal_time_before(25), Some(200));
+        assert_eq!(m.proximal_time_before(1000), Some(300));
+
+        assert_eq!(m.proximal_seqno_before_time(99), None);
+        assert_eq!(m.proximal_seqno_before_time(100), Some(10));
+        assert_eq!(m.proximal_seqno_before_time(250), Some(20));
+        assert_eq!(m.proximal_seqno_before_time(u64::MAX), Some(30));
+    }
+
+    #[test]
+    fn samples_only_move_forward() {
+        let mut m = mapping(&[(10, 100), (20, 200)]);
+        m.append(15, 300);
+        m.append(30, 150);
+        assert_eq!(m, mapping(&[(10, 100), (20, 200)]));
+
+        // No writes since the last sample
+        m.append(20, 250);
+        assert_eq!(m, mapping(&[(10, 100), (20, 250)]));
+
+        for i in 0..MAX_SAMPLES as u64 {
+            m.append(100 + i, 1000 + i);
+        }
+        assert_eq!(m.pairs().len(), MAX_SAMPLES);
+        assert_eq!(m.pairs()[0].seq, 100);
+
+        let mut merged = mapping(&[(10, 100), (30, 300)]);
+        merged.merge(&mapping(&[(20, 200), (40, 400)]));
+        assert_eq!(
+            merged,
+            mapping(&[(10, 100), (20, 200), (30, 300), (40, 400)])
+        );
+        // Of two contradicting samples the one with the smaller seq no is kept
+        merged.merge(&mapping(&[(25, 350)]));
+        assert_eq!(
+            merged,
+            mapping(&[(10, 100), (20, 200), (25, 350), (40, 400)])
+        );
+    }
+
+    #[test]
+    fn samples_of_a_file() {
+        let m = mapping(&[(10, 100), (20, 200), (30, 300), (40, 400)]);
+        assert_eq!(m.for_range(21, 29), mapping(&[(20, 200), (30, 300)]));
+        assert_eq!(
+            m.for_range(20, 30),
+            mapping(&[(10, 100), (20, 200), (30, 300)])
+        );
+        assert_eq!(m.for_range(1, 5), mapping(&[(10, 100)]));
+        assert_eq!(m.for_range(50, 60), mapping(&[(40, 400)]));
+
+        let m = mapping(&(0..1000).map(|i| (i, i)).collect::<Vec<_>>());
+        let file = m.for_range(0, 999);
+        assert_eq!(file.pairs().len(), MAX_SAMPLES_PER_FILE);
+        assert_eq!(file.pairs()[0].seq, 0);
+        assert_eq!(file.pairs()[MAX_SAMPLES_PER_FILE - 1].seq, 999);
+    }
+}
@@ -0,0 +1,143 @@
+//
+//
+//
+// Snapshots
+//
+// A Snapshot pins a read sequence number - reads through ReadOptions::snapshot only see entries written at or before it, however many
+// writes land afterwards.
+//
+// The SnapshotList keeps every live snapshot ordered by seq no. Flushes and compactions take the list (sequence_numbers()) and hand it
+// to the CompactionIterator, which keeps the newest version of a key in every snapshot stripe so each snapshot keeps reading what it
+// read when it was taken. A snapshot taken after a job read the list is at or above every seq no in the job's input, so it only ever
+// reads the newest version of each key, which is always kept.
+//
+// Handles unregister themselves when dropped. Several snapshots can share a seq no (no writes in between) so the list counts them.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+pub(crate) struct SnapshotList {
+    // seq no -> number of live snapshots at it
+    live: Mutex<BTreeMap<u64, usize>>,
+}
+
+impl SnapshotList {
+    pub(crate) fn new() -> Arc<Self> {
+        Arc::new(Self {
+            live: Mutex::new(BTreeMap::new()),
+        })
+    }
+
+    /// Registers a snapshot at `sequence`. It stays in the list until the handle is dropped.
+    pub(crate) fn new_snapshot(self: &Arc<Self>, sequence: u64) -> Snapshot {
+        *self.live.lock().unwrap().entry(sequence).or_insert(0) += 1;
+        Snapshot {
+            sequence,
+            list: Arc::clone(self),
+        }
+    }
+
+    /// Registers a snapshot at the seq no returned by `sequence` while holding the list lock, so the snapshot is visible to any job
+    /// which reads the list after the seq no was chosen.
+    pub(crate) fn new_snapshot_with(self: &Arc<Self>, sequence: impl FnOnce() -> u64) -> Snapshot {
+        let mut live = self.live.lock().unwrap();
+        let sequence = sequence();
+        *live.entry(sequence).or_insert(0) += 1;
+        Snapshot {
+            sequence,
+            list: Arc::clone(self),
+        }
+    }
+
+    fn unregister(&self, sequence: u64) {
+        let mut live = self.live.lock().unwrap();
+        let count = live
+            .get_mut(&sequence)
+            .expect("snapshot released twice or by another list");
+        *count -= 1;
+        if *count == 0 {
+            live.remove(&sequence);
+        }
+    }
+
+    /// Seq nos of the live snapshots in ascending order, one entry per distinct seq no.
+    pub(crate) fn sequence_numbers(&self) -> Vec<u64> {
+        self.live.lock().unwrap().keys().copied().collect()
+    }
+
+    pub(crate) fn oldest(&self) -> Option<u64> {
+        self.live.lock().unwrap().keys().next().copied()
+    }
+
+    pub(crate) fn newest(&self) -> Option<u64> {
+        self.live.lock().unwrap().keys().next_back().copied()
+    }
+
+    /// Number of live snapshot handles.
+    pub(crate) fn len(&self) -> usize {
+        self.live.lock().unwrap().values().sum()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.live.lock().unwrap().is_empty()
+    }
+}
+
+pub(crate) struct Snapshot {
+    sequence: u64,
+    list: Arc<SnapshotList>,
+}
+
+impl Snapshot {
+    #[inline]
+    pub(crate) fn sequence(&self) -> u64 {
+        self.sequence
+    }
+}
+
+impl Drop for Snapshot {
+    fn drop(&mut self) {
+        self.list.unregister(self.sequence);
+    }
+}
+
+impl fmt::Debug for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Snapshot")
+            .field("sequence", &self.sequence)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn list_is_ordered_and_counts_handles() {
+        let list = SnapshotList::new();
+        assert!(list.is_empty());
+
+        let s7 = list.new_snapshot(7);
+        let s3 = list.new_snapshot(3);
+        let s7_again = list.new_snapshot(7);
+        let s5 = list.new_snapshot_with(|| 5);
+
+        assert_eq!(list.sequence_numbers(), vec![3, 5, 7]);
+        assert_eq!(list.len(), 4);
+        assert_eq!(list.oldest(), Some(3));
+        assert_eq!(list.newest(), Some(7));
+        assert_eq!(s5.sequence(), 5);
+
+        drop(s7);
+        assert_eq!(list.sequence_numbers(), vec![3, 5, 7]);
+        drop(s7_again);
+        assert_eq!(list.sequence_numbers(), vec![3, 5]);
+
+        drop(s3);
+        drop(s5);
+        assert!(list.is_empty());
+        assert_eq!(list.oldest(), None);
+    }
+}
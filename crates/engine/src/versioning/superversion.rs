@@ -3,15 +3,29 @@
 //
 //
 //
+use std::cmp::Ordering;
 use std::ptr::NonNull;
 use std::sync::Arc;
 
 use mem::hazard::domain::Global;
 use mem::hazard::hazard_ptr::HzdPtr;
 
+use crate::blob::blob_iter::BlobResolvingIterator;
 use crate::column_family::cf::ColumnFamilyData;
+use crate::db::read_path::{GetContext, GetSource};
+use crate::error::{Error, Result};
+use crate::iterator::bounds::IterBounds;
+use crate::iterator::db_iter::DBIterBuilder;
+use crate::iterator::internal_iterator::InternalIterator;
+use crate::iterator::iter_alloc::IterAllocStrategy;
+use crate::key::comparator::Comparator;
+use crate::key::internal_key::{InternalKeyRef, MAX_SEQUENCE_NUMBER, OperationType};
+use crate::key::lookup_key::LookUpInternalKey;
 use crate::memtable::memtable::{Immutable, Memtable, Mutable, ReadableMemtable};
+use crate::multi_get::{MultiGetBatch, MultiGetSource};
+use crate::versioning::file_version::{FileMetaData, NUM_LEVELS, Version};
 use crate::versioning::memtable_list::MemListVersion;
+use crate::versioning::seqno_to_time::SeqnoToTimeMapping;
 
 pub(crate) struct Superversion {
     // NOTE: Backpointer which should be guranteed to outlive all super versions it must also be a stable heap-allocated object
@@ -25,16 +39,142 @@ pub(crate) struct Superversion {
     // Even though SuperVersion is protected by HazardPointer that protection is only granted to itself and the objects it owns NOT for shared objects that
     // exist elsewhere
     imm: Arc<MemListVersion>,
+    // Snapshot of the DB's seq no to time mapping when the superversion was installed, usually shared between superversions
+    seqno_to_time: Arc<SeqnoToTimeMapping>,
     // TO_ADD:
-    // version: *version,
+    // version: Arc<Version> (read through the column family for now)
     // version_number
     // write_stall_condition
-    //
-    // From RocksDB:
-    // An immutable snapshot of the DB's seqno to time mapping, usually shared
-    // between SuperVersions.
-    // std::shared_ptr<const SeqnoToTimeMapping> seqno_to_time_mapping{nullptr};
-    //
+}
+
+// SAFETY: The memtables are only read through a superversion and the column family behind the back pointer outlives it
+unsafe impl Send for Superversion {}
+unsafe impl Sync for Superversion {}
+
+impl Superversion {
+    pub(crate) fn new(
+        cf: NonNull<ColumnFamilyData>,
+        mem: ReadableMemtable,
+        imm: Arc<MemListVersion>,
+        seqno_to_time: Arc<SeqnoToTimeMapping>,
+    ) -> Self {
+        Self {
+            cf,
+            mem,
+            imm,
+            seqno_to_time,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn seqno_to_time(&self) -> &Arc<SeqnoToTimeMapping> {
+        &self.seqno_to_time
+    }
+
+    /// Seq no of the newest write to the user key in the memtables. None if the key has not been written since the oldest of them.
+    pub(crate) fn newest_sequence_in_memtables(&self, user_key: &[u8]) -> Option<u64> {
+        self.mem
+            .newest_sequence(user_key)
+            .or_else(|| self.imm.newest_sequence(user_key))
+    }
+
+    /// Seq no of the last write before the oldest memtable was created. Newer writes are all in the memtables, older ones may only be
+    /// in the table files.
+    pub(crate) fn earliest_sequence_in_memtables(&self) -> u64 {
+        self.imm.memtables().last().map_or_else(
+            || self.mem.earliest_sequence(),
+            |imm| imm.earliest_sequence(),
+        )
+    }
+
+    /// True if a memtable holds an entry of a user key in [smallest, largest].
+    pub(crate) fn memtables_overlap(
+        &self,
+        smallest: &[u8],
+        largest: &[u8],
+        user_comparator: &dyn Comparator,
+    ) -> bool {
+        let lookup = LookUpInternalKey::new(smallest, MAX_SEQUENCE_NUMBER, OperationType::Max);
+        let overlaps = |iter: &mut dyn InternalIterator| {
+            iter.seek(lookup.as_ref());
+            iter.valid()
+                && user_comparator.compare(InternalKeyRef::from(iter.key()).user_key, largest)
+                    != Ordering::Greater
+        };
+
+        overlaps(&mut self.mem.iter())
+            || self
+                .imm
+                .memtables()
+                .iter()
+                .any(|imm| overlaps(&mut imm.iter()))
+    }
+
+    /// Adds an iterator over each source to the builder, newest first. Table files resolve their blob references and skip the data
+    /// blocks outside `bounds`.
+    pub(crate) fn add_iterators<'a, S: IterAllocStrategy>(
+        &'a self,
+        builder: &mut DBIterBuilder<'a, S>,
+        bounds: Option<&Arc<IterBounds>>,
+    ) -> Result<()> {
+        builder.add_child(self.mem.iter());
+        for imm in self.imm.memtables() {
+            builder.add_child(imm.iter());
+        }
+
+        let version = self.version();
+        for (_, f) in version.files() {
+            let table = f
+                .table_reader()
+                .ok_or_else(|| Error::Corruption(format!("table file {} is not open", f.number)))?;
+            builder.add_child(BlobResolvingIterator::new(
+                table.iter(version.internal_comparator().clone(), bounds.cloned()),
+                Arc::clone(&version),
+            ));
+        }
+        Ok(())
+    }
+
+    // NOTE: The Version is read through the column family until it is part of the superversion
+    fn version(&self) -> Arc<Version> {
+        // SAFETY: The column family outlives every superversion it installs
+        unsafe { self.cf.as_ref() }.current_version()
+    }
+}
+
+impl GetSource for Superversion {
+    fn get(&self, lookup_key: &[u8], ctx: &mut GetContext<'_>) -> bool {
+        self.mem.get(lookup_key, ctx)
+            || self.imm.get(lookup_key, ctx)
+            || self.version().get(lookup_key, ctx)
+    }
+}
+
+// One superversion serves a whole multi_get batch so every key is read from the same memtables
+impl MultiGetSource for Superversion {
+    fn multi_get(&self, batch: &mut MultiGetBatch<'_>) {
+        self.mem.multi_get(batch);
+        if !batch.is_done() {
+            self.imm.multi_get(batch);
+        }
+
+        let version = self.version();
+        let open_table = |f: &FileMetaData| -> Result<Box<dyn InternalIterator>> {
+            let table = f
+                .table_reader()
+                .ok_or_else(|| Error::Corruption(format!("table file {} is not open", f.number)))?;
+            Ok(Box::new(BlobResolvingIterator::new(
+                table.iter(version.internal_comparator().clone(), None),
+                Arc::clone(&version),
+            )))
+        };
+        for level in 0..NUM_LEVELS {
+            if batch.is_done() {
+                return;
+            }
+            batch.walk_files(version.level_files(level), &open_table);
+        }
+    }
 }
 
 // SuperVersion Cache to be stored in Thread Local Storage which is effectively static for the lifetime of the programme
@@ -0,0 +1,384 @@
+//
+//
+//
+// VersionEdit
+//
+// A VersionEdit is one record of the MANIFEST: a delta applied to the state recovered from every earlier record. Each edit belongs to
+// one column family (0 is the default column family) and only carries the fields which changed:
+//
+// | tag (1 byte) | field ... | tag | field ... |
+//
+// Numbers are fixed width little endian, names and keys are a VarInt length followed by the bytes. Tags are never reused - an unknown tag means
+// the MANIFEST was written by a newer version and can't be read safely.
+
+use crate::error::{Error, Result};
+use crate::range::fragment::RangeTombstone;
+use crate::utils::var_int::VarInt;
+use crate::versioning::file_version::FileMetaData;
+
+const TAG_COLUMN_FAMILY: u8 = 1;
+const TAG_COLUMN_FAMILY_ADD: u8 = 2;
+const TAG_COLUMN_FAMILY_DROP: u8 = 3;
+const TAG_LOG_NUMBER: u8 = 4;
+const TAG_NEXT_FILE_NUMBER: u8 = 5;
+const TAG_LAST_SEQUENCE: u8 = 6;
+const TAG_MAX_COLUMN_FAMILY: u8 = 7;
+const TAG_NEW_FILE: u8 = 8;
+const TAG_NEW_BLOB_FILE: u8 = 9;
+const TAG_BLOB_GARBAGE: u8 = 10;
+const TAG_DELETED_FILE: u8 = 11;
+const TAG_FULL_HISTORY_TS_LOW: u8 = 12;
+const TAG_COMPARATOR: u8 = 13;
+const TAG_RANGE_TOMBSTONE: u8 = 14;
+
+/// A table file added to a level of the column family.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct NewFile {
+    pub(crate) level: u8,
+    pub(crate) number: u64,
+    pub(crate) file_size: u64,
+    // Smallest and largest internal keys in the file
+    pub(crate) smallest: Vec<u8>,
+    pub(crate) largest: Vec<u8>,
+    pub(crate) smallest_seqno: u64,
+    pub(crate) largest_seqno: u64,
+}
+
+impl NewFile {
+    pub(crate) fn from_meta(level: usize, file: &FileMetaData) -> Self {
+        Self {
+            level: level as u8,
+            number: file.number,
+            file_size: file.file_size,
+            smallest: file.smallest.clone(),
+            largest: file.largest.clone(),
+            smallest_seqno: file.smallest_seqno,
+            largest_seqno: file.largest_seqno,
+        }
+    }
+
+    fn encode_to(&self, buf: &mut Vec<u8>) {
+        buf.push(self.level);
+        for value in [self.number, self.file_size] {
+            buf.extend_from_slice(&value.to_le_bytes());
+        }
+        for key in [&self.smallest, &self.largest] {
+            buf.extend_from_slice(VarInt::new(key.len() as u32).as_slice());
+            buf.extend_from_slice(key);
+        }
+        for value in [self.smallest_seqno, self.largest_seqno] {
+            buf.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+
+    fn decode_from(src: &mut &[u8]) -> Result<Self> {
+        Ok(Self {
+            level: take(src, 1)?[0],
+            number: take_u64(src)?,
+            file_size: take_u64(src)?,
+            smallest: take_bytes(src)?.to_vec(),
+            largest: take_bytes(src)?.to_vec(),
+            smallest_seqno: take_u64(src)?,
+            largest_seqno: take_u64(src)?,
+        })
+    }
+}
+
+/// A blob file added to the column family (NewBlobFile), or garbage found in one (BlobGarbage) - blobs no table references anymore.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct BlobFileCount {
+    pub(crate) number: u64,
+    pub(crate) blob_count: u64,
+    pub(crate) blob_bytes: u64,
+}
+
+impl BlobFileCount {
+    fn encode_to(&self, buf: &mut Vec<u8>) {
+        for value in [self.number, self.blob_count, self.blob_bytes] {
+            buf.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+
+    fn decode_from(src: &mut &[u8]) -> Result<Self> {
+        Ok(Self {
+            number: take_u64(src)?,
+            blob_count: take_u64(src)?,
+            blob_bytes: take_u64(src)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct VersionEdit {
+    pub(crate) column_family: u32,
+    // Name of a column family created by this edit
+    pub(crate) column_family_add: Option<String>,
+    pub(crate) column_family_drop: bool,
+    // WAL files older than this hold nothing the column family still needs
+    pub(crate) log_number: Option<u64>,
+    pub(crate) next_file_number: Option<u64>,
+    pub(crate) last_sequence: Option<u64>,
+    // Largest column family id handed out so far - ids of dropped column families are never reused
+    pub(crate) max_column_family: Option<u32>,
+    pub(crate) new_files: Vec<NewFile>,
+    // (level, number) of table files removed from the column family
+    pub(crate) deleted_files: Vec<(u8, u64)>,
+    pub(crate) new_blob_files: Vec<BlobFileCount>,
+    pub(crate) blob_garbage: Vec<BlobFileCount>,
+    // Versions of a key with an older timestamp may be trimmed (see key/timestamp.rs)
+    pub(crate) full_history_ts_low: Option<Vec<u8>>,
+    // Name of the comparator the column family's keys are ordered by
+    pub(crate) comparator: Option<String>,
+    // Range deletions added to the column family's RangeDelIndex - logged before the WAL holding them is released
+    pub(crate) range_tombstones: Vec<RangeTombstone>,
+}
+
+impl VersionEdit {
+    pub(crate) fn add_column_family(id: u32, name: &str) -> Self {
+        Self {
+            column_family: id,
+            column_family_add: Some(name.to_string()),
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn drop_column_family(id: u32) -> Self {
+        Self {
+            column_family: id,
+            column_family_drop: true,
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(64);
+
+        if self.column_family != 0 {
+            buf.push(TAG_COLUMN_FAMILY);
+            buf.extend_from_slice(&self.column_family.to_le_bytes());
+        }
+        if let Some(name) = &self.column_family_add {
+            buf.push(TAG_COLUMN_FAMILY_ADD);
+            buf.extend_from_slice(VarInt::new(name.len() as u32).as_slice());
+            buf.extend_from_slice(name.as_bytes());
+        }
+        if self.column_family_drop {
+            buf.push(TAG_COLUMN_FAMILY_DROP);
+        }
+
+        let numbers = [
+            (TAG_LOG_NUMBER, self.log_number),
+            (TAG_NEXT_FILE_NUMBER, self.next_file_number),
+            (TAG_LAST_SEQUENCE, self.last_sequence),
+        ];
+        for (tag, value) in numbers {
+            if let Some(value) = value {
+                buf.push(tag);
+                buf.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+
+        if let Some(max) = self.max_column_family {
+            buf.push(TAG_MAX_COLUMN_FAMILY);
+            buf.extend_from_slice(&max.to_le_bytes());
+        }
+        for &(level, number) in &self.deleted_files {
+            buf.push(TAG_DELETED_FILE);
+            buf.push(level);
+            buf.extend_from_slice(&number.to_le_bytes());
+        }
+        for file in &self.new_files {
+            buf.push(TAG_NEW_FILE);
+            file.encode_to(&mut buf);
+        }
+        for (tag, counts) in [
+            (TAG_NEW_BLOB_FILE, &self.new_blob_files),
+            (TAG_BLOB_GARBAGE, &self.blob_garbage),
+        ] {
+            for count in counts {
+                buf.push(tag);
+                count.encode_to(&mut buf);
+            }
+        }
+        if let Some(ts) = &self.full_history_ts_low {
+            buf.push(TAG_FULL_HISTORY_TS_LOW);
+            buf.extend_from_slice(VarInt::new(ts.len() as u32).as_slice());
+            buf.extend_from_slice(ts);
+        }
+        if let Some(name) = &self.comparator {
+            buf.push(TAG_COMPARATOR);
+            buf.extend_from_slice(VarInt::new(name.len() as u32).as_slice());
+            buf.extend_from_slice(name.as_bytes());
+        }
+        for tombstone in &self.range_tombstones {
+            buf.push(TAG_RANGE_TOMBSTONE);
+            for key in [&tombstone.start, &tombstone.end] {
+                buf.extend_from_slice(VarInt::new(key.len() as u32).as_slice());
+                buf.extend_from_slice(key);
+            }
+            buf.extend_from_slice(&tombstone.seq.to_le_bytes());
+        }
+
+        buf
+    }
+
+    pub(crate) fn decode(mut src: &[u8]) -> Result<Self> {
+        let mut edit = VersionEdit::default();
+
+        while let Some((&tag, rest)) = src.split_first() {
+            src = rest;
+            match tag {
+                TAG_COLUMN_FAMILY => edit.column_family = take_u32(&mut src)?,
+                TAG_COLUMN_FAMILY_ADD => edit.column_family_add = Some(take_string(&mut src)?),
+                TAG_COLUMN_FAMILY_DROP => edit.column_family_drop = true,
+                TAG_LOG_NUMBER => edit.log_number = Some(take_u64(&mut src)?),
+                TAG_NEXT_FILE_NUMBER => edit.next_file_number = Some(take_u64(&mut src)?),
+                TAG_LAST_SEQUENCE => edit.last_sequence = Some(take_u64(&mut src)?),
+                TAG_MAX_COLUMN_FAMILY => edit.max_column_family = Some(take_u32(&mut src)?),
+                TAG_NEW_FILE => edit.new_files.push(NewFile::decode_from(&mut src)?),
+                TAG_NEW_BLOB_FILE => edit
+                    .new_blob_files
+                    .push(BlobFileCount::decode_from(&mut src)?),
+                TAG_BLOB_GARBAGE => edit
+                    .blob_garbage
+                    .push(BlobFileCount::decode_from(&mut src)?),
+                TAG_DELETED_FILE => {
+                    let level = take(&mut src, 1)?[0];
+                    edit.deleted_files.push((level, take_u64(&mut src)?));
+                }
+                TAG_FULL_HISTORY_TS_LOW => {
+                    edit.full_history_ts_low = Some(take_bytes(&mut src)?.to_vec())
+                }
+                TAG_COMPARATOR => edit.comparator = Some(take_string(&mut src)?),
+                TAG_RANGE_TOMBSTONE => {
+                    let start = take_bytes(&mut src)?;
+                    let end = take_bytes(&mut src)?;
+                    edit.range_tombstones.push(RangeTombstone::new(
+                        start,
+                        end,
+                        take_u64(&mut src)?,
+                    ));
+                }
+                _ => {
+                    return Err(Error::Corruption(format!("unknown version edit tag {tag}")));
+                }
+            }
+        }
+
+        Ok(edit)
+    }
+}
+
+fn take<'a>(src: &mut &'a [u8], n: usize) -> Result<&'a [u8]> {
+    if src.len() < n {
+        return Err(Error::Corruption("truncated version edit".into()));
+    }
+    let (head, rest) = src.split_at(n);
+    *src = rest;
+    Ok(head)
+}
+
+fn take_u32(src: &mut &[u8]) -> Result<u32> {
+    Ok(u32::from_le_bytes(take(src, 4)?.try_into().unwrap()))
+}
+
+fn take_u64(src: &mut &[u8]) -> Result<u64> {
+    Ok(u64::from_le_bytes(take(src, 8)?.try_into().unwrap()))
+}
+
+fn take_bytes<'a>(src: &mut &'a [u8]) -> Result<&'a [u8]> {
+    let (len, read) = VarInt::try_decode(src)
+        .ok_or_else(|| Error::Corruption("truncated version edit".into()))?;
+    *src = &src[read..];
+    take(src, len as usize)
+}
+
+fn take_string(src: &mut &[u8]) -> Result<String> {
+    let bytes = take_bytes(src)?;
+    String::from_utf8(bytes.to_vec())
+        .map_err(|_| Error::Corruption("column family name is not utf-8".into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edits_round_trip() {
+        let edits = [
+            VersionEdit::default(),
+            VersionEdit::add_column_family(3, "users"),
+            VersionEdit::drop_column_family(3),
+            VersionEdit {
+                log_number: Some(7),
+                next_file_number: Some(12),
+                last_sequence: Some(1 << 40),
+                max_column_family: Some(3),
+                ..Default::default()
+            },
+            VersionEdit {
+                column_family: 2,
+                new_files: vec![
+                    NewFile {
+                        level: 6,
+                        number: 14,
+                        file_size: 4096,
+                        smallest: b"a\0\0\0\0\0\0\0\x01".to_vec(),
+                        largest: b"zz\0\0\0\0\0\0\0\x01".to_vec(),
+                        smallest_seqno: 0,
+                        largest_seqno: 0,
+                    },
+                    NewFile {
+                        level: 0,
+                        number: 15,
+                        file_size: 1,
+                        smallest: Vec::new(),
+                        largest: Vec::new(),
+                        smallest_seqno: 9,
+                        largest_seqno: 9,
+                    },
+                ],
+                ..Default::default()
+            },
+            VersionEdit {
+                deleted_files: vec![(0, 15), (6, 14)],
+                new_blob_files: vec![BlobFileCount {
+                    number: 16,
+                    blob_count: 3,
+                    blob_bytes: 90_000,
+                }],
+                blob_garbage: vec![BlobFileCount {
+                    number: 11,
+                    blob_count: 1,
+                    blob_bytes: 30_000,
+                }],
+                ..Default::default()
+            },
+            VersionEdit {
+                column_family: 1,
+                full_history_ts_low: Some(vec![0, 0, 0, 0, 0, 0, 0, 42]),
+                comparator: Some("BytewiseComparator".into()),
+                ..Default::default()
+            },
+            VersionEdit {
+                column_family: 4,
+                range_tombstones: vec![
+                    RangeTombstone::new(b"a", b"k", 12),
+                    RangeTombstone::new(b"", b"\xff\xff", 1 << 40),
+                ],
+                ..Default::default()
+            },
+        ];
+
+        for edit in edits {
+            assert_eq!(VersionEdit::decode(&edit.encode()).unwrap(), edit);
+        }
+    }
+
+    #[test]
+    fn truncated_and_unknown_fields_are_corruption() {
+        let encoded = VersionEdit::add_column_family(3, "users").encode();
+        assert!(VersionEdit::decode(&encoded[..encoded.len() - 1]).is_err());
+        assert!(VersionEdit::decode(&[200]).is_err());
+    }
+}
@@ -0,0 +1,494 @@
+//
+//
+//
+// VersionSet
+//
+// The VersionSet owns the MANIFEST and the state it describes: the column families, the next file number and the last sequence number.
+// Every change is written to the MANIFEST as a VersionEdit and synced before it is applied in memory, so the state recovered on open is
+// exactly the state last acknowledged.
+//
+// On open the MANIFEST named by CURRENT is replayed and a new MANIFEST holding only the recovered state is written, so the log doesn't
+// grow across restarts. CURRENT is switched to the new MANIFEST once it is synced, then the old one is deleted.
+//
+// Each column family's table files are rebuilt from the new and deleted file records of its edits, its blob files from the new blob file
+// and blob garbage records. Every file is opened before the DB is.
+//
+// A VersionSet without a directory (in_memory) applies edits without logging them.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::blob::blob_file::BlobFile;
+use crate::column_family::cf::{
+    ColumnFamilyData, ColumnFamilyDescriptor, ColumnFamilySet, DEFAULT_COLUMN_FAMILY_ID,
+    DEFAULT_COLUMN_FAMILY_NAME,
+};
+use crate::db::filename::{
+    blob_file, current_file, manifest_file, read_current_file, set_current_file, table_file,
+};
+use crate::db::log::{LogReader, LogWriter};
+use crate::error::{Error, Result};
+use crate::key::comparator::InternalKeyComparator;
+use crate::options::{ColumnFamilyOptions, DbOptions};
+use crate::range::fragment::RangeTombstone;
+use crate::table::table_reader::Table;
+use crate::utils::random_access_file::RandomAccessFile;
+use crate::versioning::file_version::{BlobFileMetaData, FileMetaData, NUM_LEVELS, Version};
+use crate::versioning::version_edit::{BlobFileCount, NewFile, VersionEdit};
+
+// State rebuilt by replaying a MANIFEST
+#[derive(Default)]
+struct RecoveredState {
+    // id -> name
+    column_families: BTreeMap<u32, String>,
+    log_numbers: HashMap<u32, u64>,
+    files: HashMap<u32, Vec<NewFile>>,
+    blob_files: HashMap<u32, BTreeMap<u64, BlobFileMetaData>>,
+    full_history_ts_low: HashMap<u32, Vec<u8>>,
+    // id -> comparator name, missing for a MANIFEST written before comparator names were persisted
+    comparators: HashMap<u32, String>,
+    range_tombstones: HashMap<u32, Vec<RangeTombstone>>,
+    max_column_family: u32,
+    next_file_number: u64,
+    last_sequence: u64,
+    // The MANIFEST replayed, 0 for a new DB
+    manifest_number: u64,
+}
+
+impl RecoveredState {
+    fn apply(&mut self, edit: &VersionEdit) -> Result<()> {
+        let id = edit.column_family;
+
+        if let Some(name) = &edit.column_family_add {
+            if self.column_families.insert(id, name.clone()).is_some() {
+                return Err(Error::Corruption(format!(
+                    "MANIFEST adds column family {id} twice"
+                )));
+            }
+        } else if !self.column_families.contains_key(&id) {
+            return Err(Error::Corruption(format!(
+                "MANIFEST edit for unknown column family {id}"
+            )));
+        }
+
+        if edit.column_family_drop {
+            self.column_families.remove(&id);
+            self.log_numbers.remove(&id);
+            self.files.remove(&id);
+            self.blob_files.remove(&id);
+            self.full_history_ts_low.remove(&id);
+            self.comparators.remove(&id);
+            self.range_tombstones.remove(&id);
+        } else {
+            if let Some(number) = edit.log_number {
+                self.log_numbers.insert(id, number);
+            }
+            if let Some(ts) = &edit.full_history_ts_low {
+                self.full_history_ts_low.insert(id, ts.clone());
+            }
+            if let Some(comparator) = &edit.comparator {
+                self.comparators.insert(id, comparator.clone());
+            }
+            self.range_tombstones
+                .entry(id)
+                .or_default()
+                .extend(edit.range_tombstones.iter().cloned());
+            let files = self.files.entry(id).or_default();
+            files.retain(|f| !edit.deleted_files.contains(&(f.level, f.number)));
+            files.extend(edit.new_files.iter().cloned());
+
+            let blob_files = self.blob_files.entry(id).or_default();
+            for added in &edit.new_blob_files {
+                blob_files.insert(
+                    added.number,
+                    BlobFileMetaData::new(added.number, added.blob_count, added.blob_bytes),
+                );
+            }
+            for garbage in &edit.blob_garbage {
+                let meta = blob_files.get(&garbage.number).ok_or_else(|| {
+                    Error::Corruption(format!(
+                        "MANIFEST has garbage for unknown blob file {}",
+                        garbage.number
+                    ))
+                })?;
+                let meta = meta.with_garbage(garbage.blob_count, garbage.blob_bytes);
+                if meta.is_all_garbage() {
+                    blob_files.remove(&garbage.number);
+                } else {
+                    blob_files.insert(garbage.number, meta);
+                }
+            }
+        }
+
+        if let Some(number) = edit.next_file_number {
+            self.next_file_number = self.next_file_number.max(number);
+        }
+        if let Some(sequence) = edit.last_sequence {
+            self.last_sequence = self.last_sequence.max(sequence);
+        }
+        self.max_column_family = self
+            .max_column_family
+            .max(edit.max_column_family.unwrap_or(0))
+            .max(id);
+        Ok(())
+    }
+}
+
+fn read_manifest(db_path: &Path) -> Result<RecoveredState> {
+    let number = read_current_file(db_path)?;
+    let mut reader = LogReader::open(&manifest_file(db_path, number))?;
+
+    let mut state = RecoveredState {
+        manifest_number: number,
+        ..Default::default()
+    };
+    while let Some(record) = reader.read_record()? {
+        state.apply(&VersionEdit::decode(record)?)?;
+    }
+    // Tolerated: the tail is an edit which was never acknowledged
+
+    if !state
+        .column_families
+        .contains_key(&DEFAULT_COLUMN_FAMILY_ID)
+    {
+        return Err(Error::Corruption(
+            "MANIFEST has no default column family".into(),
+        ));
+    }
+    Ok(state)
+}
+
+// Opens the table and blob files of a column family and builds its Version
+fn load_version(
+    db_path: &Path,
+    files: &[NewFile],
+    blob_files: BTreeMap<u64, BlobFileMetaData>,
+    icmp: Arc<InternalKeyComparator>,
+) -> Result<Version> {
+    let mut levels = vec![Vec::new(); NUM_LEVELS];
+    for file in files {
+        if file.level as usize >= NUM_LEVELS {
+            return Err(Error::Corruption(format!(
+                "table file {} is in level {}",
+                file.number, file.level
+            )));
+        }
+        let reader = RandomAccessFile::open(&table_file(db_path, file.number)).map_err(|e| {
+            Error::Corruption(format!("table file {} can't be opened: {e}", file.number))
+        })?;
+        let table = Table::open(reader)?;
+        let meta = FileMetaData::new(
+            file.number,
+            file.file_size,
+            file.smallest.clone(),
+            file.largest.clone(),
+            file.smallest_seqno,
+            file.largest_seqno,
+            table.properties().clone(),
+        );
+        meta.set_table_reader(table);
+        levels[file.level as usize].push(Arc::new(meta));
+    }
+
+    let blob_files = blob_files
+        .into_values()
+        .map(|meta| {
+            let file = RandomAccessFile::open(&blob_file(db_path, meta.number)).map_err(|e| {
+                Error::Corruption(format!("blob file {} can't be opened: {e}", meta.number))
+            })?;
+            meta.set_blob_reader(BlobFile::open(meta.number, file)?);
+            Ok(Arc::new(meta))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(Version::from_levels(levels, icmp).with_blob_files(blob_files))
+}
+
+pub(crate) struct VersionSet {
+    db_path: Option<PathBuf>,
+    manifest: Option<LogWriter>,
+    manifest_number: u64,
+    next_file_number: u64,
+    last_sequence: u64,
+    column_families: ColumnFamilySet,
+}
+
+impl VersionSet {
+    /// A VersionSet which is never persisted, holding only the default column family.
+    pub(crate) fn in_memory(default_options: ColumnFamilyOptions) -> Self {
+        let mut column_families = ColumnFamilySet::new();
+        column_families.create(
+            DEFAULT_COLUMN_FAMILY_ID,
+            DEFAULT_COLUMN_FAMILY_NAME,
+            default_options,
+        );
+        Self {
+            db_path: None,
+            manifest: None,
+            manifest_number: 0,
+            next_file_number: 1,
+            last_sequence: 0,
+            column_families,
+        }
+    }
+
+    /// Recovers the DB in `db_path` (or creates it). Every existing column family must be in `descriptors` - the default column family
+    /// falls back to default options when it isn't.
+    pub(crate) fn open(
+        db_path: &Path,
+        options: &DbOptions,
+        mut descriptors: Vec<ColumnFamilyDescriptor>,
+    ) -> Result<Self> {
+        let mut state = if current_file(db_path).exists() {
+            read_manifest(db_path)?
+        } else if options.create_if_missing {
+            fs::create_dir_all(db_path)?;
+            let mut state = RecoveredState {
+                next_file_number: 1,
+                ..Default::default()
+            };
+            state.column_families.insert(
+                DEFAULT_COLUMN_FAMILY_ID,
+                DEFAULT_COLUMN_FAMILY_NAME.to_string(),
+            );
+            state
+        } else {
+            return Err(Error::InvalidArgument(format!(
+                "{} does not exist (create_if_missing is false)",
+                db_path.display()
+            )));
+        };
+
+        let mut column_families = ColumnFamilySet::new();
+        for (&id, name) in &state.column_families {
+            let options = match descriptors.iter().position(|d| &d.name == name) {
+                Some(i) => descriptors.swap_remove(i).options,
+                None if id == DEFAULT_COLUMN_FAMILY_ID => ColumnFamilyOptions::default(),
+                None => {
+                    return Err(Error::InvalidArgument(format!(
+                        "column family {name} exists but was not opened"
+                    )));
+                }
+            };
+            // The keys of the column family are ordered by the comparator it was created with
+            if let Some(comparator) = state.comparators.remove(&id)
+                && comparator != options.comparator.name()
+            {
+                return Err(Error::InvalidArgument(format!(
+                    "column family {name} was created with comparator {comparator}, not {}",
+                    options.comparator.name()
+                )));
+            }
+            let cfd = column_families.create(id, name, options);
+            cfd.set_log_number(state.log_numbers.get(&id).copied().unwrap_or(0));
+            let files = state.files.remove(&id).unwrap_or_default();
+            let blob_files = state.blob_files.remove(&id).unwrap_or_default();
+            if let Some(ts) = state.full_history_ts_low.remove(&id) {
+                cfd.increase_full_history_ts_low(&ts)?;
+            }
+            for tombstone in state.range_tombstones.remove(&id).unwrap_or_default() {
+                cfd.range_del()
+                    .insert(&tombstone.start, &tombstone.end, tombstone.seq)?;
+            }
+            cfd.install_version(Arc::new(load_version(
+                db_path,
+                &files,
+                blob_files,
+                Arc::clone(cfd.internal_comparator()),
+            )?));
+        }
+        column_families.set_max_column_family(state.max_column_family);
+
+        if !descriptors.is_empty() && !options.create_missing_column_families {
+            return Err(Error::InvalidArgument(format!(
+                "column family {} does not exist (create_missing_column_families is false)",
+                descriptors[0].name
+            )));
+        }
+
+        let mut versions = Self {
+            db_path: Some(db_path.to_path_buf()),
+            manifest: None,
+            manifest_number: state.manifest_number,
+            next_file_number: state.next_file_number.max(1),
+            last_sequence: state.last_sequence,
+            column_families,
+        };
+        versions.write_snapshot()?;
+
+        for descriptor in descriptors {
+            versions.create_column_family(&descriptor.name, descriptor.options)?;
+        }
+        Ok(versions)
+    }
+
+    /// Names of the column families of the DB in `db_path`, by id (the default column family first).
+    pub(crate) fn list_column_families(db_path: &Path) -> Result<Vec<String>> {
+        Ok(read_manifest(db_path)?
+            .column_families
+            .into_values()
+            .collect())
+    }
+
+    // Starts a new MANIFEST holding the current state and makes it current
+    fn write_snapshot(&mut self) -> Result<()> {
+        let Some(db_path) = self.db_path.clone() else {
+            return Ok(());
+        };
+
+        let old_number = (self.manifest_number != 0).then_some(self.manifest_number);
+        let number = self.new_file_number();
+        let manifest = self.write_manifest(&db_path, number)?;
+        self.manifest = Some(manifest);
+        self.manifest_number = number;
+
+        if let Some(old) = old_number {
+            fs::remove_file(manifest_file(&db_path, old))?;
+        }
+        Ok(())
+    }
+
+    /// Writes a MANIFEST with the current state as MANIFEST-`number` of `dir` and points CURRENT of `dir` at it. Also used to give a copy
+    /// of the DB (a checkpoint) its own MANIFEST.
+    pub(crate) fn write_manifest(&self, dir: &Path, number: u64) -> Result<LogWriter> {
+        let mut manifest = LogWriter::create(&manifest_file(dir, number))?;
+
+        for cfd in self.column_families.iter() {
+            let mut edit = VersionEdit::add_column_family(cfd.id(), cfd.name());
+            edit.log_number = Some(cfd.log_number());
+            edit.full_history_ts_low = cfd.full_history_ts_low();
+            edit.comparator = Some(cfd.user_comparator().name().to_string());
+            edit.range_tombstones = cfd.range_del().version().tombstones().collect();
+            let version = cfd.current_version();
+            edit.new_files = version
+                .files()
+                .map(|(level, file)| NewFile::from_meta(level, file))
+                .collect();
+            for blob_file in version.blob_files().values() {
+                edit.new_blob_files.push(BlobFileCount {
+                    number: blob_file.number,
+                    blob_count: blob_file.total_blob_count,
+                    blob_bytes: blob_file.total_blob_bytes,
+                });
+                if blob_file.garbage_blob_count > 0 {
+                    edit.blob_garbage.push(BlobFileCount {
+                        number: blob_file.number,
+                        blob_count: blob_file.garbage_blob_count,
+                        blob_bytes: blob_file.garbage_blob_bytes,
+                    });
+                }
+            }
+            manifest.add_record(&edit.encode())?;
+        }
+        let edit = VersionEdit {
+            next_file_number: Some(self.next_file_number),
+            last_sequence: Some(self.last_sequence),
+            max_column_family: Some(self.column_families.max_column_family()),
+            ..Default::default()
+        };
+        manifest.add_record(&edit.encode())?;
+        manifest.sync()?;
+
+        set_current_file(dir, number)?;
+        Ok(manifest)
+    }
+
+    /// Writes the edit to the MANIFEST (with the current file and seq no counters) and syncs it.
+    pub(crate) fn log_and_apply(&mut self, mut edit: VersionEdit) -> Result<()> {
+        edit.next_file_number = Some(self.next_file_number);
+        edit.last_sequence = Some(self.last_sequence);
+
+        if let Some(manifest) = self.manifest.as_mut() {
+            manifest.add_record(&edit.encode())?;
+            manifest.sync()?;
+        }
+
+        if let Some(max) = edit.max_column_family {
+            self.column_families.set_max_column_family(max);
+        }
+        if !edit.column_family_drop
+            && let Some(number) = edit.log_number
+            && let Some(cfd) = self.column_families.get(edit.column_family)
+        {
+            cfd.set_log_number(number);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn create_column_family(
+        &mut self,
+        name: &str,
+        options: ColumnFamilyOptions,
+    ) -> Result<Arc<ColumnFamilyData>> {
+        if name.is_empty() {
+            return Err(Error::InvalidArgument("column family name is empty".into()));
+        }
+        if self.column_families.get_by_name(name).is_some() {
+            return Err(Error::InvalidArgument(format!(
+                "column family {name} already exists"
+            )));
+        }
+
+        let id = self.column_families.max_column_family() + 1;
+        let mut edit = VersionEdit::add_column_family(id, name);
+        edit.max_column_family = Some(id);
+        edit.comparator = Some(options.comparator.name().to_string());
+        self.log_and_apply(edit)?;
+
+        Ok(self.column_families.create(id, name, options))
+    }
+
+    pub(crate) fn drop_column_family(&mut self, id: u32) -> Result<()> {
+        if id == DEFAULT_COLUMN_FAMILY_ID {
+            return Err(Error::InvalidArgument(
+                "the default column family can't be dropped".into(),
+            ));
+        }
+        if self.column_families.get(id).is_none() {
+            return Err(Error::InvalidArgument(format!(
+                "column family {id} does not exist or was already dropped"
+            )));
+        }
+
+        self.log_and_apply(VersionEdit::drop_column_family(id))?;
+        self.column_families.drop_column_family(id);
+        Ok(())
+    }
+
+    #[inline]
+    pub(crate) fn column_families(&self) -> &ColumnFamilySet {
+        &self.column_families
+    }
+
+    #[inline]
+    pub(crate) fn column_families_mut(&mut self) -> &mut ColumnFamilySet {
+        &mut self.column_families
+    }
+
+    #[inline]
+    pub(crate) fn db_path(&self) -> Option<&Path> {
+        self.db_path.as_deref()
+    }
+
+    #[inline]
+    pub(crate) fn manifest_number(&self) -> u64 {
+        self.manifest_number
+    }
+
+    pub(crate) fn new_file_number(&mut self) -> u64 {
+        let number = self.next_file_number;
+        self.next_file_number += 1;
+        number
+    }
+
+    #[inline]
+    pub(crate) fn last_sequence(&self) -> u64 {
+        self.last_sequence
+    }
+
+    /// Recorded with the next edit.
+    pub(crate) fn set_last_sequence(&mut self, sequence: u64) {
+        self.last_sequence = self.last_sequence.max(sequence);
+    }
+}